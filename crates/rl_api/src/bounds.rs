@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::num::NonZeroU32;
 
@@ -6,7 +7,7 @@ pub const MAX_WINDOW_SIZE: u32 = 10000;
 pub const MAX_DIFF_BYTES: u64 = 10 * 1024 * 1024; // 10MB
 pub const MAX_DIFF_HUNKS: u32 = 10000;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PageSize(NonZeroU32);
 
 impl PageSize {
@@ -29,7 +30,7 @@ impl TryFrom<u32> for PageSize {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WindowSize(NonZeroU32);
 
 impl WindowSize {
@@ -52,7 +53,7 @@ impl TryFrom<u32> for WindowSize {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MaxBytes(u64);
 
 impl MaxBytes {
@@ -75,7 +76,7 @@ impl TryFrom<u64> for MaxBytes {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MaxHunks(u32);
 
 impl MaxHunks {
@@ -115,7 +116,7 @@ impl std::fmt::Display for BoundsError {
 
 impl std::error::Error for BoundsError {}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Cursor(String);
 
 impl Cursor {
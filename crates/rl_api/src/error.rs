@@ -1,14 +1,18 @@
 //! Typed error model for the repo-lens API.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// Typed error codes with categories.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ErrorCode {
     // Request validation errors
     InvalidRequest,
+    UnsupportedVersion,
+    UnsupportedEncoding,
+    InvalidCursor,
 
     // Repository errors
     RepoNotFound,
@@ -28,6 +32,16 @@ pub enum ErrorCode {
     // Timeouts
     Timeout,
 
+    // Load shedding
+    Overloaded,
+
+    // Transport errors
+    ConnectionLost,
+
+    // Per-client limits
+    RateLimited,
+    QuotaExceeded,
+
     // Internal errors
     Internal,
 }
@@ -36,19 +50,26 @@ impl fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidRequest => write!(f, "invalid_request"),
+            Self::UnsupportedVersion => write!(f, "unsupported_version"),
+            Self::UnsupportedEncoding => write!(f, "unsupported_encoding"),
+            Self::InvalidCursor => write!(f, "invalid_cursor"),
             Self::RepoNotFound => write!(f, "repo_not_found"),
             Self::GitBackendError => write!(f, "git_backend_error"),
             Self::Conflict => write!(f, "conflict"),
             Self::AuthRequired => write!(f, "auth_required"),
             Self::OperationCanceled => write!(f, "operation_canceled"),
             Self::Timeout => write!(f, "timeout"),
+            Self::Overloaded => write!(f, "overloaded"),
+            Self::ConnectionLost => write!(f, "connection_lost"),
+            Self::RateLimited => write!(f, "rate_limited"),
+            Self::QuotaExceeded => write!(f, "quota_exceeded"),
             Self::Internal => write!(f, "internal"),
         }
     }
 }
 
 /// Structured error response.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Error {
     /// Error code
     pub code: ErrorCode,
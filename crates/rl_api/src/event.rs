@@ -1,9 +1,10 @@
 //! Event DTOs for the repo-lens API.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Event types for reactive UI updates.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Event {
     /// HEAD reference changed
@@ -22,8 +23,23 @@ pub enum Event {
     OperationProgress(OperationProgressEvent),
 }
 
+impl Event {
+    /// Repository the event concerns.
+    pub fn repo_path(&self) -> &str {
+        match self {
+            Self::HeadChanged(event) => &event.repo_path,
+            Self::IndexChanged(event) => &event.repo_path,
+            Self::WorkdirChanged(event) => &event.repo_path,
+            Self::RefsChanged(event) => &event.repo_path,
+            Self::RepoOpened(event) => &event.repo_path,
+            Self::RepoClosed(event) => &event.repo_path,
+            Self::OperationProgress(event) => &event.repo_path,
+        }
+    }
+}
+
 /// HEAD changed event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct HeadChangedEvent {
     /// Repository path
     pub repo_path: String,
@@ -34,7 +50,7 @@ pub struct HeadChangedEvent {
 }
 
 /// Index changed event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IndexChangedEvent {
     /// Repository path
     pub repo_path: String,
@@ -43,7 +59,7 @@ pub struct IndexChangedEvent {
 }
 
 /// Working directory changed event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WorkdirChangedEvent {
     /// Repository path
     pub repo_path: String,
@@ -52,7 +68,7 @@ pub struct WorkdirChangedEvent {
 }
 
 /// References changed event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RefsChangedEvent {
     /// Repository path
     pub repo_path: String,
@@ -61,21 +77,21 @@ pub struct RefsChangedEvent {
 }
 
 /// Repository opened event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RepoOpenedEvent {
     /// Repository path
     pub repo_path: String,
 }
 
 /// Repository closed event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RepoClosedEvent {
     /// Repository path
     pub repo_path: String,
 }
 
 /// Operation progress event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OperationProgressEvent {
     /// Repository path
     pub repo_path: String,
@@ -9,6 +9,7 @@ pub mod event;
 pub mod paging;
 pub mod request;
 pub mod response;
+pub mod schema;
 pub mod version;
 
 // Re-export main types for convenience
@@ -16,7 +17,7 @@ pub use bounds::{Cursor, MaxBytes, MaxHunks, PageSize, WindowSize};
 pub use error::{Error, ErrorCode};
 pub use event::Event;
 pub use paging::{Paging, StreamingChunk};
-pub use request::Request;
+pub use request::{Request, RequestPriority};
 pub use response::Response;
 pub use version::ApiVersion;
 
@@ -27,21 +28,19 @@ mod tests {
     #[test]
     fn test_deterministic_serialization() {
         // Test that serialization is deterministic (same input -> same output)
-        let request1 = Request {
-            version: ApiVersion::V0,
-            id: "test-123".to_string(),
-            payload: request::RequestPayload::Status(request::StatusRequest {
+        let request1 = Request::new(
+            "test-123",
+            request::RequestPayload::Status(request::StatusRequest {
                 repo_path: "/path/to/repo".to_string(),
             }),
-        };
+        );
 
-        let request2 = Request {
-            version: ApiVersion::V0,
-            id: "test-123".to_string(),
-            payload: request::RequestPayload::Status(request::StatusRequest {
+        let request2 = Request::new(
+            "test-123",
+            request::RequestPayload::Status(request::StatusRequest {
                 repo_path: "/path/to/repo".to_string(),
             }),
-        };
+        );
 
         let json1 = serde_json::to_string(&request1).unwrap();
         let json2 = serde_json::to_string(&request2).unwrap();
@@ -94,4 +93,17 @@ mod tests {
         let cursor = Cursor::from("test".to_string());
         assert_eq!(cursor.get(), "test");
     }
+
+    #[test]
+    fn test_set_repo_path() {
+        let mut payload = request::RequestPayload::Status(request::StatusRequest {
+            repo_path: "/src/one".to_string(),
+        });
+        payload.set_repo_path("/src/two".to_string());
+        assert_eq!(payload.repo_path(), "/src/two");
+
+        let mut payload = request::RequestPayload::Capabilities(request::CapabilitiesRequest {});
+        payload.set_repo_path("/src/two".to_string());
+        assert_eq!(payload.repo_path(), "");
+    }
 }
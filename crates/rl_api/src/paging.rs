@@ -1,10 +1,11 @@
 //! Pagination and streaming support structures.
 
 use crate::bounds::{Cursor, PageSize};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Pagination parameters for list endpoints.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Paging {
     /// Maximum number of items to return
     pub page_size: PageSize,
@@ -13,7 +14,7 @@ pub struct Paging {
 }
 
 /// A chunk in a streaming response.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StreamingChunk<T> {
     /// Sequence number for ordering chunks
     pub sequence: u64,
@@ -2,10 +2,11 @@
 
 use crate::bounds::{Cursor, MaxBytes, MaxHunks, WindowSize};
 use crate::paging::Paging;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Top-level request envelope.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Request {
     /// API version
     pub version: crate::ApiVersion,
@@ -13,10 +14,55 @@ pub struct Request {
     pub id: String,
     /// The actual request payload
     pub payload: RequestPayload,
+    /// Scheduling hint; requests without one are treated as `ui_immediate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<RequestPriority>,
+    /// Report how long each step of handling took in the final response's
+    /// `meta`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timings: bool,
+    /// Report the id the engine logs the request under in the final
+    /// response's `meta`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trace: bool,
+    /// Key identifying a mutation across retries; a mutation repeating the
+    /// key of a recent one is answered with its result instead of running
+    /// again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+impl Request {
+    /// A request for `payload` under `id`, in the default API version,
+    /// with no scheduling hint and nothing extra asked for.
+    pub fn new(id: impl Into<String>, payload: RequestPayload) -> Self {
+        Self {
+            version: crate::ApiVersion::default(),
+            id: id.into(),
+            payload,
+            priority: None,
+            timings: false,
+            trace: false,
+            idempotency_key: None,
+        }
+    }
+}
+
+/// How urgently the client needs a response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPriority {
+    /// A user is waiting on the result
+    #[default]
+    UiImmediate,
+    /// Speculative work the UI may need soon
+    Prefetch,
+    /// Background work with no one waiting
+    Maintenance,
 }
 
 /// Request payload variants.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RequestPayload {
     /// Get repository status
@@ -51,23 +97,208 @@ pub enum RequestPayload {
     Merge(MergeRequest),
     /// Rebase operation
     Rebase(RebaseRequest),
+    /// Reset operation
+    Reset(ResetRequest),
+    /// Remove untracked files
+    Clean(CleanRequest),
     /// Stash operation
     Stash(StashRequest),
+    /// Reverse the most recent operation the engine performed
+    Undo(UndoRequest),
+    /// Get the journal of operations the engine performed
+    Journal(JournalRequest),
     /// Watch for events
     Watch(WatchRequest),
+    /// Open a repository and keep it managed by the engine
+    OpenRepo(OpenRepoRequest),
+    /// Release a repository managed by the engine
+    CloseRepo(CloseRepoRequest),
+    /// List repositories managed by the engine
+    ListRepos(ListReposRequest),
+    /// Report the git backend and what it can do
+    Capabilities(CapabilitiesRequest),
+    /// Reload the server's configuration
+    ReloadConfig(ReloadConfigRequest),
+}
+
+impl RequestPayload {
+    /// Wire name of the request type (e.g. `"diff_content"`).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Status(_) => "status",
+            Self::Log(_) => "log",
+            Self::Graph(_) => "graph",
+            Self::ShowCommit(_) => "show_commit",
+            Self::DiffSummary(_) => "diff_summary",
+            Self::DiffContent(_) => "diff_content",
+            Self::Blame(_) => "blame",
+            Self::Branches(_) => "branches",
+            Self::Tags(_) => "tags",
+            Self::Remotes(_) => "remotes",
+            Self::Checkout(_) => "checkout",
+            Self::Commit(_) => "commit",
+            Self::Fetch(_) => "fetch",
+            Self::Push(_) => "push",
+            Self::Merge(_) => "merge",
+            Self::Rebase(_) => "rebase",
+            Self::Reset(_) => "reset",
+            Self::Clean(_) => "clean",
+            Self::Stash(_) => "stash",
+            Self::Undo(_) => "undo",
+            Self::Journal(_) => "journal",
+            Self::Watch(_) => "watch",
+            Self::OpenRepo(_) => "open_repo",
+            Self::CloseRepo(_) => "close_repo",
+            Self::ListRepos(_) => "list_repos",
+            Self::Capabilities(_) => "capabilities",
+            Self::ReloadConfig(_) => "reload_config",
+        }
+    }
+
+    /// Whether the request only reads repository state, making it safe to
+    /// send again if the first attempt may or may not have run.
+    ///
+    /// Dry runs of mutations only read.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Self::Checkout(req) => req.dry_run,
+            Self::Push(req) => req.dry_run,
+            Self::Merge(req) => req.dry_run,
+            Self::Rebase(req) => req.dry_run,
+            Self::Reset(req) => req.dry_run,
+            Self::Clean(req) => req.dry_run,
+            Self::Stash(req) => matches!(req.action, StashAction::List | StashAction::Show),
+            Self::Status(_)
+            | Self::Log(_)
+            | Self::Graph(_)
+            | Self::ShowCommit(_)
+            | Self::DiffSummary(_)
+            | Self::DiffContent(_)
+            | Self::Blame(_)
+            | Self::Branches(_)
+            | Self::Tags(_)
+            | Self::Remotes(_)
+            | Self::Journal(_)
+            | Self::Watch(_)
+            | Self::OpenRepo(_)
+            | Self::CloseRepo(_)
+            | Self::ListRepos(_)
+            | Self::Capabilities(_)
+            | Self::ReloadConfig(_) => true,
+            Self::Commit(_) | Self::Fetch(_) | Self::Undo(_) => false,
+        }
+    }
+
+    /// Whether the request may be answered with several responses: chunks
+    /// or progress updates followed by a final response.
+    pub fn streams(&self) -> bool {
+        match self {
+            Self::DiffContent(_) | Self::Blame(_) | Self::Fetch(_) => true,
+            Self::Push(req) => !req.dry_run,
+            Self::Rebase(req) => !req.dry_run,
+            Self::Status(_)
+            | Self::Log(_)
+            | Self::Graph(_)
+            | Self::ShowCommit(_)
+            | Self::DiffSummary(_)
+            | Self::Branches(_)
+            | Self::Tags(_)
+            | Self::Remotes(_)
+            | Self::Checkout(_)
+            | Self::Commit(_)
+            | Self::Merge(_)
+            | Self::Reset(_)
+            | Self::Clean(_)
+            | Self::Stash(_)
+            | Self::Undo(_)
+            | Self::Journal(_)
+            | Self::Watch(_)
+            | Self::OpenRepo(_)
+            | Self::CloseRepo(_)
+            | Self::ListRepos(_)
+            | Self::Capabilities(_)
+            | Self::ReloadConfig(_) => false,
+        }
+    }
+
+    /// Repository the request targets.
+    ///
+    /// Empty for requests about the engine as a whole, such as `list_repos`.
+    pub fn repo_path(&self) -> &str {
+        match self {
+            Self::Status(req) => &req.repo_path,
+            Self::Log(req) => &req.repo_path,
+            Self::Graph(req) => &req.repo_path,
+            Self::ShowCommit(req) => &req.repo_path,
+            Self::DiffSummary(req) => &req.repo_path,
+            Self::DiffContent(req) => &req.repo_path,
+            Self::Blame(req) => &req.repo_path,
+            Self::Branches(req) => &req.repo_path,
+            Self::Tags(req) => &req.repo_path,
+            Self::Remotes(req) => &req.repo_path,
+            Self::Checkout(req) => &req.repo_path,
+            Self::Commit(req) => &req.repo_path,
+            Self::Fetch(req) => &req.repo_path,
+            Self::Push(req) => &req.repo_path,
+            Self::Merge(req) => &req.repo_path,
+            Self::Rebase(req) => &req.repo_path,
+            Self::Reset(req) => &req.repo_path,
+            Self::Clean(req) => &req.repo_path,
+            Self::Stash(req) => &req.repo_path,
+            Self::Undo(req) => &req.repo_path,
+            Self::Journal(req) => &req.repo_path,
+            Self::Watch(req) => &req.repo_path,
+            Self::OpenRepo(req) => &req.repo_path,
+            Self::CloseRepo(req) => &req.repo_path,
+            Self::ListRepos(_) | Self::Capabilities(_) | Self::ReloadConfig(_) => "",
+        }
+    }
+
+    /// Point the request at another repository, e.g. to run one command in
+    /// several. Requests about the engine as a whole are left unchanged.
+    pub fn set_repo_path(&mut self, repo_path: String) {
+        let target = match self {
+            Self::Status(req) => &mut req.repo_path,
+            Self::Log(req) => &mut req.repo_path,
+            Self::Graph(req) => &mut req.repo_path,
+            Self::ShowCommit(req) => &mut req.repo_path,
+            Self::DiffSummary(req) => &mut req.repo_path,
+            Self::DiffContent(req) => &mut req.repo_path,
+            Self::Blame(req) => &mut req.repo_path,
+            Self::Branches(req) => &mut req.repo_path,
+            Self::Tags(req) => &mut req.repo_path,
+            Self::Remotes(req) => &mut req.repo_path,
+            Self::Checkout(req) => &mut req.repo_path,
+            Self::Commit(req) => &mut req.repo_path,
+            Self::Fetch(req) => &mut req.repo_path,
+            Self::Push(req) => &mut req.repo_path,
+            Self::Merge(req) => &mut req.repo_path,
+            Self::Rebase(req) => &mut req.repo_path,
+            Self::Reset(req) => &mut req.repo_path,
+            Self::Clean(req) => &mut req.repo_path,
+            Self::Stash(req) => &mut req.repo_path,
+            Self::Undo(req) => &mut req.repo_path,
+            Self::Journal(req) => &mut req.repo_path,
+            Self::Watch(req) => &mut req.repo_path,
+            Self::OpenRepo(req) => &mut req.repo_path,
+            Self::CloseRepo(req) => &mut req.repo_path,
+            Self::ListRepos(_) | Self::Capabilities(_) | Self::ReloadConfig(_) => return,
+        };
+        *target = repo_path;
+    }
 }
 
 // Query requests
 
 /// Status request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StatusRequest {
     /// Repository path
     pub repo_path: String,
 }
 
 /// Log request with pagination.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LogRequest {
     /// Repository path
     pub repo_path: String,
@@ -79,7 +310,7 @@ pub struct LogRequest {
 }
 
 /// Graph request for commit graph window.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GraphRequest {
     /// Repository path
     pub repo_path: String,
@@ -92,7 +323,7 @@ pub struct GraphRequest {
 }
 
 /// Show commit request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ShowCommitRequest {
     /// Repository path
     pub repo_path: String,
@@ -101,7 +332,7 @@ pub struct ShowCommitRequest {
 }
 
 /// Diff summary request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffSummaryRequest {
     /// Repository path
     pub repo_path: String,
@@ -116,7 +347,7 @@ pub struct DiffSummaryRequest {
 }
 
 /// Diff content request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffContentRequest {
     /// Repository path
     pub repo_path: String,
@@ -131,7 +362,7 @@ pub struct DiffContentRequest {
 }
 
 /// Blame request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BlameRequest {
     /// Repository path
     pub repo_path: String,
@@ -139,24 +370,36 @@ pub struct BlameRequest {
     pub path: String,
     /// Optional revision
     pub revision: Option<String>,
+    /// Annotate only these lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<LineRange>,
+}
+
+/// Lines of a file, numbered from 1, including both ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LineRange {
+    /// First line
+    pub start: usize,
+    /// Last line
+    pub end: usize,
 }
 
 /// Branches request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BranchesRequest {
     /// Repository path
     pub repo_path: String,
 }
 
 /// Tags request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TagsRequest {
     /// Repository path
     pub repo_path: String,
 }
 
 /// Remotes request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RemotesRequest {
     /// Repository path
     pub repo_path: String,
@@ -165,7 +408,7 @@ pub struct RemotesRequest {
 // Mutation requests
 
 /// Checkout request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CheckoutRequest {
     /// Repository path
     pub repo_path: String,
@@ -173,10 +416,13 @@ pub struct CheckoutRequest {
     pub target: String,
     /// Create new branch
     pub create_branch: bool,
+    /// Report what would change instead of checking out
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 /// Commit request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitRequest {
     /// Repository path
     pub repo_path: String,
@@ -189,7 +435,7 @@ pub struct CommitRequest {
 }
 
 /// Fetch request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FetchRequest {
     /// Repository path
     pub repo_path: String,
@@ -200,7 +446,7 @@ pub struct FetchRequest {
 }
 
 /// Push request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PushRequest {
     /// Repository path
     pub repo_path: String,
@@ -210,10 +456,13 @@ pub struct PushRequest {
     pub refspecs: Option<Vec<String>>,
     /// Force push
     pub force: bool,
+    /// Report what would change instead of pushing
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 /// Merge request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MergeRequest {
     /// Repository path
     pub repo_path: String,
@@ -221,10 +470,13 @@ pub struct MergeRequest {
     pub source: String,
     /// Commit message
     pub message: Option<String>,
+    /// Report what would change instead of merging
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 /// Rebase request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RebaseRequest {
     /// Repository path
     pub repo_path: String,
@@ -232,20 +484,138 @@ pub struct RebaseRequest {
     pub onto: String,
     /// Upstream branch (optional)
     pub upstream: Option<String>,
+    /// Report what would change instead of rebasing
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Reset request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResetRequest {
+    /// Repository path
+    pub repo_path: String,
+    /// Commit to move the current branch to
+    pub target: String,
+    /// What else to reset besides the branch
+    #[serde(default)]
+    pub mode: ResetMode,
+    /// Report what would change instead of resetting
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What a reset resets besides the current branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetMode {
+    /// Only the branch; the index and working tree are kept
+    Soft,
+    /// The index too; the working tree is kept
+    #[default]
+    Mixed,
+    /// The index and working tree too, discarding uncommitted changes
+    Hard,
+}
+
+/// Clean request, removing untracked files from the working tree.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CleanRequest {
+    /// Repository path
+    pub repo_path: String,
+    /// Also remove untracked directories
+    #[serde(default)]
+    pub directories: bool,
+    /// Also remove ignored files
+    #[serde(default)]
+    pub ignored: bool,
+    /// Report what would be removed instead of removing it
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 /// Stash request.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StashRequest {
     /// Repository path
     pub repo_path: String,
-    /// Stash message
+    /// What to do with the stash
+    #[serde(default)]
+    pub action: StashAction,
+    /// Message of the entry to push
     pub message: Option<String>,
+    /// Whether the entry to push also takes untracked files
+    #[serde(default)]
+    pub include_untracked: bool,
+    /// Entry to pop, apply, drop or show, 0 being the most recent
+    /// (`stash@{0}`); the most recent if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+}
+
+/// Operation on the stash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StashAction {
+    /// Save uncommitted changes as a new entry and clean the working tree
+    #[default]
+    Push,
+    /// Apply an entry to the working tree and drop it
+    Pop,
+    /// Apply an entry to the working tree, keeping it
+    Apply,
+    /// Delete an entry
+    Drop,
+    /// List the entries
+    List,
+    /// Summarize the changes an entry holds
+    Show,
+}
+
+/// Undo request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UndoRequest {
+    /// Repository path
+    pub repo_path: String,
+}
+
+/// Operation journal request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JournalRequest {
+    /// Repository path
+    pub repo_path: String,
 }
 
 /// Watch request for event stream.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WatchRequest {
     /// Repository path
     pub repo_path: String,
 }
+
+// Lifecycle requests
+
+/// Open repository request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OpenRepoRequest {
+    /// Repository path
+    pub repo_path: String,
+}
+
+/// Close repository request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CloseRepoRequest {
+    /// Repository path
+    pub repo_path: String,
+}
+
+/// List repositories request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ListReposRequest {}
+
+/// Backend capabilities request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CapabilitiesRequest {}
+
+/// Reload configuration request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ReloadConfigRequest {}
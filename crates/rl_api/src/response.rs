@@ -1,21 +1,97 @@
 //! Response DTOs for the repo-lens API.
+//!
+//! Paths in responses are UTF-8. A path whose bytes aren't, as file names
+//! on POSIX systems need not be, has each byte outside a UTF-8 character
+//! written as `\xNN`: `caf\xe9.txt` for `café.txt` in Latin-1.
 
 use crate::bounds::Cursor;
 use crate::paging::StreamingChunk;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Top-level response envelope.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Response {
     /// Request ID for correlation
     pub id: String,
     /// Response payload or error
     #[serde(flatten)]
     pub result: Result<ResponsePayload, crate::Error>,
+    /// How the request was handled; set on the final response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ResponseMeta>,
+}
+
+impl Response {
+    /// Whether this is the last response for its request.
+    ///
+    /// Streaming payloads end with a chunk marked `is_final`; errors and all
+    /// other payloads always end the exchange.
+    pub fn is_final(&self) -> bool {
+        match &self.result {
+            Ok(ResponsePayload::DiffContent(chunk)) => chunk.is_final,
+            Ok(ResponsePayload::Blame(chunk)) => chunk.is_final,
+            Ok(ResponsePayload::Progress(chunk)) => chunk.is_final,
+            _ => true,
+        }
+    }
+}
+
+/// Details of how a request was handled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ResponseMeta {
+    /// Time from the engine receiving the request to answering it, in
+    /// milliseconds
+    #[serde(default)]
+    pub elapsed_ms: f64,
+    /// Whether the result was served from a cache instead of computed
+    #[serde(default)]
+    pub cached: bool,
+    /// The bound that cut the result short, if one did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<Truncation>,
+    /// Steps the request went through, in the order they finished; only
+    /// for requests sent with `timings`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepTiming>,
+    /// The `request_id` of the engine's `request` span, which its log
+    /// lines carry; only for requests sent with `trace`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// A request bound that left part of the result out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Truncation {
+    /// Which bound was reached
+    pub bound: TruncationBound,
+    /// Its value in the request
+    pub limit: u64,
+}
+
+/// Request bounds that can truncate a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationBound {
+    /// `max_bytes`: the rest of the diff was not read
+    MaxBytes,
+    /// `max_hunks`: further hunks were left out
+    MaxHunks,
+    /// `page_size`: more items follow on the next page
+    PageSize,
+}
+
+/// Time spent in one step of handling a request.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StepTiming {
+    /// Step name, e.g. `queue` or `status`
+    pub name: String,
+    /// Wall-clock time in milliseconds
+    pub elapsed_ms: f64,
 }
 
 /// Response payload variants.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ResponsePayload {
     /// Status response
@@ -44,16 +120,32 @@ pub enum ResponsePayload {
     MergeResult(MergeResult),
     /// Rebase result
     RebaseResult(RebaseResult),
+    /// Untracked files a clean removed
+    Clean(CleanResult),
+    /// Stash entries, or the entry a stash operation acted on
+    Stash(StashResult),
+    /// What a mutation run as a dry run would change
+    DryRun(DryRunReport),
+    /// The operation an undo reversed
+    Undo(JournalEntry),
+    /// Operation journal response
+    Journal(OperationJournal),
     /// Progress stream
     Progress(StreamingChunk<ProgressUpdate>),
     /// Event stream
     Event(crate::Event),
+    /// Open repository response
+    Repo(RepoInfo),
+    /// Repository list response
+    Repos(RepoList),
+    /// Backend capabilities response
+    Capabilities(Capabilities),
 }
 
 // Data types
 
 /// Repository status view.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StatusView {
     /// Current branch name
     pub branch: Option<String>,
@@ -66,29 +158,31 @@ pub struct StatusView {
 }
 
 /// Working directory status.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WorkdirStatus {
-    /// Modified files
+    /// Files modified in the working tree since they were staged, or with
+    /// unresolved conflicts
     pub modified: Vec<String>,
     /// Added files
     pub added: Vec<String>,
-    /// Deleted files
+    /// Files deleted from the working tree but not from the index
     pub deleted: Vec<String>,
-    /// Renamed files
+    /// Files renamed in the index, as (old, new)
     pub renamed: Vec<(String, String)>,
     /// Untracked files
     pub untracked: Vec<String>,
 }
 
 /// Index status.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IndexStatus {
-    /// Staged files
+    /// Files with changes staged in the index, whether or not they were
+    /// changed again since
     pub staged: Vec<String>,
 }
 
 /// Paged commit list.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitListPage {
     /// Commits in this page
     pub commits: Vec<CommitSummary>,
@@ -99,7 +193,7 @@ pub struct CommitListPage {
 }
 
 /// Commit summary for lists.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitSummary {
     /// Commit OID
     pub id: String,
@@ -116,7 +210,7 @@ pub struct CommitSummary {
 }
 
 /// Commit graph window.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitGraphWindow {
     /// Commits with graph information
     pub commits: Vec<CommitGraphNode>,
@@ -127,17 +221,21 @@ pub struct CommitGraphWindow {
 }
 
 /// Commit with graph lane information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitGraphNode {
     /// Commit summary
     #[serde(flatten)]
     pub commit: CommitSummary,
     /// Graph lanes for this commit
     pub lanes: Vec<GraphLane>,
+    /// Full names of the refs pointing at the commit, e.g.
+    /// `refs/heads/main` or `refs/tags/v1.0`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refs: Vec<String>,
 }
 
 /// Graph lane representation.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GraphLane {
     /// Lane index
     pub index: usize,
@@ -146,21 +244,22 @@ pub struct GraphLane {
 }
 
 /// Type of graph lane.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum LaneType {
     /// Commit on this lane
     Commit,
-    /// Merge line
+    /// Line joining the commit's lane in this row: to a merge's other
+    /// parents, or from the commits that branched off it
     Merge,
-    /// Branch line
+    /// Line of another branch passing this row
     Branch,
     /// Empty space
     Empty,
 }
 
 /// Detailed commit information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommitDetails {
     /// Commit summary
     #[serde(flatten)]
@@ -172,7 +271,7 @@ pub struct CommitDetails {
 }
 
 /// File change in a commit.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FileChange {
     /// File path
     pub path: String,
@@ -188,7 +287,7 @@ pub struct FileChange {
 }
 
 /// Type of file change.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ChangeType {
     /// File added
@@ -202,7 +301,7 @@ pub enum ChangeType {
 }
 
 /// Diff summary.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffSummary {
     /// Total files changed
     pub files_changed: usize,
@@ -215,7 +314,7 @@ pub struct DiffSummary {
 }
 
 /// Chunk of diff content.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffChunk {
     /// File path
     pub path: String,
@@ -224,7 +323,7 @@ pub struct DiffChunk {
 }
 
 /// Diff hunk.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffHunk {
     /// Old file range
     pub old_range: Range,
@@ -237,7 +336,7 @@ pub struct DiffHunk {
 }
 
 /// Range in a file.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Range {
     /// Starting line number
     pub start: usize,
@@ -246,7 +345,7 @@ pub struct Range {
 }
 
 /// Line in a diff hunk.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DiffLine {
     /// Line type
     pub line_type: DiffLineType,
@@ -259,7 +358,7 @@ pub struct DiffLine {
 }
 
 /// Type of diff line.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum DiffLineType {
     /// Context line
@@ -271,7 +370,7 @@ pub enum DiffLineType {
 }
 
 /// Chunk of blame information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BlameChunk {
     /// File path
     pub path: String,
@@ -280,7 +379,7 @@ pub struct BlameChunk {
 }
 
 /// Line in blame output.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BlameLine {
     /// Line number
     pub line_number: usize,
@@ -290,12 +389,15 @@ pub struct BlameLine {
     pub author_name: String,
     /// Author email
     pub author_email: String,
+    /// Author time (Unix timestamp)
+    #[serde(default)]
+    pub author_time: i64,
     /// Line content
     pub content: String,
 }
 
 /// Branch list.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BranchList {
     /// Local branches
     pub local: Vec<BranchInfo>,
@@ -306,7 +408,7 @@ pub struct BranchList {
 }
 
 /// Branch information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct BranchInfo {
     /// Branch name
     pub name: String,
@@ -317,14 +419,14 @@ pub struct BranchInfo {
 }
 
 /// Tag list.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TagList {
     /// Tags
     pub tags: Vec<TagInfo>,
 }
 
 /// Tag information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TagInfo {
     /// Tag name
     pub name: String,
@@ -335,14 +437,14 @@ pub struct TagInfo {
 }
 
 /// Remote list.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RemoteList {
     /// Remotes
     pub remotes: Vec<RemoteInfo>,
 }
 
 /// Remote information.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RemoteInfo {
     /// Remote name
     pub name: String,
@@ -355,7 +457,7 @@ pub struct RemoteInfo {
 }
 
 /// Generic operation result.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OperationResult {
     /// Whether the operation succeeded
     pub success: bool,
@@ -364,7 +466,7 @@ pub struct OperationResult {
 }
 
 /// Merge operation result.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MergeResult {
     /// Whether the merge succeeded
     pub success: bool,
@@ -375,7 +477,7 @@ pub struct MergeResult {
 }
 
 /// Type of merge.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum MergeType {
     /// Fast-forward merge
@@ -387,7 +489,7 @@ pub enum MergeType {
 }
 
 /// Rebase operation result.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RebaseResult {
     /// Whether the rebase succeeded
     pub success: bool,
@@ -397,8 +499,95 @@ pub struct RebaseResult {
     pub conflicts: Vec<String>,
 }
 
+/// Clean operation result.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CleanResult {
+    /// Paths removed, directories ending in `/`
+    pub removed: Vec<String>,
+}
+
+/// Changes a mutation would make, reported by a dry run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DryRunReport {
+    /// Working directory files that would be overwritten
+    pub files: Vec<String>,
+    /// References that would move
+    pub refs: Vec<RefUpdate>,
+    /// Commits that would be rewritten or dropped, newest first
+    pub commits_rewritten: Vec<String>,
+}
+
+/// A reference move.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RefUpdate {
+    /// Reference name (e.g. `refs/heads/main`)
+    pub name: String,
+    /// Commit OID before the move; `None` if the reference would be created
+    pub old: Option<String>,
+    /// Commit OID after the move; `None` if the reference is deleted, or
+    /// would point at a commit yet to be created
+    pub new: Option<String>,
+}
+
+/// Result of a stash operation.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StashResult {
+    /// For `list`, every entry, most recent first; otherwise the entry
+    /// acted on, or none when `push` found no changes to save
+    pub entries: Vec<StashEntry>,
+    /// For `show`, the changes the entry holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<DiffSummary>,
+}
+
+/// An entry of the stash.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StashEntry {
+    /// Position in the stash, 0 being the most recent (`stash@{0}`)
+    pub index: usize,
+    /// Commit OID of the entry
+    pub commit: String,
+    /// Message, e.g. `WIP on main: 1a2b3c4 Fix parser`
+    pub message: String,
+    /// When the entry was made, in seconds since the Unix epoch
+    pub timestamp: i64,
+}
+
+/// Operations the engine performed on a repository.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OperationJournal {
+    /// Operations, most recent first
+    pub entries: Vec<JournalEntry>,
+}
+
+/// An operation the engine performed, as recorded in its journal.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JournalEntry {
+    /// Sequence number, increasing within the repository
+    pub id: u64,
+    /// Request type that performed the operation (e.g. `"merge"`)
+    pub operation: String,
+    /// When the operation finished, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// HEAD commit OID before the operation
+    pub head_before: Option<String>,
+    /// HEAD commit OID after the operation
+    pub head_after: Option<String>,
+    /// Branch checked out before the operation; `None` if detached
+    pub branch_before: Option<String>,
+    /// Branch checked out after the operation; `None` if detached
+    pub branch_after: Option<String>,
+    /// References the operation created, moved or deleted
+    pub refs: Vec<RefUpdate>,
+    /// Stash entry the operation created
+    pub stash_created: Option<String>,
+    /// Stash entry the operation popped or dropped
+    #[serde(default)]
+    pub stash_dropped: Option<String>,
+}
+
 /// Progress update for long-running operations.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProgressUpdate {
     /// Operation stage
     pub stage: String,
@@ -407,3 +596,52 @@ pub struct ProgressUpdate {
     /// Optional message
     pub message: Option<String>,
 }
+
+/// The git backend serving requests and what it can do.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Capabilities {
+    /// Active backend, e.g. `cli`
+    pub backend: String,
+    /// Whether git could be run
+    pub git_available: bool,
+    /// Installed git version, e.g. `2.39.5`
+    pub git_version: Option<String>,
+    /// Oldest git version the backend works with, if it needs git
+    pub min_git_version: Option<String>,
+    /// Whether requests can be served: git, if needed, is present and
+    /// recent enough
+    pub supported: bool,
+    /// Optional git features
+    pub features: GitFeatures,
+}
+
+/// Optional git features and whether the installed git has them.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GitFeatures {
+    /// Renames can be detected in diffs
+    pub rename_detection: bool,
+    /// Commit-graph files can speed up history walks
+    pub commit_graph: bool,
+    /// The built-in file system monitor daemon is available
+    pub fsmonitor: bool,
+}
+
+/// Repositories managed by the engine.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RepoList {
+    /// Open repositories
+    pub repos: Vec<RepoInfo>,
+}
+
+/// State of a repository managed by the engine.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RepoInfo {
+    /// Canonical repository path
+    pub repo_path: String,
+    /// Whether a client is watching the repository for events
+    pub watch_active: bool,
+    /// Bytes held in the engine's caches for the repository
+    pub cache_bytes: u64,
+    /// Git backend serving the repository (e.g. `"cli"`)
+    pub backend: String,
+}
@@ -0,0 +1,249 @@
+//! Machine-readable description of the API contract.
+//!
+//! [`json_schema`] describes [`Request`] and [`Response`], and every type
+//! they are built from, as one JSON Schema (draft 7) document;
+//! [`typescript`] renders the same definitions as TypeScript types. Both
+//! are generated from the Rust types, so clients generated from them stay
+//! in sync with the contract.
+
+use crate::{ApiVersion, Request, Response};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Map, Value};
+
+/// JSON Schema of the API, with `Request` and `Response` among its
+/// definitions.
+pub fn json_schema() -> Value {
+    let mut generator = SchemaSettings::draft07().into_generator();
+    generator.subschema_for::<Request>();
+    generator.subschema_for::<Response>();
+    let definitions: Map<String, Value> = generator
+        .take_definitions()
+        .into_iter()
+        .map(|(name, schema)| (name, json!(schema)))
+        .collect();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": format!("repo-lens API {}", ApiVersion::default().as_str()),
+        "definitions": definitions,
+    })
+}
+
+/// TypeScript declarations for every definition in [`json_schema`].
+pub fn typescript() -> String {
+    let schema = json_schema();
+    let mut out = format!(
+        "// repo-lens API {}, generated from its JSON Schema.\n",
+        ApiVersion::default().as_str()
+    );
+    if let Some(definitions) = schema["definitions"].as_object() {
+        for (name, definition) in definitions {
+            out.push('\n');
+            doc_comment(&mut out, definition, 0);
+            out.push_str(&format!(
+                "export type {} = {};\n",
+                name,
+                ts_type(definition, 0)
+            ));
+        }
+    }
+    out
+}
+
+/// TypeScript type for `schema`, indented for nesting level `depth`.
+fn ts_type(schema: &Value, depth: usize) -> String {
+    let Some(schema) = schema.as_object() else {
+        // `true` accepts anything
+        return "unknown".to_string();
+    };
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return values
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+
+    let mut parts = Vec::new();
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            types => types.as_str().into_iter().collect(),
+        };
+        let types: Vec<String> = types
+            .into_iter()
+            .map(|kind| match kind {
+                "object" => object_type(schema, depth),
+                "array" => array_type(schema, depth),
+                "integer" | "number" => "number".to_string(),
+                kind => kind.to_string(),
+            })
+            .collect();
+        parts.push(group(types, " | "));
+    } else if schema.contains_key("properties") {
+        parts.push(object_type(schema, depth));
+    }
+    for (keyword, separator) in [("allOf", " & "), ("anyOf", " | "), ("oneOf", " | ")] {
+        if let Some(variants) = schema.get(keyword).and_then(Value::as_array) {
+            let variants = variants
+                .iter()
+                .map(|variant| ts_type(variant, depth))
+                .collect();
+            parts.push(group(variants, separator));
+        }
+    }
+    match parts.len() {
+        0 => "unknown".to_string(),
+        _ => group(parts, " & "),
+    }
+}
+
+/// Object type with the schema's properties, or a record of its
+/// `additionalProperties`.
+fn object_type(schema: &Map<String, Value>, depth: usize) -> String {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return match schema.get("additionalProperties") {
+            Some(Value::Bool(false)) => "{}".to_string(),
+            Some(values) => format!("Record<string, {}>", ts_type(values, depth)),
+            // A struct without fields
+            None => "Record<string, never>".to_string(),
+        };
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let indent = "  ".repeat(depth + 1);
+    let mut out = String::from("{\n");
+    for (name, property) in properties {
+        doc_comment(&mut out, property, depth + 1);
+        let optional = if required.contains(&name.as_str()) {
+            ""
+        } else {
+            "?"
+        };
+        out.push_str(&format!(
+            "{}{}{}: {};\n",
+            indent,
+            property_name(name),
+            optional,
+            ts_type(property, depth + 1)
+        ));
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push('}');
+    out
+}
+
+/// Array type of the schema's items; a tuple when they are listed.
+fn array_type(schema: &Map<String, Value>, depth: usize) -> String {
+    match schema.get("items") {
+        Some(Value::Array(items)) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(|item| ts_type(item, depth))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Some(item) => {
+            let item = ts_type(item, depth);
+            if compound(&item) {
+                format!("({})[]", item)
+            } else {
+                format!("{}[]", item)
+            }
+        }
+        None => "unknown[]".to_string(),
+    }
+}
+
+/// `types` joined by `separator`, each in parentheses if it is a union or
+/// intersection itself.
+fn group(types: Vec<String>, separator: &str) -> String {
+    if types.len() == 1 {
+        return types.into_iter().next().unwrap_or_default();
+    }
+    types
+        .into_iter()
+        .map(|kind| {
+            if compound(&kind) {
+                format!("({})", kind)
+            } else {
+                kind
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Whether `kind` is a union or intersection, outside any brackets.
+fn compound(kind: &str) -> bool {
+    let mut nesting = 0;
+    let mut previous = ' ';
+    for c in kind.chars() {
+        match c {
+            '{' | '[' | '(' | '<' => nesting += 1,
+            '}' | ']' | ')' | '>' => nesting -= 1,
+            '|' | '&' if nesting == 0 && previous == ' ' => return true,
+            _ => {}
+        }
+        previous = c;
+    }
+    false
+}
+
+/// `name`, quoted unless it is a valid identifier.
+fn property_name(name: &str) -> String {
+    let identifier = name.chars().enumerate().all(|(i, c)| {
+        c == '_' || c == '$' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+    });
+    if identifier && !name.is_empty() {
+        name.to_string()
+    } else {
+        Value::from(name).to_string()
+    }
+}
+
+/// Write the schema's description, if any, as a doc comment.
+fn doc_comment(out: &mut String, schema: &Value, depth: usize) {
+    let Some(description) = schema.get("description").and_then(Value::as_str) else {
+        return;
+    };
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!("{}/**\n", indent));
+    for line in description.lines() {
+        out.push_str(&format!("{} * {}\n", indent, line).replace("*/", "*\\/"));
+    }
+    out.push_str(&format!("{} */\n", indent));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_describes_requests_and_responses() {
+        let schema = json_schema();
+        let definitions = schema["definitions"].as_object().unwrap();
+        assert!(definitions.contains_key("Request"));
+        assert!(definitions.contains_key("Response"));
+        assert!(definitions.contains_key("StatusRequest"));
+
+        let typescript = typescript();
+        assert!(typescript.contains("export type Request = {\n"));
+        assert!(typescript.contains("  repo_path: string;\n"));
+        // Unions are grouped before they are intersected
+        assert!(typescript.contains("} & ({\n  Ok: ResponsePayload;\n} | {\n  Err: Error;\n});"));
+    }
+}
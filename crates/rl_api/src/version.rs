@@ -1,13 +1,26 @@
 //! API versioning for the repo-lens contract.
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Current API version identifier.
 /// Breaking changes require bumping this version.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, Default)]
 pub enum ApiVersion {
     /// Version 0 - initial stable contract
     #[serde(rename = "v0")]
     #[default]
     V0,
 }
+
+impl ApiVersion {
+    /// Versions this build can serve.
+    pub const SUPPORTED: &'static [ApiVersion] = &[ApiVersion::V0];
+
+    /// Wire name of the version, as used in the `version` field.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::V0 => "v0",
+        }
+    }
+}
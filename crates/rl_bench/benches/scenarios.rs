@@ -0,0 +1,50 @@
+//! Criterion benchmarks of status, log_page and diff_summary against a real
+//! repository.
+//!
+//! The repository is the dataset named by `RL_BENCH_DATASET` (`git` by
+//! default), cloned at its pinned revision into `target/rl_bench/datasets`
+//! the first time, as shallow as its manifest entry allows. When it cannot be cloned, e.g. offline, the benchmarks
+//! run against the repo-lens checkout itself.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rl_bench::benches::{diff_summary, log_page, status};
+use rl_bench::datasets::{DatasetManifest, DatasetResolver};
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the dataset to benchmark against.
+const DATASET_ENV: &str = "RL_BENCH_DATASET";
+
+/// Path of the repository to benchmark against.
+fn repo_path() -> PathBuf {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let name = std::env::var(DATASET_ENV).unwrap_or_else(|_| "git".to_string());
+    let resolved = DatasetManifest::load().and_then(|manifest| {
+        let dataset = manifest
+            .find_by_name(&name)
+            .ok_or_else(|| format!("Dataset '{}' not found", name))?;
+        // Shared with repo-lens-bench, which runs from the workspace root
+        DatasetResolver::with_cache_dir(workspace.join("target/rl_bench/datasets"))?
+            .resolve(dataset)
+    });
+    match resolved {
+        Ok(path) => path,
+        Err(error) => {
+            eprintln!(
+                "Dataset '{}' unavailable ({}); benchmarking the repo-lens checkout",
+                name, error
+            );
+            workspace
+        }
+    }
+}
+
+fn scenarios(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start tokio runtime");
+    let repo_path = repo_path();
+    status::bench_status(c, &runtime, &repo_path);
+    log_page::bench_log_page(c, &runtime, &repo_path);
+    diff_summary::bench_diff_summary(c, &runtime, &repo_path);
+}
+
+criterion_group!(benches, scenarios);
+criterion_main!(benches);
@@ -1,19 +1,21 @@
 //! Diff summary benchmark scenario
 
-use criterion::{black_box, Criterion};
-use rl_api::{request::*, ApiVersion, Request};
+use criterion::{black_box, Criterion, Throughput};
+use rl_api::response::ResponsePayload;
+use rl_api::{request::*, Request};
 use rl_core::RepoEngine;
 use std::path::Path;
+use tokio::runtime::Runtime;
 
-#[allow(dead_code)]
-pub fn bench_diff_summary(c: &mut Criterion, repo_path: &Path) {
+/// Time the summary of the last ten commits' diff, in changed lines per
+/// second.
+pub fn bench_diff_summary(c: &mut Criterion, runtime: &Runtime, repo_path: &Path) {
     let engine = RepoEngine::new();
     let repo_path_str = repo_path.to_string_lossy().to_string();
 
-    let request = Request {
-        version: ApiVersion::V0,
-        id: "bench-diff-summary".to_string(),
-        payload: RequestPayload::DiffSummary(DiffSummaryRequest {
+    let request = Request::new(
+        "bench-diff-summary",
+        RequestPayload::DiffSummary(DiffSummaryRequest {
             repo_path: repo_path_str,
             // Use commits that exist in the Git v2.45.0 repository
             from: Some("HEAD~10".to_string()),
@@ -21,13 +23,24 @@ pub fn bench_diff_summary(c: &mut Criterion, repo_path: &Path) {
             max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
             max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
         }),
+    );
+
+    let lines = match runtime.block_on(engine.handle(request.clone())).result {
+        Ok(ResponsePayload::DiffSummary(diff)) => diff.additions + diff.deletions,
+        other => panic!(
+            "diff summary failed on {}: {:?}",
+            repo_path.display(),
+            other
+        ),
     };
 
-    c.bench_function("diff_summary", |b| {
+    let mut group = c.benchmark_group("diff_summary");
+    group.throughput(Throughput::Elements(lines.max(1) as u64));
+    group.bench_function("diff_summary", |b| {
         b.iter(|| {
             let request = black_box(request.clone());
-            // This calls the stubbed engine - will return "not implemented" but measures overhead
-            let _result = futures::executor::block_on(engine.handle(request));
+            runtime.block_on(engine.handle(request))
         });
     });
+    group.finish();
 }
@@ -1,19 +1,20 @@
 //! Log page benchmark scenario
 
-use criterion::{black_box, Criterion};
-use rl_api::{request::*, ApiVersion, Request};
+use criterion::{black_box, Criterion, Throughput};
+use rl_api::response::ResponsePayload;
+use rl_api::{request::*, Request};
 use rl_core::RepoEngine;
 use std::path::Path;
+use tokio::runtime::Runtime;
 
-#[allow(dead_code)]
-pub fn bench_log_page(c: &mut Criterion, repo_path: &Path) {
+/// Time the first page of history, in commits per second.
+pub fn bench_log_page(c: &mut Criterion, runtime: &Runtime, repo_path: &Path) {
     let engine = RepoEngine::new();
     let repo_path_str = repo_path.to_string_lossy().to_string();
 
-    let request = Request {
-        version: ApiVersion::V0,
-        id: "bench-log-page".to_string(),
-        payload: RequestPayload::Log(LogRequest {
+    let request = Request::new(
+        "bench-log-page",
+        RequestPayload::Log(LogRequest {
             repo_path: repo_path_str,
             paging: rl_api::Paging {
                 page_size: rl_api::PageSize::try_from(200).unwrap(),
@@ -21,13 +22,20 @@ pub fn bench_log_page(c: &mut Criterion, repo_path: &Path) {
             },
             revision_range: None,
         }),
+    );
+
+    let commits = match runtime.block_on(engine.handle(request.clone())).result {
+        Ok(ResponsePayload::Log(page)) => page.commits.len(),
+        other => panic!("log failed on {}: {:?}", repo_path.display(), other),
     };
 
-    c.bench_function("log_page", |b| {
+    let mut group = c.benchmark_group("log_page");
+    group.throughput(Throughput::Elements(commits as u64));
+    group.bench_function("log_page", |b| {
         b.iter(|| {
             let request = black_box(request.clone());
-            // This calls the stubbed engine - will return "not implemented" but measures overhead
-            let _result = futures::executor::block_on(engine.handle(request));
+            runtime.block_on(engine.handle(request))
         });
     });
+    group.finish();
 }
@@ -1,28 +1,43 @@
 //! Status benchmark scenario
 
-use criterion::{black_box, Criterion};
-use rl_api::{request::*, ApiVersion, Request};
+use criterion::{black_box, Criterion, Throughput};
+use rl_api::response::ResponsePayload;
+use rl_api::{request::*, Request};
 use rl_core::RepoEngine;
 use std::path::Path;
+use tokio::runtime::Runtime;
 
-#[allow(dead_code)]
-pub fn bench_status(c: &mut Criterion, repo_path: &Path) {
+/// Time status requests, in changed files per second.
+pub fn bench_status(c: &mut Criterion, runtime: &Runtime, repo_path: &Path) {
     let engine = RepoEngine::new();
     let repo_path_str = repo_path.to_string_lossy().to_string();
 
-    let request = Request {
-        version: ApiVersion::V0,
-        id: "bench-status".to_string(),
-        payload: RequestPayload::Status(StatusRequest {
+    let request = Request::new(
+        "bench-status",
+        RequestPayload::Status(StatusRequest {
             repo_path: repo_path_str,
         }),
+    );
+
+    let files = match runtime.block_on(engine.handle(request.clone())).result {
+        Ok(ResponsePayload::Status(status)) => {
+            status.index.staged.len()
+                + status.workdir.modified.len()
+                + status.workdir.deleted.len()
+                + status.workdir.renamed.len()
+                + status.workdir.untracked.len()
+        }
+        other => panic!("status failed on {}: {:?}", repo_path.display(), other),
     };
 
-    c.bench_function("status", |b| {
+    let mut group = c.benchmark_group("status");
+    // A clean tree still reads every entry, so count at least one
+    group.throughput(Throughput::Elements(files.max(1) as u64));
+    group.bench_function("status", |b| {
         b.iter(|| {
             let request = black_box(request.clone());
-            // This calls the stubbed engine - will return "not implemented" but measures overhead
-            let _result = futures::executor::block_on(engine.handle(request));
+            runtime.block_on(engine.handle(request))
         });
     });
+    group.finish();
 }
@@ -0,0 +1,90 @@
+//! Per-scenario budgets.
+//!
+//! A budgets file gives each scenario its own limits on each dataset, as
+//! TOML tables named `[<dataset>.<scenario>]`:
+//!
+//! ```toml
+//! [git.log_page]
+//! cold_ms = 400
+//! warm_ms = 30
+//! p99_ms = 80
+//! ```
+//!
+//! Any budget left out is not checked. `repo-lens-bench run --budgets`
+//! loads the file; `--budget-ms` still overrides `p99_ms`, and the
+//! manifest's `budgets_ms` fill in `p99_ms` for scenarios the file leaves
+//! out.
+
+use crate::scenarios::{CacheMode, TimingInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Budgets by dataset name, then scenario name
+pub type Budgets = BTreeMap<String, BTreeMap<String, ScenarioBudget>>;
+
+/// Load a budgets file.
+pub fn load(path: &Path) -> Result<Budgets, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read budgets file {}: {}", path.display(), e))?;
+    toml::from_str(&content)
+        .map_err(|e| format!("Invalid budgets file {}: {}", path.display(), e).into())
+}
+
+/// Budgets for one scenario on one dataset, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioBudget {
+    /// Median cold run, or the first run when only warm runs are timed
+    pub cold_ms: Option<f64>,
+    /// Median warm run
+    pub warm_ms: Option<f64>,
+    /// Time 99% of the runs in a result's `timings` must stay within
+    pub p99_ms: Option<f64>,
+}
+
+/// A budget a run went over
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExceededBudget {
+    /// Which budget: `cold`, `warm` or `p99`
+    pub budget: String,
+    /// The budget in milliseconds
+    pub limit_ms: f64,
+    /// The time held to it in milliseconds
+    pub actual_ms: f64,
+}
+
+impl ScenarioBudget {
+    /// The budgets that runs in `cache` mode went over. `timings` are cold
+    /// runs in `cold` mode and warm runs otherwise; `cold_timings` are the
+    /// cold runs of `both`.
+    pub fn exceeded(
+        &self,
+        cache: CacheMode,
+        timings: &TimingInfo,
+        cold_timings: Option<&TimingInfo>,
+    ) -> Vec<ExceededBudget> {
+        let cold_ms = match (cache, cold_timings) {
+            (CacheMode::Cold, _) => timings.p50_ms,
+            (_, Some(cold)) => cold.p50_ms,
+            (_, None) => timings.cold_ms,
+        };
+        let warm_ms = (cache != CacheMode::Cold).then_some(timings.p50_ms);
+
+        [
+            ("cold", self.cold_ms, Some(cold_ms)),
+            ("warm", self.warm_ms, warm_ms),
+            ("p99", self.p99_ms, Some(timings.p99_ms)),
+        ]
+        .into_iter()
+        .filter_map(|(budget, limit_ms, actual_ms)| {
+            let (limit_ms, actual_ms) = (limit_ms?, actual_ms?);
+            (actual_ms > limit_ms).then(|| ExceededBudget {
+                budget: budget.to_string(),
+                limit_ms,
+                actual_ms,
+            })
+        })
+        .collect()
+    }
+}
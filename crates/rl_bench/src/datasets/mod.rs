@@ -1,9 +1,12 @@
 //! Benchmark dataset management.
 //!
-//! This module handles downloading, caching, and preparing external Git repositories
-//! used for performance benchmarking.
+//! This module handles downloading, caching, and preparing external Git
+//! repositories used for performance benchmarking, and generating synthetic
+//! ones locally.
 
+use rl_fixtures::large_repo::{LargeRepo, LargeRepoSpec};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -21,6 +24,29 @@ pub struct Dataset {
     pub revision: String,
     /// Size category for informational purposes
     pub size_category: String,
+    /// Budget in milliseconds for each scenario, that 99% of warm runs must
+    /// stay within; scenarios without one always pass
+    #[serde(default)]
+    pub budgets_ms: BTreeMap<String, f64>,
+    /// Fetch only this many commits of history, unless full history is
+    /// asked for
+    #[serde(default)]
+    pub clone_depth: Option<u32>,
+    /// Partial clone filter, e.g. `blob:none`, unless full history is asked
+    /// for; filtered objects are fetched when first read
+    #[serde(default)]
+    pub clone_filter: Option<String>,
+    /// Generate the repository locally from this spec instead of cloning
+    /// `url`, which then only describes where it came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synthetic: Option<LargeRepoSpec>,
+}
+
+impl Dataset {
+    /// Whether the manifest asks for a shallow or partial clone.
+    pub fn is_partial(&self) -> bool {
+        self.clone_depth.is_some() || self.clone_filter.is_some()
+    }
 }
 
 /// Dataset manifest containing all available datasets
@@ -73,22 +99,56 @@ impl DatasetResolver {
     /// Resolve a dataset by name, cloning if necessary and ensuring correct revision
     #[allow(dead_code)]
     pub fn resolve(&self, dataset: &Dataset) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        self.resolve_with(dataset, false)
+    }
+
+    /// Like [`DatasetResolver::resolve`], but with `full` the dataset gets
+    /// its complete history even if the manifest asks for a shallow or
+    /// partial clone, as scenarios such as blame need. A shallow or partial
+    /// clone already in the cache is then cloned again.
+    pub fn resolve_with(
+        &self,
+        dataset: &Dataset,
+        full: bool,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
         let dataset_path = self.cache_dir.join(&dataset.name);
+        if let Some(spec) = &dataset.synthetic {
+            // Generated whole, so there is no shallower version of it
+            if !LargeRepo::is_built(spec, &dataset_path) {
+                println!("Generating dataset '{}' ({})...", dataset.name, spec.name());
+            }
+            LargeRepo::ensure_at(spec, &dataset_path)?;
+            return Ok(dataset_path);
+        }
+        // A complete clone already in the cache is kept complete
+        let full = full || !dataset.is_partial() || is_complete_clone(&dataset_path);
+
+        if full && dataset_path.exists() && is_partial_clone(&dataset_path) {
+            println!("Replacing partial clone of dataset '{}'...", dataset.name);
+            fs::remove_dir_all(&dataset_path)?;
+        }
 
         // Clone if doesn't exist
         if !dataset_path.exists() {
             println!("Cloning dataset '{}' from {}...", dataset.name, dataset.url);
-            self.clone_repository(&dataset.url, &dataset_path)?;
+            if full {
+                self.clone_repository(&dataset.url, &dataset_path)?;
+            } else {
+                self.init_partial_clone(&dataset.url, &dataset_path)?;
+            }
         }
 
         // Ensure correct revision is checked out
-        self.checkout_revision(&dataset_path, &dataset.revision)?;
+        if full {
+            self.checkout_revision(&dataset_path, &dataset.revision)?;
+        } else {
+            self.fetch_revision(&dataset_path, dataset)?;
+        }
 
         Ok(dataset_path)
     }
 
     /// Clone a repository to the specified path
-    #[allow(dead_code)]
     fn clone_repository(&self, url: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
         let status = Command::new("git")
             .args(["clone", "--quiet", url, &path.to_string_lossy()])
@@ -101,8 +161,76 @@ impl DatasetResolver {
         Ok(())
     }
 
+    /// Create an empty repository at `path` fetching from `url`, for
+    /// [`DatasetResolver::fetch_revision`] to fill.
+    fn init_partial_clone(&self, url: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let init_status = Command::new("git")
+            .args(["init", "--quiet", &path.to_string_lossy()])
+            .status()?;
+        let remote_status = Command::new("git")
+            .args(["remote", "add", "origin", url])
+            .current_dir(path)
+            .status()?;
+
+        if !init_status.success() || !remote_status.success() {
+            return Err(format!("Failed to create repository for {}", url).into());
+        }
+
+        Ok(())
+    }
+
+    /// Fetch just the dataset's revision, as shallow and filtered as the
+    /// manifest asks, and check it out. The revision is recorded in the
+    /// repository's config, so a later resolve doesn't fetch it again.
+    fn fetch_revision(
+        &self,
+        path: &Path,
+        dataset: &Dataset,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let pinned = Command::new("git")
+            .args(["config", "rl-bench.revision"])
+            .current_dir(path)
+            .output()?;
+        if String::from_utf8_lossy(&pinned.stdout).trim() == dataset.revision {
+            return Ok(());
+        }
+
+        let mut args = vec!["fetch".to_string(), "--quiet".to_string()];
+        args.extend(
+            dataset
+                .clone_depth
+                .map(|depth| format!("--depth={}", depth)),
+        );
+        args.extend(
+            dataset
+                .clone_filter
+                .as_ref()
+                .map(|filter| format!("--filter={}", filter)),
+        );
+        args.extend(["origin".to_string(), dataset.revision.clone()]);
+        let fetch_status = Command::new("git").args(&args).current_dir(path).status()?;
+
+        if !fetch_status.success() {
+            return Err(format!("Failed to fetch revision {}", dataset.revision).into());
+        }
+
+        let checkout_status = Command::new("git")
+            .args(["checkout", "--quiet", "--detach", "FETCH_HEAD"])
+            .current_dir(path)
+            .status()?;
+        let pin_status = Command::new("git")
+            .args(["config", "rl-bench.revision", &dataset.revision])
+            .current_dir(path)
+            .status()?;
+
+        if !checkout_status.success() || !pin_status.success() {
+            return Err(format!("Failed to checkout revision {}", dataset.revision).into());
+        }
+
+        Ok(())
+    }
+
     /// Checkout the specified revision in the repository
-    #[allow(dead_code)]
     fn checkout_revision(
         &self,
         path: &Path,
@@ -165,6 +293,26 @@ impl DatasetResolver {
     }
 }
 
+/// Whether the repository at `path` is missing history or objects: a
+/// shallow clone, or a partial one fetching filtered objects on demand.
+fn is_partial_clone(path: &Path) -> bool {
+    let promisor = Command::new("git")
+        .args(["config", "--bool", "remote.origin.promisor"])
+        .current_dir(path)
+        .output();
+    path.join(".git").join("shallow").exists()
+        || promisor.is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+/// Whether `path` holds a repository with a checkout and all its history.
+fn is_complete_clone(path: &Path) -> bool {
+    let head = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .current_dir(path)
+        .output();
+    head.is_ok_and(|output| output.status.success()) && !is_partial_clone(path)
+}
+
 /// Get the default dataset (Git repository)
 #[allow(dead_code)]
 pub fn default_dataset() -> Result<Dataset, Box<dyn std::error::Error>> {
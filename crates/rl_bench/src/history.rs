@@ -0,0 +1,193 @@
+//! Benchmark history.
+//!
+//! `repo-lens-bench run --record` appends each run's results to a JSONL
+//! store, one line per run, keyed by the repo-lens commit benchmarked and
+//! the machine it ran on. `repo-lens-bench history` shows how timings moved
+//! across the last runs, so gradual drift is visible before it trips a
+//! budget or a baseline comparison.
+
+use crate::scenarios::{CacheMode, SentinelResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+/// Default location of the history store
+pub const DEFAULT_HISTORY_PATH: &str = "target/rl_bench/history.jsonl";
+
+/// One recorded benchmark run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the run finished
+    pub timestamp: String,
+    /// repo-lens commit benchmarked, if it was run from a git checkout
+    pub commit: Option<String>,
+    /// Whether the checkout had uncommitted changes
+    #[serde(default)]
+    pub dirty: bool,
+    /// Machine the run happened on, from [`machine_fingerprint`]
+    pub machine: String,
+    /// Result of each scenario
+    pub results: Vec<SentinelResult>,
+}
+
+impl HistoryEntry {
+    /// Record `results` as a run of the current checkout on this machine.
+    pub fn new(results: Vec<SentinelResult>) -> Self {
+        let commit = git_output(&["rev-parse", "HEAD"]);
+        let dirty = git_output(&["status", "--porcelain", "--untracked-files=no"])
+            .is_some_and(|changes| !changes.is_empty());
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            commit,
+            dirty,
+            machine: machine_fingerprint(),
+            results,
+        }
+    }
+}
+
+/// Identifies the machine a run happened on: its OS, architecture, number
+/// of CPUs and host name. Timings are only comparable on one machine.
+pub fn machine_fingerprint() -> String {
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let host = fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!(
+        "{}-{}-{}cpu-{}",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        cpus,
+        host
+    )
+}
+
+/// Trimmed stdout of a successful git command in the working directory.
+fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Append `entry` to the store at `path`, creating it if needed.
+pub fn append(path: &Path, entry: &HistoryEntry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+}
+
+/// Every run in the store at `path`, oldest first; none if it doesn't exist.
+pub fn load(path: &Path) -> std::io::Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(std::io::Error::from))
+        .collect()
+}
+
+/// Timings of one scenario in one run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendPoint {
+    /// When the run finished
+    pub timestamp: String,
+    /// repo-lens commit benchmarked
+    pub commit: Option<String>,
+    /// Whether the checkout had uncommitted changes
+    pub dirty: bool,
+    /// Median run time in milliseconds
+    pub p50_ms: f64,
+    /// Run time in milliseconds that 99% of runs stayed within
+    pub p99_ms: f64,
+    /// Relative change of `p50_ms` since the previous run
+    pub p50_change: Option<f64>,
+    /// Relative change of `p99_ms` since the previous run
+    pub p99_change: Option<f64>,
+}
+
+/// How one scenario's timings moved across runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trend {
+    /// Dataset name
+    pub dataset: String,
+    /// Scenario name
+    pub scenario: String,
+    /// Cache state the runs started from
+    pub cache: CacheMode,
+    /// Timings of each run, oldest first
+    pub points: Vec<TrendPoint>,
+    /// Relative change of the median from the first run shown to the last
+    pub p50_drift: Option<f64>,
+}
+
+/// Trends shown by `repo-lens-bench history`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryReport {
+    /// Machine whose runs are shown; `None` when runs from every machine are
+    pub machine: Option<String>,
+    /// Number of runs shown
+    pub runs: usize,
+    /// Trend of each scenario
+    pub trends: Vec<Trend>,
+}
+
+/// Trends of every scenario over the last `last` runs in `entries`, by
+/// dataset, scenario and cache mode.
+pub fn trends(entries: &[HistoryEntry], last: usize) -> Vec<Trend> {
+    let change = |from: f64, to: f64| (from > 0.0).then(|| (to - from) / from);
+
+    let mut series = BTreeMap::<_, Vec<TrendPoint>>::new();
+    for entry in &entries[entries.len().saturating_sub(last)..] {
+        for result in &entry.results {
+            let key = (
+                result.dataset.name.clone(),
+                result.scenario.clone(),
+                result.cache,
+            );
+            let points = series.entry(key).or_default();
+            let previous = points.last();
+            points.push(TrendPoint {
+                timestamp: entry.timestamp.clone(),
+                commit: entry.commit.clone(),
+                dirty: entry.dirty,
+                p50_ms: result.timings.p50_ms,
+                p99_ms: result.timings.p99_ms,
+                p50_change: previous.and_then(|p| change(p.p50_ms, result.timings.p50_ms)),
+                p99_change: previous.and_then(|p| change(p.p99_ms, result.timings.p99_ms)),
+            });
+        }
+    }
+
+    series
+        .into_iter()
+        .map(|((dataset, scenario, cache), points)| {
+            let p50_drift = match (points.first(), points.last()) {
+                (Some(first), Some(last)) if points.len() > 1 => change(first.p50_ms, last.p50_ms),
+                _ => None,
+            };
+            Trend {
+                dataset,
+                scenario,
+                cache,
+                points,
+                p50_drift,
+            }
+        })
+        .collect()
+}
@@ -4,9 +4,13 @@
 //! repo-lens-bench binary.
 
 pub mod benches;
+pub mod budgets;
 pub mod datasets;
+pub mod history;
 pub mod oracle;
+pub mod profile;
 pub mod regression;
+pub mod report;
 pub mod scenarios;
 
 #[cfg(test)]
@@ -46,7 +50,8 @@ mod tests {
     async fn test_oracle_status_correctness() {
         use rl_fixtures::synth_repo::SynthRepo;
 
-        let synth = match SynthRepo::ensure("oracle_status") {
+        // Its working tree is changed, so it is not shared with other tests
+        let synth = match SynthRepo::isolated("oracle_status") {
             Ok(repo) => repo,
             Err(e) => {
                 eprintln!("Failed to create synthetic repo: {}", e);
@@ -71,13 +76,12 @@ mod tests {
         };
 
         let engine = rl_core::RepoEngine::new();
-        let request = rl_api::Request {
-            version: rl_api::ApiVersion::V0,
-            id: "oracle-test".to_string(),
-            payload: rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+        let request = rl_api::Request::new(
+            "oracle-test",
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
                 repo_path: synth.path.to_string_lossy().to_string(),
             }),
-        };
+        );
 
         let response = engine.handle(request).await;
 
@@ -158,19 +162,16 @@ mod tests {
             .unwrap();
 
         let engine = rl_core::RepoEngine::new();
-        let request = rl_api::Request {
-            version: rl_api::ApiVersion::V0,
-            id: "oracle-diff-test".to_string(),
-            payload: rl_api::request::RequestPayload::DiffSummary(
-                rl_api::request::DiffSummaryRequest {
-                    repo_path: synth.path.to_string_lossy().to_string(),
-                    from: Some("C0".to_string()),
-                    to: Some("C1".to_string()),
-                    max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
-                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
-                },
-            ),
-        };
+        let request = rl_api::Request::new(
+            "oracle-diff-test",
+            rl_api::request::RequestPayload::DiffSummary(rl_api::request::DiffSummaryRequest {
+                repo_path: synth.path.to_string_lossy().to_string(),
+                from: Some("C0".to_string()),
+                to: Some("C1".to_string()),
+                max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            }),
+        );
 
         let response = engine.handle(request).await;
 
@@ -237,19 +238,16 @@ mod tests {
         };
 
         let engine = rl_core::RepoEngine::new();
-        let request = rl_api::Request {
-            version: rl_api::ApiVersion::V0,
-            id: "oracle-diff-test".to_string(),
-            payload: rl_api::request::RequestPayload::DiffSummary(
-                rl_api::request::DiffSummaryRequest {
-                    repo_path: synth.path.to_string_lossy().to_string(),
-                    from: Some("C1".to_string()),
-                    to: Some("C2".to_string()),
-                    max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
-                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
-                },
-            ),
-        };
+        let request = rl_api::Request::new(
+            "oracle-diff-test",
+            rl_api::request::RequestPayload::DiffSummary(rl_api::request::DiffSummaryRequest {
+                repo_path: synth.path.to_string_lossy().to_string(),
+                from: Some("C1".to_string()),
+                to: Some("C2".to_string()),
+                max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            }),
+        );
 
         let response = engine.handle(request).await;
 
@@ -282,19 +280,16 @@ mod tests {
         };
 
         let engine = rl_core::RepoEngine::new();
-        let request = rl_api::Request {
-            version: rl_api::ApiVersion::V0,
-            id: "oracle-diff-test".to_string(),
-            payload: rl_api::request::RequestPayload::DiffSummary(
-                rl_api::request::DiffSummaryRequest {
-                    repo_path: synth.path.to_string_lossy().to_string(),
-                    from: Some("C2".to_string()),
-                    to: Some("C3".to_string()),
-                    max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
-                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
-                },
-            ),
-        };
+        let request = rl_api::Request::new(
+            "oracle-diff-test",
+            rl_api::request::RequestPayload::DiffSummary(rl_api::request::DiffSummaryRequest {
+                repo_path: synth.path.to_string_lossy().to_string(),
+                from: Some("C2".to_string()),
+                to: Some("C3".to_string()),
+                max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            }),
+        );
 
         let response = engine.handle(request).await;
 
@@ -318,4 +313,1304 @@ mod tests {
 
         eprintln!("✓ Oracle diff C2..C3 test passed");
     }
+
+    #[tokio::test]
+    async fn test_oracle_diff_summary_binary_and_lfs_files() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("oracle_diff") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+        let git_cli = oracle::git_cli::GitCli::new(&synth.path);
+        let engine = rl_core::RepoEngine::new();
+
+        // A large binary added and changed, a file flipping between text and
+        // binary, and an LFS pointer, which is text whatever it points to
+        for (from, to) in [("C3", "C4"), ("C4", "C5"), ("C5", "C6")] {
+            let numstat = git_cli
+                .run(&["diff", "--numstat", &format!("{}..{}", from, to)])
+                .unwrap();
+            // git counts no lines in a binary file
+            let mut expected: Vec<String> = numstat
+                .stdout
+                .lines()
+                .map(|line| {
+                    let fields: Vec<&str> = line.splitn(3, '\t').collect();
+                    let count = |field: &str| field.parse::<usize>().unwrap_or(0);
+                    format!("{} {} {}", count(fields[0]), count(fields[1]), fields[2])
+                })
+                .collect();
+            expected.sort();
+
+            let response = engine
+                .handle(rl_api::Request::new(
+                    "oracle-diff-binary",
+                    rl_api::request::RequestPayload::DiffSummary(
+                        rl_api::request::DiffSummaryRequest {
+                            repo_path: synth.path.to_string_lossy().to_string(),
+                            from: Some(from.to_string()),
+                            to: Some(to.to_string()),
+                            max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                            max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+                        },
+                    ),
+                ))
+                .await;
+            let diff_summary = match response.result {
+                Ok(rl_api::response::ResponsePayload::DiffSummary(diff)) => diff,
+                Ok(other) => panic!("Expected DiffSummary response, got {:?}", other),
+                Err(e) => panic!("Engine returned error: {}", e),
+            };
+            let mut actual: Vec<String> = diff_summary
+                .changes
+                .iter()
+                .map(|c| format!("{} {} {}", c.additions, c.deletions, c.path))
+                .collect();
+            actual.sort();
+
+            assert_eq!(actual, expected, "{}..{}", from, to);
+        }
+    }
+
+    /// The first `limit` commits of `git log`, one line per commit with its
+    /// id, author, committer time, parents and subject.
+    fn oracle_log(repo_path: &Path, limit: usize) -> Vec<String> {
+        let git_cli = oracle::git_cli::GitCli::new(repo_path);
+        let count = format!("--max-count={}", limit);
+        let output = git_cli
+            .run(&[
+                "log",
+                "--format=%H%x00%an%x00%ae%x00%ct%x00%P%x00%B%x1e",
+                &count,
+            ])
+            .unwrap();
+        output
+            .stdout
+            .split('\x1e')
+            .map(str::trim_start)
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                // The engine keeps the first line of the message
+                let (fields, message) = record.rsplit_once('\0').unwrap();
+                format!("{}\0{}", fields, message.lines().next().unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// The first `limit` commits the engine lists, read `page_size` at a
+    /// time, in the same form as [`oracle_log`].
+    async fn engine_log(repo_path: &Path, page_size: u32, limit: usize) -> Vec<String> {
+        let engine = rl_core::RepoEngine::new();
+        let mut cursor = rl_api::Cursor::initial();
+        let mut lines = Vec::new();
+        while lines.len() < limit {
+            let request = rl_api::Request::new(
+                "oracle-log-test",
+                rl_api::request::RequestPayload::Log(rl_api::request::LogRequest {
+                    repo_path: repo_path.to_string_lossy().to_string(),
+                    paging: rl_api::Paging {
+                        page_size: rl_api::PageSize::try_from(page_size).unwrap(),
+                        cursor: cursor.clone(),
+                    },
+                    revision_range: None,
+                }),
+            );
+            let page = match engine.handle(request).await.result {
+                Ok(rl_api::response::ResponsePayload::Log(page)) => page,
+                Ok(other) => panic!("Expected Log response, got {:?}", other),
+                Err(e) => panic!("Engine returned error: {}", e),
+            };
+            lines.extend(page.commits.into_iter().map(|commit| {
+                format!(
+                    "{}\0{}\0{}\0{}\0{}\0{}",
+                    commit.id,
+                    commit.author_name,
+                    commit.author_email,
+                    commit.time,
+                    commit.parents.join(" "),
+                    commit.message
+                )
+            }));
+            match page.next_cursor {
+                Some(next) if page.has_more => cursor = next,
+                _ => break,
+            }
+        }
+        lines.truncate(limit);
+        lines
+    }
+
+    /// Compare the engine's history of `repo_path` with git's, paging
+    /// through it `page_size` commits at a time.
+    async fn assert_log_matches_oracle(repo_path: &Path, page_size: u32, limit: usize) {
+        let expected = oracle::normalize::sort_stable(oracle_log(repo_path, limit));
+        let actual = oracle::normalize::sort_stable(engine_log(repo_path, page_size, limit).await);
+        if let Err(diff) = oracle::compare::compare_lines(&expected, &actual) {
+            eprintln!(
+                "Expected {} commits, got {}",
+                diff.expected_len, diff.actual_len
+            );
+            if let Some(idx) = diff.first_mismatch {
+                eprintln!("First mismatch at line {}", idx);
+            }
+            panic!("Oracle log comparison failed for {}", repo_path.display());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oracle_log_synth_pages() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("oracle_log") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+
+        // Pages smaller than the history, so every boundary is crossed
+        assert_log_matches_oracle(&synth.path, 2, usize::MAX).await;
+    }
+
+    #[tokio::test]
+    async fn test_oracle_log_dataset_first_commits() {
+        let dataset_path = Path::new("target/rl_bench/datasets/git");
+
+        if !dataset_path.exists() {
+            return; // Skip test if dataset doesn't exist
+        }
+
+        assert_log_matches_oracle(dataset_path, 200, 500).await;
+    }
+
+    /// `git blame --line-porcelain` of `path`, one line per source line with
+    /// its number, commit, author, author time and content.
+    fn oracle_blame(repo_path: &Path, path: &str, revision: Option<&str>) -> Vec<String> {
+        let git_cli = oracle::git_cli::GitCli::new(repo_path);
+        let mut args = vec!["blame", "--line-porcelain"];
+        args.extend(revision);
+        args.extend(["--", path]);
+        let output = git_cli.run(&args).unwrap();
+
+        let mut lines = Vec::new();
+        let mut header: Option<(String, String)> = None;
+        let (mut author, mut email, mut time) = (String::new(), String::new(), String::new());
+        for line in output.stdout.lines() {
+            if let Some(content) = line.strip_prefix('\t') {
+                let (commit_id, line_number) = header.take().unwrap();
+                lines.push(format!(
+                    "{}\0{}\0{}\0{}\0{}\0{}",
+                    line_number, commit_id, author, email, time, content
+                ));
+            } else if let Some(value) = line.strip_prefix("author ") {
+                author = value.to_string();
+            } else if let Some(value) = line.strip_prefix("author-mail ") {
+                email = value.trim_matches(['<', '>']).to_string();
+            } else if let Some(value) = line.strip_prefix("author-time ") {
+                time = value.to_string();
+            } else if header.is_none() {
+                // "<commit> <original line> <final line> [<group size>]"
+                let fields: Vec<&str> = line.split(' ').collect();
+                header = Some((fields[0].to_string(), fields[2].to_string()));
+            }
+        }
+        lines
+    }
+
+    /// Every blame line the engine streams for `path`, in the same form as
+    /// [`oracle_blame`].
+    async fn engine_blame(repo_path: &Path, path: &str, revision: Option<&str>) -> Vec<String> {
+        let engine = rl_core::RepoEngine::new();
+        let request = rl_api::Request::new(
+            "oracle-blame-test",
+            rl_api::request::RequestPayload::Blame(rl_api::request::BlameRequest {
+                repo_path: repo_path.to_string_lossy().to_string(),
+                path: path.to_string(),
+                revision: revision.map(str::to_string),
+                lines: None,
+            }),
+        );
+
+        let (responses, mut received) = tokio::sync::mpsc::channel::<rl_api::Response>(16);
+        let collect = async {
+            let mut lines = Vec::new();
+            while let Some(response) = received.recv().await {
+                match response.result {
+                    Ok(rl_api::response::ResponsePayload::Blame(chunk)) => {
+                        lines.extend(chunk.data.lines.into_iter().map(|line| {
+                            format!(
+                                "{}\0{}\0{}\0{}\0{}\0{}",
+                                line.line_number,
+                                line.commit_id,
+                                line.author_name,
+                                line.author_email,
+                                line.author_time,
+                                line.content
+                            )
+                        }))
+                    }
+                    Ok(other) => panic!("Expected Blame response, got {:?}", other),
+                    Err(e) => panic!("Engine returned error: {}", e),
+                }
+            }
+            lines
+        };
+        let ((), lines) = tokio::join!(engine.handle_stream(request, responses), collect);
+        lines
+    }
+
+    /// Compare the engine's blame of `path` with git's, line by line.
+    async fn assert_blame_matches_oracle(repo_path: &Path, path: &str, revision: Option<&str>) {
+        let expected = oracle_blame(repo_path, path, revision);
+        assert!(!expected.is_empty(), "git blamed no lines of {}", path);
+        let actual = engine_blame(repo_path, path, revision).await;
+        if let Err(diff) = oracle::compare::compare_lines(&expected, &actual) {
+            eprintln!(
+                "Expected {} lines, got {}",
+                diff.expected_len, diff.actual_len
+            );
+            if let Some(idx) = diff.first_mismatch {
+                eprintln!("First mismatch at line {}", idx);
+                eprintln!("Expected: {:?}", expected.get(idx));
+                eprintln!("Actual: {:?}", actual.get(idx));
+            }
+            panic!("Oracle blame comparison failed for {}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oracle_blame_synth_files() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("oracle_blame") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+
+        // Lines from C0 and C1, at HEAD and before C1 changed them
+        assert_blame_matches_oracle(&synth.path, "a.txt", None).await;
+        assert_blame_matches_oracle(&synth.path, "a.txt", Some("C0")).await;
+        // Renamed from dir/b.txt in C2, so its lines still belong to C0
+        assert_blame_matches_oracle(&synth.path, "dir/c.txt", None).await;
+    }
+
+    /// A clone of the synthetic repo with remote-tracking branches, a local
+    /// branch, an annotated tag and a second remote, rebuilt on every run.
+    fn refs_fixture() -> Option<std::path::PathBuf> {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let origin = match SynthRepo::ensure("oracle_refs") {
+            Ok(repo) => repo.path,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return None;
+            }
+        };
+        let clone = origin.with_file_name("clone");
+        if clone.exists() {
+            std::fs::remove_dir_all(&clone).unwrap();
+        }
+        let origin_url = origin.to_string_lossy().to_string();
+        let clone_path = clone.to_string_lossy().to_string();
+        oracle::git_cli::GitCli::new(&origin)
+            .run(&["clone", "--quiet", &origin_url, &clone_path])
+            .unwrap();
+
+        let git_cli = oracle::git_cli::GitCli::new(&clone);
+        for args in [
+            &["branch", "feature", "C1"][..],
+            &[
+                "-c",
+                "user.name=Test User",
+                "-c",
+                "user.email=test@example.com",
+                "tag",
+                "-a",
+                "v1.0",
+                "-m",
+                "Release 1.0",
+                "-m",
+                "With notes",
+                "C2",
+            ],
+            &["remote", "add", "upstream", &origin_url],
+            &[
+                "config",
+                "--add",
+                "remote.upstream.push",
+                "refs/heads/feature",
+            ],
+        ] {
+            git_cli.run(args).unwrap();
+        }
+        Some(clone)
+    }
+
+    /// The engine's answer to `payload`.
+    async fn engine_payload(
+        payload: rl_api::request::RequestPayload,
+    ) -> rl_api::response::ResponsePayload {
+        let request = rl_api::Request::new("oracle-refs-test", payload);
+        match rl_core::RepoEngine::new().handle(request).await.result {
+            Ok(payload) => payload,
+            Err(e) => panic!("Engine returned error: {}", e),
+        }
+    }
+
+    fn assert_lines_match(what: &str, expected: Vec<String>, actual: Vec<String>) {
+        let expected = oracle::normalize::sort_stable(expected);
+        let actual = oracle::normalize::sort_stable(actual);
+        if let Err(diff) = oracle::compare::compare_lines(&expected, &actual) {
+            eprintln!("Expected: {:?}", expected);
+            eprintln!("Actual: {:?}", actual);
+            if let Some(idx) = diff.first_mismatch {
+                eprintln!("First mismatch at line {}", idx);
+            }
+            panic!("Oracle {} comparison failed", what);
+        }
+    }
+
+    /// Branches as `git for-each-ref` lists them, current one marked `*`,
+    /// compared with the engine's.
+    async fn assert_branches_match_oracle(repo_path: &Path) -> rl_api::response::BranchList {
+        let output = oracle::git_cli::GitCli::new(repo_path)
+            .run(&[
+                "for-each-ref",
+                "--format=%(HEAD)%00%(refname)%00%(objectname)%00%(symref)",
+                "refs/heads",
+                "refs/remotes",
+            ])
+            .unwrap();
+        let expected = output
+            .stdout
+            .lines()
+            .filter_map(|line| line.strip_suffix('\0'))
+            .map(str::to_string)
+            .collect();
+
+        let payload = rl_api::request::RequestPayload::Branches(rl_api::request::BranchesRequest {
+            repo_path: repo_path.to_string_lossy().to_string(),
+        });
+        let branches = match engine_payload(payload).await {
+            rl_api::response::ResponsePayload::Branches(branches) => branches,
+            other => panic!("Expected Branches response, got {:?}", other),
+        };
+        let local = branches.local.iter().map(|branch| {
+            let current = branches.current.as_deref() == Some(branch.name.as_str());
+            format!(
+                "{}\0refs/heads/{}\0{}",
+                if current { "*" } else { " " },
+                branch.name,
+                branch.commit_id
+            )
+        });
+        let remote = branches
+            .remote
+            .iter()
+            .map(|branch| format!(" \0refs/remotes/{}\0{}", branch.name, branch.commit_id));
+        assert_lines_match("branches", expected, local.chain(remote).collect());
+        branches
+    }
+
+    #[tokio::test]
+    async fn test_oracle_branches_tags_remotes() {
+        let Some(repo_path) = refs_fixture() else {
+            return;
+        };
+        let git_cli = oracle::git_cli::GitCli::new(&repo_path);
+        let repo = repo_path.to_string_lossy().to_string();
+
+        let branches = assert_branches_match_oracle(&repo_path).await;
+        assert!(
+            !branches.remote.is_empty(),
+            "Expected remote-tracking branches"
+        );
+        git_cli
+            .run(&["checkout", "--quiet", "--detach", "C1"])
+            .unwrap();
+        let branches = assert_branches_match_oracle(&repo_path).await;
+        assert_eq!(branches.current, None, "Detached HEAD is on no branch");
+
+        // Annotated tags are peeled to their commit and keep their message
+        let output = git_cli
+            .run(&[
+                "for-each-ref",
+                "--format=%(refname:strip=2)%00\
+                 %(if)%(*objectname)%(then)%(*objectname)%(else)%(objectname)%(end)%00\
+                 %(if)%(taggername)%(then)%(contents:subject)%(end)",
+                "refs/tags",
+            ])
+            .unwrap();
+        let expected = oracle::normalize::normalize_lines(&output.stdout);
+        let payload = rl_api::request::RequestPayload::Tags(rl_api::request::TagsRequest {
+            repo_path: repo.clone(),
+        });
+        let tags = match engine_payload(payload).await {
+            rl_api::response::ResponsePayload::Tags(tags) => tags,
+            other => panic!("Expected Tags response, got {:?}", other),
+        };
+        let actual = tags
+            .tags
+            .iter()
+            .map(|tag| {
+                let subject = tag
+                    .message
+                    .as_deref()
+                    .and_then(|message| message.lines().next());
+                format!(
+                    "{}\0{}\0{}",
+                    tag.name,
+                    tag.commit_id,
+                    subject.unwrap_or_default()
+                )
+            })
+            .collect();
+        assert_lines_match("tags", expected, actual);
+
+        let output = git_cli.run(&["remote", "-v"]).unwrap();
+        let expected = output
+            .stdout
+            .lines()
+            .filter_map(|line| line.strip_suffix(" (fetch)"))
+            .map(|line| line.replacen('\t', "\0", 1))
+            .collect();
+        let payload = rl_api::request::RequestPayload::Remotes(rl_api::request::RemotesRequest {
+            repo_path: repo,
+        });
+        let remotes = match engine_payload(payload).await {
+            rl_api::response::ResponsePayload::Remotes(remotes) => remotes,
+            other => panic!("Expected Remotes response, got {:?}", other),
+        };
+        let actual = remotes
+            .remotes
+            .iter()
+            .map(|remote| format!("{}\0{}", remote.name, remote.url))
+            .collect();
+        assert_lines_match("remotes", expected, actual);
+        let upstream = remotes
+            .remotes
+            .iter()
+            .find(|remote| remote.name == "upstream");
+        assert_eq!(
+            upstream.map(|remote| remote.push_refspecs.clone()),
+            Some(vec!["refs/heads/feature".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_timing_percentiles() {
+        // 1..=100 ms, shuffled
+        let samples = (1..=100).map(|i| ((i * 37) % 100 + 1) as f64).collect();
+        let timings = scenarios::TimingInfo::from_samples(5.0, samples);
+        assert_eq!(timings.iterations, 100);
+        assert_eq!(timings.warm_total_ms, 5050.0);
+        assert_eq!(timings.p50_ms, 50.0);
+        assert_eq!(timings.p90_ms, 90.0);
+        assert_eq!(timings.p99_ms, 99.0);
+        assert_eq!(timings.max_ms, 100.0);
+        assert!((timings.stddev_ms - 28.866).abs() < 0.001);
+
+        let (status, reason) = scenarios::budget_status(&timings, Some(98.0));
+        assert_eq!(status, "fail");
+        assert_eq!(reason.as_deref(), Some("budget_exceeded"));
+        assert_eq!(scenarios::budget_status(&timings, Some(99.0)).0, "pass");
+    }
+
+    #[test]
+    fn test_sampling_trims_outliers_from_both_ends() {
+        let sampling = scenarios::Sampling {
+            warmup: 0,
+            iterations: 20,
+            trim_percent: 10.0,
+        };
+        // 18 runs of 10..=27 ms, one stalled and one impossibly quick
+        let mut samples: Vec<f64> = (10..28).map(f64::from).collect();
+        samples.extend([500.0, 0.1]);
+        let timings = sampling.summarize(5.0, samples);
+        assert_eq!(timings.trimmed, 4);
+        assert_eq!(timings.iterations, 16);
+        assert_eq!(timings.samples_ms.first(), Some(&11.0));
+        assert_eq!(timings.max_ms, 26.0);
+
+        // However much is trimmed, a run is kept
+        let greedy = scenarios::Sampling {
+            trim_percent: 49.0,
+            ..sampling
+        };
+        let timings = greedy.summarize(5.0, vec![3.0, 1.0, 2.0]);
+        assert_eq!((timings.trimmed, timings.p50_ms), (2, 2.0));
+        let untrimmed = scenarios::Sampling::new(3).summarize(5.0, vec![3.0, 1.0, 2.0]);
+        assert_eq!((untrimmed.trimmed, untrimmed.iterations), (0, 3));
+    }
+
+    #[test]
+    fn test_regression_needs_more_than_noise() {
+        use regression::{compare, Verdict};
+
+        // A single sample each can only be compared by the threshold
+        let single = compare(&[10.0], &[13.0]);
+        assert_eq!(single.verdict, Verdict::Regression);
+        assert_eq!(single.p_value, None);
+
+        // Two runners alternating between fast and slow: the medians differ
+        // by far more than 20%, but the timings are the same noise
+        let baseline = [10.0, 11.0, 12.0, 13.0, 14.0, 40.0, 41.0, 42.0, 43.0];
+        let current = [10.0, 11.0, 12.0, 13.0, 40.0, 41.0, 42.0, 43.0, 44.0];
+        let noisy = compare(&baseline, &current);
+        assert!(noisy.relative_change > 1.0);
+        assert_eq!(noisy.verdict, Verdict::Stable);
+        assert!(noisy.confidence().unwrap() < 0.95);
+        assert!(noisy.status().starts_with("STABLE"));
+
+        // Every run half as slow again is a regression beyond doubt
+        let baseline: Vec<f64> = (0..20).map(|i| 10.0 + i as f64 * 0.1).collect();
+        let current: Vec<f64> = baseline.iter().map(|ms| ms * 1.5).collect();
+        let slower = compare(&baseline, &current);
+        assert_eq!(slower.verdict, Verdict::Regression);
+        assert!(slower.confidence().unwrap() > 0.999);
+        assert_eq!(compare(&current, &baseline).verdict, Verdict::Improvement);
+        // Identical runs are stable with no confidence of a difference
+        assert!(compare(&baseline, &baseline).confidence().unwrap() < 1e-6);
+    }
+
+    #[test]
+    fn test_budgets_file_reports_each_exceeded_budget() {
+        let path =
+            std::env::temp_dir().join(format!("rl_bench_budgets_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[git.log_page]\ncold_ms = 4\nwarm_ms = 60\np99_ms = 98.5\n",
+        )
+        .unwrap();
+        let budgets = budgets::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let budget = budgets["git"]["log_page"];
+        assert_eq!(budget.warm_ms, Some(60.0));
+
+        // First run 5 ms, warm runs 1..=100 ms
+        let samples = (1..=100).map(|i| i as f64).collect();
+        let timings = scenarios::TimingInfo::from_samples(5.0, samples);
+        let exceeded = budget.exceeded(scenarios::CacheMode::Warm, &timings, None);
+        let names: Vec<_> = exceeded.iter().map(|e| e.budget.as_str()).collect();
+        assert_eq!(names, ["cold", "p99"]);
+        assert_eq!(exceeded[0].actual_ms, 5.0);
+        assert_eq!(exceeded[1].limit_ms, 98.5);
+
+        // In cold mode the timings are cold runs, and there is no warm one
+        let exceeded = budget.exceeded(scenarios::CacheMode::Cold, &timings, None);
+        assert_eq!(exceeded[0].budget, "cold");
+        assert_eq!(exceeded[0].actual_ms, 50.0);
+    }
+
+    #[tokio::test]
+    async fn test_cold_runs_use_a_fresh_engine_each_time() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("bench_cold") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+
+        let scenario = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .find(|s| s.name == "log_page")
+            .unwrap();
+        let timings = scenarios::time_cold_scenario(&scenario, &scenarios::Sampling::new(3), false)
+            .await
+            .unwrap();
+        assert_eq!(timings.iterations, 3);
+        assert!(timings.cold_ms > 0.0);
+        assert!(timings.p50_ms <= timings.max_ms);
+    }
+
+    #[tokio::test]
+    async fn test_backends_are_compared_on_the_same_scenario() {
+        use rl_core::config::BackendKind;
+        use rl_fixtures::synth_repo::SynthRepo;
+        use scenarios::backends::{time_backend_scenario, BackendScenarioResult};
+
+        let synth = match SynthRepo::ensure("bench_backends") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+        let scenario = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .find(|s| s.name == "status")
+            .unwrap();
+        let sampling = scenarios::Sampling::new(3);
+        let mut timings = Vec::new();
+        for name in ["cli", "stub"] {
+            let backend: BackendKind = name.parse().unwrap();
+            timings.push(
+                time_backend_scenario(backend, &scenario, &sampling)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let result = BackendScenarioResult::new(&scenario.name, timings);
+
+        // The stub serves nothing, so it is reported failed, not fastest
+        assert_eq!(result.fastest.as_deref(), Some("cli"));
+        assert_eq!(result.backends[0].vs_fastest, Some(1.0));
+        assert!(result.backends[1].timings.is_none() && result.backends[1].error.is_some());
+        let table = report::Report::from_backends("backends", &[result]);
+        assert_eq!(table.failures(), 1);
+        assert!(table
+            .render(report::ReportFormat::Markdown)
+            .contains("| cli | status | ✅ pass | "));
+        assert!("gitoxide".parse::<BackendKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats_count_repeats_served_from_cache() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("bench_cache") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+        // The synthetic history is too short for `diff_summary`'s HEAD~10
+        let scenario = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .find(|s| s.name == "log_page")
+            .unwrap();
+        let engine = rl_core::RepoEngine::new();
+        assert!(engine.handle(scenario.request.clone()).await.result.is_ok());
+        let stats = scenarios::cache_stats(&engine, &scenario, 5, 0.9).await;
+        assert_eq!(stats.requests, 5);
+        assert_eq!(stats.hits, 5);
+        assert_eq!(stats.hit_rate, 1.0);
+        assert!(!stats.is_below_minimum());
+
+        // With caching off every repeat runs again
+        let engine = rl_core::RepoEngine::with_config(rl_core::EngineConfig {
+            cache_enabled: false,
+            ..Default::default()
+        });
+        let stats = scenarios::cache_stats(&engine, &scenario, 5, 0.9).await;
+        assert_eq!(stats.hits, 0);
+        assert!(stats.is_below_minimum());
+
+        // Falling short fails the scenario, and the report says by how much
+        let unused = scenarios::CacheStats {
+            requests: 50,
+            hits: 0,
+            hit_rate: 0.0,
+            min_hit_rate: 0.9,
+        };
+        assert!(unused.is_below_minimum());
+        let result = scenarios::SentinelResult {
+            dataset: scenarios::DatasetInfo {
+                name: "git".to_string(),
+                url: String::new(),
+                rev: "v2.45.0".to_string(),
+                path: String::new(),
+                exists: true,
+            },
+            scenario: "diff_summary".to_string(),
+            cache: scenarios::CacheMode::Warm,
+            timings: scenarios::TimingInfo::from_samples(1.0, vec![1.0]),
+            cold_timings: None,
+            warm_speedup: None,
+            page_cache_dropped: false,
+            budget_ms: None,
+            cold_budget_ms: None,
+            warm_budget_ms: Some(5.0),
+            exceeded: Vec::new(),
+            response: None,
+            cache_stats: Some(unused),
+            status: "fail".to_string(),
+            reason: Some("cache_hit_rate".to_string()),
+        };
+        let results = std::collections::BTreeMap::from([(
+            "git".to_string(),
+            std::collections::BTreeMap::from([("diff_summary".to_string(), result)]),
+        )]);
+        let markdown =
+            report::Report::from_results("cache", &results).render(report::ReportFormat::Markdown);
+        assert!(markdown.contains("cache_hit_rate; 0 of 50 repeats served from cache, below 90%"));
+    }
+
+    #[tokio::test]
+    async fn test_timings_break_down_by_engine_step() {
+        use rl_api::response::StepTiming;
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        // Repeated steps add up within a run; skipped ones count fewer runs
+        let step = |name: &str, elapsed_ms| StepTiming {
+            name: name.to_string(),
+            elapsed_ms,
+        };
+        let steps = scenarios::step_breakdown(&[
+            vec![step("open", 1.0), step("parse", 2.0), step("parse", 2.0)],
+            vec![step("parse", 3.0)],
+        ]);
+        let summary: Vec<_> = steps.iter().map(|s| (s.name.as_str(), s.runs)).collect();
+        assert_eq!(summary, [("open", 1), ("parse", 2)]);
+        assert_eq!(steps[1].p50_ms, 3.0);
+
+        let synth = match SynthRepo::ensure("bench_steps") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+        let scenario = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .find(|s| s.name == "status")
+            .unwrap();
+        let engine = rl_core::RepoEngine::new();
+        let timings = scenarios::time_scenario(&engine, &scenario, &scenarios::Sampling::new(3))
+            .await
+            .unwrap();
+        let status = timings
+            .steps
+            .iter()
+            .find(|step| step.name == "git_status_porcelain")
+            .expect("status is timed");
+        assert!(status.runs <= 3 && status.p50_ms <= timings.max_ms);
+    }
+
+    #[tokio::test]
+    async fn test_ipc_scenario_goes_through_a_serve_process() {
+        use rl_fixtures::synth_repo::SynthRepo;
+        use scenarios::ipc::{default_server_bin, time_ipc_scenario, IpcScenarioResult};
+
+        let server = default_server_bin();
+        if !server.is_file() {
+            eprintln!("{} not built, skipping", server.display());
+            return;
+        }
+        let synth = match SynthRepo::ensure("bench_ipc") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+
+        let scenario = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .find(|s| s.name == "log_page")
+            .unwrap();
+        let serve = scenarios::ipc::ServeProcess::spawn(&server).await.unwrap();
+        let ipc = time_ipc_scenario(&serve.client, &scenario, 5)
+            .await
+            .unwrap();
+        assert_eq!(ipc.iterations, 5);
+        let engine = rl_core::RepoEngine::new();
+        let in_process = scenarios::time_scenario(&engine, &scenario, &scenarios::Sampling::new(5))
+            .await
+            .unwrap();
+        let result = IpcScenarioResult::new(&scenario.name, ipc, in_process);
+        assert_eq!(
+            result.overhead_p50_ms,
+            result.ipc.p50_ms - result.engine.p50_ms
+        );
+
+        // A request the engine rejects comes back as an error, not a timing
+        let mut broken = scenario.clone();
+        if let rl_api::request::RequestPayload::Log(log) = &mut broken.request.payload {
+            log.repo_path = synth.path.join("missing").to_string_lossy().to_string();
+        }
+        assert!(time_ipc_scenario(&serve.client, &broken, 1).await.is_err());
+    }
+
+    #[test]
+    fn test_shallow_dataset_is_completed_with_full_history() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("dataset_origin") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+        let cache_dir = synth.path.with_file_name("cache");
+        if cache_dir.exists() {
+            std::fs::remove_dir_all(&cache_dir).unwrap();
+        }
+        let dataset = datasets::Dataset {
+            name: "synth".to_string(),
+            description: "Synthetic repo".to_string(),
+            // Depth is ignored when cloning a plain local path
+            url: format!("file://{}", synth.path.display()),
+            revision: "C2".to_string(),
+            size_category: "tiny".to_string(),
+            budgets_ms: Default::default(),
+            clone_depth: Some(1),
+            clone_filter: None,
+            synthetic: None,
+        };
+        let resolver = datasets::DatasetResolver::with_cache_dir(cache_dir).unwrap();
+        let commits = |path: &Path| {
+            let git_cli = oracle::git_cli::GitCli::new(path);
+            let output = git_cli.run(&["rev-list", "--count", "HEAD"]).unwrap();
+            output.stdout.trim().parse::<usize>().unwrap()
+        };
+
+        let path = resolver.resolve(&dataset).unwrap();
+        assert_eq!(commits(&path), 1);
+        // Already at the pinned revision, so nothing is fetched
+        assert_eq!(resolver.resolve(&dataset).unwrap(), path);
+
+        let path = resolver.resolve_with(&dataset, true).unwrap();
+        assert_eq!(commits(&path), 3);
+        assert!(!path.join(".git/shallow").exists());
+    }
+
+    #[test]
+    fn test_synthetic_dataset_is_the_same_everywhere() {
+        use rl_fixtures::large_repo::{LargeRepo, LargeRepoSpec};
+
+        let spec = LargeRepoSpec {
+            commits: 60,
+            files: 600,
+            branchiness: 0.3,
+            seed: 7,
+        };
+        let first = match LargeRepo::ensure(&spec) {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create large repo: {}", e);
+                return;
+            }
+        };
+        let git = |path: &Path, args: &[&str]| {
+            let output = oracle::git_cli::GitCli::new(path).run(args).unwrap();
+            output.stdout.trim().to_string()
+        };
+        assert_eq!(
+            git(
+                &first.path,
+                &["rev-list", "--count", "--first-parent", "HEAD"]
+            ),
+            "60"
+        );
+        assert_ne!(
+            git(&first.path, &["rev-list", "--count", "--merges", "HEAD"]),
+            "0"
+        );
+        assert_eq!(git(&first.path, &["ls-files"]).lines().count(), 600);
+
+        // Generated anew as a dataset elsewhere, it has the same commit ids
+        let cache_dir = first.path.with_file_name("cache");
+        let dataset = datasets::Dataset {
+            name: "synthetic".to_string(),
+            description: "Generated repo".to_string(),
+            url: "rl_fixtures/large_repo".to_string(),
+            revision: "master".to_string(),
+            size_category: "tiny".to_string(),
+            budgets_ms: Default::default(),
+            clone_depth: None,
+            clone_filter: None,
+            synthetic: Some(spec),
+        };
+        let resolver = datasets::DatasetResolver::with_cache_dir(cache_dir).unwrap();
+        let path = resolver.resolve(&dataset).unwrap();
+        assert_eq!(
+            git(&path, &["rev-parse", "HEAD"]),
+            git(&first.path, &["rev-parse", "HEAD"])
+        );
+
+        // Another seed is another repository, replacing the first
+        let reseeded = datasets::Dataset {
+            synthetic: Some(LargeRepoSpec { seed: 8, ..spec }),
+            ..dataset
+        };
+        let path = resolver.resolve(&reseeded).unwrap();
+        assert_ne!(
+            git(&path, &["rev-parse", "HEAD"]),
+            git(&first.path, &["rev-parse", "HEAD"])
+        );
+    }
+
+    #[test]
+    fn test_reports_mark_failed_scenarios() {
+        use report::{Report, ReportFormat};
+
+        let result = |scenario: &str, exceeded: Vec<budgets::ExceededBudget>| {
+            let status = if exceeded.is_empty() { "pass" } else { "fail" };
+            scenarios::SentinelResult {
+                dataset: scenarios::DatasetInfo {
+                    name: "git".to_string(),
+                    url: String::new(),
+                    rev: "v2.45.0".to_string(),
+                    path: String::new(),
+                    exists: true,
+                },
+                scenario: scenario.to_string(),
+                cache: scenarios::CacheMode::Warm,
+                timings: scenarios::TimingInfo::from_samples(5.0, vec![4.0, 12.0]),
+                cold_timings: None,
+                warm_speedup: None,
+                page_cache_dropped: false,
+                budget_ms: Some(10.0),
+                cold_budget_ms: None,
+                warm_budget_ms: None,
+                reason: (!exceeded.is_empty()).then(|| "budget_exceeded".to_string()),
+                exceeded,
+                response: None,
+                cache_stats: None,
+                status: status.to_string(),
+            }
+        };
+        let exceeded = budgets::ExceededBudget {
+            budget: "p99".to_string(),
+            limit_ms: 10.0,
+            actual_ms: 12.0,
+        };
+        let results = std::collections::BTreeMap::from([(
+            "git".to_string(),
+            std::collections::BTreeMap::from([
+                ("log_page".to_string(), result("log_page", Vec::new())),
+                ("a<b&c".to_string(), result("a<b&c", vec![exceeded])),
+            ]),
+        )]);
+        let report = Report::from_results("bench", &results);
+        assert_eq!(report.failures(), 1);
+
+        let junit = report.render(ReportFormat::Junit);
+        assert!(junit.contains(r#"<testsuite name="git" tests="2" failures="1""#));
+        assert!(junit.contains(r#"<testcase classname="git" name="a&lt;b&amp;c""#));
+        assert!(junit.contains(
+            r#"<failure message="budget_exceeded; p99 12.00 ms over its 10.00 ms budget">"#
+        ));
+
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains("1 passed, 1 failed"));
+        assert!(markdown.contains("| git | log_page | ✅ pass | 4.00 | 12.00 | 10.00 | 2 |"));
+
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains(r#"<tr class="fail"><td>git</td><td>a&lt;b&amp;c</td>"#));
+    }
+
+    #[test]
+    fn test_history_trends_show_change_between_runs() {
+        let run = |commit: &str, p50_ms: f64| {
+            let result = scenarios::SentinelResult {
+                dataset: scenarios::DatasetInfo {
+                    name: "git".to_string(),
+                    url: String::new(),
+                    rev: "v2.45.0".to_string(),
+                    path: String::new(),
+                    exists: true,
+                },
+                scenario: "status".to_string(),
+                cache: scenarios::CacheMode::Warm,
+                timings: scenarios::TimingInfo::from_samples(p50_ms, vec![p50_ms]),
+                cold_timings: None,
+                warm_speedup: None,
+                page_cache_dropped: false,
+                budget_ms: None,
+                cold_budget_ms: None,
+                warm_budget_ms: None,
+                exceeded: Vec::new(),
+                response: None,
+                cache_stats: None,
+                status: "pass".to_string(),
+                reason: None,
+            };
+            history::HistoryEntry {
+                timestamp: String::new(),
+                commit: Some(commit.to_string()),
+                dirty: false,
+                machine: "test".to_string(),
+                results: vec![result],
+            }
+        };
+        let entries = [
+            run("a", 10.0),
+            run("b", 20.0),
+            run("c", 25.0),
+            run("d", 30.0),
+        ];
+
+        // Only the last three runs
+        let trends = history::trends(&entries, 3);
+        assert_eq!(trends.len(), 1);
+        let points = &trends[0].points;
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].commit.as_deref(), Some("b"));
+        assert_eq!(points[0].p50_change, None);
+        assert_eq!(points[1].p50_change, Some(0.25));
+        assert_eq!(trends[0].p50_drift, Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_answers_every_request() {
+        use rl_fixtures::synth_repo::SynthRepo;
+
+        let synth = match SynthRepo::ensure("bench_concurrency") {
+            Ok(repo) => repo,
+            Err(e) => {
+                eprintln!("Failed to create synthetic repo: {}", e);
+                return;
+            }
+        };
+
+        let mix: Vec<_> = scenarios::generate_scenarios(&synth.path)
+            .into_iter()
+            .filter(|s| s.name == "status" || s.name == "log_page")
+            .collect();
+        let engine = std::sync::Arc::new(rl_core::RepoEngine::new());
+        let level = scenarios::time_concurrency(&engine, &mix, 4, 30)
+            .await
+            .unwrap();
+        assert_eq!(level.concurrency, 4);
+        assert_eq!(level.requests, 30);
+        assert_eq!(level.errors, 0);
+        assert!(level.throughput_rps > 0.0);
+        assert!(level.p50_ms <= level.p99_ms && level.p99_ms <= level.max_ms);
+    }
+
+    #[tokio::test]
+    async fn test_pathological_scenarios_are_streamed_and_bounded() {
+        let engine = rl_core::RepoEngine::new();
+        // 500 files, a 1MB file, 10,000 lines and 1,000 commits
+        for pathological in scenarios::pathological::pathological_scenarios(0.01) {
+            let (_repo, scenario) = pathological.prepare().unwrap();
+            let shape = scenarios::response_shape(&engine, &scenario).await.unwrap();
+            assert_eq!(shape.error, None, "{} failed", scenario.name);
+            assert_eq!(
+                shape.truncated.map(|truncation| truncation.bound),
+                pathological.expect_truncated,
+                "{} was cut short by the wrong bound",
+                scenario.name
+            );
+            if scenario.name == "long_file_blame" {
+                // 500 lines a chunk, the last one marked final
+                assert_eq!(shape.chunks, 20);
+            }
+        }
+    }
+
+    /// What the engine should report of each path in `git status`, one
+    /// line per fact: the index column gives what is staged and the
+    /// working tree column what is modified or deleted.
+    fn oracle_status(repo_path: &Path) -> Vec<String> {
+        let output = oracle::git_cli::GitCli::new(repo_path)
+            .run(&["status", "--porcelain=v1", "-z"])
+            .unwrap();
+        let mut records = output.stdout.split('\0').filter(|r| !r.is_empty());
+        let mut lines = Vec::new();
+        while let Some(record) = records.next() {
+            let (code, path) = record.split_at(3);
+            let (x, y) = (code.as_bytes()[0], code.as_bytes()[1]);
+            if code == "?? " {
+                lines.push(format!("untracked {}", path));
+                continue;
+            }
+            if code.contains('U') || code == "AA " || code == "DD " {
+                lines.push(format!("modified {}", path));
+                continue;
+            }
+            // The path it was renamed from follows
+            if x == b'R' {
+                lines.push(format!("renamed {} {}", records.next().unwrap(), path));
+            }
+            if x != b' ' {
+                lines.push(format!("staged {}", path));
+            }
+            match y {
+                b'M' => lines.push(format!("modified {}", path)),
+                b'D' => lines.push(format!("deleted {}", path)),
+                _ => {}
+            }
+        }
+        lines
+    }
+
+    /// Compare the engine's status of `repo_path` with git's.
+    async fn assert_status_matches_oracle(repo_path: &Path) {
+        use rl_api::request::{RequestPayload, StatusRequest};
+        use rl_api::response::ResponsePayload;
+
+        let status = match engine_payload(RequestPayload::Status(StatusRequest {
+            repo_path: repo_path.to_string_lossy().to_string(),
+        }))
+        .await
+        {
+            ResponsePayload::Status(status) => status,
+            other => panic!("Expected Status response, got {:?}", other),
+        };
+        let lines = |kind: &str, paths: &[String]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| format!("{} {}", kind, path))
+                .collect()
+        };
+        let mut actual = lines("staged", &status.index.staged);
+        actual.extend(lines("modified", &status.workdir.modified));
+        actual.extend(lines("deleted", &status.workdir.deleted));
+        actual.extend(lines("untracked", &status.workdir.untracked));
+        let renamed = status.workdir.renamed.iter();
+        actual.extend(renamed.map(|(from, to)| format!("renamed {} {}", from, to)));
+        assert_lines_match("status", oracle_status(repo_path), actual);
+    }
+
+    #[tokio::test]
+    async fn test_oracle_status_separates_staged_and_unstaged_changes() {
+        use rl_fixtures::mixed_status_repo::{self, MixedStatusRepo};
+
+        let repo = MixedStatusRepo::create("bench_mixed_status").unwrap();
+        assert_status_matches_oracle(&repo.path).await;
+
+        // Pinned as well, so that the oracle can't drift along with the engine
+        let mut expected = vec![
+            format!("staged {}", mixed_status_repo::STAGED),
+            format!("modified {}", mixed_status_repo::UNSTAGED),
+            format!("staged {}", mixed_status_repo::STAGED_MODIFIED),
+            format!("modified {}", mixed_status_repo::STAGED_MODIFIED),
+            format!("staged {}", mixed_status_repo::STAGED_DELETED),
+            format!(
+                "renamed {} {}",
+                mixed_status_repo::RENAMED_FROM,
+                mixed_status_repo::RENAMED_TO
+            ),
+            format!("staged {}", mixed_status_repo::RENAMED_TO),
+            format!("modified {}", mixed_status_repo::RENAMED_TO),
+        ];
+        expected.sort();
+        let mut oracle = oracle_status(&repo.path);
+        oracle.sort();
+        assert_eq!(oracle, expected);
+    }
+
+    /// Changes between `from` and `to` as `git diff -M` reports them, with
+    /// their line counts.
+    fn oracle_diff(repo_path: &Path, from: &str, to: &str) -> Vec<String> {
+        let git_cli = oracle::git_cli::GitCli::new(repo_path);
+        let range = format!("{}..{}", from, to);
+        let name_status = git_cli
+            .run(&["diff", "--name-status", "-M", "-z", &range])
+            .unwrap();
+        let numstat = git_cli
+            .run(&["diff", "--numstat", "-M", "-z", &range])
+            .unwrap();
+
+        let mut counts = std::collections::HashMap::new();
+        let mut records = numstat.stdout.split('\0');
+        while let Some(record) = records.next().filter(|r| !r.is_empty()) {
+            let mut fields = record.splitn(3, '\t');
+            let (added, deleted) = (fields.next().unwrap(), fields.next().unwrap());
+            let path = match fields.next().unwrap() {
+                // Renames give the old and new paths as records of their own
+                "" => records.nth(1).unwrap(),
+                path => path,
+            };
+            counts.insert(path.to_string(), format!("{}\t{}", added, deleted));
+        }
+
+        let mut lines = Vec::new();
+        let mut records = name_status.stdout.split('\0');
+        while let Some(status) = records.next().filter(|r| !r.is_empty()) {
+            let kind = &status[..1];
+            let paths = match kind {
+                "R" => format!("{}\t{}", records.next().unwrap(), records.next().unwrap()),
+                _ => records.next().unwrap().to_string(),
+            };
+            let path = paths.rsplit('\t').next().unwrap();
+            lines.push(format!("{}\t{}\t{}", kind, paths, counts[path]));
+        }
+        lines
+    }
+
+    /// Compare the engine's status, diffs between `picks` of commits and
+    /// history of a random repository with git's.
+    async fn assert_random_repo_matches_oracle(
+        repo_path: &Path,
+        picks: (proptest::sample::Index, proptest::sample::Index),
+        page_size: u32,
+    ) {
+        use rl_api::request::{DiffSummaryRequest, RequestPayload};
+        use rl_api::response::{ChangeType, ResponsePayload};
+
+        let repo = repo_path.to_string_lossy().to_string();
+        assert_status_matches_oracle(repo_path).await;
+
+        let commits: Vec<String> = oracle::git_cli::GitCli::new(repo_path)
+            .run(&["rev-list", "HEAD"])
+            .unwrap()
+            .stdout
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let (from, to) = (picks.0.get(&commits), picks.1.get(&commits));
+        let diff = match engine_payload(RequestPayload::DiffSummary(DiffSummaryRequest {
+            repo_path: repo.clone(),
+            from: Some(from.clone()),
+            to: Some(to.clone()),
+            max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+            max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+        }))
+        .await
+        {
+            ResponsePayload::DiffSummary(diff) => diff,
+            other => panic!("Expected DiffSummary response, got {:?}", other),
+        };
+        let actual = diff
+            .changes
+            .iter()
+            .map(|change| {
+                let kind = match change.change_type {
+                    ChangeType::Added => "A",
+                    ChangeType::Modified => "M",
+                    ChangeType::Deleted => "D",
+                    ChangeType::Renamed => "R",
+                };
+                let paths = match &change.old_path {
+                    Some(old_path) => format!("{}\t{}", old_path, change.path),
+                    None => change.path.clone(),
+                };
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    kind, paths, change.additions, change.deletions
+                )
+            })
+            .collect();
+        assert_lines_match("diff", oracle_diff(repo_path, from, to), actual);
+
+        assert_log_matches_oracle(repo_path, page_size, usize::MAX).await;
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig {
+            cases: 200,
+            ..Default::default()
+        })]
+
+        #[test]
+        fn test_oracle_random_repos(
+            plan in rl_fixtures::random_repo::repo_plan(),
+            picks in proptest::prelude::any::<(proptest::sample::Index, proptest::sample::Index)>(),
+            page_size in 1u32..4,
+        ) {
+            let repo = rl_fixtures::random_repo::RandomRepo::create("oracle_random", &plan).unwrap();
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(assert_random_repo_matches_oracle(&repo.path, picks, page_size));
+        }
+    }
 }
@@ -4,18 +4,23 @@
 //! to ensure queries meet performance budgets.
 
 use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::time::Instant;
 
-mod benches;
+mod budgets;
 mod datasets;
+mod history;
+mod profile;
 mod regression;
+mod report;
 mod scenarios;
 
 use datasets::{DatasetManifest, DatasetResolver};
 use regression::{default_baseline_name, load_baseline, save_baseline, RegressionAnalysis};
+use report::{Report, ReportFormat};
 use scenarios::{
-    generate_scenarios, BenchmarkResult, BenchmarkRun, DatasetInfo, SentinelResult, TimingInfo,
+    generate_scenarios, BenchmarkResult, BenchmarkRun, CacheMode, ConcurrencyReport, DatasetInfo,
+    MatrixReport, SentinelResult,
 };
 
 #[derive(Parser)]
@@ -39,7 +44,8 @@ struct Cli {
 enum Commands {
     /// Run benchmarks against datasets
     Run {
-        /// Dataset to use (default: git)
+        /// Dataset to use (default: git), or "all" to run every scenario on
+        /// every dataset
         #[arg(long, default_value = "git")]
         dataset: String,
 
@@ -51,9 +57,218 @@ enum Commands {
         #[arg(long)]
         scenarios: Option<Vec<String>>,
 
-        /// Budget in milliseconds for warm average timing
+        /// Budget in milliseconds that 99% of warm runs must stay within
         #[arg(long)]
         budget_ms: Option<f64>,
+
+        /// TOML file of cold, warm and p99 budgets for each scenario on
+        /// each dataset
+        #[arg(long, value_name = "FILE")]
+        budgets: Option<PathBuf>,
+
+        /// Cache state runs start from: warm (one engine for every run),
+        /// cold (a fresh engine for every run) or both, reporting how much
+        /// faster warm runs are
+        #[arg(long, value_enum, default_value_t = CacheMode::Warm)]
+        cache: CacheMode,
+
+        /// Drop the OS page cache before every cold run (needs root on Linux)
+        #[arg(long)]
+        drop_page_cache: bool,
+
+        /// Untimed warm runs before timing starts
+        #[arg(long, default_value_t = 0)]
+        warmup: usize,
+
+        /// Runs to time of each scenario (default: 200 warm, 20 cold)
+        #[arg(long)]
+        iterations: Option<usize>,
+
+        /// Percentage of the fastest and of the slowest timed runs to drop
+        /// as outliers
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0,
+              value_parser = parse_trim_percent)]
+        trim: f64,
+
+        /// Append the results to a history store, by default
+        /// target/rl_bench/history.jsonl
+        #[arg(long, value_name = "PATH", num_args = 0..=1,
+              default_missing_value = history::DEFAULT_HISTORY_PATH)]
+        record: Option<PathBuf>,
+
+        /// Write a flamegraph of each scenario as <dataset>-<scenario>.svg
+        /// in this directory, by default target/rl_bench/flamegraphs
+        #[cfg(feature = "profile")]
+        #[arg(long, value_name = "DIR", num_args = 0..=1,
+              default_missing_value = profile::DEFAULT_PROFILE_DIR)]
+        profile: Option<PathBuf>,
+
+        /// Print a report in this format instead of the JSON, which still
+        /// goes to --output
+        #[arg(long, value_enum)]
+        report: Option<ReportFormat>,
+    },
+
+    /// Show how timings moved across the runs recorded with `run --record`
+    History {
+        /// History store to read
+        #[arg(long, default_value = history::DEFAULT_HISTORY_PATH)]
+        store: PathBuf,
+
+        /// Number of most recent runs to show
+        #[arg(long, default_value_t = 10)]
+        last: usize,
+
+        /// Include runs from other machines, whose timings aren't comparable
+        #[arg(long)]
+        all_machines: bool,
+    },
+
+    /// Measure throughput and latency with increasing numbers of clients
+    /// sending a mix of status, log and diff requests at once
+    Concurrency {
+        /// Dataset to use
+        #[arg(long, default_value = "git")]
+        dataset: String,
+
+        /// Numbers of concurrent clients to measure
+        #[arg(long, value_delimiter = ',', default_value = "1,2,4,8,16")]
+        levels: Vec<usize>,
+
+        /// Requests to send at each level
+        #[arg(long, default_value_t = 400)]
+        requests: usize,
+
+        /// Output file for results (JSON)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Time scenarios through a `repo-lens serve` process as well as in
+    /// process, reporting what serialization and transport add
+    Ipc {
+        /// Dataset to use
+        #[arg(long, default_value = "git")]
+        dataset: String,
+
+        /// Scenarios to run (default: all)
+        #[arg(long)]
+        scenarios: Option<Vec<String>>,
+
+        /// Warm runs to time of each scenario, each way
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+
+        /// `repo-lens` binary to serve requests (default: the one built
+        /// alongside this binary, else `repo-lens` on PATH)
+        #[arg(long, value_name = "PATH")]
+        server: Option<PathBuf>,
+
+        /// Output file for results (JSON)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Repeat the same log and diff requests with caching enabled and fail
+    /// unless enough of them are served from the cache, quickly
+    Cache {
+        /// Dataset to use
+        #[arg(long, default_value = "git")]
+        dataset: String,
+
+        /// Repeats of each request to count cache hits over, and warm runs
+        /// to time
+        #[arg(long, default_value_t = 50)]
+        iterations: usize,
+
+        /// Lowest share of repeats that must be cache hits
+        #[arg(long, default_value_t = 0.9)]
+        min_hit_rate: f64,
+
+        /// Budget for the median warm run in milliseconds, for scenarios the
+        /// budgets file gives no `warm_ms`
+        #[arg(long, default_value_t = 5.0)]
+        max_warm_ms: f64,
+
+        /// Per-scenario budgets file (TOML), as for `run`
+        #[arg(long, value_name = "PATH")]
+        budgets: Option<PathBuf>,
+
+        /// Output file for results (JSON)
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Print a report in this format instead of the JSON results
+        #[arg(long, value_enum)]
+        report: Option<ReportFormat>,
+    },
+
+    /// Time scenarios against each git backend on the same dataset and
+    /// print a table comparing them
+    Backends {
+        /// Dataset to use
+        #[arg(long, default_value = "git")]
+        dataset: String,
+
+        /// Scenarios to run (default: all)
+        #[arg(long)]
+        scenarios: Option<Vec<String>>,
+
+        /// Backends to compare (default: every one that can serve requests)
+        #[arg(long, value_delimiter = ',')]
+        backends: Option<Vec<rl_core::config::BackendKind>>,
+
+        /// Warm runs to time of each scenario on each backend
+        #[arg(long, default_value_t = 100)]
+        iterations: usize,
+
+        /// Output file for results (JSON)
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Format of the comparison table
+        #[arg(long, value_enum, default_value = "markdown")]
+        report: ReportFormat,
+    },
+
+    /// Run worst-case scenarios against generated repositories: a commit
+    /// changing 50,000 files, a 100MB diff, blame of a million-line file and
+    /// a history 100,000 commits deep
+    Pathological {
+        /// Scenarios to run (default: all)
+        #[arg(long)]
+        scenarios: Option<Vec<String>>,
+
+        /// Size of the generated repositories relative to their full size
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+
+        /// Warm runs to time of each scenario, and cold ones with `--cache`
+        #[arg(long, default_value_t = 5)]
+        iterations: usize,
+
+        /// Untimed warm runs before timing starts
+        #[arg(long, default_value_t = 0)]
+        warmup: usize,
+
+        /// Percentage of the fastest and of the slowest timed runs to drop
+        /// as outliers
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0,
+              value_parser = parse_trim_percent)]
+        trim: f64,
+
+        /// TOML file of budgets, with the scenarios under `[pathological]`
+        #[arg(long, value_name = "FILE")]
+        budgets: Option<PathBuf>,
+
+        /// Output file for results (JSON)
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Print a report in this format instead of the JSON, which still
+        /// goes to --output
+        #[arg(long, value_enum)]
+        report: Option<ReportFormat>,
     },
 
     /// Baseline operations
@@ -69,10 +284,27 @@ enum Commands {
 
         /// Path to current results JSON file
         current: PathBuf,
+
+        /// Print a report in this format instead of the JSON
+        #[arg(long, value_enum)]
+        report: Option<ReportFormat>,
     },
 
     /// List available datasets
     ListDatasets,
+
+    /// Clone datasets into the cache, or update them to their pinned
+    /// revision
+    Fetch {
+        /// Dataset to fetch, or "all"
+        #[arg(long, default_value = "all")]
+        dataset: String,
+
+        /// Fetch complete history even if the manifest asks for a shallow or
+        /// partial clone, as scenarios such as blame need
+        #[arg(long)]
+        full: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -88,6 +320,10 @@ enum BaselineCommands {
     Compare {
         /// Path to baseline JSON file
         baseline: PathBuf,
+
+        /// Print a report in this format instead of the JSON
+        #[arg(long, value_enum)]
+        report: Option<ReportFormat>,
     },
 }
 
@@ -95,7 +331,7 @@ enum BaselineCommands {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    rl_core::telemetry::init_telemetry(cli.log.as_deref(), cli.log_json);
+    rl_core::telemetry::init_telemetry(cli.log.as_deref(), cli.log_json, None, None)?;
 
     match cli.command {
         Commands::Run {
@@ -103,22 +339,141 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             output,
             scenarios,
             budget_ms,
+            budgets,
+            cache,
+            drop_page_cache,
+            warmup,
+            iterations,
+            trim,
+            record,
+            #[cfg(feature = "profile")]
+            profile,
+            report,
+        } => {
+            let options = RunOptions {
+                budgets: budgets
+                    .as_deref()
+                    .map(budgets::load)
+                    .transpose()?
+                    .unwrap_or_default(),
+                cache,
+                drop_page_cache,
+                warmup,
+                iterations,
+                trim_percent: trim,
+                #[cfg(feature = "profile")]
+                profile,
+                #[cfg(not(feature = "profile"))]
+                profile: None,
+            };
+            run_benchmarks(
+                &dataset, output, scenarios, budget_ms, &options, record, report,
+            )
+            .await?;
+        }
+        Commands::History {
+            store,
+            last,
+            all_machines,
         } => {
-            run_benchmarks(&dataset, output, scenarios, budget_ms).await?;
+            show_history(&store, last, all_machines)?;
+        }
+        Commands::Concurrency {
+            dataset,
+            levels,
+            requests,
+            output,
+        } => {
+            run_concurrency(&dataset, &levels, requests, output).await?;
+        }
+        Commands::Ipc {
+            dataset,
+            scenarios,
+            iterations,
+            server,
+            output,
+        } => {
+            let server = server.unwrap_or_else(scenarios::ipc::default_server_bin);
+            run_ipc(&dataset, scenarios, iterations, &server, output).await?;
+        }
+        Commands::Cache {
+            dataset,
+            iterations,
+            min_hit_rate,
+            max_warm_ms,
+            budgets,
+            output,
+            report,
+        } => {
+            let options = RunOptions {
+                budgets: budgets
+                    .as_deref()
+                    .map(budgets::load)
+                    .transpose()?
+                    .unwrap_or_default(),
+                iterations: Some(iterations),
+                ..RunOptions::default()
+            };
+            let expected = CacheExpectation {
+                min_hit_rate,
+                max_warm_ms,
+            };
+            run_cache(&dataset, options, &expected, output, report).await?;
+        }
+        Commands::Backends {
+            dataset,
+            scenarios,
+            backends,
+            iterations,
+            output,
+            report,
+        } => {
+            let backends = backends.unwrap_or_else(scenarios::backends::default_backends);
+            run_backends(&dataset, scenarios, &backends, iterations, output, report).await?;
+        }
+        Commands::Pathological {
+            scenarios,
+            scale,
+            iterations,
+            warmup,
+            trim,
+            budgets,
+            output,
+            report,
+        } => {
+            let options = RunOptions {
+                budgets: budgets
+                    .as_deref()
+                    .map(budgets::load)
+                    .transpose()?
+                    .unwrap_or_default(),
+                warmup,
+                iterations: Some(iterations),
+                trim_percent: trim,
+                ..RunOptions::default()
+            };
+            run_pathological(scenarios, scale, &options, output, report).await?;
         }
         Commands::Baseline { command } => match command {
             BaselineCommands::Save { output } => {
                 run_and_save_baseline(output).await?;
             }
-            BaselineCommands::Compare { baseline } => {
-                compare_against_baseline(&baseline).await?;
+            BaselineCommands::Compare { baseline, report } => {
+                compare_against_baseline(&baseline, report).await?;
             }
         },
         Commands::ListDatasets => {
             list_datasets()?;
         }
-        Commands::Compare { baseline, current } => {
-            compare_baselines(&baseline, &current)?;
+        Commands::Fetch { dataset, full } => {
+            fetch_datasets(&dataset, full)?;
+        }
+        Commands::Compare {
+            baseline,
+            current,
+            report,
+        } => {
+            compare_baselines(&baseline, &current, report)?;
         }
     }
 
@@ -130,7 +485,22 @@ async fn run_benchmarks(
     output_path: Option<PathBuf>,
     scenario_filter: Option<Vec<String>>,
     budget_ms: Option<f64>,
+    options: &RunOptions,
+    record: Option<PathBuf>,
+    report: Option<ReportFormat>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if dataset_name == "all" {
+        return run_matrix(
+            output_path,
+            scenario_filter,
+            budget_ms,
+            options,
+            record,
+            report,
+        )
+        .await;
+    }
+
     // Load dataset manifest and find requested dataset
     let manifest = DatasetManifest::load()?;
     let dataset = manifest
@@ -177,6 +547,7 @@ async fn run_benchmarks(
             &dataset_path,
             dataset_exists,
             budget_ms,
+            options,
         )
         .await?;
         results.push(result);
@@ -184,19 +555,19 @@ async fn run_benchmarks(
 
     // Check for failures before consuming results
     let has_failure = results.iter().any(|r| r.status == "fail");
+    if let Some(store) = record {
+        record_run(&store, results.clone())?;
+    }
+    let report = report.map(|format| {
+        let scenarios = results.iter().map(|r| (r.scenario.clone(), r.clone()));
+        let results = BTreeMap::from([(dataset.name.clone(), scenarios.collect())]);
+        Report::from_results(REPORT_TITLE, &results).render(format)
+    });
 
     // For single scenario (sentinel), output the result directly
     if results.len() == 1 {
         let json_output = serde_json::to_string_pretty(&results[0])?;
-        match output_path {
-            Some(path) => {
-                std::fs::write(&path, &json_output)?;
-                eprintln!("Results saved to {}", path.display());
-            }
-            None => {
-                println!("{}", json_output);
-            }
-        }
+        write_output(&json_output, output_path, report)?;
     } else {
         // Fallback to old format for multiple scenarios
         let run = BenchmarkRun {
@@ -209,20 +580,18 @@ async fn run_benchmarks(
                     wall_time_ns: (sr.timings.cold_ms * 1_000_000.0) as u64, // Convert to ns
                     success: sr.status == "pass",
                     error: None,
+                    samples_ns: sr
+                        .timings
+                        .samples_ms
+                        .iter()
+                        .map(|ms| (ms * 1_000_000.0) as u64)
+                        .collect(),
                 })
                 .collect(),
         };
 
         let json_output = serde_json::to_string_pretty(&run)?;
-        match output_path {
-            Some(path) => {
-                std::fs::write(&path, &json_output)?;
-                eprintln!("Results saved to {}", path.display());
-            }
-            None => {
-                println!("{}", json_output);
-            }
-        }
+        write_output(&json_output, output_path, report)?;
     }
 
     // Exit with error code if any scenario failed
@@ -233,6 +602,536 @@ async fn run_benchmarks(
     Ok(())
 }
 
+/// Run every scenario, or those in `scenario_filter`, on every dataset in
+/// the manifest, reporting the results by dataset and scenario.
+async fn run_matrix(
+    output_path: Option<PathBuf>,
+    scenario_filter: Option<Vec<String>>,
+    budget_ms: Option<f64>,
+    options: &RunOptions,
+    record: Option<PathBuf>,
+    report: Option<ReportFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = DatasetManifest::load()?;
+    let resolver = DatasetResolver::new()?;
+    let results = matrix_results(
+        &manifest,
+        &resolver,
+        scenario_filter.as_deref(),
+        budget_ms,
+        options,
+    )
+    .await?;
+
+    let has_failure = results
+        .values()
+        .flat_map(BTreeMap::values)
+        .any(|r| r.status == "fail");
+    if let Some(store) = record {
+        let flattened = results.values().flat_map(BTreeMap::values).cloned();
+        record_run(&store, flattened.collect())?;
+    }
+    let rendered = report.map(|format| Report::from_results(REPORT_TITLE, &results).render(format));
+    let report = MatrixReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        results,
+    };
+    let json_output = serde_json::to_string_pretty(&report)?;
+    write_output(&json_output, output_path, rendered)?;
+
+    if has_failure {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Results of every scenario, or those in `scenario_filter`, on every
+/// dataset in `manifest`, found in `resolver`'s cache, by dataset and
+/// scenario.
+async fn matrix_results(
+    manifest: &DatasetManifest,
+    resolver: &DatasetResolver,
+    scenario_filter: Option<&[String]>,
+    budget_ms: Option<f64>,
+    options: &RunOptions,
+) -> Result<BTreeMap<String, BTreeMap<String, SentinelResult>>, Box<dyn std::error::Error>> {
+    let engine = rl_core::RepoEngine::new();
+    let mut results = BTreeMap::<String, BTreeMap<_, _>>::new();
+    for dataset in &manifest.datasets {
+        let dataset_path = resolver.cache_dir().join(&dataset.name);
+        let dataset_exists = dataset_path.exists();
+        let scenarios = generate_scenarios(&dataset_path)
+            .into_iter()
+            .filter(|s| scenario_filter.is_none_or(|f| f.contains(&s.name)));
+        for scenario in scenarios {
+            eprintln!("Running scenario: {} on {}", scenario.name, dataset.name);
+            let result = run_sentinel_scenario(
+                &engine,
+                &scenario,
+                dataset,
+                &dataset_path,
+                dataset_exists,
+                budget_ms,
+                options,
+            )
+            .await?;
+            results
+                .entry(dataset.name.clone())
+                .or_default()
+                .insert(scenario.name, result);
+        }
+    }
+    if results.is_empty() {
+        return Err("No scenarios to run".into());
+    }
+    Ok(results)
+}
+
+/// Parse `--trim`, a percentage of runs to drop from each end.
+fn parse_trim_percent(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(percent) if (0.0..50.0).contains(&percent) => Ok(percent),
+        Ok(_) => Err("must be at least 0 and below 50".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Title of the reports of `run`
+const REPORT_TITLE: &str = "repo-lens benchmarks";
+
+/// Title of the reports of baseline comparisons
+const REGRESSION_TITLE: &str = "repo-lens performance regressions";
+
+/// Title of the reports of `backends`
+const BACKENDS_TITLE: &str = "repo-lens backends";
+
+/// Save `json_output` to `output_path`, or print it. With a `report`, the
+/// report is printed instead, leaving the JSON only in `output_path`.
+fn write_output(
+    json_output: &str,
+    output_path: Option<PathBuf>,
+    report: Option<String>,
+) -> std::io::Result<()> {
+    if let Some(path) = output_path {
+        std::fs::write(&path, json_output)?;
+        eprintln!("Results saved to {}", path.display());
+    } else if report.is_none() {
+        println!("{}", json_output);
+    }
+    if let Some(report) = report {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/// Append a run's `results` to the history store at `store`.
+fn record_run(
+    store: &std::path::Path,
+    results: Vec<SentinelResult>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    history::append(store, &history::HistoryEntry::new(results))?;
+    eprintln!("Run recorded in {}", store.display());
+    Ok(())
+}
+
+/// Print the trend of every scenario over the last `last` runs in `store`,
+/// by default only those from this machine.
+fn show_history(
+    store: &std::path::Path,
+    last: usize,
+    all_machines: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let machine = history::machine_fingerprint();
+    let entries: Vec<_> = history::load(store)?
+        .into_iter()
+        .filter(|entry| all_machines || entry.machine == machine)
+        .collect();
+
+    let report = history::HistoryReport {
+        machine: (!all_machines).then_some(machine),
+        runs: entries.len().min(last),
+        trends: history::trends(&entries, last),
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+/// Scenarios whose requests the concurrency benchmark mixes.
+const CONCURRENCY_MIX: [&str; 3] = ["status", "log_page", "diff_summary"];
+
+/// Send the [`CONCURRENCY_MIX`] at one engine from each number of clients
+/// in `levels`, reporting how throughput and latency scale.
+async fn run_concurrency(
+    dataset_name: &str,
+    levels: &[usize],
+    requests: usize,
+    output_path: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = DatasetManifest::load()?;
+    let dataset = manifest
+        .find_by_name(dataset_name)
+        .ok_or_else(|| format!("Dataset '{}' not found", dataset_name))?;
+    let resolver = DatasetResolver::new()?;
+    let dataset_path = resolver.cache_dir().join(&dataset.name);
+
+    let mix: Vec<_> = generate_scenarios(&dataset_path)
+        .into_iter()
+        .filter(|s| CONCURRENCY_MIX.contains(&s.name.as_str()))
+        .collect();
+    let engine = std::sync::Arc::new(rl_core::RepoEngine::new());
+
+    // Warm up first, so the first level doesn't pay for opening the repo
+    for scenario in &mix {
+        engine.handle(scenario.request.clone()).await;
+    }
+
+    let mut results: Vec<scenarios::ConcurrencyLevel> = Vec::new();
+    for &concurrency in levels {
+        eprintln!("Running {} requests from {} clients", requests, concurrency);
+        let mut level = scenarios::time_concurrency(&engine, &mix, concurrency, requests).await?;
+        if let Some(single) = results.iter().find(|level| level.concurrency == 1) {
+            level.speedup = level.throughput_rps / single.throughput_rps;
+        }
+        results.push(level);
+    }
+
+    let report = ConcurrencyReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        dataset: DatasetInfo {
+            name: dataset.name.clone(),
+            url: dataset.url.clone(),
+            rev: dataset.revision.clone(),
+            path: dataset_path.to_string_lossy().to_string(),
+            exists: dataset_path.exists(),
+        },
+        scenarios: mix.into_iter().map(|s| s.name).collect(),
+        levels: results,
+    };
+    let json_output = serde_json::to_string_pretty(&report)?;
+    match output_path {
+        Some(path) => {
+            std::fs::write(&path, &json_output)?;
+            eprintln!("Results saved to {}", path.display());
+        }
+        None => {
+            println!("{}", json_output);
+        }
+    }
+
+    Ok(())
+}
+
+/// Time every scenario, or those in `scenario_filter`, through a `server
+/// serve` process and against an engine in this process.
+async fn run_ipc(
+    dataset_name: &str,
+    scenario_filter: Option<Vec<String>>,
+    iterations: usize,
+    server: &std::path::Path,
+    output_path: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use scenarios::ipc::{time_ipc_scenario, IpcReport, IpcScenarioResult, ServeProcess};
+
+    let manifest = DatasetManifest::load()?;
+    let dataset = manifest
+        .find_by_name(dataset_name)
+        .ok_or_else(|| format!("Dataset '{}' not found", dataset_name))?;
+    let resolver = DatasetResolver::new()?;
+    let dataset_path = resolver.cache_dir().join(&dataset.name);
+
+    let scenarios_to_run = generate_scenarios(&dataset_path).into_iter().filter(|s| {
+        scenario_filter
+            .as_ref()
+            .is_none_or(|f| f.iter().any(|n| n == &s.name))
+    });
+    let serve = ServeProcess::spawn(server).await?;
+    let engine = rl_core::RepoEngine::new();
+    let mut results = Vec::new();
+    for scenario in scenarios_to_run {
+        eprintln!("Running scenario: {}", scenario.name);
+        let ipc = time_ipc_scenario(&serve.client, &scenario, iterations).await?;
+        let sampling = scenarios::Sampling::new(iterations);
+        let in_process = scenarios::time_scenario(&engine, &scenario, &sampling).await?;
+        results.push(IpcScenarioResult::new(&scenario.name, ipc, in_process));
+    }
+
+    let report = IpcReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        dataset: DatasetInfo {
+            name: dataset.name.clone(),
+            url: dataset.url.clone(),
+            rev: dataset.revision.clone(),
+            path: dataset_path.to_string_lossy().to_string(),
+            exists: dataset_path.exists(),
+        },
+        server: server.to_string_lossy().to_string(),
+        results,
+    };
+    let json_output = serde_json::to_string_pretty(&report)?;
+    match output_path {
+        Some(path) => {
+            std::fs::write(&path, &json_output)?;
+            eprintln!("Results saved to {}", path.display());
+        }
+        None => {
+            println!("{}", json_output);
+        }
+    }
+
+    Ok(())
+}
+
+/// Scenarios whose repeats `cache` expects to be served from the cache
+const CACHE_SCENARIOS: &[&str] = &["log_page", "diff_summary"];
+
+/// What `cache` holds its scenarios to
+struct CacheExpectation {
+    /// Lowest share of repeated requests that must be cache hits
+    min_hit_rate: f64,
+    /// Warm budget for scenarios the budgets file leaves without one
+    max_warm_ms: f64,
+}
+
+/// Check that repeats of the log and diff scenarios are served from the
+/// cache: each fails if too few repeats are hits, or if its warm runs go
+/// over budget.
+async fn run_cache(
+    dataset_name: &str,
+    mut options: RunOptions,
+    expected: &CacheExpectation,
+    output_path: Option<PathBuf>,
+    report: Option<ReportFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = DatasetManifest::load()?;
+    let dataset = manifest
+        .find_by_name(dataset_name)
+        .ok_or_else(|| format!("Dataset '{}' not found", dataset_name))?;
+    let resolver = DatasetResolver::new()?;
+    let dataset_path = resolver.cache_dir().join(&dataset.name);
+    let dataset_exists = dataset_path.exists();
+
+    let config = rl_core::EngineConfig {
+        cache_enabled: true,
+        ..rl_core::EngineConfig::default()
+    };
+    let iterations = options.iterations.unwrap_or_default();
+    let mut results = BTreeMap::new();
+    for scenario in generate_scenarios(&dataset_path)
+        .into_iter()
+        .filter(|s| CACHE_SCENARIOS.contains(&s.name.as_str()))
+    {
+        eprintln!("Running scenario: {}", scenario.name);
+        let budget = options
+            .budgets
+            .entry(dataset.name.clone())
+            .or_default()
+            .entry(scenario.name.clone())
+            .or_default();
+        budget.warm_ms = budget.warm_ms.or(Some(expected.max_warm_ms));
+
+        // A fresh engine, so that the first request is the only miss
+        let engine = rl_core::RepoEngine::with_config(config.clone());
+        let stats =
+            scenarios::cache_stats(&engine, &scenario, iterations, expected.min_hit_rate).await;
+        let mut result = run_sentinel_scenario(
+            &engine,
+            &scenario,
+            dataset,
+            &dataset_path,
+            dataset_exists,
+            None,
+            &options,
+        )
+        .await?;
+        if stats.is_below_minimum() {
+            result.status = "fail".to_string();
+            // Too few hits explains a slow warm run as well
+            result.reason = Some("cache_hit_rate".to_string());
+        }
+        result.cache_stats = Some(stats);
+        results.insert(scenario.name, result);
+    }
+
+    let has_failure = results.values().any(|r| r.status == "fail");
+    let results = BTreeMap::from([(dataset.name.clone(), results)]);
+    let rendered = report.map(|format| {
+        Report::from_results("repo-lens cache effectiveness", &results).render(format)
+    });
+    let report = MatrixReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        results,
+    };
+    write_output(
+        &serde_json::to_string_pretty(&report)?,
+        output_path,
+        rendered,
+    )?;
+
+    if has_failure {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Time every scenario, or those in `scenario_filter`, on each of
+/// `backends`, printing a table comparing them.
+async fn run_backends(
+    dataset_name: &str,
+    scenario_filter: Option<Vec<String>>,
+    backends: &[rl_core::config::BackendKind],
+    iterations: usize,
+    output_path: Option<PathBuf>,
+    report: ReportFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use scenarios::backends::{time_backend_scenario, BackendReport, BackendScenarioResult};
+
+    let manifest = DatasetManifest::load()?;
+    let dataset = manifest
+        .find_by_name(dataset_name)
+        .ok_or_else(|| format!("Dataset '{}' not found", dataset_name))?;
+    let resolver = DatasetResolver::new()?;
+    let dataset_path = resolver.cache_dir().join(&dataset.name);
+
+    let scenarios_to_run = generate_scenarios(&dataset_path).into_iter().filter(|s| {
+        scenario_filter
+            .as_ref()
+            .is_none_or(|f| f.iter().any(|n| n == &s.name))
+    });
+    let sampling = scenarios::Sampling::new(iterations);
+    let mut results = Vec::new();
+    for scenario in scenarios_to_run {
+        let mut timings = Vec::with_capacity(backends.len());
+        for &backend in backends {
+            eprintln!("Running scenario: {} ({})", scenario.name, backend.name());
+            timings.push(time_backend_scenario(backend, &scenario, &sampling).await?);
+        }
+        results.push(BackendScenarioResult::new(&scenario.name, timings));
+    }
+
+    let comparison = BackendReport::new(
+        DatasetInfo {
+            name: dataset.name.clone(),
+            url: dataset.url.clone(),
+            rev: dataset.revision.clone(),
+            path: dataset_path.to_string_lossy().to_string(),
+            exists: dataset_path.exists(),
+        },
+        backends,
+        results,
+    );
+    let rendered = Report::from_backends(BACKENDS_TITLE, &comparison.results).render(report);
+    write_output(
+        &serde_json::to_string_pretty(&comparison)?,
+        output_path,
+        Some(rendered),
+    )?;
+    Ok(())
+}
+
+/// Run the worst-case scenarios, or those in `scenario_filter`, against
+/// repositories `scale` times their full size, generating them first if
+/// needed. Besides its budgets, a scenario fails if its request fails or
+/// isn't cut short by the bound it should be.
+async fn run_pathological(
+    scenario_filter: Option<Vec<String>>,
+    scale: f64,
+    options: &RunOptions,
+    output_path: Option<PathBuf>,
+    report: Option<ReportFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use scenarios::pathological::{pathological_scenarios, DATASET_NAME};
+
+    let engine = rl_core::RepoEngine::new();
+    let mut results = BTreeMap::new();
+    let pathological = pathological_scenarios(scale).into_iter().filter(|s| {
+        scenario_filter
+            .as_ref()
+            .is_none_or(|f| f.iter().any(|n| n == s.name))
+    });
+    for pathological in pathological {
+        eprintln!("Preparing scenario: {}", pathological.name);
+        let (repo, scenario) = pathological.prepare()?;
+        let dataset = datasets::Dataset {
+            name: DATASET_NAME.to_string(),
+            description: pathological.description.to_string(),
+            url: format!("rl_fixtures/pathological/{}", pathological.pathology.name()),
+            revision: "HEAD".to_string(),
+            size_category: DATASET_NAME.to_string(),
+            budgets_ms: BTreeMap::new(),
+            clone_depth: None,
+            clone_filter: None,
+            synthetic: None,
+        };
+
+        eprintln!("Running scenario: {}", scenario.name);
+        let mut result = run_sentinel_scenario(
+            &engine, &scenario, &dataset, &repo.path, true, None, options,
+        )
+        .await?;
+        let response = scenarios::response_shape(&engine, &scenario).await?;
+        let truncated_by = response.truncated.map(|truncation| truncation.bound);
+        let reason = if response.error.is_some() {
+            Some("error")
+        } else if pathological.expect_truncated.is_some()
+            && truncated_by != pathological.expect_truncated
+        {
+            Some("bound_not_enforced")
+        } else {
+            None
+        };
+        if let (Some(reason), "pass") = (reason, result.status.as_str()) {
+            result.status = "fail".to_string();
+            result.reason = Some(reason.to_string());
+        }
+        result.response = Some(response);
+        results.insert(scenario.name, result);
+    }
+    if results.is_empty() {
+        return Err("No scenarios to run".into());
+    }
+
+    let has_failure = results.values().any(|r| r.status == "fail");
+    let results = BTreeMap::from([(DATASET_NAME.to_string(), results)]);
+    let rendered = report.map(|format| {
+        Report::from_results("repo-lens pathological benchmarks", &results).render(format)
+    });
+    let report = MatrixReport {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        results,
+    };
+    let json_output = serde_json::to_string_pretty(&report)?;
+    write_output(&json_output, output_path, rendered)?;
+
+    if has_failure {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// How `run` times scenarios and holds them to budgets, from `--budgets`,
+/// `--cache`, `--drop-page-cache`, `--warmup`, `--iterations`, `--trim`
+/// and `--profile`.
+#[derive(Debug, Clone, Default)]
+struct RunOptions {
+    budgets: budgets::Budgets,
+    cache: CacheMode,
+    drop_page_cache: bool,
+    /// Untimed warm runs before timing starts
+    warmup: usize,
+    /// Runs to time of each kind, instead of the usual number
+    iterations: Option<usize>,
+    /// Percentage of the fastest and of the slowest runs to drop
+    trim_percent: f64,
+    /// Directory to write a flamegraph of each scenario to
+    profile: Option<PathBuf>,
+}
+
+/// Time `scenario`, held to its budgets in `options`; `budget_ms`, if
+/// given, overrides its p99 budget, and the dataset's budget for it fills
+/// in a missing one.
 async fn run_sentinel_scenario(
     engine: &rl_core::RepoEngine,
     scenario: &scenarios::BenchmarkScenario,
@@ -240,37 +1139,66 @@ async fn run_sentinel_scenario(
     dataset_path: &std::path::Path,
     dataset_exists: bool,
     budget_ms: Option<f64>,
+    options: &RunOptions,
 ) -> Result<SentinelResult, Box<dyn std::error::Error>> {
     const WARM_ITERATIONS: usize = 200;
+    // Every cold run opens the repository from scratch
+    const COLD_ITERATIONS: usize = 20;
+    let sampling = |default_iterations| scenarios::Sampling {
+        warmup: options.warmup,
+        iterations: options.iterations.unwrap_or(default_iterations),
+        trim_percent: options.trim_percent,
+    };
 
-    // Cold run (first execution)
-    let start = Instant::now();
-    let response = engine.handle(scenario.request.clone()).await;
-    let cold_time_ms = start.elapsed().as_nanos() as f64 / 1_000_000.0;
-
-    // Ensure response is used to prevent optimization
-    let _serialized = serde_json::to_string(&response)?;
-
-    // Warm runs - time the entire loop as one block
-    let warm_start = Instant::now();
-    for _ in 0..WARM_ITERATIONS {
-        let response = engine.handle(scenario.request.clone()).await;
-        // Ensure response is used to prevent optimization
-        let _serialized = serde_json::to_string(&response)?;
+    let mut budget = options
+        .budgets
+        .get(&dataset.name)
+        .and_then(|scenarios| scenarios.get(&scenario.name))
+        .copied()
+        .unwrap_or_default();
+    budget.p99_ms = budget_ms
+        .or(budget.p99_ms)
+        .or_else(|| dataset.budgets_ms.get(&scenario.name).copied());
+    let flamegraph = options
+        .profile
+        .as_ref()
+        .map(|dir| dir.join(format!("{}-{}.svg", dataset.name, scenario.name)));
+    let (warm_timings, cold_timings) = profile::profiled(flamegraph.as_deref(), async {
+        let warm_timings = match options.cache {
+            CacheMode::Cold => None,
+            CacheMode::Warm | CacheMode::Both => {
+                let sampling = sampling(WARM_ITERATIONS);
+                Some(scenarios::time_scenario(engine, scenario, &sampling).await?)
+            }
+        };
+        let cold_timings = match options.cache {
+            CacheMode::Warm => None,
+            CacheMode::Cold | CacheMode::Both => {
+                let sampling = sampling(COLD_ITERATIONS);
+                Some(
+                    scenarios::time_cold_scenario(scenario, &sampling, options.drop_page_cache)
+                        .await?,
+                )
+            }
+        };
+        Ok::<_, std::io::Error>((warm_timings, cold_timings))
+    })
+    .await?;
+    if let Some(flamegraph) = &flamegraph {
+        eprintln!("Flamegraph written to {}", flamegraph.display());
     }
-    let warm_total_ms = warm_start.elapsed().as_nanos() as f64 / 1_000_000.0;
-    let warm_avg_ms = warm_total_ms / WARM_ITERATIONS as f64;
-
-    // Determine status and reason
-    let (status, reason) = if let Some(budget) = budget_ms {
-        if warm_avg_ms > budget {
-            ("fail".to_string(), Some("budget_exceeded".to_string()))
-        } else {
-            ("pass".to_string(), None)
-        }
-    } else {
-        // Sentinel benchmarks always pass unless there's a hard error
-        ("pass".to_string(), None)
+    let warm_speedup = warm_timings
+        .as_ref()
+        .zip(cold_timings.as_ref())
+        .map(|(warm, cold)| cold.p50_ms / warm.p50_ms);
+    let (timings, cold_timings) = match warm_timings {
+        Some(warm) => (warm, cold_timings),
+        None => (cold_timings.ok_or("No runs timed")?, None),
+    };
+    let exceeded = budget.exceeded(options.cache, &timings, cold_timings.as_ref());
+    let (status, reason) = match exceeded.is_empty() {
+        true => ("pass".to_string(), None),
+        false => ("fail".to_string(), Some("budget_exceeded".to_string())),
     };
 
     let result = SentinelResult {
@@ -282,12 +1210,17 @@ async fn run_sentinel_scenario(
             exists: dataset_exists,
         },
         scenario: scenario.name.clone(),
-        timings: TimingInfo {
-            cold_ms: cold_time_ms,
-            warm_total_ms,
-            warm_avg_ms,
-            iterations: WARM_ITERATIONS,
-        },
+        cache: options.cache,
+        timings,
+        cold_timings,
+        warm_speedup,
+        page_cache_dropped: options.drop_page_cache && options.cache != CacheMode::Warm,
+        budget_ms: budget.p99_ms,
+        cold_budget_ms: budget.cold_ms,
+        warm_budget_ms: budget.warm_ms,
+        exceeded,
+        response: None,
+        cache_stats: None,
         status,
         reason,
     };
@@ -298,15 +1231,21 @@ async fn run_sentinel_scenario(
 fn compare_baselines(
     baseline_path: &std::path::Path,
     current_path: &std::path::Path,
+    report: Option<ReportFormat>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let baseline_results = load_baseline(baseline_path)?;
     let current_results = load_baseline(current_path)?;
 
     let analysis = RegressionAnalysis::analyze(&baseline_results, &current_results);
 
-    // Output analysis as JSON
-    let json_output = serde_json::to_string_pretty(&analysis)?;
-    println!("{}", json_output);
+    // Output analysis as JSON, or as a report
+    match report {
+        Some(format) => {
+            let report = Report::from_regressions(REGRESSION_TITLE, &analysis.scenario_results);
+            print!("{}", report.render(format));
+        }
+        None => println!("{}", serde_json::to_string_pretty(&analysis)?),
+    }
 
     // Exit with error if regressions detected
     if analysis.has_regressions {
@@ -331,6 +1270,23 @@ fn list_datasets() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn fetch_datasets(dataset_name: &str, full: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = DatasetManifest::load()?;
+    let datasets: Vec<_> = match dataset_name {
+        "all" => manifest.datasets.iter().collect(),
+        name => vec![manifest
+            .find_by_name(name)
+            .ok_or_else(|| format!("Dataset '{}' not found", name))?],
+    };
+
+    let resolver = DatasetResolver::new()?;
+    for dataset in datasets {
+        let path = resolver.resolve_with(dataset, full)?;
+        eprintln!("Dataset '{}' ready at {}", dataset.name, path.display());
+    }
+    Ok(())
+}
+
 async fn run_and_save_baseline(
     output_path: Option<PathBuf>,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -338,7 +1294,16 @@ async fn run_and_save_baseline(
         output_path.unwrap_or_else(|| PathBuf::from("crates/rl_bench/baselines/local.json"));
 
     // Run benchmark and save as baseline
-    run_benchmarks("git", Some(output_path.clone()), None, None).await?;
+    run_benchmarks(
+        "git",
+        Some(output_path.clone()),
+        None,
+        None,
+        &RunOptions::default(),
+        None,
+        None,
+    )
+    .await?;
 
     eprintln!("Baseline saved to {}", output_path.display());
     Ok(())
@@ -346,6 +1311,7 @@ async fn run_and_save_baseline(
 
 async fn compare_against_baseline(
     baseline_path: &std::path::Path,
+    report: Option<ReportFormat>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Load baseline
     let baseline_content = std::fs::read_to_string(baseline_path)?;
@@ -375,15 +1341,18 @@ async fn compare_against_baseline(
         &dataset_path,
         dataset_exists,
         None,
+        &RunOptions::default(),
     )
     .await?;
 
-    // Compare results using warm_avg_ms
-    let regression_threshold = 0.20; // 20%
-    let avg_regression =
-        (current.timings.warm_avg_ms - baseline.timings.warm_avg_ms) / baseline.timings.warm_avg_ms;
-
-    let has_regression = avg_regression > regression_threshold;
+    // Compare medians, and every run when both sides kept them: the tail of
+    // a couple of hundred runs is too noisy to gate on
+    let samples = |timings: &scenarios::TimingInfo| match timings.samples_ms.is_empty() {
+        true => vec![timings.p50_ms],
+        false => timings.samples_ms.clone(),
+    };
+    let comparison = regression::compare(&samples(&baseline.timings), &samples(&current.timings));
+    let has_regression = comparison.verdict == regression::Verdict::Regression;
 
     // Create result with status and reason
     let status = if has_regression { "fail" } else { "pass" };
@@ -399,13 +1368,45 @@ async fn compare_against_baseline(
         "baseline": baseline,
         "current": current,
         "comparison": {
-            "avg_regression": avg_regression,
+            "p50_regression": comparison.relative_change,
+            "p_value": comparison.p_value,
+            "confidence": comparison.confidence(),
             "has_regression": has_regression,
-            "threshold": regression_threshold
-        }
+            "verdict": comparison.status(),
+        },
+        "steps": regression::step_changes(&baseline.timings, &current.timings),
     });
 
-    println!("{}", serde_json::to_string_pretty(&comparison_result)?);
+    match report {
+        Some(format) => {
+            // Timed by the median, as compared
+            let as_result = |result: &SentinelResult| BenchmarkResult {
+                scenario: result.scenario.clone(),
+                wall_time_ns: (result.timings.p50_ms * 1_000_000.0) as u64,
+                success: result.status == "pass",
+                error: None,
+                samples_ns: result
+                    .timings
+                    .samples_ms
+                    .iter()
+                    .map(|ms| (ms * 1_000_000.0) as u64)
+                    .collect(),
+            };
+            let regression = regression::ScenarioRegression {
+                scenario: current.scenario.clone(),
+                baseline: as_result(&baseline),
+                current: as_result(&current),
+                relative_change: comparison.relative_change,
+                p_value: comparison.p_value,
+                confidence: comparison.confidence(),
+                is_regression: has_regression,
+                status: comparison.status(),
+            };
+            let report = Report::from_regressions(REGRESSION_TITLE, &[regression]);
+            print!("{}", report.render(format));
+        }
+        None => println!("{}", serde_json::to_string_pretty(&comparison_result)?),
+    }
 
     if has_regression {
         std::process::exit(1);
@@ -427,3 +1428,103 @@ fn save_as_baseline(
     eprintln!("Baseline saved to {}", output_path.display());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_matrix_reports_every_scenario_on_every_dataset() {
+        let cache_dir =
+            std::env::temp_dir().join(format!("rl_bench_matrix_{}", std::process::id()));
+        let manifest: DatasetManifest = toml::from_str(
+            r#"
+            [[datasets]]
+            name = "small-linear"
+            description = "Generated history without merges"
+            url = "rl_fixtures/large_repo"
+            revision = "master"
+            size_category = "small"
+
+            [datasets.budgets_ms]
+            status = 60000
+
+            [datasets.synthetic]
+            commits = 12
+            files = 20
+            branchiness = 0.0
+            seed = 1
+
+            [[datasets]]
+            name = "small-merges"
+            description = "Generated history, half of it merges"
+            url = "rl_fixtures/large_repo"
+            revision = "master"
+            size_category = "small"
+
+            [datasets.synthetic]
+            commits = 12
+            files = 20
+            branchiness = 0.5
+            seed = 2
+            "#,
+        )
+        .unwrap();
+        let resolver = DatasetResolver::with_cache_dir(cache_dir.clone()).unwrap();
+        for dataset in &manifest.datasets {
+            resolver.resolve(dataset).unwrap();
+        }
+        let options = RunOptions {
+            iterations: Some(3),
+            ..RunOptions::default()
+        };
+        let scenarios = ["status".to_string(), "log_page".to_string()];
+
+        let results = matrix_results(&manifest, &resolver, Some(&scenarios), None, &options)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+
+        let cells: Vec<_> = results
+            .iter()
+            .flat_map(|(dataset, scenarios)| {
+                scenarios
+                    .iter()
+                    .map(move |(scenario, result)| (dataset.as_str(), scenario.as_str(), result))
+            })
+            .collect();
+        let names: Vec<_> = cells.iter().map(|(d, s, _)| (*d, *s)).collect();
+        assert_eq!(
+            names,
+            [
+                ("small-linear", "log_page"),
+                ("small-linear", "status"),
+                ("small-merges", "log_page"),
+                ("small-merges", "status"),
+            ]
+        );
+        for (dataset, scenario, result) in &cells {
+            assert_eq!(result.dataset.name, *dataset);
+            assert_eq!(result.scenario, *scenario);
+            assert!(result.dataset.exists);
+            assert_eq!(result.status, "pass", "{} on {}", scenario, dataset);
+        }
+        assert_eq!(results["small-linear"]["status"].budget_ms, Some(60000.0));
+        assert_eq!(results["small-merges"]["status"].budget_ms, None);
+
+        let markdown = Report::from_results(REPORT_TITLE, &results).render(ReportFormat::Markdown);
+        for (dataset, scenario) in &names {
+            let row = format!("| {} | {} | ✅ pass |", dataset, scenario);
+            assert!(markdown.contains(&row), "no row {:?} in\n{}", row, markdown);
+        }
+        let report = MatrixReport {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            results,
+        };
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json["results"]["small-merges"]["log_page"]["dataset"]["name"],
+            "small-merges"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Flamegraphs of benchmark scenarios.
+//!
+//! Built with the `profile` feature, `repo-lens-bench run --profile`
+//! samples the process while each scenario runs and writes the stacks it
+//! saw as a flamegraph SVG. Time spent inside git subprocesses is not
+//! sampled; it shows up as the engine waiting on them.
+
+use std::error::Error;
+use std::future::Future;
+use std::path::Path;
+
+/// Default directory for flamegraphs
+#[cfg(feature = "profile")]
+pub const DEFAULT_PROFILE_DIR: &str = "target/rl_bench/flamegraphs";
+
+/// Samples taken per second
+#[cfg(feature = "profile")]
+const FREQUENCY: i32 = 999;
+
+/// Await `run`, writing a flamegraph of it to `svg` if given.
+#[cfg(feature = "profile")]
+pub async fn profiled<T, E: Into<Box<dyn Error>>>(
+    svg: Option<&Path>,
+    run: impl Future<Output = Result<T, E>>,
+) -> Result<T, Box<dyn Error>> {
+    let Some(svg) = svg else {
+        return run.await.map_err(Into::into);
+    };
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    let result = run.await;
+    let report = guard.report().build()?;
+
+    if let Some(parent) = svg.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    report.flamegraph(std::fs::File::create(svg)?)?;
+    result.map_err(Into::into)
+}
+
+/// Await `run`; without the `profile` feature there is nothing to sample
+/// with, and `--profile` is not offered.
+#[cfg(not(feature = "profile"))]
+pub async fn profiled<T, E: Into<Box<dyn Error>>>(
+    _svg: Option<&Path>,
+    run: impl Future<Output = Result<T, E>>,
+) -> Result<T, Box<dyn Error>> {
+    run.await.map_err(Into::into)
+}
+
+#[cfg(all(test, feature = "profile"))]
+mod tests {
+    use super::*;
+    use crate::scenarios::{generate_scenarios, time_scenario, Sampling};
+    use rl_fixtures::large_repo::{LargeRepo, LargeRepoSpec};
+
+    #[tokio::test]
+    async fn test_profiled_scenario_writes_a_flamegraph() {
+        let dir = std::env::temp_dir().join(format!("rl_bench_profile_{}", std::process::id()));
+        let repo_path = dir.join("repo");
+        let spec = LargeRepoSpec {
+            commits: 5,
+            files: 10,
+            branchiness: 0.0,
+            seed: 1,
+        };
+        LargeRepo::ensure_at(&spec, &repo_path).unwrap();
+        let scenario = generate_scenarios(&repo_path)
+            .into_iter()
+            .find(|s| s.name == "status")
+            .unwrap();
+        let engine = rl_core::RepoEngine::new();
+        let sampling = Sampling {
+            warmup: 0,
+            iterations: 20,
+            trim_percent: 0.0,
+        };
+        // Written under a directory that doesn't exist yet
+        let svg = dir.join("flamegraphs").join("small-status.svg");
+
+        let timings = profiled(Some(&svg), time_scenario(&engine, &scenario, &sampling))
+            .await
+            .unwrap();
+        let contents = std::fs::read_to_string(&svg).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(timings.iterations, 20);
+        assert!(contents.contains("<svg"), "not an SVG: {}", contents);
+    }
+}
@@ -1,17 +1,26 @@
 //! Regression detection for benchmark results.
 //!
-//! This module provides simple regression analysis by comparing benchmark runs
-//! against saved baselines and detecting performance regressions.
+//! This module compares benchmark runs against saved baselines. Where both
+//! sides timed several runs, a Mann-Whitney U test decides whether the
+//! change in the median is more than noise, so a single slow run on a busy
+//! CI runner isn't reported as a regression.
 
-use crate::scenarios::BenchmarkResult;
+use crate::scenarios::{BenchmarkResult, TimingInfo};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-/// Regression threshold (20% increase in wall time)
+/// Regression threshold (20% increase in median wall time)
 const REGRESSION_THRESHOLD: f64 = 0.20;
 
+/// Confidence a change must reach to count as a regression or improvement
+const CONFIDENCE_LEVEL: f64 = 0.95;
+
+/// Fewest runs on each side to test for significance; with fewer, the
+/// medians alone decide
+const MIN_SAMPLES: usize = 5;
+
 /// Regression analysis result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegressionAnalysis {
@@ -32,12 +41,220 @@ pub struct ScenarioRegression {
     pub current: BenchmarkResult,
     /// Relative change (positive = regression, negative = improvement)
     pub relative_change: f64,
+    /// Probability of a difference at least this large if nothing changed,
+    /// when both sides have enough samples to test
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p_value: Option<f64>,
+    /// Confidence that the timings really differ, `1 - p_value`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
     /// Whether this is a regression
     pub is_regression: bool,
     /// Human-readable status
     pub status: String,
 }
 
+/// Outcome of comparing two sets of timings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Regression,
+    Improvement,
+    Stable,
+}
+
+/// How current timings compare with baseline ones
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    /// Relative change in the median (positive = slower)
+    pub relative_change: f64,
+    /// Two-sided p-value of the Mann-Whitney U test, when both sides have
+    /// at least `MIN_SAMPLES` samples
+    pub p_value: Option<f64>,
+    /// Whether the change is a regression, an improvement or neither
+    pub verdict: Verdict,
+}
+
+impl Comparison {
+    /// Confidence that the timings really differ
+    pub fn confidence(&self) -> Option<f64> {
+        self.p_value.map(|p| 1.0 - p)
+    }
+
+    /// Human-readable status, as reported for each scenario
+    pub fn status(&self) -> String {
+        let percent = self.relative_change * 100.0;
+        let confidence = match self.confidence() {
+            Some(confidence) => format!(" ({:.1}% confidence)", confidence * 100.0),
+            None => String::new(),
+        };
+        match self.verdict {
+            Verdict::Regression => format!("REGRESSION: {:.1}% increase{}", percent, confidence),
+            Verdict::Improvement => format!("IMPROVEMENT: {:.1}% decrease{}", -percent, confidence),
+            Verdict::Stable => format!("STABLE: {:.1}% change{}", percent, confidence),
+        }
+    }
+}
+
+/// Compare `current` timings with `baseline` ones, in any unit.
+///
+/// A change counts when the median moves by more than 20% and, if both
+/// sides have enough samples, the Mann-Whitney U test is 95% confident the
+/// timings differ at all.
+pub fn compare(baseline: &[f64], current: &[f64]) -> Comparison {
+    let (baseline_median, current_median) = (median(baseline), median(current));
+    let relative_change = if baseline_median > 0.0 {
+        (current_median - baseline_median) / baseline_median
+    } else {
+        0.0
+    };
+    let p_value = (baseline.len() >= MIN_SAMPLES && current.len() >= MIN_SAMPLES)
+        .then(|| mann_whitney_p(baseline, current));
+    let significant = p_value.is_none_or(|p| 1.0 - p >= CONFIDENCE_LEVEL);
+
+    let verdict = if significant && relative_change > REGRESSION_THRESHOLD {
+        Verdict::Regression
+    } else if significant && relative_change < -REGRESSION_THRESHOLD {
+        Verdict::Improvement
+    } else {
+        Verdict::Stable
+    };
+    Comparison {
+        relative_change,
+        p_value,
+        verdict,
+    }
+}
+
+fn median(samples: &[f64]) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+        n => sorted[n / 2],
+    }
+}
+
+/// Two-sided p-value of the Mann-Whitney U test, by the normal
+/// approximation with tie and continuity corrections.
+fn mann_whitney_p(a: &[f64], b: &[f64]) -> f64 {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let n = n1 + n2;
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // Rank sum of `a`, giving tied samples the average of their ranks
+    let mut rank_sum = 0.0;
+    let mut tie_term = 0.0;
+    let mut start = 0;
+    while start < all.len() {
+        let end = start
+            + all[start..]
+                .iter()
+                .take_while(|x| x.0 == all[start].0)
+                .count();
+        let ties = (end - start) as f64;
+        let rank = (start + end + 1) as f64 / 2.0;
+        rank_sum += rank * all[start..end].iter().filter(|x| x.1).count() as f64;
+        tie_term += ties.powi(3) - ties;
+        start = end;
+    }
+
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    (2.0 * (1.0 - normal_cdf(z))).clamp(0.0, 1.0)
+}
+
+/// Standard normal CDF, using Abramowitz and Stegun's approximation of
+/// erf (7.1.26), accurate to about 1e-7.
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    match z >= 0.0 {
+        true => 0.5 * (1.0 + erf),
+        false => 0.5 * (1.0 - erf),
+    }
+}
+
+/// Every timed run of `result` in nanoseconds, or its one wall time for
+/// results recorded before samples were kept.
+fn samples_ns(result: &BenchmarkResult) -> Vec<f64> {
+    match result.samples_ns.is_empty() {
+        true => vec![result.wall_time_ns as f64],
+        false => result.samples_ns.iter().map(|&ns| ns as f64).collect(),
+    }
+}
+
+/// Median of every timed run of `result` in milliseconds, as compared
+pub fn median_ms(result: &BenchmarkResult) -> f64 {
+    median(&samples_ns(result)) / 1_000_000.0
+}
+
+/// How long one engine step took in the baseline and now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepChange {
+    /// Step name
+    pub step: String,
+    /// Median time in the baseline, if it went through the step
+    pub baseline_p50_ms: Option<f64>,
+    /// Median time now, if it went through the step
+    pub current_p50_ms: Option<f64>,
+    /// Relative change, when both went through the step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_change: Option<f64>,
+}
+
+/// Line up the step breakdowns of two timings, current steps first, so
+/// that a regressed median can be traced to the step that got slower.
+pub fn step_changes(baseline: &TimingInfo, current: &TimingInfo) -> Vec<StepChange> {
+    let p50 = |timings: &TimingInfo, name: &str| {
+        timings
+            .steps
+            .iter()
+            .find(|step| step.name == name)
+            .map(|step| step.p50_ms)
+    };
+    let mut names: Vec<&str> = current
+        .steps
+        .iter()
+        .map(|step| step.name.as_str())
+        .collect();
+    for step in &baseline.steps {
+        if !names.contains(&step.name.as_str()) {
+            names.push(&step.name);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let (before, after) = (p50(baseline, name), p50(current, name));
+            StepChange {
+                step: name.to_string(),
+                baseline_p50_ms: before,
+                current_p50_ms: after,
+                relative_change: match (before, after) {
+                    (Some(before), Some(after)) if before > 0.0 => Some((after - before) / before),
+                    _ => None,
+                },
+            }
+        })
+        .collect()
+}
+
 impl RegressionAnalysis {
     /// Analyze regressions by comparing current results against a baseline
     pub fn analyze(
@@ -55,34 +272,22 @@ impl RegressionAnalysis {
 
         for current in current_results {
             if let Some(baseline) = baseline_map.get(&current.scenario) {
-                let relative_change = if baseline.wall_time_ns > 0 {
-                    (current.wall_time_ns as f64 - baseline.wall_time_ns as f64)
-                        / baseline.wall_time_ns as f64
-                } else {
-                    0.0
-                };
-
-                let is_regression = relative_change > REGRESSION_THRESHOLD;
+                let comparison = compare(&samples_ns(baseline), &samples_ns(current));
+                let is_regression = comparison.verdict == Verdict::Regression;
 
                 if is_regression {
                     has_regressions = true;
                 }
 
-                let status = if is_regression {
-                    format!("REGRESSION: {:.1}% increase", relative_change * 100.0)
-                } else if relative_change < -REGRESSION_THRESHOLD {
-                    format!("IMPROVEMENT: {:.1}% decrease", -relative_change * 100.0)
-                } else {
-                    format!("STABLE: {:.1}% change", relative_change * 100.0)
-                };
-
                 scenario_results.push(ScenarioRegression {
                     scenario: current.scenario.clone(),
                     baseline: (*baseline).clone(),
                     current: current.clone(),
-                    relative_change,
+                    relative_change: comparison.relative_change,
+                    p_value: comparison.p_value,
+                    confidence: comparison.confidence(),
                     is_regression,
-                    status,
+                    status: comparison.status(),
                 });
             }
         }
@@ -0,0 +1,357 @@
+//! Bench results and regression comparisons as JUnit XML, Markdown or HTML,
+//! so CI can show them in test summaries and PR comments as they are.
+//!
+//! Each scenario is one test case: it fails when the scenario went over a
+//! budget, failed outright or regressed against its baseline. Comparing
+//! backends, each scenario is a case on every backend, failed where the
+//! backend couldn't serve it.
+
+use crate::regression::{self, ScenarioRegression};
+use crate::scenarios::backends::BackendScenarioResult;
+use crate::scenarios::SentinelResult;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Format of a report, from `--report`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// JUnit XML, read by most CI test summaries
+    Junit,
+    /// A Markdown table, for PR comments and job summaries
+    Markdown,
+    /// A standalone HTML page
+    Html,
+}
+
+/// One scenario in a report
+#[derive(Debug, Clone)]
+pub struct Case {
+    /// Group the case belongs to: its dataset, or the comparison it is from
+    pub suite: String,
+    /// Scenario name
+    pub name: String,
+    /// Typical run time in milliseconds
+    pub time_ms: f64,
+    /// Why the case failed, if it did
+    pub failure: Option<String>,
+    /// Values for the report's columns
+    pub cells: Vec<String>,
+}
+
+/// Results to report, one case per scenario
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Heading of the report
+    pub title: String,
+    /// Header of the column of suites
+    pub suite_header: &'static str,
+    /// Headers of the columns after the result
+    pub columns: Vec<&'static str>,
+    /// Cases in order
+    pub cases: Vec<Case>,
+}
+
+impl Report {
+    /// A report of bench results by dataset, then scenario.
+    pub fn from_results(
+        title: &str,
+        results: &BTreeMap<String, BTreeMap<String, SentinelResult>>,
+    ) -> Self {
+        let cases = results
+            .iter()
+            .flat_map(|(dataset, scenarios)| {
+                scenarios.iter().map(move |(scenario, result)| {
+                    let timings = &result.timings;
+                    Case {
+                        suite: dataset.clone(),
+                        name: scenario.clone(),
+                        time_ms: timings.p50_ms,
+                        failure: (result.status != "pass").then(|| result_failure(result)),
+                        cells: vec![
+                            format!("{:.2}", timings.p50_ms),
+                            format!("{:.2}", timings.p99_ms),
+                            result
+                                .budget_ms
+                                .map_or_else(|| "-".to_string(), |ms| format!("{:.2}", ms)),
+                            timings.iterations.to_string(),
+                        ],
+                    }
+                })
+            })
+            .collect();
+        Self {
+            title: title.to_string(),
+            suite_header: "Dataset",
+            columns: vec!["p50 ms", "p99 ms", "p99 budget ms", "Runs"],
+            cases,
+        }
+    }
+
+    /// A report of scenarios compared with their baselines.
+    pub fn from_regressions(title: &str, regressions: &[ScenarioRegression]) -> Self {
+        let cases = regressions
+            .iter()
+            .map(|regression| Case {
+                suite: "baseline".to_string(),
+                name: regression.scenario.clone(),
+                time_ms: regression::median_ms(&regression.current),
+                failure: regression.is_regression.then(|| regression.status.clone()),
+                cells: vec![
+                    format!("{:.2}", regression::median_ms(&regression.baseline)),
+                    format!("{:.2}", regression::median_ms(&regression.current)),
+                    format!("{:+.1}%", regression.relative_change * 100.0),
+                    regression
+                        .confidence
+                        .map_or_else(|| "-".to_string(), |c| format!("{:.1}%", c * 100.0)),
+                ],
+            })
+            .collect();
+        Self {
+            title: title.to_string(),
+            suite_header: "Suite",
+            columns: vec!["Baseline ms", "Current ms", "Change", "Confidence"],
+            cases,
+        }
+    }
+
+    /// A report of scenarios timed on each backend, scenario by scenario.
+    pub fn from_backends(title: &str, results: &[BackendScenarioResult]) -> Self {
+        let cases = results
+            .iter()
+            .flat_map(|result| {
+                result.backends.iter().map(move |backend| {
+                    let timings = backend.timings.as_ref();
+                    let ms = |ms: Option<f64>| {
+                        ms.map_or_else(|| "-".to_string(), |ms| format!("{:.2}", ms))
+                    };
+                    Case {
+                        suite: backend.backend.clone(),
+                        name: result.scenario.clone(),
+                        time_ms: timings.map_or(0.0, |t| t.p50_ms),
+                        failure: backend.error.clone(),
+                        cells: vec![
+                            ms(timings.map(|t| t.p50_ms)),
+                            ms(timings.map(|t| t.p99_ms)),
+                            backend
+                                .vs_fastest
+                                .map_or_else(|| "-".to_string(), |ratio| format!("{:.2}x", ratio)),
+                            timings.map_or(0, |t| t.iterations).to_string(),
+                        ],
+                    }
+                })
+            })
+            .collect();
+        Self {
+            title: title.to_string(),
+            suite_header: "Backend",
+            columns: vec!["p50 ms", "p99 ms", "vs fastest", "Runs"],
+            cases,
+        }
+    }
+
+    /// Number of failed cases
+    pub fn failures(&self) -> usize {
+        self.cases
+            .iter()
+            .filter(|case| case.failure.is_some())
+            .count()
+    }
+
+    /// Render the report in `format`.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Junit => self.junit(),
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Html => self.html(),
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} passed, {} failed",
+            self.cases.len() - self.failures(),
+            self.failures()
+        )
+    }
+
+    fn junit(&self) -> String {
+        let seconds = |cases: &[&Case]| cases.iter().map(|case| case.time_ms).sum::<f64>() / 1000.0;
+        let mut suites = BTreeMap::<&str, Vec<&Case>>::new();
+        for case in &self.cases {
+            suites.entry(&case.suite).or_default().push(case);
+        }
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let all: Vec<_> = self.cases.iter().collect();
+        let _ = writeln!(
+            out,
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+            escape(&self.title),
+            self.cases.len(),
+            self.failures(),
+            seconds(&all)
+        );
+        for (suite, cases) in &suites {
+            let failures = cases.iter().filter(|case| case.failure.is_some()).count();
+            let _ = writeln!(
+                out,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+                escape(suite),
+                cases.len(),
+                failures,
+                seconds(cases)
+            );
+            for case in cases {
+                let _ = write!(
+                    out,
+                    "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                    escape(suite),
+                    escape(&case.name),
+                    case.time_ms / 1000.0
+                );
+                let details: Vec<_> = self
+                    .columns
+                    .iter()
+                    .zip(&case.cells)
+                    .map(|(column, cell)| format!("{}: {}", column, cell))
+                    .collect();
+                match &case.failure {
+                    Some(failure) => {
+                        let _ = writeln!(
+                            out,
+                            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                            escape(failure),
+                            escape(&details.join("\n"))
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(
+                            out,
+                            ">\n      <system-out>{}</system-out>\n    </testcase>",
+                            escape(&details.join("\n"))
+                        );
+                    }
+                }
+            }
+            out.push_str("  </testsuite>\n");
+        }
+        out.push_str("</testsuites>\n");
+        out
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = format!("## {}\n\n{}\n\n", self.title, self.summary());
+        let headers: Vec<&str> = [self.suite_header, "Scenario", "Result"]
+            .into_iter()
+            .chain(self.columns.iter().copied())
+            .collect();
+        let _ = writeln!(out, "| {} |", headers.join(" | "));
+        let _ = writeln!(out, "|---|---|---|{}", "---:|".repeat(self.columns.len()));
+        for case in &self.cases {
+            let result = match &case.failure {
+                Some(failure) => format!("❌ {}", failure),
+                None => "✅ pass".to_string(),
+            };
+            let row: Vec<String> = [&case.suite, &case.name, &result]
+                .into_iter()
+                .chain(&case.cells)
+                .map(|cell| cell.replace('|', "\\|").replace('\n', " "))
+                .collect();
+            let _ = writeln!(out, "| {} |", row.join(" | "));
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(out, "<title>{}</title>", escape(&self.title));
+        out.push_str(
+            "<style>\
+             body { font-family: sans-serif; }\
+             table { border-collapse: collapse; }\
+             th, td { border: 1px solid #ccc; padding: 4px 8px; }\
+             td.number { text-align: right; }\
+             tr.fail { background: #fdd; }\
+             </style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(
+            out,
+            "<h1>{}</h1>\n<p>{}</p>\n<table>",
+            escape(&self.title),
+            self.summary()
+        );
+        out.push_str("<tr>");
+        for header in [self.suite_header, "Scenario", "Result"]
+            .into_iter()
+            .chain(self.columns.iter().copied())
+        {
+            let _ = write!(out, "<th>{}</th>", escape(header));
+        }
+        out.push_str("</tr>\n");
+        for case in &self.cases {
+            let (class, result) = match &case.failure {
+                Some(failure) => ("fail", failure.as_str()),
+                None => ("pass", "pass"),
+            };
+            let _ = write!(
+                out,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td>",
+                class,
+                escape(&case.suite),
+                escape(&case.name),
+                escape(result)
+            );
+            for cell in &case.cells {
+                let _ = write!(out, "<td class=\"number\">{}</td>", escape(cell));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+        out
+    }
+}
+
+/// Why a bench result failed: its reason, each budget it went over and a
+/// cache hit rate it fell short on.
+fn result_failure(result: &SentinelResult) -> String {
+    let mut failure = result
+        .reason
+        .clone()
+        .unwrap_or_else(|| result.status.clone());
+    for exceeded in &result.exceeded {
+        let _ = write!(
+            failure,
+            "; {} {:.2} ms over its {:.2} ms budget",
+            exceeded.budget, exceeded.actual_ms, exceeded.limit_ms
+        );
+    }
+    if let Some(stats) = result.cache_stats.as_ref().filter(|s| s.is_below_minimum()) {
+        let _ = write!(
+            failure,
+            "; {} of {} repeats served from cache, below {:.0}%",
+            stats.hits,
+            stats.requests,
+            stats.min_hit_rate * 100.0
+        );
+    }
+    if let Some(error) = result.response.as_ref().and_then(|r| r.error.as_ref()) {
+        let _ = write!(failure, "; {}", error);
+    }
+    failure
+}
+
+/// Escape text for XML and HTML, in content and attributes alike.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
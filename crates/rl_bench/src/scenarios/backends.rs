@@ -0,0 +1,137 @@
+//! The same scenarios against each git backend, on the same dataset, to
+//! choose the default backend by measurement rather than by guess.
+//!
+//! Each backend gets an engine of its own, so that one backend's caches
+//! never answer for another.
+
+use super::{response_shape, time_scenario, BenchmarkScenario, DatasetInfo, Sampling, TimingInfo};
+use rl_core::config::BackendKind;
+use rl_core::{EngineConfig, RepoEngine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// One scenario on one backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendTiming {
+    /// Backend name, as the `backend` setting spells it
+    pub backend: String,
+    /// Timings, unless the backend failed the scenario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<TimingInfo>,
+    /// Median time over the fastest backend's median, for backends that
+    /// served the scenario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vs_fastest: Option<f64>,
+    /// Why the backend failed the scenario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One scenario on every backend compared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendScenarioResult {
+    /// Scenario name
+    pub scenario: String,
+    /// Backend with the lowest median, if any served the scenario
+    pub fastest: Option<String>,
+    /// Timings on each backend, in the order they were compared
+    pub backends: Vec<BackendTiming>,
+}
+
+/// Results of `repo-lens-bench backends`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendReport {
+    /// Timestamp of the run
+    pub timestamp: String,
+    /// Dataset information
+    pub dataset: DatasetInfo,
+    /// Backends compared
+    pub backends: Vec<String>,
+    /// Results for each scenario
+    pub results: Vec<BackendScenarioResult>,
+    /// Scenarios each backend was fastest on, by backend
+    pub wins: BTreeMap<String, usize>,
+}
+
+impl BackendScenarioResult {
+    /// Rank the timings of `scenario` on each backend against the fastest.
+    pub fn new(scenario: &str, mut backends: Vec<BackendTiming>) -> Self {
+        let fastest = backends
+            .iter()
+            .filter_map(|b| Some((b, b.timings.as_ref()?.p50_ms)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(b, p50_ms)| (b.backend.clone(), p50_ms));
+        if let Some((_, fastest_ms)) = fastest {
+            for backend in &mut backends {
+                backend.vs_fastest = backend
+                    .timings
+                    .as_ref()
+                    .map(|timings| timings.p50_ms / fastest_ms);
+            }
+        }
+        Self {
+            scenario: scenario.to_string(),
+            fastest: fastest.map(|(backend, _)| backend),
+            backends,
+        }
+    }
+}
+
+impl BackendReport {
+    /// Put together the results of comparing `backends`.
+    pub fn new(
+        dataset: DatasetInfo,
+        backends: &[BackendKind],
+        results: Vec<BackendScenarioResult>,
+    ) -> Self {
+        let mut wins: BTreeMap<_, _> = backends
+            .iter()
+            .map(|kind| (kind.name().to_string(), 0))
+            .collect();
+        for fastest in results.iter().filter_map(|r| r.fastest.as_ref()) {
+            *wins.entry(fastest.clone()).or_default() += 1;
+        }
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            dataset,
+            backends: backends
+                .iter()
+                .map(|kind| kind.name().to_string())
+                .collect(),
+            results,
+            wins,
+        }
+    }
+}
+
+/// Backends compared when none are named: every one that can serve
+/// requests.
+pub fn default_backends() -> Vec<BackendKind> {
+    BackendKind::ALL
+        .iter()
+        .copied()
+        .filter(|&kind| kind != BackendKind::Stub)
+        .collect()
+}
+
+/// Time `scenario` on a fresh engine using `backend`. A backend that fails
+/// the request is reported with its error rather than its timings, so that
+/// an incomplete backend can still be compared on what it supports.
+pub async fn time_backend_scenario(
+    backend: BackendKind,
+    scenario: &BenchmarkScenario,
+    sampling: &Sampling,
+) -> serde_json::Result<BackendTiming> {
+    let engine = RepoEngine::with_config(EngineConfig {
+        backend,
+        ..EngineConfig::default()
+    });
+    let timings = time_scenario(&engine, scenario, sampling).await?;
+    let error = response_shape(&engine, scenario).await?.error;
+    Ok(BackendTiming {
+        backend: backend.name().to_string(),
+        timings: error.is_none().then_some(timings),
+        vs_fastest: None,
+        error,
+    })
+}
@@ -0,0 +1,139 @@
+//! End-to-end scenarios through a `repo-lens serve` process. Each request
+//! is serialized, written to the server's stdin, decoded, handled by its
+//! engine and answered on its stdout, then decoded again, so timing the
+//! same scenarios in process as well shows what the protocol costs apart
+//! from the engine.
+
+use super::{BenchmarkScenario, DatasetInfo, TimingInfo};
+use rl_ipc::{IpcClient, TransportConfig};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Instant;
+use tokio::process::{Child, Command};
+
+/// One scenario timed through IPC and in process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcScenarioResult {
+    /// Scenario name
+    pub scenario: String,
+    /// Timings through the `serve` process, from sending the request to
+    /// decoding its final response
+    pub ipc: TimingInfo,
+    /// Timings of the same request handled by an engine in this process
+    pub engine: TimingInfo,
+    /// Median IPC time less median engine time, in milliseconds
+    pub overhead_p50_ms: f64,
+    /// Median IPC time over median engine time
+    pub overhead_ratio: f64,
+}
+
+/// Results of `repo-lens-bench ipc`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IpcReport {
+    /// Timestamp of the run
+    pub timestamp: String,
+    /// Dataset information
+    pub dataset: DatasetInfo,
+    /// The `repo-lens` binary that served the requests
+    pub server: String,
+    /// Results for each scenario
+    pub results: Vec<IpcScenarioResult>,
+}
+
+impl IpcScenarioResult {
+    /// Put IPC and in-process timings of `scenario` side by side.
+    pub fn new(scenario: &str, ipc: TimingInfo, engine: TimingInfo) -> Self {
+        Self {
+            scenario: scenario.to_string(),
+            overhead_p50_ms: ipc.p50_ms - engine.p50_ms,
+            overhead_ratio: ipc.p50_ms / engine.p50_ms,
+            ipc,
+            engine,
+        }
+    }
+}
+
+/// A `repo-lens serve` process speaking the IPC protocol over stdio,
+/// killed when dropped
+pub struct ServeProcess {
+    /// Client connected to the process's stdin and stdout
+    pub client: IpcClient,
+    _child: Child,
+}
+
+impl ServeProcess {
+    /// Start `server serve` and negotiate the protocol with it.
+    pub async fn spawn(server: &Path) -> io::Result<Self> {
+        let mut child = Command::new(server)
+            .arg("serve")
+            // A token in the environment would have the server wait for one
+            .env_remove("REPO_LENS_TOKEN")
+            .env_remove("REPO_LENS_TOKEN_FILE")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to start {}: {}", server.display(), e),
+                )
+            })?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let client = IpcClient::connect(stdout, stdin, TransportConfig::default());
+        client.hello().await.map_err(|e| {
+            io::Error::other(format!("Handshake with {} failed: {}", server.display(), e))
+        })?;
+        Ok(Self {
+            client,
+            _child: child,
+        })
+    }
+}
+
+/// The `repo-lens` binary built alongside this one, or one found on `PATH`.
+///
+/// Test binaries live a directory further down, in `deps`, so the parent
+/// directory is tried too.
+pub fn default_server_bin() -> PathBuf {
+    let name = format!("repo-lens{}", std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.ancestors()
+                .skip(1)
+                .take(2)
+                .map(|dir| dir.join(&name))
+                .find(|candidate| candidate.is_file())
+        })
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Time `scenario` through `client`: one cold run, then `iterations` warm
+/// runs timed one by one, each from sending the request to decoding its
+/// final response. Fails with the first error, from the transport or the
+/// engine.
+pub async fn time_ipc_scenario(
+    client: &IpcClient,
+    scenario: &BenchmarkScenario,
+    iterations: usize,
+) -> Result<TimingInfo, rl_api::Error> {
+    let run = |n: usize| async move {
+        let mut request = scenario.request.clone();
+        request.id = format!("{}-{}", request.id, n);
+        let start = Instant::now();
+        client.send_request(request).await?.result?;
+        Ok::<_, rl_api::Error>(start.elapsed().as_nanos() as f64 / 1_000_000.0)
+    };
+
+    let cold_ms = run(0).await?;
+    let mut warm_ms = Vec::with_capacity(iterations);
+    for n in 1..=iterations {
+        warm_ms.push(run(n).await?);
+    }
+    Ok(TimingInfo::from_samples(cold_ms, warm_ms))
+}
@@ -3,9 +3,18 @@
 //! This module defines deterministic benchmark scenarios that correspond to
 //! typical UI interactions, using pinned commits from real repositories.
 
-use rl_api::{request::*, ApiVersion, Request};
+use rl_api::response::StepTiming;
+use rl_api::{request::*, Request};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub mod backends;
+pub mod ipc;
+pub mod pathological;
 
 /// A benchmark scenario with deterministic inputs
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +38,10 @@ pub struct BenchmarkResult {
     pub success: bool,
     /// Error message if failed
     pub error: Option<String>,
+    /// Wall-clock time of every timed run in nanoseconds, for comparisons
+    /// that tell a regression from noise
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples_ns: Vec<u64>,
 }
 
 /// Sentinel benchmark result with detailed timing and dataset info
@@ -38,8 +51,40 @@ pub struct SentinelResult {
     pub dataset: DatasetInfo,
     /// Scenario name
     pub scenario: String,
-    /// Timing information
+    /// Cache state the runs started from
+    #[serde(default)]
+    pub cache: CacheMode,
+    /// Timing information: of cold runs with `cold`, of warm runs otherwise
     pub timings: TimingInfo,
+    /// Timing information of cold runs, with `both`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_timings: Option<TimingInfo>,
+    /// Median cold run time over median warm run time, with `both`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_speedup: Option<f64>,
+    /// Whether the OS page cache was dropped before every cold run
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub page_cache_dropped: bool,
+    /// Budget in milliseconds that 99% of runs in `timings` had to stay within
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_ms: Option<f64>,
+    /// Budget in milliseconds for the median cold run, or the first run
+    /// when only warm runs were timed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_budget_ms: Option<f64>,
+    /// Budget in milliseconds for the median warm run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_budget_ms: Option<f64>,
+    /// Budgets the run went over
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exceeded: Vec<crate::budgets::ExceededBudget>,
+    /// What the response looked like, for scenarios that check it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ResponseShape>,
+    /// How often repeats of the request were served from a cache, for
+    /// scenarios that check it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_stats: Option<CacheStats>,
     /// Status
     pub status: String,
     /// Reason for status (null if pass)
@@ -47,6 +92,66 @@ pub struct SentinelResult {
     pub reason: Option<String>,
 }
 
+/// Cache state benchmark runs start from
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// One engine serves every run, after a first cold one
+    #[default]
+    Warm,
+    /// Every run gets a fresh engine, with nothing cached
+    Cold,
+    /// Warm runs, then cold ones
+    Both,
+}
+
+/// How a scenario was answered
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseShape {
+    /// Responses sent, counting each streamed chunk
+    pub chunks: usize,
+    /// Size of every response serialized, in bytes
+    pub bytes: usize,
+    /// The bound that cut the result short, if one did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<rl_api::response::Truncation>,
+    /// Why the request failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How often the engine served repeats of a request from a cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Repeats sent, after the first request
+    pub requests: usize,
+    /// Repeats the engine marked as served from a cache
+    pub hits: usize,
+    /// `hits` over `requests`
+    pub hit_rate: f64,
+    /// Lowest hit rate that passes
+    pub min_hit_rate: f64,
+}
+
+impl CacheStats {
+    /// Whether the hit rate is below the minimum
+    pub fn is_below_minimum(&self) -> bool {
+        self.hit_rate < self.min_hit_rate
+    }
+}
+
 /// Dataset information for benchmark results
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatasetInfo {
@@ -69,10 +174,183 @@ pub struct TimingInfo {
     pub cold_ms: f64,
     /// Total warm run time in milliseconds (all iterations)
     pub warm_total_ms: f64,
-    /// Average warm run time in milliseconds per iteration
-    pub warm_avg_ms: f64,
+    /// Median warm run time in milliseconds
+    pub p50_ms: f64,
+    /// Warm run time in milliseconds that 90% of iterations stayed within
+    pub p90_ms: f64,
+    /// Warm run time in milliseconds that 99% of iterations stayed within
+    pub p99_ms: f64,
+    /// Slowest warm run time in milliseconds
+    pub max_ms: f64,
+    /// Standard deviation of warm run times in milliseconds
+    pub stddev_ms: f64,
     /// Number of warm iterations
     pub iterations: usize,
+    /// Every iteration's time in milliseconds, fastest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples_ms: Vec<f64>,
+    /// Iterations dropped as outliers, fastest and slowest together, and
+    /// left out of every other field
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub trimmed: usize,
+    /// Time spent in each step the engine went through, such as
+    /// `git_open_repo` or `parse_diff`, in the order the steps finished
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepBreakdown>,
+}
+
+/// Time spent in one step of handling a scenario's request, over its runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepBreakdown {
+    /// Step name, as the engine records it
+    pub name: String,
+    /// Median time in milliseconds over the runs that went through the step
+    pub p50_ms: f64,
+    /// Runs that went through the step; fewer than were timed when some
+    /// were answered from a cache
+    pub runs: usize,
+}
+
+/// Summarize the steps of each timed run, as the engine reported them in
+/// `ResponseMeta::steps`. A step that ran more than once in a run counts
+/// once, with its times added up.
+pub fn step_breakdown(runs: &[Vec<StepTiming>]) -> Vec<StepBreakdown> {
+    let mut names: Vec<&str> = Vec::new();
+    let mut times = BTreeMap::<&str, Vec<f64>>::new();
+    for steps in runs {
+        let mut totals = BTreeMap::<&str, f64>::new();
+        for step in steps {
+            if !names.contains(&step.name.as_str()) {
+                names.push(&step.name);
+            }
+            *totals.entry(&step.name).or_default() += step.elapsed_ms;
+        }
+        for (name, total) in totals {
+            times.entry(name).or_default().push(total);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let times = times.remove(name).unwrap_or_default();
+            StepBreakdown {
+                name: name.to_string(),
+                runs: times.len(),
+                p50_ms: TimingInfo::from_samples(0.0, times).p50_ms,
+            }
+        })
+        .collect()
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// How many runs of a scenario to time, and which of them to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Untimed runs before timing starts, after the first cold run
+    pub warmup: usize,
+    /// Timed runs
+    pub iterations: usize,
+    /// Percentage of the fastest runs, and of the slowest, to drop as
+    /// outliers, below 50
+    pub trim_percent: f64,
+}
+
+impl Sampling {
+    /// Time `iterations` runs, with no warmup and no trimming.
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            warmup: 0,
+            iterations,
+            trim_percent: 0.0,
+        }
+    }
+
+    /// Summarize the cold run and each timed run's time, in milliseconds,
+    /// after dropping outliers.
+    pub fn summarize(&self, cold_ms: f64, mut samples_ms: Vec<f64>) -> TimingInfo {
+        samples_ms.sort_by(f64::total_cmp);
+        let n = samples_ms.len();
+        // Keep at least one run, however much is trimmed
+        let trim = ((n as f64 * self.trim_percent / 100.0) as usize).min(n.saturating_sub(1) / 2);
+        let kept = samples_ms[trim..n - trim].to_vec();
+        TimingInfo {
+            trimmed: 2 * trim,
+            ..TimingInfo::from_samples(cold_ms, kept)
+        }
+    }
+}
+
+impl TimingInfo {
+    /// Summarize the cold run and each warm run's time, in milliseconds.
+    pub fn from_samples(cold_ms: f64, mut warm_ms: Vec<f64>) -> Self {
+        warm_ms.sort_by(f64::total_cmp);
+        let iterations = warm_ms.len();
+        let warm_total_ms: f64 = warm_ms.iter().sum();
+        let mean = warm_total_ms / iterations.max(1) as f64;
+        let variance = warm_ms
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / iterations.max(1) as f64;
+        // Nearest rank: the smallest sample at least `p`% of samples are within
+        let percentile = |p: f64| match iterations {
+            0 => 0.0,
+            n => warm_ms[((p / 100.0 * n as f64).ceil() as usize).clamp(1, n) - 1],
+        };
+        Self {
+            cold_ms,
+            warm_total_ms,
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: percentile(100.0),
+            stddev_ms: variance.sqrt(),
+            iterations,
+            samples_ms: warm_ms,
+            trimmed: 0,
+            steps: Vec::new(),
+        }
+    }
+}
+
+/// Throughput and latency with a number of clients sending requests at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyLevel {
+    /// Number of clients, each sending its next request once the last is
+    /// answered
+    pub concurrency: usize,
+    /// Requests answered
+    pub requests: usize,
+    /// Requests answered with an error
+    pub errors: usize,
+    /// Wall-clock time for all requests in milliseconds
+    pub elapsed_ms: f64,
+    /// Requests answered per second
+    pub throughput_rps: f64,
+    /// Throughput relative to a single client
+    pub speedup: f64,
+    /// Median request latency in milliseconds
+    pub p50_ms: f64,
+    /// Request latency in milliseconds that 99% of requests stayed within
+    pub p99_ms: f64,
+    /// Slowest request latency in milliseconds
+    pub max_ms: f64,
+}
+
+/// Results of the concurrency scenario, from `repo-lens-bench concurrency`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConcurrencyReport {
+    /// Timestamp of the run
+    pub timestamp: String,
+    /// Dataset information
+    pub dataset: DatasetInfo,
+    /// Scenarios whose requests are mixed, in the order they are sent
+    pub scenarios: Vec<String>,
+    /// Results by number of concurrent clients, in increasing order
+    pub levels: Vec<ConcurrencyLevel>,
 }
 
 /// Collection of benchmark results from a run
@@ -86,6 +364,15 @@ pub struct BenchmarkRun {
     pub results: Vec<BenchmarkResult>,
 }
 
+/// Results of every scenario on every dataset, from `run --dataset all`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MatrixReport {
+    /// Timestamp of the run
+    pub timestamp: String,
+    /// Results by dataset name, then scenario name
+    pub results: BTreeMap<String, BTreeMap<String, SentinelResult>>,
+}
+
 /// Generate benchmark scenarios for a given repository path
 pub fn generate_scenarios(repo_path: &Path) -> Vec<BenchmarkScenario> {
     let repo_path_str = repo_path.to_string_lossy().to_string();
@@ -94,32 +381,29 @@ pub fn generate_scenarios(repo_path: &Path) -> Vec<BenchmarkScenario> {
         BenchmarkScenario {
             name: "engine_overhead".to_string(),
             description: "Measure engine overhead with minimal Status request".to_string(),
-            request: Request {
-                version: ApiVersion::V0,
-                id: "bench-engine-overhead".to_string(),
-                payload: RequestPayload::Status(StatusRequest {
+            request: Request::new(
+                "bench-engine-overhead",
+                RequestPayload::Status(StatusRequest {
                     repo_path: repo_path_str.clone(),
                 }),
-            },
+            ),
         },
         BenchmarkScenario {
             name: "status".to_string(),
             description: "Get repository status".to_string(),
-            request: Request {
-                version: ApiVersion::V0,
-                id: "bench-status".to_string(),
-                payload: RequestPayload::Status(StatusRequest {
+            request: Request::new(
+                "bench-status",
+                RequestPayload::Status(StatusRequest {
                     repo_path: repo_path_str.clone(),
                 }),
-            },
+            ),
         },
         BenchmarkScenario {
             name: "log_page".to_string(),
             description: "Get commit log with pagination (200 commits)".to_string(),
-            request: Request {
-                version: ApiVersion::V0,
-                id: "bench-log".to_string(),
-                payload: RequestPayload::Log(LogRequest {
+            request: Request::new(
+                "bench-log",
+                RequestPayload::Log(LogRequest {
                     repo_path: repo_path_str.clone(),
                     paging: rl_api::Paging {
                         page_size: rl_api::PageSize::try_from(200).unwrap(),
@@ -127,15 +411,14 @@ pub fn generate_scenarios(repo_path: &Path) -> Vec<BenchmarkScenario> {
                     },
                     revision_range: None,
                 }),
-            },
+            ),
         },
         BenchmarkScenario {
             name: "diff_summary".to_string(),
             description: "Get diff summary between two specific commits".to_string(),
-            request: Request {
-                version: ApiVersion::V0,
-                id: "bench-diff-summary".to_string(),
-                payload: RequestPayload::DiffSummary(DiffSummaryRequest {
+            request: Request::new(
+                "bench-diff-summary",
+                RequestPayload::DiffSummary(DiffSummaryRequest {
                     repo_path: repo_path_str.clone(),
                     // Using commits that exist in Git v2.45.0
                     from: Some("HEAD~10".to_string()),
@@ -143,11 +426,228 @@ pub fn generate_scenarios(repo_path: &Path) -> Vec<BenchmarkScenario> {
                     max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
                     max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
                 }),
-            },
+            ),
         },
     ]
 }
 
+/// Time `scenario` against `engine`: one cold run, then the warmup runs,
+/// then `sampling.iterations` warm runs timed one by one, with the time the
+/// warm runs spent in each step. Every response is serialized, as a client
+/// would, so none of the work can be optimized away.
+pub async fn time_scenario(
+    engine: &rl_core::RepoEngine,
+    scenario: &BenchmarkScenario,
+    sampling: &Sampling,
+) -> serde_json::Result<TimingInfo> {
+    let run = || async {
+        let start = Instant::now();
+        let response = engine.handle(timed_request(scenario)).await;
+        serde_json::to_string(&response)?;
+        let elapsed_ms = start.elapsed().as_nanos() as f64 / 1_000_000.0;
+        Ok::<_, serde_json::Error>((elapsed_ms, response.meta.unwrap_or_default().steps))
+    };
+
+    let (cold_ms, _) = run().await?;
+    for _ in 0..sampling.warmup {
+        run().await?;
+    }
+    let mut warm_ms = Vec::with_capacity(sampling.iterations);
+    let mut steps = Vec::with_capacity(sampling.iterations);
+    for _ in 0..sampling.iterations {
+        let (elapsed_ms, run_steps) = run().await?;
+        warm_ms.push(elapsed_ms);
+        steps.push(run_steps);
+    }
+    Ok(TimingInfo {
+        steps: step_breakdown(&steps),
+        ..sampling.summarize(cold_ms, warm_ms)
+    })
+}
+
+/// The scenario's request, asking the engine for the time of each step.
+/// The engine records them either way, so asking costs next to nothing.
+fn timed_request(scenario: &BenchmarkScenario) -> Request {
+    Request {
+        timings: true,
+        ..scenario.request.clone()
+    }
+}
+
+/// Run `scenario` once against `engine`, streaming its response, and
+/// describe what came back.
+pub async fn response_shape(
+    engine: &rl_core::RepoEngine,
+    scenario: &BenchmarkScenario,
+) -> serde_json::Result<ResponseShape> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<rl_api::Response>(16);
+    let handled = engine.handle_stream(scenario.request.clone(), sender);
+    let received = async {
+        let mut shape = ResponseShape {
+            chunks: 0,
+            bytes: 0,
+            truncated: None,
+            error: None,
+        };
+        while let Some(response) = receiver.recv().await {
+            shape.chunks += 1;
+            shape.bytes += serde_json::to_vec(&response)?.len();
+            if let Some(meta) = &response.meta {
+                shape.truncated = shape.truncated.or(meta.truncated);
+            }
+            if let Err(error) = &response.result {
+                shape.error = Some(error.to_string());
+            }
+        }
+        Ok(shape)
+    };
+    let ((), shape) = tokio::join!(handled, received);
+    shape
+}
+
+/// Send `scenario`'s request to `engine` once, so that it can be cached,
+/// then `requests` times more, counting the repeats the engine reports as
+/// served from a cache.
+pub async fn cache_stats(
+    engine: &rl_core::RepoEngine,
+    scenario: &BenchmarkScenario,
+    requests: usize,
+    min_hit_rate: f64,
+) -> CacheStats {
+    engine.handle(scenario.request.clone()).await;
+    let mut hits = 0;
+    for _ in 0..requests {
+        let response = engine.handle(scenario.request.clone()).await;
+        if response.meta.is_some_and(|meta| meta.cached) {
+            hits += 1;
+        }
+    }
+    CacheStats {
+        requests,
+        hits,
+        hit_rate: hits as f64 / requests.max(1) as f64,
+        min_hit_rate,
+    }
+}
+
+/// Send `requests` requests at `engine` from `concurrency` clients at once,
+/// cycling through the requests of `mix`, and measure throughput and the
+/// latency of each request. `speedup` is left at 1 for the caller to fill
+/// in against its single-client level.
+pub async fn time_concurrency(
+    engine: &Arc<rl_core::RepoEngine>,
+    mix: &[BenchmarkScenario],
+    concurrency: usize,
+    requests: usize,
+) -> serde_json::Result<ConcurrencyLevel> {
+    let next = Arc::new(AtomicUsize::new(0));
+    let mix: Arc<[Request]> = mix.iter().map(|s| s.request.clone()).collect();
+
+    let start = Instant::now();
+    let clients: Vec<_> = (0..concurrency.max(1))
+        .map(|_| {
+            let (engine, next, mix) = (engine.clone(), next.clone(), mix.clone());
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut errors = 0;
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= requests || mix.is_empty() {
+                        break;
+                    }
+                    let mut request = mix[index % mix.len()].clone();
+                    request.id = format!("{}-{}", request.id, index);
+
+                    let sent = Instant::now();
+                    let response = engine.handle(request).await;
+                    serde_json::to_string(&response)?;
+                    latencies.push(sent.elapsed().as_nanos() as f64 / 1_000_000.0);
+                    errors += usize::from(response.result.is_err());
+                }
+                Ok::<_, serde_json::Error>((latencies, errors))
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(requests);
+    let mut errors = 0;
+    for client in clients {
+        let (client_latencies, client_errors) = client.await.expect("bench client panicked")?;
+        latencies.extend(client_latencies);
+        errors += client_errors;
+    }
+    let elapsed_ms = start.elapsed().as_nanos() as f64 / 1_000_000.0;
+
+    let timings = TimingInfo::from_samples(0.0, latencies);
+    Ok(ConcurrencyLevel {
+        concurrency,
+        requests: timings.iterations,
+        errors,
+        elapsed_ms,
+        throughput_rps: timings.iterations as f64 / (elapsed_ms / 1000.0),
+        speedup: 1.0,
+        p50_ms: timings.p50_ms,
+        p99_ms: timings.p99_ms,
+        max_ms: timings.max_ms,
+    })
+}
+
+/// Time `sampling.iterations` runs of `scenario`, and their steps, each on
+/// a fresh engine so that nothing the engine or its index caches survives
+/// from one to the next; there is nothing to warm up. With
+/// `drop_page_cache`, the OS page cache is dropped before each run as well,
+/// which needs root on Linux and is unsupported elsewhere.
+///
+/// The first run is reported as `cold_ms` too.
+pub async fn time_cold_scenario(
+    scenario: &BenchmarkScenario,
+    sampling: &Sampling,
+    drop_page_cache: bool,
+) -> std::io::Result<TimingInfo> {
+    let mut cold_ms = Vec::with_capacity(sampling.iterations);
+    let mut steps = Vec::with_capacity(sampling.iterations);
+    for _ in 0..sampling.iterations.max(1) {
+        if drop_page_cache {
+            drop_os_page_cache()?;
+        }
+        let engine = rl_core::RepoEngine::new();
+        let start = Instant::now();
+        let response = engine.handle(timed_request(scenario)).await;
+        serde_json::to_string(&response)?;
+        cold_ms.push(start.elapsed().as_nanos() as f64 / 1_000_000.0);
+        steps.push(response.meta.unwrap_or_default().steps);
+    }
+    Ok(TimingInfo {
+        steps: step_breakdown(&steps),
+        ..sampling.summarize(cold_ms[0], cold_ms)
+    })
+}
+
+/// Flush dirty pages and drop the page cache, dentries and inodes.
+fn drop_os_page_cache() -> std::io::Result<()> {
+    std::process::Command::new("sync").status()?;
+    std::fs::write("/proc/sys/vm/drop_caches", "3").map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Cannot drop the page cache (needs root on Linux): {}", e),
+        )
+    })
+}
+
+/// Status of a run and the reason for it: `fail` with `budget_exceeded`
+/// when more than 1% of warm runs took longer than `budget_ms`, else
+/// `pass`. A UI misses its budget on the slow runs, not the average one.
+#[allow(dead_code)]
+pub fn budget_status(timings: &TimingInfo, budget_ms: Option<f64>) -> (String, Option<String>) {
+    match budget_ms {
+        Some(budget) if timings.p99_ms > budget => {
+            ("fail".to_string(), Some("budget_exceeded".to_string()))
+        }
+        // Sentinel benchmarks always pass unless there's a hard error
+        _ => ("pass".to_string(), None),
+    }
+}
+
 /// Get the names of all available scenarios
 #[allow(dead_code)]
 pub fn scenario_names() -> Vec<String> {
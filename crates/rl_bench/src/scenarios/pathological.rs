@@ -0,0 +1,155 @@
+//! Worst-case scenarios, run against repositories generated by
+//! `rl_fixtures` rather than cloned datasets: a commit changing 50,000
+//! files, a 100MB diff, blame of a file with a million lines and a history
+//! 100,000 commits deep. Each one checks that the response was cut short by
+//! the bound it should have been, as well as timing it.
+
+use super::BenchmarkScenario;
+use rl_api::response::TruncationBound;
+use rl_api::{request::*, Request};
+use rl_fixtures::pathological::{self, PathologicalRepo, Pathology};
+use rl_fixtures::synth_repo::FixtureError;
+
+/// Name the pathological scenarios are reported under, in place of a
+/// dataset's
+pub const DATASET_NAME: &str = "pathological";
+
+/// A worst-case scenario and the repository it runs against
+#[derive(Debug, Clone)]
+pub struct PathologicalScenario {
+    /// Scenario name
+    pub name: &'static str,
+    /// Human-readable description
+    pub description: &'static str,
+    /// Shape of the repository to generate
+    pub pathology: Pathology,
+    /// The bound that must cut the response short, if any
+    pub expect_truncated: Option<TruncationBound>,
+    /// The request against a repository at the given path
+    payload: fn(String) -> RequestPayload,
+}
+
+impl PathologicalScenario {
+    /// Generate the scenario's repository if needed and return the
+    /// scenario to time against it.
+    pub fn prepare(&self) -> Result<(PathologicalRepo, BenchmarkScenario), FixtureError> {
+        let repo = PathologicalRepo::ensure(self.pathology)?;
+        let scenario = BenchmarkScenario {
+            name: self.name.to_string(),
+            description: self.description.to_string(),
+            request: Request::new(
+                format!("bench-{}", self.name.replace('_', "-")),
+                (self.payload)(repo.path.to_string_lossy().to_string()),
+            ),
+        };
+        Ok((repo, scenario))
+    }
+}
+
+/// The worst-case scenarios, with repositories `scale` times their full
+/// size; a small scale makes them quick enough for tests.
+pub fn pathological_scenarios(scale: f64) -> Vec<PathologicalScenario> {
+    let scaled = |size: usize| ((size as f64 * scale) as usize).max(1);
+    let wide = Pathology::WideCommit {
+        files: scaled(pathological::WIDE_COMMIT_FILES),
+    };
+    let deep = Pathology::DeepHistory {
+        commits: scaled(pathological::DEEP_HISTORY_COMMITS),
+    };
+
+    vec![
+        PathologicalScenario {
+            name: "wide_commit_diff_summary",
+            description: "Diff summary of a commit changing 50,000 files",
+            pathology: wide,
+            expect_truncated: None,
+            payload: |repo_path| {
+                RequestPayload::DiffSummary(DiffSummaryRequest {
+                    repo_path,
+                    from: Some("HEAD~1".to_string()),
+                    to: Some("HEAD".to_string()),
+                    max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+                })
+            },
+        },
+        PathologicalScenario {
+            name: "wide_commit_diff_content",
+            description: "Diff of a commit changing 50,000 files, cut at 64KB",
+            pathology: wide,
+            expect_truncated: Some(TruncationBound::MaxBytes),
+            payload: |repo_path| {
+                RequestPayload::DiffContent(DiffContentRequest {
+                    repo_path,
+                    from: Some("HEAD~1".to_string()),
+                    to: Some("HEAD".to_string()),
+                    path: None,
+                    max_bytes: rl_api::MaxBytes::try_from(64 * 1024).unwrap(),
+                })
+            },
+        },
+        PathologicalScenario {
+            name: "huge_file_diff",
+            description: "Diff rewriting every line of a 100MB file, cut at 1MB",
+            pathology: Pathology::HugeDiff {
+                bytes: scaled(pathological::HUGE_DIFF_BYTES),
+            },
+            expect_truncated: Some(TruncationBound::MaxBytes),
+            payload: |repo_path| {
+                RequestPayload::DiffContent(DiffContentRequest {
+                    repo_path,
+                    from: Some("HEAD~1".to_string()),
+                    to: Some("HEAD".to_string()),
+                    path: Some("huge.txt".to_string()),
+                    max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(),
+                })
+            },
+        },
+        PathologicalScenario {
+            name: "long_file_blame",
+            description: "Blame of a file with a million lines, streamed in chunks",
+            pathology: Pathology::LongFile {
+                lines: scaled(pathological::LONG_FILE_LINES),
+            },
+            expect_truncated: None,
+            payload: |repo_path| {
+                RequestPayload::Blame(BlameRequest {
+                    repo_path,
+                    path: "long.txt".to_string(),
+                    revision: None,
+                    lines: None,
+                })
+            },
+        },
+        PathologicalScenario {
+            name: "deep_history_log_page",
+            description: "First log page of a history 100,000 commits deep",
+            pathology: deep,
+            expect_truncated: Some(TruncationBound::PageSize),
+            payload: |repo_path| {
+                RequestPayload::Log(LogRequest {
+                    repo_path,
+                    paging: rl_api::Paging {
+                        page_size: rl_api::PageSize::try_from(200).unwrap(),
+                        cursor: rl_api::Cursor::initial(),
+                    },
+                    revision_range: None,
+                })
+            },
+        },
+        PathologicalScenario {
+            name: "deep_history_blame",
+            description: "Blame of a file last changed 100,000 commits ago",
+            pathology: deep,
+            expect_truncated: None,
+            payload: |repo_path| {
+                RequestPayload::Blame(BlameRequest {
+                    repo_path,
+                    path: "root.txt".to_string(),
+                    revision: None,
+                    lines: None,
+                })
+            },
+        },
+    ]
+}
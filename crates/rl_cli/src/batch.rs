@@ -0,0 +1,170 @@
+//! Batch mode, for `repo-lens batch`.
+//!
+//! Reads one JSON [`Request`] per line from stdin and prints every response
+//! as it arrives, so a script can run many queries against one engine, and
+//! its caches, without starting a daemon. Responses carry the id of their
+//! request; with more than one request in flight they may interleave.
+//!
+//! A line that is not a valid request is answered with an `invalid_request`
+//! error and the rest of the batch still runs.
+//!
+//! Commands given several repositories run the same way, one request per
+//! repository, through [`run_all`].
+
+use crate::output::Printer;
+use rl_api::{Error, ErrorCode, Request, Response};
+use rl_core::RepoEngine;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+
+/// Run every request read from stdin against `engine`, at most
+/// `concurrency` at once, each given up on after `timeout`.
+pub(crate) async fn run(
+    engine: RepoEngine,
+    concurrency: usize,
+    timeout: Option<Duration>,
+    printer: &mut Printer,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let engine = Arc::new(engine);
+    let (response_tx, mut response_rx) = mpsc::channel(64);
+
+    let read = async move {
+        let limit = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut number = 0;
+        while let Some(line) = lines.next_line().await? {
+            number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(error) => {
+                    // The printer only stops early if stdout fails
+                    let _ = response_tx.send(invalid_line(number, &line, error)).await;
+                    continue;
+                }
+            };
+            let permit = limit.clone().acquire_owned().await?;
+            let engine = engine.clone();
+            let responses = response_tx.clone();
+            tasks.spawn(async move {
+                handle(&engine, request, responses, timeout).await;
+                drop(permit);
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        Ok::<_, Box<dyn std::error::Error>>(())
+    };
+    let print = async {
+        while let Some(response) = response_rx.recv().await {
+            printer.response(&response)?;
+        }
+        Ok::<_, Box<dyn std::error::Error>>(())
+    };
+    let (read, print) = tokio::join!(read, print);
+    print?;
+    read
+}
+
+/// Run `requests` against `engine` all at once, printing every response as
+/// it arrives. Returns the code the last failed request failed with.
+pub(crate) async fn run_all(
+    engine: RepoEngine,
+    requests: Vec<Request>,
+    timeout: Option<Duration>,
+    printer: &mut Printer,
+) -> Result<Option<ErrorCode>, Box<dyn std::error::Error>> {
+    let engine = Arc::new(engine);
+    let (response_tx, mut response_rx) = mpsc::channel(16);
+    let mut tasks = JoinSet::new();
+    for request in requests {
+        let engine = engine.clone();
+        let responses = response_tx.clone();
+        tasks.spawn(async move { handle(&engine, request, responses, timeout).await });
+    }
+    drop(response_tx);
+    let handlers = async move { while tasks.join_next().await.is_some() {} };
+    let print = async {
+        let mut failure = None;
+        while let Some(response) = response_rx.recv().await {
+            failure = printer.response(&response)?.or(failure);
+        }
+        Ok::<_, Box<dyn std::error::Error>>(failure)
+    };
+    let ((), failure) = tokio::join!(handlers, print);
+    failure
+}
+
+/// Handle one request, answering with `timeout` if it takes too long.
+async fn handle(
+    engine: &RepoEngine,
+    request: Request,
+    responses: mpsc::Sender<Response>,
+    timeout: Option<Duration>,
+) {
+    let Some(timeout) = timeout else {
+        return engine.handle_stream(request, responses).await;
+    };
+    let id = request.id.clone();
+    let handled = engine.handle_stream(request, responses.clone());
+    if tokio::time::timeout(timeout, handled).await.is_err() {
+        let _ = responses.send(timed_out(id, timeout)).await;
+    }
+}
+
+/// Response to a request the CLI stopped waiting for after `timeout`.
+pub(crate) fn timed_out(id: String, timeout: Duration) -> Response {
+    let error = Error::new(
+        ErrorCode::Timeout,
+        format!("No response within {} ms", timeout.as_millis()),
+    )
+    .with_remediation("Raise --timeout-ms or REPO_LENS_TIMEOUT_MS");
+    Response {
+        id,
+        result: Err(error),
+        meta: None,
+    }
+}
+
+/// Answer line `number` of the batch, which is not a request because of
+/// `error`. The response keeps the line's `id` when it has one.
+fn invalid_line(number: usize, line: &str, error: serde_json::Error) -> Response {
+    let id = serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|value| value.get("id")?.as_str().map(str::to_string))
+        .unwrap_or_default();
+    Response {
+        id,
+        result: Err(Error::new(
+            ErrorCode::InvalidRequest,
+            format!("Line {} is not a valid request: {}", number, error),
+        )),
+        meta: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_lines_are_answered_by_id() {
+        let answer = |number, line: &str| {
+            let error = serde_json::from_str::<Request>(line).unwrap_err();
+            invalid_line(number, line, error)
+        };
+
+        let response = answer(2, r#"{"version":"v0","id":"b","payload":{}}"#);
+        assert_eq!(response.id, "b");
+        let error = response.result.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidRequest);
+        assert!(error.message.starts_with("Line 2 "));
+
+        assert_eq!(answer(3, "not json").id, "");
+    }
+}
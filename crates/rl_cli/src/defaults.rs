@@ -0,0 +1,105 @@
+//! Defaults for the CLI's global options.
+//!
+//! Options people repeat on every invocation can be set once instead.
+//! Each is taken from the first of:
+//!
+//! 1. the command-line flag, e.g. `--page-size 100`
+//! 2. its environment variable, e.g. `REPO_LENS_PAGE_SIZE=100`
+//! 3. the CLI configuration file: `REPO_LENS_CLI_CONFIG` if set, else
+//!    `cli.toml` next to the engine's `config.toml` if it exists
+//! 4. the built-in default
+//!
+//! ```toml
+//! repo = "/src/monorepo"
+//! page_size = 100
+//! format = "table"
+//! timeout_ms = 10000
+//! connect = "/tmp/repo-lens.sock"
+//! ```
+//!
+//! The file configures only the CLI; engine settings stay in `config.toml`.
+
+use crate::output::Format;
+use serde::Deserialize;
+use std::io;
+use std::path::PathBuf;
+
+/// Environment variable naming the CLI configuration file to load.
+pub(crate) const CLI_CONFIG_ENV: &str = "REPO_LENS_CLI_CONFIG";
+
+/// Commits per page when nothing says otherwise.
+pub(crate) const PAGE_SIZE: u32 = 50;
+
+/// Global options read from the CLI configuration file.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Defaults {
+    /// Repository path
+    pub(crate) repo: Option<String>,
+    /// Page size for paginated commands
+    pub(crate) page_size: Option<u32>,
+    /// Output format
+    pub(crate) format: Option<Format>,
+    /// Timeout in milliseconds
+    pub(crate) timeout_ms: Option<u64>,
+    /// Socket of the daemon to send requests to
+    pub(crate) connect: Option<PathBuf>,
+}
+
+impl Defaults {
+    /// Read the CLI configuration file, if there is one.
+    pub(crate) fn load() -> io::Result<Self> {
+        let path = match std::env::var_os(CLI_CONFIG_ENV) {
+            Some(path) => PathBuf::from(path),
+            None => match rl_core::config::default_path() {
+                Some(path) if path.with_file_name("cli.toml").exists() => {
+                    path.with_file_name("cli.toml")
+                }
+                _ => return Ok(Self::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Failed to read {}: {}", path.display(), error),
+            )
+        })?;
+        Self::from_toml(&text)
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))
+    }
+
+    /// Parse CLI configuration file contents.
+    fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str(text).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid CLI configuration: {}", error),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_parse_and_reject_engine_settings() {
+        let defaults =
+            Defaults::from_toml("repo = \"/src/monorepo\"\npage_size = 100\nformat = \"table\"\n")
+                .unwrap();
+        assert_eq!(
+            defaults,
+            Defaults {
+                repo: Some("/src/monorepo".to_string()),
+                page_size: Some(100),
+                format: Some(Format::Table),
+                ..Default::default()
+            }
+        );
+
+        // Engine settings belong in config.toml
+        let error = Defaults::from_toml("max_concurrent_queries = 4").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,62 @@
+//! Process exit codes.
+//!
+//! A request the engine answers with an error exits with a code naming the
+//! [`ErrorCode`], so scripts can tell a missing repository from a conflict
+//! without parsing output. The numbers are part of the CLI's interface:
+//! existing ones never change, and new error codes get new numbers.
+
+use rl_api::ErrorCode;
+use std::process::ExitCode;
+
+/// The CLI failed outside the engine, e.g. to read its configuration or
+/// reach a daemon.
+pub(crate) const FAILURE: u8 = 1;
+
+/// Ctrl-C was pressed: the request was cancelled, as 128 + SIGINT says in
+/// shells.
+pub(crate) const INTERRUPTED: u8 = 130;
+
+/// Listing for `--help`.
+pub(crate) const HELP: &str = "\
+Exit codes:
+  0   success
+  1   the CLI itself failed (configuration, connection, output)
+  2   invalid command line
+  3   invalid_request        10  auth_required
+  4   unsupported_version    11  operation_canceled
+  5   unsupported_encoding   12  timeout
+  6   invalid_cursor         13  overloaded
+  7   repo_not_found         14  connection_lost
+  8   git_backend_error      15  rate_limited
+  9   conflict               16  quota_exceeded
+                             17  internal
+  130 interrupted with Ctrl-C, cancelling the request";
+
+/// Exit code for a request that failed with `code`.
+pub(crate) fn for_error(code: ErrorCode) -> u8 {
+    match code {
+        ErrorCode::InvalidRequest => 3,
+        ErrorCode::UnsupportedVersion => 4,
+        ErrorCode::UnsupportedEncoding => 5,
+        ErrorCode::InvalidCursor => 6,
+        ErrorCode::RepoNotFound => 7,
+        ErrorCode::GitBackendError => 8,
+        ErrorCode::Conflict => 9,
+        ErrorCode::AuthRequired => 10,
+        ErrorCode::OperationCanceled => 11,
+        ErrorCode::Timeout => 12,
+        ErrorCode::Overloaded => 13,
+        ErrorCode::ConnectionLost => 14,
+        ErrorCode::RateLimited => 15,
+        ErrorCode::QuotaExceeded => 16,
+        ErrorCode::Internal => 17,
+    }
+}
+
+/// Exit status for a request that ended with `failure`, if it failed.
+pub(crate) fn status(failure: Option<ErrorCode>) -> ExitCode {
+    match failure {
+        Some(code) => ExitCode::from(for_error(code)),
+        None => ExitCode::SUCCESS,
+    }
+}
@@ -1,36 +1,61 @@
 //! Thin CLI for repo-lens that maps subcommands to API requests.
 //!
 //! This binary provides a command-line interface to repo-lens functionality.
-//! By default, it outputs JSON for machine consumption. Use --pretty for human-readable output.
+//! By default, it outputs one line of JSON per response for machine
+//! consumption; `--format` picks JSON, YAML or tables meant for people instead.
+//!
+//! Each invocation runs its own engine unless `--connect` names the socket
+//! of a running `repo-lens serve --socket` daemon, in which case the request
+//! is sent there and answered from the daemon's warm caches.
+
+mod batch;
+mod defaults;
+mod exit;
+mod output;
+mod pager;
+mod progress;
+mod table;
+#[cfg(feature = "tui")]
+mod tui;
 
 use clap::{Parser, Subcommand};
-use rl_api::{request::*, ApiVersion, Request};
-use rl_core::RepoEngine;
+use output::{Format, Printer};
+use rl_api::{request::*, Request, Response};
+use rl_core::{EngineConfig, RepoEngine};
 use std::io::{self, Write};
+use std::process::ExitCode;
 
 #[derive(Parser)]
 #[command(name = "repo-lens")]
 #[command(about = "High-performance Git UI backend")]
 #[command(version)]
+#[command(after_help = exit::HELP)]
 struct Cli {
-    /// Repository path
-    #[arg(short, long, global = true)]
-    repo: Option<String>,
+    /// Repository path [default: the repository enclosing the working
+    /// directory]; repeat it to run the command in each repository at once
+    #[arg(short, long, global = true, env = "REPO_LENS_REPO")]
+    repo: Vec<String>,
 
-    /// Output pretty-printed JSON instead of compact JSON
+    /// Also run the command in each repository listed in this file, one
+    /// path per line (blank lines and lines starting with # are skipped)
     #[arg(long, global = true)]
-    pretty: bool,
+    repos_from: Option<std::path::PathBuf>,
+
+    /// Output format [default: ndjson]
+    #[arg(long, global = true, value_enum, env = "REPO_LENS_FORMAT")]
+    format: Option<Format>,
 
-    /// Page size for paginated commands
-    #[arg(long, global = true, default_value = "50")]
-    page_size: u32,
+    /// Page size for paginated commands [default: 50]
+    #[arg(long, global = true, env = "REPO_LENS_PAGE_SIZE")]
+    page_size: Option<u32>,
 
     /// Cursor for pagination
     #[arg(long, global = true, default_value = "")]
     cursor: String,
 
-    /// Timeout in milliseconds
-    #[arg(long, global = true)]
+    /// Give up on the request after this many milliseconds, failing with
+    /// `timeout`
+    #[arg(long, global = true, env = "REPO_LENS_TIMEOUT_MS")]
     timeout_ms: Option<u64>,
 
     /// Log filter (e.g., debug, rl_core=trace, rl_git=debug)
@@ -41,6 +66,59 @@ struct Cli {
     #[arg(long, global = true)]
     log_json: bool,
 
+    /// Write logs to this file instead of stderr
+    #[arg(long, global = true)]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Start a new log file every hour or day, named after the --log-file
+    /// path and the date: never, hourly or daily
+    #[arg(
+        long,
+        global = true,
+        default_value = "never",
+        requires = "log_file",
+        value_parser = parse_log_rotation
+    )]
+    log_rotate: rl_core::telemetry::LogRotation,
+
+    /// Rotated log files to keep, deleting the oldest
+    #[arg(long, global = true, requires = "log_file")]
+    log_keep: Option<usize>,
+
+    /// Export request spans to this OpenTelemetry collector over OTLP/HTTP
+    /// (e.g. http://localhost:4318)
+    #[arg(long, global = true, env = "REPO_LENS_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
+    /// Engine configuration file [default: $REPO_LENS_CONFIG, or
+    /// ~/.config/repo-lens/config.toml if it exists]
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+
+    /// Report how long each step took in the response's `meta`
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Id to send the request under, which the engine's logs carry
+    /// [default: cli-request]
+    #[arg(long, global = true)]
+    id: Option<String>,
+
+    /// Report the id the engine logs the request under in the response's
+    /// `meta`
+    #[arg(long, global = true)]
+    trace: bool,
+
+    /// Print long tables straight to the terminal instead of through
+    /// `$REPO_LENS_PAGER`, `$PAGER` or less
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// Send the request to the daemon listening on this Unix socket instead
+    /// of running an engine in this process
+    #[arg(long, global = true, env = "REPO_LENS_CONNECT")]
+    connect: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -72,6 +150,12 @@ enum Commands {
         /// To revision
         #[arg(long)]
         to: Option<String>,
+        /// Largest diff to read, in bytes (at most 10485760)
+        #[arg(long, default_value = "1048576", value_parser = parse_max_bytes)]
+        max_bytes: rl_api::MaxBytes,
+        /// Most hunks to read (at most 10000)
+        #[arg(long, default_value = "1000", value_parser = parse_max_hunks)]
+        max_hunks: rl_api::MaxHunks,
     },
     /// Get diff content
     Diff {
@@ -84,6 +168,9 @@ enum Commands {
         /// Path filter
         #[arg(long)]
         path: Option<String>,
+        /// Largest diff to read, in bytes (at most 10485760)
+        #[arg(long, default_value = "1048576", value_parser = parse_max_bytes)]
+        max_bytes: rl_api::MaxBytes,
     },
     /// Get blame information
     Blame {
@@ -92,6 +179,15 @@ enum Commands {
         /// Revision
         #[arg(long)]
         revision: Option<String>,
+        /// Annotate only lines START to END, numbered from 1
+        #[arg(short = 'L', value_name = "START,END", value_parser = parse_line_range)]
+        lines: Option<LineRange>,
+        /// Show authors' email addresses instead of their names in tables
+        #[arg(long)]
+        email: bool,
+        /// How tables show dates
+        #[arg(long, value_enum, default_value_t)]
+        date: table::DateFormat,
     },
     /// List branches
     Branches,
@@ -106,6 +202,9 @@ enum Commands {
         /// Create new branch
         #[arg(long)]
         create_branch: bool,
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Commit operation
     Commit {
@@ -139,6 +238,9 @@ enum Commands {
         /// Force push
         #[arg(long)]
         force: bool,
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Merge operation
     Merge {
@@ -147,6 +249,9 @@ enum Commands {
         /// Commit message
         #[arg(long)]
         message: Option<String>,
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Rebase operation
     Rebase {
@@ -155,29 +260,271 @@ enum Commands {
         /// Upstream branch
         #[arg(long)]
         upstream: Option<String>,
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Reset operation; resets the index too unless `--soft`
+    Reset {
+        /// Commit to move the current branch to
+        target: String,
+        /// Keep the index as it is
+        #[arg(long, conflicts_with = "hard")]
+        soft: bool,
+        /// Reset the working tree too, discarding uncommitted changes
+        #[arg(long)]
+        hard: bool,
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove untracked files
+    Clean {
+        /// Remove untracked directories too
+        #[arg(short, long)]
+        directories: bool,
+        /// Remove ignored files too
+        #[arg(short = 'x', long)]
+        ignored: bool,
+        /// Report what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Stash operation
+    #[command(args_conflicts_with_subcommands = true)]
     Stash {
-        /// Stash message
-        #[arg(long)]
-        message: Option<String>,
+        #[command(subcommand)]
+        command: Option<StashCommand>,
+        /// Without a subcommand, push
+        #[command(flatten)]
+        push: StashPush,
     },
+    /// Undo the most recent operation performed through repo-lens
+    Undo,
+    /// List the operations performed through repo-lens
+    Journal,
     /// Watch for repository changes
     Watch,
-    /// Run benchmarks
-    Bench,
+    /// Report the git backend and what it can do
+    Capabilities,
+    /// Time a benchmark scenario against the repository
+    Bench {
+        /// Scenario to run: engine_overhead, status, log_page or
+        /// diff_summary
+        #[arg(long, default_value = "engine_overhead")]
+        scenario: String,
+        /// Fail if more than 1% of warm runs take longer than this
+        #[arg(long)]
+        budget_ms: Option<f64>,
+        /// Warm runs to average
+        #[arg(long, default_value = "200")]
+        iterations: usize,
+    },
+    /// Print the JSON Schema of the API's requests and responses
+    Schema {
+        /// Print TypeScript definitions instead
+        #[arg(long)]
+        typescript: bool,
+    },
+    /// Serve the IPC protocol (stdio by default)
+    Serve {
+        /// TCP address to listen on (e.g. 127.0.0.1:7878)
+        #[arg(long)]
+        listen: Option<String>,
+        /// Unix socket path to listen on
+        #[arg(long, conflicts_with = "listen")]
+        socket: Option<String>,
+        /// Append one JSON record per request to this file ("-" for stderr)
+        #[arg(long)]
+        access_log: Option<String>,
+        /// Record every frame to this file for later replay
+        #[arg(long)]
+        record: Option<String>,
+        /// Serve Prometheus metrics over HTTP at /metrics on this address
+        /// (e.g. 127.0.0.1:9090)
+        #[arg(long)]
+        metrics: Option<String>,
+    },
+    /// Serve the gRPC API
+    #[cfg(feature = "grpc")]
+    ServeGrpc {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7879")]
+        listen: std::net::SocketAddr,
+    },
+    /// Browse history and diffs interactively
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Run newline-delimited JSON requests read from stdin and print their
+    /// responses; failed requests do not change the exit status
+    Batch {
+        /// Requests run at once
+        #[arg(long, default_value = "1")]
+        concurrency: usize,
+    },
+    /// Replay a recording made with `serve --record` against the engine
+    Replay {
+        /// Recording file
+        file: String,
+        /// Keep the recorded spacing between requests
+        #[arg(long)]
+        realtime: bool,
+    },
+}
+
+/// Stash operations; `stash` alone pushes.
+#[derive(Subcommand)]
+enum StashCommand {
+    /// Save uncommitted changes as a new entry and clean the working tree
+    Push(StashPush),
+    /// Apply an entry and drop it from the stash
+    Pop {
+        /// Entry, as N or stash@{N} [default: stash@{0}]
+        #[arg(value_parser = parse_stash_index)]
+        entry: Option<usize>,
+    },
+    /// Apply an entry, keeping it in the stash
+    Apply {
+        /// Entry, as N or stash@{N} [default: stash@{0}]
+        #[arg(value_parser = parse_stash_index)]
+        entry: Option<usize>,
+    },
+    /// Delete an entry
+    Drop {
+        /// Entry, as N or stash@{N} [default: stash@{0}]
+        #[arg(value_parser = parse_stash_index)]
+        entry: Option<usize>,
+    },
+    /// List the entries, most recent first
+    List,
+    /// Summarize the changes an entry holds
+    Show {
+        /// Entry, as N or stash@{N} [default: stash@{0}]
+        #[arg(value_parser = parse_stash_index)]
+        entry: Option<usize>,
+    },
+}
+
+#[derive(clap::Args)]
+struct StashPush {
+    /// Stash message
+    #[arg(short, long)]
+    message: Option<String>,
+    /// Stash untracked files too
+    #[arg(short = 'u', long)]
+    include_untracked: bool,
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let result = run(Cli::parse()).await;
+    rl_core::telemetry::flush_telemetry();
+    result
+}
+
+/// Run the command `cli` names.
+async fn run(cli: Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let config = EngineConfig::load(cli.config.as_deref())?;
+    let log_file = cli
+        .log_file
+        .clone()
+        .map(|path| rl_core::telemetry::LogFile {
+            path,
+            rotation: cli.log_rotate,
+            keep: cli.log_keep,
+        });
+    let otlp = cli
+        .otlp_endpoint
+        .clone()
+        .map(|endpoint| rl_core::telemetry::OtlpExporter {
+            endpoint,
+            service_name: "repo-lens".to_string(),
+        });
+    rl_core::telemetry::init_telemetry(
+        cli.log.as_deref().or(config.log_filter.as_deref()),
+        cli.log_json,
+        log_file.as_ref(),
+        otlp.as_ref(),
+    )?;
 
-    rl_core::telemetry::init_telemetry(cli.log.as_deref(), cli.log_json);
+    // Flags and their environment variables win over cli.toml
+    let defaults = defaults::Defaults::load()?;
+    let format = cli.format.or(defaults.format).unwrap_or_default();
+    let page_size = cli
+        .page_size
+        .or(defaults.page_size)
+        .unwrap_or(defaults::PAGE_SIZE);
+    let page_size = rl_api::PageSize::try_from(page_size)
+        .map_err(|e| format!("Invalid page size {}: {}", page_size, e))?;
+    let timeout = cli
+        .timeout_ms
+        .or(defaults.timeout_ms)
+        .map(std::time::Duration::from_millis);
+    let connect = cli.connect.or(defaults.connect);
 
-    // Get repository path
-    let repo_path = cli.repo.unwrap_or_else(|| ".".to_string());
+    // Get repository paths; a daemon resolves relative paths against its
+    // own working directory, so they are made absolute first
+    let mut repo_paths = cli.repo;
+    if let Some(file) = &cli.repos_from {
+        repo_paths.extend(read_repo_list(file)?);
+    }
+    // Without one, use the repository enclosing the working directory, as
+    // git does; failing to find one only matters to commands that need it
+    let mut undiscovered = None;
+    if repo_paths.is_empty() {
+        let repo_path = match defaults.repo {
+            Some(repo_path) => repo_path,
+            None => match rl_git::CliBackend::new()
+                .discover(&std::env::current_dir()?)
+                .await
+            {
+                Ok(root) => root.display().to_string(),
+                Err(error) => {
+                    undiscovered =
+                        Some(error.with_remediation(
+                            "Run repo-lens inside a git repository or pass --repo",
+                        ));
+                    ".".to_string()
+                }
+            },
+        };
+        repo_paths.push(repo_path);
+    }
+    if connect.is_some() {
+        for repo_path in &mut repo_paths {
+            *repo_path = std::path::absolute(&*repo_path)?.display().to_string();
+        }
+    }
+    let repo_path = repo_paths[0].clone();
+    if repo_paths.len() > 1
+        && matches!(
+            cli.command,
+            Commands::Serve { .. } | Commands::Batch { .. } | Commands::Replay { .. }
+        )
+    {
+        return Err("serve, batch and replay take a single repository".into());
+    }
+    if connect.is_some()
+        && matches!(
+            cli.command,
+            Commands::Serve { .. } | Commands::Batch { .. } | Commands::Replay { .. }
+        )
+    {
+        return Err("--connect cannot be used with serve, batch or replay".into());
+    }
 
     // Create request based on command
+    // Long human-readable output is paged, as git does
+    let paged = !cli.no_pager
+        && matches!(
+            cli.command,
+            Commands::Log { .. }
+                | Commands::Graph { .. }
+                | Commands::Diff { .. }
+                | Commands::Blame { .. }
+        );
+
+    let mut table_options = table::Options::default();
     let request_payload = match cli.command {
         Commands::Status => RequestPayload::Status(StatusRequest {
             repo_path: repo_path.clone(),
@@ -185,14 +532,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Log { revision_range } => RequestPayload::Log(LogRequest {
             repo_path: repo_path.clone(),
             paging: rl_api::Paging {
-                page_size: rl_api::PageSize::try_from(cli.page_size).unwrap(),
+                page_size: page_size.clone(),
                 cursor: rl_api::Cursor::from(cli.cursor.clone()),
             },
             revision_range,
         }),
         Commands::Graph { revision_range } => RequestPayload::Graph(GraphRequest {
             repo_path: repo_path.clone(),
-            window_size: rl_api::WindowSize::try_from(cli.page_size).unwrap(),
+            window_size: rl_api::WindowSize::try_from(page_size.get()).unwrap(),
             cursor: rl_api::Cursor::from(cli.cursor.clone()),
             revision_range,
         }),
@@ -200,25 +547,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             repo_path: repo_path.clone(),
             commit_id,
         }),
-        Commands::DiffSummary { from, to } => RequestPayload::DiffSummary(DiffSummaryRequest {
+        Commands::DiffSummary {
+            from,
+            to,
+            max_bytes,
+            max_hunks,
+        } => RequestPayload::DiffSummary(DiffSummaryRequest {
             repo_path: repo_path.clone(),
             from,
             to,
-            max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(), // 1MB default
-            max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            max_bytes,
+            max_hunks,
         }),
-        Commands::Diff { from, to, path } => RequestPayload::DiffContent(DiffContentRequest {
+        Commands::Diff {
+            from,
+            to,
+            path,
+            max_bytes,
+        } => RequestPayload::DiffContent(DiffContentRequest {
             repo_path: repo_path.clone(),
             from,
             to,
             path,
-            max_bytes: rl_api::MaxBytes::try_from(1024 * 1024).unwrap(), // 1MB default
+            max_bytes,
         }),
-        Commands::Blame { path, revision } => RequestPayload::Blame(BlameRequest {
-            repo_path: repo_path.clone(),
+        Commands::Blame {
             path,
             revision,
-        }),
+            lines,
+            email,
+            date,
+        } => {
+            table_options = table::Options {
+                email,
+                date,
+                ..Default::default()
+            };
+            RequestPayload::Blame(BlameRequest {
+                repo_path: repo_path.clone(),
+                path,
+                revision,
+                lines,
+            })
+        }
         Commands::Branches => RequestPayload::Branches(BranchesRequest {
             repo_path: repo_path.clone(),
         }),
@@ -231,10 +602,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Checkout {
             target,
             create_branch,
+            dry_run,
         } => RequestPayload::Checkout(CheckoutRequest {
             repo_path: repo_path.clone(),
             target,
             create_branch,
+            dry_run,
         }),
         Commands::Commit {
             message,
@@ -255,54 +628,653 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             remote,
             refspecs,
             force,
+            dry_run,
         } => RequestPayload::Push(PushRequest {
             repo_path: repo_path.clone(),
             remote,
             refspecs,
             force,
+            dry_run,
         }),
-        Commands::Merge { source, message } => RequestPayload::Merge(MergeRequest {
+        Commands::Merge {
+            source,
+            message,
+            dry_run,
+        } => RequestPayload::Merge(MergeRequest {
             repo_path: repo_path.clone(),
             source,
             message,
+            dry_run,
         }),
-        Commands::Rebase { onto, upstream } => RequestPayload::Rebase(RebaseRequest {
+        Commands::Rebase {
+            onto,
+            upstream,
+            dry_run,
+        } => RequestPayload::Rebase(RebaseRequest {
             repo_path: repo_path.clone(),
             onto,
             upstream,
+            dry_run,
         }),
-        Commands::Stash { message } => RequestPayload::Stash(StashRequest {
+        Commands::Reset {
+            target,
+            soft,
+            hard,
+            dry_run,
+        } => RequestPayload::Reset(ResetRequest {
+            repo_path: repo_path.clone(),
+            target,
+            mode: if soft {
+                ResetMode::Soft
+            } else if hard {
+                ResetMode::Hard
+            } else {
+                ResetMode::Mixed
+            },
+            dry_run,
+        }),
+        Commands::Clean {
+            directories,
+            ignored,
+            dry_run,
+        } => RequestPayload::Clean(CleanRequest {
+            repo_path: repo_path.clone(),
+            directories,
+            ignored,
+            dry_run,
+        }),
+        Commands::Stash { command, push } => {
+            let (action, index, push) = match command {
+                None => (StashAction::Push, None, push),
+                Some(StashCommand::Push(push)) => (StashAction::Push, None, push),
+                Some(StashCommand::Pop { entry }) => (StashAction::Pop, entry, push),
+                Some(StashCommand::Apply { entry }) => (StashAction::Apply, entry, push),
+                Some(StashCommand::Drop { entry }) => (StashAction::Drop, entry, push),
+                Some(StashCommand::List) => (StashAction::List, None, push),
+                Some(StashCommand::Show { entry }) => (StashAction::Show, entry, push),
+            };
+            table_options.stash = Some(action);
+            RequestPayload::Stash(StashRequest {
+                repo_path: repo_path.clone(),
+                action,
+                message: push.message,
+                include_untracked: push.include_untracked,
+                index,
+            })
+        }
+        Commands::Undo => RequestPayload::Undo(UndoRequest {
+            repo_path: repo_path.clone(),
+        }),
+        Commands::Journal => RequestPayload::Journal(JournalRequest {
             repo_path: repo_path.clone(),
-            message,
         }),
         Commands::Watch => RequestPayload::Watch(WatchRequest {
             repo_path: repo_path.clone(),
         }),
-        Commands::Bench => {
-            // For bench command, delegate to the bench binary
-            eprintln!("Use 'repo-lens-bench' for benchmarking");
-            std::process::exit(1);
+        Commands::Capabilities => RequestPayload::Capabilities(CapabilitiesRequest {}),
+        Commands::Bench {
+            scenario,
+            budget_ms,
+            iterations,
+        } => {
+            if connect.is_some() {
+                return Err("--connect cannot be used with bench".into());
+            }
+            if repo_paths.len() > 1 {
+                return Err("bench takes a single repository".into());
+            }
+            if let Some(error) = undiscovered {
+                return Err(error.into());
+            }
+            let scenarios = rl_bench::scenarios::generate_scenarios(repo_path.as_ref());
+            let scenario =
+                rl_bench::scenarios::find_scenario(&scenarios, &scenario).ok_or_else(|| {
+                    format!(
+                        "Unknown scenario {}; expected one of {}",
+                        scenario,
+                        rl_bench::scenarios::scenario_names().join(", ")
+                    )
+                })?;
+            let engine = RepoEngine::with_config(config);
+            let sampling = rl_bench::scenarios::Sampling::new(iterations);
+            let timings = rl_bench::scenarios::time_scenario(&engine, scenario, &sampling).await?;
+            let (status, reason) = rl_bench::scenarios::budget_status(&timings, budget_ms);
+            let mut printer = Printer::new(format, false);
+            printer.value(&serde_json::json!({
+                "scenario": scenario.name,
+                "repo_path": repo_path,
+                "timings": timings,
+                "status": status,
+                "reason": reason,
+            }))?;
+            printer.finish()?;
+            return Ok(match reason {
+                Some(_) => ExitCode::from(exit::FAILURE),
+                None => ExitCode::SUCCESS,
+            });
+        }
+        Commands::Schema { typescript } => {
+            let mut stdout = io::stdout().lock();
+            if typescript {
+                stdout.write_all(rl_api::schema::typescript().as_bytes())?;
+            } else {
+                serde_json::to_writer_pretty(&mut stdout, &rl_api::schema::json_schema())?;
+                writeln!(stdout)?;
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Commands::Serve {
+            listen,
+            socket,
+            access_log,
+            record,
+            metrics,
+        } => {
+            let engine = RepoEngine::with_config(config);
+            return serve(engine, listen, socket, access_log, record, metrics)
+                .await
+                .map(|()| ExitCode::SUCCESS);
+        }
+        #[cfg(feature = "grpc")]
+        Commands::ServeGrpc { listen } => {
+            if connect.is_some() {
+                return Err("--connect cannot be used with serve-grpc".into());
+            }
+            return serve_grpc(RepoEngine::with_config(config), listen)
+                .await
+                .map(|()| ExitCode::SUCCESS);
+        }
+        #[cfg(feature = "tui")]
+        Commands::Tui => {
+            if connect.is_some() {
+                return Err("--connect cannot be used with tui".into());
+            }
+            if repo_paths.len() > 1 {
+                return Err("tui takes a single repository".into());
+            }
+            if let Some(error) = undiscovered {
+                return Err(error.into());
+            }
+            return tui::run(RepoEngine::with_config(config), repo_path, page_size)
+                .await
+                .map(|()| ExitCode::SUCCESS);
+        }
+        Commands::Batch { concurrency } => {
+            let mut printer = Printer::new(format, true);
+            let engine = RepoEngine::with_config(config);
+            let ran = interruptible(batch::run(engine, concurrency, timeout, &mut printer)).await;
+            printer.finish()?;
+            return match ran {
+                Some(ran) => ran.map(|()| ExitCode::SUCCESS),
+                None => Ok(ExitCode::from(exit::INTERRUPTED)),
+            };
+        }
+        Commands::Replay { file, realtime } => {
+            let engine = RepoEngine::with_config(config);
+            return replay(&engine, &file, realtime, format)
+                .await
+                .map(|()| ExitCode::SUCCESS);
         }
     };
 
-    let request = Request {
-        version: ApiVersion::V0,
-        id: "cli-request".to_string(),
-        payload: request_payload,
+    let id = cli.id.clone().unwrap_or_else(|| "cli-request".to_string());
+    if let Some(error) = undiscovered.filter(|_| !request_payload.repo_path().is_empty()) {
+        let mut printer = Printer::new(format, false);
+        let failure = printer.response(&Response {
+            id,
+            result: Err(error),
+            meta: None,
+        })?;
+        printer.finish()?;
+        return Ok(exit::status(failure));
+    }
+
+    // With several repositories the command runs in each, answered under
+    // the repository's path as the response id, after `--id` if given
+    let request = |id: String, payload: RequestPayload| Request {
+        timings: cli.timings,
+        trace: cli.trace,
+        ..Request::new(id, payload)
+    };
+    let requests: Vec<Request> = if repo_paths.len() == 1 || request_payload.repo_path().is_empty()
+    {
+        vec![request(id, request_payload)]
+    } else {
+        repo_paths
+            .into_iter()
+            .map(|repo_path| {
+                let mut payload = request_payload.clone();
+                payload.set_repo_path(repo_path.clone());
+                let id = match &cli.id {
+                    Some(id) => format!("{}:{}", id, repo_path),
+                    None => repo_path,
+                };
+                request(id, payload)
+            })
+            .collect()
     };
 
-    // Create engine and handle request
-    let engine = RepoEngine::new();
-    let response = engine.handle(request).await;
+    // Handle the requests; streaming requests print every chunk
+    let many = requests.len() > 1;
+    let mut printer = Printer::new(format, many || requests[0].payload.streams());
+    printer.table_options(table_options);
+    if many {
+        printer.head_by_id();
+    }
+    if paged {
+        printer.page()?;
+    }
+    let handled = match &connect {
+        // Dropping the requests, on Ctrl-C, stops them and the git processes
+        // they started
+        None => {
+            let engine = RepoEngine::with_config(config);
+            interruptible(batch::run_all(engine, requests, timeout, &mut printer)).await
+        }
+        Some(socket) => {
+            let mut failure = None;
+            let mut interrupted = false;
+            for request in requests {
+                match send_to_daemon(socket, request, timeout, &mut printer).await? {
+                    Sent::Answered(failed) => failure = failed.or(failure),
+                    Sent::Interrupted => {
+                        interrupted = true;
+                        break;
+                    }
+                }
+            }
+            (!interrupted).then_some(Ok(failure))
+        }
+    };
+    let Some(failure) = handled else {
+        printer.finish()?;
+        return Ok(ExitCode::from(exit::INTERRUPTED));
+    };
+    let failure = failure?;
+    printer.finish()?;
 
-    // Output response
-    let json = if cli.pretty {
-        serde_json::to_string_pretty(&response)?
-    } else {
-        serde_json::to_string(&response)?
+    Ok(exit::status(failure))
+}
+
+/// Run `work` to completion, or until Ctrl-C is pressed, when it is dropped
+/// and `None` returned.
+async fn interruptible<T>(work: impl std::future::Future<Output = T>) -> Option<T> {
+    let interrupt = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            // Without a handler Ctrl-C just ends the process
+            std::future::pending::<()>().await;
+        }
+        eprintln!("Interrupted");
+    };
+    tokio::select! {
+        output = work => Some(output),
+        () = interrupt => None,
+    }
+}
+
+/// Read the repositories listed in `file` for `--repos-from`.
+fn read_repo_list(file: &std::path::Path) -> io::Result<Vec<String>> {
+    let text = std::fs::read_to_string(file).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("Failed to read {}: {}", file.display(), error),
+        )
+    })?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Parse a `-L` line range such as `10,20`.
+fn parse_line_range(value: &str) -> Result<LineRange, String> {
+    let (start, end) = value
+        .split_once(',')
+        .ok_or_else(|| "expected START,END".to_string())?;
+    let line = |text: &str| {
+        text.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("{}: {}", text, e))
+    };
+    Ok(LineRange {
+        start: line(start)?,
+        end: line(end)?,
+    })
+}
+
+/// Parse a stash entry given as `N` or `stash@{N}`.
+fn parse_stash_index(value: &str) -> Result<usize, String> {
+    let index = value
+        .strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(value);
+    index
+        .parse()
+        .map_err(|_| "expected N or stash@{N}".to_string())
+}
+
+/// Parse `--max-bytes`, within the bounds rl_api accepts.
+fn parse_max_bytes(value: &str) -> Result<rl_api::MaxBytes, String> {
+    let bytes = value.parse::<u64>().map_err(|e| e.to_string())?;
+    rl_api::MaxBytes::try_from(bytes).map_err(|_| {
+        format!(
+            "must be between 1 and {} bytes",
+            rl_api::bounds::MAX_DIFF_BYTES
+        )
+    })
+}
+
+/// Parse `--log-rotate`.
+fn parse_log_rotation(value: &str) -> Result<rl_core::telemetry::LogRotation, String> {
+    use rl_core::telemetry::LogRotation;
+    match value {
+        "never" => Ok(LogRotation::Never),
+        "hourly" => Ok(LogRotation::Hourly),
+        "daily" => Ok(LogRotation::Daily),
+        _ => Err("expected never, hourly or daily".to_string()),
+    }
+}
+
+/// Parse `--max-hunks`, within the bounds rl_api accepts.
+fn parse_max_hunks(value: &str) -> Result<rl_api::MaxHunks, String> {
+    let hunks = value.parse::<u32>().map_err(|e| e.to_string())?;
+    rl_api::MaxHunks::try_from(hunks)
+        .map_err(|_| format!("must be between 1 and {}", rl_api::bounds::MAX_DIFF_HUNKS))
+}
+
+/// How a request sent to the daemon ended.
+enum Sent {
+    /// With its final response, or a timeout; failed with the code, if any
+    Answered(Option<rl_api::ErrorCode>),
+    /// With Ctrl-C
+    Interrupted,
+}
+
+/// How long to wait for the daemon to answer a cancelled request.
+const CANCEL_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Send `request` to the daemon listening on `socket` and print every
+/// response for it.
+///
+/// On timeout or Ctrl-C the daemon is told to cancel the request, stopping
+/// the git processes it started, before giving up on it.
+///
+/// Authenticates with `REPO_LENS_TOKEN` or `REPO_LENS_TOKEN_FILE` when set.
+#[cfg(unix)]
+async fn send_to_daemon(
+    socket: &std::path::Path,
+    request: Request,
+    timeout: Option<std::time::Duration>,
+    printer: &mut Printer,
+) -> Result<Sent, Box<dyn std::error::Error>> {
+    let client = rl_ipc::IpcClient::connect_unix(socket, rl_ipc::TransportConfig::default())
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", socket.display(), e))?;
+    if let Some(token) = rl_ipc::AuthToken::from_env()? {
+        client.authenticate(&token).await?;
+    }
+    client.hello().await?;
+
+    // Non-streaming requests end with their only response
+    let id = request.id.clone();
+    let mut responses = client.send_streaming_request(request).await?;
+    let deadline = async {
+        match timeout {
+            Some(timeout) => {
+                tokio::time::sleep(timeout).await;
+                timeout
+            }
+            None => std::future::pending().await,
+        }
+    };
+    let stop = interruptible(deadline);
+    tokio::pin!(stop);
+    let mut finished = false;
+    let mut failure = None;
+    loop {
+        tokio::select! {
+            response = responses.recv() => {
+                let Some(response) = response else { break };
+                finished = response.is_final();
+                failure = printer.response(&response)?;
+            }
+            stopped = &mut stop => {
+                // The cancelled request's answer shows the daemon got the cancel
+                let _ = client.cancel(&id).await;
+                let drained = async { while responses.recv().await.is_some() {} };
+                let _ = tokio::time::timeout(CANCEL_GRACE, drained).await;
+                return Ok(match stopped {
+                    Some(timeout) => Sent::Answered(printer.response(&batch::timed_out(id, timeout))?),
+                    None => Sent::Interrupted,
+                });
+            }
+        }
+    }
+    if !finished {
+        return Err("Connection to the daemon closed before the request finished".into());
+    }
+    Ok(Sent::Answered(failure))
+}
+
+#[cfg(not(unix))]
+async fn send_to_daemon(
+    socket: &std::path::Path,
+    _request: Request,
+    _timeout: Option<std::time::Duration>,
+    _printer: &mut Printer,
+) -> Result<Sent, Box<dyn std::error::Error>> {
+    Err(format!(
+        "Unix sockets are not supported on this platform: {}",
+        socket.display()
+    )
+    .into())
+}
+
+/// Run the IPC server until its listener closes.
+///
+/// Socket clients must authenticate when `REPO_LENS_TOKEN` or
+/// `REPO_LENS_TOKEN_FILE` is set.
+async fn serve(
+    engine: RepoEngine,
+    listen: Option<String>,
+    socket: Option<String>,
+    access_log: Option<String>,
+    record: Option<String>,
+    metrics: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let access_log = match access_log.as_deref() {
+        Some("-") => Some(rl_ipc::AccessLog::stderr()),
+        Some(path) => Some(rl_ipc::AccessLog::open(path)?),
+        None => None,
     };
+    let config = rl_ipc::TransportConfig {
+        auth_token: rl_ipc::AuthToken::from_env()?,
+        access_log,
+        recorder: record.map(rl_ipc::Recorder::create).transpose()?,
+        ..Default::default()
+    };
+    let server = rl_ipc::IpcServer::with_config(engine, config.clone());
+    reload_on_hangup(server.engine().clone())?;
+    if let Some(addr) = metrics {
+        let endpoint = rl_ipc::MetricsEndpoint::bind(&addr, server.engine().clone()).await?;
+        eprintln!(
+            "Serving metrics on http://{}/metrics",
+            endpoint.local_addr()?
+        );
+        tokio::spawn(async move {
+            if let Err(e) = endpoint.serve().await {
+                eprintln!("Metrics endpoint failed: {}", e);
+            }
+        });
+    }
+
+    if let Some(addr) = listen {
+        let listener = rl_ipc::TcpListener::bind(&addr, &config).await?;
+        eprintln!("Listening on {}", listener.local_addr()?);
+        return server.serve(listener).await;
+    }
+
+    if let Some(path) = socket {
+        #[cfg(unix)]
+        {
+            let listener = rl_ipc::UnixListener::bind(&path, &config)?;
+            eprintln!("Listening on {}", path);
+            return server.serve(listener).await;
+        }
+        #[cfg(not(unix))]
+        return Err(format!("Unix sockets are not supported on this platform: {}", path).into());
+    }
+
+    server.run().await
+}
+
+/// Run the gRPC server until the transport fails.
+///
+/// Calls must carry a bearer token when `REPO_LENS_TOKEN` or
+/// `REPO_LENS_TOKEN_FILE` is set.
+#[cfg(feature = "grpc")]
+async fn serve_grpc(
+    engine: RepoEngine,
+    listen: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server = rl_grpc::GrpcServer::with_auth_token(engine, rl_ipc::AuthToken::from_env()?);
+    reload_on_hangup(server.engine().clone())?;
+    eprintln!("Serving gRPC on {}", listen);
+    server.serve(listen).await?;
+    Ok(())
+}
+
+/// Reload the engine's configuration whenever the process receives SIGHUP.
+#[cfg(unix)]
+fn reload_on_hangup(engine: std::sync::Arc<RepoEngine>) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
 
-    writeln!(io::stdout(), "{}", json)?;
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match engine.reload_config() {
+                Ok(restart) if restart.is_empty() => eprintln!("Reloaded configuration"),
+                Ok(restart) => eprintln!(
+                    "Reloaded configuration; restart to apply changes to {}",
+                    restart.join(", ")
+                ),
+                Err(e) => eprintln!("Failed to reload configuration: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reload_on_hangup(_engine: std::sync::Arc<RepoEngine>) -> io::Result<()> {
+    Ok(())
+}
+
+/// Replay a recorded session, printing one outcome per request and a
+/// summary of responses that changed.
+async fn replay(
+    engine: &RepoEngine,
+    file: &str,
+    realtime: bool,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frames = rl_ipc::read_recording(io::BufReader::new(std::fs::File::open(file)?))?;
+    let outcomes = rl_ipc::replay(engine, &frames, realtime).await;
+
+    let mut printer = Printer::new(format, true);
+    for outcome in &outcomes {
+        printer.value(outcome)?;
+    }
+    printer.finish()?;
 
+    let changed = outcomes.iter().filter(|outcome| !outcome.matches()).count();
+    writeln!(
+        io::stderr(),
+        "Replayed {} requests, {} differ from the recording",
+        outcomes.len(),
+        changed
+    )?;
     Ok(())
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use rl_api::response::ResponsePayload;
+    use rl_core::handler::{Handler, HandlerContext};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Never answers Status, noting when the daemon gives up on it.
+    struct Hang(Arc<AtomicBool>);
+
+    /// Set once the request it was made for is dropped unanswered.
+    struct Dropped(Arc<AtomicBool>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Handler for Hang {
+        type Request = StatusRequest;
+
+        async fn handle(
+            &self,
+            _request: StatusRequest,
+            _cx: &HandlerContext<'_>,
+        ) -> Result<ResponsePayload, rl_api::Error> {
+            let _dropped = Dropped(self.0.clone());
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_request_is_cancelled_on_the_daemon() {
+        let socket =
+            std::env::temp_dir().join(format!("rl-cli-timeout-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut engine = RepoEngine::new();
+        engine.register_handler(Hang(cancelled.clone()));
+        let config = rl_ipc::TransportConfig::default();
+        let listener = rl_ipc::UnixListener::bind(&socket, &config).unwrap();
+        let server = rl_ipc::IpcServer::with_config(engine, config);
+        tokio::spawn(async move {
+            let _ = server.serve(listener).await;
+        });
+
+        let request = Request::new(
+            "slow",
+            RequestPayload::Status(StatusRequest {
+                repo_path: "/hang".to_string(),
+            }),
+        );
+        // Collected for one JSON array, which is never printed
+        let mut printer = Printer::new(Format::Json, true);
+        let started = Instant::now();
+        let sent = send_to_daemon(
+            &socket,
+            request,
+            Some(Duration::from_millis(50)),
+            &mut printer,
+        )
+        .await
+        .unwrap();
+
+        let Sent::Answered(failure) = sent else {
+            panic!("expected the request to time out");
+        };
+        assert_eq!(failure, Some(rl_api::ErrorCode::Timeout));
+        assert_eq!(exit::status(failure), ExitCode::from(12));
+        // The daemon answered the cancel before the grace period ran out
+        assert!(started.elapsed() < CANCEL_GRACE);
+        assert!(cancelled.load(Ordering::SeqCst));
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -0,0 +1,173 @@
+//! Printing responses in the format chosen with `--format`.
+//!
+//! Every subcommand prints through a [`Printer`], so the format applies the
+//! same way whether responses come from an engine in this process, a daemon
+//! or a replayed recording.
+
+use crate::pager::Pager;
+use crate::progress::Bars;
+use crate::table;
+use clap::ValueEnum;
+use rl_api::response::ResponsePayload;
+use rl_api::{ErrorCode, Response};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// How responses are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    /// One compact JSON document; the responses of a streaming request are
+    /// collected into an array
+    Json,
+    /// One compact JSON document per line, printed as each response arrives
+    #[default]
+    Ndjson,
+    /// One YAML document per response
+    Yaml,
+    /// Aligned tables and summaries, colored on a terminal
+    Table,
+}
+
+/// Prints the responses to one command in a [`Format`].
+pub(crate) struct Printer {
+    format: Format,
+    /// Whether several documents may follow, so `json` prints an array
+    many: bool,
+    /// Documents held back for the `json` array
+    collected: Vec<String>,
+    /// Pager standard output goes through, if any
+    pager: Option<Pager>,
+    /// What tables show
+    table: table::Options,
+    /// Whether tables are headed by the id of the responses below, and the
+    /// id last headed
+    head_by_id: bool,
+    headed: Option<String>,
+    /// Progress bars on stderr, once a table shows progress on a terminal
+    bars: Option<Bars>,
+}
+
+impl Printer {
+    /// Print in `format`; `many` if the command may print several documents.
+    pub(crate) fn new(format: Format, many: bool) -> Self {
+        Self {
+            format,
+            many,
+            collected: Vec::new(),
+            pager: None,
+            table: table::Options::default(),
+            head_by_id: false,
+            headed: None,
+            bars: None,
+        }
+    }
+
+    /// Choose what tables show.
+    pub(crate) fn table_options(&mut self, options: table::Options) {
+        self.table = options;
+    }
+
+    /// Head each run of tables with the id of its responses, e.g. the
+    /// repository when a command runs in several.
+    pub(crate) fn head_by_id(&mut self) {
+        self.head_by_id = true;
+    }
+
+    /// Send what is printed to stdout through a pager, when one applies.
+    /// Only tables are paged; other formats are meant for programs.
+    pub(crate) fn page(&mut self) -> io::Result<()> {
+        if self.format == Format::Table {
+            self.pager = Pager::start()?;
+        }
+        Ok(())
+    }
+
+    fn out(&mut self) -> Box<dyn Write + '_> {
+        match &mut self.pager {
+            Some(pager) => Box::new(pager),
+            None => Box::new(io::stdout().lock()),
+        }
+    }
+
+    /// Print one response, returning the code it failed with if it is an
+    /// error. Tables show errors on stderr; other formats keep them on
+    /// stdout with the rest of the output.
+    pub(crate) fn response(&mut self, response: &Response) -> io::Result<Option<ErrorCode>> {
+        let failure = response.result.as_ref().err().map(|error| error.code);
+        if self.format == Format::Table {
+            // Progress is drawn as bars, until the request answers
+            if let Ok(ResponsePayload::Progress(chunk)) = &response.result {
+                if !chunk.is_final {
+                    if self.bars.is_none() {
+                        self.bars = Bars::start(self.head_by_id);
+                    }
+                    if let Some(bars) = &mut self.bars {
+                        bars.update(&response.id, &chunk.data);
+                        return Ok(None);
+                    }
+                }
+            }
+            if let Some(bars) = &mut self.bars {
+                bars.finish(&response.id);
+            }
+            let style = table::Style::detect(failure.is_some());
+            let mut text = table::render(response, &self.table, style);
+            if self.head_by_id && self.headed.as_ref() != Some(&response.id) {
+                let gap = if self.headed.is_some() { "\n" } else { "" };
+                let heading = table::heading(&response.id, style);
+                text = format!("{}{}\n{}", gap, heading, text);
+                self.headed = Some(response.id.clone());
+            }
+            let bars = self.bars.take();
+            let mut print = || match failure {
+                Some(_) => write!(io::stderr(), "{}", text),
+                None => write!(self.out(), "{}", text),
+            };
+            let printed = match &bars {
+                Some(bars) => bars.suspend(print),
+                None => print(),
+            };
+            self.bars = bars;
+            printed?;
+        } else {
+            self.value(response)?;
+        }
+        Ok(failure)
+    }
+
+    /// Print any other document, e.g. a replay outcome. Tables show it as
+    /// indented JSON.
+    pub(crate) fn value<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        match self.format {
+            Format::Json if self.many => {
+                self.collected.push(serde_json::to_string(value)?);
+                Ok(())
+            }
+            Format::Json | Format::Ndjson => {
+                writeln!(self.out(), "{}", serde_json::to_string(value)?)
+            }
+            Format::Yaml => {
+                // Through JSON, so enums read as keys rather than YAML tags
+                let value = serde_json::to_value(value)?;
+                let yaml = serde_yaml::to_string(&value).map_err(io::Error::other)?;
+                write!(self.out(), "---\n{}", yaml)
+            }
+            Format::Table => {
+                writeln!(self.out(), "{}", serde_json::to_string_pretty(value)?)
+            }
+        }
+    }
+
+    /// Print whatever was held back, then wait for the pager to be quit.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if self.format == Format::Json && self.many {
+            let array = format!("[{}]", self.collected.join(","));
+            writeln!(self.out(), "{}", array)?;
+        }
+        match self.pager {
+            Some(pager) => pager.finish(),
+            None => Ok(()),
+        }
+    }
+}
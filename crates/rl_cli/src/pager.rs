@@ -0,0 +1,94 @@
+//! Paging long output, as git does.
+//!
+//! Diffs, logs, graphs and blame shown as tables on a terminal go through
+//! `$REPO_LENS_PAGER`, else `$PAGER`, else `less`. With `LESS` unset, less
+//! is started as `less -FRX`: it exits at once if the output fits on one
+//! screen, passes colors through and leaves the output on the terminal. An
+//! empty pager or `cat` turns paging off, as does `--no-pager`.
+
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// Environment variable naming the pager for repo-lens alone.
+const PAGER_ENV: &str = "REPO_LENS_PAGER";
+
+/// A running pager, reading what is written to it.
+pub(crate) struct Pager {
+    child: Child,
+    input: Option<ChildStdin>,
+}
+
+impl Pager {
+    /// Start the configured pager, if output goes to a terminal and paging
+    /// is not turned off.
+    pub(crate) fn start() -> io::Result<Option<Self>> {
+        if !io::stdout().is_terminal() {
+            return Ok(None);
+        }
+        let command = std::env::var(PAGER_ENV)
+            .or_else(|_| std::env::var("PAGER"))
+            .unwrap_or_else(|_| "less".to_string());
+        let command = command.trim();
+        if command.is_empty() || command == "cat" {
+            return Ok(None);
+        }
+
+        // The pager may be a command line, e.g. `less -S`
+        let mut pager = if cfg!(windows) {
+            let mut pager = Command::new("cmd");
+            pager.arg("/C").arg(command);
+            pager
+        } else {
+            let mut pager = Command::new("sh");
+            pager.arg("-c").arg(command);
+            pager
+        };
+        if std::env::var_os("LESS").is_none() {
+            pager.env("LESS", "FRX");
+        }
+        let mut child = pager.stdin(Stdio::piped()).spawn().map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Failed to start pager {}: {}", command, error),
+            )
+        })?;
+        let input = child.stdin.take();
+        Ok(Some(Self { child, input }))
+    }
+
+    /// Wait for the user to quit the pager.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        drop(self.input.take());
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(input) = &mut self.input else {
+            return Ok(buf.len());
+        };
+        match input.write(buf) {
+            // Quitting the pager early just discards the rest
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                self.input = None;
+                Ok(buf.len())
+            }
+            written => written,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.input {
+            Some(input) => match input.flush() {
+                Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                    self.input = None;
+                    Ok(())
+                }
+                flushed => flushed,
+            },
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Progress bars for long operations, for `--format table`.
+//!
+//! Fetch, push and rebase stream `Progress` chunks as git reports each
+//! stage (counting, compressing, receiving and writing objects, resolving
+//! deltas, replaying commits). On a terminal every stage is drawn as a bar
+//! on stderr, with git's counts and transfer rate beside it; a finished
+//! stage's bar stays on screen. Other formats print the chunks as they are.
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rl_api::response::ProgressUpdate;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+
+/// Bars of the operations in progress.
+pub(crate) struct Bars {
+    multi: MultiProgress,
+    /// Stage each request is in and its bar, by request id
+    bars: HashMap<String, (String, ProgressBar)>,
+    /// Whether bars name the request they belong to
+    label_ids: bool,
+}
+
+impl Bars {
+    /// Draw bars on stderr, if it is a terminal. With `label_ids`, bars
+    /// start with the id of their request, e.g. the repository.
+    pub(crate) fn start(label_ids: bool) -> Option<Self> {
+        if !io::stderr().is_terminal() {
+            return None;
+        }
+        Some(Self {
+            multi: MultiProgress::with_draw_target(ProgressDrawTarget::stderr()),
+            bars: HashMap::new(),
+            label_ids,
+        })
+    }
+
+    /// Show `update` for request `id`, finishing the bar of the stage it
+    /// was in if this starts another.
+    pub(crate) fn update(&mut self, id: &str, update: &ProgressUpdate) {
+        if let Some((stage, bar)) = self.bars.get(id) {
+            if *stage != update.stage {
+                bar.finish();
+                self.bars.remove(id);
+            }
+        }
+        let (_, bar) = self.bars.entry(id.to_string()).or_insert_with(|| {
+            let bar = self.multi.add(ProgressBar::new(100));
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{prefix:>24.bold} [{bar:30.cyan/blue}] {percent:>3}% {msg}",
+                )
+                .expect("valid template")
+                .progress_chars("=> "),
+            );
+            bar.set_prefix(match self.label_ids {
+                true => format!("{} {}", id, update.stage),
+                false => update.stage.clone(),
+            });
+            (update.stage.clone(), bar)
+        });
+        bar.set_position(update.progress.into());
+        bar.set_message(details(update));
+    }
+
+    /// Run `print` with the bars cleared, so its output is not drawn over.
+    pub(crate) fn suspend<R>(&self, print: impl FnOnce() -> R) -> R {
+        self.multi.suspend(print)
+    }
+
+    /// Finish the bar of request `id`, once it has answered.
+    pub(crate) fn finish(&mut self, id: &str) {
+        if let Some((_, bar)) = self.bars.remove(id) {
+            bar.finish();
+        }
+    }
+}
+
+/// What git reported beside the percentage, e.g.
+/// `(450/1000), 1.20 MiB | 2.00 MiB/s`.
+fn details(update: &ProgressUpdate) -> String {
+    let Some(message) = &update.message else {
+        return String::new();
+    };
+    let rest = message
+        .strip_prefix(update.stage.as_str())
+        .unwrap_or(message)
+        .trim_start_matches(':');
+    let rest = rest.split_once('%').map_or(rest, |(_, rest)| rest);
+    rest.trim()
+        .trim_end_matches(", done.")
+        .trim_end_matches(", done")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_details_keep_counts_and_rate() {
+        let update = |stage: &str, message: &str| ProgressUpdate {
+            stage: stage.to_string(),
+            progress: 45,
+            message: Some(message.to_string()),
+        };
+        assert_eq!(
+            details(&update(
+                "Receiving objects",
+                "Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s"
+            )),
+            "(450/1000), 1.20 MiB | 2.00 MiB/s"
+        );
+        assert_eq!(
+            details(&update(
+                "Resolving deltas",
+                "Resolving deltas: 100% (20/20), done."
+            )),
+            "(20/20)"
+        );
+        assert_eq!(details(&update("Rebasing", "Rebasing (3/10)")), "(3/10)");
+    }
+}
@@ -0,0 +1,890 @@
+//! Human-readable rendering of responses, for `--format table`.
+//!
+//! Status, branches, tags, stash entries, log pages, blame and diff
+//! summaries are laid out as aligned tables and summaries in the spirit of
+//! git's own porcelain, diffs are shown as unified diffs, and graph windows
+//! are drawn like `git log --graph --decorate`; other payloads fall back to
+//! indented JSON. Colors are used only when the output goes to a terminal
+//! and `NO_COLOR` is not set.
+
+use clap::ValueEnum;
+use rl_api::request::StashAction;
+use rl_api::response::{
+    BlameChunk, BranchList, ChangeType, CommitGraphNode, CommitGraphWindow, CommitListPage,
+    DiffChunk, DiffLineType, DiffSummary, LaneType, ResponsePayload, StashResult, StatusView,
+    TagList,
+};
+use rl_api::{Error, Response};
+use std::io::IsTerminal;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const MAGENTA: &str = "35";
+const CYAN: &str = "36";
+const DIM: &str = "2";
+const BOLD: &str = "1";
+
+/// Whether output is colored.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Style {
+    color: bool,
+}
+
+impl Style {
+    /// Color output if it goes to a terminal, stderr for errors or stdout
+    /// otherwise, and `NO_COLOR` is not set.
+    pub(crate) fn detect(error: bool) -> Self {
+        let terminal = if error {
+            std::io::stderr().is_terminal()
+        } else {
+            std::io::stdout().is_terminal()
+        };
+        Self {
+            color: terminal && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Rows of cells, each cell with the color it is painted in.
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<(String, Option<&'static str>)>>,
+}
+
+impl Table {
+    fn row(&mut self, cells: Vec<(String, Option<&'static str>)>) {
+        self.rows.push(cells);
+    }
+
+    /// Pad every column but the last to its widest cell.
+    fn render(&self, style: Style, out: &mut String) {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|(text, _)| text.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        for row in &self.rows {
+            let mut line = String::new();
+            for (column, (text, color)) in row.iter().enumerate() {
+                if column > 0 {
+                    line.push_str("  ");
+                }
+                let padded = if column + 1 < row.len() {
+                    format!("{:<width$}", text, width = widths[column])
+                } else {
+                    text.clone()
+                };
+                match color {
+                    Some(code) => line.push_str(&style.paint(code, &padded)),
+                    None => line.push_str(&padded),
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+}
+
+/// How dates are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum DateFormat {
+    /// How long ago, e.g. `3 days ago`
+    #[default]
+    Relative,
+    /// Date and time in UTC, e.g. `2024-05-01 09:30:00 +0000`
+    Iso,
+    /// Date alone, e.g. `2024-05-01`
+    Short,
+    /// Seconds since the Unix epoch
+    Unix,
+}
+
+/// Choices about what tables show, set from the command line.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Options {
+    /// Show authors' email addresses instead of their names
+    pub(crate) email: bool,
+    /// How dates are shown
+    pub(crate) date: DateFormat,
+    /// Stash operation answered, to word its result
+    pub(crate) stash: Option<StashAction>,
+}
+
+/// Render `response` for a person to read, ending with a newline.
+pub(crate) fn render(response: &Response, options: &Options, style: Style) -> String {
+    let mut out = String::new();
+    match &response.result {
+        Ok(ResponsePayload::Status(status)) => render_status(status, style, &mut out),
+        Ok(ResponsePayload::Branches(branches)) => render_branches(branches, style, &mut out),
+        Ok(ResponsePayload::Tags(tags)) => render_tags(tags, style, &mut out),
+        Ok(ResponsePayload::Log(page)) => render_log(page, style, &mut out),
+        Ok(ResponsePayload::Graph(window)) => render_graph(window, style, &mut out),
+        Ok(ResponsePayload::DiffSummary(summary)) => render_diff_summary(summary, style, &mut out),
+        Ok(ResponsePayload::DiffContent(chunk)) => render_diff(&chunk.data, style, &mut out),
+        Ok(ResponsePayload::Blame(chunk)) => render_blame(&chunk.data, options, style, &mut out),
+        Ok(ResponsePayload::Stash(result)) => render_stash(result, options, style, &mut out),
+        Ok(ResponsePayload::Progress(chunk)) => {
+            let update = &chunk.data;
+            out.push_str(&format!(
+                "{} {}\n",
+                style.paint(DIM, &format!("{:>3}%", update.progress)),
+                update.message.as_deref().unwrap_or(&update.stage)
+            ));
+        }
+        Ok(payload) => {
+            let json = serde_json::to_string_pretty(payload).unwrap_or_default();
+            out.push_str(&json);
+            out.push('\n');
+        }
+        Err(error) => render_error(error, style, &mut out),
+    }
+    out
+}
+
+/// Line heading the tables of one repository, as `head` heads each file.
+pub(crate) fn heading(id: &str, style: Style) -> String {
+    style.paint(BOLD, &format!("==> {} <==", id))
+}
+
+fn render_status(status: &StatusView, style: Style, out: &mut String) {
+    match (&status.branch, &status.head) {
+        (Some(branch), Some(head)) => out.push_str(&format!(
+            "On branch {} {}\n",
+            style.paint(BOLD, branch),
+            style.paint(YELLOW, short_id(head))
+        )),
+        (Some(branch), None) => out.push_str(&format!(
+            "On branch {}, no commits yet\n",
+            style.paint(BOLD, branch)
+        )),
+        (None, Some(head)) => out.push_str(&format!(
+            "HEAD detached at {}\n",
+            style.paint(YELLOW, short_id(head))
+        )),
+        (None, None) => out.push_str("No commits yet\n"),
+    }
+
+    let workdir = &status.workdir;
+    let mut table = Table::default();
+    let mut add = |label: &str, color: &'static str, paths: &[String]| {
+        for path in paths {
+            table.row(vec![
+                (label.to_string(), Some(color)),
+                (path.clone(), Some(color)),
+            ]);
+        }
+    };
+    add("staged", GREEN, &status.index.staged);
+    add("modified", YELLOW, &workdir.modified);
+    add("added", GREEN, &workdir.added);
+    add("deleted", RED, &workdir.deleted);
+    add("untracked", DIM, &workdir.untracked);
+    for (from, to) in &workdir.renamed {
+        table.row(vec![
+            ("renamed".to_string(), Some(CYAN)),
+            (format!("{} -> {}", from, to), Some(CYAN)),
+        ]);
+    }
+
+    if table.rows.is_empty() {
+        out.push_str("Working tree clean\n");
+    } else {
+        out.push('\n');
+        table.render(style, out);
+    }
+}
+
+fn render_branches(branches: &BranchList, style: Style, out: &mut String) {
+    let mut table = Table::default();
+    for branch in &branches.local {
+        let current = branches.current.as_deref() == Some(branch.name.as_str());
+        table.row(vec![
+            (if current { "*" } else { " " }.to_string(), None),
+            (
+                branch.name.clone(),
+                Some(if current { GREEN } else { BOLD }),
+            ),
+            (short_id(&branch.commit_id).to_string(), Some(YELLOW)),
+        ]);
+    }
+    for branch in &branches.remote {
+        table.row(vec![
+            (" ".to_string(), None),
+            (branch.name.clone(), Some(RED)),
+            (short_id(&branch.commit_id).to_string(), Some(YELLOW)),
+        ]);
+    }
+    if table.rows.is_empty() {
+        out.push_str("No branches\n");
+    }
+    table.render(style, out);
+}
+
+fn render_tags(tags: &TagList, style: Style, out: &mut String) {
+    let mut table = Table::default();
+    for tag in &tags.tags {
+        table.row(vec![
+            (tag.name.clone(), Some(BOLD)),
+            (short_id(&tag.commit_id).to_string(), Some(YELLOW)),
+            (
+                tag.message
+                    .as_deref()
+                    .and_then(|message| message.lines().next())
+                    .unwrap_or_default()
+                    .to_string(),
+                None,
+            ),
+        ]);
+    }
+    if table.rows.is_empty() {
+        out.push_str("No tags\n");
+    }
+    table.render(style, out);
+}
+
+fn render_stash(result: &StashResult, options: &Options, style: Style, out: &mut String) {
+    let name = |index: usize| style.paint(YELLOW, &format!("stash@{{{}}}", index));
+    let action = options.stash.unwrap_or(StashAction::List);
+    let verb = match action {
+        StashAction::Push => "Saved",
+        StashAction::Pop => "Popped",
+        StashAction::Apply => "Applied",
+        StashAction::Drop => "Dropped",
+        StashAction::List | StashAction::Show => {
+            if let (Some(entry), Some(diff)) = (result.entries.first(), &result.diff) {
+                out.push_str(&format!("{} {}\n", name(entry.index), entry.message));
+                render_diff_summary(diff, style, out);
+                return;
+            }
+            let now = now();
+            let mut table = Table::default();
+            for entry in &result.entries {
+                table.row(vec![
+                    (format!("stash@{{{}}}", entry.index), Some(YELLOW)),
+                    (age(now - entry.timestamp), Some(DIM)),
+                    (entry.message.clone(), None),
+                ]);
+            }
+            if table.rows.is_empty() {
+                out.push_str("No stash entries\n");
+            }
+            table.render(style, out);
+            return;
+        }
+    };
+    match result.entries.first() {
+        Some(entry) => out.push_str(&format!(
+            "{} {} {}: {}\n",
+            verb,
+            name(entry.index),
+            style.paint(DIM, &format!("({})", short_id(&entry.commit))),
+            entry.message
+        )),
+        None => out.push_str("No local changes to save\n"),
+    }
+}
+
+fn render_log(page: &CommitListPage, style: Style, out: &mut String) {
+    let now = now();
+    let mut table = Table::default();
+    for commit in &page.commits {
+        table.row(vec![
+            (short_id(&commit.id).to_string(), Some(YELLOW)),
+            (age(now - commit.time), Some(DIM)),
+            (commit.author_name.clone(), Some(CYAN)),
+            (commit.message.clone(), None),
+        ]);
+    }
+    if table.rows.is_empty() {
+        out.push_str("No commits\n");
+    }
+    table.render(style, out);
+
+    if let (true, Some(cursor)) = (page.has_more, &page.next_cursor) {
+        out.push_str(&style.paint(
+            DIM,
+            &format!("More commits follow: --cursor {}", cursor.get()),
+        ));
+        out.push('\n');
+    }
+}
+
+fn render_graph(window: &CommitGraphWindow, style: Style, out: &mut String) {
+    let graphs: Vec<String> = window.commits.iter().map(graph_row).collect();
+    let width = graphs.iter().map(|graph| graph.chars().count()).max();
+    for (node, graph) in window.commits.iter().zip(&graphs) {
+        let graph = format!("{:<width$}", graph, width = width.unwrap_or(0));
+        let mut line = format!(
+            "{}  {}",
+            style.paint(MAGENTA, &graph),
+            style.paint(YELLOW, short_id(&node.commit.id))
+        );
+        if !node.refs.is_empty() {
+            let refs: Vec<String> = node
+                .refs
+                .iter()
+                .map(|name| decoration(name, style))
+                .collect();
+            line.push_str(&format!(" ({})", refs.join(", ")));
+        }
+        line.push(' ');
+        line.push_str(&node.commit.message);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    if window.commits.is_empty() {
+        out.push_str("No commits\n");
+    }
+
+    if let (true, Some(cursor)) = (window.has_more, &window.next_cursor) {
+        out.push_str(&style.paint(
+            DIM,
+            &format!("More commits follow: --cursor {}", cursor.get()),
+        ));
+        out.push('\n');
+    }
+}
+
+/// The graph drawn left of a commit, one column per lane.
+///
+/// Merge lanes are joined to the commit by a sideways line, which turns down
+/// to a merge's other parents or up to the commits that branched off.
+fn graph_row(node: &CommitGraphNode) -> String {
+    let columns = node.lanes.iter().map(|lane| lane.index + 1).max();
+    let mut lanes = vec![&LaneType::Empty; columns.unwrap_or(0)];
+    for lane in &node.lanes {
+        lanes[lane.index] = &lane.lane_type;
+    }
+
+    // Columns each sideways line runs between
+    let commit = lanes
+        .iter()
+        .position(|lane| matches!(lane, LaneType::Commit));
+    let spans: Vec<(usize, usize)> = lanes
+        .iter()
+        .enumerate()
+        .filter(|(_, lane)| matches!(lane, LaneType::Merge))
+        .filter_map(|(merge, _)| Some((commit?.min(merge), commit?.max(merge))))
+        .collect();
+    let crossed = |column: usize| spans.iter().any(|&(from, to)| from < column && column < to);
+    let turn = if node.commit.parents.len() > 1 {
+        '╮'
+    } else {
+        '╯'
+    };
+
+    let mut row = String::new();
+    for (column, lane) in lanes.iter().enumerate() {
+        if column > 0 {
+            let joined = spans
+                .iter()
+                .any(|&(from, to)| from < column && column <= to);
+            row.push(if joined { '─' } else { ' ' });
+        }
+        row.push(match lane {
+            LaneType::Commit => '●',
+            LaneType::Merge => turn,
+            LaneType::Branch if crossed(column) => '┼',
+            LaneType::Branch => '│',
+            LaneType::Empty if crossed(column) => '─',
+            LaneType::Empty => ' ',
+        });
+    }
+    row.trim_end().to_string()
+}
+
+/// A ref, shown and colored as `git log --decorate` does.
+fn decoration(name: &str, style: Style) -> String {
+    let name = name.strip_prefix("refs/").unwrap_or(name);
+    if let Some(branch) = name.strip_prefix("heads/") {
+        style.paint(GREEN, branch)
+    } else if let Some(branch) = name.strip_prefix("remotes/") {
+        style.paint(RED, branch)
+    } else if let Some(tag) = name.strip_prefix("tags/") {
+        style.paint(YELLOW, &format!("tag: {}", tag))
+    } else {
+        name.to_string()
+    }
+}
+
+fn render_diff_summary(summary: &DiffSummary, style: Style, out: &mut String) {
+    let mut table = Table::default();
+    for change in &summary.changes {
+        let (status, color) = match change.change_type {
+            ChangeType::Added => ("A", GREEN),
+            ChangeType::Modified => ("M", YELLOW),
+            ChangeType::Deleted => ("D", RED),
+            ChangeType::Renamed => ("R", CYAN),
+        };
+        let path = match &change.old_path {
+            Some(old_path) => format!("{} -> {}", old_path, change.path),
+            None => change.path.clone(),
+        };
+        table.row(vec![
+            (status.to_string(), Some(color)),
+            (path, None),
+            (format!("+{}", change.additions), Some(GREEN)),
+            (format!("-{}", change.deletions), Some(RED)),
+        ]);
+    }
+    table.render(style, out);
+
+    out.push_str(&format!(
+        "{} {} changed, {} {}(+), {} {}(-)\n",
+        summary.files_changed,
+        plural(summary.files_changed, "file", "files"),
+        summary.additions,
+        plural(summary.additions, "insertion", "insertions"),
+        summary.deletions,
+        plural(summary.deletions, "deletion", "deletions"),
+    ));
+}
+
+fn render_diff(diff: &DiffChunk, style: Style, out: &mut String) {
+    // Only an added file has nothing before its first hunk, and only a
+    // deleted one nothing after
+    let empty = |range: &rl_api::response::Range| range.start == 0 && range.count == 0;
+    let added = diff.hunks.len() == 1 && empty(&diff.hunks[0].old_range);
+    let deleted = diff.hunks.len() == 1 && empty(&diff.hunks[0].new_range);
+    let old = if added {
+        "/dev/null".to_string()
+    } else {
+        format!("a/{}", diff.path)
+    };
+    let new = if deleted {
+        "/dev/null".to_string()
+    } else {
+        format!("b/{}", diff.path)
+    };
+    for header in [
+        format!("diff --git a/{} b/{}", diff.path, diff.path),
+        format!("--- {}", old),
+        format!("+++ {}", new),
+    ] {
+        out.push_str(&style.paint(BOLD, &header));
+        out.push('\n');
+    }
+
+    for hunk in &diff.hunks {
+        let range = format!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old_range.start, hunk.old_range.count, hunk.new_range.start, hunk.new_range.count
+        );
+        out.push_str(format!("{} {}", style.paint(CYAN, &range), hunk.header).trim_end());
+        out.push('\n');
+
+        // Lines replacing as many others are highlighted where they differ
+        let mut changed = vec![None; hunk.lines.len()];
+        let mut start = 0;
+        while start < hunk.lines.len() {
+            let run = |from: usize, line_type: fn(&DiffLineType) -> bool| {
+                hunk.lines[from..]
+                    .iter()
+                    .take_while(|line| line_type(&line.line_type))
+                    .count()
+            };
+            let deletions = run(start, |t| matches!(t, DiffLineType::Deletion));
+            let additions = run(start + deletions, |t| matches!(t, DiffLineType::Addition));
+            if deletions > 0 && deletions == additions {
+                for offset in 0..deletions {
+                    let (old, new) = (start + offset, start + deletions + offset);
+                    let (old_span, new_span) =
+                        changed_spans(&hunk.lines[old].content, &hunk.lines[new].content);
+                    changed[old] = old_span;
+                    changed[new] = new_span;
+                }
+            }
+            start += (deletions + additions).max(1);
+        }
+
+        for (line, changed) in hunk.lines.iter().zip(changed) {
+            let (prefix, color) = match line.line_type {
+                DiffLineType::Addition => ("+", GREEN),
+                DiffLineType::Deletion => ("-", RED),
+                DiffLineType::Context => {
+                    out.push_str(&format!(" {}\n", line.content));
+                    continue;
+                }
+            };
+            match changed.filter(|_| style.color) {
+                Some(span) => {
+                    let content = &line.content;
+                    out.push_str(
+                        &style.paint(color, &format!("{}{}", prefix, &content[..span.start])),
+                    );
+                    out.push_str(&style.paint(&format!("7;{}", color), &content[span.clone()]));
+                    out.push_str(&style.paint(color, &content[span.end..]));
+                }
+                None => out.push_str(&style.paint(color, &format!("{}{}", prefix, line.content))),
+            }
+            out.push('\n');
+        }
+    }
+}
+
+/// The parts of `old` and `new` between what they have in common at the
+/// start and end, or `None` if nothing is in common and the lines are best
+/// read whole.
+fn changed_spans(
+    old: &str,
+    new: &str,
+) -> (
+    Option<std::ops::Range<usize>>,
+    Option<std::ops::Range<usize>>,
+) {
+    let prefix: usize = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+    if prefix + suffix == 0 {
+        return (None, None);
+    }
+    (
+        Some(prefix..old.len() - suffix),
+        Some(prefix..new.len() - suffix),
+    )
+}
+
+/// Blame arrives in chunks, so its columns have fixed widths rather than
+/// the widest cell's, keeping every chunk aligned with the ones before.
+fn render_blame(blame: &BlameChunk, options: &Options, style: Style, out: &mut String) {
+    const AUTHOR_WIDTH: usize = 16;
+    const LINE_WIDTH: usize = 4;
+    let now = now();
+    for line in &blame.lines {
+        let author = if options.email {
+            &line.author_email
+        } else {
+            &line.author_name
+        };
+        let author = match author.char_indices().nth(AUTHOR_WIDTH) {
+            Some(_) => {
+                let end = author.char_indices().nth(AUTHOR_WIDTH - 1).unwrap().0;
+                format!("{}…", &author[..end])
+            }
+            None => author.clone(),
+        };
+        let date = match options.date {
+            DateFormat::Relative => format!("{:<14}", age(now - line.author_time)),
+            DateFormat::Iso => format_time(line.author_time, true),
+            DateFormat::Short => format_time(line.author_time, false),
+            DateFormat::Unix => line.author_time.to_string(),
+        };
+        let annotation = format!(
+            "{} {} {} {}",
+            style.paint(YELLOW, short_id(&line.commit_id)),
+            style.paint(CYAN, &format!("{:<AUTHOR_WIDTH$}", author)),
+            style.paint(DIM, &date),
+            style.paint(DIM, &format!("{:>LINE_WIDTH$}", line.line_number)),
+        );
+        out.push_str(format!("{}  {}", annotation, line.content).trim_end());
+        out.push('\n');
+    }
+}
+
+fn render_error(error: &Error, style: Style, out: &mut String) {
+    out.push_str(&format!(
+        "{} {} ({})\n",
+        style.paint(RED, "error:"),
+        error.message,
+        error.code
+    ));
+    if let Some(remediation) = &error.remediation {
+        out.push_str(&format!("{} {}\n", style.paint(CYAN, "hint:"), remediation));
+    }
+}
+
+/// Abbreviated commit id, as git shows it.
+fn short_id(id: &str) -> &str {
+    id.get(..7).unwrap_or(id)
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// `time`, in seconds since the Unix epoch, as a UTC date and, if `clock`,
+/// time of day.
+fn format_time(time: i64, clock: bool) -> String {
+    // Civil date from days since the epoch, after Howard Hinnant's
+    // `civil_from_days`
+    let (days, seconds) = (time.div_euclid(86_400), time.rem_euclid(86_400));
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    if !clock {
+        return date;
+    }
+    format!(
+        "{} {:02}:{:02}:{:02} +0000",
+        date,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// How long ago something `seconds` old happened, e.g. `3 days ago`.
+fn age(seconds: i64) -> String {
+    const UNITS: &[(i64, &str, &str)] = &[
+        (365 * 24 * 3600, "year", "years"),
+        (30 * 24 * 3600, "month", "months"),
+        (7 * 24 * 3600, "week", "weeks"),
+        (24 * 3600, "day", "days"),
+        (3600, "hour", "hours"),
+        (60, "minute", "minutes"),
+    ];
+    let seconds = seconds.max(0);
+    for (unit, singular, plural_name) in UNITS {
+        if seconds >= *unit {
+            let count = seconds / unit;
+            return format!(
+                "{} {} ago",
+                count,
+                plural(count as usize, singular, plural_name)
+            );
+        }
+    }
+    "just now".to_string()
+}
+
+fn plural<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 {
+        singular
+    } else {
+        plural
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::response::{CommitSummary, DiffHunk, DiffLine, FileChange, GraphLane, Range};
+
+    #[test]
+    fn test_diff_summary_is_aligned_and_totalled() {
+        let change = |path: &str, change_type, additions| FileChange {
+            path: path.to_string(),
+            change_type,
+            additions,
+            deletions: 1,
+            old_path: None,
+        };
+        let response = Response {
+            id: "1".to_string(),
+            result: Ok(ResponsePayload::DiffSummary(DiffSummary {
+                files_changed: 2,
+                additions: 11,
+                deletions: 2,
+                changes: vec![
+                    change("src/lib.rs", ChangeType::Modified, 10),
+                    change("a", ChangeType::Added, 1),
+                ],
+            })),
+            meta: None,
+        };
+
+        assert_eq!(
+            render(&response, &Options::default(), Style { color: false }),
+            "M  src/lib.rs  +10  -1\n\
+             A  a           +1   -1\n\
+             2 files changed, 11 insertions(+), 2 deletions(-)\n"
+        );
+        assert_eq!(age(3 * 24 * 3600 + 5), "3 days ago");
+        assert_eq!(
+            format_time(1_714_555_800, true),
+            "2024-05-01 09:30:00 +0000"
+        );
+        assert_eq!(format_time(-86_400, false), "1969-12-31");
+    }
+    #[test]
+    fn test_diff_is_shown_as_unified_diff() {
+        let line = |line_type, content: &str| DiffLine {
+            line_type,
+            old_line: None,
+            new_line: None,
+            content: content.to_string(),
+        };
+        let diff = DiffChunk {
+            path: "src/lib.rs".to_string(),
+            hunks: vec![DiffHunk {
+                old_range: Range { start: 3, count: 2 },
+                new_range: Range { start: 3, count: 2 },
+                header: "fn main() {".to_string(),
+                lines: vec![
+                    line(DiffLineType::Context, "let a = 1;"),
+                    line(DiffLineType::Deletion, "let b = 2;"),
+                    line(DiffLineType::Addition, "let b = 3;"),
+                ],
+            }],
+        };
+
+        let mut out = String::new();
+        render_diff(&diff, Style { color: false }, &mut out);
+        assert_eq!(
+            out,
+            "diff --git a/src/lib.rs b/src/lib.rs\n\
+             --- a/src/lib.rs\n\
+             +++ b/src/lib.rs\n\
+             @@ -3,2 +3,2 @@ fn main() {\n \
+             let a = 1;\n\
+             -let b = 2;\n\
+             +let b = 3;\n"
+        );
+
+        // Only the changed part of a replaced line is highlighted
+        assert_eq!(
+            changed_spans("let b = 2;", "let b = 30;"),
+            (Some(8..9), Some(8..10))
+        );
+        assert_eq!(changed_spans("abc", "xyz"), (None, None));
+    }
+
+    #[test]
+    fn test_graph_joins_merges_and_decorates_refs() {
+        let node = |id: &str, parents: usize, lanes: &[LaneType], refs: &[&str]| CommitGraphNode {
+            commit: CommitSummary {
+                id: id.repeat(7),
+                message: format!("commit {}", id),
+                author_name: "a".to_string(),
+                author_email: "a@example.com".to_string(),
+                time: 0,
+                parents: vec![String::new(); parents],
+            },
+            lanes: lanes
+                .iter()
+                .enumerate()
+                .map(|(index, lane_type)| GraphLane {
+                    index,
+                    lane_type: lane_type.clone(),
+                })
+                .collect(),
+            refs: refs.iter().map(|name| name.to_string()).collect(),
+        };
+        use LaneType::*;
+        let response = Response {
+            id: "1".to_string(),
+            result: Ok(ResponsePayload::Graph(CommitGraphWindow {
+                commits: vec![
+                    node(
+                        "m",
+                        2,
+                        &[Commit, Branch, Merge],
+                        &["refs/heads/main", "refs/tags/v1"],
+                    ),
+                    node(
+                        "b",
+                        1,
+                        &[Branch, Branch, Commit],
+                        &["refs/remotes/origin/b"],
+                    ),
+                    node("a", 1, &[Commit, Branch], &[]),
+                    node("r", 0, &[Commit, Empty, Merge], &[]),
+                ],
+                next_cursor: None,
+                has_more: false,
+            })),
+            meta: None,
+        };
+
+        assert_eq!(
+            render(&response, &Options::default(), Style { color: false }),
+            "●─┼─╮  mmmmmmm (main, tag: v1) commit m\n\
+             │ │ ●  bbbbbbb (origin/b) commit b\n\
+             ● │    aaaaaaa commit a\n\
+             ●───╯  rrrrrrr commit r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graph_of_octopus_and_criss_cross_merges() {
+        use rl_api::request::{GraphRequest, Request, RequestPayload};
+        use rl_fixtures::topology_repo::TopologyRepo;
+
+        let repo = TopologyRepo::ensure("table_graph").expect("Failed to create topology repo");
+        let engine = rl_core::RepoEngine::new();
+        let mut cursor = rl_api::Cursor::initial();
+        let mut shown = String::new();
+        // Two windows, so the lanes of the second follow on from the first
+        for _ in 0..2 {
+            let response = engine
+                .handle(Request::new(
+                    "graph",
+                    RequestPayload::Graph(GraphRequest {
+                        repo_path: repo.path.display().to_string(),
+                        window_size: rl_api::WindowSize::try_from(5).unwrap(),
+                        cursor: cursor.clone(),
+                        revision_range: None,
+                    }),
+                ))
+                .await;
+            if let Ok(ResponsePayload::Graph(window)) = &response.result {
+                cursor = window
+                    .next_cursor
+                    .clone()
+                    .unwrap_or_else(rl_api::Cursor::initial);
+            }
+            shown.push_str(&render(
+                &response,
+                &Options::default(),
+                Style { color: false },
+            ));
+        }
+
+        // The criss-cross keeps three lanes open until both sides are met,
+        // and the octopus opens one for each branch it merges
+        assert_eq!(
+            shown,
+            "●─╮    50d4d34 (master, tag: crossed) crossed\n\
+             │ ●─╮  7658946 (right, tag: right2) right2\n\
+             ●─╮ │  b048477 (left, tag: left2) left2\n\
+             │ ● │  397dd00 (tag: right1) right1\n\
+             ●─┼─╯  6b08075 (tag: left1) left1\n\
+             More commits follow: --cursor v1:50d4d34f168b36251569868bc7f3144261131ca3:5:6b08075550aff7a234f3fb7ec70f88a72eb1a4eb\n\
+             ●─╮─╮─╮  8ef0e94 (tag: octopus) octopus\n\
+             │ │ │ ●  52ec874 (tag: c) c\n\
+             │ │ ● │  3fe7ef1 (tag: b) b\n\
+             │ ● │ │  e42a88f (tag: a) a\n\
+             ●─╯─╯─╯  390bd9e (tag: root) root\n"
+        );
+    }
+}
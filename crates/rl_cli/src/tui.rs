@@ -0,0 +1,545 @@
+//! Interactive terminal UI, for `repo-lens tui`.
+//!
+//! Three panes: the commit graph, the selected commit's details, and its
+//! diff. Everything shown comes from rl_api requests to the engine (Log
+//! pages for the history, DiffSummary and DiffContent for the selected
+//! commit), so the UI doubles as a reference client for the API. Lanes of
+//! the graph are laid out here from each commit's parents.
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rl_api::paging::Paging;
+use rl_api::request::{DiffContentRequest, DiffSummaryRequest, LogRequest, RequestPayload};
+use rl_api::response::{CommitSummary, DiffLineType, DiffSummary, ResponsePayload};
+use rl_api::{ApiVersion, Cursor, Error, MaxBytes, MaxHunks, PageSize, Request};
+use rl_core::RepoEngine;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Tree with no entries, diffed against to show a root commit.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Commits left below the selection before the next page is loaded.
+const LOAD_AHEAD: usize = 10;
+
+/// Largest diff shown for one commit.
+const MAX_DIFF_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Pane receiving movement keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Graph,
+    Diff,
+}
+
+/// What is known about one commit beyond its summary.
+struct Loaded {
+    summary: Result<DiffSummary, Error>,
+    diff: Result<Vec<Line<'static>>, Error>,
+}
+
+struct App {
+    engine: RepoEngine,
+    repo_path: String,
+    page_size: PageSize,
+    commits: Vec<CommitSummary>,
+    /// Graph column drawn for each commit
+    graph: Vec<String>,
+    lanes: Lanes,
+    next_cursor: Option<Cursor>,
+    /// Why the history could not be loaded, if it could not
+    error: Option<Error>,
+    list: ListState,
+    loaded: HashMap<String, Loaded>,
+    focus: Focus,
+    diff_scroll: u16,
+    next_id: u64,
+}
+
+/// Run the UI against `engine` until the user quits.
+pub(crate) async fn run(
+    engine: RepoEngine,
+    repo_path: String,
+    page_size: PageSize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut app = App {
+        engine,
+        repo_path,
+        page_size,
+        commits: Vec::new(),
+        graph: Vec::new(),
+        lanes: Lanes::default(),
+        next_cursor: Some(Cursor::initial()),
+        error: None,
+        list: ListState::default(),
+        loaded: HashMap::new(),
+        focus: Focus::Graph,
+        diff_scroll: 0,
+        next_id: 0,
+    };
+    app.load_more().await;
+    app.list.select((!app.commits.is_empty()).then_some(0));
+
+    // Restores the terminal on panic, too
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+impl App {
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            self.load_selected().await;
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // Reading the terminal blocks, so keep it off the async workers
+            let event = tokio::task::block_in_place(|| {
+                if event::poll(Duration::from_millis(250))? {
+                    event::read().map(Some)
+                } else {
+                    Ok(None)
+                }
+            })?;
+            let Some(Event::Key(key)) = event else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match (key.code, self.focus) {
+                (KeyCode::Char('q') | KeyCode::Esc, _) => return Ok(()),
+                (KeyCode::Tab, Focus::Graph) => self.focus = Focus::Diff,
+                (KeyCode::Tab, Focus::Diff) => self.focus = Focus::Graph,
+                (KeyCode::Down | KeyCode::Char('j'), Focus::Graph) => self.select(1).await,
+                (KeyCode::Up | KeyCode::Char('k'), Focus::Graph) => self.select(-1).await,
+                (KeyCode::PageDown, Focus::Graph) => self.select(10).await,
+                (KeyCode::PageUp, Focus::Graph) => self.select(-10).await,
+                (KeyCode::Down | KeyCode::Char('j'), Focus::Diff) => self.scroll(1),
+                (KeyCode::Up | KeyCode::Char('k'), Focus::Diff) => self.scroll(-1),
+                (KeyCode::PageDown, Focus::Diff) => self.scroll(20),
+                (KeyCode::PageUp, Focus::Diff) => self.scroll(-20),
+                _ => {}
+            }
+        }
+    }
+
+    /// Move the selection by `delta` commits, loading more history as the
+    /// end comes near.
+    async fn select(&mut self, delta: isize) {
+        let Some(selected) = self.list.selected() else {
+            return;
+        };
+        if selected + LOAD_AHEAD >= self.commits.len() {
+            self.load_more().await;
+        }
+        let last = self.commits.len().saturating_sub(1);
+        let selected = selected.saturating_add_signed(delta).min(last);
+        if self.list.selected() != Some(selected) {
+            self.list.select(Some(selected));
+            self.diff_scroll = 0;
+        }
+    }
+
+    fn scroll(&mut self, delta: i16) {
+        self.diff_scroll = self.diff_scroll.saturating_add_signed(delta);
+    }
+
+    /// Fetch the next page of history, if there is one.
+    async fn load_more(&mut self) {
+        let Some(cursor) = self.next_cursor.take() else {
+            return;
+        };
+        let payload = RequestPayload::Log(LogRequest {
+            repo_path: self.repo_path.clone(),
+            paging: Paging {
+                page_size: self.page_size.clone(),
+                cursor,
+            },
+            revision_range: None,
+        });
+        match self.request(payload).await {
+            Ok(ResponsePayload::Log(page)) => {
+                for commit in &page.commits {
+                    self.graph
+                        .push(self.lanes.place(&commit.id, &commit.parents));
+                }
+                self.commits.extend(page.commits);
+                self.next_cursor = page.next_cursor.filter(|_| page.has_more);
+            }
+            Ok(other) => self.error = Some(unexpected(&other)),
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Fetch the details and diff of the selected commit, once.
+    async fn load_selected(&mut self) {
+        let Some(commit) = self.list.selected().and_then(|i| self.commits.get(i)) else {
+            return;
+        };
+        if self.loaded.contains_key(&commit.id) {
+            return;
+        }
+        let id = commit.id.clone();
+        let parent = commit
+            .parents
+            .first()
+            .cloned()
+            .unwrap_or_else(|| EMPTY_TREE.to_string());
+
+        let summary = match self
+            .request(RequestPayload::DiffSummary(DiffSummaryRequest {
+                repo_path: self.repo_path.clone(),
+                from: Some(parent.clone()),
+                to: Some(id.clone()),
+                max_bytes: MaxBytes::try_from(MAX_DIFF_BYTES).unwrap(),
+                max_hunks: MaxHunks::try_from(1000).unwrap(),
+            }))
+            .await
+        {
+            Ok(ResponsePayload::DiffSummary(summary)) => Ok(summary),
+            Ok(other) => Err(unexpected(&other)),
+            Err(error) => Err(error),
+        };
+        let diff = self.diff(parent, id.clone()).await;
+        self.loaded.insert(id, Loaded { summary, diff });
+    }
+
+    /// The diff from `from` to `to`, one file per streamed chunk.
+    async fn diff(&mut self, from: String, to: String) -> Result<Vec<Line<'static>>, Error> {
+        let request = self.envelope(RequestPayload::DiffContent(DiffContentRequest {
+            repo_path: self.repo_path.clone(),
+            from: Some(from),
+            to: Some(to),
+            path: None,
+            max_bytes: MaxBytes::try_from(MAX_DIFF_BYTES).unwrap(),
+        }));
+        let (response_tx, mut response_rx) = mpsc::channel(16);
+        let handler = self.engine.handle_stream(request, response_tx);
+        let collect = async {
+            let mut lines = Vec::new();
+            while let Some(response) = response_rx.recv().await {
+                let chunk = match response.result? {
+                    ResponsePayload::DiffContent(chunk) => chunk.data,
+                    other => return Err(unexpected(&other)),
+                };
+                lines.push(Line::styled(
+                    chunk.path,
+                    Style::new().add_modifier(Modifier::BOLD),
+                ));
+                for hunk in chunk.hunks {
+                    lines.push(Line::styled(hunk.header, Style::new().fg(Color::Cyan)));
+                    for line in hunk.lines {
+                        let (prefix, style) = match line.line_type {
+                            DiffLineType::Addition => ("+", Style::new().fg(Color::Green)),
+                            DiffLineType::Deletion => ("-", Style::new().fg(Color::Red)),
+                            DiffLineType::Context => (" ", Style::new()),
+                        };
+                        lines.push(Line::styled(format!("{}{}", prefix, line.content), style));
+                    }
+                }
+                lines.push(Line::default());
+            }
+            Ok(lines)
+        };
+        let ((), lines) = tokio::join!(handler, collect);
+        lines
+    }
+
+    async fn request(&mut self, payload: RequestPayload) -> Result<ResponsePayload, Error> {
+        let request = self.envelope(payload);
+        self.engine.handle(request).await.result
+    }
+
+    fn envelope(&mut self, payload: RequestPayload) -> Request {
+        self.next_id += 1;
+        Request::new(format!("tui-{}", self.next_id), payload)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [graph, right] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main);
+        let [details, diff] =
+            Layout::vertical([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(right);
+
+        self.draw_graph(frame, graph);
+        self.draw_details(frame, details);
+        self.draw_diff(frame, diff);
+        frame.render_widget(
+            Line::styled(
+                " q quit  ↑↓/jk move  PgUp/PgDn page  tab switch pane",
+                Style::new().add_modifier(Modifier::DIM),
+            ),
+            help,
+        );
+    }
+
+    fn draw_graph(&mut self, frame: &mut Frame, area: Rect) {
+        let block = self.block("History", Focus::Graph);
+        if let Some(error) = &self.error {
+            frame.render_widget(Paragraph::new(error_text(error)).block(block), area);
+            return;
+        }
+
+        let items: Vec<ListItem> = self
+            .commits
+            .iter()
+            .zip(&self.graph)
+            .map(|(commit, graph)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", graph), Style::new().fg(Color::Magenta)),
+                    Span::styled(short_id(&commit.id), Style::new().fg(Color::Yellow)),
+                    Span::raw(" "),
+                    Span::raw(commit.message.clone()),
+                    Span::styled(
+                        format!("  {}", commit.author_name),
+                        Style::new().add_modifier(Modifier::DIM),
+                    ),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block("Commit", None);
+        let Some(commit) = self.list.selected().and_then(|i| self.commits.get(i)) else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw("commit "),
+                Span::styled(commit.id.clone(), Style::new().fg(Color::Yellow)),
+            ]),
+            Line::raw(format!(
+                "Author: {} <{}>",
+                commit.author_name, commit.author_email
+            )),
+            Line::raw(format!("Parents: {}", commit.parents.join(" "))),
+            Line::default(),
+            Line::styled(
+                commit.message.clone(),
+                Style::new().add_modifier(Modifier::BOLD),
+            ),
+            Line::default(),
+        ];
+        match self.loaded.get(&commit.id).map(|loaded| &loaded.summary) {
+            Some(Ok(summary)) => {
+                for change in &summary.changes {
+                    lines.push(Line::from(vec![
+                        Span::styled(
+                            format!("+{:<5}", change.additions),
+                            Style::new().fg(Color::Green),
+                        ),
+                        Span::styled(
+                            format!("-{:<5}", change.deletions),
+                            Style::new().fg(Color::Red),
+                        ),
+                        Span::raw(change.path.clone()),
+                    ]));
+                }
+                lines.push(Line::raw(format!(
+                    "{} files changed, {} insertions(+), {} deletions(-)",
+                    summary.files_changed, summary.additions, summary.deletions
+                )));
+            }
+            Some(Err(error)) => lines.extend(error_text(error).lines),
+            None => lines.push(Line::raw("Loading…")),
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_diff(&self, frame: &mut Frame, area: Rect) {
+        let block = self.block("Diff", Focus::Diff);
+        let text = match self
+            .list
+            .selected()
+            .and_then(|i| self.commits.get(i))
+            .and_then(|commit| self.loaded.get(&commit.id))
+        {
+            Some(Loaded {
+                diff: Ok(lines), ..
+            }) => Text::from(lines.clone()),
+            Some(Loaded {
+                diff: Err(error), ..
+            }) => error_text(error),
+            None => Text::default(),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .block(block)
+                .scroll((self.diff_scroll, 0)),
+            area,
+        );
+    }
+
+    /// Bordered pane, highlighted while it has focus.
+    fn block(&self, title: &'static str, focus: impl Into<Option<Focus>>) -> Block<'static> {
+        let style = if focus.into() == Some(self.focus) {
+            Style::new().fg(Color::Cyan)
+        } else {
+            Style::new()
+        };
+        Block::bordered().title(title).border_style(style)
+    }
+}
+
+fn error_text(error: &Error) -> Text<'static> {
+    let mut text = Text::styled(
+        format!("{} ({})", error.message, error.code),
+        Style::new().fg(Color::Red),
+    );
+    if let Some(remediation) = &error.remediation {
+        text.push_line(Line::raw(remediation.clone()));
+    }
+    text
+}
+
+fn unexpected(payload: &ResponsePayload) -> Error {
+    Error::new(
+        rl_api::ErrorCode::Internal,
+        format!("Unexpected response: {:?}", payload),
+    )
+}
+
+fn short_id(id: &str) -> String {
+    id.chars().take(7).collect()
+}
+
+/// Lanes of the graph, each waiting for the commit it leads to.
+#[derive(Debug, Default)]
+struct Lanes {
+    expected: Vec<Option<String>>,
+}
+
+impl Lanes {
+    /// Place the next commit of the history, newest first, and return the
+    /// graph column drawn for it.
+    fn place(&mut self, id: &str, parents: &[String]) -> String {
+        let column = match self.position(id) {
+            Some(column) => column,
+            None => self.free_lane(),
+        };
+        let graph: String = self
+            .expected
+            .iter()
+            .enumerate()
+            .map(|(lane, expected)| match expected {
+                _ if lane == column => '●',
+                Some(_) => '│',
+                None => ' ',
+            })
+            .collect();
+
+        // Other lanes that led to this commit end here
+        for expected in &mut self.expected {
+            if expected.as_deref() == Some(id) {
+                *expected = None;
+            }
+        }
+        let mut parents = parents.iter();
+        self.expected[column] = parents.next().cloned();
+        for parent in parents {
+            if self.position(parent).is_none() {
+                let lane = self.free_lane();
+                self.expected[lane] = Some(parent.clone());
+            }
+        }
+        while self.expected.last() == Some(&None) {
+            self.expected.pop();
+        }
+        graph.trim_end().to_string()
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.expected
+            .iter()
+            .position(|expected| expected.as_deref() == Some(id))
+    }
+
+    fn free_lane(&mut self) -> usize {
+        match self.expected.iter().position(Option::is_none) {
+            Some(lane) => lane,
+            None => {
+                self.expected.push(None);
+                self.expected.len() - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lanes_follow_merges_and_branches() {
+        let mut lanes = Lanes::default();
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        // m merges b into a; both grow from root
+        assert_eq!(lanes.place("m", &ids(&["a", "b"])), "●");
+        assert_eq!(lanes.place("a", &ids(&["root"])), "●│");
+        assert_eq!(lanes.place("b", &ids(&["root"])), "│●");
+        assert_eq!(lanes.place("root", &[]), "●│");
+        assert!(lanes.expected.is_empty());
+    }
+
+    #[test]
+    fn test_lanes_follow_octopus_and_criss_cross_merges() {
+        use rl_fixtures::topology_repo::TopologyRepo;
+        use std::process::Command;
+
+        let repo = TopologyRepo::ensure("tui_lanes").expect("Failed to create topology repo");
+        let output = Command::new("git")
+            .current_dir(&repo.path)
+            .args(["log", "--topo-order", "--format=%H %P %s", "master"])
+            .output()
+            .unwrap();
+        let mut lanes = Lanes::default();
+        let rows: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| {
+                let mut fields: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+                let subject = fields.pop().unwrap();
+                let graph = lanes.place(&fields[0], &fields[1..]);
+                format!("{} {}", graph, subject)
+            })
+            .collect();
+
+        // The criss-cross keeps three lanes open until both sides are met;
+        // the octopus opens one for each branch it merges
+        assert_eq!(
+            rows,
+            [
+                "● crossed",
+                "│● right2",
+                "●││ left2",
+                "│●│ right1",
+                "●││ left1",
+                "●│ octopus",
+                "│││● c",
+                "││●│ b",
+                "│●││ a",
+                "●│││ root",
+            ]
+        );
+        assert!(lanes.expected.is_empty());
+    }
+}
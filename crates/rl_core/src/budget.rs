@@ -0,0 +1,175 @@
+//! Engine-wide memory budget.
+//!
+//! Cache limits bound what the engine keeps, but not what it is about to
+//! send: a blame of a huge file or a client that stops reading a diff stream
+//! can pile up responses faster than they leave. Responses waiting to be
+//! delivered, including the chunks of a stream backed up behind a slow
+//! reader, reserve their serialized size from a [`MemoryBudget`] until they
+//! are handed over. Once the budget is spent, new requests are turned away
+//! and oversized responses are replaced with an `Overloaded` error, so a
+//! pathological repository sheds load instead of exhausting memory.
+
+use rl_api::{Error, ErrorCode, Response};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Bytes the engine may hold in undelivered responses, shared by clones.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Bytes that may be reserved at once; `0` means unlimited
+    limit: AtomicU64,
+    /// Bytes reserved now
+    used: AtomicU64,
+}
+
+impl MemoryBudget {
+    /// Create a budget of `limit` bytes, or an unlimited one.
+    pub fn new(limit: Option<u64>) -> Self {
+        let budget = Self::default();
+        budget.set_limit(limit);
+        budget
+    }
+
+    /// Change the limit. Reservations already made are kept.
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.inner
+            .limit
+            .store(limit.unwrap_or(0), Ordering::Release);
+    }
+
+    /// Bytes that may be reserved at once, if limited.
+    pub fn limit(&self) -> Option<u64> {
+        match self.inner.limit.load(Ordering::Acquire) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Bytes reserved now.
+    pub fn in_use(&self) -> u64 {
+        self.inner.used.load(Ordering::Acquire)
+    }
+
+    /// Fail with `Overloaded` if no further bytes can be reserved.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.limit() {
+            Some(limit) if self.in_use() >= limit => Err(self.exceeded(self.in_use(), 0, limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reserve `bytes`, failing with `Overloaded` if that would exceed the
+    /// limit. The bytes are returned when the reservation is dropped.
+    pub fn try_reserve(&self, bytes: u64) -> Result<Reservation, Error> {
+        let limit = self.limit().unwrap_or(u64::MAX);
+        self.inner
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|total| *total <= limit)
+            })
+            .map_err(|used| self.exceeded(used, bytes, limit))?;
+        Ok(Reservation {
+            inner: self.inner.clone(),
+            bytes,
+        })
+    }
+
+    /// Reserve room for `response` until it is delivered.
+    ///
+    /// A response that does not fit is replaced with an `Overloaded` error
+    /// under the same id, which is small enough to send regardless.
+    pub fn admit(&self, response: Response) -> (Response, Option<Reservation>) {
+        match self.try_reserve(serialized_len(&response)) {
+            Ok(reservation) => (response, Some(reservation)),
+            Err(error) => (
+                Response {
+                    id: response.id,
+                    result: Err(error),
+                    meta: response.meta,
+                },
+                None,
+            ),
+        }
+    }
+
+    fn exceeded(&self, used: u64, requested: u64, limit: u64) -> Error {
+        Error::new(
+            ErrorCode::Overloaded,
+            format!(
+                "Engine memory budget of {} bytes exhausted ({} in use, {} requested)",
+                limit, used, requested
+            ),
+        )
+        .with_remediation("Retry later, or request smaller pages or ranges")
+        .with_details(serde_json::json!({
+            "memory_budget_bytes": limit,
+            "in_use_bytes": used,
+            "requested_bytes": requested,
+        }))
+    }
+}
+
+/// Bytes held from a [`MemoryBudget`]; returned on drop.
+#[derive(Debug)]
+pub struct Reservation {
+    inner: Arc<Inner>,
+    bytes: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.inner.used.fetch_sub(self.bytes, Ordering::AcqRel);
+    }
+}
+
+/// Size of `response` encoded as JSON, as an estimate of the memory it
+/// holds.
+pub(crate) fn serialized_len(response: &Response) -> u64 {
+    struct Counter(u64);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing to a counter cannot fail, and responses always serialize
+    let _ = serde_json::to_writer(&mut counter, response);
+    counter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_are_limited_and_returned_on_drop() {
+        let budget = MemoryBudget::new(Some(100));
+        let first = budget.try_reserve(60).unwrap();
+        assert_eq!(budget.in_use(), 60);
+
+        let error = budget.try_reserve(60).unwrap_err();
+        assert_eq!(error.code, ErrorCode::Overloaded);
+        assert_eq!(error.details.unwrap()["in_use_bytes"], 60);
+
+        drop(first);
+        assert_eq!(budget.in_use(), 0);
+        let _second = budget.try_reserve(100).unwrap();
+        assert!(budget.check().is_err());
+
+        budget.set_limit(None);
+        assert!(budget.try_reserve(u64::MAX / 2).is_ok());
+    }
+}
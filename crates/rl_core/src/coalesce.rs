@@ -0,0 +1,191 @@
+//! Coalescing of identical in-flight queries.
+//!
+//! A UI refreshing rapidly tends to ask for the same status or log page
+//! several times before the first answer arrives. The first such request
+//! leads and runs; identical requests arriving while it runs follow it and
+//! receive a copy of its result instead of starting the same git work again.
+//!
+//! Only read-only queries answered with a single response are coalesced.
+//! Streaming requests, watches, mutations and repository lifecycle requests
+//! always run on their own.
+
+use rl_api::request::RequestPayload;
+use rl_api::response::ResponsePayload;
+use rl_api::{Error, ErrorCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Result shared by a leader with its followers; `None` until it finishes.
+type Shared = Option<Result<ResponsePayload, Error>>;
+
+/// Queries currently running, by payload.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    /// Result channel of each running query, keyed by its serialized payload
+    queries: Arc<Mutex<HashMap<String, watch::Receiver<Shared>>>>,
+}
+
+/// A request's part in coalescing.
+pub(crate) enum Claim {
+    /// No identical query is running; run it and publish the result
+    Lead(Lead),
+    /// An identical query is running; wait for its result
+    Follow(watch::Receiver<Shared>),
+}
+
+impl InFlight {
+    /// Decide whether `payload` runs or follows an identical running query.
+    ///
+    /// Returns `None` for requests that are never coalesced.
+    pub(crate) fn claim(&self, payload: &RequestPayload) -> Option<Claim> {
+        if !coalescible(payload) {
+            return None;
+        }
+        let key = serde_json::to_string(payload).ok()?;
+
+        let mut queries = self.queries.lock().unwrap();
+        if let Some(result) = queries.get(&key) {
+            return Some(Claim::Follow(result.clone()));
+        }
+        let (result, receiver) = watch::channel(None);
+        queries.insert(key.clone(), receiver);
+        Some(Claim::Lead(Lead {
+            key,
+            result,
+            queries: self.queries.clone(),
+        }))
+    }
+}
+
+/// Wait for the leader's result.
+///
+/// Returns `None` if the leader gave up without one, e.g. because it was
+/// cancelled; the follower should then run the query itself.
+pub(crate) async fn follow(mut result: watch::Receiver<Shared>) -> Shared {
+    let result = result.wait_for(Option::is_some).await.ok()?;
+    result.clone()
+}
+
+/// The running copy of a query; dropping it lets the next identical request
+/// lead.
+pub(crate) struct Lead {
+    /// Key the query is registered under
+    key: String,
+    /// Channel its followers wait on
+    result: watch::Sender<Shared>,
+    /// Registry to remove the query from when it finishes
+    queries: Arc<Mutex<HashMap<String, watch::Receiver<Shared>>>>,
+}
+
+impl Lead {
+    /// Hand the result to every follower.
+    ///
+    /// A cancellation only concerns the leader's own request, so followers
+    /// are released to run the query themselves instead.
+    pub(crate) fn finish(self, result: &Result<ResponsePayload, Error>) {
+        let canceled = matches!(result, Err(error) if error.code == ErrorCode::OperationCanceled);
+        if !canceled {
+            self.result.send_replace(Some(result.clone()));
+        }
+    }
+}
+
+impl Drop for Lead {
+    fn drop(&mut self) {
+        self.queries.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Whether identical copies of the request may share one result.
+fn coalescible(payload: &RequestPayload) -> bool {
+    match payload {
+        RequestPayload::Status(_)
+        | RequestPayload::Log(_)
+        | RequestPayload::Graph(_)
+        | RequestPayload::ShowCommit(_)
+        | RequestPayload::DiffSummary(_)
+        | RequestPayload::Branches(_)
+        | RequestPayload::Tags(_)
+        | RequestPayload::Remotes(_) => true,
+        RequestPayload::DiffContent(_)
+        | RequestPayload::Blame(_)
+        | RequestPayload::Watch(_)
+        | RequestPayload::Checkout(_)
+        | RequestPayload::Commit(_)
+        | RequestPayload::Fetch(_)
+        | RequestPayload::Push(_)
+        | RequestPayload::Merge(_)
+        | RequestPayload::Rebase(_)
+        | RequestPayload::Reset(_)
+        | RequestPayload::Clean(_)
+        | RequestPayload::Stash(_)
+        | RequestPayload::Undo(_)
+        | RequestPayload::Journal(_)
+        | RequestPayload::OpenRepo(_)
+        | RequestPayload::CloseRepo(_)
+        | RequestPayload::ListRepos(_)
+        | RequestPayload::Capabilities(_)
+        | RequestPayload::ReloadConfig(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::request::{BlameRequest, StatusRequest};
+
+    fn status(repo_path: &str) -> RequestPayload {
+        RequestPayload::Status(StatusRequest {
+            repo_path: repo_path.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_identical_queries_share_the_leaders_result() {
+        let in_flight = InFlight::default();
+        let Some(Claim::Lead(lead)) = in_flight.claim(&status("/a")) else {
+            panic!("first request should lead");
+        };
+        let Some(Claim::Follow(follower)) = in_flight.claim(&status("/a")) else {
+            panic!("identical request should follow");
+        };
+        assert!(matches!(
+            in_flight.claim(&status("/b")),
+            Some(Claim::Lead(_))
+        ));
+
+        let error = Error::new(ErrorCode::RepoNotFound, "gone");
+        lead.finish(&Err(error));
+        let shared = follow(follower).await.unwrap();
+        assert_eq!(shared.unwrap_err().code, ErrorCode::RepoNotFound);
+
+        // Finished queries no longer absorb new requests
+        assert!(matches!(
+            in_flight.claim(&status("/a")),
+            Some(Claim::Lead(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_releases_followers() {
+        let in_flight = InFlight::default();
+        let Some(Claim::Lead(lead)) = in_flight.claim(&status("/a")) else {
+            panic!("first request should lead");
+        };
+        let Some(Claim::Follow(follower)) = in_flight.claim(&status("/a")) else {
+            panic!("identical request should follow");
+        };
+
+        lead.finish(&Err(Error::new(ErrorCode::OperationCanceled, "cancelled")));
+        assert!(follow(follower).await.is_none());
+
+        let blame = RequestPayload::Blame(BlameRequest {
+            repo_path: "/a".to_string(),
+            path: "f".to_string(),
+            revision: None,
+            lines: None,
+        });
+        assert!(in_flight.claim(&blame).is_none());
+    }
+}
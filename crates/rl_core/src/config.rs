@@ -0,0 +1,270 @@
+//! Loading [`EngineConfig`] from a TOML file and the environment.
+//!
+//! Daemons are tuned without code changes: settings are read from
+//! `$XDG_CONFIG_HOME/repo-lens/config.toml` (by default
+//! `~/.config/repo-lens/config.toml`), or the file named by
+//! `REPO_LENS_CONFIG`, and individual settings can then be overridden with
+//! `REPO_LENS_*` environment variables. A running engine can load them
+//! again with [`RepoEngine::reload_config`](crate::RepoEngine::reload_config),
+//! e.g. on SIGHUP. Every setting is optional:
+//!
+//! ```toml
+//! backend = "cli"
+//! max_concurrent_queries = 16
+//! max_concurrent_queries_per_repo = 4
+//! memory_budget_bytes = 268435456
+//! allowed_roots = ["/srv/repos"]
+//!
+//! [cache]
+//! max_total_bytes = 536870912
+//! eviction = "lru"
+//!
+//! # Overrides for one repository, by path
+//! [repos."/srv/monorepo"]
+//! max_concurrent_queries = 8
+//! ```
+
+use crate::EngineConfig;
+use rl_git::{CliBackend, GitBackend, StubGitBackend};
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the configuration file to load.
+pub const CONFIG_ENV: &str = "REPO_LENS_CONFIG";
+
+/// Environment variables overriding settings, and the setting each sets.
+const ENV_SETTINGS: &[(&str, &[&str])] = &[
+    ("REPO_LENS_BACKEND", &["backend"]),
+    (
+        "REPO_LENS_MAX_CONCURRENT_QUERIES",
+        &["max_concurrent_queries"],
+    ),
+    (
+        "REPO_LENS_MAX_CONCURRENT_QUERIES_PER_REPO",
+        &["max_concurrent_queries_per_repo"],
+    ),
+    ("REPO_LENS_QUERY_TIMEOUT_MS", &["query_timeout_ms"]),
+    ("REPO_LENS_MAX_OPEN_REPOS", &["max_open_repos"]),
+    ("REPO_LENS_REPO_IDLE_TIMEOUT_MS", &["repo_idle_timeout_ms"]),
+    ("REPO_LENS_CACHE_ENABLED", &["cache_enabled"]),
+    (
+        "REPO_LENS_CACHE_MAX_TOTAL_BYTES",
+        &["cache", "max_total_bytes"],
+    ),
+    (
+        "REPO_LENS_CACHE_MAX_PER_REPO_BYTES",
+        &["cache", "max_per_repo_bytes"],
+    ),
+    ("REPO_LENS_CACHE_EVICTION", &["cache", "eviction"]),
+    ("REPO_LENS_MEMORY_BUDGET_BYTES", &["memory_budget_bytes"]),
+    // A TOML array, e.g. `["/srv/repos"]`
+    ("REPO_LENS_ALLOWED_ROOTS", &["allowed_roots"]),
+    ("REPO_LENS_LOG_FILTER", &["log_filter"]),
+];
+
+/// Where a configuration came from, so it can be loaded again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built in code; there is nothing to reload
+    #[default]
+    Code,
+    /// Loaded by [`EngineConfig::load`] with this path
+    Load(Option<PathBuf>),
+}
+
+/// Git implementation serving requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Shell out to the installed git
+    #[default]
+    Cli,
+    /// Fail every repository operation; for tests and scaffolding
+    Stub,
+}
+
+impl BackendKind {
+    /// Every kind of backend
+    pub const ALL: &'static [BackendKind] = &[Self::Cli, Self::Stub];
+
+    /// Name of the kind, as the `backend` setting spells it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cli => "cli",
+            Self::Stub => "stub",
+        }
+    }
+
+    /// Create a backend of this kind.
+    pub fn create(self) -> Box<dyn GitBackend> {
+        match self {
+            Self::Cli => Box::new(CliBackend::new()),
+            Self::Stub => Box::new(StubGitBackend),
+        }
+    }
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown backend '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Settings for one repository, replacing the engine-wide ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Maximum concurrent queries against the repository
+    pub max_concurrent_queries: Option<usize>,
+}
+
+/// Where the configuration file is looked for when `REPO_LENS_CONFIG` is
+/// not set.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("repo-lens").join("config.toml"))
+}
+
+impl EngineConfig {
+    /// Load the configuration for a daemon.
+    ///
+    /// Reads `path` if given, else the file named by `REPO_LENS_CONFIG`,
+    /// else the file at [`default_path`] if there is one, then applies
+    /// `REPO_LENS_*` overrides from the environment. Settings found nowhere
+    /// keep their defaults.
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let source = ConfigSource::Load(path.map(Path::to_path_buf));
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from));
+        let text = match path {
+            Some(path) => Some(read(&path)?),
+            None => match default_path() {
+                Some(path) if path.exists() => Some(read(&path)?),
+                _ => None,
+            },
+        };
+        let config = Self::parse(text.as_deref().unwrap_or_default(), std::env::vars())?;
+        Ok(Self { source, ..config })
+    }
+
+    /// Parse configuration file contents, without environment overrides.
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        Self::parse(text, std::iter::empty())
+    }
+
+    /// Parse `text`, then override settings from the `REPO_LENS_*` entries
+    /// of `vars`.
+    fn parse(text: &str, vars: impl IntoIterator<Item = (String, String)>) -> io::Result<Self> {
+        let mut settings: toml::Table = toml::from_str(text).map_err(invalid)?;
+        for (name, value) in vars {
+            let Some((_, key)) = ENV_SETTINGS.iter().find(|(setting, _)| *setting == name) else {
+                continue;
+            };
+            let (last, parents) = key.split_last().expect("settings have a key");
+            let mut table = &mut settings;
+            for parent in parents {
+                table = table
+                    .entry(*parent)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .ok_or_else(|| invalid(format!("`{}` is not a table", parent)))?;
+            }
+            table.insert(last.to_string(), env_value(&value));
+        }
+        let config: Self = settings.try_into().map_err(invalid)?;
+        if let Some(filter) = &config.log_filter {
+            crate::telemetry::validate_log_filter(filter)
+                .map_err(|error| invalid(format!("log_filter: {}", error)))?;
+        }
+        Ok(config)
+    }
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    std::fs::read_to_string(path).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("Failed to read {}: {}", path.display(), error),
+        )
+    })
+}
+
+/// The TOML value an environment variable spells, e.g. `16`, `true` or,
+/// failing those, the string `lru`.
+fn env_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+fn invalid(error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid configuration: {}", error),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_overrides_file_settings() {
+        let text = r#"
+            backend = "stub"
+            max_concurrent_queries = 4
+
+            [cache]
+            eviction = "lfu"
+
+            [repos."/srv/monorepo"]
+            max_concurrent_queries = 8
+        "#;
+        let vars = [
+            ("REPO_LENS_MAX_CONCURRENT_QUERIES", "16"),
+            ("REPO_LENS_CACHE_MAX_TOTAL_BYTES", "1024"),
+            ("REPO_LENS_TOKEN", "not a setting"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let config = EngineConfig::parse(text, vars).unwrap();
+        assert_eq!(config.backend, BackendKind::Stub);
+        assert_eq!(config.max_concurrent_queries, 16);
+        assert_eq!(config.cache.max_total_bytes, 1024);
+        assert!(matches!(
+            config.cache.eviction,
+            rl_index::EvictionStrategy::Lfu
+        ));
+        assert_eq!(
+            config.repos[Path::new("/srv/monorepo")].max_concurrent_queries,
+            Some(8)
+        );
+        // Untouched settings keep their defaults
+        assert_eq!(
+            config.max_open_repos,
+            EngineConfig::default().max_open_repos
+        );
+
+        let error = EngineConfig::from_toml("max_open_repo = 1").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,183 @@
+//! Stable cursors for paging through history.
+//!
+//! The first Log page, or Graph window, resolves the requested range to the
+//! commit IDs it names at that moment, e.g. `main` becomes the commit `main`
+//! points at. Its cursor carries that pinned range, how many commits have
+//! been returned so far and the last one of them, so later pages walk the
+//! same history however the branches move in between: commits arriving
+//! meanwhile appear on the next first page instead of shifting the pages
+//! already read.
+//!
+//! Cursors are opaque to clients and versioned, so the encoding can change
+//! without misreading cursors handed out by an older server. A cursor that
+//! cannot be read, or whose history is gone (e.g. pruned after a force
+//! push), is rejected with `InvalidCursor`; the client starts again from the
+//! first page.
+
+use rl_api::{Cursor, Error, ErrorCode};
+use rl_git::RepoHandle;
+
+/// Version prefix of the cursors handed out now.
+const VERSION: &str = "v1";
+
+/// Position within a pinned Log range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogCursor {
+    /// Range with every revision resolved to a commit ID
+    pub(crate) range: String,
+    /// Commits of the range already returned
+    pub(crate) skip: usize,
+    /// Last commit returned, expected at position `skip - 1`
+    pub(crate) last_seen: String,
+}
+
+impl LogCursor {
+    /// Position at the start of `range`, which must already be pinned.
+    pub(crate) fn start(range: String) -> Self {
+        Self {
+            range,
+            skip: 0,
+            last_seen: String::new(),
+        }
+    }
+
+    /// Encode for a client to send back.
+    pub(crate) fn encode(&self) -> Cursor {
+        Cursor::from(format!(
+            "{}:{}:{}:{}",
+            VERSION, self.range, self.skip, self.last_seen
+        ))
+    }
+
+    /// Read a cursor a client sent back, or `None` for the first page.
+    pub(crate) fn decode(cursor: &Cursor) -> Result<Option<Self>, Error> {
+        if cursor.get().is_empty() {
+            return Ok(None);
+        }
+
+        let mut fields = cursor.get().split(':');
+        let version = fields.next().unwrap_or_default();
+        if version != VERSION {
+            return Err(invalid_cursor(
+                "Cursor is from an incompatible server version",
+            ));
+        }
+        let (Some(range), Some(skip), Some(last_seen), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid_cursor("Cursor is malformed"));
+        };
+        let skip = skip
+            .parse::<usize>()
+            .map_err(|_| invalid_cursor("Cursor is malformed"))?;
+        // Both end up on git's command line, so only commit IDs are let through
+        let pinned = range_revisions(range);
+        if skip == 0 || !is_commit_id(last_seen) || !pinned.iter().all(|id| is_commit_id(id)) {
+            return Err(invalid_cursor("Cursor is malformed"));
+        }
+
+        Ok(Some(Self {
+            range: range.to_string(),
+            skip,
+            last_seen: last_seen.to_string(),
+        }))
+    }
+
+    /// Fail with `InvalidCursor` if any commit the range was pinned to no
+    /// longer exists in `repo`.
+    pub(crate) async fn check(&self, repo: &dyn RepoHandle) -> Result<(), Error> {
+        for id in range_revisions(&self.range) {
+            if repo.rev_parse(id).await?.is_none() {
+                return Err(invalid_cursor(
+                    "Cursor has expired: the history it points into no longer exists",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolve every revision in `range` (by default `HEAD`) to its commit ID.
+///
+/// Returns `None` when no range was given and HEAD has no commits yet.
+pub(crate) async fn pin_range(
+    repo: &dyn RepoHandle,
+    range: Option<&str>,
+) -> Result<Option<String>, Error> {
+    let Some(range) = range else {
+        return repo.rev_parse("HEAD").await;
+    };
+
+    let (separator, sides) = match range.split_once("...") {
+        Some((from, to)) => ("...", vec![from, to]),
+        None => match range.split_once("..") {
+            Some((from, to)) => ("..", vec![from, to]),
+            None => ("", vec![range]),
+        },
+    };
+    let mut pinned = Vec::new();
+    for side in sides {
+        // An empty side of a range means HEAD, as it does to git
+        let revision = if side.is_empty() { "HEAD" } else { side };
+        let id = repo.rev_parse(revision).await?.ok_or_else(|| {
+            Error::new(
+                ErrorCode::InvalidRequest,
+                format!("Unknown revision: {}", revision),
+            )
+        })?;
+        pinned.push(id);
+    }
+    Ok(Some(pinned.join(separator)))
+}
+
+/// The revisions a range of the form `a`, `a..b` or `a...b` names.
+fn range_revisions(range: &str) -> Vec<&str> {
+    range
+        .split("..")
+        .map(|side| side.trim_start_matches('.'))
+        .collect()
+}
+
+/// Whether `value` is a full SHA-1 or SHA-256 commit ID.
+fn is_commit_id(value: &str) -> bool {
+    matches!(value.len(), 40 | 64) && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn invalid_cursor(message: &str) -> Error {
+    Error::new(ErrorCode::InvalidCursor, message)
+        .with_remediation("Request the first page again without a cursor")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trips_and_rejects_anything_else() {
+        let a = "a".repeat(40);
+        let b = "b".repeat(40);
+        let cursor = LogCursor {
+            range: format!("{}..{}", a, b),
+            skip: 50,
+            last_seen: "c".repeat(40),
+        };
+        assert_eq!(
+            LogCursor::decode(&cursor.encode()).unwrap(),
+            Some(cursor.clone())
+        );
+        assert_eq!(LogCursor::decode(&Cursor::initial()).unwrap(), None);
+        assert_eq!(range_revisions(&format!("{}...{}", a, b)), vec![&a, &b]);
+
+        for text in [
+            format!("v0:{}:50:{}", a, b),
+            format!("v1:{}:0:{}", a, b),
+            format!("v1:{}:fifty:{}", a, b),
+            format!("v1:--output=x:50:{}", b),
+            format!("v1:{}:50:{}:extra", a, b),
+            "garbage".to_string(),
+        ] {
+            let error = LogCursor::decode(&Cursor::from(text)).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidCursor);
+        }
+    }
+}
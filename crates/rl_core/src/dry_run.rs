@@ -0,0 +1,277 @@
+//! Dry runs of mutations.
+//!
+//! A UI asking "are you sure?" before a checkout, merge, rebase, reset,
+//! clean or push wants to show what will actually happen. A dry run works
+//! it out from the repository's current state with read-only git commands:
+//! the working directory files that would be overwritten, the references
+//! that would move, and the commits that would be rewritten or dropped.
+
+use rl_api::request::ResetMode;
+use rl_api::response::{DryRunReport, RefUpdate};
+use rl_api::{Error, ErrorCode};
+use rl_git::RepoHandle;
+
+/// What checking out `req.target` would change.
+pub(crate) async fn checkout(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::CheckoutRequest,
+) -> Result<DryRunReport, Error> {
+    let head = repo.rev_parse("HEAD").await?;
+    if req.create_branch {
+        // The new branch starts at HEAD, so the working directory stays as is
+        return Ok(DryRunReport {
+            refs: vec![RefUpdate {
+                name: format!("refs/heads/{}", req.target),
+                old: None,
+                new: head,
+            }],
+            ..Default::default()
+        });
+    }
+
+    let target = resolve(repo, &req.target).await?;
+    if head.as_deref() == Some(target.as_str()) {
+        return Ok(DryRunReport::default());
+    }
+    let files = match &head {
+        Some(head) => changed_files(
+            &repo
+                .diff_name_status(&format!("{}..{}", head, target))
+                .await?,
+        ),
+        None => Vec::new(),
+    };
+
+    Ok(DryRunReport {
+        files,
+        refs: vec![RefUpdate {
+            name: "HEAD".to_string(),
+            old: head,
+            new: Some(target),
+        }],
+        commits_rewritten: Vec::new(),
+    })
+}
+
+/// What merging `req.source` into the current branch would change.
+pub(crate) async fn merge(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::MergeRequest,
+) -> Result<DryRunReport, Error> {
+    let head = resolve(repo, "HEAD").await?;
+    let source = resolve(repo, &req.source).await?;
+    if repo
+        .rev_list(&format!("{}..{}", head, source))
+        .await?
+        .is_empty()
+    {
+        // Already up to date
+        return Ok(DryRunReport::default());
+    }
+
+    let fast_forward = repo
+        .rev_list(&format!("{}..{}", source, head))
+        .await?
+        .is_empty();
+    let files = changed_files(
+        &repo
+            .diff_name_status(&format!("{}...{}", head, source))
+            .await?,
+    );
+
+    Ok(DryRunReport {
+        files,
+        refs: vec![RefUpdate {
+            name: head_ref(repo).await?,
+            old: Some(head),
+            // A merge commit does not exist until the merge runs
+            new: fast_forward.then_some(source),
+        }],
+        commits_rewritten: Vec::new(),
+    })
+}
+
+/// What rebasing the current branch onto `req.onto` would change.
+pub(crate) async fn rebase(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::RebaseRequest,
+) -> Result<DryRunReport, Error> {
+    let head = resolve(repo, "HEAD").await?;
+    let onto = resolve(repo, &req.onto).await?;
+    let upstream = match &req.upstream {
+        Some(upstream) => resolve(repo, upstream).await?,
+        None => {
+            if repo
+                .rev_list(&format!("{}..{}", head, onto))
+                .await?
+                .is_empty()
+            {
+                // `onto` is already part of the branch
+                return Ok(DryRunReport::default());
+            }
+            onto.clone()
+        }
+    };
+
+    let commits_rewritten = repo.rev_list(&format!("{}..{}", upstream, head)).await?;
+    let files = changed_files(
+        &repo
+            .diff_name_status(&format!("{}...{}", head, onto))
+            .await?,
+    );
+
+    Ok(DryRunReport {
+        files,
+        refs: vec![RefUpdate {
+            name: head_ref(repo).await?,
+            old: Some(head),
+            // With nothing to replay the branch simply moves to `onto`
+            new: commits_rewritten.is_empty().then_some(onto),
+        }],
+        commits_rewritten,
+    })
+}
+
+/// What resetting the current branch to `req.target` would change.
+///
+/// Only a hard reset touches the working directory; the commits the branch
+/// would no longer contain are reported as rewritten.
+pub(crate) async fn reset(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::ResetRequest,
+) -> Result<DryRunReport, Error> {
+    let head = resolve(repo, "HEAD").await?;
+    let target = resolve(repo, &req.target).await?;
+    let files = match req.mode {
+        // Against the working tree: uncommitted changes are lost too
+        ResetMode::Hard => changed_files(&repo.diff_name_status(&target).await?),
+        ResetMode::Soft | ResetMode::Mixed => Vec::new(),
+    };
+    if head == target {
+        return Ok(DryRunReport {
+            files,
+            ..Default::default()
+        });
+    }
+
+    Ok(DryRunReport {
+        files,
+        refs: vec![RefUpdate {
+            name: head_ref(repo).await?,
+            old: Some(head.clone()),
+            new: Some(target.clone()),
+        }],
+        commits_rewritten: repo.rev_list(&format!("{}..{}", target, head)).await?,
+    })
+}
+
+/// The untracked files a clean would remove.
+pub(crate) async fn clean(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::CleanRequest,
+) -> Result<DryRunReport, Error> {
+    Ok(DryRunReport {
+        files: repo.clean(req.directories, req.ignored, true).await?,
+        ..Default::default()
+    })
+}
+
+/// What pushing would change on the remote, as of the last fetch from it.
+///
+/// Moves are reported for the remote-tracking references, which follow the
+/// remote's branches. A force push reports the remote commits it would drop
+/// as rewritten.
+pub(crate) async fn push(
+    repo: &dyn RepoHandle,
+    req: &rl_api::request::PushRequest,
+) -> Result<DryRunReport, Error> {
+    let remote = req.remote.as_deref().unwrap_or("origin");
+    let refspecs = match &req.refspecs {
+        Some(refspecs) => refspecs.clone(),
+        None => {
+            let branch = repo.snapshot().await?.branch.ok_or_else(|| {
+                Error::new(
+                    ErrorCode::InvalidRequest,
+                    "HEAD is detached; name the refspecs to push",
+                )
+            })?;
+            vec![branch]
+        }
+    };
+
+    let mut report = DryRunReport::default();
+    for refspec in &refspecs {
+        let (source, destination) = parse_refspec(refspec);
+        let local = resolve(repo, source).await?;
+        let tracking = format!("refs/remotes/{}/{}", remote, destination);
+        let old = repo.rev_parse(&tracking).await?;
+        if old.as_deref() == Some(local.as_str()) {
+            continue;
+        }
+
+        if let (true, Some(old)) = (req.force, &old) {
+            let dropped = repo.rev_list(&format!("{}..{}", local, old)).await?;
+            report.commits_rewritten.extend(dropped);
+        }
+        report.refs.push(RefUpdate {
+            name: tracking,
+            old,
+            new: Some(local),
+        });
+    }
+    Ok(report)
+}
+
+async fn resolve(repo: &dyn RepoHandle, revision: &str) -> Result<String, Error> {
+    repo.rev_parse(revision).await?.ok_or_else(|| {
+        Error::new(
+            ErrorCode::InvalidRequest,
+            format!("Unknown revision: {}", revision),
+        )
+    })
+}
+
+/// Full name of the reference HEAD points at, or `HEAD` when detached.
+async fn head_ref(repo: &dyn RepoHandle) -> Result<String, Error> {
+    Ok(match repo.snapshot().await?.branch {
+        Some(branch) => format!("refs/heads/{}", branch),
+        None => "HEAD".to_string(),
+    })
+}
+
+/// Paths named in `git diff --name-status` output, taking the new path of
+/// renames.
+fn changed_files(name_status: &str) -> Vec<String> {
+    name_status
+        .lines()
+        .filter_map(|line| line.split('\t').skip(1).last())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Local source and remote branch of a push refspec such as `+main:release`.
+fn parse_refspec(refspec: &str) -> (&str, &str) {
+    let refspec = refspec.trim_start_matches('+');
+    let (source, destination) = refspec.split_once(':').unwrap_or((refspec, refspec));
+    (source, destination.trim_start_matches("refs/heads/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_files_and_refspecs_are_parsed() {
+        let name_status = "M\tsrc/lib.rs\nR087\told.rs\tnew.rs\nD\tgone.rs\n";
+        assert_eq!(
+            changed_files(name_status),
+            vec!["src/lib.rs", "new.rs", "gone.rs"]
+        );
+
+        assert_eq!(parse_refspec("main"), ("main", "main"));
+        assert_eq!(
+            parse_refspec("+HEAD:refs/heads/release"),
+            ("HEAD", "release")
+        );
+    }
+}
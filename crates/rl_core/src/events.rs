@@ -0,0 +1,53 @@
+//! Engine-internal event bus.
+//!
+//! Repository change events are published on one broadcast channel instead
+//! of being pushed to each interested party. Watchers and the engine itself
+//! publish; the engine's caches and every watching client subscribe, so
+//! invalidation and notification never run as part of the request that
+//! noticed the change.
+
+use rl_api::Event;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber; slower subscribers miss older events.
+pub const EVENT_BUFFER: usize = 256;
+
+/// Broadcast channel for repository change events.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    /// Create a bus with no subscribers.
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::Sender::new(EVENT_BUFFER),
+        }
+    }
+
+    /// Send `event` to every current subscriber.
+    pub fn publish(&self, event: Event) {
+        // Having nobody listening is not an error
+        let _ = self.tx.send(event);
+    }
+
+    /// Receive every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Canonical form of a repository path, used to match events to watchers
+/// however either side spelled the path.
+pub fn canonical_repo_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
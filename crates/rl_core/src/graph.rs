@@ -0,0 +1,118 @@
+//! Lane layout of the commit graph.
+//!
+//! Commits are placed newest first, in topological order, each on the lane
+//! waiting for it or on the leftmost free one. A commit's first parent is
+//! then waited for on its own lane and each other parent on a lane of its
+//! own, so a branch keeps its column for as long as it runs.
+
+use rl_api::response::{GraphLane, LaneType};
+
+/// Lanes of the graph, each waiting for the commit it leads to.
+#[derive(Debug, Default)]
+pub(crate) struct Lanes {
+    expected: Vec<Option<String>>,
+}
+
+impl Lanes {
+    /// Place the next commit and return the lanes of its row.
+    ///
+    /// Lanes ending at the commit, and lanes opened for a merge's other
+    /// parents, are `Merge` lanes; lanes passing by are `Branch` lanes.
+    pub(crate) fn place(&mut self, id: &str, parents: &[String]) -> Vec<GraphLane> {
+        let column = match self.position(id) {
+            Some(column) => column,
+            None => self.free_lane(),
+        };
+        let mut row: Vec<LaneType> = self
+            .expected
+            .iter()
+            .enumerate()
+            .map(|(lane, expected)| match expected {
+                _ if lane == column => LaneType::Commit,
+                Some(expected) if expected == id => LaneType::Merge,
+                Some(_) => LaneType::Branch,
+                None => LaneType::Empty,
+            })
+            .collect();
+
+        // Other lanes that led to this commit end here
+        for expected in &mut self.expected {
+            if expected.as_deref() == Some(id) {
+                *expected = None;
+            }
+        }
+        let mut parents = parents.iter();
+        self.expected[column] = parents.next().cloned();
+        for parent in parents {
+            let lane = match self.position(parent) {
+                Some(lane) => lane,
+                None => {
+                    let lane = self.free_lane();
+                    self.expected[lane] = Some(parent.clone());
+                    lane
+                }
+            };
+            if row.len() <= lane {
+                row.resize(lane + 1, LaneType::Empty);
+            }
+            row[lane] = LaneType::Merge;
+        }
+        while self.expected.last() == Some(&None) {
+            self.expected.pop();
+        }
+
+        while matches!(row.last(), Some(LaneType::Empty)) {
+            row.pop();
+        }
+        row.into_iter()
+            .enumerate()
+            .map(|(index, lane_type)| GraphLane { index, lane_type })
+            .collect()
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.expected
+            .iter()
+            .position(|expected| expected.as_deref() == Some(id))
+    }
+
+    fn free_lane(&mut self) -> usize {
+        match self.expected.iter().position(Option::is_none) {
+            Some(lane) => lane,
+            None => {
+                self.expected.push(None);
+                self.expected.len() - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lanes of a row as one letter each: commit, merge, branch or empty
+    fn letters(row: &[GraphLane]) -> String {
+        row.iter()
+            .map(|lane| match lane.lane_type {
+                LaneType::Commit => 'c',
+                LaneType::Merge => 'm',
+                LaneType::Branch => 'b',
+                LaneType::Empty => '.',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merges_open_lanes_and_forks_close_them() {
+        let mut lanes = Lanes::default();
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        // m merges b into a; both grow from root
+        assert_eq!(letters(&lanes.place("m", &ids(&["a", "b"]))), "cm");
+        assert_eq!(letters(&lanes.place("a", &ids(&["root"]))), "cb");
+        assert_eq!(letters(&lanes.place("b", &ids(&["root"]))), "bc");
+        assert_eq!(letters(&lanes.place("root", &[])), "cm");
+        assert!(lanes.expected.is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+//! Typed request handlers, registered by payload variant.
+//!
+//! Every request is routed to the [`Handler`] registered for its payload
+//! variant, which receives the variant's request type rather than the whole
+//! payload. The engine registers its own handlers when it is created;
+//! embedders can replace any of them with
+//! [`RepoEngine::register_handler`](crate::RepoEngine::register_handler),
+//! e.g. to serve a request type from somewhere other than git. Adding a
+//! request type takes a [`PayloadVariant`] entry and a handler here instead
+//! of another arm in dispatch.
+
+use crate::session::Session;
+use crate::stream::ChunkSink;
+use crate::{CancellationToken, RepoEngine};
+use rl_api::request::*;
+use rl_api::response::ResponsePayload;
+use rl_api::Error;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Request type carried by one [`RequestPayload`] variant.
+pub trait PayloadVariant: Send + Sized + 'static {
+    /// Wire name of the variant, as returned by [`RequestPayload::kind`]
+    const KIND: &'static str;
+
+    /// Take the request out of `payload` if it is this variant.
+    fn from_payload(payload: RequestPayload) -> Option<Self>;
+}
+
+macro_rules! payload_variants {
+    ($($variant:ident($request:ty) => $kind:literal,)*) => {
+        $(
+            impl PayloadVariant for $request {
+                const KIND: &'static str = $kind;
+
+                fn from_payload(payload: RequestPayload) -> Option<Self> {
+                    match payload {
+                        RequestPayload::$variant(request) => Some(request),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+payload_variants! {
+    Status(StatusRequest) => "status",
+    Log(LogRequest) => "log",
+    Graph(GraphRequest) => "graph",
+    ShowCommit(ShowCommitRequest) => "show_commit",
+    DiffSummary(DiffSummaryRequest) => "diff_summary",
+    DiffContent(DiffContentRequest) => "diff_content",
+    Blame(BlameRequest) => "blame",
+    Branches(BranchesRequest) => "branches",
+    Tags(TagsRequest) => "tags",
+    Remotes(RemotesRequest) => "remotes",
+    Checkout(CheckoutRequest) => "checkout",
+    Commit(CommitRequest) => "commit",
+    Fetch(FetchRequest) => "fetch",
+    Push(PushRequest) => "push",
+    Merge(MergeRequest) => "merge",
+    Rebase(RebaseRequest) => "rebase",
+    Reset(ResetRequest) => "reset",
+    Clean(CleanRequest) => "clean",
+    Stash(StashRequest) => "stash",
+    Undo(UndoRequest) => "undo",
+    Journal(JournalRequest) => "journal",
+    Watch(WatchRequest) => "watch",
+    OpenRepo(OpenRepoRequest) => "open_repo",
+    CloseRepo(CloseRepoRequest) => "close_repo",
+    ListRepos(ListReposRequest) => "list_repos",
+    Capabilities(CapabilitiesRequest) => "capabilities",
+    ReloadConfig(ReloadConfigRequest) => "reload_config",
+}
+
+/// What a handler runs with besides its request.
+pub struct HandlerContext<'a> {
+    /// Engine handling the request
+    pub engine: &'a RepoEngine,
+    /// Session the request arrived on
+    pub session: &'a Session,
+    /// Destination for the intermediate chunks of a streaming response
+    pub sink: &'a ChunkSink,
+    /// Cancellation of the request; check it between steps
+    pub cancellation: &'a CancellationToken,
+}
+
+/// Serves one request type.
+///
+/// Handlers run once the request has its repository lock and a turn from
+/// the scheduler, inside a step named after the variant. A streaming
+/// handler sends every chunk but the last through the context's sink and
+/// returns the last one.
+#[async_trait::async_trait]
+pub trait Handler: Send + Sync {
+    /// Request type handled
+    type Request: PayloadVariant;
+
+    /// Answer `request`.
+    async fn handle(
+        &self,
+        request: Self::Request,
+        cx: &HandlerContext<'_>,
+    ) -> Result<ResponsePayload, Error>;
+}
+
+/// [`Handler`] with its request type erased, so handlers for every
+/// variant fit in one map.
+#[async_trait::async_trait]
+trait AnyHandler: Send + Sync {
+    async fn handle_payload(
+        &self,
+        payload: RequestPayload,
+        cx: &HandlerContext<'_>,
+    ) -> Result<ResponsePayload, Error>;
+}
+
+#[async_trait::async_trait]
+impl<H: Handler> AnyHandler for H {
+    async fn handle_payload(
+        &self,
+        payload: RequestPayload,
+        cx: &HandlerContext<'_>,
+    ) -> Result<ResponsePayload, Error> {
+        let kind = payload.kind();
+        let Some(request) = H::Request::from_payload(payload) else {
+            return Err(Error::new(
+                rl_api::ErrorCode::Internal,
+                format!(
+                    "Handler for {} requests was given a {} request",
+                    H::Request::KIND,
+                    kind
+                ),
+            ));
+        };
+        self.handle(request, cx).await
+    }
+}
+
+/// Handlers by the wire name of the variant they serve.
+pub(crate) struct Handlers {
+    by_kind: HashMap<&'static str, Arc<dyn AnyHandler>>,
+}
+
+impl Handlers {
+    /// The engine's own handler for every variant.
+    pub(crate) fn builtin() -> Self {
+        let mut handlers = Self {
+            by_kind: HashMap::new(),
+        };
+        builtin::register(&mut handlers);
+        handlers
+    }
+
+    /// Route requests of `H::Request`'s variant to `handler`, replacing the
+    /// handler registered for it before.
+    pub(crate) fn insert<H: Handler + 'static>(&mut self, handler: H) {
+        self.by_kind.insert(H::Request::KIND, Arc::new(handler));
+    }
+
+    /// Answer `payload` with the handler registered for its variant.
+    pub(crate) async fn handle(
+        &self,
+        payload: RequestPayload,
+        cx: &HandlerContext<'_>,
+    ) -> Result<ResponsePayload, Error> {
+        match self.by_kind.get(payload.kind()) {
+            Some(handler) => handler.handle_payload(payload, cx).await,
+            None => Err(Error::new(
+                rl_api::ErrorCode::InvalidRequest,
+                format!("No handler is registered for {} requests", payload.kind()),
+            )),
+        }
+    }
+}
+
+/// Handlers backed by the engine's own `handle_*` methods.
+mod builtin {
+    use super::*;
+
+    macro_rules! builtin_handlers {
+        ($($name:ident($request:ty) => |$engine:ident, $req:ident, $cx:ident| $body:expr;)*) => {
+            $(
+                struct $name;
+
+                #[async_trait::async_trait]
+                impl Handler for $name {
+                    type Request = $request;
+
+                    async fn handle(
+                        &self,
+                        $req: $request,
+                        $cx: &HandlerContext<'_>,
+                    ) -> Result<ResponsePayload, Error> {
+                        let $engine = $cx.engine;
+                        $body
+                    }
+                }
+            )*
+
+            pub(super) fn register(handlers: &mut Handlers) {
+                $(handlers.insert($name);)*
+            }
+        };
+    }
+
+    builtin_handlers! {
+        Status(StatusRequest) => |engine, req, cx| {
+            engine.handle_status(req, cx.session, cx.cancellation).await
+        };
+        Log(LogRequest) => |engine, req, cx| {
+            engine.handle_log(req, cx.session, cx.cancellation).await
+        };
+        Graph(GraphRequest) => |engine, req, cx| {
+            engine.handle_graph(req, cx.session, cx.cancellation).await
+        };
+        ShowCommit(ShowCommitRequest) => |engine, req, _cx| {
+            engine.handle_show_commit(req).await
+        };
+        DiffSummary(DiffSummaryRequest) => |engine, req, cx| {
+            engine.handle_diff_summary(req, cx.session, cx.cancellation).await
+        };
+        DiffContent(DiffContentRequest) => |engine, req, cx| {
+            engine
+                .handle_diff_content(req, cx.session, cx.sink, cx.cancellation)
+                .await
+        };
+        Blame(BlameRequest) => |engine, req, cx| {
+            engine
+                .handle_blame(req, cx.session, cx.sink, cx.cancellation)
+                .await
+        };
+        Branches(BranchesRequest) => |engine, req, cx| {
+            engine.handle_branches(req, cx.session).await
+        };
+        Tags(TagsRequest) => |engine, req, cx| engine.handle_tags(req, cx.session).await;
+        Remotes(RemotesRequest) => |engine, req, cx| {
+            engine.handle_remotes(req, cx.session).await
+        };
+        Checkout(CheckoutRequest) => |engine, req, cx| {
+            engine.handle_checkout(req, cx.session).await
+        };
+        Commit(CommitRequest) => |engine, req, _cx| engine.handle_commit(req).await;
+        Fetch(FetchRequest) => |engine, req, cx| {
+            engine
+                .handle_fetch(req, cx.session, cx.sink, cx.cancellation)
+                .await
+        };
+        Push(PushRequest) => |engine, req, cx| {
+            engine
+                .handle_push(req, cx.session, cx.sink, cx.cancellation)
+                .await
+        };
+        Merge(MergeRequest) => |engine, req, cx| engine.handle_merge(req, cx.session).await;
+        Rebase(RebaseRequest) => |engine, req, cx| {
+            engine
+                .handle_rebase(req, cx.session, cx.sink, cx.cancellation)
+                .await
+        };
+        Reset(ResetRequest) => |engine, req, cx| engine.handle_reset(req, cx.session).await;
+        Clean(CleanRequest) => |engine, req, cx| engine.handle_clean(req, cx.session).await;
+        Stash(StashRequest) => |engine, req, cx| engine.handle_stash(req, cx.session).await;
+        Undo(UndoRequest) => |engine, req, cx| engine.handle_undo(req, cx.session).await;
+        Journal(JournalRequest) => |engine, req, _cx| Ok(engine.handle_journal(req));
+        Watch(WatchRequest) => |engine, req, cx| engine.handle_watch(req, cx.session).await;
+        OpenRepo(OpenRepoRequest) => |engine, req, cx| {
+            engine.handle_open_repo(req, cx.session).await
+        };
+        CloseRepo(CloseRepoRequest) => |engine, req, cx| {
+            engine.handle_close_repo(req, cx.session).await
+        };
+        ListRepos(ListReposRequest) => |engine, _req, _cx| Ok(engine.handle_list_repos().await);
+        Capabilities(CapabilitiesRequest) => |engine, _req, _cx| {
+            Ok(engine.handle_capabilities().await)
+        };
+        ReloadConfig(ReloadConfigRequest) => |engine, _req, _cx| engine.handle_reload_config();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::response::OperationResult;
+    use rl_api::Request;
+
+    /// Answers every Status request without touching git.
+    struct FixedStatus;
+
+    #[async_trait::async_trait]
+    impl Handler for FixedStatus {
+        type Request = StatusRequest;
+
+        async fn handle(
+            &self,
+            request: StatusRequest,
+            _cx: &HandlerContext<'_>,
+        ) -> Result<ResponsePayload, Error> {
+            Ok(ResponsePayload::OperationResult(OperationResult {
+                success: true,
+                message: Some(request.repo_path),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler_replaces_builtin() {
+        let mut engine = RepoEngine::new();
+        engine.register_handler(FixedStatus);
+
+        let response = engine
+            .handle(Request::new(
+                "status",
+                RequestPayload::Status(StatusRequest {
+                    repo_path: "/not/a/repo".to_string(),
+                }),
+            ))
+            .await;
+        match response.result.unwrap() {
+            ResponsePayload::OperationResult(result) => {
+                assert_eq!(result.message.as_deref(), Some("/not/a/repo"))
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Replaying retried mutations.
+//!
+//! A client that loses its connection while a commit or push is running
+//! cannot tell whether it happened, and retrying could do it twice. A
+//! mutating request may carry an idempotency key: the engine remembers the
+//! result of each key for a while, and a request repeating a key is answered
+//! with that result instead of running again. A retry arriving while the
+//! original still runs waits for it.
+//!
+//! Results that say nothing about whether the mutation happened, such as
+//! cancellation or load shedding, are not remembered, so a retry after one
+//! of them runs. Reusing a key for a different request is rejected with
+//! `InvalidRequest`.
+
+use rl_api::request::RequestPayload;
+use rl_api::response::ResponsePayload;
+use rl_api::{Error, ErrorCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How long the result of a key is remembered.
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(600);
+
+/// Finished keys remembered at most.
+const MAX_KEYS: usize = 1024;
+
+/// Longest key accepted, in bytes.
+const MAX_KEY_LEN: usize = 256;
+
+/// Result of the request that first used a key; `None` until it finishes.
+type Shared = Option<Result<ResponsePayload, Error>>;
+
+/// Idempotency keys seen recently, and what they were answered with.
+#[derive(Default)]
+pub(crate) struct IdempotencyKeys {
+    keys: Arc<Mutex<HashMap<String, Remembered>>>,
+}
+
+struct Remembered {
+    /// Serialized payload of the request that first used the key
+    payload: String,
+    /// Its result, once finished
+    result: watch::Receiver<Shared>,
+    /// When it finished; `None` while it runs
+    finished_at: Option<Instant>,
+}
+
+/// A keyed request's part in idempotency.
+pub(crate) enum Claim {
+    /// The key is new; run the request and remember the result
+    Run(Pending),
+    /// The key was used before; answer with that request's result
+    Replay(watch::Receiver<Shared>),
+}
+
+impl IdempotencyKeys {
+    /// Decide whether a request with `key` runs or replays an earlier result.
+    pub(crate) fn claim(&self, key: &str, payload: &RequestPayload) -> Result<Claim, Error> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(Error::new(
+                ErrorCode::InvalidRequest,
+                format!(
+                    "Idempotency keys must be between 1 and {} bytes long",
+                    MAX_KEY_LEN
+                ),
+            ));
+        }
+        let serialized = serde_json::to_string(payload).map_err(|error| {
+            Error::new(
+                ErrorCode::Internal,
+                format!("Failed to serialize request: {}", error),
+            )
+        })?;
+
+        let mut keys = self.keys.lock().unwrap();
+        let now = Instant::now();
+        keys.retain(|_, remembered| {
+            remembered
+                .finished_at
+                .is_none_or(|at| now.duration_since(at) < IDEMPOTENCY_TTL)
+        });
+
+        if let Some(remembered) = keys.get(key) {
+            if remembered.payload != serialized {
+                return Err(Error::new(
+                    ErrorCode::InvalidRequest,
+                    format!(
+                        "Idempotency key {} was already used for a different request",
+                        key
+                    ),
+                )
+                .with_remediation("Use a new idempotency key for each operation"));
+            }
+            return Ok(Claim::Replay(remembered.result.clone()));
+        }
+
+        // Requests still running are never forgotten, so their retries
+        // cannot slip through
+        while keys.len() >= MAX_KEYS {
+            let oldest = keys
+                .iter()
+                .filter_map(|(key, remembered)| Some((remembered.finished_at?, key)))
+                .min()
+                .map(|(_, key)| key.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            keys.remove(&oldest);
+        }
+
+        let (result, receiver) = watch::channel(None);
+        keys.insert(
+            key.to_string(),
+            Remembered {
+                payload: serialized,
+                result: receiver,
+                finished_at: None,
+            },
+        );
+        Ok(Claim::Run(Pending {
+            key: key.to_string(),
+            result,
+            keys: self.keys.clone(),
+        }))
+    }
+}
+
+/// Wait for the result of the request that first used the key.
+///
+/// Returns `None` if it finished without a result worth replaying; the
+/// retry should then run itself.
+pub(crate) async fn replay(mut result: watch::Receiver<Shared>) -> Shared {
+    let result = result.wait_for(Option::is_some).await.ok()?;
+    result.clone()
+}
+
+/// The first request with a key; dropping it without finishing forgets the
+/// key.
+pub(crate) struct Pending {
+    key: String,
+    result: watch::Sender<Shared>,
+    keys: Arc<Mutex<HashMap<String, Remembered>>>,
+}
+
+impl Pending {
+    /// Remember `result` for retries, unless it leaves open whether the
+    /// mutation happened.
+    pub(crate) fn finish(self, result: &Result<ResponsePayload, Error>) {
+        let inconclusive = matches!(
+            result,
+            Err(error) if matches!(
+                error.code,
+                ErrorCode::OperationCanceled
+                    | ErrorCode::Timeout
+                    | ErrorCode::Overloaded
+                    | ErrorCode::ConnectionLost
+                    | ErrorCode::RateLimited
+                    | ErrorCode::QuotaExceeded
+            )
+        );
+        if inconclusive {
+            return;
+        }
+        if let Some(remembered) = self.keys.lock().unwrap().get_mut(&self.key) {
+            remembered.finished_at = Some(Instant::now());
+        }
+        self.result.send_replace(Some(result.clone()));
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if self.result.borrow().is_none() {
+            self.keys.lock().unwrap().remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::request::StashRequest;
+    use rl_api::response::OperationResult;
+
+    fn stash(repo_path: &str) -> RequestPayload {
+        RequestPayload::Stash(StashRequest {
+            repo_path: repo_path.to_string(),
+            action: Default::default(),
+            message: None,
+            include_untracked: false,
+            index: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_keys_replay_conclusive_results_only() {
+        let keys = IdempotencyKeys::default();
+
+        let Claim::Run(pending) = keys.claim("a", &stash("/repo")).unwrap() else {
+            panic!("a new key should run");
+        };
+        // A retry while the first request runs waits for its result
+        let Claim::Replay(waiting) = keys.claim("a", &stash("/repo")).unwrap() else {
+            panic!("a running key should replay");
+        };
+        pending.finish(&Ok(ResponsePayload::OperationResult(OperationResult {
+            success: true,
+            message: None,
+        })));
+        assert!(matches!(replay(waiting).await, Some(Ok(_))));
+
+        let error = keys.claim("a", &stash("/other")).err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidRequest);
+
+        let Claim::Run(pending) = keys.claim("b", &stash("/repo")).unwrap() else {
+            panic!("a new key should run");
+        };
+        pending.finish(&Err(Error::new(ErrorCode::OperationCanceled, "canceled")));
+        assert!(matches!(
+            keys.claim("b", &stash("/repo")).unwrap(),
+            Claim::Run(_)
+        ));
+    }
+}
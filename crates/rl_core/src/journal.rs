@@ -0,0 +1,351 @@
+//! Journal of the operations the engine performed, and undoing them.
+//!
+//! Each mutation that succeeds is recorded per repository as the change it
+//! made to HEAD, the references and the stash, found by comparing their
+//! state before and after it ran. Undo restores the state before the most
+//! recent entry, provided the repository is still in the state that entry
+//! left it in: anything else changed the repository since, and rewinding
+//! over that could lose work. Only operations run through the engine are
+//! journaled.
+
+use crate::events::canonical_repo_path;
+use rl_api::request::RequestPayload;
+use rl_api::response::{JournalEntry, RefUpdate};
+use rl_api::{Error, ErrorCode};
+use rl_git::RepoHandle;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries kept per repository; older ones can no longer be undone.
+const MAX_ENTRIES: usize = 100;
+
+/// Name of the reference holding the most recent stash entry.
+const STASH_REF: &str = "refs/stash";
+
+/// Operations performed per repository.
+#[derive(Default)]
+pub(crate) struct Journal {
+    /// Journals by canonical repository path
+    repos: Mutex<HashMap<PathBuf, RepoJournal>>,
+}
+
+#[derive(Default)]
+struct RepoJournal {
+    /// Entries, oldest first
+    entries: VecDeque<JournalEntry>,
+    next_id: u64,
+}
+
+/// HEAD, the references and the stash of a repository at one moment.
+pub(crate) struct RefState {
+    head: Option<String>,
+    branch: Option<String>,
+    /// Commit OID of each direct reference, by full name
+    refs: HashMap<String, String>,
+    /// Commit OIDs of the stash entries, most recent first
+    stash: Vec<String>,
+}
+
+impl RefState {
+    /// Read the current state of `repo`.
+    pub(crate) async fn capture(repo: &dyn RepoHandle) -> Result<Self, Error> {
+        let snapshot = repo.snapshot().await?;
+        let refs = repo
+            .refs_store()
+            .all_refs()
+            .await?
+            .into_iter()
+            .filter(|info| !info.is_symbolic)
+            .map(|info| (info.name, info.target))
+            .collect();
+        let stash = repo
+            .stash_list()
+            .await?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect();
+        Ok(Self {
+            head: snapshot.head,
+            branch: snapshot.branch,
+            refs,
+            stash,
+        })
+    }
+}
+
+/// Whether a successful run of `payload` is recorded in the journal.
+///
+/// Undo itself is not, so undoing again reverses the operation before.
+pub(crate) fn journaled(payload: &RequestPayload) -> bool {
+    !payload.is_read_only() && !matches!(payload, RequestPayload::Undo(_))
+}
+
+impl Journal {
+    /// Record `operation` as the change from `before` to `after`. Nothing is
+    /// recorded for an operation that changed nothing.
+    pub(crate) fn record(
+        &self,
+        repo_path: &Path,
+        operation: &str,
+        before: RefState,
+        after: RefState,
+    ) {
+        let names: BTreeSet<&String> = before.refs.keys().chain(after.refs.keys()).collect();
+        let refs: Vec<RefUpdate> = names
+            .into_iter()
+            .filter(|name| before.refs.get(*name) != after.refs.get(*name))
+            .map(|name| RefUpdate {
+                name: name.clone(),
+                old: before.refs.get(name).cloned(),
+                new: after.refs.get(name).cloned(),
+            })
+            .collect();
+        // `refs/stash` names only the newest entry: the entries themselves
+        // tell a push from a pop that left an older entry on top
+        let stash_created = after
+            .stash
+            .iter()
+            .find(|id| !before.stash.contains(id))
+            .cloned();
+        let stash_dropped = before
+            .stash
+            .iter()
+            .find(|id| !after.stash.contains(id))
+            .cloned();
+        if refs.is_empty()
+            && before.head == after.head
+            && before.branch == after.branch
+            && stash_created.is_none()
+            && stash_dropped.is_none()
+        {
+            return;
+        }
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+        let mut repos = self.repos.lock().unwrap();
+        let journal = repos.entry(canonical_repo_path(repo_path)).or_default();
+        journal.next_id += 1;
+        if journal.entries.len() >= MAX_ENTRIES {
+            journal.entries.pop_front();
+        }
+        journal.entries.push_back(JournalEntry {
+            id: journal.next_id,
+            operation: operation.to_string(),
+            timestamp_ms,
+            head_before: before.head,
+            head_after: after.head,
+            branch_before: before.branch,
+            branch_after: after.branch,
+            refs,
+            stash_created,
+            stash_dropped,
+        });
+    }
+
+    /// The journal of the repository at `repo_path`, most recent first.
+    pub(crate) fn entries(&self, repo_path: &Path) -> Vec<JournalEntry> {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(&canonical_repo_path(repo_path))
+            .map(|journal| journal.entries.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The most recent entry for the repository at `repo_path`.
+    pub(crate) fn last(&self, repo_path: &Path) -> Option<JournalEntry> {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(&canonical_repo_path(repo_path))?
+            .entries
+            .back()
+            .cloned()
+    }
+
+    /// Drop entry `id` once it has been undone.
+    pub(crate) fn remove(&self, repo_path: &Path, id: u64) {
+        if let Some(journal) = self
+            .repos
+            .lock()
+            .unwrap()
+            .get_mut(&canonical_repo_path(repo_path))
+        {
+            journal.entries.retain(|entry| entry.id != id);
+        }
+    }
+}
+
+/// Return `repo` to the state before `entry`.
+///
+/// The checked-out branch is moved with `git reset --keep`, so uncommitted
+/// changes survive, and the undo fails instead of overwriting them.
+pub(crate) async fn undo(repo: &dyn RepoHandle, entry: &JournalEntry) -> Result<(), Error> {
+    let current = RefState::capture(repo).await?;
+    let untouched = current.head == entry.head_after
+        && current.branch == entry.branch_after
+        && entry
+            .refs
+            .iter()
+            .all(|update| current.refs.get(&update.name) == update.new.as_ref())
+        && entry
+            .stash_created
+            .as_ref()
+            .is_none_or(|created| current.stash.first() == Some(created))
+        && entry
+            .stash_dropped
+            .as_ref()
+            .is_none_or(|dropped| !current.stash.contains(dropped));
+    if !untouched {
+        return Err(Error::new(
+            ErrorCode::Conflict,
+            format!("The repository changed after the {}", entry.operation),
+        )
+        .with_remediation("Undo only reverses the most recent change to the repository"));
+    }
+
+    let checked_out = entry
+        .branch_after
+        .as_ref()
+        .map(|branch| format!("refs/heads/{}", branch));
+    // The stash is a reflog, not a reference: moving `refs/stash` would
+    // lose the entries below the one moved
+    let mut remaining: Vec<&RefUpdate> = entry
+        .refs
+        .iter()
+        .filter(|update| update.name != STASH_REF)
+        .collect();
+    undo_stash(repo, entry).await?;
+
+    // Move the checked-out branch together with the working directory
+    let moved_branch = remaining
+        .iter()
+        .position(|update| Some(&update.name) == checked_out.as_ref() && update.old.is_some());
+    if let Some(index) = moved_branch {
+        let update = remaining.remove(index);
+        if let Some(old) = &update.old {
+            repo.reset_keep(old).await?;
+        }
+    } else if entry.branch_after.is_none() && entry.branch_before.is_none() {
+        if let (Some(head), true) = (&entry.head_before, entry.head_before != entry.head_after) {
+            repo.checkout(head, true).await?;
+        }
+    }
+
+    if entry.branch_before != entry.branch_after {
+        match (&entry.branch_before, &entry.head_before) {
+            (Some(branch), _) => repo.checkout(branch, false).await?,
+            (None, Some(head)) => repo.checkout(head, true).await?,
+            // There is no commit to return an unborn branch to
+            (None, None) => {}
+        }
+    }
+
+    // Branches created by the operation are no longer checked out, so they
+    // can be deleted along with the other references
+    for update in remaining {
+        repo.update_ref(&update.name, update.old.as_deref(), update.new.as_deref())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Reverse the change `entry` made to the stash.
+///
+/// An entry it pushed is popped back into the working tree. An entry it
+/// popped or dropped is stored again, on top of the stash; changes a pop
+/// applied stay in the working tree.
+async fn undo_stash(repo: &dyn RepoHandle, entry: &JournalEntry) -> Result<(), Error> {
+    if entry.stash_created.is_some() {
+        // The state check made sure it is still the newest entry
+        let conflicts = repo.stash_apply(0, true).await?;
+        if !conflicts.is_empty() {
+            return Err(Error::new(
+                ErrorCode::Conflict,
+                format!(
+                    "Restoring the stashed changes conflicts in {}",
+                    conflicts.join(", ")
+                ),
+            )
+            .with_remediation(
+                "The entry is kept in the stash; resolve the conflicts, then drop it",
+            ));
+        }
+    }
+    if let Some(dropped) = &entry.stash_dropped {
+        repo.stash_store(dropped).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(head: &str, refs: &[(&str, &str)]) -> RefState {
+        let stash = refs
+            .iter()
+            .filter(|(name, _)| *name == STASH_REF)
+            .map(|(_, target)| target.to_string())
+            .collect();
+        RefState {
+            head: Some(head.to_string()),
+            branch: Some("main".to_string()),
+            refs: refs
+                .iter()
+                .map(|(name, target)| (name.to_string(), target.to_string()))
+                .collect(),
+            stash,
+        }
+    }
+
+    #[test]
+    fn test_only_changed_references_are_journaled() {
+        let journal = Journal::default();
+        let repo = Path::new("/repo");
+        journal.record(
+            repo,
+            "fetch",
+            state("a", &[("refs/heads/main", "a")]),
+            state("a", &[("refs/heads/main", "a")]),
+        );
+        assert!(journal.last(repo).is_none());
+
+        journal.record(
+            repo,
+            "stash",
+            state("a", &[("refs/heads/main", "a"), ("refs/tags/old", "t")]),
+            state("a", &[("refs/heads/main", "a"), ("refs/stash", "s")]),
+        );
+        let entry = journal.last(repo).unwrap();
+        assert_eq!(entry.operation, "stash");
+        assert_eq!(entry.stash_created.as_deref(), Some("s"));
+        assert_eq!(entry.stash_dropped, None);
+        let changed: Vec<_> = entry
+            .refs
+            .iter()
+            .map(|update| {
+                (
+                    update.name.as_str(),
+                    update.old.as_deref(),
+                    update.new.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                ("refs/stash", None, Some("s")),
+                ("refs/tags/old", Some("t"), None)
+            ]
+        );
+
+        journal.remove(repo, entry.id);
+        assert!(journal.entries(repo).is_empty());
+    }
+}
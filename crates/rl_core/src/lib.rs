@@ -3,38 +3,91 @@
 //! This crate provides the core engine logic that coordinates Git operations,
 //! caching, and query execution without any CLI/IPC/UI dependencies.
 
-use rl_api::{response::ResponsePayload, Error, Request, Response};
-use rl_git::CliBackend;
-use rl_index::IndexManager;
+use budget::MemoryBudget;
+use coalesce::{Claim, InFlight};
+use events::EventBus;
+use handler::{Handler, HandlerContext, Handlers};
+use idempotency::IdempotencyKeys;
+use journal::{Journal, RefState};
+use locks::RepoLocks;
+use metrics::Metrics;
+use middleware::Middleware;
+use prefetch::Prefetcher;
+use queue::QueryQueue;
+use registry::RepoRegistry;
+use results::ResultCache;
+use rl_api::{response::ResponsePayload, Error, Request, RequestPriority, Response};
+use rl_index::{CachePolicy, IndexManager};
+use sandbox::Sandbox;
+use session::Session;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use stream::ChunkSink;
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::Instrument;
 
+pub mod budget;
+mod coalesce;
+pub mod config;
+mod cursor;
+mod dry_run;
+pub mod events;
+mod graph;
+pub mod handler;
+mod idempotency;
+mod journal;
+mod locks;
+pub mod metrics;
+pub mod middleware;
+mod otlp;
+mod prefetch;
+mod queue;
+mod registry;
+mod results;
+mod sandbox;
+pub mod session;
+pub mod stream;
 pub mod telemetry;
 
-#[allow(dead_code)]
-#[async_trait::async_trait]
-trait Handler {
-    type Request;
-    type Response;
-
-    async fn handle_impl(&self, request: Self::Request) -> Result<Self::Response, Error>;
-}
-
 /// Long-lived engine instance managing a repository.
 pub struct RepoEngine {
-    /// Engine configuration
-    #[allow(dead_code)]
-    config: EngineConfig,
-    /// Git backend
-    #[allow(dead_code)]
-    git_backend: Box<dyn rl_git::GitBackend>,
+    /// Engine configuration, replaced on reload
+    config: std::sync::Mutex<EngineConfig>,
+    /// Open repositories, shared by every session
+    repos: RepoRegistry,
     /// Index manager for caching
-    #[allow(dead_code)]
-    index_manager: IndexManager,
+    index_manager: std::sync::Mutex<IndexManager>,
+    /// Events the index manager has yet to apply
+    index_events: std::sync::Mutex<broadcast::Receiver<rl_api::Event>>,
+    /// Repository change events
+    events: EventBus,
     /// Scheduler for query execution
-    #[allow(dead_code)]
-    scheduler: Scheduler,
+    queue: QueryQueue,
+    /// Keeps mutations of a repository from overlapping anything else on it
+    locks: RepoLocks,
+    /// Mutations performed, for display and undo
+    journal: Journal,
+    /// Queries running now, for identical requests to share
+    in_flight: InFlight,
+    /// Results of recent mutations by idempotency key, for retries
+    idempotency: IdempotencyKeys,
+    /// Speculatively fetched Log and Graph windows
+    prefetcher: Prefetcher,
+    /// Log pages and diff summaries over fixed history, for repeats
+    results: ResultCache,
+    /// Counts and timings of the requests answered so far
+    metrics: Metrics,
+    /// Hooks around every request, in registration order
+    middleware: Vec<Arc<dyn Middleware>>,
+    /// Handler for each payload variant
+    handlers: Handlers,
+    /// What the backend's git can do, detected on first request for it
+    capabilities: tokio::sync::OnceCell<rl_git::GitCapabilities>,
+    /// Bytes held in responses not yet delivered
+    memory_budget: MemoryBudget,
+    /// Directories requests may name repositories in
+    sandbox: std::sync::RwLock<Sandbox>,
 }
 
 fn parse_diff_summary(
@@ -55,7 +108,7 @@ fn parse_diff_summary(
         if parts.len() >= 3 {
             let added = parts[0].parse().unwrap_or(0);
             let deleted = parts[1].parse().unwrap_or(0);
-            let path = parts[2..].join(" ");
+            let path = rl_git::path::unquote(&renamed_to(&parts[2..].join(" ")));
             numstat_map.insert(path, (added, deleted));
         }
     }
@@ -65,7 +118,8 @@ fn parse_diff_summary(
             continue;
         }
 
-        let parts: Vec<&str> = line.split('\t').collect();
+        // Paths git had to quote are unquoted; the status code never is
+        let parts: Vec<String> = line.split('\t').map(rl_git::path::unquote).collect();
         if parts.is_empty() {
             continue;
         }
@@ -76,19 +130,21 @@ fn parse_diff_summary(
                 if parts.len() < 2 {
                     continue;
                 }
-                (ChangeType::Added, parts[1].to_string(), None)
+                (ChangeType::Added, parts[1].clone(), None)
             }
-            'M' => {
+            // A type change, such as a file becoming a symlink, is still
+            // the same path changing
+            'M' | 'T' => {
                 if parts.len() < 2 {
                     continue;
                 }
-                (ChangeType::Modified, parts[1].to_string(), None)
+                (ChangeType::Modified, parts[1].clone(), None)
             }
             'D' => {
                 if parts.len() < 2 {
                     continue;
                 }
-                (ChangeType::Deleted, parts[1].to_string(), None)
+                (ChangeType::Deleted, parts[1].clone(), None)
             }
             'R' => {
                 if parts.len() < 3 {
@@ -96,8 +152,8 @@ fn parse_diff_summary(
                 }
                 (
                     ChangeType::Renamed,
-                    parts[2].to_string(),
-                    Some(parts[1].to_string()),
+                    parts[2].clone(),
+                    Some(parts[1].clone()),
                 )
             }
             _ => continue,
@@ -115,8 +171,13 @@ fn parse_diff_summary(
     }
 
     let files_changed = changes.len();
-    let additions = changes.iter().map(|c| c.additions).sum();
-    let deletions = changes.iter().map(|c| c.deletions).sum();
+    // Counts come from git, but need not be sane
+    let additions = changes
+        .iter()
+        .fold(0usize, |sum, c| sum.saturating_add(c.additions));
+    let deletions = changes
+        .iter()
+        .fold(0usize, |sum, c| sum.saturating_add(c.deletions));
 
     Ok(rl_api::response::DiffSummary {
         files_changed,
@@ -126,101 +187,747 @@ fn parse_diff_summary(
     })
 }
 
+/// The new path of a numstat entry, which git gives for a rename as
+/// `old => new`, or as `dir/{old => new}/file` when the paths share parts.
+fn renamed_to(path: &str) -> String {
+    let Some((old, new)) = path.split_once(" => ") else {
+        return path.to_string();
+    };
+    match (old.rsplit_once('{'), new.split_once('}')) {
+        // An empty side leaves a doubled slash, as in `dir/{ => sub}/file`
+        (Some((prefix, _)), Some((new, suffix))) => {
+            format!("{}{}{}", prefix, new, suffix).replace("//", "/")
+        }
+        _ => new.to_string(),
+    }
+}
+
+/// A commit as listed in Log pages and Graph windows, by the first line of
+/// its message.
+fn commit_summary(commit: rl_git::Commit) -> rl_api::response::CommitSummary {
+    rl_api::response::CommitSummary {
+        message: commit
+            .message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+        author_name: commit.author.name,
+        author_email: commit.author.email,
+        time: commit.committer.time,
+        parents: commit.parent_ids,
+        id: commit.id,
+    }
+}
+
+/// The tree of a SHA-1 repository with no files, which the working tree is
+/// diffed against while HEAD has no commit
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Where a diff starts when the request names no revision: HEAD, or the
+/// empty tree before the first commit, so that everything staged or
+/// tracked shows as added.
+async fn diff_base(repo_handle: &dyn rl_git::RepoHandle) -> Result<String, Error> {
+    Ok(match repo_handle.rev_parse("HEAD").await? {
+        Some(_) => "HEAD".to_string(),
+        None => EMPTY_TREE.to_string(),
+    })
+}
+
 #[allow(clippy::new_without_default)]
 impl RepoEngine {
     /// Create a new engine with default configuration.
     pub fn new() -> Self {
-        Self {
-            config: EngineConfig::default(),
-            git_backend: Box::new(CliBackend::new()),
-            index_manager: IndexManager::new(),
-            scheduler: Scheduler::new(),
-        }
+        Self::with_config(EngineConfig::default())
     }
 
     /// Create a new engine with custom configuration.
     pub fn with_config(config: EngineConfig) -> Self {
+        let events = EventBus::new();
+        let index_manager = IndexManager::with_policy(config.cache.clone());
         Self {
-            config,
-            git_backend: Box::new(CliBackend::new()),
-            index_manager: IndexManager::new(),
-            scheduler: Scheduler::new(),
+            queue: QueryQueue::new(
+                config.max_concurrent_queries,
+                config.max_concurrent_queries_per_repo,
+                config
+                    .repos
+                    .iter()
+                    .filter_map(|(path, repo)| Some((path.clone(), repo.max_concurrent_queries?)))
+                    .collect(),
+            ),
+            repos: RepoRegistry::new(
+                config.backend.create(),
+                events.clone(),
+                config.max_open_repos,
+                Duration::from_millis(config.repo_idle_timeout_ms),
+            ),
+            memory_budget: MemoryBudget::new(config.memory_budget_bytes),
+            sandbox: std::sync::RwLock::new(Sandbox::new(&config.allowed_roots)),
+            config: std::sync::Mutex::new(config),
+            index_manager: std::sync::Mutex::new(index_manager),
+            index_events: std::sync::Mutex::new(events.subscribe()),
+            events,
+            locks: RepoLocks::default(),
+            journal: Journal::default(),
+            in_flight: InFlight::default(),
+            idempotency: IdempotencyKeys::default(),
+            prefetcher: Prefetcher::default(),
+            results: ResultCache::default(),
+            metrics: Metrics::default(),
+            middleware: Vec::new(),
+            handlers: Handlers::builtin(),
+            capabilities: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    /// Run `middleware` around every request handled from now on.
+    ///
+    /// Middleware registered earlier wraps middleware registered later; see
+    /// [`Middleware`] for the order hooks run in.
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Budget for responses not yet delivered.
+    ///
+    /// Transports can reserve the buffers they hold from it too, so they
+    /// count towards the same limit.
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+
+    /// Counts and timings of the requests answered so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Serve requests of `handler`'s payload variant with it from now on,
+    /// in place of the engine's own handler.
+    ///
+    /// Requests still queue, take their repository's lock and pass through
+    /// middleware as before; only what runs once they have a turn changes.
+    pub fn register_handler(&mut self, handler: impl Handler + 'static) {
+        self.handlers.insert(handler);
+    }
+
+    /// Receive every repository change event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<rl_api::Event> {
+        self.events.subscribe()
+    }
+
+    /// Announce a repository change, e.g. from a file system watcher.
+    ///
+    /// Caches affected by the change are invalidated before the next request
+    /// is served, and watching clients are notified.
+    pub fn publish(&self, event: rl_api::Event) {
+        self.events.publish(event);
+    }
+
+    /// Load the configuration again from where it was loaded and apply it
+    /// with [`RepoEngine::reconfigure`].
+    ///
+    /// Fails for engines configured in code, and leaves the configuration
+    /// unchanged if the new one cannot be loaded.
+    pub fn reload_config(&self) -> std::io::Result<Vec<&'static str>> {
+        let source = self.config.lock().unwrap().source.clone();
+        let config::ConfigSource::Load(path) = source else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "The configuration was not loaded from a file",
+            ));
+        };
+        let config = EngineConfig::load(path.as_deref())?;
+        Ok(self.reconfigure(config))
+    }
+
+    /// Replace the engine's configuration while it runs.
+    ///
+    /// Caching and its limits, the query and idle timeouts, the memory
+    /// budget, the allowed roots and the log filter take effect at once.
+    /// Returns the settings that changed but only apply after a restart:
+    /// the backend and the concurrency limits.
+    pub fn reconfigure(&self, config: EngineConfig) -> Vec<&'static str> {
+        self.index_manager
+            .lock()
+            .unwrap()
+            .set_policy(config.cache.clone());
+        self.repos.set_limits(
+            config.max_open_repos,
+            Duration::from_millis(config.repo_idle_timeout_ms),
+        );
+        if !config.cache_enabled {
+            self.results.clear();
+        }
+        self.memory_budget.set_limit(config.memory_budget_bytes);
+        *self.sandbox.write().unwrap() = Sandbox::new(&config.allowed_roots);
+        if let Some(filter) = &config.log_filter {
+            if let Err(error) = telemetry::set_log_filter(filter) {
+                tracing::warn!(error = %error, "log filter not applied");
+            }
+        }
+
+        let mut current = self.config.lock().unwrap();
+        let mut restart = Vec::new();
+        if config.backend != current.backend {
+            restart.push("backend");
+        }
+        if config.max_concurrent_queries != current.max_concurrent_queries {
+            restart.push("max_concurrent_queries");
+        }
+        if config.max_concurrent_queries_per_repo != current.max_concurrent_queries_per_repo {
+            restart.push("max_concurrent_queries_per_repo");
+        }
+        if config.repos != current.repos {
+            restart.push("repos");
+        }
+        *current = config;
+        restart
+    }
+
+    /// Apply the events published since the last request to the caches.
+    fn sync_index(&self) {
+        use broadcast::error::TryRecvError;
+        use rl_api::Event;
+
+        let mut events = self.index_events.lock().unwrap();
+        let mut index = self.index_manager.lock().unwrap();
+        loop {
+            match events.try_recv() {
+                Ok(Event::HeadChanged(_) | Event::RefsChanged(_)) => index.invalidate_history(),
+                Ok(Event::IndexChanged(_) | Event::WorkdirChanged(_)) => {
+                    index.invalidate_worktree()
+                }
+                Ok(Event::RepoClosed(_)) | Err(TryRecvError::Lagged(_)) => {
+                    // Missed events could have touched anything
+                    index.invalidate_history();
+                    index.invalidate_worktree();
+                }
+                Ok(Event::RepoOpened(_) | Event::OperationProgress(_)) => {}
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Answer from the result kept under `key` if caching is enabled and
+    /// there is one; otherwise run `query`, keeping what it returns.
+    async fn cached(
+        &self,
+        key: String,
+        query: impl std::future::Future<Output = Result<ResponsePayload, Error>>,
+    ) -> Result<ResponsePayload, Error> {
+        let (enabled, max_bytes) = {
+            let config = self.config.lock().unwrap();
+            (config.cache_enabled, config.cache.max_total_bytes)
+        };
+        if !enabled {
+            return query.await;
         }
+        if let Some(payload) = self.results.get(&key) {
+            telemetry::record_cache_hit();
+            return Ok(payload);
+        }
+        let payload = query.await?;
+        self.results.put(key, &payload, max_bytes);
+        Ok(payload)
     }
 
     /// Handle a request and return a response.
+    ///
+    /// For streaming requests only the final chunk is returned; use
+    /// [`RepoEngine::handle_stream`] to receive every chunk.
     pub async fn handle(&self, request: Request) -> Response {
+        let cancellation = CancellationToken::new();
+        let sink = ChunkSink::discard(request.id.clone(), cancellation.clone());
+        let response = self
+            .run(request, &Session::new(), &sink, cancellation)
+            .await;
+        self.memory_budget.admit(response).0
+    }
+
+    /// Handle a request, sending every response for it to `responses`.
+    ///
+    /// Streaming requests produce ordered chunks ending with one marked
+    /// `is_final`; all other requests produce exactly one response.
+    pub async fn handle_stream(&self, request: Request, responses: mpsc::Sender<Response>) {
+        self.handle_in_session(request, &Session::new(), responses)
+            .await
+    }
+
+    /// Like [`RepoEngine::handle_stream`], but reuses repository handles
+    /// already open in `session` and keeps newly opened ones there.
+    pub async fn handle_in_session(
+        &self,
+        request: Request,
+        session: &Session,
+        responses: mpsc::Sender<Response>,
+    ) {
+        let cancellation = CancellationToken::new();
+        let sink = ChunkSink::new(
+            request.id.clone(),
+            responses.clone(),
+            cancellation.clone(),
+            self.memory_budget.clone(),
+        );
+        let response = self.run(request, session, &sink, cancellation).await;
+        let (response, _reservation) = self.memory_budget.admit(response);
+        let _ = responses.send(response).await;
+    }
+
+    /// Like [`RepoEngine::handle_in_session`], but stops early once
+    /// `cancellation` is cancelled.
+    ///
+    /// Handlers check the token between steps; an operation in progress,
+    /// including any git process it started, is abandoned and killed. The
+    /// request is then answered with `OperationCanceled`.
+    ///
+    /// After a Log or Graph window is served, the next window is fetched in
+    /// the background at prefetch priority, ready for when the client
+    /// scrolls to it.
+    pub async fn handle_cancellable(
+        self: &Arc<Self>,
+        request: Request,
+        session: &Session,
+        responses: mpsc::Sender<Response>,
+        cancellation: CancellationToken,
+    ) {
+        let version = request.version;
+        let payload = request.payload.clone();
+        let sink = ChunkSink::new(
+            request.id.clone(),
+            responses.clone(),
+            cancellation.clone(),
+            self.memory_budget.clone(),
+        );
+        let response = self.run(request, session, &sink, cancellation).await;
+        if let Some(window) = self.prefetcher.next(&payload, &response.result) {
+            self.spawn_prefetch(version, window);
+        }
+        let (response, _reservation) = self.memory_budget.admit(response);
+        let _ = responses.send(response).await;
+    }
+
+    /// Fetch `window` in the background and keep the result for a while.
+    fn spawn_prefetch(self: &Arc<Self>, version: rl_api::ApiVersion, window: prefetch::Window) {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let id = format!("prefetch-{}", telemetry::new_request_id());
+            let request = Request {
+                version,
+                priority: Some(RequestPriority::Prefetch),
+                ..Request::new(id, window.payload.clone())
+            };
+            let cancellation = window.cancellation.clone();
+            let sink = ChunkSink::discard(request.id.clone(), cancellation.clone());
+            let response = engine
+                .run(request, &Session::new(), &sink, cancellation)
+                .await;
+            engine.prefetcher.finish(window, response.result);
+        });
+    }
+
+    /// Serve a request and describe how it went in the response's `meta`,
+    /// including the time spent in each step if the request asked.
+    async fn run(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        let started = Instant::now();
+        let kind = request.payload.kind();
+        let prefetch = request.priority == Some(RequestPriority::Prefetch);
+        let timings = request.timings;
+        let trace = request.trace;
+        let page_size = match &request.payload {
+            rl_api::request::RequestPayload::Log(req) => Some(req.paging.page_size.get()),
+            _ => None,
+        };
+        let (mut response, mut meta) =
+            telemetry::collect_meta(self.intercept(request, session, sink, cancellation)).await;
+
+        meta.elapsed_ms = started.elapsed().as_nanos() as f64 / 1_000_000.0;
+        // Also true of pages served from a prefetch or another request
+        if let (Some(limit), Ok(ResponsePayload::Log(page))) = (page_size, &response.result) {
+            if page.has_more {
+                meta.truncated = Some(rl_api::response::Truncation {
+                    bound: rl_api::response::TruncationBound::PageSize,
+                    limit: u64::from(limit),
+                });
+            }
+        }
+        if !timings {
+            meta.steps.clear();
+        }
+        if !trace {
+            meta.request_id = None;
+        }
+        response.meta = Some(meta);
+        // Prefetches are the engine's own requests, not clients'
+        if !prefetch {
+            self.metrics.record(kind, &response, started.elapsed());
+        }
+        response
+    }
+
+    /// Serve a request, passing it through the registered middleware.
+    async fn intercept(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        if self.middleware.is_empty() {
+            return self.serve(request, session, sink, cancellation).await;
+        }
+
+        let started = Instant::now();
+        let mut entered = 0;
+        let mut rejection = None;
+        for middleware in &self.middleware {
+            if let Err(error) = middleware.before(&request).await {
+                rejection = Some(error);
+                break;
+            }
+            entered += 1;
+        }
+        let response = match rejection {
+            Some(error) => Response {
+                id: request.id.clone(),
+                result: Err(error),
+                meta: None,
+            },
+            None => {
+                self.serve(request.clone(), session, sink, cancellation)
+                    .await
+            }
+        };
+
+        let elapsed = started.elapsed();
+        for middleware in self.middleware[..entered].iter().rev() {
+            middleware.after(&request, &response, elapsed).await;
+        }
+        response
+    }
+
+    /// Wait for the scheduler to give the request a turn, then run it.
+    ///
+    /// At most `max_concurrent_queries` requests run at once, and at most
+    /// `max_concurrent_queries_per_repo` against one repository; the rest
+    /// wait by priority. A queued prefetch superseded by a newer request from the
+    /// same session is answered with `OperationCanceled` without running, as
+    /// is a request cancelled while it waits or runs.
+    ///
+    /// Requests for repositories outside the allowed roots are rejected
+    /// first.
+    ///
+    /// A query identical to one already running waits for that one's result
+    /// instead of running again, and is answered with it under its own id;
+    /// a window already prefetched is answered straight away. So is a
+    /// mutation repeating the idempotency key of one already served.
+    async fn serve(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        let allowed = self
+            .sandbox
+            .read()
+            .unwrap()
+            .check(request.payload.repo_path());
+        if let Err(error) = allowed {
+            return Response {
+                id: request.id,
+                result: Err(error),
+                meta: None,
+            };
+        }
+
+        match request.idempotency_key.clone() {
+            Some(key) if !request.payload.is_read_only() => {
+                self.serve_idempotent(&key, request, session, sink, cancellation)
+                    .await
+            }
+            _ => {
+                self.serve_shared(request, session, sink, cancellation)
+                    .await
+            }
+        }
+    }
+
+    /// Serve a mutation carrying an idempotency key, answering retries with
+    /// the result of the first request to use the key.
+    async fn serve_idempotent(
+        &self,
+        key: &str,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        loop {
+            let pending = match self.idempotency.claim(key, &request.payload) {
+                Ok(idempotency::Claim::Run(pending)) => pending,
+                Ok(idempotency::Claim::Replay(result)) => {
+                    let replayed = tokio::select! {
+                        biased;
+                        _ = cancellation.cancelled() => Some(Err(canceled_error())),
+                        replayed = idempotency::replay(result) => {
+                            if replayed.is_some() {
+                                telemetry::record_cache_hit();
+                            }
+                            replayed
+                        }
+                    };
+                    match replayed {
+                        Some(result) => {
+                            return Response {
+                                id: request.id,
+                                result,
+                                meta: None,
+                            }
+                        }
+                        // The first request ended inconclusively; run this one
+                        None => continue,
+                    }
+                }
+                Err(error) => {
+                    return Response {
+                        id: request.id,
+                        result: Err(error),
+                        meta: None,
+                    }
+                }
+            };
+
+            let response = self
+                .serve_shared(request, session, sink, cancellation)
+                .await;
+            pending.finish(&response.result);
+            return response;
+        }
+    }
+
+    /// Serve a request from a prefetched window or an identical running
+    /// query if there is one, else run it.
+    async fn serve_shared(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        self.sync_index();
+
+        let immediate = Priority::of(&request) == Priority::UiImmediate;
+        if let Some(payload) = self.prefetcher.take(&request.payload, immediate) {
+            telemetry::record_cache_hit();
+            return Response {
+                id: request.id,
+                result: Ok(payload),
+                meta: None,
+            };
+        }
+
+        loop {
+            let lead = match self.in_flight.claim(&request.payload) {
+                Some(Claim::Follow(result)) => {
+                    let waiting = Instant::now();
+                    let shared = tokio::select! {
+                        biased;
+                        _ = cancellation.cancelled() => Some(Err(canceled_error())),
+                        shared = coalesce::follow(result) => shared,
+                    };
+                    telemetry::record_step("coalesced", waiting.elapsed());
+                    match shared {
+                        Some(result) => {
+                            return Response {
+                                id: request.id,
+                                result,
+                                meta: None,
+                            }
+                        }
+                        // The leader was cancelled; try again, likely leading
+                        None => continue,
+                    }
+                }
+                Some(Claim::Lead(lead)) => Some(lead),
+                None => None,
+            };
+
+            let response = self.execute(request, session, sink, cancellation).await;
+            if let Some(lead) = lead {
+                lead.finish(&response.result);
+            }
+            return response;
+        }
+    }
+
+    /// Run a request once its repository is free for it and the scheduler
+    /// gives it a turn.
+    ///
+    /// While the memory budget is spent on responses not yet delivered,
+    /// the request is turned away with `Overloaded` instead.
+    async fn execute(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: CancellationToken,
+    ) -> Response {
+        let id = request.id.clone();
+        let failed = |error| Response {
+            id: id.clone(),
+            result: Err(error),
+            meta: None,
+        };
+
+        if let Err(error) = self.memory_budget.check() {
+            return failed(error);
+        }
+
+        // Taken before the turn, so requests waiting on a repository do not
+        // hold permits other repositories could use
+        let waiting = Instant::now();
+        let guard = tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => return failed(canceled_error()),
+            guard = self.locks.lock(&request.payload) => guard,
+        };
+        telemetry::record_step("repo_lock", waiting.elapsed());
+        let guard = match guard {
+            Ok(guard) => guard,
+            Err(error) => return failed(error),
+        };
+
+        let waiting = Instant::now();
+        let turn = tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => return failed(canceled_error()),
+            turn = self.queue.wait_turn(&request, session.id(), &cancellation) => turn,
+        };
+        telemetry::record_step("queue", waiting.elapsed());
+        let Some(turn) = turn else {
+            return failed(Error::new(
+                rl_api::ErrorCode::OperationCanceled,
+                "Superseded by a newer request for the same data",
+            ));
+        };
+
+        // Read for each request, so a reload applies to the next one
+        let timeout = match self.config.lock().unwrap().query_timeout_ms {
+            0 => None,
+            _ if !request.payload.is_read_only() => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        let timed_out = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        // Dropping the handler mid-step abandons its git operation
+        let response = tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => failed(canceled_error()),
+            _ = timed_out => failed(Error::new(
+                rl_api::ErrorCode::Timeout,
+                format!(
+                    "Query did not finish within {} ms",
+                    timeout.unwrap_or_default().as_millis()
+                ),
+            )),
+            response = self.dispatch_journaled(request, session, sink, &cancellation) => response,
+        };
+        drop(turn);
+        drop(guard);
+        response
+    }
+
+    /// Dispatch a request, recording what a successful mutation changed in
+    /// the journal.
+    async fn dispatch_journaled(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
+    ) -> Response {
+        if !journal::journaled(&request.payload) {
+            return self.dispatch(request, session, sink, cancellation).await;
+        }
+
+        let operation = request.payload.kind();
+        let repo_path = std::path::PathBuf::from(request.payload.repo_path());
+        // A repository that cannot be read is left for the handler to report
+        let before = match session.open(&self.repos, &repo_path).await {
+            Ok(repo) => RefState::capture(&*repo).await.ok(),
+            Err(_) => None,
+        };
+        let response = self.dispatch(request, session, sink, cancellation).await;
+
+        if let (Some(before), Ok(_)) = (before, &response.result) {
+            if let Ok(repo) = session.open(&self.repos, &repo_path).await {
+                if let Ok(after) = RefState::capture(&*repo).await {
+                    self.journal.record(&repo_path, operation, before, after);
+                }
+            }
+        }
+        response
+    }
+
+    /// Route a request to the handler registered for its variant; streaming
+    /// handlers push intermediate chunks into `sink` and return the final
+    /// one.
+    async fn dispatch(
+        &self,
+        request: Request,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
+    ) -> Response {
         let request_id = telemetry::new_request_id();
         let request_type = format!("{:?}", request.payload);
 
         // Extract repo path from request
         let repo_path = extract_repo_path(&request.payload);
 
-        let span = telemetry::RequestSpan::new(&request_id, &repo_path, &request_type);
+        let span = telemetry::RequestSpan::new(&request_id, &request.id, &repo_path, &request_type);
+        telemetry::record_request_id(&request_id);
 
         let result = async {
             tracing::info!("handling request");
 
-            let result = match request.payload {
-                rl_api::request::RequestPayload::Status(req) => {
-                    step!("status", { self.handle_status(req).await })
-                }
-                rl_api::request::RequestPayload::Log(req) => {
-                    step!("log", { self.handle_log(req).await })
-                }
-                rl_api::request::RequestPayload::Graph(req) => {
-                    step!("graph", { self.handle_graph(req).await })
-                }
-                rl_api::request::RequestPayload::ShowCommit(req) => {
-                    step!("show_commit", { self.handle_show_commit(req).await })
-                }
-                rl_api::request::RequestPayload::DiffSummary(req) => {
-                    step!("diff_summary", { self.handle_diff_summary(req).await })
-                }
-                rl_api::request::RequestPayload::DiffContent(req) => {
-                    step!("diff_content", { self.handle_diff_content(req).await })
-                }
-                rl_api::request::RequestPayload::Blame(req) => {
-                    step!("blame", { self.handle_blame(req).await })
-                }
-                rl_api::request::RequestPayload::Branches(req) => {
-                    step!("branches", { self.handle_branches(req).await })
-                }
-                rl_api::request::RequestPayload::Tags(req) => {
-                    step!("tags", { self.handle_tags(req).await })
-                }
-                rl_api::request::RequestPayload::Remotes(req) => {
-                    step!("remotes", { self.handle_remotes(req).await })
-                }
-                rl_api::request::RequestPayload::Checkout(req) => {
-                    step!("checkout", { self.handle_checkout(req).await })
-                }
-                rl_api::request::RequestPayload::Commit(req) => {
-                    step!("commit", { self.handle_commit(req).await })
-                }
-                rl_api::request::RequestPayload::Fetch(req) => {
-                    step!("fetch", { self.handle_fetch(req).await })
-                }
-                rl_api::request::RequestPayload::Push(req) => {
-                    step!("push", { self.handle_push(req).await })
-                }
-                rl_api::request::RequestPayload::Merge(req) => {
-                    step!("merge", { self.handle_merge(req).await })
-                }
-                rl_api::request::RequestPayload::Rebase(req) => {
-                    step!("rebase", { self.handle_rebase(req).await })
-                }
-                rl_api::request::RequestPayload::Stash(req) => {
-                    step!("stash", { self.handle_stash(req).await })
-                }
-                rl_api::request::RequestPayload::Watch(req) => {
-                    step!("watch", { self.handle_watch(req).await })
-                }
+            let kind = request.payload.kind();
+            let cx = HandlerContext {
+                engine: self,
+                session,
+                sink,
+                cancellation,
             };
+            // Named at run time, so timed here rather than with `step!`
+            let started = Instant::now();
+            let result = self
+                .handlers
+                .handle(request.payload, &cx)
+                .instrument(tracing::info_span!("handler", kind))
+                .await;
+            telemetry::record_step(kind, started.elapsed());
 
             match &result {
-                Ok(_) => tracing::info!("request completed successfully"),
-                Err(e) => tracing::error!(error = %e, "request failed"),
+                Ok(_) => tracing::info!(
+                    steps = %telemetry::step_summary(),
+                    "request completed successfully"
+                ),
+                Err(e) => tracing::error!(
+                    error = %e,
+                    steps = %telemetry::step_summary(),
+                    "request failed"
+                ),
             }
 
             result
@@ -231,6 +938,7 @@ impl RepoEngine {
         Response {
             id: request.id,
             result,
+            meta: None,
         }
     }
 
@@ -239,6 +947,8 @@ impl RepoEngine {
     async fn handle_status(
         &self,
         req: rl_api::request::StatusRequest,
+        session: &Session,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
         use std::path::Path;
 
@@ -246,23 +956,24 @@ impl RepoEngine {
 
         // Step 1: Open the repository
         let repo_handle = step!("git_open_repo", {
-            self.git_backend.open_repo(repo_path).await
+            session.open(&self.repos, repo_path).await
         })?;
+        cancellation.check()?;
 
         // Step 2: Get repository snapshot (HEAD, branch)
         let snapshot = step!("git_snapshot", { repo_handle.snapshot().await })?;
+        cancellation.check()?;
 
         // Step 3: Get working directory status (runs git status --porcelain=v1)
         let workdir_status = step!("git_status_porcelain", {
             repo_handle.workdir().status().await
         })?;
+        cancellation.check()?;
 
         // Step 4: Build response
         let response = step!("build_response", {
-            // Determine which files are staged by looking at the index status
-            // For now, we'll derive this from the workdir status
             // Files with index changes (XY where X != ' ') are staged
-            let staged = workdir_status.added.clone();
+            let staged = workdir_status.staged.clone();
 
             Ok(ResponsePayload::Status(rl_api::response::StatusView {
                 branch: snapshot.branch,
@@ -281,24 +992,219 @@ impl RepoEngine {
         Ok(response)
     }
 
+    /// Serve one page of history. See [`cursor`] for how pages stay stable
+    /// while new commits arrive.
     async fn handle_log(
         &self,
-        _req: rl_api::request::LogRequest,
+        req: rl_api::request::LogRequest,
+        session: &Session,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Log not implemented",
-        ))
+        use cursor::LogCursor;
+        use rl_api::response::{CommitListPage, CommitSummary};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        cancellation.check()?;
+
+        let position = match LogCursor::decode(&req.paging.cursor)? {
+            Some(position) => {
+                step!("check_cursor", { position.check(&*repo_handle).await })?;
+                position
+            }
+            None => {
+                let range = step!("pin_range", {
+                    cursor::pin_range(&*repo_handle, req.revision_range.as_deref()).await
+                })?;
+                match range {
+                    Some(range) => LogCursor::start(range),
+                    // Nothing has been committed yet
+                    None => {
+                        return Ok(ResponsePayload::Log(CommitListPage {
+                            commits: Vec::new(),
+                            next_cursor: None,
+                            has_more: false,
+                        }))
+                    }
+                }
+            }
+        };
+        cancellation.check()?;
+
+        // The range is pinned to commit ids, so the page never changes
+        let page_size = req.paging.page_size.get() as usize;
+        let key = format!(
+            "log\0{}\0{}\0{}\0{}\0{}",
+            req.repo_path, position.range, position.skip, position.last_seen, page_size
+        );
+        self.cached(key, async {
+            // Read the previous page's last commit again to check the cursor
+            // against, and one commit past the page to know whether more follow
+            let overlap = usize::from(position.skip > 0);
+            let commits = step!("git_log", {
+                repo_handle
+                    .log(
+                        &position.range,
+                        position.skip - overlap,
+                        page_size + overlap + 1,
+                    )
+                    .await
+            })?;
+            let mut commits = commits.into_iter();
+            if overlap > 0 && commits.next().map(|commit| commit.id) != Some(position.last_seen) {
+                return Err(Error::new(
+                    rl_api::ErrorCode::InvalidCursor,
+                    "Cursor does not match the repository's history",
+                )
+                .with_remediation("Request the first page again without a cursor"));
+            }
+
+            let mut page: Vec<CommitSummary> = commits.map(commit_summary).collect();
+            let has_more = page.len() > page_size;
+            page.truncate(page_size);
+            let next_cursor = match page.last() {
+                Some(last) if has_more => Some(
+                    LogCursor {
+                        range: position.range,
+                        skip: position.skip + page.len(),
+                        last_seen: last.id.clone(),
+                    }
+                    .encode(),
+                ),
+                _ => None,
+            };
+
+            Ok(ResponsePayload::Log(CommitListPage {
+                commits: page,
+                next_cursor,
+                has_more,
+            }))
+        })
+        .await
     }
 
+    /// Serve one window of the commit graph. Windows page like Log pages,
+    /// with lanes laid out as in [`graph`].
     async fn handle_graph(
         &self,
-        _req: rl_api::request::GraphRequest,
+        req: rl_api::request::GraphRequest,
+        session: &Session,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Graph not implemented",
-        ))
+        use cursor::LogCursor;
+        use rl_api::response::{CommitGraphNode, CommitGraphWindow};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        cancellation.check()?;
+
+        let position = match LogCursor::decode(&req.cursor)? {
+            Some(position) => {
+                step!("check_cursor", { position.check(&*repo_handle).await })?;
+                position
+            }
+            None => {
+                let range = step!("pin_range", {
+                    cursor::pin_range(&*repo_handle, req.revision_range.as_deref()).await
+                })?;
+                match range {
+                    Some(range) => LogCursor::start(range),
+                    // Nothing has been committed yet
+                    None => {
+                        return Ok(ResponsePayload::Graph(CommitGraphWindow {
+                            commits: Vec::new(),
+                            next_cursor: None,
+                            has_more: false,
+                        }))
+                    }
+                }
+            }
+        };
+        cancellation.check()?;
+
+        // Lanes and commits depend only on the pinned range, so they are
+        // kept; refs move, so they are read for every window
+        let window_size = req.window_size.get() as usize;
+        let key = format!(
+            "graph\0{}\0{}\0{}\0{}\0{}",
+            req.repo_path, position.range, position.skip, position.last_seen, window_size
+        );
+        let window = self
+            .cached(key, async {
+                // A row's lanes depend on every commit above it, so the graph is
+                // laid out from the top of the range, reading one commit past the
+                // window to know whether more follow
+                let end = position.skip + window_size;
+                let commits = step!("git_log", {
+                    repo_handle.log_topo(&position.range, 0, end + 1).await
+                })?;
+                if position.skip > 0
+                    && commits
+                        .get(position.skip - 1)
+                        .map(|commit| commit.id.as_str())
+                        != Some(position.last_seen.as_str())
+                {
+                    return Err(Error::new(
+                        rl_api::ErrorCode::InvalidCursor,
+                        "Cursor does not match the repository's history",
+                    )
+                    .with_remediation("Request the first page again without a cursor"));
+                }
+
+                let has_more = commits.len() > end;
+                let mut lanes = graph::Lanes::default();
+                let window: Vec<CommitGraphNode> = commits
+                    .into_iter()
+                    .take(end)
+                    .map(|commit| CommitGraphNode {
+                        lanes: lanes.place(&commit.id, &commit.parent_ids),
+                        commit: commit_summary(commit),
+                        refs: Vec::new(),
+                    })
+                    .skip(position.skip)
+                    .collect();
+                let next_cursor = match window.last() {
+                    Some(last) if has_more => Some(
+                        LogCursor {
+                            range: position.range,
+                            skip: end,
+                            last_seen: last.commit.id.clone(),
+                        }
+                        .encode(),
+                    ),
+                    _ => None,
+                };
+
+                Ok(ResponsePayload::Graph(CommitGraphWindow {
+                    commits: window,
+                    next_cursor,
+                    has_more,
+                }))
+            })
+            .await?;
+        cancellation.check()?;
+
+        let ResponsePayload::Graph(mut window) = window else {
+            return Ok(window);
+        };
+        let refs = step!("git_for_each_ref", {
+            repo_handle.refs_store().all_refs().await
+        })?;
+        let mut decorations: HashMap<String, Vec<String>> = HashMap::new();
+        for reference in refs.into_iter().filter(|reference| !reference.is_symbolic) {
+            let target = reference.peeled.unwrap_or(reference.target);
+            decorations.entry(target).or_default().push(reference.name);
+        }
+        for node in &mut window.commits {
+            node.refs = decorations.remove(&node.commit.id).unwrap_or_default();
+        }
+        Ok(ResponsePayload::Graph(window))
     }
 
     async fn handle_show_commit(
@@ -314,95 +1220,313 @@ impl RepoEngine {
     async fn handle_diff_summary(
         &self,
         req: rl_api::request::DiffSummaryRequest,
+        session: &Session,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
         use std::path::Path;
 
         let repo_path = Path::new(&req.repo_path);
 
         let repo_handle = step!("git_open_repo", {
-            self.git_backend.open_repo(repo_path).await
+            session.open(&self.repos, repo_path).await
         })?;
+        cancellation.check()?;
 
-        let from = req.from.as_deref().unwrap_or("HEAD");
+        let from = match req.from {
+            Some(from) => from,
+            None => diff_base(repo_handle.as_ref()).await?,
+        };
         let to = req.to.as_deref().unwrap_or("");
-        let range = if to.is_empty() {
-            from.to_string()
-        } else {
-            format!("{}..{}", from, to)
+        let repo_handle = &repo_handle;
+        let summarize = |range: String| async move {
+            let name_status_output = step!("git_diff_name_status", {
+                repo_handle.diff_name_status(&range).await
+            })?;
+            cancellation.check()?;
+
+            let numstat_output = step!("git_diff_numstat", {
+                repo_handle.diff_numstat(&range).await
+            })?;
+            cancellation.check()?;
+
+            let response = step!("parse_diff", {
+                parse_diff_summary(&name_status_output, &numstat_output)
+            })?;
+            Ok(ResponsePayload::DiffSummary(response))
         };
+        if to.is_empty() {
+            // Against the working directory, which may change at any time
+            return summarize(from).await;
+        }
 
-        let name_status_output = step!("git_diff_name_status", {
-            repo_handle.diff_name_status(&range).await
-        })?;
-
-        let numstat_output = step!("git_diff_numstat", {
-            repo_handle.diff_numstat(&range).await
-        })?;
-
-        let response = step!("parse_diff", {
-            parse_diff_summary(&name_status_output, &numstat_output)
+        // Between two commits the summary never changes
+        let pinned = step!("pin_range", {
+            Ok::<_, Error>((
+                repo_handle.rev_parse(&from).await?,
+                repo_handle.rev_parse(to).await?,
+            ))
         })?;
-
-        Ok(ResponsePayload::DiffSummary(response))
+        match pinned {
+            (Some(from), Some(to)) => {
+                let key = format!("diff_summary\0{}\0{}\0{}", req.repo_path, from, to);
+                self.cached(key, summarize(format!("{}..{}", from, to)))
+                    .await
+            }
+            // Left for git to report
+            _ => summarize(format!("{}..{}", from, to)).await,
+        }
     }
 
     async fn handle_diff_content(
         &self,
-        _req: rl_api::request::DiffContentRequest,
+        req: rl_api::request::DiffContentRequest,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Diff content not implemented",
-        ))
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+
+        let repo_handle = step!("git_open_repo", {
+            session.open(&self.repos, repo_path).await
+        })?;
+        cancellation.check()?;
+
+        let from = match req.from {
+            Some(from) => from,
+            None => diff_base(repo_handle.as_ref()).await?,
+        };
+        let to = req.to.as_deref().unwrap_or("");
+        let range = if to.is_empty() {
+            from.to_string()
+        } else {
+            format!("{}..{}", from, to)
+        };
+
+        let patch = step!("git_diff_patch", {
+            repo_handle.diff_patch(&range, req.path.as_deref()).await
+        })?;
+        cancellation.check()?;
+
+        let files = stream::parse_diff_patch(&patch, req.max_bytes.get());
+        if patch.len() as u64 > req.max_bytes.get() {
+            telemetry::record_truncation(
+                rl_api::response::TruncationBound::MaxBytes,
+                req.max_bytes.get(),
+            );
+        }
+
+        // One chunk per file
+        let empty = rl_api::response::DiffChunk {
+            path: req.path.unwrap_or_default(),
+            hunks: Vec::new(),
+        };
+        step!("stream_chunks", {
+            sink.stream_all(files, empty, ResponsePayload::DiffContent)
+                .await
+        })
     }
 
     async fn handle_blame(
         &self,
-        _req: rl_api::request::BlameRequest,
+        req: rl_api::request::BlameRequest,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Blame not implemented",
-        ))
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+        if let Some(lines) = req.lines {
+            if lines.start == 0 || lines.end < lines.start {
+                return Err(Error::new(
+                    rl_api::ErrorCode::InvalidRequest,
+                    format!(
+                        "Invalid line range {},{}: lines are numbered from 1 and the range \
+                         must not end before it starts",
+                        lines.start, lines.end
+                    ),
+                ));
+            }
+        }
+
+        let repo_handle = step!("git_open_repo", {
+            session.open(&self.repos, repo_path).await
+        })?;
+        cancellation.check()?;
+
+        let output = step!("git_blame_porcelain", {
+            repo_handle
+                .blame_porcelain(
+                    &req.path,
+                    req.revision.as_deref(),
+                    req.lines.map(|lines| (lines.start, lines.end)),
+                )
+                .await
+        })?;
+        cancellation.check()?;
+
+        let lines = stream::parse_blame_porcelain(&output);
+
+        let mut chunks = Vec::new();
+        let mut lines = lines.into_iter().peekable();
+        while lines.peek().is_some() {
+            chunks.push(rl_api::response::BlameChunk {
+                path: req.path.clone(),
+                lines: lines.by_ref().take(stream::BLAME_CHUNK_LINES).collect(),
+            });
+        }
+
+        let empty = rl_api::response::BlameChunk {
+            path: req.path.clone(),
+            lines: Vec::new(),
+        };
+        step!("stream_chunks", {
+            sink.stream_all(chunks, empty, ResponsePayload::Blame).await
+        })
     }
 
     async fn handle_branches(
         &self,
-        _req: rl_api::request::BranchesRequest,
+        req: rl_api::request::BranchesRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Branches not implemented",
-        ))
-    }
+        use rl_api::response::{BranchInfo, BranchList};
 
-    async fn handle_tags(
-        &self,
-        _req: rl_api::request::TagsRequest,
-    ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Tags not implemented",
-        ))
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        let snapshot = step!("git_snapshot", { repo_handle.snapshot().await })?;
+        let refs = step!("git_for_each_ref", {
+            repo_handle.refs_store().all_refs().await
+        })?;
+
+        let mut branches = BranchList {
+            local: Vec::new(),
+            remote: Vec::new(),
+            current: snapshot.branch,
+        };
+        // Symbolic refs such as `origin/HEAD` only point at another branch
+        for reference in refs.into_iter().filter(|reference| !reference.is_symbolic) {
+            if let Some(name) = reference.name.strip_prefix("refs/heads/") {
+                branches.local.push(BranchInfo {
+                    name: name.to_string(),
+                    commit_id: reference.target,
+                    is_remote: false,
+                });
+            } else if let Some(name) = reference.name.strip_prefix("refs/remotes/") {
+                branches.remote.push(BranchInfo {
+                    name: name.to_string(),
+                    commit_id: reference.target,
+                    is_remote: true,
+                });
+            }
+        }
+        Ok(ResponsePayload::Branches(branches))
+    }
+
+    async fn handle_tags(
+        &self,
+        req: rl_api::request::TagsRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        use rl_api::response::{TagInfo, TagList};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        let tags = step!("git_tag_list", { repo_handle.tag_list().await })?;
+        Ok(ResponsePayload::Tags(TagList {
+            tags: tags
+                .into_iter()
+                .map(|tag| TagInfo {
+                    name: tag.name,
+                    commit_id: tag.id,
+                    message: tag.message,
+                })
+                .collect(),
+        }))
     }
 
     async fn handle_remotes(
         &self,
-        _req: rl_api::request::RemotesRequest,
+        req: rl_api::request::RemotesRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Remotes not implemented",
-        ))
+        use rl_api::response::{RemoteInfo, RemoteList};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        let remotes = step!("git_remote_list", { repo_handle.remote_list().await })?;
+        Ok(ResponsePayload::Remotes(RemoteList {
+            remotes: remotes
+                .into_iter()
+                .map(|remote| RemoteInfo {
+                    name: remote.name,
+                    url: remote.url,
+                    fetch_refspecs: remote.fetch_refspecs,
+                    push_refspecs: remote.push_refspecs,
+                })
+                .collect(),
+        }))
     }
 
+    /// Check out a branch, or detach HEAD at any other revision; with
+    /// `create_branch`, make a new branch at HEAD and switch to it.
     async fn handle_checkout(
         &self,
-        _req: rl_api::request::CheckoutRequest,
+        req: rl_api::request::CheckoutRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Checkout not implemented",
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::checkout(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+
+        let old_head = repo_handle.rev_parse("HEAD").await?;
+        let branch = format!("refs/heads/{}", req.target);
+        if req.create_branch {
+            let head = old_head.as_deref().ok_or_else(|| {
+                Error::new(
+                    rl_api::ErrorCode::InvalidRequest,
+                    "HEAD has no commit to start the branch at",
+                )
+            })?;
+            step!("git_update_ref", {
+                repo_handle.update_ref(&branch, Some(head), None).await
+            })?;
+            self.events.publish(rl_api::Event::RefsChanged(
+                rl_api::event::RefsChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    changed_refs: vec![branch.clone()],
+                },
+            ));
+        }
+        let detach = repo_handle.rev_parse(&branch).await?.is_none();
+        step!("git_checkout", {
+            repo_handle.checkout(&req.target, detach).await
+        })?;
+
+        self.publish_head_change(&req.repo_path, old_head, &*repo_handle)
+            .await?;
+        Ok(ResponsePayload::OperationResult(
+            rl_api::response::OperationResult {
+                success: true,
+                message: Some(format!("Checked out {}", req.target)),
+            },
         ))
     }
 
@@ -418,110 +1542,739 @@ impl RepoEngine {
 
     async fn handle_fetch(
         &self,
-        _req: rl_api::request::FetchRequest,
+        req: rl_api::request::FetchRequest,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Fetch not implemented",
-        ))
+        use rl_api::paging::StreamingChunk;
+        use rl_api::response::ProgressUpdate;
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+
+        let repo_handle = step!("git_open_repo", {
+            session.open(&self.repos, repo_path).await
+        })?;
+        cancellation.check()?;
+
+        let remote = req.remote.as_deref().unwrap_or("origin");
+        let refspecs = req.refspecs.unwrap_or_default();
+
+        let (line_tx, line_rx) = mpsc::unbounded_channel();
+        let ((), sequence) = step!("git_fetch", {
+            sink.forward_progress(repo_handle.fetch(remote, &refspecs, line_tx), line_rx)
+                .await
+        })?;
+
+        self.events.publish(rl_api::Event::RefsChanged(
+            rl_api::event::RefsChangedEvent {
+                repo_path: req.repo_path.clone(),
+                changed_refs: refspecs.clone(),
+            },
+        ));
+
+        Ok(ResponsePayload::Progress(StreamingChunk {
+            sequence,
+            is_final: true,
+            data: ProgressUpdate {
+                stage: "done".to_string(),
+                progress: 100,
+                message: Some(format!("Fetched from {}", remote)),
+            },
+        }))
     }
 
     async fn handle_push(
         &self,
-        _req: rl_api::request::PushRequest,
+        req: rl_api::request::PushRequest,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Push not implemented",
-        ))
+        use rl_api::paging::StreamingChunk;
+        use rl_api::response::ProgressUpdate;
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::push(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+        cancellation.check()?;
+
+        let remote = req.remote.as_deref().unwrap_or("origin");
+        let refspecs = req.refspecs.unwrap_or_default();
+        let (line_tx, line_rx) = mpsc::unbounded_channel();
+        let ((), sequence) = step!("git_push", {
+            sink.forward_progress(
+                repo_handle.push(remote, &refspecs, req.force, line_tx),
+                line_rx,
+            )
+            .await
+        })?;
+
+        // Remote-tracking references moved with the push
+        self.events.publish(rl_api::Event::RefsChanged(
+            rl_api::event::RefsChangedEvent {
+                repo_path: req.repo_path.clone(),
+                changed_refs: refspecs,
+            },
+        ));
+
+        Ok(ResponsePayload::Progress(StreamingChunk {
+            sequence,
+            is_final: true,
+            data: ProgressUpdate {
+                stage: "done".to_string(),
+                progress: 100,
+                message: Some(format!("Pushed to {}", remote)),
+            },
+        }))
     }
 
+    /// Merge a branch or commit into the current branch. A merge that hits
+    /// conflicts is aborted and reported as unsuccessful, listing them.
     async fn handle_merge(
         &self,
-        _req: rl_api::request::MergeRequest,
+        req: rl_api::request::MergeRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Merge not implemented",
-        ))
+        use rl_api::response::{MergeResult, MergeType};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::merge(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+
+        let unknown = |revision: &str| {
+            Error::new(
+                rl_api::ErrorCode::InvalidRequest,
+                format!("Unknown revision: {}", revision),
+            )
+        };
+        let head = repo_handle
+            .rev_parse("HEAD")
+            .await?
+            .ok_or_else(|| unknown("HEAD"))?;
+        let source = repo_handle
+            .rev_parse(&req.source)
+            .await?
+            .ok_or_else(|| unknown(&req.source))?;
+        let merge_type = if repo_handle
+            .rev_list(&format!("{}..{}", head, source))
+            .await?
+            .is_empty()
+        {
+            MergeType::UpToDate
+        } else if repo_handle
+            .rev_list(&format!("{}..{}", source, head))
+            .await?
+            .is_empty()
+        {
+            MergeType::FastForward
+        } else {
+            MergeType::MergeCommit
+        };
+        if let MergeType::UpToDate = merge_type {
+            return Ok(ResponsePayload::MergeResult(MergeResult {
+                success: true,
+                merge_type,
+                conflicts: Vec::new(),
+            }));
+        }
+
+        let conflicts = step!("git_merge", {
+            repo_handle.merge(&req.source, req.message.as_deref()).await
+        })?;
+        if conflicts.is_empty() {
+            self.publish_head_change(&req.repo_path, Some(head), &*repo_handle)
+                .await?;
+        }
+        Ok(ResponsePayload::MergeResult(MergeResult {
+            success: conflicts.is_empty(),
+            merge_type,
+            conflicts,
+        }))
     }
 
+    /// Rebase, streaming progress chunks while commits are replayed and
+    /// ending with the result. A rebase that hits conflicts is aborted and
+    /// reported as unsuccessful, listing them.
     async fn handle_rebase(
         &self,
-        _req: rl_api::request::RebaseRequest,
+        req: rl_api::request::RebaseRequest,
+        session: &Session,
+        sink: &ChunkSink,
+        cancellation: &CancellationToken,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Rebase not implemented",
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::rebase(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+        cancellation.check()?;
+
+        let upstream = req.upstream.as_deref().unwrap_or(&req.onto);
+        let replayed = step!("git_rev_list", {
+            repo_handle.rev_list(&format!("{}..HEAD", upstream)).await
+        })?;
+        let old_head = repo_handle.rev_parse("HEAD").await?;
+        cancellation.check()?;
+
+        let (line_tx, line_rx) = mpsc::unbounded_channel();
+        let (conflicts, _) = step!("git_rebase", {
+            sink.forward_progress(
+                repo_handle.rebase(&req.onto, req.upstream.as_deref(), line_tx),
+                line_rx,
+            )
+            .await
+        })?;
+
+        let new_head = repo_handle.rev_parse("HEAD").await?;
+        // A branch already based on `onto` is left as it is
+        let moved = conflicts.is_empty() && new_head != old_head;
+        if moved {
+            self.events.publish(rl_api::Event::HeadChanged(
+                rl_api::event::HeadChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    new_head,
+                    old_head,
+                },
+            ));
+        }
+        Ok(ResponsePayload::RebaseResult(
+            rl_api::response::RebaseResult {
+                success: conflicts.is_empty(),
+                commits_rebased: if moved { replayed.len() } else { 0 },
+                conflicts,
+            },
         ))
     }
 
-    async fn handle_stash(
+    /// Move the current branch to another commit, resetting the index and
+    /// working tree as the mode asks.
+    async fn handle_reset(
         &self,
-        _req: rl_api::request::StashRequest,
+        req: rl_api::request::ResetRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Stash not implemented",
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::reset(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+
+        let old_head = repo_handle.rev_parse("HEAD").await?;
+        step!("git_reset", {
+            repo_handle.reset(&req.target, req.mode).await
+        })?;
+
+        self.publish_head_change(&req.repo_path, old_head, &*repo_handle)
+            .await?;
+        if req.mode != rl_api::request::ResetMode::Soft {
+            self.events.publish(rl_api::Event::IndexChanged(
+                rl_api::event::IndexChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    changed_files: Vec::new(),
+                },
+            ));
+        }
+        Ok(ResponsePayload::OperationResult(
+            rl_api::response::OperationResult {
+                success: true,
+                message: Some(format!("Reset to {}", req.target)),
+            },
         ))
     }
 
+    /// Remove untracked files from the working tree.
+    async fn handle_clean(
+        &self,
+        req: rl_api::request::CleanRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        if req.dry_run {
+            let report = step!("dry_run", { dry_run::clean(&*repo_handle, &req).await })?;
+            return Ok(ResponsePayload::DryRun(report));
+        }
+
+        let removed = step!("git_clean", {
+            repo_handle.clean(req.directories, req.ignored, false).await
+        })?;
+        if !removed.is_empty() {
+            self.events.publish(rl_api::Event::WorkdirChanged(
+                rl_api::event::WorkdirChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    changed_files: removed.clone(),
+                },
+            ));
+        }
+        Ok(ResponsePayload::Clean(rl_api::response::CleanResult {
+            removed,
+        }))
+    }
+
+    /// Publish `HeadChanged` if HEAD no longer points at `old_head`.
+    async fn publish_head_change(
+        &self,
+        repo_path: &str,
+        old_head: Option<String>,
+        repo: &dyn rl_git::RepoHandle,
+    ) -> Result<(), Error> {
+        let new_head = repo.rev_parse("HEAD").await?;
+        if new_head != old_head {
+            self.events.publish(rl_api::Event::HeadChanged(
+                rl_api::event::HeadChangedEvent {
+                    repo_path: repo_path.to_string(),
+                    new_head,
+                    old_head,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Push, pop, apply, drop, list or show stash entries. Applying an
+    /// entry that conflicts with the working tree fails with `conflict`,
+    /// leaving the conflicts to resolve and the entry in the stash.
+    async fn handle_stash(
+        &self,
+        req: rl_api::request::StashRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        use rl_api::request::StashAction;
+        use rl_api::response::{StashEntry, StashResult};
+
+        let repo_handle = step!("git_open_repo", {
+            session
+                .open(&self.repos, std::path::Path::new(&req.repo_path))
+                .await
+        })?;
+        let entry = |entry: rl_git::StashEntry| StashEntry {
+            index: entry.index,
+            commit: entry.id,
+            message: entry.message,
+            timestamp: entry.time,
+        };
+        let stash_changed = || {
+            self.events.publish(rl_api::Event::RefsChanged(
+                rl_api::event::RefsChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    changed_refs: vec!["refs/stash".to_string()],
+                },
+            ));
+        };
+
+        // The entry `req.index` names, which must exist
+        let target = |entries: Vec<rl_git::StashEntry>| {
+            let index = req.index.unwrap_or(0);
+            entries.into_iter().nth(index).ok_or_else(|| {
+                Error::new(
+                    rl_api::ErrorCode::InvalidRequest,
+                    format!("No stash entry stash@{{{}}}", index),
+                )
+                .with_remediation("List the entries with the `list` stash action")
+            })
+        };
+
+        let entries = step!("git_stash_list", { repo_handle.stash_list().await })?;
+        let result = match req.action {
+            StashAction::List => StashResult {
+                entries: entries.into_iter().map(entry).collect(),
+                diff: None,
+            },
+            StashAction::Push => {
+                let saved = step!("git_stash_push", {
+                    repo_handle
+                        .stash_push(req.message.as_deref(), req.include_untracked)
+                        .await
+                })?;
+                let mut created = Vec::new();
+                if saved {
+                    stash_changed();
+                    created.extend(repo_handle.stash_list().await?.into_iter().take(1));
+                }
+                StashResult {
+                    entries: created.into_iter().map(entry).collect(),
+                    diff: None,
+                }
+            }
+            StashAction::Pop | StashAction::Apply => {
+                let target = target(entries)?;
+                let pop = req.action == StashAction::Pop;
+                let conflicts = step!("git_stash_apply", {
+                    repo_handle.stash_apply(target.index, pop).await
+                })?;
+                if !conflicts.is_empty() {
+                    return Err(Error::new(
+                        rl_api::ErrorCode::Conflict,
+                        format!(
+                            "Applying stash@{{{}}} left conflicts in {}",
+                            target.index,
+                            conflicts.join(", ")
+                        ),
+                    )
+                    .with_remediation("Resolve the conflicts; the entry is still in the stash"));
+                }
+                if pop {
+                    stash_changed();
+                }
+                StashResult {
+                    entries: vec![entry(target)],
+                    diff: None,
+                }
+            }
+            StashAction::Drop => {
+                let target = target(entries)?;
+                step!("git_stash_drop", {
+                    repo_handle.stash_drop(target.index).await
+                })?;
+                stash_changed();
+                StashResult {
+                    entries: vec![entry(target)],
+                    diff: None,
+                }
+            }
+            StashAction::Show => {
+                let target = target(entries)?;
+                let range = format!("{}^..{}", target.id, target.id);
+                let name_status = step!("git_diff_name_status", {
+                    repo_handle.diff_name_status(&range).await
+                })?;
+                let numstat = step!("git_diff_numstat", {
+                    repo_handle.diff_numstat(&range).await
+                })?;
+                StashResult {
+                    diff: Some(parse_diff_summary(&name_status, &numstat)?),
+                    entries: vec![entry(target)],
+                }
+            }
+        };
+        Ok(ResponsePayload::Stash(result))
+    }
+
+    /// Confirm the repository can be watched, and keep it open while the
+    /// session watches it.
+    ///
+    /// Change events themselves are delivered from the event bus, outside
+    /// the request; see [`RepoEngine::subscribe`].
     async fn handle_watch(
         &self,
-        _req: rl_api::request::WatchRequest,
+        req: rl_api::request::WatchRequest,
+        session: &Session,
     ) -> Result<ResponsePayload, Error> {
-        Err(Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "Watch not implemented",
+        use std::path::Path;
+
+        step!("git_open_repo", {
+            session.open(&self.repos, Path::new(&req.repo_path)).await
+        })?;
+        // Held by the session, so a caller that goes away stops watching
+        session.watch(&self.repos, Path::new(&req.repo_path));
+
+        Ok(ResponsePayload::Event(rl_api::Event::RepoOpened(
+            rl_api::event::RepoOpenedEvent {
+                repo_path: req.repo_path,
+            },
+        )))
+    }
+
+    /// Reverse the most recent operation the engine performed on the repo.
+    async fn handle_undo(
+        &self,
+        req: rl_api::request::UndoRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+        let repo_handle = step!("git_open_repo", {
+            session.open(&self.repos, repo_path).await
+        })?;
+        let entry = self.journal.last(repo_path).ok_or_else(|| {
+            Error::new(
+                rl_api::ErrorCode::InvalidRequest,
+                "No operation to undo in this repository",
+            )
+        })?;
+
+        step!("git_undo", { journal::undo(&*repo_handle, &entry).await })?;
+        self.journal.remove(repo_path, entry.id);
+
+        if entry.head_before != entry.head_after {
+            self.events.publish(rl_api::Event::HeadChanged(
+                rl_api::event::HeadChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    new_head: entry.head_before.clone(),
+                    old_head: entry.head_after.clone(),
+                },
+            ));
+        }
+        if !entry.refs.is_empty() {
+            self.events.publish(rl_api::Event::RefsChanged(
+                rl_api::event::RefsChangedEvent {
+                    repo_path: req.repo_path.clone(),
+                    changed_refs: entry
+                        .refs
+                        .iter()
+                        .map(|update| update.name.clone())
+                        .collect(),
+                },
+            ));
+        }
+
+        Ok(ResponsePayload::Undo(entry))
+    }
+
+    /// Report the operations the engine performed on the repo.
+    fn handle_journal(&self, req: rl_api::request::JournalRequest) -> ResponsePayload {
+        ResponsePayload::Journal(rl_api::response::OperationJournal {
+            entries: self.journal.entries(std::path::Path::new(&req.repo_path)),
+        })
+    }
+
+    /// Open a repository ahead of use and report its state.
+    async fn handle_open_repo(
+        &self,
+        req: rl_api::request::OpenRepoRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+        step!("git_open_repo", {
+            session.open(&self.repos, repo_path).await
+        })?;
+
+        let key = events::canonical_repo_path(repo_path);
+        let watched = self.repos.is_watched(&key);
+        Ok(ResponsePayload::Repo(self.repo_info(&key, watched)))
+    }
+
+    /// Release a repository from the session and the engine.
+    async fn handle_close_repo(
+        &self,
+        req: rl_api::request::CloseRepoRequest,
+        session: &Session,
+    ) -> Result<ResponsePayload, Error> {
+        use std::path::Path;
+
+        let repo_path = Path::new(&req.repo_path);
+        session.close(repo_path).await;
+        let closed = self.repos.close(repo_path).await;
+
+        Ok(ResponsePayload::OperationResult(
+            rl_api::response::OperationResult {
+                success: true,
+                message: (!closed).then(|| "Repository was not open".to_string()),
+            },
+        ))
+    }
+
+    /// Report every repository the engine holds open.
+    async fn handle_list_repos(&self) -> ResponsePayload {
+        let repos = self
+            .repos
+            .list()
+            .await
+            .into_iter()
+            .map(|repo| self.repo_info(&repo.path, repo.watched))
+            .collect();
+        ResponsePayload::Repos(rl_api::response::RepoList { repos })
+    }
+
+    /// Report the backend and what its git can do. Detection runs once per
+    /// engine; upgrading git takes effect on restart.
+    async fn handle_capabilities(&self) -> ResponsePayload {
+        let capabilities = self
+            .capabilities
+            .get_or_init(|| self.repos.capabilities())
+            .await;
+        ResponsePayload::Capabilities(rl_api::response::Capabilities {
+            backend: self.repos.backend_kind().to_string(),
+            git_available: capabilities.version.is_some(),
+            git_version: capabilities.version.map(|version| version.to_string()),
+            min_git_version: capabilities.min_version.map(|version| version.to_string()),
+            supported: capabilities.supported(),
+            features: rl_api::response::GitFeatures {
+                rename_detection: capabilities.rename_detection,
+                commit_graph: capabilities.commit_graph,
+                fsmonitor: capabilities.fsmonitor,
+            },
+        })
+    }
+
+    /// Reload the configuration, reporting settings that need a restart.
+    fn handle_reload_config(&self) -> Result<ResponsePayload, Error> {
+        let restart = self.reload_config().map_err(|error| {
+            let code = match error.kind() {
+                std::io::ErrorKind::Unsupported => rl_api::ErrorCode::InvalidRequest,
+                _ => rl_api::ErrorCode::Internal,
+            };
+            Error::new(code, error.to_string())
+        })?;
+        let message = if restart.is_empty() {
+            "Reloaded configuration".to_string()
+        } else {
+            format!(
+                "Reloaded configuration; restart to apply changes to {}",
+                restart.join(", ")
+            )
+        };
+        Ok(ResponsePayload::OperationResult(
+            rl_api::response::OperationResult {
+                success: true,
+                message: Some(message),
+            },
         ))
     }
+
+    fn repo_info(&self, path: &std::path::Path, watched: bool) -> rl_api::response::RepoInfo {
+        let repo_path = path.display().to_string();
+        let cache_bytes = self.index_manager.lock().unwrap().repo_bytes(&repo_path);
+        rl_api::response::RepoInfo {
+            repo_path,
+            watch_active: watched,
+            cache_bytes,
+            backend: self.repos.backend_kind().to_string(),
+        }
+    }
 }
 
 /// Engine configuration.
-#[derive(Debug, Clone)]
+///
+/// See [`config`] for loading it from a file and the environment.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct EngineConfig {
+    /// Git implementation serving requests
+    pub backend: config::BackendKind,
     /// Maximum concurrent queries
     pub max_concurrent_queries: usize,
-    /// Query timeout in milliseconds
+    /// Maximum concurrent queries against any one repository, if limited
+    pub max_concurrent_queries_per_repo: Option<usize>,
+    /// Longest a read-only request may run once its turn comes, in
+    /// milliseconds, or 0 for no limit. Mutations are never cut short, as
+    /// that could leave the repository half changed.
     pub query_timeout_ms: u64,
-    /// Cache configuration
+    /// Repositories kept open at most; the least recently used is closed
+    /// to make room
+    pub max_open_repos: usize,
+    /// Repositories unused for this long are closed, in milliseconds
+    pub repo_idle_timeout_ms: u64,
+    /// Whether log pages and diff summaries over fixed history are kept
+    /// and repeats answered from them
     pub cache_enabled: bool,
+    /// Cache size limits and eviction
+    pub cache: CachePolicy,
+    /// Settings for individual repositories, by path
+    pub repos: HashMap<std::path::PathBuf, config::RepoConfig>,
+    /// Directories requests may name repositories in; requests for paths
+    /// outside all of them are rejected. Empty allows any path
+    pub allowed_roots: Vec<std::path::PathBuf>,
+    /// Bytes of responses and stream backlog held at once before requests
+    /// are shed with `Overloaded`, if limited
+    pub memory_budget_bytes: Option<u64>,
+    /// Log filter, e.g. `rl_core=debug,info`; on reload it replaces the
+    /// filter the process started with
+    pub log_filter: Option<String>,
+    /// Where the configuration was loaded from
+    #[serde(skip)]
+    pub source: config::ConfigSource,
 }
 
 impl Default for EngineConfig {
     fn default() -> Self {
         Self {
+            backend: config::BackendKind::default(),
             max_concurrent_queries: 10,
+            max_concurrent_queries_per_repo: None,
             query_timeout_ms: 30000, // 30 seconds
+            max_open_repos: 64,
+            repo_idle_timeout_ms: 600_000, // 10 minutes
             cache_enabled: true,
+            cache: CachePolicy::default(),
+            repos: HashMap::new(),
+            allowed_roots: Vec::new(),
+            memory_budget_bytes: None,
+            log_filter: None,
+            source: config::ConfigSource::default(),
         }
     }
 }
 
 /// Simple cancellation token.
+///
+/// Clones share state, so any clone can cancel the request the others are
+/// watching.
 #[derive(Debug, Clone)]
 pub struct CancellationToken {
     /// Internal cancellation state
-    cancelled: Arc<RwLock<bool>>,
+    cancelled: Arc<watch::Sender<bool>>,
 }
 
 impl CancellationToken {
     /// Create a new cancellation token.
     pub fn new() -> Self {
         Self {
-            cancelled: Arc::new(RwLock::new(false)),
+            cancelled: Arc::new(watch::Sender::new(false)),
         }
     }
 
     /// Check if the operation has been cancelled.
-    pub async fn is_cancelled(&self) -> bool {
-        *self.cancelled.read().await
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
     }
 
     /// Cancel the operation.
-    pub async fn cancel(&self) {
-        *self.cancelled.write().await = true;
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    /// Wait until the operation is cancelled.
+    pub async fn cancelled(&self) {
+        let mut cancelled = self.cancelled.subscribe();
+        // The sender lives in `self`, so the channel cannot close
+        let _ = cancelled.wait_for(|cancelled| *cancelled).await;
     }
+
+    /// Fail with `OperationCanceled` if the operation has been cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(canceled_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Error reported for a request cancelled before it finished.
+fn canceled_error() -> Error {
+    Error::new(
+        rl_api::ErrorCode::OperationCanceled,
+        "Request was cancelled",
+    )
 }
 
 impl Default for CancellationToken {
@@ -531,47 +2284,82 @@ impl Default for CancellationToken {
 }
 
 /// Query scheduler with priority queues.
-pub struct Scheduler {
+///
+/// Queries are taken highest priority first and, within a priority, in the
+/// order they were scheduled.
+pub struct Scheduler<T = PendingQuery> {
     /// UI immediate priority queue
-    ui_immediate: Vec<PendingQuery>,
+    ui_immediate: VecDeque<T>,
     /// UI prefetch priority queue
-    ui_prefetch: Vec<PendingQuery>,
+    ui_prefetch: VecDeque<T>,
     /// Maintenance priority queue
-    maintenance: Vec<PendingQuery>,
+    maintenance: VecDeque<T>,
 }
 
 #[allow(clippy::new_without_default)]
-impl Scheduler {
+impl<T> Scheduler<T> {
     /// Create a new scheduler.
     pub fn new() -> Self {
         Self {
-            ui_immediate: Vec::new(),
-            ui_prefetch: Vec::new(),
-            maintenance: Vec::new(),
+            ui_immediate: VecDeque::new(),
+            ui_prefetch: VecDeque::new(),
+            maintenance: VecDeque::new(),
         }
     }
 
     /// Schedule a query with the given priority.
-    pub fn schedule(&mut self, query: PendingQuery, priority: Priority) {
+    pub fn schedule(&mut self, query: T, priority: Priority) {
         match priority {
-            Priority::UiImmediate => self.ui_immediate.push(query),
-            Priority::UiPrefetch => self.ui_prefetch.push(query),
-            Priority::Maintenance => self.maintenance.push(query),
+            Priority::UiImmediate => self.ui_immediate.push_back(query),
+            Priority::UiPrefetch => self.ui_prefetch.push_back(query),
+            Priority::Maintenance => self.maintenance.push_back(query),
         }
     }
 
     /// Get the next query to execute.
-    pub fn next_query(&mut self) -> Option<PendingQuery> {
-        // UI immediate takes precedence
-        if let Some(query) = self.ui_immediate.pop() {
-            return Some(query);
+    pub fn next_query(&mut self) -> Option<T> {
+        self.next_query_where(|_| true)
+    }
+
+    /// Get the most urgent query that `runnable` accepts, leaving the ones
+    /// it skips in place.
+    pub fn next_query_where(&mut self, mut runnable: impl FnMut(&T) -> bool) -> Option<T> {
+        // UI immediate takes precedence, then UI prefetch, finally maintenance
+        for queue in [
+            &mut self.ui_immediate,
+            &mut self.ui_prefetch,
+            &mut self.maintenance,
+        ] {
+            if let Some(index) = queue.iter().position(&mut runnable) {
+                return queue.remove(index);
+            }
         }
-        // Then UI prefetch
-        if let Some(query) = self.ui_prefetch.pop() {
-            return Some(query);
+        None
+    }
+
+    /// Number of queries waiting.
+    pub fn len(&self) -> usize {
+        self.ui_immediate.len() + self.ui_prefetch.len() + self.maintenance.len()
+    }
+
+    /// Whether no queries are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove and return every waiting query that matches `predicate`.
+    pub fn remove_where(&mut self, mut predicate: impl FnMut(&T) -> bool) -> Vec<T> {
+        let mut removed = Vec::new();
+        for queue in [
+            &mut self.ui_immediate,
+            &mut self.ui_prefetch,
+            &mut self.maintenance,
+        ] {
+            let (matched, kept) = queue.drain(..).partition(|query| predicate(query));
+            *queue = kept;
+            removed.extend(matched);
         }
-        // Finally maintenance
-        self.maintenance.pop()
+        removed
     }
 }
 
@@ -584,10 +2372,20 @@ pub struct PendingQuery {
     pub payload: rl_api::request::RequestPayload,
     /// Cancellation token
     pub cancellation: CancellationToken,
+    /// Session the query belongs to
+    pub(crate) session: u64,
+    /// Priority the query was scheduled with
+    pub(crate) priority: Priority,
+    /// Canonical path of the repository it runs against
+    pub(crate) repo: std::path::PathBuf,
+    /// Queries allowed to run at once against its repository, if limited
+    pub(crate) repo_limit: Option<usize>,
+    /// Wakes the waiting request when it may run
+    pub(crate) start: tokio::sync::oneshot::Sender<queue::Turn>,
 }
 
 /// Query execution priority.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Priority {
     /// Immediate UI response required
     UiImmediate,
@@ -597,28 +2395,997 @@ pub enum Priority {
     Maintenance,
 }
 
+impl Priority {
+    /// Priority a request asked for, defaulting to [`Priority::UiImmediate`].
+    pub fn of(request: &Request) -> Self {
+        request.priority.unwrap_or_default().into()
+    }
+}
+
+impl From<RequestPriority> for Priority {
+    fn from(priority: RequestPriority) -> Self {
+        match priority {
+            RequestPriority::UiImmediate => Self::UiImmediate,
+            RequestPriority::Prefetch => Self::UiPrefetch,
+            RequestPriority::Maintenance => Self::Maintenance,
+        }
+    }
+}
+
 /// Extract repo path from request payload for telemetry.
 fn extract_repo_path(payload: &rl_api::request::RequestPayload) -> String {
-    use rl_api::request::RequestPayload;
-
-    match payload {
-        RequestPayload::Status(req) => req.repo_path.clone(),
-        RequestPayload::Log(req) => req.repo_path.clone(),
-        RequestPayload::Graph(req) => req.repo_path.clone(),
-        RequestPayload::ShowCommit(req) => req.repo_path.clone(),
-        RequestPayload::DiffSummary(req) => req.repo_path.clone(),
-        RequestPayload::DiffContent(req) => req.repo_path.clone(),
-        RequestPayload::Blame(req) => req.repo_path.clone(),
-        RequestPayload::Branches(req) => req.repo_path.clone(),
-        RequestPayload::Tags(req) => req.repo_path.clone(),
-        RequestPayload::Remotes(req) => req.repo_path.clone(),
-        RequestPayload::Checkout(req) => req.repo_path.clone(),
-        RequestPayload::Commit(req) => req.repo_path.clone(),
-        RequestPayload::Fetch(req) => req.repo_path.clone(),
-        RequestPayload::Push(req) => req.repo_path.clone(),
-        RequestPayload::Merge(req) => req.repo_path.clone(),
-        RequestPayload::Rebase(req) => req.repo_path.clone(),
-        RequestPayload::Stash(req) => req.repo_path.clone(),
-        RequestPayload::Watch(req) => req.repo_path.clone(),
+    payload.repo_path().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduler_takes_urgent_work_first_in_arrival_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule("prefetch-1", Priority::UiPrefetch);
+        scheduler.schedule("maintenance", Priority::Maintenance);
+        scheduler.schedule("prefetch-2", Priority::UiPrefetch);
+        scheduler.schedule("immediate", Priority::UiImmediate);
+
+        let order: Vec<_> = std::iter::from_fn(|| scheduler.next_query()).collect();
+        assert_eq!(
+            order,
+            vec!["immediate", "prefetch-1", "prefetch-2", "maintenance"]
+        );
+    }
+
+    #[test]
+    fn test_request_priority_defaults_to_immediate() {
+        let mut request = Request::new(
+            "r1",
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                repo_path: ".".to_string(),
+            }),
+        );
+        assert_eq!(Priority::of(&request), Priority::UiImmediate);
+
+        request.priority = Some(RequestPriority::Prefetch);
+        assert_eq!(Priority::of(&request), Priority::UiPrefetch);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_is_answered_with_operation_canceled() {
+        let engine = Arc::new(RepoEngine::new());
+        let request = Request::new(
+            "r1",
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                repo_path: ".".to_string(),
+            }),
+        );
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        let (tx, mut rx) = mpsc::channel(4);
+        engine
+            .handle_cancellable(request, &Session::new(), tx, cancellation)
+            .await;
+        let response = rx.recv().await.unwrap();
+        assert_eq!(response.id, "r1");
+        assert_eq!(
+            response.result.unwrap_err().code,
+            rl_api::ErrorCode::OperationCanceled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_is_acknowledged_and_events_reach_subscribers() {
+        use rl_api::event::HeadChangedEvent;
+        use rl_api::Event;
+
+        let engine = RepoEngine::new();
+        let mut events = engine.subscribe();
+        let response = engine
+            .handle(Request::new(
+                "w1",
+                rl_api::request::RequestPayload::Watch(rl_api::request::WatchRequest {
+                    repo_path: ".".to_string(),
+                }),
+            ))
+            .await;
+        assert!(matches!(
+            response.result,
+            Ok(ResponsePayload::Event(Event::RepoOpened(_)))
+        ));
+        // Opening the repo was announced on the bus
+        assert!(matches!(events.recv().await, Ok(Event::RepoOpened(_))));
+
+        engine.publish(Event::HeadChanged(HeadChangedEvent {
+            repo_path: ".".to_string(),
+            new_head: None,
+            old_head: None,
+        }));
+        assert!(matches!(events.recv().await, Ok(Event::HeadChanged(_))));
+    }
+
+    #[tokio::test]
+    async fn test_watches_end_with_the_session_that_made_them() {
+        use rl_api::request::{ListReposRequest, RequestPayload, WatchRequest};
+
+        let engine = RepoEngine::new();
+        let request = |payload| Request::new("r1", payload);
+        let watch = || {
+            request(RequestPayload::Watch(WatchRequest {
+                repo_path: ".".to_string(),
+            }))
+        };
+        let watched = || async {
+            match engine
+                .handle(request(RequestPayload::ListRepos(ListReposRequest {})))
+                .await
+                .result
+            {
+                Ok(ResponsePayload::Repos(list)) => list.repos.iter().any(|repo| repo.watch_active),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        // A one-off request has nobody to deliver events to afterwards
+        assert!(engine.handle(watch()).await.result.is_ok());
+        assert!(!watched().await);
+
+        let session = Session::new();
+        let (tx, mut rx) = mpsc::channel(4);
+        for _ in 0..2 {
+            engine
+                .handle_in_session(watch(), &session, tx.clone())
+                .await;
+            assert!(rx.recv().await.unwrap().result.is_ok());
+        }
+        assert!(watched().await);
+        assert!(session.unwatch(std::path::Path::new(".")));
+        assert!(!watched().await);
+
+        engine.handle_in_session(watch(), &session, tx).await;
+        assert!(rx.recv().await.unwrap().result.is_ok());
+        drop(session);
+        assert!(!watched().await);
+    }
+    #[tokio::test]
+    async fn test_repos_can_be_opened_listed_and_closed() {
+        use rl_api::request::{
+            CloseRepoRequest, ListReposRequest, OpenRepoRequest, RequestPayload,
+        };
+
+        let engine = RepoEngine::new();
+        let request = |payload| Request::new("r1", payload);
+        let list = || request(RequestPayload::ListRepos(ListReposRequest {}));
+
+        let opened = engine
+            .handle(request(RequestPayload::OpenRepo(OpenRepoRequest {
+                repo_path: ".".to_string(),
+            })))
+            .await;
+        let Ok(ResponsePayload::Repo(info)) = opened.result else {
+            panic!("expected repo info, got {:?}", opened.result);
+        };
+        assert_eq!(info.backend, "cli");
+        assert!(!info.watch_active);
+
+        let Ok(ResponsePayload::Repos(listed)) = engine.handle(list()).await.result else {
+            panic!("expected repo list");
+        };
+        assert_eq!(listed.repos.len(), 1);
+        assert_eq!(listed.repos[0].repo_path, info.repo_path);
+
+        engine
+            .handle(request(RequestPayload::CloseRepo(CloseRepoRequest {
+                repo_path: ".".to_string(),
+            })))
+            .await
+            .result
+            .unwrap();
+        let Ok(ResponsePayload::Repos(listed)) = engine.handle(list()).await.result else {
+            panic!("expected repo list");
+        };
+        assert!(listed.repos.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diff_summary_reports_mode_and_type_changes() {
+        use rl_api::request::{DiffSummaryRequest, RequestPayload};
+        use rl_api::response::ChangeType;
+        use rl_fixtures::modes_repo::{self, ModesRepo};
+
+        let repo = ModesRepo::ensure("core_diff_modes").expect("Failed to create modes repo");
+        let engine = RepoEngine::new();
+        let diff = |from: &str, to: &str| {
+            engine.handle(Request::new(
+                "d1",
+                RequestPayload::DiffSummary(DiffSummaryRequest {
+                    repo_path: repo.path.to_string_lossy().to_string(),
+                    from: Some(from.to_string()),
+                    to: Some(to.to_string()),
+                    max_bytes: rl_api::MaxBytes::try_from(1_000_000).unwrap(),
+                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+                }),
+            ))
+        };
+        let changes = |response: Response| {
+            let Ok(ResponsePayload::DiffSummary(summary)) = response.result else {
+                panic!("expected a diff summary, got {:?}", response.result);
+            };
+            let mut changes: Vec<_> = summary
+                .changes
+                .into_iter()
+                .map(|c| (c.path, format!("{:?}", c.change_type)))
+                .collect();
+            changes.sort();
+            changes
+        };
+        let modified = format!("{:?}", ChangeType::Modified);
+        let added = format!("{:?}", ChangeType::Added);
+
+        // An executable bit flipped, with no change to the contents
+        assert_eq!(
+            changes(diff("M0", "M1").await),
+            [(modes_repo::SCRIPT.to_string(), modified.clone())]
+        );
+        // A symlink added, and a file turned into one
+        assert_eq!(
+            changes(diff("M1", "M2").await),
+            [
+                (modes_repo::BECOMES_LINK.to_string(), modified.clone()),
+                (modes_repo::DIR_LINK.to_string(), added),
+            ]
+        );
+        // A symlink retargeted
+        assert_eq!(
+            changes(diff("M2", "M3").await),
+            [
+                (modes_repo::LINK.to_string(), modified.clone()),
+                (modes_repo::SCRIPT.to_string(), modified),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broken_repos_fail_with_the_matching_error_code() {
+        use rl_api::request::{
+            DiffSummaryRequest, RequestPayload, StashAction, StashRequest, StatusRequest,
+        };
+        use rl_api::ErrorCode;
+        use rl_fixtures::broken_repo::{self, Breakage, BrokenRepo};
+
+        let engine = RepoEngine::new();
+        let request = |payload| Request::new("b1", payload);
+        let code = |response: Response| match response.result {
+            Err(error) => error.code,
+            Ok(payload) => panic!("expected an error, got {:?}", payload),
+        };
+        let status = |repo: &BrokenRepo| {
+            engine.handle(request(RequestPayload::Status(StatusRequest {
+                repo_path: repo.path.to_string_lossy().to_string(),
+            })))
+        };
+
+        for breakage in [Breakage::NotARepository, Breakage::DanglingGitFile] {
+            let repo = BrokenRepo::create(breakage).unwrap();
+            assert_eq!(code(status(&repo).await), ErrorCode::RepoNotFound);
+        }
+
+        // Status reads no blobs, so only the diff finds the one missing
+        let repo = BrokenRepo::create(Breakage::MissingObject).unwrap();
+        assert!(status(&repo).await.result.is_ok());
+        let diff = engine
+            .handle(request(RequestPayload::DiffSummary(DiffSummaryRequest {
+                repo_path: repo.path.to_string_lossy().to_string(),
+                from: Some(broken_repo::FIRST.to_string()),
+                to: Some(broken_repo::SECOND.to_string()),
+                max_bytes: rl_api::MaxBytes::try_from(1_000_000).unwrap(),
+                max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            })))
+            .await;
+        assert_eq!(code(diff), ErrorCode::GitBackendError);
+
+        let repo = BrokenRepo::create(Breakage::TruncatedPack).unwrap();
+        assert_eq!(code(status(&repo).await), ErrorCode::GitBackendError);
+
+        // Reads go on around a stale lock; only writes are refused
+        let repo = BrokenRepo::create(Breakage::StaleIndexLock).unwrap();
+        assert!(status(&repo).await.result.is_ok());
+        std::fs::write(repo.path.join(broken_repo::NOTES), "changed\n").unwrap();
+        let stash = engine
+            .handle(request(RequestPayload::Stash(StashRequest {
+                repo_path: repo.path.to_string_lossy().to_string(),
+                action: StashAction::Push,
+                message: None,
+                include_untracked: false,
+                index: None,
+            })))
+            .await;
+        let Err(error) = stash.result else {
+            panic!("expected the stash to fail, got {:?}", stash.result);
+        };
+        assert_eq!(error.code, ErrorCode::Conflict, "{:?}", error);
+        assert!(error.remediation.unwrap().contains("index.lock"));
+    }
+
+    #[tokio::test]
+    async fn test_repos_without_commits_are_reported() {
+        use rl_api::paging::Paging;
+        use rl_api::request::{
+            BranchesRequest, DiffSummaryRequest, LogRequest, RequestPayload, StatusRequest,
+        };
+        use rl_fixtures::empty_repo::{self, EmptyRepo, EmptyState};
+
+        let engine = RepoEngine::new();
+        let handle = |payload| engine.handle(Request::new("e1", payload));
+        for state in [EmptyState::Fresh, EmptyState::Staged] {
+            let repo = EmptyRepo::create("core_empty", state).unwrap();
+            let repo_path = repo.path.to_string_lossy().to_string();
+
+            let status = handle(RequestPayload::Status(StatusRequest {
+                repo_path: repo_path.clone(),
+            }));
+            let Ok(ResponsePayload::Status(status)) = status.await.result else {
+                panic!("expected status of {:?}", state);
+            };
+            assert_eq!(status.branch.as_deref(), Some(empty_repo::BRANCH));
+            assert_eq!(status.head, None);
+
+            let branches = handle(RequestPayload::Branches(BranchesRequest {
+                repo_path: repo_path.clone(),
+            }));
+            let Ok(ResponsePayload::Branches(branches)) = branches.await.result else {
+                panic!("expected branches of {:?}", state);
+            };
+            assert_eq!(branches.current.as_deref(), Some(empty_repo::BRANCH));
+            assert!(branches.local.is_empty());
+
+            let log = handle(RequestPayload::Log(LogRequest {
+                repo_path: repo_path.clone(),
+                paging: Paging {
+                    page_size: rl_api::PageSize::try_from(10).unwrap(),
+                    cursor: rl_api::Cursor::initial(),
+                },
+                revision_range: None,
+            }));
+            let Ok(ResponsePayload::Log(log)) = log.await.result else {
+                panic!("expected a log page of {:?}", state);
+            };
+            assert!(log.commits.is_empty() && !log.has_more);
+
+            // With no HEAD to diff against, whatever is staged is added
+            let diff = handle(RequestPayload::DiffSummary(DiffSummaryRequest {
+                repo_path,
+                from: None,
+                to: None,
+                max_bytes: rl_api::MaxBytes::try_from(1_000_000).unwrap(),
+                max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+            }));
+            let Ok(ResponsePayload::DiffSummary(diff)) = diff.await.result else {
+                panic!("expected a diff summary of {:?}", state);
+            };
+            let added: Vec<_> = diff.changes.iter().map(|c| c.path.as_str()).collect();
+
+            match state {
+                EmptyState::Fresh => {
+                    assert!(status.index.staged.is_empty());
+                    assert!(status.workdir.untracked.is_empty());
+                    assert!(added.is_empty());
+                }
+                EmptyState::Staged => {
+                    assert_eq!(status.index.staged, empty_repo::STAGED);
+                    assert_eq!(status.workdir.untracked, [empty_repo::UNTRACKED]);
+                    assert_eq!(added, empty_repo::STAGED);
+                    assert_eq!(diff.additions, empty_repo::STAGED.len());
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_paths_that_are_not_utf8_are_escaped() {
+        use rl_api::request::{DiffSummaryRequest, RequestPayload, StatusRequest};
+        use rl_fixtures::non_utf8_repo::{self, NonUtf8Repo};
+
+        let repo = NonUtf8Repo::create("core_non_utf8").unwrap();
+        let engine = RepoEngine::new();
+        let handle = |payload| engine.handle(Request::new("u1", payload));
+
+        let status = handle(RequestPayload::Status(StatusRequest {
+            repo_path: repo.path.to_string_lossy().to_string(),
+        }))
+        .await;
+        let Ok(ResponsePayload::Status(view)) = &status.result else {
+            panic!("expected status, got {:?}", status.result);
+        };
+        assert_eq!(view.workdir.modified, ["caf\\xe9.txt"]);
+        assert_eq!(view.workdir.untracked, ["\\xfcber.txt"]);
+        // The escapes are plain text to JSON, and survive a round trip
+        let json = serde_json::to_string(&status).unwrap();
+        assert!(json.contains(r#""caf\\xe9.txt""#), "{}", json);
+        let parsed: Response = serde_json::from_str(&json).unwrap();
+        let Ok(ResponsePayload::Status(parsed)) = parsed.result else {
+            panic!("expected status");
+        };
+        assert_eq!(parsed.workdir.modified, view.workdir.modified);
+
+        let diff = handle(RequestPayload::DiffSummary(DiffSummaryRequest {
+            repo_path: repo.path.to_string_lossy().to_string(),
+            from: Some(non_utf8_repo::FIRST.to_string()),
+            to: Some(non_utf8_repo::SECOND.to_string()),
+            max_bytes: rl_api::MaxBytes::try_from(1_000_000).unwrap(),
+            max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+        }))
+        .await;
+        let Ok(ResponsePayload::DiffSummary(diff)) = diff.result else {
+            panic!("expected a diff summary, got {:?}", diff.result);
+        };
+        let changes: Vec<_> = diff
+            .changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.old_path.as_deref(), c.additions))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("caf\\xe9.txt", None, 1),
+                (
+                    "na\\xefve/r\\xe9sum\\xe9-final.md",
+                    Some("na\\xefve/r\\xe9sum\\xe9.md"),
+                    0
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heads_off_a_born_branch_are_reported() {
+        use rl_api::paging::Paging;
+        use rl_api::request::{BranchesRequest, LogRequest, RequestPayload, StatusRequest};
+        use rl_api::response::{BranchList, CommitListPage, StatusView};
+        use rl_fixtures::head_states_repo::{self, HeadState, HeadStatesRepo};
+
+        let engine = RepoEngine::new();
+        let request = |payload| Request::new("h1", payload);
+        let heads = |repo: &HeadStatesRepo| {
+            let repo_path = repo.path.to_string_lossy().to_string();
+            let status = engine.handle(request(RequestPayload::Status(StatusRequest {
+                repo_path: repo_path.clone(),
+            })));
+            let branches = engine.handle(request(RequestPayload::Branches(BranchesRequest {
+                repo_path: repo_path.clone(),
+            })));
+            let log = engine.handle(request(RequestPayload::Log(LogRequest {
+                repo_path,
+                paging: Paging {
+                    page_size: rl_api::PageSize::try_from(10).unwrap(),
+                    cursor: rl_api::Cursor::initial(),
+                },
+                revision_range: None,
+            })));
+            async move {
+                let Ok(ResponsePayload::Status(status)) = status.await.result else {
+                    panic!("expected status");
+                };
+                let Ok(ResponsePayload::Branches(branches)) = branches.await.result else {
+                    panic!("expected branches");
+                };
+                let Ok(ResponsePayload::Log(log)) = log.await.result else {
+                    panic!("expected a log page");
+                };
+                (status, branches, log)
+            }
+        };
+        let messages = |log: &CommitListPage| -> Vec<String> {
+            log.commits.iter().map(|c| c.message.clone()).collect()
+        };
+        let local = |branches: &BranchList| -> Vec<String> {
+            branches.local.iter().map(|b| b.name.clone()).collect()
+        };
+
+        let repo = HeadStatesRepo::create("core_heads", HeadState::DetachedAtTag).unwrap();
+        let (status, branches, log): (StatusView, _, _) = heads(&repo).await;
+        assert_eq!(status.branch, None);
+        assert_eq!(
+            status.head,
+            Some(repo.rev_parse(head_states_repo::TAG).unwrap())
+        );
+        assert_eq!(branches.current, None);
+        assert_eq!(messages(&log), ["first commit"]);
+
+        let repo = HeadStatesRepo::create("core_heads", HeadState::Orphan).unwrap();
+        let (status, branches, log) = heads(&repo).await;
+        assert_eq!(status.branch.as_deref(), Some(head_states_repo::ORPHAN));
+        assert_eq!(branches.current.as_deref(), Some(head_states_repo::ORPHAN));
+        assert_eq!(messages(&log), ["orphan commit"]);
+
+        // The branch is checked out but has no ref until its first commit
+        let repo = HeadStatesRepo::create("core_heads", HeadState::Unborn).unwrap();
+        let (status, branches, log) = heads(&repo).await;
+        assert_eq!(status.branch.as_deref(), Some(head_states_repo::UNBORN));
+        assert_eq!(status.head, None);
+        assert_eq!(status.index.staged, ["README.md"]);
+        assert_eq!(branches.current.as_deref(), Some(head_states_repo::UNBORN));
+        assert_eq!(
+            local(&branches),
+            [head_states_repo::MASTER, head_states_repo::ORPHAN]
+        );
+        assert!(log.commits.is_empty() && !log.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_step_timings_are_returned_when_requested() {
+        let engine = RepoEngine::new();
+        let request = |timings| Request {
+            timings,
+            trace: timings,
+            ..Request::new(
+                "t1",
+                rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                    repo_path: ".".to_string(),
+                }),
+            )
+        };
+
+        let meta = engine.handle(request(false)).await.meta.unwrap();
+        assert!(meta.steps.is_empty());
+        assert!(meta.request_id.is_none());
+        assert!(meta.elapsed_ms > 0.0);
+        assert!(!meta.cached);
+
+        let meta = engine.handle(request(true)).await.meta.unwrap();
+        let steps: Vec<_> = meta.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(steps[..2], ["repo_lock", "queue"]);
+        // Steps inside the handler finish before the handler itself
+        assert!(steps.contains(&"git_status_porcelain"));
+        assert_eq!(steps.last(), Some(&"status"));
+        assert!(meta.request_id.unwrap().starts_with("req_"));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_client_requests() {
+        let engine = RepoEngine::new();
+        let request = |priority| Request {
+            priority,
+            ..Request::new(
+                "m1",
+                rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                    repo_path: ".".to_string(),
+                }),
+            )
+        };
+
+        let spawned = rl_git::backend::processes_spawned();
+        assert!(engine.handle(request(None)).await.result.is_ok());
+        assert!(engine.handle(request(None)).await.result.is_ok());
+        let prefetch = request(Some(RequestPriority::Prefetch));
+        assert!(engine.handle(prefetch).await.result.is_ok());
+        assert_eq!(engine.metrics().requests("status"), 2);
+        assert!(rl_git::backend::processes_spawned() > spawned);
+
+        let text = engine.metrics().render();
+        assert!(text.contains("repo_lens_requests_total{type=\"status\"} 2\n"));
+        assert!(text.contains("repo_lens_request_duration_seconds_count{type=\"status\"} 2\n"));
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_reports_settings_that_need_a_restart() {
+        let engine = RepoEngine::new();
+        let response = engine
+            .handle(Request::new(
+                "c1",
+                rl_api::request::RequestPayload::ReloadConfig(
+                    rl_api::request::ReloadConfigRequest {},
+                ),
+            ))
+            .await;
+        // Configured in code, so there is no file to reload
+        assert_eq!(
+            response.result.unwrap_err().code,
+            rl_api::ErrorCode::InvalidRequest
+        );
+
+        let restart = engine.reconfigure(EngineConfig {
+            max_open_repos: 1,
+            max_concurrent_queries: 2,
+            ..Default::default()
+        });
+        assert_eq!(restart, vec!["max_concurrent_queries"]);
+        assert_eq!(engine.config.lock().unwrap().max_open_repos, 1);
+    }
+
+    #[tokio::test]
+    async fn test_diff_summaries_between_commits_are_cached() {
+        use rl_api::request::{DiffSummaryRequest, RequestPayload};
+        use rl_fixtures::builder::FixtureBuilder;
+
+        let repo = FixtureBuilder::new()
+            .file("a.txt", "one\n")
+            .commit("first")
+            .file("a.txt", "two\n")
+            .commit("second")
+            .file("a.txt", "uncommitted\n")
+            .build("diff_cache")
+            .unwrap();
+        let engine = RepoEngine::new();
+        let diff = |to: Option<&str>| {
+            engine.handle(Request::new(
+                "d1",
+                RequestPayload::DiffSummary(DiffSummaryRequest {
+                    repo_path: repo.path.to_string_lossy().to_string(),
+                    from: Some("HEAD~1".to_string()),
+                    to: to.map(str::to_string),
+                    max_bytes: rl_api::MaxBytes::try_from(1_000_000).unwrap(),
+                    max_hunks: rl_api::MaxHunks::try_from(1000).unwrap(),
+                }),
+            ))
+        };
+        let cached = |response: Response| {
+            assert!(response.result.is_ok());
+            response.meta.unwrap().cached
+        };
+
+        assert!(!cached(diff(Some("HEAD")).await));
+        assert!(cached(diff(Some("HEAD")).await));
+        // The working directory may have changed since
+        assert!(!cached(diff(None).await));
+        assert!(!cached(diff(None).await));
+
+        engine.reconfigure(EngineConfig {
+            cache_enabled: false,
+            ..Default::default()
+        });
+        assert!(!cached(diff(Some("HEAD")).await));
+    }
+
+    #[tokio::test]
+    async fn test_push_progress_streams_before_the_final_response() {
+        use rl_api::request::{PushRequest, RequestPayload};
+        use rl_fixtures::builder::FixtureBuilder;
+        use std::process::Command;
+
+        let repo = FixtureBuilder::new()
+            .file("a.txt", "one\n")
+            .commit("first")
+            .build("push_progress")
+            .unwrap();
+        let remote =
+            std::env::temp_dir().join(format!("rl-push-progress-{}.git", std::process::id()));
+        let _ = std::fs::remove_dir_all(&remote);
+        let init = Command::new("git")
+            .args(["init", "--quiet", "--bare"])
+            .arg(&remote)
+            .status()
+            .unwrap();
+        assert!(init.success());
+
+        let engine = RepoEngine::new();
+        let (tx, mut rx) = mpsc::channel(64);
+        engine
+            .handle_stream(
+                Request::new(
+                    "p1",
+                    RequestPayload::Push(PushRequest {
+                        repo_path: repo.path.to_string_lossy().to_string(),
+                        remote: Some(remote.to_string_lossy().to_string()),
+                        refspecs: Some(vec!["HEAD:refs/heads/master".to_string()]),
+                        force: false,
+                        dry_run: false,
+                    }),
+                ),
+                tx,
+            )
+            .await;
+        let mut chunks = Vec::new();
+        while let Ok(response) = rx.try_recv() {
+            match response.result {
+                Ok(ResponsePayload::Progress(chunk)) => chunks.push(chunk),
+                other => panic!("expected progress, got {:?}", other),
+            }
+        }
+
+        // git's own progress lines come first, in order, then the answer
+        let (last, progress) = chunks.split_last().expect("no responses");
+        assert!(last.is_final);
+        assert_eq!(last.data.stage, "done");
+        assert!(progress.iter().all(|chunk| !chunk.is_final));
+        assert!(progress.iter().any(|chunk| chunk
+            .data
+            .message
+            .as_deref()
+            .unwrap_or_default()
+            .starts_with("Writing objects")));
+        let sequences: Vec<u64> = chunks.iter().map(|chunk| chunk.sequence).collect();
+        assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let pushed = Command::new("git")
+            .arg("--git-dir")
+            .arg(&remote)
+            .args(["rev-parse", "master"])
+            .output()
+            .unwrap();
+        let head = Command::new("git")
+            .current_dir(&repo.path)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .unwrap();
+        assert_eq!(pushed.stdout, head.stdout);
+        std::fs::remove_dir_all(&remote).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_query_timeout_applies_to_the_next_query() {
+        use handler::HandlerContext;
+        use rl_api::request::{RequestPayload, StatusRequest};
+        use rl_api::response::OperationResult;
+
+        /// Answers Status after a while.
+        struct SlowStatus;
+
+        #[async_trait::async_trait]
+        impl Handler for SlowStatus {
+            type Request = StatusRequest;
+
+            async fn handle(
+                &self,
+                _request: StatusRequest,
+                _cx: &HandlerContext<'_>,
+            ) -> Result<ResponsePayload, Error> {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(ResponsePayload::OperationResult(OperationResult {
+                    success: true,
+                    message: None,
+                }))
+            }
+        }
+
+        let mut engine = RepoEngine::new();
+        engine.register_handler(SlowStatus);
+        let status = || {
+            engine.handle(Request::new(
+                "t1",
+                RequestPayload::Status(StatusRequest {
+                    repo_path: "/slow".to_string(),
+                }),
+            ))
+        };
+
+        assert!(status().await.result.is_ok());
+        engine.reconfigure(EngineConfig {
+            query_timeout_ms: 20,
+            ..Default::default()
+        });
+        let error = status().await.result.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_undo_restores_stash_entries_and_keeps_the_others() {
+        use rl_api::request::{RequestPayload, StashAction, StashRequest, UndoRequest};
+        use rl_api::response::ResponsePayload;
+        use rl_fixtures::mixed_status_repo::{self, MixedStatusRepo};
+
+        let repo = MixedStatusRepo::create("undo_stash").unwrap();
+        let repo_path = repo.path.to_string_lossy().to_string();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8(output.stdout).unwrap()
+        };
+        let stash_list = || -> Vec<String> {
+            git(&["stash", "list", "--format=%H"])
+                .lines()
+                .map(str::to_string)
+                .collect()
+        };
+        let engine = RepoEngine::new();
+        let handle = |payload| engine.handle(Request::new("u1", payload));
+        let stash = |action, index| {
+            handle(RequestPayload::Stash(StashRequest {
+                repo_path: repo_path.clone(),
+                action,
+                message: None,
+                include_untracked: false,
+                index,
+            }))
+        };
+        let undo = || {
+            handle(RequestPayload::Undo(UndoRequest {
+                repo_path: repo_path.clone(),
+            }))
+        };
+        let undone = |response: Response| match response.result {
+            Ok(ResponsePayload::Undo(entry)) => entry,
+            other => panic!("expected an undone entry, got {:?}", other),
+        };
+        let unstaged = repo.path.join(mixed_status_repo::UNSTAGED);
+
+        // Made outside the engine, so never journaled
+        git(&["stash", "push", "--quiet", "--message", "before"]);
+        let before = stash_list();
+        std::fs::write(&unstaged, "first\n").unwrap();
+        assert!(stash(StashAction::Push, None).await.result.is_ok());
+        std::fs::write(&unstaged, "second\n").unwrap();
+        assert!(stash(StashAction::Push, None).await.result.is_ok());
+        let pushed = stash_list();
+        assert_eq!(pushed.len(), 3);
+        assert_eq!(pushed[2], before[0]);
+
+        let entry = undone(undo().await);
+        assert_eq!(entry.stash_created.as_deref(), Some(pushed[0].as_str()));
+        assert_eq!(stash_list(), pushed[1..]);
+        assert_eq!(std::fs::read_to_string(&unstaged).unwrap(), "second\n");
+
+        // Dropping the newest entry leaves another on top of `refs/stash`
+        git(&["checkout", "--quiet", "--", mixed_status_repo::UNSTAGED]);
+        assert!(stash(StashAction::Drop, Some(0)).await.result.is_ok());
+        assert_eq!(stash_list(), pushed[2..]);
+        let entry = undone(undo().await);
+        assert_eq!(entry.stash_created, None);
+        assert_eq!(entry.stash_dropped.as_deref(), Some(pushed[1].as_str()));
+        assert_eq!(stash_list(), pushed[1..]);
+
+        // The first push is no longer on top of the stash
+        std::fs::write(&unstaged, "third\n").unwrap();
+        git(&["stash", "push", "--quiet", "--message", "after"]);
+        let error = undo().await.result.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::Conflict);
+        assert_eq!(stash_list()[1..], pushed[1..]);
+    }
+
+    #[tokio::test]
+    async fn test_mutations_do_what_their_dry_runs_report() {
+        use rl_api::request::{
+            CheckoutRequest, CleanRequest, MergeRequest, RequestPayload, ResetMode, ResetRequest,
+        };
+        use rl_api::response::{DryRunReport, MergeType, ResponsePayload};
+        use rl_fixtures::builder::FixtureBuilder;
+
+        let repo = FixtureBuilder::new()
+            .file("a.txt", "one\n")
+            .commit("first")
+            .branch("feature")
+            .file("b.txt", "feature\n")
+            .commit("add b")
+            .checkout("master")
+            .file("a.txt", "two\n")
+            .commit("change a")
+            .build("mutations")
+            .unwrap();
+        let repo_path = repo.path.to_string_lossy().to_string();
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+        let engine = RepoEngine::new();
+        let handle = |payload| async {
+            engine
+                .handle(Request::new("m1", payload))
+                .await
+                .result
+                .unwrap()
+        };
+        let report = |payload: ResponsePayload| match payload {
+            ResponsePayload::DryRun(report) => report,
+            other => panic!("expected a dry run report, got {:?}", other),
+        };
+        let checkout = |target: &str, create_branch, dry_run| {
+            RequestPayload::Checkout(CheckoutRequest {
+                repo_path: repo_path.clone(),
+                target: target.to_string(),
+                create_branch,
+                dry_run,
+            })
+        };
+        let merge = |dry_run| {
+            RequestPayload::Merge(MergeRequest {
+                repo_path: repo_path.clone(),
+                source: "feature".to_string(),
+                message: None,
+                dry_run,
+            })
+        };
+        let master = git(&["rev-parse", "master"]);
+        let feature = git(&["rev-parse", "feature"]);
+
+        let DryRunReport { files, refs, .. } =
+            report(handle(checkout("feature", false, true)).await);
+        assert_eq!(files, vec!["a.txt", "b.txt"]);
+        assert_eq!(refs[0].new.as_deref(), Some(feature.as_str()));
+        handle(checkout("feature", false, false)).await;
+        assert_eq!(git(&["symbolic-ref", "--short", "HEAD"]), "feature");
+        assert_eq!(git(&["rev-parse", "HEAD"]), feature);
+        handle(checkout(&master, false, false)).await;
+        assert!(git(&["status", "--porcelain", "--branch"]).contains("no branch"));
+        handle(checkout("topic", true, false)).await;
+        assert_eq!(git(&["symbolic-ref", "--short", "HEAD"]), "topic");
+        assert_eq!(git(&["rev-parse", "topic"]), master);
+
+        // A merge commit does not exist until the merge runs
+        let DryRunReport { refs, .. } = report(handle(merge(true)).await);
+        assert_eq!(refs[0].name, "refs/heads/topic");
+        assert_eq!(refs[0].new, None);
+        match handle(merge(false)).await {
+            ResponsePayload::MergeResult(result) => {
+                assert!(result.success);
+                assert!(matches!(result.merge_type, MergeType::MergeCommit));
+            }
+            other => panic!("expected a merge result, got {:?}", other),
+        }
+        assert_eq!(git(&["rev-parse", "HEAD^2"]), feature);
+        match handle(merge(false)).await {
+            ResponsePayload::MergeResult(result) => {
+                assert!(matches!(result.merge_type, MergeType::UpToDate))
+            }
+            other => panic!("expected a merge result, got {:?}", other),
+        }
+        let merged = git(&["rev-parse", "HEAD"]);
+
+        std::fs::write(repo.path.join("a.txt"), "local\n").unwrap();
+        std::fs::write(repo.path.join("scratch.txt"), "untracked\n").unwrap();
+        let reset = |dry_run| {
+            RequestPayload::Reset(ResetRequest {
+                repo_path: repo_path.clone(),
+                target: master.clone(),
+                mode: ResetMode::Hard,
+                dry_run,
+            })
+        };
+        let DryRunReport {
+            files,
+            commits_rewritten,
+            ..
+        } = report(handle(reset(true)).await);
+        assert_eq!(files, vec!["a.txt", "b.txt"]);
+        assert_eq!(commits_rewritten, vec![merged, feature]);
+        handle(reset(false)).await;
+        assert_eq!(git(&["rev-parse", "HEAD"]), master);
+        assert_eq!(
+            std::fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+            "two\n"
+        );
+        assert!(!repo.path.join("b.txt").exists());
+
+        let clean = |dry_run| {
+            RequestPayload::Clean(CleanRequest {
+                repo_path: repo_path.clone(),
+                directories: false,
+                ignored: false,
+                dry_run,
+            })
+        };
+        let DryRunReport { files, .. } = report(handle(clean(true)).await);
+        assert_eq!(files, vec!["scratch.txt"]);
+        assert!(repo.path.join("scratch.txt").exists());
+        match handle(clean(false)).await {
+            ResponsePayload::Clean(result) => assert_eq!(result.removed, vec!["scratch.txt"]),
+            other => panic!("expected a clean result, got {:?}", other),
+        }
+        assert!(!repo.path.join("scratch.txt").exists());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_parse_diff_summary_survives_garbled_output(
+            name_status in rl_fixtures::malformed::garbled_str(
+                "M\tsrc/lib.rs\nA\tnew file.txt\nD\told.txt\nR090\tfrom.rs\tto.rs\n",
+            ),
+            numstat in rl_fixtures::malformed::garbled_str(
+                "3\t1\tsrc/lib.rs\n5\t0\tnew file.txt\n0\t7\told.txt\n-\t-\tbin.dat\n",
+            ),
+        ) {
+            let summary = parse_diff_summary(&name_status, &numstat).unwrap();
+            proptest::prop_assert!(summary.files_changed <= name_status.lines().count());
+            proptest::prop_assert_eq!(summary.files_changed, summary.changes.len());
+        }
     }
 }
@@ -0,0 +1,181 @@
+//! Per-repository read/write locking.
+//!
+//! Git does not expect two mutations of one repository at once: a commit
+//! racing a checkout can leave HEAD or the index in a state neither asked
+//! for. Queries take their repository's lock shared and run alongside each
+//! other; mutations take it exclusively, so they wait for running queries
+//! and queries arriving after them wait in turn. A mutation arriving while
+//! another is pending or running on the same repository is refused with
+//! `ErrorCode::Conflict` instead of queued, as the client decided on it
+//! without knowing the repository was about to change.
+
+use crate::events::canonical_repo_path;
+use rl_api::request::{RequestPayload, StashAction};
+use rl_api::{Error, ErrorCode};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedMutexGuard, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+/// Locks of the repositories with requests holding or waiting for them.
+#[derive(Default)]
+pub(crate) struct RepoLocks {
+    /// Locks by canonical repository path
+    repos: Mutex<HashMap<PathBuf, RepoLock>>,
+}
+
+#[derive(Default)]
+struct RepoLock {
+    /// Shared by queries, exclusive to mutations
+    access: Arc<RwLock<()>>,
+    /// Held by the one mutation pending or running
+    mutation: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// How a request uses its repository.
+enum Access {
+    Read,
+    Write,
+}
+
+/// A request's hold on its repository; dropping it releases the lock.
+pub(crate) struct RepoGuard {
+    _read: Option<OwnedRwLockReadGuard<()>>,
+    _write: Option<OwnedRwLockWriteGuard<()>>,
+    _mutation: Option<OwnedMutexGuard<()>>,
+}
+
+impl RepoLocks {
+    /// Wait until `payload` may use its repository.
+    ///
+    /// Returns `None` for requests that do not touch a repository's state.
+    pub(crate) async fn lock(&self, payload: &RequestPayload) -> Result<Option<RepoGuard>, Error> {
+        let Some(access) = access(payload) else {
+            return Ok(None);
+        };
+        let (lock, mutation) = {
+            let mut repos = self.repos.lock().unwrap();
+            // Nobody holds or waits for these any more
+            repos.retain(|_, lock| {
+                Arc::strong_count(&lock.access) > 1 || Arc::strong_count(&lock.mutation) > 1
+            });
+            let lock = repos
+                .entry(canonical_repo_path(payload.repo_path()))
+                .or_default();
+            (lock.access.clone(), lock.mutation.clone())
+        };
+
+        let guard = match access {
+            Access::Read => RepoGuard {
+                _read: Some(lock.read_owned().await),
+                _write: None,
+                _mutation: None,
+            },
+            Access::Write => {
+                let mutation = mutation.try_lock_owned().map_err(|_| {
+                    Error::new(
+                        ErrorCode::Conflict,
+                        "Another operation is already changing this repository",
+                    )
+                    .with_remediation("Retry once the running operation has finished")
+                })?;
+                RepoGuard {
+                    _read: None,
+                    _write: Some(lock.write_owned().await),
+                    _mutation: Some(mutation),
+                }
+            }
+        };
+        Ok(Some(guard))
+    }
+}
+
+/// How `payload` uses its repository; `None` for requests that leave it
+/// alone.
+fn access(payload: &RequestPayload) -> Option<Access> {
+    match payload {
+        RequestPayload::Status(_)
+        | RequestPayload::Log(_)
+        | RequestPayload::Graph(_)
+        | RequestPayload::ShowCommit(_)
+        | RequestPayload::DiffSummary(_)
+        | RequestPayload::DiffContent(_)
+        | RequestPayload::Blame(_)
+        | RequestPayload::Branches(_)
+        | RequestPayload::Tags(_)
+        | RequestPayload::Remotes(_) => Some(Access::Read),
+        // Dry runs only read
+        RequestPayload::Checkout(req) if req.dry_run => Some(Access::Read),
+        RequestPayload::Push(req) if req.dry_run => Some(Access::Read),
+        RequestPayload::Merge(req) if req.dry_run => Some(Access::Read),
+        RequestPayload::Rebase(req) if req.dry_run => Some(Access::Read),
+        RequestPayload::Reset(req) if req.dry_run => Some(Access::Read),
+        RequestPayload::Clean(req) if req.dry_run => Some(Access::Read),
+        // Listing and showing entries only read
+        RequestPayload::Stash(req)
+            if matches!(req.action, StashAction::List | StashAction::Show) =>
+        {
+            Some(Access::Read)
+        }
+        RequestPayload::Checkout(_)
+        | RequestPayload::Commit(_)
+        | RequestPayload::Fetch(_)
+        | RequestPayload::Push(_)
+        | RequestPayload::Merge(_)
+        | RequestPayload::Rebase(_)
+        | RequestPayload::Reset(_)
+        | RequestPayload::Clean(_)
+        | RequestPayload::Stash(_)
+        | RequestPayload::Undo(_) => Some(Access::Write),
+        RequestPayload::Journal(_)
+        | RequestPayload::Watch(_)
+        | RequestPayload::OpenRepo(_)
+        | RequestPayload::CloseRepo(_)
+        | RequestPayload::ListRepos(_)
+        | RequestPayload::Capabilities(_)
+        | RequestPayload::ReloadConfig(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::request::{CommitRequest, StatusRequest};
+
+    fn status(repo_path: &str) -> RequestPayload {
+        RequestPayload::Status(StatusRequest {
+            repo_path: repo_path.to_string(),
+        })
+    }
+
+    fn commit(repo_path: &str) -> RequestPayload {
+        RequestPayload::Commit(CommitRequest {
+            repo_path: repo_path.to_string(),
+            message: "m".to_string(),
+            author_name: None,
+            author_email: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_second_mutation_conflicts_and_queries_wait_for_the_first() {
+        let locks = Arc::new(RepoLocks::default());
+        let first = locks.lock(&commit("/a")).await.unwrap();
+
+        let error = locks.lock(&commit("/a")).await.err().unwrap();
+        assert_eq!(error.code, ErrorCode::Conflict);
+        // Other repositories are unaffected
+        assert!(locks.lock(&commit("/b")).await.is_ok());
+
+        let query = {
+            let locks = locks.clone();
+            tokio::spawn(async move { locks.lock(&status("/a")).await.is_ok() })
+        };
+        tokio::task::yield_now().await;
+        assert!(!query.is_finished());
+
+        drop(first);
+        assert!(query.await.unwrap());
+        assert!(locks.lock(&commit("/a")).await.is_ok());
+    }
+}
@@ -0,0 +1,262 @@
+//! Request counters and latency histograms, for scraping by Prometheus.
+//!
+//! Every request a client sends is counted under its wire name once it has
+//! been answered, along with how long that took, the code it failed with
+//! if it failed, and whether it was answered from a cache. Windows the
+//! engine prefetches on its own are not counted as requests. Git processes
+//! are counted as the backend starts them, prefetches included.
+//!
+//! [`Metrics::render`] writes everything in the Prometheus text exposition
+//! format; serving it over HTTP is left to the transport.
+
+use rl_api::Response;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+
+/// What the engine has done since it started, by request type.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<&'static str, RequestMetrics>>,
+}
+
+/// Counts for one request type
+#[derive(Debug, Default)]
+struct RequestMetrics {
+    /// Requests answered
+    count: u64,
+    /// Requests answered from a cache
+    cached: u64,
+    /// Requests that failed, by error code
+    errors: BTreeMap<String, u64>,
+    /// Requests answered within each of [`BUCKETS`], not cumulative
+    buckets: [u64; BUCKETS.len()],
+    /// Total time spent answering, in seconds
+    seconds: f64,
+}
+
+impl Metrics {
+    /// Count a request of type `kind`, answered with `response` after
+    /// `elapsed`.
+    pub(crate) fn record(&self, kind: &'static str, response: &Response, elapsed: Duration) {
+        let mut requests = self.requests.lock().unwrap();
+        let metrics = requests.entry(kind).or_default();
+        metrics.count += 1;
+        if response.meta.as_ref().is_some_and(|meta| meta.cached) {
+            metrics.cached += 1;
+        }
+        if let Err(error) = &response.result {
+            *metrics.errors.entry(error.code.to_string()).or_default() += 1;
+        }
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            metrics.buckets[bucket] += 1;
+        }
+        metrics.seconds += seconds;
+    }
+
+    /// Requests of type `kind` (e.g. `"status"`) answered so far.
+    pub fn requests(&self, kind: &str) -> u64 {
+        let requests = self.requests.lock().unwrap();
+        requests.get(kind).map_or(0, |metrics| metrics.count)
+    }
+
+    /// Everything counted so far, in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let requests = self.requests.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "repo_lens_requests_total",
+            "counter",
+            "Requests answered, by type.",
+        );
+        for (kind, metrics) in requests.iter() {
+            sample(
+                &mut out,
+                "repo_lens_requests_total",
+                kind,
+                "",
+                metrics.count,
+            );
+        }
+
+        header(
+            &mut out,
+            "repo_lens_request_errors_total",
+            "counter",
+            "Requests that failed, by type and error code.",
+        );
+        for (kind, metrics) in requests.iter() {
+            for (code, count) in &metrics.errors {
+                let labels = format!(",code=\"{}\"", code);
+                sample(
+                    &mut out,
+                    "repo_lens_request_errors_total",
+                    kind,
+                    &labels,
+                    count,
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "repo_lens_request_duration_seconds",
+            "histogram",
+            "Time taken to answer requests, by type.",
+        );
+        for (kind, metrics) in requests.iter() {
+            let mut answered = 0;
+            for (bound, count) in BUCKETS.iter().zip(metrics.buckets) {
+                answered += count;
+                let labels = format!(",le=\"{}\"", bound);
+                sample(
+                    &mut out,
+                    "repo_lens_request_duration_seconds_bucket",
+                    kind,
+                    &labels,
+                    answered,
+                );
+            }
+            let name = "repo_lens_request_duration_seconds";
+            sample(
+                &mut out,
+                &format!("{}_bucket", name),
+                kind,
+                ",le=\"+Inf\"",
+                metrics.count,
+            );
+            sample(
+                &mut out,
+                &format!("{}_sum", name),
+                kind,
+                "",
+                metrics.seconds,
+            );
+            sample(
+                &mut out,
+                &format!("{}_count", name),
+                kind,
+                "",
+                metrics.count,
+            );
+        }
+
+        header(
+            &mut out,
+            "repo_lens_cache_hits_total",
+            "counter",
+            "Requests answered from a cache, by type.",
+        );
+        for (kind, metrics) in requests.iter() {
+            sample(
+                &mut out,
+                "repo_lens_cache_hits_total",
+                kind,
+                "",
+                metrics.cached,
+            );
+        }
+
+        header(
+            &mut out,
+            "repo_lens_cache_hit_ratio",
+            "gauge",
+            "Share of requests answered from a cache, by type.",
+        );
+        for (kind, metrics) in requests.iter() {
+            let ratio = metrics.cached as f64 / metrics.count as f64;
+            sample(&mut out, "repo_lens_cache_hit_ratio", kind, "", ratio);
+        }
+
+        header(
+            &mut out,
+            "repo_lens_git_processes_total",
+            "counter",
+            "Git processes started.",
+        );
+        let _ = writeln!(
+            out,
+            "repo_lens_git_processes_total {}",
+            rl_git::backend::processes_spawned()
+        );
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Write one sample of `name` for request type `kind`, with any further
+/// labels in `labels`, each preceded by a comma.
+fn sample(out: &mut String, name: &str, kind: &str, labels: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{}{{type=\"{}\"{}}} {}", name, kind, labels, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::response::{RepoList, ResponseMeta, ResponsePayload};
+    use rl_api::{Error, ErrorCode};
+
+    fn response(result: Result<(), Error>, cached: bool) -> Response {
+        Response {
+            id: "1".to_string(),
+            result: result.map(|()| ResponsePayload::Repos(RepoList { repos: Vec::new() })),
+            meta: Some(ResponseMeta {
+                cached,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_metrics_count_requests_by_type() {
+        let metrics = Metrics::default();
+        let ms = Duration::from_millis;
+        metrics.record("status", &response(Ok(()), false), ms(3));
+        metrics.record("status", &response(Ok(()), true), ms(40));
+        let failed = Err(Error::new(ErrorCode::RepoNotFound, "gone"));
+        metrics.record("status", &response(failed, false), ms(2000));
+        metrics.record("log", &response(Ok(()), false), ms(1));
+
+        assert_eq!(metrics.requests("status"), 3);
+        assert_eq!(metrics.requests("blame"), 0);
+        let text = metrics.render();
+        for line in [
+            "# TYPE repo_lens_requests_total counter",
+            "repo_lens_requests_total{type=\"log\"} 1",
+            "repo_lens_requests_total{type=\"status\"} 3",
+            "repo_lens_request_errors_total{type=\"status\",code=\"repo_not_found\"} 1",
+            "# TYPE repo_lens_request_duration_seconds histogram",
+            "repo_lens_request_duration_seconds_bucket{type=\"status\",le=\"0.001\"} 0",
+            "repo_lens_request_duration_seconds_bucket{type=\"status\",le=\"0.005\"} 1",
+            "repo_lens_request_duration_seconds_bucket{type=\"status\",le=\"0.05\"} 2",
+            "repo_lens_request_duration_seconds_bucket{type=\"status\",le=\"2.5\"} 3",
+            "repo_lens_request_duration_seconds_bucket{type=\"status\",le=\"+Inf\"} 3",
+            "repo_lens_request_duration_seconds_count{type=\"status\"} 3",
+            "repo_lens_cache_hits_total{type=\"log\"} 0",
+            "repo_lens_cache_hits_total{type=\"status\"} 1",
+            "# TYPE repo_lens_git_processes_total counter",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} missing from\n{}",
+                line,
+                text
+            );
+        }
+        assert!(text.contains("repo_lens_request_duration_seconds_sum{type=\"status\"} 2.04"));
+        assert!(text.contains("repo_lens_cache_hit_ratio{type=\"status\"} 0.333"));
+    }
+}
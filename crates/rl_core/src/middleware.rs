@@ -0,0 +1,114 @@
+//! Hooks embedders run around every request the engine handles.
+//!
+//! Auditing and policy checks differ from one embedder to the next, so
+//! the engine does not build them in. Instead any number of
+//! [`Middleware`] can be registered with
+//! [`RepoEngine::add_middleware`](crate::RepoEngine::add_middleware). Each
+//! sees every request before it runs and may reject it, and sees the final
+//! response once it has run, along with how long that took.
+
+use rl_api::{Error, Request, Response};
+use std::time::Duration;
+
+/// Hooks run around request handling.
+///
+/// Registered middleware run their [`before`](Middleware::before) hooks in
+/// registration order and their [`after`](Middleware::after) hooks in
+/// reverse, so the first registered wraps all the others.
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync {
+    /// Called before `request` runs.
+    ///
+    /// Returning an error rejects the request: it is answered with that
+    /// error without running, and later middleware do not see it.
+    async fn before(&self, _request: &Request) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once `request` has been answered with `response`, which took
+    /// `elapsed` including any time spent queued.
+    ///
+    /// Streaming requests are reported once, with their final response.
+    /// Requests a middleware rejected are reported only to the middleware
+    /// that ran before it.
+    async fn after(&self, _request: &Request, _response: &Response, _elapsed: Duration) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RepoEngine;
+    use rl_api::request::{PushRequest, RequestPayload, StatusRequest};
+    use rl_api::ErrorCode;
+    use std::sync::{Arc, Mutex};
+
+    /// Rejects force pushes.
+    struct DenyForcePush;
+
+    #[async_trait::async_trait]
+    impl Middleware for DenyForcePush {
+        async fn before(&self, request: &Request) -> Result<(), Error> {
+            match &request.payload {
+                RequestPayload::Push(push) if push.force => Err(Error::new(
+                    ErrorCode::InvalidRequest,
+                    "Force push is disabled",
+                )),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// Records the id and outcome of every request.
+    #[derive(Default)]
+    struct Audit {
+        log: Arc<Mutex<Vec<(String, bool)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Middleware for Audit {
+        async fn after(&self, request: &Request, response: &Response, _elapsed: Duration) {
+            let ok = response.result.is_ok();
+            self.log.lock().unwrap().push((request.id.clone(), ok));
+        }
+    }
+
+    fn request(id: &str, payload: RequestPayload) -> Request {
+        Request::new(id, payload)
+    }
+
+    #[tokio::test]
+    async fn test_middleware_can_reject_and_observe_requests() {
+        let audit = Audit::default();
+        let log = audit.log.clone();
+        let mut engine = RepoEngine::new();
+        engine.add_middleware(audit);
+        engine.add_middleware(DenyForcePush);
+
+        let push = engine
+            .handle(request(
+                "push",
+                RequestPayload::Push(PushRequest {
+                    repo_path: ".".to_string(),
+                    remote: None,
+                    refspecs: None,
+                    force: true,
+                    dry_run: false,
+                }),
+            ))
+            .await;
+        assert_eq!(push.result.unwrap_err().message, "Force push is disabled");
+
+        engine
+            .handle(request(
+                "status",
+                RequestPayload::Status(StatusRequest {
+                    repo_path: ".".to_string(),
+                }),
+            ))
+            .await;
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("push".to_string(), false), ("status".to_string(), true)]
+        );
+    }
+}
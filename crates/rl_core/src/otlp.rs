@@ -0,0 +1,206 @@
+//! Span export over OTLP/HTTP, for tracing a request in Jaeger or Tempo.
+//!
+//! Spans are handed to the OpenTelemetry SDK through `tracing-opentelemetry`:
+//! spans opened inside another share its trace and name it as their parent,
+//! and a span that logged an error is marked failed. The SDK's batch
+//! processor posts closed spans from a thread of its own, as OTLP's JSON
+//! encoding over HTTP or HTTPS, and drops spans rather than slowing requests
+//! down when the collector falls behind or can't be reached.
+
+use crate::telemetry::OtlpExporter;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{BatchConfigBuilder, BatchSpanProcessor, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Closed spans waiting to be sent; spans closing while it is full are
+/// dropped
+const QUEUE_LENGTH: usize = 4096;
+
+/// Most spans sent in one request
+const MAX_BATCH: usize = 512;
+
+/// Longest a closed span waits before it is sent
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time allowed for each request to the collector
+const COLLECTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Start exporting spans to `exporter`'s collector, or say why its endpoint
+/// can't be used.
+pub(crate) fn provider(exporter: &OtlpExporter) -> Result<SdkTracerProvider, String> {
+    let url = traces_url(&exporter.endpoint)?;
+    let spans = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpJson)
+        .with_endpoint(url)
+        .with_timeout(COLLECTOR_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Invalid OTLP endpoint {}: {}", exporter.endpoint, e))?;
+    let batches = BatchConfigBuilder::default()
+        .with_max_queue_size(QUEUE_LENGTH)
+        .with_max_export_batch_size(MAX_BATCH)
+        .with_scheduled_delay(EXPORT_INTERVAL)
+        .build();
+    let processor = BatchSpanProcessor::builder(spans)
+        .with_batch_config(batches)
+        .build();
+    Ok(SdkTracerProvider::builder()
+        .with_span_processor(processor)
+        .with_resource(
+            Resource::builder_empty()
+                .with_service_name(exporter.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// The layer turning spans into OTLP spans for `provider` to export.
+pub(crate) fn layer<S>(
+    provider: &SdkTracerProvider,
+) -> OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("repo-lens"))
+}
+
+/// The traces URL under a collector URL such as `http://localhost:4318`,
+/// adding the traces path unless it is already there.
+fn traces_url(endpoint: &str) -> Result<String, String> {
+    let invalid = |reason: &str| format!("Invalid OTLP endpoint {}: {}", endpoint, reason);
+    let rest = endpoint
+        .strip_prefix("http://")
+        .or_else(|| endpoint.strip_prefix("https://"))
+        .ok_or_else(|| invalid("only http:// and https:// URLs are supported"))?;
+    if rest.is_empty() || rest.starts_with('/') {
+        return Err(invalid("no host"));
+    }
+    let url = endpoint.trim_end_matches('/');
+    if url.ends_with("/v1/traces") {
+        Ok(url.to_string())
+    } else {
+        Ok(format!("{}/v1/traces", url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Accept one request, answer it with `status` and return its head and
+    /// body.
+    fn collect_one(listener: &TcpListener, status: &str) -> (String, Value) {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+        }
+        let length: usize = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-length")
+                    .then(|| value.trim().to_string())
+            })
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        (head, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn test_collector_urls_are_completed() {
+        let url = |endpoint| traces_url(endpoint).ok();
+        let expect = |url: &str| Some(url.to_string());
+        assert_eq!(
+            url("http://localhost:4318"),
+            expect("http://localhost:4318/v1/traces")
+        );
+        assert_eq!(url("http://tempo/"), expect("http://tempo/v1/traces"));
+        assert_eq!(
+            url("https://[::1]:4318/otlp"),
+            expect("https://[::1]:4318/otlp/v1/traces")
+        );
+        assert_eq!(url("http://h:1/v1/traces"), expect("http://h:1/v1/traces"));
+        assert_eq!(url("ftp://localhost:4318"), None);
+        assert_eq!(url("http:///v1/traces"), None);
+        assert!(provider(&OtlpExporter {
+            endpoint: "https://collector:4318".to_string(),
+            service_name: "repo-lens-test".to_string(),
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn test_nested_spans_are_exported_as_one_trace() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let exporter = OtlpExporter {
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            service_name: "repo-lens-test".to_string(),
+        };
+        let provider = provider(&exporter).unwrap();
+        let collector = std::thread::spawn(move || collect_one(&listener, "200 OK"));
+
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", request_type = "status", id = 7u64);
+            let _request = request.enter();
+            let step = tracing::info_span!("git_status_porcelain");
+            let _step = step.enter();
+            tracing::error!(error = "git exited with 128", "step failed");
+        });
+        provider.force_flush().unwrap();
+
+        let (head, body) = collector.join().unwrap();
+        assert!(head.starts_with("POST /v1/traces HTTP/1.1\r\n"));
+        assert!(head
+            .to_ascii_lowercase()
+            .contains("content-type: application/json\r\n"));
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["key"], "service.name");
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "repo-lens-test"
+        );
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        // Inner spans close first
+        let [step, request] = &spans[..] else {
+            panic!("expected two spans, got {:?}", spans);
+        };
+        assert_eq!(request["name"], "request");
+        assert_eq!(request["parentSpanId"], "");
+        let attribute = |span: &Value, key: &str| {
+            span["attributes"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|attribute| attribute["key"] == key)
+                .map(|attribute| attribute["value"].clone())
+        };
+        assert_eq!(
+            attribute(request, "request_type").unwrap()["stringValue"],
+            "status"
+        );
+        assert_eq!(step["name"], "git_status_porcelain");
+        assert_eq!(step["traceId"], request["traceId"]);
+        assert_eq!(step["parentSpanId"], request["spanId"]);
+        assert_eq!(step["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(step["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(step["status"]["code"], 2);
+        assert_eq!(request["status"]["code"], 0);
+    }
+}
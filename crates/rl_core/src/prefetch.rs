@@ -0,0 +1,247 @@
+//! Speculative prefetching of the next Log or Graph window.
+//!
+//! A client scrolling through history asks for one window after another.
+//! Once a window is served, the engine fetches the one after it at prefetch
+//! priority and keeps the result briefly, so the request for it is answered
+//! without waiting on git. An immediate request for a different window of
+//! the same history means the client jumped elsewhere, and the speculation
+//! is cancelled.
+
+use crate::CancellationToken;
+use rl_api::paging::Paging;
+use rl_api::request::{GraphRequest, LogRequest, RequestPayload};
+use rl_api::response::ResponsePayload;
+use rl_api::Error;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a prefetched window is served before it is considered stale.
+const PREFETCH_TTL: Duration = Duration::from_secs(10);
+
+/// Prefetched windows kept at most.
+const MAX_PREFETCHED: usize = 32;
+
+/// Speculative window fetches, running and finished.
+#[derive(Default)]
+pub(crate) struct Prefetcher {
+    /// Finished speculation results, by request key
+    ready: Mutex<HashMap<String, Prefetched>>,
+    /// Speculation running for each history, by request kind and repository
+    running: Mutex<HashMap<(&'static str, String), Speculation>>,
+}
+
+/// A prefetched window waiting to be asked for.
+struct Prefetched {
+    payload: ResponsePayload,
+    fetched_at: Instant,
+}
+
+/// A speculative fetch in progress.
+struct Speculation {
+    /// Key of the window being fetched
+    key: String,
+    cancellation: CancellationToken,
+}
+
+/// A window to fetch speculatively.
+pub(crate) struct Window {
+    /// Request for the window
+    pub(crate) payload: RequestPayload,
+    /// Key the result is stored under
+    key: String,
+    /// Cancels the fetch
+    pub(crate) cancellation: CancellationToken,
+}
+
+impl Prefetcher {
+    /// Take the prefetched result for `payload`, if one is fresh.
+    ///
+    /// An immediate request for another window of a history whose next
+    /// window is being fetched cancels that fetch.
+    pub(crate) fn take(
+        &self,
+        payload: &RequestPayload,
+        immediate: bool,
+    ) -> Option<ResponsePayload> {
+        let history = history(payload)?;
+        let key = serde_json::to_string(payload).ok()?;
+
+        if immediate {
+            let mut running = self.running.lock().unwrap();
+            if running
+                .get(&history)
+                .is_some_and(|speculation| speculation.key != key)
+            {
+                if let Some(speculation) = running.remove(&history) {
+                    speculation.cancellation.cancel();
+                }
+            }
+        }
+
+        let prefetched = self.ready.lock().unwrap().remove(&key)?;
+        (prefetched.fetched_at.elapsed() < PREFETCH_TTL).then_some(prefetched.payload)
+    }
+
+    /// Plan a speculative fetch of the window after `response`.
+    ///
+    /// Returns `None` when there is no next window, or it is already
+    /// prefetched or being fetched.
+    pub(crate) fn next(
+        &self,
+        payload: &RequestPayload,
+        response: &Result<ResponsePayload, Error>,
+    ) -> Option<Window> {
+        let next = next_window(payload, response.as_ref().ok()?)?;
+        let history = history(&next)?;
+        let key = serde_json::to_string(&next).ok()?;
+        if self.ready.lock().unwrap().contains_key(&key) {
+            return None;
+        }
+
+        let mut running = self.running.lock().unwrap();
+        if running
+            .get(&history)
+            .is_some_and(|speculation| speculation.key == key)
+        {
+            return None;
+        }
+        let cancellation = CancellationToken::new();
+        // A fetch for an older window of the same history is now pointless
+        if let Some(stale) = running.insert(
+            history,
+            Speculation {
+                key: key.clone(),
+                cancellation: cancellation.clone(),
+            },
+        ) {
+            stale.cancellation.cancel();
+        }
+
+        Some(Window {
+            payload: next,
+            key,
+            cancellation,
+        })
+    }
+
+    /// Record the outcome of a speculative fetch.
+    pub(crate) fn finish(&self, window: Window, result: Result<ResponsePayload, Error>) {
+        if let Some(history) = history(&window.payload) {
+            let mut running = self.running.lock().unwrap();
+            if running
+                .get(&history)
+                .is_some_and(|speculation| speculation.key == window.key)
+            {
+                running.remove(&history);
+            }
+        }
+
+        let Ok(payload) = result else {
+            return;
+        };
+        let mut ready = self.ready.lock().unwrap();
+        ready.retain(|_, prefetched| prefetched.fetched_at.elapsed() < PREFETCH_TTL);
+        if ready.len() >= MAX_PREFETCHED {
+            let oldest = ready
+                .iter()
+                .min_by_key(|(_, prefetched)| prefetched.fetched_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                ready.remove(&oldest);
+            }
+        }
+        ready.insert(
+            window.key,
+            Prefetched {
+                payload,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+}
+
+/// The history a windowed request pages through; `None` for other requests.
+fn history(payload: &RequestPayload) -> Option<(&'static str, String)> {
+    match payload {
+        RequestPayload::Log(_) | RequestPayload::Graph(_) => {
+            Some((payload.kind(), payload.repo_path().to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Request for the window after the one `response` answered.
+fn next_window(payload: &RequestPayload, response: &ResponsePayload) -> Option<RequestPayload> {
+    match (payload, response) {
+        (RequestPayload::Log(req), ResponsePayload::Log(page)) if page.has_more => {
+            Some(RequestPayload::Log(LogRequest {
+                repo_path: req.repo_path.clone(),
+                paging: Paging {
+                    page_size: req.paging.page_size.clone(),
+                    cursor: page.next_cursor.clone()?,
+                },
+                revision_range: req.revision_range.clone(),
+            }))
+        }
+        (RequestPayload::Graph(req), ResponsePayload::Graph(window)) if window.has_more => {
+            Some(RequestPayload::Graph(GraphRequest {
+                repo_path: req.repo_path.clone(),
+                window_size: req.window_size.clone(),
+                cursor: window.next_cursor.clone()?,
+                revision_range: req.revision_range.clone(),
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::response::CommitListPage;
+    use rl_api::Cursor;
+
+    fn log(cursor: &str) -> RequestPayload {
+        RequestPayload::Log(LogRequest {
+            repo_path: "/repo".to_string(),
+            paging: Paging {
+                page_size: 50.try_into().unwrap(),
+                cursor: Cursor::from(cursor.to_string()),
+            },
+            revision_range: None,
+        })
+    }
+
+    fn page(next_cursor: &str) -> Result<ResponsePayload, Error> {
+        Ok(ResponsePayload::Log(CommitListPage {
+            commits: Vec::new(),
+            next_cursor: Some(Cursor::from(next_cursor.to_string())),
+            has_more: true,
+        }))
+    }
+
+    #[test]
+    fn test_next_window_is_prefetched_once_and_served_once() {
+        let prefetcher = Prefetcher::default();
+        let window = prefetcher.next(&log(""), &page("c1")).unwrap();
+        assert!(prefetcher.next(&log(""), &page("c1")).is_none());
+
+        prefetcher.finish(window, page("c2"));
+        assert!(prefetcher.take(&log("c1"), true).is_some());
+        assert!(prefetcher.take(&log("c1"), true).is_none());
+    }
+
+    #[test]
+    fn test_jumping_to_another_window_cancels_speculation() {
+        let prefetcher = Prefetcher::default();
+        let window = prefetcher.next(&log(""), &page("c1")).unwrap();
+
+        // Prefetch requests never cancel
+        assert!(prefetcher.take(&log("c9"), false).is_none());
+        assert!(!window.cancellation.is_cancelled());
+
+        assert!(prefetcher.take(&log("c9"), true).is_none());
+        assert!(window.cancellation.is_cancelled());
+    }
+}
@@ -0,0 +1,368 @@
+//! Request execution through the [`Scheduler`].
+//!
+//! Every request must hold a permit from the engine-wide semaphore while it
+//! runs, and, when a limit is configured for its repository, one from that
+//! repository's semaphore too, so a burst of UI requests cannot start an
+//! unbounded number of git subprocesses. Requests without permits wait in
+//! the scheduler's priority queues, so an interactive query overtakes queued
+//! prefetch and maintenance work instead of queueing behind it. A queued
+//! prefetch is dropped when the same session asks for the same kind of data
+//! from the same repository again: the client has moved on and only the
+//! newer request is worth running.
+
+use crate::events::canonical_repo_path;
+use crate::{CancellationToken, PendingQuery, Priority, Scheduler};
+use rl_api::Request;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+/// Priority queue of requests waiting for permission to run.
+pub(crate) struct QueryQueue {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// Requests waiting and the per-repository semaphores
+    state: Mutex<State>,
+    /// Permits for requests running across the whole engine
+    engine: Arc<Semaphore>,
+    /// Requests allowed to run at once against one repository, if limited
+    per_repo: Option<usize>,
+    /// Limits replacing `per_repo` for some repositories, by canonical path
+    repo_limits: HashMap<PathBuf, usize>,
+}
+
+struct State {
+    waiting: Scheduler,
+    /// Semaphores of repositories with requests running, by canonical path
+    repos: HashMap<PathBuf, RepoSlots>,
+}
+
+/// Turns for one repository.
+struct RepoSlots {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+}
+
+impl QueryQueue {
+    /// Create a queue that runs up to `workers` requests at once, and up to
+    /// `per_repo` against any one repository when set. Repositories listed
+    /// in `repo_limits` get their own limit instead.
+    pub(crate) fn new(
+        workers: usize,
+        per_repo: Option<usize>,
+        repo_limits: HashMap<PathBuf, usize>,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    waiting: Scheduler::new(),
+                    repos: HashMap::new(),
+                }),
+                engine: Arc::new(Semaphore::new(workers.max(1))),
+                per_repo: per_repo.map(|limit| limit.max(1)),
+                repo_limits: repo_limits
+                    .into_iter()
+                    .map(|(path, limit)| (canonical_repo_path(path), limit.max(1)))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Wait until `request` may run.
+    ///
+    /// Returns `None` if the request was superseded while it waited. The
+    /// returned turn must be held for as long as the request runs.
+    pub(crate) async fn wait_turn(
+        &self,
+        request: &Request,
+        session: u64,
+        cancellation: &CancellationToken,
+    ) -> Option<Turn> {
+        let priority = Priority::of(request);
+        let repo = canonical_repo_path(request.payload.repo_path());
+        let repo_limit = self.shared.repo_limit(&repo);
+        let (start, started) = oneshot::channel();
+
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            // Dropping a superseded query wakes its waiter with an error
+            state.waiting.remove_where(|queued| {
+                queued.priority == Priority::UiPrefetch
+                    && queued.session == session
+                    && queued.payload.kind() == request.payload.kind()
+                    && queued.repo == repo
+            });
+            state.waiting.schedule(
+                PendingQuery {
+                    id: request.id.clone(),
+                    payload: request.payload.clone(),
+                    cancellation: cancellation.clone(),
+                    session,
+                    priority,
+                    repo,
+                    repo_limit,
+                    start,
+                },
+                priority,
+            );
+            self.shared.start_next(&mut state);
+        }
+
+        started.await.ok()
+    }
+}
+
+impl Shared {
+    /// Requests allowed to run at once against the repository at canonical
+    /// path `repo`, if limited.
+    fn repo_limit(&self, repo: &Path) -> Option<usize> {
+        self.repo_limits.get(repo).copied().or(self.per_repo)
+    }
+
+    /// Hand out turns while permits are free and requests are waiting.
+    ///
+    /// A request whose repository is at its limit stays queued without
+    /// holding up requests for other repositories.
+    fn start_next(self: &Arc<Self>, state: &mut State) {
+        let State { waiting, repos } = state;
+        // Their requesters have already answered and stopped waiting
+        waiting.remove_where(|query| query.cancellation.is_cancelled());
+        repos.retain(|_, repo| repo.semaphore.available_permits() < repo.limit);
+
+        loop {
+            let Ok(engine_permit) = self.engine.clone().try_acquire_owned() else {
+                return;
+            };
+            let query = waiting.next_query_where(|query| {
+                query.repo_limit.is_none()
+                    || repos
+                        .get(&query.repo)
+                        .is_none_or(|repo| repo.semaphore.available_permits() > 0)
+            });
+            let Some(query) = query else {
+                return;
+            };
+            let repo_permit = query.repo_limit.map(|limit| {
+                repos
+                    .entry(query.repo.clone())
+                    .or_insert_with(|| RepoSlots {
+                        semaphore: Arc::new(Semaphore::new(limit)),
+                        limit,
+                    })
+                    .semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .expect("repository was checked for a free permit")
+            });
+
+            let turn = Turn {
+                shared: Some(self.clone()),
+                engine_permit: Some(engine_permit),
+                repo_permit,
+            };
+            // The requester gave up while queued; its permits go straight
+            // back without waking anyone, as this loop is already doing so
+            if let Err(mut turn) = query.start.send(turn) {
+                turn.shared = None;
+            }
+        }
+    }
+}
+
+/// Permission to run one request; dropping it returns the permits.
+pub(crate) struct Turn {
+    /// Queue to wake once the permits are back; `None` once disarmed
+    shared: Option<Arc<Shared>>,
+    engine_permit: Option<OwnedSemaphorePermit>,
+    repo_permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        // Release before waking the queue so the next request can take them
+        self.engine_permit.take();
+        self.repo_permit.take();
+        if let Some(shared) = self.shared.take() {
+            let mut state = shared.state.lock().unwrap();
+            shared.start_next(&mut state);
+        }
+    }
+}
+
+impl fmt::Debug for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Turn")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::request::{LogRequest, RequestPayload, StatusRequest};
+    use rl_api::{Paging, RequestPriority};
+
+    fn request(id: &str, payload: RequestPayload, priority: RequestPriority) -> Request {
+        Request {
+            priority: Some(priority),
+            ..Request::new(id, payload)
+        }
+    }
+
+    fn status(id: &str, priority: RequestPriority) -> Request {
+        status_of("/repo", id, priority)
+    }
+
+    fn status_of(repo_path: &str, id: &str, priority: RequestPriority) -> Request {
+        request(
+            id,
+            RequestPayload::Status(StatusRequest {
+                repo_path: repo_path.to_string(),
+            }),
+            priority,
+        )
+    }
+
+    fn log(id: &str, priority: RequestPriority) -> Request {
+        request(
+            id,
+            RequestPayload::Log(LogRequest {
+                repo_path: "/repo".to_string(),
+                paging: Paging {
+                    page_size: 50.try_into().unwrap(),
+                    cursor: rl_api::Cursor::initial(),
+                },
+                revision_range: None,
+            }),
+            priority,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_immediate_work_overtakes_prefetch_and_stale_prefetch_is_dropped() {
+        let queue = Arc::new(QueryQueue::new(1, None, HashMap::new()));
+        let busy = queue
+            .wait_turn(
+                &status("busy", RequestPriority::UiImmediate),
+                1,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let (order_tx, mut order) = tokio::sync::mpsc::unbounded_channel();
+        let spawn = |request: Request, session: u64| {
+            let queue = queue.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let turn = queue
+                    .wait_turn(&request, session, &CancellationToken::new())
+                    .await;
+                let _ = order_tx.send((request.id, turn.is_some()));
+            })
+        };
+
+        let stale = spawn(log("stale", RequestPriority::Prefetch), 1);
+        tokio::task::yield_now().await;
+        let other_session = spawn(log("other", RequestPriority::Prefetch), 2);
+        tokio::task::yield_now().await;
+        let fresh = spawn(log("fresh", RequestPriority::Prefetch), 1);
+        tokio::task::yield_now().await;
+        let urgent = spawn(status("urgent", RequestPriority::UiImmediate), 1);
+        tokio::task::yield_now().await;
+
+        // Superseded before it ever ran
+        stale.await.unwrap();
+        assert_eq!(order.recv().await.unwrap(), ("stale".to_string(), false));
+
+        drop(busy);
+        for task in [urgent, other_session, fresh] {
+            task.await.unwrap();
+        }
+        let ran: Vec<_> = std::iter::from_fn(|| order.try_recv().ok())
+            .map(|(id, ran)| {
+                assert!(ran);
+                id
+            })
+            .collect();
+        assert_eq!(ran, vec!["urgent", "other", "fresh"]);
+    }
+
+    #[tokio::test]
+    async fn test_busy_repository_does_not_hold_up_others() {
+        let queue = Arc::new(QueryQueue::new(2, Some(1), HashMap::new()));
+        let busy = queue
+            .wait_turn(
+                &status_of("/a", "busy", RequestPriority::UiImmediate),
+                1,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let request = status_of("/a", "same-repo", RequestPriority::UiImmediate);
+                queue
+                    .wait_turn(&request, 1, &CancellationToken::new())
+                    .await
+                    .is_some()
+            })
+        };
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        // The engine still has a permit for another repository
+        let other = queue
+            .wait_turn(
+                &status_of("/b", "other", RequestPriority::Maintenance),
+                1,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        drop(other);
+        assert!(!waiter.is_finished());
+
+        drop(busy);
+        assert!(waiter.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_limit_holds_however_the_repository_is_spelled() {
+        let dir = std::env::temp_dir().join(format!("rl_core_queue_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let plain = dir.to_string_lossy().to_string();
+        let roundabout = dir.join("sub").join("..").to_string_lossy().to_string();
+
+        let queue = Arc::new(QueryQueue::new(2, Some(1), HashMap::new()));
+        let busy = queue
+            .wait_turn(
+                &status_of(&plain, "busy", RequestPriority::UiImmediate),
+                1,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let request = status_of(&roundabout, "same-repo", RequestPriority::UiImmediate);
+                queue
+                    .wait_turn(&request, 1, &CancellationToken::new())
+                    .await
+                    .is_some()
+            })
+        };
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        drop(busy);
+        assert!(waiter.await.unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
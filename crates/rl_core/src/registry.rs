@@ -0,0 +1,297 @@
+//! Engine-wide registry of open repositories.
+//!
+//! Opening a repository spawns git just to verify the path. The registry
+//! keeps one handle per repository, keyed by its canonical path, and shares
+//! it between every request and session that names the repo, however the
+//! path is spelled. Repositories nobody has used for a while are closed, as
+//! is the least recently used one once too many are open. Repositories a
+//! client is watching are kept open however long they sit idle, until the
+//! [`Watch`] recording it is dropped. Opening and closing are announced on
+//! the engine's event bus.
+
+use crate::events::{canonical_repo_path, EventBus};
+use rl_api::event::{RepoClosedEvent, RepoOpenedEvent};
+use rl_api::Event;
+use rl_git::{GitBackend, RepoHandle};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Open repositories shared by the whole engine.
+pub(crate) struct RepoRegistry {
+    /// Backend used to open repositories on first use
+    backend: Box<dyn GitBackend>,
+    /// Where opening and closing are announced
+    events: EventBus,
+    /// Open repositories by canonical path
+    repos: Mutex<HashMap<PathBuf, OpenRepo>>,
+    /// When repositories are closed
+    limits: std::sync::Mutex<Limits>,
+    /// Active watches by canonical path
+    watchers: Arc<std::sync::Mutex<HashMap<PathBuf, usize>>>,
+}
+
+/// A client watching a repository, which keeps it open until this is
+/// dropped.
+pub(crate) struct Watch {
+    /// The registry's watch counts
+    watchers: Arc<std::sync::Mutex<HashMap<PathBuf, usize>>>,
+    /// Canonical path of the watched repository
+    path: PathBuf,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(count) = watchers.get_mut(&self.path) {
+            *count -= 1;
+            if *count == 0 {
+                watchers.remove(&self.path);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    /// Repositories kept open at most
+    capacity: usize,
+    /// Unused repositories are closed after this long
+    idle_timeout: Duration,
+}
+
+/// A repository held open by the registry.
+struct OpenRepo {
+    /// Shared handle
+    handle: Arc<dyn RepoHandle>,
+    /// When a request last asked for the repo
+    last_used: Instant,
+}
+
+/// A repository as reported by [`RepoRegistry::list`].
+pub(crate) struct RepoState {
+    /// Canonical path
+    pub(crate) path: PathBuf,
+    /// Whether a client is watching the repository
+    pub(crate) watched: bool,
+}
+
+impl RepoRegistry {
+    /// Create a registry that keeps up to `capacity` repositories open, each
+    /// for at most `idle_timeout` after its last use.
+    pub(crate) fn new(
+        backend: Box<dyn GitBackend>,
+        events: EventBus,
+        capacity: usize,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self {
+            backend,
+            events,
+            repos: Mutex::new(HashMap::new()),
+            limits: std::sync::Mutex::new(Limits {
+                capacity: capacity.max(1),
+                idle_timeout,
+            }),
+            watchers: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Change how many repositories are kept open and for how long. Takes
+    /// effect the next time a repository is opened.
+    pub(crate) fn set_limits(&self, capacity: usize, idle_timeout: Duration) {
+        *self.limits.lock().unwrap() = Limits {
+            capacity: capacity.max(1),
+            idle_timeout,
+        };
+    }
+
+    /// Name of the backend repositories are opened with.
+    pub(crate) fn backend_kind(&self) -> &'static str {
+        self.backend.kind()
+    }
+
+    /// What the git installation behind the backend can do.
+    pub(crate) async fn capabilities(&self) -> rl_git::GitCapabilities {
+        self.backend.capabilities().await
+    }
+
+    /// Return the shared handle for the repository at `path`, opening it on
+    /// first use.
+    pub(crate) async fn open(&self, path: &Path) -> rl_git::Result<Arc<dyn RepoHandle>> {
+        // Paths that do not resolve are left to the backend to reject
+        let key = canonical_repo_path(path);
+
+        let limits = *self.limits.lock().unwrap();
+        let mut repos = self.repos.lock().await;
+        let now = Instant::now();
+        let idle: Vec<PathBuf> = repos
+            .iter()
+            .filter(|(path, repo)| {
+                now.duration_since(repo.last_used) >= limits.idle_timeout && !self.is_watched(path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in idle {
+            self.remove(&mut repos, &path);
+        }
+
+        if let Some(repo) = repos.get_mut(&key) {
+            repo.last_used = now;
+            return Ok(repo.handle.clone());
+        }
+
+        let handle: Arc<dyn RepoHandle> = Arc::from(self.backend.open_repo(path).await?);
+        // Several may have to go after the capacity was lowered
+        while repos.len() >= limits.capacity {
+            // Watched repositories go last, and only if all of them are
+            let oldest = repos
+                .iter()
+                .min_by_key(|(path, repo)| (self.is_watched(path), repo.last_used))
+                .map(|(path, _)| path.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            self.remove(&mut repos, &oldest);
+        }
+        self.events.publish(Event::RepoOpened(RepoOpenedEvent {
+            repo_path: key.display().to_string(),
+        }));
+        repos.insert(
+            key,
+            OpenRepo {
+                handle: handle.clone(),
+                last_used: now,
+            },
+        );
+        Ok(handle)
+    }
+
+    /// Stop managing the repository at `path`. Returns whether it was open.
+    ///
+    /// Requests already running keep its handle until they finish.
+    pub(crate) async fn close(&self, path: &Path) -> bool {
+        let key = canonical_repo_path(path);
+        let mut repos = self.repos.lock().await;
+        self.remove(&mut repos, &key)
+    }
+
+    /// The open repositories, sorted by path.
+    pub(crate) async fn list(&self) -> Vec<RepoState> {
+        let mut paths: Vec<PathBuf> = self.repos.lock().await.keys().cloned().collect();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| RepoState {
+                watched: self.is_watched(&path),
+                path,
+            })
+            .collect()
+    }
+
+    /// Record a client watching the repository at `path` until the
+    /// returned [`Watch`] is dropped.
+    pub(crate) fn watch(&self, path: &Path) -> Watch {
+        let path = canonical_repo_path(path);
+        *self
+            .watchers
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default() += 1;
+        Watch {
+            watchers: self.watchers.clone(),
+            path,
+        }
+    }
+
+    /// Whether a client is watching the repository at canonical `path`.
+    pub(crate) fn is_watched(&self, path: &Path) -> bool {
+        self.watchers.lock().unwrap().contains_key(path)
+    }
+
+    fn remove(&self, repos: &mut HashMap<PathBuf, OpenRepo>, path: &Path) -> bool {
+        let removed = repos.remove(path).is_some();
+        if removed {
+            self.events.publish(Event::RepoClosed(RepoClosedEvent {
+                repo_path: path.display().to_string(),
+            }));
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Backend that counts how often a repo is opened.
+    #[derive(Default)]
+    struct CountingBackend {
+        opens: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl GitBackend for CountingBackend {
+        fn kind(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn open_repo(&self, _path: &Path) -> rl_git::Result<Box<dyn RepoHandle>> {
+            self.opens.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(rl_git::StubRepoHandle))
+        }
+
+        async fn is_repo(&self, _path: &Path) -> rl_git::Result<bool> {
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_repo_is_closed_when_full() {
+        let backend = CountingBackend::default();
+        let opens = backend.opens.clone();
+        let registry = RepoRegistry::new(
+            Box::new(backend),
+            EventBus::new(),
+            2,
+            Duration::from_secs(60),
+        );
+
+        registry.open(Path::new("/a")).await.unwrap();
+        registry.open(Path::new("/b")).await.unwrap();
+        registry.open(Path::new("/a")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+
+        // "/b" is the least recently used, so it makes room for "/c"
+        registry.open(Path::new("/c")).await.unwrap();
+        registry.open(Path::new("/a")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 3);
+        registry.open(Path::new("/b")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_idle_repos_are_closed() {
+        let backend = CountingBackend::default();
+        let opens = backend.opens.clone();
+        let registry = RepoRegistry::new(Box::new(backend), EventBus::new(), 8, Duration::ZERO);
+
+        registry.open(Path::new("/a")).await.unwrap();
+        registry.open(Path::new("/a")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+
+        // Watched repos stay open while idle
+        let watch = registry.watch(Path::new("/a"));
+        registry.open(Path::new("/a")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+        assert!(registry.list().await[0].watched);
+
+        drop(watch);
+        registry.open(Path::new("/a")).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,116 @@
+//! Results of queries over fixed history, kept for repeats.
+//!
+//! A `log` page read from a pinned range and a `diff_summary` between two
+//! commits depend only on commit ids, which always name the same content,
+//! so the engine keeps them and answers a repeat without running git. An
+//! entry never goes stale, so nothing invalidates it: the least recently
+//! used are evicted once the serialized size of the entries passes the
+//! cache policy's `max_total_bytes`.
+
+use rl_api::response::ResponsePayload;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Kept results, by a key naming the query and the commits it read.
+#[derive(Default)]
+pub(crate) struct ResultCache {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    /// Serialized size of every entry
+    bytes: u64,
+    /// Advanced on each use, to find the least recently used entry
+    clock: u64,
+}
+
+struct Entry {
+    payload: ResponsePayload,
+    bytes: u64,
+    last_used: u64,
+}
+
+impl ResultCache {
+    /// The result kept under `key`, if any.
+    pub(crate) fn get(&self, key: &str) -> Option<ResponsePayload> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        let entry = entries.by_key.get_mut(key)?;
+        entry.last_used = clock;
+        Some(entry.payload.clone())
+    }
+
+    /// Keep `payload` under `key`, evicting the least recently used results
+    /// to stay within `max_bytes`. A result larger than that is not kept.
+    pub(crate) fn put(&self, key: String, payload: &ResponsePayload, max_bytes: u64) {
+        let bytes = serde_json::to_vec(payload).map_or(u64::MAX, |json| json.len() as u64);
+        if bytes > max_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let entry = Entry {
+            payload: payload.clone(),
+            bytes,
+            last_used: entries.clock,
+        };
+        if let Some(replaced) = entries.by_key.insert(key, entry) {
+            entries.bytes -= replaced.bytes;
+        }
+        entries.bytes += bytes;
+        while entries.bytes > max_bytes {
+            let oldest = entries
+                .by_key
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else { break };
+            if let Some(evicted) = entries.by_key.remove(&oldest) {
+                entries.bytes -= evicted.bytes;
+            }
+        }
+    }
+
+    /// Drop every result, e.g. once caching is turned off.
+    pub(crate) fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.by_key.clear();
+        entries.bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::response::OperationResult;
+
+    fn payload(message: &str) -> ResponsePayload {
+        ResponsePayload::OperationResult(OperationResult {
+            success: true,
+            message: Some(message.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_least_recently_used_results_are_evicted_to_fit() {
+        let size = serde_json::to_vec(&payload("a")).unwrap().len() as u64;
+        let cache = ResultCache::default();
+        cache.put("a".to_string(), &payload("a"), size * 2);
+        cache.put("b".to_string(), &payload("b"), size * 2);
+        assert!(cache.get("a").is_some());
+
+        // `b` was used least recently
+        cache.put("c".to_string(), &payload("c"), size * 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+
+        cache.put("d".to_string(), &payload("d"), size - 1);
+        assert!(cache.get("d").is_none());
+        cache.clear();
+        assert!(cache.get("a").is_none());
+    }
+}
@@ -0,0 +1,98 @@
+//! Confining requests to configured root directories.
+//!
+//! A daemon listening on a socket should not answer for any path a client
+//! names. When [`EngineConfig::allowed_roots`](crate::EngineConfig) is set,
+//! a request's `repo_path` is canonicalized, resolving `..` and symlinks,
+//! and the request is rejected with `InvalidRequest` unless the result lies
+//! within one of the roots. Paths that cannot be resolved are rejected too,
+//! as where they lead cannot be known.
+
+use rl_api::{Error, ErrorCode};
+use std::path::PathBuf;
+
+/// Directories requests may name repositories in.
+#[derive(Debug, Default)]
+pub(crate) struct Sandbox {
+    /// Canonical roots; empty allows any path
+    roots: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    /// Confine requests to `roots`, or to nothing if empty.
+    ///
+    /// Roots that cannot be resolved now are kept as written, so they only
+    /// match once they exist at that exact path.
+    pub(crate) fn new(roots: &[PathBuf]) -> Self {
+        Self {
+            roots: roots
+                .iter()
+                .map(|root| std::fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+                .collect(),
+        }
+    }
+
+    /// Fail with `InvalidRequest` unless `repo_path` lies within a root.
+    ///
+    /// Requests that name no repository are always allowed.
+    pub(crate) fn check(&self, repo_path: &str) -> Result<(), Error> {
+        if self.roots.is_empty() || repo_path.is_empty() {
+            return Ok(());
+        }
+
+        let resolved = std::fs::canonicalize(repo_path).map_err(|error| {
+            self.rejected(format!(
+                "Repository path {} cannot be resolved: {}",
+                repo_path, error
+            ))
+        })?;
+        if self.roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(())
+        } else {
+            Err(self.rejected(format!(
+                "Repository path {} is outside the allowed roots",
+                repo_path
+            )))
+        }
+    }
+
+    fn rejected(&self, message: String) -> Error {
+        Error::new(ErrorCode::InvalidRequest, message)
+            .with_remediation("Use a repository within the server's allowed roots")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_paths_outside_roots_are_rejected_after_resolving_symlinks() {
+        let base = std::env::temp_dir().join(format!("rl-sandbox-{}", std::process::id()));
+        let allowed = base.join("allowed");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(allowed.join("repo")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let link = allowed.join("escape");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+
+        let sandbox = Sandbox::new(std::slice::from_ref(&allowed));
+        assert!(sandbox
+            .check(allowed.join("repo").to_str().unwrap())
+            .is_ok());
+        assert!(sandbox.check("").is_ok());
+
+        for path in [
+            link.clone(),
+            allowed.join("..").join("outside"),
+            allowed.join("missing"),
+        ] {
+            let error = sandbox.check(path.to_str().unwrap()).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidRequest);
+        }
+        assert!(Sandbox::default().check(outside.to_str().unwrap()).is_ok());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
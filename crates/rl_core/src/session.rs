@@ -0,0 +1,181 @@
+//! Client sessions that keep repositories open between requests.
+//!
+//! Opening a repository spawns git just to verify the path, which is a
+//! noticeable share of small queries. A [`Session`] records each repo a
+//! client touches and asks the engine's registry for its handle on every
+//! use, so follow-up requests reuse the registry's open handle, and sessions
+//! naming the same repo share one. Once the registry closes a repo, whether
+//! it sat idle or a client sent `CloseRepo`, the next use opens it afresh
+//! instead of serving the closed handle.
+//!
+//! Watches belong to sessions too: a repository watched from a session stays
+//! open, however idle, until the session stops watching it or is dropped.
+
+use crate::events::canonical_repo_path;
+use crate::registry::{RepoRegistry, Watch};
+use rl_git::RepoHandle;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Repositories open in one client, by the path the client used to name
+/// each repo.
+pub struct Session {
+    /// Process-unique id, used to tell one client's queued work from another's
+    id: u64,
+    /// Paths of the repos opened from this session
+    repos: Mutex<HashSet<PathBuf>>,
+    /// Repositories watched from this session, by canonical path
+    watches: std::sync::Mutex<HashMap<PathBuf, Watch>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            repos: Mutex::new(HashSet::new()),
+            watches: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Session {
+    /// Create an empty session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process-unique session id.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Return the handle for `path` from `registry`, recording the repo as
+    /// open in this session.
+    pub(crate) async fn open(
+        &self,
+        registry: &RepoRegistry,
+        path: &Path,
+    ) -> rl_git::Result<Arc<dyn RepoHandle>> {
+        // Resolved every time, as the registry may have closed it since
+        let handle = registry.open(path).await?;
+        self.repos.lock().await.insert(path.to_path_buf());
+        Ok(handle)
+    }
+
+    /// Forget the repo at `path`. Returns whether it was open.
+    pub async fn close(&self, path: &Path) -> bool {
+        self.repos.lock().await.remove(path)
+    }
+
+    /// Watch the repository at `path` from this session. Watching it again
+    /// changes nothing.
+    pub(crate) fn watch(&self, registry: &RepoRegistry, path: &Path) {
+        self.watches
+            .lock()
+            .unwrap()
+            .entry(canonical_repo_path(path))
+            .or_insert_with(|| registry.watch(path));
+    }
+
+    /// Stop watching the repository at `path`. Returns whether it was
+    /// watched.
+    pub fn unwatch(&self, path: &Path) -> bool {
+        let watch = self
+            .watches
+            .lock()
+            .unwrap()
+            .remove(&canonical_repo_path(path));
+        watch.is_some()
+    }
+
+    /// Paths of the repositories currently open in this session, sorted.
+    pub async fn open_repos(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = self.repos.lock().await.iter().cloned().collect();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_git::GitBackend;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    /// Backend that counts how often a repo is opened.
+    #[derive(Default)]
+    struct CountingBackend {
+        opens: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl GitBackend for CountingBackend {
+        fn kind(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn open_repo(&self, _path: &Path) -> rl_git::Result<Box<dyn RepoHandle>> {
+            self.opens.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(rl_git::StubRepoHandle))
+        }
+
+        async fn is_repo(&self, _path: &Path) -> rl_git::Result<bool> {
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_reuses_handles_until_closed() {
+        let backend = CountingBackend::default();
+        let opens = backend.opens.clone();
+        let registry = RepoRegistry::new(
+            Box::new(backend),
+            crate::events::EventBus::new(),
+            1,
+            Duration::from_secs(60),
+        );
+        let session = Session::new();
+        let repo = Path::new("/repo");
+
+        session.open(&registry, repo).await.unwrap();
+        session.open(&registry, repo).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 1);
+        assert_eq!(session.open_repos().await, vec![repo.to_path_buf()]);
+
+        assert!(session.close(repo).await);
+        assert!(!session.close(repo).await);
+        assert!(session.open_repos().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_session_reopens_repos_the_registry_closed() {
+        let backend = CountingBackend::default();
+        let opens = backend.opens.clone();
+        let registry = RepoRegistry::new(
+            Box::new(backend),
+            crate::events::EventBus::new(),
+            1,
+            Duration::from_secs(60),
+        );
+        let session = Session::new();
+        let repo = Path::new("/repo");
+
+        let first = session.open(&registry, repo).await.unwrap();
+        // Another client closed it
+        assert!(registry.close(repo).await);
+        let second = session.open(&registry, repo).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        // Evicted to make room for another repo
+        registry.open(Path::new("/other")).await.unwrap();
+        let third = session.open(&registry, repo).await.unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 4);
+        assert!(!Arc::ptr_eq(&second, &third));
+    }
+}
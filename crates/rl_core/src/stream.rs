@@ -0,0 +1,506 @@
+//! Streaming response support.
+//!
+//! Streaming requests (DiffContent, Blame, and the progress of Fetch and
+//! Push) yield ordered chunks sharing the request id. Every chunk but the
+//! last is pushed through a [`ChunkSink`]; the handler returns the final
+//! chunk, marked `is_final`, as its ordinary result. Rebase streams progress
+//! chunks the same way but ends with its `RebaseResult`.
+//!
+//! Chunks waiting in the sink's channel for the client to read them hold
+//! their size against the engine's [`MemoryBudget`]; a stream that backs up
+//! past the budget ends with an `Overloaded` error.
+
+use crate::budget::{self, MemoryBudget, Reservation};
+use crate::CancellationToken;
+use rl_api::paging::StreamingChunk;
+use rl_api::response::{
+    BlameLine, DiffChunk, DiffHunk, DiffLine, DiffLineType, ProgressUpdate, Range, ResponsePayload,
+};
+use rl_api::{Error, Response};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+/// Blame lines per streamed chunk.
+pub const BLAME_CHUNK_LINES: usize = 500;
+
+/// Destination for the intermediate chunks of a streaming response.
+pub struct ChunkSink {
+    /// Request id stamped on every chunk
+    id: String,
+    /// Receiver of chunks; `None` drops intermediate chunks
+    tx: Option<mpsc::Sender<Response>>,
+    /// Cancellation of the request the chunks belong to
+    cancellation: CancellationToken,
+    /// Budget chunks are sent against
+    budget: MemoryBudget,
+    /// Reservations of chunks sent but possibly still buffered, oldest first
+    backlog: Mutex<VecDeque<Reservation>>,
+}
+
+impl ChunkSink {
+    /// Sink that forwards chunks to `tx`.
+    pub(crate) fn new(
+        id: String,
+        tx: mpsc::Sender<Response>,
+        cancellation: CancellationToken,
+        budget: MemoryBudget,
+    ) -> Self {
+        Self {
+            id,
+            tx: Some(tx),
+            cancellation,
+            budget,
+            backlog: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sink that drops intermediate chunks.
+    pub(crate) fn discard(id: String, cancellation: CancellationToken) -> Self {
+        Self {
+            id,
+            tx: None,
+            cancellation,
+            budget: MemoryBudget::default(),
+            backlog: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Send an intermediate chunk.
+    ///
+    /// Fails with `OperationCanceled` once the request is cancelled or the
+    /// receiver is gone, so handlers stop producing output nobody will read,
+    /// and with `Overloaded` when the memory budget has no room for the
+    /// chunk.
+    pub(crate) async fn send(&self, payload: ResponsePayload) -> Result<(), Error> {
+        self.cancellation.check()?;
+        let Some(tx) = &self.tx else {
+            return Ok(());
+        };
+        let response = Response {
+            id: self.id.clone(),
+            result: Ok(payload),
+            meta: None,
+        };
+        let reservation = self.budget.try_reserve(budget::serialized_len(&response))?;
+        tx.send(response).await.map_err(|_| {
+            Error::new(
+                rl_api::ErrorCode::OperationCanceled,
+                "Client stopped reading the response stream",
+            )
+        })?;
+
+        // Chunks the receiver has taken no longer count
+        let buffered = tx.max_capacity() - tx.capacity();
+        let mut backlog = self.backlog.lock().unwrap();
+        backlog.push_back(reservation);
+        while backlog.len() > buffered {
+            backlog.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Run `operation`, streaming the git progress lines it sends to `lines`
+    /// as `Progress` chunks until it finishes.
+    ///
+    /// Redraws that repeat the previous update are skipped. Returns the
+    /// operation's output and the sequence number for the next chunk.
+    pub(crate) async fn forward_progress<T>(
+        &self,
+        operation: impl Future<Output = Result<T, Error>>,
+        mut lines: mpsc::UnboundedReceiver<String>,
+    ) -> Result<(T, u64), Error> {
+        tokio::pin!(operation);
+        let mut sequence = 0;
+        let mut last: Option<(String, u8)> = None;
+        loop {
+            tokio::select! {
+                output = &mut operation => return Ok((output?, sequence)),
+                Some(line) = lines.recv() => {
+                    let Some(update) = parse_progress_line(&line) else {
+                        continue;
+                    };
+                    let key = (update.stage.clone(), update.progress);
+                    if last.as_ref() == Some(&key) {
+                        continue;
+                    }
+                    last = Some(key);
+                    self.send(ResponsePayload::Progress(StreamingChunk {
+                        sequence,
+                        is_final: false,
+                        data: update,
+                    }))
+                    .await?;
+                    sequence += 1;
+                }
+            }
+        }
+    }
+
+    /// Stream `items` in order and return the last one as the final chunk.
+    ///
+    /// An empty list yields a single final chunk holding `empty`.
+    pub(crate) async fn stream_all<T>(
+        &self,
+        items: Vec<T>,
+        empty: T,
+        wrap: fn(StreamingChunk<T>) -> ResponsePayload,
+    ) -> Result<ResponsePayload, Error> {
+        let count = items.len();
+        let mut last = empty;
+        for (sequence, data) in items.into_iter().enumerate() {
+            if sequence + 1 == count {
+                last = data;
+                break;
+            }
+            self.send(wrap(StreamingChunk {
+                sequence: sequence as u64,
+                is_final: false,
+                data,
+            }))
+            .await?;
+        }
+
+        Ok(wrap(StreamingChunk {
+            sequence: count.saturating_sub(1) as u64,
+            is_final: true,
+            data: last,
+        }))
+    }
+}
+
+/// Parse `git diff --patch` output into one chunk per file.
+///
+/// Parsing stops once `max_bytes` of patch text has been consumed.
+pub(crate) fn parse_diff_patch(patch: &str, max_bytes: u64) -> Vec<DiffChunk> {
+    let mut files: Vec<DiffChunk> = Vec::new();
+    let mut old_line = 0;
+    let mut new_line = 0;
+    let mut consumed = 0u64;
+
+    for line in patch.lines() {
+        consumed += line.len() as u64 + 1;
+        if consumed > max_bytes {
+            break;
+        }
+
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // "a/old b/new", each side quoted if git had to; refined by the
+            // ---/+++ lines when present
+            let path = match header.rsplit_once(" \"b/") {
+                Some((_, quoted)) => rl_git::path::unquote(&format!("\"{}", quoted)),
+                None => header
+                    .rsplit_once(" b/")
+                    .map(|(_, path)| path)
+                    .unwrap_or(header)
+                    .to_string(),
+            };
+            files.push(DiffChunk {
+                path,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if let Some(header) = line.strip_prefix("@@ ") {
+            let (old_range, new_range, header) = parse_hunk_header(header);
+            old_line = old_range.start;
+            new_line = new_range.start;
+            file.hunks.push(DiffHunk {
+                old_range,
+                new_range,
+                header,
+                lines: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(hunk) = file.hunks.last_mut() else {
+            // File header lines before the first hunk
+            let path = line.strip_prefix("+++ ").map(rl_git::path::unquote);
+            if let Some(path) = path.as_deref().and_then(|path| path.strip_prefix("b/")) {
+                file.path = path.to_string();
+            }
+            continue;
+        };
+
+        let (line_type, content) = match line.as_bytes().first() {
+            Some(b'+') => (DiffLineType::Addition, &line[1..]),
+            Some(b'-') => (DiffLineType::Deletion, &line[1..]),
+            Some(b' ') => (DiffLineType::Context, &line[1..]),
+            _ => continue, // "\ No newline at end of file"
+        };
+
+        let (old, new) = match line_type {
+            DiffLineType::Addition => (None, Some(new_line)),
+            DiffLineType::Deletion => (Some(old_line), None),
+            DiffLineType::Context => (Some(old_line), Some(new_line)),
+        };
+        // Hunk headers come from git, but need not be sane
+        if old.is_some() {
+            old_line = old_line.saturating_add(1);
+        }
+        if new.is_some() {
+            new_line = new_line.saturating_add(1);
+        }
+
+        hunk.lines.push(DiffLine {
+            line_type,
+            old_line: old,
+            new_line: new,
+            content: content.to_string(),
+        });
+    }
+
+    files
+}
+
+/// Parse the part of a hunk header after "@@ ", e.g. "-1,3 +1,4 @@ fn main".
+fn parse_hunk_header(header: &str) -> (Range, Range, String) {
+    let (ranges, context) = header.split_once(" @@").unwrap_or((header, ""));
+    let mut parts = ranges.split_whitespace();
+    let old_range = parse_range(parts.next().unwrap_or("-0"));
+    let new_range = parse_range(parts.next().unwrap_or("+0"));
+    (old_range, new_range, context.trim().to_string())
+}
+
+/// Parse "-start,count" or "+start" (count defaults to 1).
+fn parse_range(range: &str) -> Range {
+    let range = range.trim_start_matches(['-', '+']);
+    let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+    Range {
+        start: start.parse().unwrap_or(0),
+        count: count.parse().unwrap_or(0),
+    }
+}
+
+/// Parse `git blame --porcelain` output into lines.
+///
+/// Commit metadata is only printed the first time a commit appears, so it is
+/// remembered for later lines from the same commit.
+pub(crate) fn parse_blame_porcelain(output: &str) -> Vec<BlameLine> {
+    let mut authors: HashMap<String, (String, String, i64)> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut commit_id = String::new();
+    let mut line_number = 0;
+
+    for line in output.lines() {
+        if let Some(content) = line.strip_prefix('\t') {
+            let (author_name, author_email, author_time) =
+                authors.get(&commit_id).cloned().unwrap_or_default();
+            lines.push(BlameLine {
+                line_number,
+                commit_id: commit_id.clone(),
+                author_name,
+                author_email,
+                author_time,
+                content: content.to_string(),
+            });
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("author ") {
+            authors.entry(commit_id.clone()).or_default().0 = name.to_string();
+        } else if let Some(mail) = line.strip_prefix("author-mail ") {
+            let mail = mail.trim_start_matches('<').trim_end_matches('>');
+            authors.entry(commit_id.clone()).or_default().1 = mail.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            authors.entry(commit_id.clone()).or_default().2 = time.parse().unwrap_or(0);
+        } else {
+            // "<sha> <orig-line> <final-line> [<count>]" starts each entry
+            let mut parts = line.split(' ');
+            if let (Some(sha), Some(_), Some(final_line)) =
+                (parts.next(), parts.next(), parts.next())
+            {
+                if sha.len() >= 40 && sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    commit_id = sha.to_string();
+                    line_number = final_line.parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+/// Parse a git progress line such as "Receiving objects:  45% (9/20)" or
+/// "Rebasing (3/8)".
+pub(crate) fn parse_progress_line(line: &str) -> Option<ProgressUpdate> {
+    let line = line.strip_prefix("remote: ").unwrap_or(line);
+    if let Some(counts) = line.strip_prefix("Rebasing (") {
+        let (done, total) = counts.trim_end_matches(')').split_once('/')?;
+        let (done, total) = (done.parse::<u64>().ok()?, total.parse::<u64>().ok()?);
+        return Some(ProgressUpdate {
+            stage: "Rebasing".to_string(),
+            progress: done.checked_mul(100)?.checked_div(total)?.min(100) as u8,
+            message: Some(line.trim().to_string()),
+        });
+    }
+    let (stage, rest) = line.split_once(':')?;
+    let (percent, _) = rest.split_once('%')?;
+    let progress = percent.trim().parse::<u8>().ok()?.min(100);
+
+    Some(ProgressUpdate {
+        stage: stage.trim().to_string(),
+        progress,
+        message: Some(line.trim().to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff_patch() {
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ fn main
+ one
+-two
++deux
+ three
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+";
+        let files = parse_diff_patch(patch, u64::MAX);
+        assert_eq!(files.len(), 2);
+
+        let hunk = &files[0].hunks[0];
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(hunk.header, "fn main");
+        assert_eq!(hunk.lines.len(), 4);
+        assert_eq!(hunk.lines[1].old_line, Some(2));
+        assert_eq!(hunk.lines[2].new_line, Some(2));
+        assert_eq!(hunk.lines[3].old_line, Some(3));
+
+        assert_eq!(files[1].path, "new.txt");
+        assert_eq!(files[1].hunks[0].new_range.count, 1);
+    }
+
+    #[test]
+    fn test_parse_blame_porcelain_reuses_commit_metadata() {
+        let sha = "a".repeat(40);
+        let output = format!(
+            "{sha} 1 1 2\nauthor Ada\nauthor-mail <ada@example.com>\nauthor-time 1700000000\n\
+             filename f.txt\n\tfirst\n\
+             {sha} 2 2\n\tsecond\n"
+        );
+        let lines = parse_blame_porcelain(&output);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].line_number, 2);
+        assert_eq!(lines[1].author_name, "Ada");
+        assert_eq!(lines[1].author_email, "ada@example.com");
+        assert_eq!(lines[1].author_time, 1_700_000_000);
+        assert_eq!(lines[1].content, "second");
+    }
+
+    #[test]
+    fn test_parse_diff_patch_unquotes_paths() {
+        let patch = r#"diff --git "a/caf\351.txt" "b/caf\351.txt"
+index 5626abf..814f4a4 100644
+--- "a/caf\351.txt"
++++ "b/caf\351.txt"
+@@ -1 +1,2 @@
+ one
++two
+diff --git "a/na\357ve/r\351sum\351.md" "b/na\357ve/r\351sum\351-final.md"
+similarity index 100%
+rename from "na\357ve/r\351sum\351.md"
+rename to "na\357ve/r\351sum\351-final.md"
+"#;
+        let files = parse_diff_patch(patch, u64::MAX);
+        let paths: Vec<_> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["caf\\xe9.txt", "na\\xefve/r\\xe9sum\\xe9-final.md"]);
+        assert_eq!(files[0].hunks[0].lines.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        let update = parse_progress_line("Receiving objects:  45% (9/20), 1.20 MiB").unwrap();
+        assert_eq!(update.stage, "Receiving objects");
+        assert_eq!(update.progress, 45);
+        assert!(parse_progress_line("From github.com:example/repo").is_none());
+
+        let update = parse_progress_line("Rebasing (3/4)").unwrap();
+        assert_eq!(update.stage, "Rebasing");
+        assert_eq!(update.progress, 75);
+    }
+
+    const PATCH: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ fn main
+ one
+-two
++deux
+ three
+\\ No newline at end of file
+diff --git a/old.txt b/new.txt
+similarity index 90%
+rename from old.txt
+rename to new.txt
+@@ -7 +7,2 @@
+-seven
++sept
++huit
+";
+
+    proptest::proptest! {
+        #[test]
+        fn test_parse_diff_patch_survives_garbled_output(
+            patch in rl_fixtures::malformed::garbled_str(PATCH),
+            max_bytes in 0..PATCH.len() as u64 + 64,
+        ) {
+            let files = parse_diff_patch(&patch, max_bytes);
+            let headers = patch.lines().filter(|l| l.starts_with("diff --git ")).count();
+            proptest::prop_assert!(files.len() <= headers);
+            let parsed: u64 = files
+                .iter()
+                .flat_map(|file| &file.hunks)
+                .flat_map(|hunk| &hunk.lines)
+                .map(|line| line.content.len() as u64)
+                .sum();
+            proptest::prop_assert!(parsed <= max_bytes);
+        }
+
+        #[test]
+        fn test_parse_blame_porcelain_survives_garbled_output(
+            output in rl_fixtures::malformed::garbled_str(&format!(
+                "{sha} 1 1 2\nauthor Ada\nauthor-mail <ada@example.com>\nauthor-time 1700000000\n\
+                 filename f.txt\n\tfirst\n{sha} 2 2\n\tsecond\n",
+                sha = "a".repeat(40),
+            ))
+        ) {
+            let lines = parse_blame_porcelain(&output);
+            let content_lines = output.lines().filter(|line| line.starts_with('\t')).count();
+            proptest::prop_assert!(lines.len() <= content_lines);
+        }
+
+        #[test]
+        fn test_parse_progress_line_survives_garbled_output(
+            line in proptest::prop_oneof![
+                rl_fixtures::malformed::garbled_str("remote: Receiving objects:  45% (9/20), 1.20 MiB"),
+                rl_fixtures::malformed::garbled_str("Rebasing (3/4)"),
+            ]
+        ) {
+            if let Some(update) = parse_progress_line(&line) {
+                proptest::prop_assert!(update.progress <= 100);
+            }
+        }
+    }
+}
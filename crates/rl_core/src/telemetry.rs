@@ -1,24 +1,194 @@
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use rl_api::response::{ResponseMeta, StepTiming, Truncation, TruncationBound};
+use std::cell::RefCell;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 use tracing::{info_span, Span};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt};
+use tracing_subscriber::{EnvFilter, Layer, Registry};
 
 static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
 
-pub fn init_telemetry(filter: Option<&str>, json: bool) {
+/// Swaps the filter of the subscriber installed by [`init_telemetry`]
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Exports the spans of the subscriber installed by [`init_telemetry`]
+static OTLP_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+tokio::task_local! {
+    /// What is known so far about how the request the current task is
+    /// serving was handled
+    static META: RefCell<ResponseMeta>;
+}
+
+/// Install the subscriber writing logs, and exporting spans to `otlp` if
+/// given.
+///
+/// The log filter only decides what is logged: spans at info level and
+/// above are exported whatever it is. An endpoint that can't be used is
+/// logged as a warning and nothing is exported; only a log file that can't
+/// be opened fails.
+pub fn init_telemetry(
+    filter: Option<&str>,
+    json: bool,
+    file: Option<&LogFile>,
+    otlp: Option<&OtlpExporter>,
+) -> io::Result<()> {
     // Default to "off" if no filter specified, so JSON output is clean by default
     let filter = filter.unwrap_or("off");
-    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(filter));
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter));
+    let (filter, handle) = reload::Layer::new(filter);
 
-    let registry = tracing_subscriber::registry().with(filter);
+    let writer = match file {
+        Some(file) => BoxMakeWriter::new(file.appender()?),
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(file.is_none());
 
-    let fmt_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let (provider, unusable) = match otlp.map(crate::otlp::provider).transpose() {
+        Ok(provider) => (provider, None),
+        Err(e) => (None, Some(e)),
+    };
 
     // Use try_init to avoid panicking if already initialized
-    if json {
-        let _ = registry.with(fmt_layer.json()).try_init();
+    let registry = tracing_subscriber::registry();
+    let initialized = if json {
+        registry
+            .with(fmt_layer.json().with_filter(filter))
+            .with(otlp_layer(provider.as_ref()))
+            .try_init()
+            .is_ok()
     } else {
-        let _ = registry.with(fmt_layer).try_init();
+        registry
+            .with(fmt_layer.with_filter(filter))
+            .with(otlp_layer(provider.as_ref()))
+            .try_init()
+            .is_ok()
+    };
+    if initialized {
+        let _ = LOG_FILTER.set(handle);
+        if let Some(provider) = provider {
+            let _ = OTLP_PROVIDER.set(provider);
+        }
+    }
+    if let Some(error) = unusable {
+        tracing::warn!(error = %error, "Not exporting spans");
+    }
+    Ok(())
+}
+
+/// The layer exporting spans at info level and above to `provider`'s
+/// collector, if any.
+fn otlp_layer<S>(provider: Option<&SdkTracerProvider>) -> Option<impl Layer<S>>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    provider.map(|provider| crate::otlp::layer(provider).with_filter(LevelFilter::INFO))
+}
+
+/// Send the spans closed so far to the OTLP collector, waiting up to five
+/// seconds for it to take them. Call before exiting, or spans from the
+/// last second are lost. Does nothing if no exporter was installed.
+pub fn flush_telemetry() {
+    if let Some(provider) = OTLP_PROVIDER.get() {
+        let _ = provider.force_flush();
+    }
+}
+
+/// An OpenTelemetry collector to export spans to, over OTLP/HTTP with JSON
+/// bodies, as Jaeger and Tempo accept on port 4318. The standard
+/// `OTEL_EXPORTER_OTLP_*` variables for headers and timeouts apply to it.
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    /// Collector URL, e.g. `http://localhost:4318`; spans are posted to
+    /// `/v1/traces` under it unless it already ends there. Both `http://`
+    /// and `https://` are supported.
+    pub endpoint: String,
+    /// `service.name` spans are reported under
+    pub service_name: String,
+}
+
+/// A file to write logs to instead of stderr.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    /// Path of the file; rotated files add the date, and hour, after it
+    pub path: PathBuf,
+    /// How often to start a new file
+    pub rotation: LogRotation,
+    /// Rotated files to keep, the oldest being deleted; all if unset
+    pub keep: Option<usize>,
+}
+
+/// How often a [`LogFile`] starts a new file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogRotation {
+    /// Always append to the same file
+    #[default]
+    Never,
+    /// Start `<path>.YYYY-MM-DD-HH` every hour
+    Hourly,
+    /// Start `<path>.YYYY-MM-DD` every day
+    Daily,
+}
+
+impl LogFile {
+    /// Open the file for appending, creating it and its directory if need be.
+    fn appender(&self) -> io::Result<RollingFileAppender> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid log file {}", self.path.display()),
+            )
+        };
+        let name = self.path.file_name().ok_or_else(invalid)?;
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let rotation = match self.rotation {
+            LogRotation::Never => Rotation::NEVER,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+        };
+        let mut builder = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(name.to_string_lossy());
+        if let Some(keep) = self.keep {
+            builder = builder.max_log_files(keep);
+        }
+        builder.build(directory).map_err(|error| {
+            io::Error::other(format!(
+                "Failed to open log file {}: {}",
+                self.path.display(),
+                error
+            ))
+        })
+    }
+}
+
+/// Check that `filter` is a valid log filter, e.g. `rl_core=debug,info`.
+pub fn validate_log_filter(filter: &str) -> Result<(), String> {
+    EnvFilter::try_new(filter)
+        .map(drop)
+        .map_err(|error| error.to_string())
+}
+
+/// Replace the log filter of the subscriber installed by
+/// [`init_telemetry`]. Does nothing if it was not installed.
+pub fn set_log_filter(filter: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(filter).map_err(|error| error.to_string())?;
+    match LOG_FILTER.get() {
+        Some(handle) => handle.reload(filter).map_err(|error| error.to_string()),
+        None => Ok(()),
     }
 }
 
@@ -37,10 +207,13 @@ pub struct RequestSpan {
 }
 
 impl RequestSpan {
-    pub fn new(request_id: &str, repo_path: &str, request_type: &str) -> Self {
+    /// Span of the request with the client's `id`, logged under
+    /// `request_id`.
+    pub fn new(request_id: &str, id: &str, repo_path: &str, request_type: &str) -> Self {
         let span = info_span!(
             "request",
             request_id = request_id,
+            id = id,
             repo_path = repo_path,
             request_type = request_type
         );
@@ -52,16 +225,69 @@ impl RequestSpan {
     }
 }
 
+/// Run `request`, collecting the steps, cache hits and truncation recorded
+/// while it runs. The elapsed time is left for the caller to fill in.
+///
+/// Only what is recorded on the task driving `request` is collected, not
+/// what tasks it spawns record.
+pub async fn collect_meta<F: Future>(request: F) -> (F::Output, ResponseMeta) {
+    META.scope(RefCell::new(ResponseMeta::default()), async {
+        let output = request.await;
+        (output, META.with(|meta| meta.take()))
+    })
+    .await
+}
+
+/// Record that step `name` took `elapsed`, for the request being collected
+/// by [`collect_meta`]. Does nothing outside of one.
+pub fn record_step(name: &str, elapsed: Duration) {
+    let _ = META.try_with(|meta| {
+        meta.borrow_mut().steps.push(StepTiming {
+            name: name.to_string(),
+            elapsed_ms: elapsed.as_nanos() as f64 / 1_000_000.0,
+        })
+    });
+}
+
+/// Record the id the engine logs the request under.
+pub fn record_request_id(request_id: &str) {
+    let _ = META.try_with(|meta| meta.borrow_mut().request_id = Some(request_id.to_string()));
+}
+
+/// Record that the request was answered from a cache.
+pub fn record_cache_hit() {
+    let _ = META.try_with(|meta| meta.borrow_mut().cached = true);
+}
+
+/// Record that `bound`, set to `limit`, cut the result short.
+pub fn record_truncation(bound: TruncationBound, limit: u64) {
+    let _ = META.try_with(|meta| meta.borrow_mut().truncated = Some(Truncation { bound, limit }));
+}
+
+/// The steps recorded so far, as `name=elapsed` pairs for logging.
+pub fn step_summary() -> String {
+    META.try_with(|meta| {
+        meta.borrow()
+            .steps
+            .iter()
+            .map(|step| format!("{}={:.1}ms", step.name, step.elapsed_ms))
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+    .unwrap_or_default()
+}
+
 #[macro_export]
 macro_rules! step {
     ($name:expr, $block:block) => {{
-        let span = tracing::info_span!($name);
-        let _enter = span.enter();
+        use tracing::Instrument as _;
 
         async {
             let start = std::time::Instant::now();
             let result = $block;
-            let elapsed_ms = start.elapsed().as_nanos() as f64 / 1_000_000.0;
+            let elapsed = start.elapsed();
+            $crate::telemetry::record_step($name, elapsed);
+            let elapsed_ms = elapsed.as_nanos() as f64 / 1_000_000.0;
 
             match &result {
                 Ok(_) => {
@@ -74,6 +300,7 @@ macro_rules! step {
 
             result
         }
+        .instrument(tracing::info_span!($name))
         .await
     }};
 }
@@ -0,0 +1,209 @@
+//! Repositories broken the ways real ones get broken, so that the errors
+//! they cause can be tested: an object deleted from under a commit, a
+//! packfile cut short, an `index.lock` left behind by a git that crashed,
+//! a `.git` file naming a gitdir that is gone, and a directory that is not
+//! a repository at all.
+//!
+//! Each is built in a temporary directory of its own, outside any
+//! repository, so that git can't find the workspace's repository above it,
+//! and is removed when dropped. Except for [`Breakage::NotARepository`],
+//! each starts from the same history: [`FIRST`], which adds
+//! [`README`] and [`NOTES`], then [`SECOND`], which changes [`NOTES`].
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::FixtureError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tag on the first commit
+pub const FIRST: &str = "first";
+/// Tag on the second commit, where `master` ends
+pub const SECOND: &str = "second";
+/// File added by [`FIRST`] and left alone since
+pub const README: &str = "README.md";
+/// File added by [`FIRST`] and changed by [`SECOND`]
+pub const NOTES: &str = "notes.txt";
+
+/// Broken repositories made by this process so far, numbering each
+static BROKEN: AtomicUsize = AtomicUsize::new(0);
+
+/// How the repository is broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakage {
+    /// The blob [`FIRST`] gave [`NOTES`] is deleted. Refs, commits and
+    /// trees are intact, so only reading that blob fails.
+    MissingObject,
+    /// Every object is in one pack, cut to half its length. Refs still
+    /// resolve, but reading almost any object fails.
+    TruncatedPack,
+    /// `.git/index.lock` exists, as a git that crashed leaves it, so
+    /// anything that writes the index fails.
+    StaleIndexLock,
+    /// `.git` is a file naming a gitdir that does not exist, as a linked
+    /// worktree or submodule whose gitdir was removed leaves it.
+    DanglingGitFile,
+    /// A directory with a file in it, and no repository in or above it.
+    NotARepository,
+}
+
+impl Breakage {
+    fn name(self) -> &'static str {
+        match self {
+            Breakage::MissingObject => "missing_object",
+            Breakage::TruncatedPack => "truncated_pack",
+            Breakage::StaleIndexLock => "stale_index_lock",
+            Breakage::DanglingGitFile => "dangling_git_file",
+            Breakage::NotARepository => "not_a_repository",
+        }
+    }
+}
+
+/// A broken repository in a temporary directory, removed when this is
+/// dropped
+pub struct BrokenRepo {
+    pub path: PathBuf,
+    root: PathBuf,
+}
+
+impl BrokenRepo {
+    /// Build a repository broken by `breakage`.
+    pub fn create(breakage: Breakage) -> Result<BrokenRepo, FixtureError> {
+        let root = std::env::temp_dir().join(format!(
+            "rl_fixtures-broken-{}-{}-{}",
+            breakage.name(),
+            std::process::id(),
+            BROKEN.fetch_add(1, Ordering::Relaxed)
+        ));
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        let path = root.join("repo");
+        fs::create_dir_all(&path)?;
+
+        // Removes the directory if the build fails
+        let repo = BrokenRepo { path, root };
+        if breakage == Breakage::NotARepository {
+            fs::write(repo.path.join(README), "# Not a repository\n")?;
+            return Ok(repo);
+        }
+        FixtureBuilder::new()
+            .file(README, "# Broken\n")
+            .file(NOTES, "first notes\n")
+            .commit(&format!("{} commit", FIRST))
+            .tag(FIRST)
+            .file(NOTES, "second notes\n")
+            .commit(&format!("{} commit", SECOND))
+            .tag(SECOND)
+            .build_at(&repo.path)?;
+
+        let git_dir = repo.path.join(".git");
+        match breakage {
+            Breakage::MissingObject => {
+                // fast-import leaves a history this small as loose objects
+                let blob = repo.rev_parse(&format!("{}:{}", FIRST, NOTES))?;
+                let (dir, file) = blob.split_at(2);
+                fs::remove_file(git_dir.join("objects").join(dir).join(file))?;
+            }
+            Breakage::TruncatedPack => {
+                git(&repo.path, &["repack", "-a", "-d", "--quiet"])?;
+                let pack = pack_file(&git_dir.join("objects").join("pack"))?;
+                // Packs are written read-only, so write a new one in its place
+                let mut bytes = fs::read(&pack)?;
+                bytes.truncate(bytes.len() / 2);
+                fs::remove_file(&pack)?;
+                fs::write(&pack, bytes)?;
+            }
+            Breakage::StaleIndexLock => {
+                fs::write(git_dir.join("index.lock"), "")?;
+            }
+            Breakage::DanglingGitFile => {
+                let gone = repo.root.join("gone.git");
+                fs::rename(&git_dir, &gone)?;
+                fs::remove_dir_all(&gone)?;
+                fs::write(&git_dir, format!("gitdir: {}\n", gone.display()))?;
+            }
+            Breakage::NotARepository => unreachable!("returned above"),
+        }
+        Ok(repo)
+    }
+
+    /// Object id that `revision` names, read before the repository was
+    /// broken.
+    fn rev_parse(&self, revision: &str) -> Result<String, FixtureError> {
+        let output = git(&self.path, &["rev-parse", revision])?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+impl Drop for BrokenRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// The one pack in `dir`
+fn pack_file(dir: &Path) -> Result<PathBuf, FixtureError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "pack")
+        {
+            return Ok(path);
+        }
+    }
+    Err(FixtureError::Git("git repack wrote no pack".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_broken_repos_fail_where_they_are_broken() {
+        let succeeds = |repo: &BrokenRepo, args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            output.status.success()
+        };
+
+        let missing = BrokenRepo::create(Breakage::MissingObject).unwrap();
+        assert!(succeeds(&missing, &["log", "--format=%H"]));
+        assert!(succeeds(
+            &missing,
+            &["cat-file", "-e", &format!("{}:{}", SECOND, NOTES)]
+        ));
+        assert!(!succeeds(
+            &missing,
+            &["cat-file", "-e", &format!("{}:{}", FIRST, NOTES)]
+        ));
+
+        let truncated = BrokenRepo::create(Breakage::TruncatedPack).unwrap();
+        assert!(succeeds(&truncated, &["rev-parse", "HEAD"]));
+        assert!(!succeeds(&truncated, &["log", "--format=%H"]));
+
+        let locked = BrokenRepo::create(Breakage::StaleIndexLock).unwrap();
+        assert!(succeeds(&locked, &["log", "--format=%H"]));
+        assert!(!succeeds(&locked, &["checkout", "--quiet", FIRST]));
+
+        for breakage in [Breakage::DanglingGitFile, Breakage::NotARepository] {
+            let repo = BrokenRepo::create(breakage).unwrap();
+            assert!(repo.path.join(README).exists());
+            assert!(
+                !succeeds(&repo, &["rev-parse", "--git-dir"]),
+                "{:?}",
+                breakage
+            );
+        }
+
+        let root = missing.root.clone();
+        drop(missing);
+        assert!(!root.exists());
+    }
+}
@@ -0,0 +1,543 @@
+//! Repositories declared step by step, for tests that need a particular
+//! history and nothing more:
+//!
+//! ```no_run
+//! use rl_fixtures::builder::FixtureBuilder;
+//!
+//! let repo = FixtureBuilder::new()
+//!     .file("a.txt", "one\n")
+//!     .commit("first")
+//!     .branch("feature")
+//!     .file("b.txt", "on feature\n")
+//!     .commit("add b")
+//!     .checkout("master")
+//!     .file("a.txt", "two\n")
+//!     .commit("change a")
+//!     .merge("feature", "merge feature")
+//!     .tag("v1")
+//!     .build("example")?;
+//! # Ok::<(), rl_fixtures::synth_repo::FixtureError>(())
+//! ```
+//!
+//! `file`, `executable`, `symlink` and `delete` change the tree that the
+//! next `commit` or `merge` records; changes still pending at `build` are
+//! left uncommitted in the working tree. Modes are recorded as declared
+//! whatever the file system supports, so only checkouts differ between
+//! platforms. Paths are bytes, so that names that aren't UTF-8 can be
+//! declared where the file system allows them. Every commit has the same
+//! author and a date a second after the one before, and an annotated tag
+//! the date of its commit, so a declaration always gives the same ids.
+//! The history is written with `git fast-import`.
+//!
+//! The builder is for tests, so a step that makes no sense, such as
+//! checking out a branch never created, panics.
+
+use crate::synth_repo::{FixtureError, SynthRepo};
+use crate::{cache, fast_import, git};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Git mode of a regular file
+const FILE: &str = "100644";
+/// Git mode of an executable file
+const EXECUTABLE: &str = "100755";
+/// Git mode of a symlink
+const SYMLINK: &str = "120000";
+
+/// A file in a tree: its git mode and contents, a symlink's contents being
+/// its target
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    mode: &'static str,
+    content: String,
+}
+
+/// Files by path
+type Tree = BTreeMap<Vec<u8>, Entry>;
+
+struct Commit {
+    message: String,
+    /// Parents, by index
+    parents: Vec<usize>,
+    /// Branch checked out when it was made
+    branch: String,
+    tree: Tree,
+}
+
+/// Declares a repository's history, then builds it
+pub struct FixtureBuilder {
+    commits: Vec<Commit>,
+    /// Commit each branch ends at, if it has any
+    branches: BTreeMap<String, Option<usize>>,
+    /// Branch checked out
+    current: String,
+    /// Tags, with the commit each is on and an annotated tag's message
+    tags: Vec<(String, usize, Option<String>)>,
+    /// Changes for the next commit: the new file, or `None` to delete
+    pending: BTreeMap<Vec<u8>, Option<Entry>>,
+}
+
+/// A repository built by [`FixtureBuilder`]
+pub struct BuiltRepo {
+    pub path: PathBuf,
+}
+
+impl Default for FixtureBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FixtureBuilder {
+    /// An empty repository on an unborn `master`.
+    pub fn new() -> Self {
+        Self {
+            commits: Vec::new(),
+            branches: BTreeMap::from([("master".to_string(), None)]),
+            current: "master".to_string(),
+            tags: Vec::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Write `content` to `path` in the next commit.
+    pub fn file(self, path: impl AsRef<[u8]>, content: &str) -> Self {
+        self.entry(path, FILE, content)
+    }
+
+    /// Write `content` to `path` as an executable in the next commit.
+    pub fn executable(self, path: impl AsRef<[u8]>, content: &str) -> Self {
+        self.entry(path, EXECUTABLE, content)
+    }
+
+    /// Make `path` a symlink to `target` in the next commit.
+    pub fn symlink(self, path: impl AsRef<[u8]>, target: &str) -> Self {
+        self.entry(path, SYMLINK, target)
+    }
+
+    /// Delete `path` in the next commit.
+    pub fn delete(mut self, path: impl AsRef<[u8]>) -> Self {
+        self.pending.insert(path.as_ref().to_vec(), None);
+        self
+    }
+
+    /// Commit the pending changes on the current branch.
+    pub fn commit(self, message: &str) -> Self {
+        self.record(message, Vec::new())
+    }
+
+    /// Create `name` at the current commit and check it out.
+    pub fn branch(mut self, name: &str) -> Self {
+        let head = self.head();
+        assert!(
+            self.branches.insert(name.to_string(), head).is_none(),
+            "branch {} already exists",
+            name
+        );
+        self.current = name.to_string();
+        self
+    }
+
+    /// Create `name` with no commits, unrelated to any other branch, and
+    /// check it out.
+    pub fn orphan(mut self, name: &str) -> Self {
+        assert!(
+            self.branches.insert(name.to_string(), None).is_none(),
+            "branch {} already exists",
+            name
+        );
+        self.current = name.to_string();
+        self
+    }
+
+    /// Check out the existing branch `name`.
+    pub fn checkout(mut self, name: &str) -> Self {
+        assert!(self.branches.contains_key(name), "no branch {}", name);
+        self.current = name.to_string();
+        self
+    }
+
+    /// Delete the branch `name`, which must not be checked out. Its
+    /// commits stay, reachable from whatever else was made on them.
+    pub fn delete_branch(mut self, name: &str) -> Self {
+        assert_ne!(name, self.current, "branch {} is checked out", name);
+        assert!(self.branches.remove(name).is_some(), "no branch {}", name);
+        self
+    }
+
+    /// Merge `branch`, or the commit tagged `branch`, into the current
+    /// branch. Each file takes the version of the side that changed it
+    /// since the merge base, `branch`'s when both did, and pending changes
+    /// apply on top, to settle what a real merge would conflict on.
+    pub fn merge(self, branch: &str, message: &str) -> Self {
+        self.merge_all(&[branch], message)
+    }
+
+    /// Merge each of `branches` in turn, as [`merge`](Self::merge) does,
+    /// in one commit: an octopus merge when there are several.
+    pub fn merge_all(self, branches: &[&str], message: &str) -> Self {
+        let merged = branches.iter().map(|name| self.named(name)).collect();
+        self.record(message, merged)
+    }
+
+    /// Tag the current commit.
+    pub fn tag(self, name: &str) -> Self {
+        self.record_tag(name, None)
+    }
+
+    /// Tag the current commit with an annotated tag carrying `message`.
+    pub fn annotated_tag(self, name: &str, message: &str) -> Self {
+        self.record_tag(name, Some(message.to_string()))
+    }
+
+    /// The latest commit that both `a` and `b` descend from.
+    fn merge_base(&self, a: usize, b: usize) -> Option<usize> {
+        let ancestors = |start: usize| {
+            let mut seen = vec![false; self.commits.len()];
+            let mut stack = vec![start];
+            while let Some(commit) = stack.pop() {
+                if !std::mem::replace(&mut seen[commit], true) {
+                    stack.extend(&self.commits[commit].parents);
+                }
+            }
+            seen
+        };
+        let (of_a, of_b) = (ancestors(a), ancestors(b));
+        (0..self.commits.len()).rev().find(|&c| of_a[c] && of_b[c])
+    }
+
+    fn head(&self) -> Option<usize> {
+        self.branches[&self.current]
+    }
+
+    fn record_tag(mut self, name: &str, message: Option<String>) -> Self {
+        let head = self.head().expect("a tag needs a commit");
+        self.tags.push((name.to_string(), head, message));
+        self
+    }
+
+    fn entry(mut self, path: impl AsRef<[u8]>, mode: &'static str, content: &str) -> Self {
+        let entry = Entry {
+            mode,
+            content: content.to_string(),
+        };
+        self.pending.insert(path.as_ref().to_vec(), Some(entry));
+        self
+    }
+
+    /// The commit the branch or tag `name` points to.
+    fn named(&self, name: &str) -> usize {
+        let branch = self.branches.get(name).copied().flatten();
+        let tag = || self.tags.iter().find(|(tag, ..)| tag == name).map(|t| t.1);
+        branch
+            .or_else(tag)
+            .unwrap_or_else(|| panic!("no commits on branch or tag {}", name))
+    }
+
+    fn record(mut self, message: &str, merged: Vec<usize>) -> Self {
+        let head = self.head();
+        let mut tree = head
+            .map(|head| self.commits[head].tree.clone())
+            .unwrap_or_default();
+        for &theirs in &merged {
+            let base = head
+                .and_then(|ours| self.merge_base(ours, theirs))
+                .map(|base| self.commits[base].tree.clone())
+                .unwrap_or_default();
+            let theirs = &self.commits[theirs].tree;
+            let paths: Vec<Vec<u8>> = base.keys().chain(theirs.keys()).cloned().collect();
+            for path in paths {
+                match theirs.get(&path) {
+                    version if version == base.get(&path) => {}
+                    Some(entry) => {
+                        tree.insert(path, entry.clone());
+                    }
+                    None => {
+                        tree.remove(&path);
+                    }
+                }
+            }
+        }
+        for (path, entry) in std::mem::take(&mut self.pending) {
+            match entry {
+                Some(entry) => tree.insert(path, entry),
+                None => tree.remove(&path),
+            };
+        }
+        self.commits.push(Commit {
+            message: message.to_string(),
+            parents: head.into_iter().chain(merged).collect(),
+            branch: self.current.clone(),
+            tree,
+        });
+        let id = self.commits.len() - 1;
+        self.branches.insert(self.current.clone(), Some(id));
+        self
+    }
+
+    /// Build the repository at `target/rl_fixtures/builder/<name>/repo`,
+    /// with the current branch checked out. A repository already there
+    /// from the same declaration, with its refs where they were left, is
+    /// reused: only its index and working tree are reset.
+    pub fn build(&self, name: &str) -> Result<BuiltRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("builder")
+            .join(name)
+            .join("repo");
+        self.build_at(path)
+    }
+
+    /// Build the repository at `path`, as [`build`](Self::build) does, for
+    /// fixtures that keep their repositories somewhere of their own.
+    pub fn build_at(&self, path: impl Into<PathBuf>) -> Result<BuiltRepo, FixtureError> {
+        let path = path.into();
+        let mut history = Vec::new();
+        self.write_history(&mut history)?;
+        writeln!(history, "HEAD {}", self.current)?;
+        writeln!(history, "pending {:?}", self.pending)?;
+        let definition = cache::hash(&history);
+
+        if cache::is_cached(&path, &definition) {
+            if self.head().is_some() {
+                git(&path, &["reset", "--hard", "--quiet"])?;
+            } else if path.join(".git").join("index").exists() {
+                // Nothing to reset to, so unstage everything
+                fs::remove_file(path.join(".git").join("index"))?;
+            }
+            git(&path, &["clean", "-d", "--force", "-x", "--quiet"])?;
+            self.write_pending(&path)?;
+            return Ok(BuiltRepo { path });
+        }
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir_all(&path)?;
+
+        git(&path, &["init", "--quiet"])?;
+        git(
+            &path,
+            &[
+                "symbolic-ref",
+                "HEAD",
+                &format!("refs/heads/{}", self.current),
+            ],
+        )?;
+        git(&path, &["config", "user.name", "Test User"])?;
+        git(&path, &["config", "user.email", "test@example.com"])?;
+        if !self.commits.is_empty() {
+            fast_import(&path, |out| self.write_history(out))?;
+        }
+        if self.head().is_some() {
+            git(&path, &["reset", "--hard", "--quiet"])?;
+        }
+        cache::record(&path, &definition)?;
+        self.write_pending(&path)?;
+        Ok(BuiltRepo { path })
+    }
+
+    /// Leave the pending changes in the working tree at `path`.
+    fn write_pending(&self, path: &Path) -> Result<(), FixtureError> {
+        for (rel_path, entry) in &self.pending {
+            let file = path.join(os_path(rel_path));
+            match entry {
+                Some(entry) => {
+                    if let Some(parent) = file.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    entry.write(&file)?;
+                }
+                None => fs::remove_file(file)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_history(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (n, commit) in self.commits.iter().enumerate() {
+            writeln!(out, "commit refs/heads/{}", commit.branch)?;
+            writeln!(out, "mark :{}", n + 1)?;
+            writeln!(
+                out,
+                "committer Test User <test@example.com> {} +0000",
+                1_700_000_000 + n
+            )?;
+            writeln!(out, "data {}\n{}", commit.message.len(), commit.message)?;
+            if let Some((first, merged)) = commit.parents.split_first() {
+                writeln!(out, "from :{}", first + 1)?;
+                for parent in merged {
+                    writeln!(out, "merge :{}", parent + 1)?;
+                }
+            }
+            writeln!(out, "deleteall")?;
+            for (path, entry) in &commit.tree {
+                write!(out, "M {} inline ", entry.mode)?;
+                out.write_all(path)?;
+                writeln!(out, "\ndata {}\n{}", entry.content.len(), entry.content)?;
+            }
+            writeln!(out)?;
+        }
+        for (branch, head) in &self.branches {
+            if let Some(head) = head {
+                writeln!(out, "reset refs/heads/{}\nfrom :{}\n", branch, head + 1)?;
+            }
+        }
+        // Without a commit to start from, fast-import deletes the branch
+        let deleted: std::collections::BTreeSet<_> = self
+            .commits
+            .iter()
+            .map(|commit| &commit.branch)
+            .filter(|branch| !self.branches.contains_key(*branch))
+            .collect();
+        for branch in deleted {
+            writeln!(out, "reset refs/heads/{}\n", branch)?;
+        }
+        for (tag, commit, message) in &self.tags {
+            match message {
+                Some(message) => {
+                    writeln!(out, "tag {}\nfrom :{}", tag, commit + 1)?;
+                    writeln!(
+                        out,
+                        "tagger Test User <test@example.com> {} +0000",
+                        1_700_000_000 + commit
+                    )?;
+                    writeln!(out, "data {}\n{}", message.len(), message)?;
+                }
+                None => writeln!(out, "reset refs/tags/{}\nfrom :{}\n", tag, commit + 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Entry {
+    /// Check the file out at `file`, as a symlink or executable where the
+    /// platform has them.
+    fn write(&self, file: &Path) -> std::io::Result<()> {
+        #[cfg(unix)]
+        if self.mode == SYMLINK {
+            if fs::symlink_metadata(file).is_ok() {
+                fs::remove_file(file)?;
+            }
+            return std::os::unix::fs::symlink(&self.content, file);
+        }
+        fs::write(file, &self.content)?;
+        #[cfg(unix)]
+        if self.mode == EXECUTABLE {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(file, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+}
+
+/// The path `bytes` name
+#[cfg(unix)]
+fn os_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+/// The path `bytes` name, which off POSIX systems must be UTF-8
+#[cfg(not(unix))]
+fn os_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_builder_builds_the_declared_history() {
+        let repo = FixtureBuilder::new()
+            .file("a.txt", "one\n")
+            .file("dir/gone.txt", "temporary\n")
+            .commit("first")
+            .branch("feature")
+            .file("b.txt", "on feature\n")
+            .delete("dir/gone.txt")
+            .commit("add b")
+            .checkout("master")
+            .file("a.txt", "two\n")
+            .commit("change a")
+            .merge("feature", "merge feature")
+            .tag("v1")
+            .file("a.txt", "uncommitted\n")
+            .build("test_builder")
+            .expect("Failed to build fixture");
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+
+        assert_eq!(
+            git(&["log", "--format=%s", "--first-parent"]),
+            "merge feature\nchange a\nfirst"
+        );
+        assert_eq!(git(&["rev-parse", "v1^2"]), git(&["rev-parse", "feature"]));
+        assert_eq!(git(&["rev-parse", "v1"]), git(&["rev-parse", "HEAD"]));
+        assert_eq!(git(&["ls-tree", "-r", "--name-only", "v1"]), "a.txt\nb.txt");
+        assert_eq!(git(&["show", "v1:a.txt"]), "two");
+        assert_eq!(git(&["status", "--porcelain"]), "M a.txt");
+
+        // The same declaration gives the same commits
+        let again = FixtureBuilder::new()
+            .file("a.txt", "one\n")
+            .file("dir/gone.txt", "temporary\n")
+            .commit("first")
+            .build("test_builder_again")
+            .unwrap();
+        let root = Command::new("git")
+            .current_dir(&again.path)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&root.stdout).trim(),
+            git(&["rev-list", "--max-parents=0", "HEAD"])
+        );
+    }
+
+    #[test]
+    fn test_builds_are_reused_until_the_declaration_changes() {
+        let declared = |content: &str| {
+            FixtureBuilder::new()
+                .file("a.txt", "one\n")
+                .commit("first")
+                .file("a.txt", content)
+        };
+        // Left in the git directory, which only a rebuild removes
+        let marker = |repo: &BuiltRepo| repo.path.join(".git").join("marker");
+
+        let repo = declared("dirty\n").build("test_builder_cache").unwrap();
+        fs::write(marker(&repo), "").unwrap();
+        fs::write(repo.path.join("a.txt"), "changed by a test\n").unwrap();
+        fs::write(repo.path.join("untracked.txt"), "").unwrap();
+
+        let repo = declared("dirty\n").build("test_builder_cache").unwrap();
+        assert!(marker(&repo).exists());
+        assert_eq!(
+            fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+            "dirty\n"
+        );
+        assert!(!repo.path.join("untracked.txt").exists());
+
+        // Refs moved by a test
+        git(&repo.path, &["commit", "--quiet", "--all", "-m", "second"]).unwrap();
+        let repo = declared("dirty\n").build("test_builder_cache").unwrap();
+        assert!(!marker(&repo).exists());
+
+        fs::write(marker(&repo), "").unwrap();
+        let repo = declared("other\n").build("test_builder_cache").unwrap();
+        assert!(!marker(&repo).exists());
+    }
+}
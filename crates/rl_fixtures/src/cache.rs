@@ -0,0 +1,74 @@
+//! Reuse of generated repositories while what they were generated from is
+//! unchanged.
+//!
+//! A repository records a hash of its definition, such as the `git
+//! fast-import` stream that wrote it, in its git config, along with a hash
+//! of the refs it was left with. It is reused only while both still match:
+//! a changed definition, or a test that moved a branch, means building it
+//! again. The hash is FNV-1a, written out here so that it never changes
+//! with a toolchain.
+
+use crate::synth_repo::FixtureError;
+use std::path::Path;
+use std::process::Command;
+
+/// Git config key recording the definition and refs hashes
+const KEY: &str = "rl-fixtures.definition";
+
+/// Hash of `bytes`, as hex
+pub(crate) fn hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Hash of where HEAD and every ref of `repo` point, if git can list them
+fn refs(repo: &Path) -> Option<String> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .current_dir(repo)
+            .args(args)
+            .output()
+            .ok()?;
+        Some(output.stdout)
+    };
+    let mut listed = git(&["for-each-ref", "--format=%(objectname) %(refname)"])?;
+    listed.extend(git(&["symbolic-ref", "--quiet", "HEAD"])?);
+    Some(hash(&listed))
+}
+
+/// Whether `repo` was built from `definition`, a hash from [`hash`], and
+/// its refs are as they were left.
+pub(crate) fn is_cached(repo: &Path, definition: &str) -> bool {
+    if !repo.join(".git").exists() {
+        return false;
+    }
+    let recorded = Command::new("git")
+        .current_dir(repo)
+        .args(["config", KEY])
+        .output();
+    let Ok(recorded) = recorded else {
+        return false;
+    };
+    let recorded = String::from_utf8_lossy(&recorded.stdout);
+    refs(repo).is_some_and(|refs| recorded.trim() == format!("{} {}", definition, refs))
+}
+
+/// Record that `repo`, as it is now, was built from `definition`.
+pub(crate) fn record(repo: &Path, definition: &str) -> Result<(), FixtureError> {
+    let refs =
+        refs(repo).ok_or_else(|| FixtureError::Git("git for-each-ref failed".to_string()))?;
+    let output = Command::new("git")
+        .current_dir(repo)
+        .args(["config", KEY, &format!("{} {}", definition, refs)])
+        .output()?;
+    if !output.status.success() {
+        return Err(FixtureError::Git(format!(
+            "git config {} failed: {}",
+            KEY,
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
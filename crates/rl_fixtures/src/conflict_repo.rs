@@ -0,0 +1,128 @@
+//! A repository stopped in the middle of a conflicted merge, for tests of
+//! conflict listing, resolution and merging.
+//!
+//! `master` and `feature` fork from a common commit and both change the
+//! same files, then `feature` is merged into `master` and left unresolved:
+//! `MERGE_HEAD` is set, the working tree has conflict markers and the
+//! index holds the base, ours and theirs versions of each conflicted path
+//! as stages 1, 2 and 3.
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Branch checked out and merged into
+pub const OURS: &str = "master";
+/// Branch being merged
+pub const THEIRS: &str = "feature";
+
+/// Changed on both branches around the same lines: stages 1, 2 and 3
+pub const BOTH_MODIFIED: &str = "conflict.txt";
+/// Added on both branches with different contents: stages 2 and 3
+pub const BOTH_ADDED: &str = "both_added.txt";
+/// Changed on `master` and deleted on `feature`: stages 1 and 2
+pub const DELETED_BY_THEM: &str = "deleted_by_them.txt";
+/// Changed on `feature` only, so merged and staged cleanly
+pub const CLEAN: &str = "clean.txt";
+
+/// Every path left conflicted, sorted
+pub const CONFLICTED_PATHS: &[&str] = &[BOTH_ADDED, BOTH_MODIFIED, DELETED_BY_THEM];
+
+/// A generated mid-merge repository
+pub struct ConflictRepo {
+    pub path: PathBuf,
+}
+
+impl ConflictRepo {
+    /// Build the repository at `target/rl_fixtures/conflict/<name>/repo`,
+    /// reusing the commits if they are already there, and start the merge.
+    pub fn create(name: &str) -> Result<ConflictRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("conflict")
+            .join(name)
+            .join("repo");
+        let built = FixtureBuilder::new()
+            .file(BOTH_MODIFIED, "line 1\nline 2\nline 3\nline 4\nline 5\n")
+            .file(DELETED_BY_THEM, "kept on master\n")
+            .file(CLEAN, "unchanged\n")
+            .commit("base")
+            .branch(THEIRS)
+            .file(
+                BOTH_MODIFIED,
+                "line 1\nline 2 from feature\nline 3\nline 4 from feature\nline 5\n",
+            )
+            .file(BOTH_ADDED, "added on feature\n")
+            .file(CLEAN, "changed on feature\n")
+            .delete(DELETED_BY_THEM)
+            .commit("feature changes")
+            .checkout(OURS)
+            .file(
+                BOTH_MODIFIED,
+                "line 1\nline 2 from master\nline 3\nline 4 from master\nline 5\n",
+            )
+            .file(BOTH_ADDED, "added on master\n")
+            .file(DELETED_BY_THEM, "changed on master\n")
+            .commit("master changes")
+            .build_at(path)?;
+
+        let repo = ConflictRepo { path: built.path };
+        // Plain `<<<<<<<`/`=======`/`>>>>>>>` markers, whatever the user's
+        // global configuration asks for
+        git(&repo.path, &["config", "merge.conflictStyle", "merge"])?;
+        let merge = Command::new("git")
+            .current_dir(&repo.path)
+            .args(["merge", "--no-edit", THEIRS])
+            .output()?;
+        if merge.status.success() || !repo.path.join(".git").join("MERGE_HEAD").exists() {
+            return Err(FixtureError::Git(format!(
+                "git merge {} did not stop on conflicts: {}",
+                THEIRS,
+                String::from_utf8_lossy(&merge.stdout)
+            )));
+        }
+        Ok(repo)
+    }
+}
+
+/// Stages of `rel_path` in the index of the repository at `repo`, as
+/// `git ls-files --stage` lists them.
+pub fn index_stages(repo: &Path, rel_path: &str) -> Result<Vec<u8>, FixtureError> {
+    let output = git(repo, &["ls-files", "--stage", "--", rel_path])?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2)?.parse().ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_conflict_repo_is_left_mid_merge() {
+        let repo = ConflictRepo::create("test_mid_merge").expect("Failed to create conflict repo");
+        assert!(repo.path.join(".git/MERGE_HEAD").exists());
+
+        assert_eq!(index_stages(&repo.path, BOTH_MODIFIED).unwrap(), [1, 2, 3]);
+        assert_eq!(index_stages(&repo.path, BOTH_ADDED).unwrap(), [2, 3]);
+        assert_eq!(index_stages(&repo.path, DELETED_BY_THEM).unwrap(), [1, 2]);
+        assert_eq!(index_stages(&repo.path, CLEAN).unwrap(), [0]);
+        let unmerged = git(&repo.path, &["diff", "--name-only", "--diff-filter=U"]).unwrap();
+        let unmerged = String::from_utf8_lossy(&unmerged.stdout);
+        assert_eq!(unmerged.lines().collect::<Vec<_>>(), CONFLICTED_PATHS);
+
+        let conflicted = fs::read_to_string(repo.path.join(BOTH_MODIFIED)).unwrap();
+        assert!(conflicted.contains("<<<<<<< HEAD\nline 2 from master\n"));
+        assert!(conflicted.contains("=======\nline 2 from feature\n"));
+        assert!(conflicted.contains(">>>>>>> feature\n"));
+        assert_eq!(
+            fs::read_to_string(repo.path.join(CLEAN)).unwrap(),
+            "changed on feature\n"
+        );
+    }
+}
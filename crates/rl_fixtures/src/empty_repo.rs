@@ -0,0 +1,96 @@
+//! Repositories with no commits at all: one just made by `git init`, and
+//! one with files staged that were never committed. HEAD names
+//! [`BRANCH`] in both, but no ref exists for it yet.
+//!
+//! Unlike [`crate::head_states_repo`]'s unborn branch, there is no other
+//! branch and no history anywhere in the repository.
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::PathBuf;
+
+/// Branch HEAD names
+pub const BRANCH: &str = "master";
+/// Files staged in [`EmptyState::Staged`]
+pub const STAGED: &[&str] = &["README.md", "src/main.rs"];
+/// File left untracked in [`EmptyState::Staged`]
+pub const UNTRACKED: &str = "notes.txt";
+
+/// What the repository holds besides its empty history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyState {
+    /// Nothing: no index and an empty working tree
+    Fresh,
+    /// [`STAGED`] added to the index, and [`UNTRACKED`] beside them
+    Staged,
+}
+
+impl EmptyState {
+    fn name(self) -> &'static str {
+        match self {
+            EmptyState::Fresh => "fresh",
+            EmptyState::Staged => "staged",
+        }
+    }
+}
+
+/// A generated repository with no commits
+pub struct EmptyRepo {
+    pub path: PathBuf,
+}
+
+impl EmptyRepo {
+    /// Build the repository at
+    /// `target/rl_fixtures/empty/<name>/<state>/repo`, reusing one already
+    /// there if it is still empty.
+    pub fn create(name: &str, state: EmptyState) -> Result<EmptyRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("empty")
+            .join(name)
+            .join(state.name())
+            .join("repo");
+        let mut builder = FixtureBuilder::new();
+        if state == EmptyState::Staged {
+            for file in STAGED {
+                builder = builder.file(file, &format!("staged {}\n", file));
+            }
+            builder = builder.file(UNTRACKED, "never added\n");
+        }
+        let built = builder.build_at(path)?;
+        if state == EmptyState::Staged {
+            let mut args = vec!["add", "--"];
+            args.extend(STAGED);
+            git(&built.path, &args)?;
+        }
+        Ok(EmptyRepo { path: built.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_repos_have_no_commits() {
+        for state in [EmptyState::Fresh, EmptyState::Staged] {
+            let repo = EmptyRepo::create("test_empty", state).unwrap();
+            let head = git(&repo.path, &["rev-parse", "--verify", "--quiet", "HEAD"]);
+            assert!(head.is_err(), "{:?} has a HEAD", state);
+            let refs = git(&repo.path, &["for-each-ref"]).unwrap();
+            assert!(refs.stdout.is_empty());
+
+            let staged = git(&repo.path, &["ls-files"]).unwrap();
+            let staged: Vec<_> = String::from_utf8_lossy(&staged.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect();
+            match state {
+                EmptyState::Fresh => assert!(staged.is_empty()),
+                EmptyState::Staged => assert_eq!(staged, STAGED),
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Repositories whose HEAD is not on a branch with history of its own:
+//! detached at a tag, on an orphan branch whose history is unrelated to
+//! `master`'s, or on an unborn branch that has no commits yet.
+//!
+//! Each starts from the same history: two commits on `master`, the first
+//! tagged [`TAG`], and one commit on [`ORPHAN`], which shares none of it.
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::PathBuf;
+
+/// Branch with the main history
+pub const MASTER: &str = "master";
+/// Annotated tag on the first commit of `master`
+pub const TAG: &str = "v1.0";
+/// Branch with one commit, unrelated to `master`
+pub const ORPHAN: &str = "orphan";
+/// Branch checked out by `git checkout --orphan`, with no commits yet
+pub const UNBORN: &str = "unborn";
+
+/// Where HEAD is left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadState {
+    /// Detached at the commit [`TAG`] points to
+    DetachedAtTag,
+    /// On [`ORPHAN`]
+    Orphan,
+    /// On [`UNBORN`], with `master`'s files still staged, as
+    /// `git checkout --orphan` leaves them
+    Unborn,
+}
+
+impl HeadState {
+    fn name(self) -> &'static str {
+        match self {
+            HeadState::DetachedAtTag => "detached",
+            HeadState::Orphan => "orphan",
+            HeadState::Unborn => "unborn",
+        }
+    }
+}
+
+/// A generated repository with HEAD in one of the [`HeadState`]s
+pub struct HeadStatesRepo {
+    pub path: PathBuf,
+}
+
+impl HeadStatesRepo {
+    /// Build the repository at
+    /// `target/rl_fixtures/head_states/<name>/<state>/repo`, reusing the
+    /// history if it is already there, and move HEAD.
+    pub fn create(name: &str, state: HeadState) -> Result<HeadStatesRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("head_states")
+            .join(name)
+            .join(state.name())
+            .join("repo");
+        let built = FixtureBuilder::new()
+            .file("README.md", "# Main history\n")
+            .commit("first commit")
+            .annotated_tag(TAG, "first release\n")
+            .file("README.md", "# Main history\n\nSecond version.\n")
+            .commit("second commit")
+            .orphan(ORPHAN)
+            .file("orphan.txt", "unrelated history\n")
+            .commit("orphan commit")
+            .build_at(path)?;
+
+        let repo = HeadStatesRepo { path: built.path };
+        match state {
+            HeadState::DetachedAtTag => {
+                git(&repo.path, &["checkout", "--quiet", "--detach", TAG])?;
+            }
+            HeadState::Orphan => {}
+            HeadState::Unborn => {
+                git(&repo.path, &["checkout", "--quiet", MASTER])?;
+                git(&repo.path, &["checkout", "--quiet", "--orphan", UNBORN])?;
+            }
+        }
+        Ok(repo)
+    }
+
+    /// Commit id that `revision` names.
+    pub fn rev_parse(&self, revision: &str) -> Result<String, FixtureError> {
+        let revision = format!("{}^{{commit}}", revision);
+        let output = git(&self.path, &["rev-parse", &revision])?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_head_states_leave_head_off_a_born_branch() {
+        let run = |repo: &HeadStatesRepo, args: &[&str]| {
+            Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        let head = |repo: &HeadStatesRepo| {
+            let symbolic = run(repo, &["symbolic-ref", "--quiet", "HEAD"]);
+            let commit = run(repo, &["rev-parse", "--verify", "--quiet", "HEAD"]);
+            (
+                String::from_utf8_lossy(&symbolic.stdout).trim().to_string(),
+                String::from_utf8_lossy(&commit.stdout).trim().to_string(),
+            )
+        };
+
+        let detached = HeadStatesRepo::create("test_states", HeadState::DetachedAtTag).unwrap();
+        assert_eq!(
+            head(&detached),
+            (String::new(), detached.rev_parse(TAG).unwrap())
+        );
+        let kind = run(
+            &detached,
+            &["cat-file", "-t", &format!("refs/tags/{}", TAG)],
+        );
+        assert_eq!(String::from_utf8_lossy(&kind.stdout).trim(), "tag");
+
+        let orphan = HeadStatesRepo::create("test_states", HeadState::Orphan).unwrap();
+        let (branch, commit) = head(&orphan);
+        assert_eq!(branch, format!("refs/heads/{}", ORPHAN));
+        let merge_base = run(&orphan, &["merge-base", &commit, MASTER]);
+        assert!(!merge_base.status.success(), "histories are unrelated");
+
+        let unborn = HeadStatesRepo::create("test_states", HeadState::Unborn).unwrap();
+        assert_eq!(
+            head(&unborn),
+            (format!("refs/heads/{}", UNBORN), String::new())
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Large repositories generated from a few parameters, for benchmarks
+//! that need the size of a real project without cloning one.
+//!
+//! A [`LargeRepoSpec`] fixes the shape: how many commits, over how many
+//! files, how often a side branch is merged, and the seed that picks what
+//! each commit changes. The same spec gives the same repository, down to
+//! its commit ids, on every machine: choices come from a generator written
+//! out below rather than one whose sequence a dependency update could
+//! change, and every commit has the same author and a fixed date.
+
+use crate::synth_repo::{FixtureError, SynthRepo};
+use crate::{fast_import, git};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lines in every file; each change rewrites one of them
+const FILE_LINES: usize = 8;
+
+/// Files in each directory under `src/`
+const FILES_PER_DIR: usize = 256;
+
+/// Files a commit changes at most
+const MAX_FILES_PER_COMMIT: usize = 4;
+
+/// Commits on a merged side branch at most
+const MAX_SIDE_COMMITS: usize = 3;
+
+/// Git config key recording the spec a repository was built from
+const SPEC_KEY: &str = "rl-fixtures.spec";
+
+/// Version of the history written for a spec. Bump it with any change to
+/// what is generated, so repositories built before are built again rather
+/// than taken for the new history.
+const GENERATOR_VERSION: u32 = 1;
+
+/// Shape of a generated repository
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LargeRepoSpec {
+    /// Commits on `master`'s first-parent line; merged side branches add
+    /// their own
+    pub commits: usize,
+    /// Files in the tree, all added by the first commit
+    pub files: usize,
+    /// Share of commits, from 0 to 1, that merge a side branch of one to
+    /// three commits forked from the commit before
+    pub branchiness: f64,
+    /// Seed picking which files each commit changes and where branches are
+    pub seed: u64,
+}
+
+impl LargeRepoSpec {
+    /// Name of the fixture, including every parameter
+    pub fn name(&self) -> String {
+        format!(
+            "large_{}c_{}f_{}b_{}s",
+            self.commits, self.files, self.branchiness, self.seed
+        )
+    }
+
+    /// What [`SPEC_KEY`] records for a repository built from this spec
+    fn built_as(&self) -> String {
+        format!("{} v{}", self.name(), GENERATOR_VERSION)
+    }
+
+    /// Write the repository's history as a `git fast-import` stream.
+    fn write_history(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let files = self.files.max(1);
+        let mut rng = SplitMix64(self.seed);
+        // Commit that last wrote each file, by mark
+        let mut versions = vec![1; files];
+
+        let mut mark = 1;
+        begin_commit(out, "refs/heads/master", mark, "add files")?;
+        for (file, &version) in versions.iter().enumerate() {
+            write_file(out, file, version)?;
+        }
+        let mut head = mark;
+
+        for n in 1..self.commits.max(1) {
+            let mut merged = None;
+            let mut side_changes = Vec::new();
+            if n > 1 && rng.chance(self.branchiness) {
+                let mut parent = head;
+                let mut side_versions = versions.clone();
+                for _ in 0..=rng.below(MAX_SIDE_COMMITS) {
+                    mark += 1;
+                    begin_commit(
+                        out,
+                        "refs/heads/side",
+                        mark,
+                        &format!("side change {}", mark),
+                    )?;
+                    writeln!(out, "from :{}", parent)?;
+                    for file in pick_files(&mut rng, files) {
+                        side_versions[file] = mark;
+                        write_file(out, file, mark)?;
+                        side_changes.push(file);
+                    }
+                    parent = mark;
+                }
+                // The merge takes the side branch's version of what it changed
+                for &file in &side_changes {
+                    versions[file] = side_versions[file];
+                }
+                merged = Some(parent);
+            }
+
+            mark += 1;
+            let message = match merged {
+                Some(side) => format!("merge side branch at :{}", side),
+                None => format!("change {}", n),
+            };
+            begin_commit(out, "refs/heads/master", mark, &message)?;
+            writeln!(out, "from :{}", head)?;
+            if let Some(side) = merged {
+                writeln!(out, "merge :{}", side)?;
+            }
+            let own = pick_files(&mut rng, files);
+            for &file in &own {
+                versions[file] = mark;
+            }
+            side_changes.sort_unstable();
+            side_changes.dedup();
+            for file in side_changes.into_iter().chain(own) {
+                write_file(out, file, versions[file])?;
+            }
+            head = mark;
+        }
+        Ok(())
+    }
+}
+
+/// One to [`MAX_FILES_PER_COMMIT`] different files out of `files`.
+fn pick_files(rng: &mut SplitMix64, files: usize) -> Vec<usize> {
+    let mut picked = Vec::new();
+    for _ in 0..=rng.below(MAX_FILES_PER_COMMIT) {
+        let file = rng.below(files);
+        if !picked.contains(&file) {
+            picked.push(file);
+        }
+    }
+    picked
+}
+
+/// SplitMix64, small and fully specified, so a seed means the same
+/// repository everywhere
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// True with probability `p`
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+/// Start commit `mark` on `branch`, a second after the one before it. With
+/// no `author` line, the committer and date stand for the author too.
+fn begin_commit(
+    out: &mut impl Write,
+    branch: &str,
+    mark: usize,
+    message: &str,
+) -> std::io::Result<()> {
+    writeln!(out, "commit {}", branch)?;
+    writeln!(out, "mark :{}", mark)?;
+    writeln!(
+        out,
+        "committer Test User <test@example.com> {} +0000",
+        1_700_000_000 + mark
+    )?;
+    writeln!(out, "data {}\n{}", message.len(), message)
+}
+
+/// Write `file` as the commit `version` left it: one of its lines names
+/// that commit, so each change is a small diff.
+fn write_file(out: &mut impl Write, file: usize, version: usize) -> std::io::Result<()> {
+    let changed = version % FILE_LINES;
+    let content: String = (0..FILE_LINES)
+        .map(|line| match line == changed {
+            true => format!("file {} line {} from commit {}\n", file, line, version),
+            false => format!("file {} line {}\n", file, line),
+        })
+        .collect();
+    writeln!(
+        out,
+        "M 100644 inline src/{}/file_{}.txt",
+        file / FILES_PER_DIR,
+        file
+    )?;
+    writeln!(out, "data {}\n{}", content.len(), content)
+}
+
+/// A generated large repository
+pub struct LargeRepo {
+    pub path: PathBuf,
+}
+
+impl LargeRepo {
+    /// The repository for `spec` under `target/rl_fixtures/large`,
+    /// generating it the first time.
+    pub fn ensure(spec: &LargeRepoSpec) -> Result<LargeRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("large")
+            .join(spec.name())
+            .join("repo");
+        Self::ensure_at(spec, &path)
+    }
+
+    /// Whether `path` holds a repository generated from `spec`.
+    pub fn is_built(spec: &LargeRepoSpec, path: &Path) -> bool {
+        let built_from = Command::new("git")
+            .current_dir(path)
+            .args(["config", SPEC_KEY])
+            .output();
+        path.join(".git").exists()
+            && built_from.is_ok_and(|output| {
+                String::from_utf8_lossy(&output.stdout).trim() == spec.built_as()
+            })
+    }
+
+    /// The repository for `spec` at `path`, generating it unless `path`
+    /// already holds one generated from the same spec.
+    pub fn ensure_at(spec: &LargeRepoSpec, path: &Path) -> Result<LargeRepo, FixtureError> {
+        if Self::is_built(spec, path) {
+            return Ok(LargeRepo {
+                path: path.to_path_buf(),
+            });
+        }
+
+        // Build it aside, so an interrupted build is never mistaken for one
+        // that finished
+        let building = path.with_extension("building");
+        for dir in [building.as_path(), path] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        fs::create_dir_all(&building)?;
+        git(&building, &["init", "--quiet"])?;
+        git(&building, &["symbolic-ref", "HEAD", "refs/heads/master"])?;
+        fast_import(&building, |out| spec.write_history(out))?;
+        git(&building, &["reset", "--hard", "--quiet"])?;
+        git(&building, &["config", SPEC_KEY, &spec.built_as()])?;
+        fs::rename(&building, path)?;
+
+        Ok(LargeRepo {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_repo_history_is_pinned() {
+        let spec = LargeRepoSpec {
+            commits: 2000,
+            files: 300,
+            branchiness: 0.2,
+            seed: 42,
+        };
+        let repo = LargeRepo::ensure(&spec).expect("Failed to create large repo");
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+
+        // Goldens and benchmarks rely on this history; a change to it must
+        // come with a new GENERATOR_VERSION and a new id here
+        assert_eq!(
+            git(&["rev-parse", "HEAD"]),
+            "0a99711a9aede08fe27cd7d802f5a3ed4d79bf5d"
+        );
+        assert_eq!(
+            git(&[
+                "log",
+                "-1",
+                "--format=%an <%ae> %ad",
+                "--date=raw",
+                "HEAD~1"
+            ]),
+            "Test User <test@example.com> 1700002724 +0000"
+        );
+    }
+}
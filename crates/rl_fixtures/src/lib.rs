@@ -4,7 +4,75 @@
 //! with various edge cases (merges, renames, conflicts, large files)
 //! for testing purposes.
 
+pub mod broken_repo;
+pub mod builder;
+mod cache;
+pub mod conflict_repo;
+pub mod empty_repo;
+pub mod head_states_repo;
+pub mod large_repo;
+pub mod malformed;
+pub mod mixed_status_repo;
+pub mod modes_repo;
+#[cfg(unix)]
+pub mod non_utf8_repo;
+pub mod pathological;
+pub mod random_repo;
 pub mod synth_repo;
+pub mod tags_repo;
+pub mod topology_repo;
+
+use std::ffi::OsStr;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{ChildStdin, Command, Output, Stdio};
+use synth_repo::FixtureError;
+
+/// Run git in `repo`, failing with what it printed if it fails.
+pub(crate) fn git<S: AsRef<OsStr>>(repo: &Path, args: &[S]) -> Result<Output, FixtureError> {
+    let output = Command::new("git").current_dir(repo).args(args).output()?;
+    if !output.status.success() {
+        let command: Vec<_> = args
+            .iter()
+            .map(|arg| arg.as_ref().to_string_lossy())
+            .collect();
+        return Err(FixtureError::Git(format!(
+            "git {} failed: {}",
+            command.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(output)
+}
+
+/// Run `git fast-import` in `repo` on the stream `write` writes, failing
+/// with what git printed if it fails.
+pub(crate) fn fast_import<T>(
+    repo: &Path,
+    write: impl FnOnce(&mut BufWriter<ChildStdin>) -> std::io::Result<T>,
+) -> Result<T, FixtureError> {
+    let mut child = Command::new("git")
+        .current_dir(repo)
+        .args(["fast-import", "--quiet"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    // Closed once written, so that git sees the end of the stream
+    let written = {
+        let mut out = BufWriter::new(stdin);
+        write(&mut out).and_then(|value| out.flush().map(|()| value))
+    };
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(FixtureError::Git(format!(
+            "git fast-import failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(written?)
+}
 
 /// Repository generator for creating synthetic test repositories.
 pub struct RepoGenerator {
@@ -0,0 +1,115 @@
+//! Malformed and truncated git output, for property tests of the parsers
+//! that read it.
+//!
+//! [`garbled`] starts from a sample of well-formed output and damages it
+//! the ways output from a crashed, killed or differently configured git
+//! can be damaged: cut off at any byte, with separators dropped, doubled or
+//! swapped, with records repeated, with numbers at the edges of their
+//! range, or replaced by arbitrary bytes outright.
+//! Starting from real output reaches far deeper into a parser than random
+//! bytes alone, which rarely get past its first field.
+
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+/// Bytes that separate fields and records in git's porcelain formats
+const SEPARATORS: &[u8] = b"\0\n\t\x1f ";
+
+/// Numbers that overflow or underflow the integer types parsers read into
+const BOUNDARY_NUMBERS: &[&str] = &[
+    "0",
+    "-1",
+    "4294967295",
+    "4294967296",
+    "18446744073709551615",
+    "99999999999999999999",
+];
+
+/// One way of damaging output
+#[derive(Debug, Clone)]
+enum Damage {
+    /// Cut the output off
+    Truncate(Index),
+    /// Drop a byte
+    Delete(Index),
+    /// Add a byte
+    Insert(Index, u8),
+    /// Overwrite a byte
+    Replace(Index, u8),
+    /// Repeat the bytes between two positions
+    Repeat(Index, Index),
+    /// Swap the next number for one at the edge of its range
+    Number(Index, &'static str),
+}
+
+impl Damage {
+    fn apply(&self, output: &mut Vec<u8>) {
+        let len = output.len();
+        match self {
+            Self::Truncate(at) => output.truncate(at.index(len + 1)),
+            Self::Delete(at) if len > 0 => {
+                output.remove(at.index(len));
+            }
+            Self::Insert(at, byte) => output.insert(at.index(len + 1), *byte),
+            Self::Replace(at, byte) if len > 0 => output[at.index(len)] = *byte,
+            Self::Repeat(from, to) => {
+                let (from, to) = (from.index(len + 1), to.index(len + 1));
+                let (start, end) = (from.min(to), from.max(to));
+                let span = output[start..end].to_vec();
+                output.splice(end..end, span);
+            }
+            Self::Number(at, number) => {
+                let at = at.index(len + 1);
+                let start = output[at..]
+                    .iter()
+                    .position(u8::is_ascii_digit)
+                    .map_or(len, |offset| at + offset);
+                let end = output[start..]
+                    .iter()
+                    .position(|byte| !byte.is_ascii_digit())
+                    .map_or(len, |offset| start + offset);
+                output.splice(start..end, number.bytes());
+            }
+            Self::Delete(_) | Self::Replace(..) => {}
+        }
+    }
+}
+
+/// A byte to add to output: usually a separator, sometimes anything.
+fn damaging_byte() -> impl Strategy<Value = u8> {
+    prop_oneof![
+        3 => prop::sample::select(SEPARATORS),
+        1 => any::<u8>(),
+    ]
+}
+
+/// `valid` output damaged by up to eight changes, or now and then
+/// replaced by arbitrary bytes.
+pub fn garbled(valid: &[u8]) -> impl Strategy<Value = Vec<u8>> {
+    let valid = valid.to_vec();
+    let damage = prop_oneof![
+        any::<Index>().prop_map(Damage::Truncate),
+        any::<Index>().prop_map(Damage::Delete),
+        (any::<Index>(), damaging_byte()).prop_map(|(at, byte)| Damage::Insert(at, byte)),
+        (any::<Index>(), damaging_byte()).prop_map(|(at, byte)| Damage::Replace(at, byte)),
+        (any::<Index>(), any::<Index>()).prop_map(|(from, to)| Damage::Repeat(from, to)),
+        (any::<Index>(), prop::sample::select(BOUNDARY_NUMBERS))
+            .prop_map(|(at, number)| Damage::Number(at, number)),
+    ];
+    prop_oneof![
+        9 => prop::collection::vec(damage, 0..8).prop_map(move |damage| {
+            let mut output = valid.clone();
+            for damage in &damage {
+                damage.apply(&mut output);
+            }
+            output
+        }),
+        1 => prop::collection::vec(any::<u8>(), 0..256),
+    ]
+}
+
+/// Like [`garbled`], for parsers that take text; invalid UTF-8 is replaced
+/// as `String::from_utf8_lossy` would.
+pub fn garbled_str(valid: &str) -> impl Strategy<Value = String> {
+    garbled(valid.as_bytes()).prop_map(|output| String::from_utf8_lossy(&output).into_owned())
+}
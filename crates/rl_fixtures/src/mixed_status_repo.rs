@@ -0,0 +1,109 @@
+//! A repository whose index and working tree disagree, each path changed
+//! in the index, in the working tree, or in both, so that the two columns
+//! of `git status --porcelain` have to be read apart.
+//!
+//! | Path                | Index             | Working tree | Porcelain |
+//! |---------------------|-------------------|--------------|-----------|
+//! | [`STAGED`]          | modified          | as staged    | `M `      |
+//! | [`UNSTAGED`]        | unchanged         | modified     | ` M`      |
+//! | [`STAGED_MODIFIED`] | modified          | modified     | `MM`      |
+//! | [`STAGED_DELETED`]  | deleted           | deleted      | `D `      |
+//! | [`RENAMED_TO`]      | renamed from [`RENAMED_FROM`] | modified | `RM` |
+//!
+//! The commit they all start from is the same on every run.
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::fs;
+use std::path::PathBuf;
+
+/// File whose change is staged and not changed again
+pub const STAGED: &str = "staged.txt";
+/// File changed in the working tree only
+pub const UNSTAGED: &str = "unstaged.txt";
+/// File whose change is staged, then changed again without staging
+pub const STAGED_MODIFIED: &str = "staged_modified.txt";
+/// File removed with `git rm`
+pub const STAGED_DELETED: &str = "staged_deleted.txt";
+/// File moved to [`RENAMED_TO`] with `git mv`
+pub const RENAMED_FROM: &str = "renamed_from.txt";
+/// Where [`RENAMED_FROM`] was moved, then changed without staging
+pub const RENAMED_TO: &str = "renamed_to.txt";
+
+/// Contents of every file in the commit, long enough that a changed line
+/// leaves a renamed file similar enough for git to still see the rename
+const ORIGINAL: &str = "line 1\nline 2\nline 3\nline 4\nline 5\nline 6\nline 7\nline 8\n";
+
+/// A generated repository with staged and unstaged changes
+pub struct MixedStatusRepo {
+    pub path: PathBuf,
+}
+
+impl MixedStatusRepo {
+    /// Build the repository at `target/rl_fixtures/mixed_status/<name>/repo`,
+    /// reusing the commit if one is already there.
+    pub fn create(name: &str) -> Result<MixedStatusRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("mixed_status")
+            .join(name)
+            .join("repo");
+        let built = [
+            STAGED,
+            UNSTAGED,
+            STAGED_MODIFIED,
+            STAGED_DELETED,
+            RENAMED_FROM,
+        ]
+        .into_iter()
+        .fold(FixtureBuilder::new(), |builder, file| {
+            builder.file(file, ORIGINAL)
+        })
+        .commit("initial commit")
+        .build_at(path)?;
+
+        let repo = MixedStatusRepo { path: built.path };
+        repo.write(STAGED, &format!("{}staged\n", ORIGINAL))?;
+        repo.write(STAGED_MODIFIED, &format!("{}staged\n", ORIGINAL))?;
+        git(&repo.path, &["add", STAGED, STAGED_MODIFIED])?;
+        git(&repo.path, &["rm", "--quiet", STAGED_DELETED])?;
+        git(&repo.path, &["mv", RENAMED_FROM, RENAMED_TO])?;
+
+        repo.write(UNSTAGED, &format!("{}unstaged\n", ORIGINAL))?;
+        repo.write(STAGED_MODIFIED, &format!("{}staged\nunstaged\n", ORIGINAL))?;
+        repo.write(RENAMED_TO, &format!("{}unstaged\n", ORIGINAL))?;
+        Ok(repo)
+    }
+
+    fn write(&self, rel_path: &str, content: &str) -> Result<(), FixtureError> {
+        Ok(fs::write(self.path.join(rel_path), content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_status_repo_has_each_column_combination() {
+        let repo = MixedStatusRepo::create("test_mixed_status").unwrap();
+        let output = git(&repo.path, &["status", "--porcelain=v1"]).unwrap();
+        let mut lines: Vec<_> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                format!(" M {}", UNSTAGED),
+                format!("D  {}", STAGED_DELETED),
+                format!("M  {}", STAGED),
+                format!("MM {}", STAGED_MODIFIED),
+                format!("RM {} -> {}", RENAMED_FROM, RENAMED_TO),
+            ]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! A repository whose history changes file modes and types rather than
+//! contents: executable bits flipped on and off, symlinks added and
+//! retargeted, and a regular file turned into a symlink.
+//!
+//! The history is declared with [`FixtureBuilder`], which records modes
+//! as given whatever the filesystem supports, so the commits are the same
+//! on every platform. Only the checkout differs: where git can't make
+//! symlinks or keep executable bits, as on Windows, links are checked out
+//! as small files holding their target and modes are not tracked.
+
+use crate::builder::FixtureBuilder;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::PathBuf;
+
+/// Script whose executable bit `M1` sets and `M3` clears
+pub const SCRIPT: &str = "script.sh";
+/// File the symlinks point to
+pub const TARGET: &str = "target.txt";
+/// File in a directory, which `LINK` points to from `M3`
+pub const DIR_FILE: &str = "dir/file.txt";
+/// Symlink to `TARGET`, retargeted to `DIR_FILE` by `M3`
+pub const LINK: &str = "link";
+/// Symlink to `dir`, added by `M2`
+pub const DIR_LINK: &str = "dir_link";
+/// Regular file until `M2` turns it into a symlink to `TARGET`
+pub const BECOMES_LINK: &str = "becomes_link.txt";
+
+/// Git mode of a regular file
+const FILE: &str = "100644";
+/// Git mode of an executable file
+const EXECUTABLE: &str = "100755";
+/// Git mode of a symlink
+const SYMLINK: &str = "120000";
+
+/// Tree of each commit, tagged `M0` to `M3`: path, mode and contents, a
+/// symlink's contents being its target
+const COMMITS: &[&[(&str, &str, &str)]] = &[
+    &[
+        (SCRIPT, FILE, "#!/bin/sh\necho hello\n"),
+        (TARGET, FILE, "the target\n"),
+        (DIR_FILE, FILE, "in a directory\n"),
+        (LINK, SYMLINK, TARGET),
+        (BECOMES_LINK, FILE, "a regular file for now\n"),
+    ],
+    &[
+        (SCRIPT, EXECUTABLE, "#!/bin/sh\necho hello\n"),
+        (TARGET, FILE, "the target\n"),
+        (DIR_FILE, FILE, "in a directory\n"),
+        (LINK, SYMLINK, TARGET),
+        (BECOMES_LINK, FILE, "a regular file for now\n"),
+    ],
+    &[
+        (SCRIPT, EXECUTABLE, "#!/bin/sh\necho hello\n"),
+        (TARGET, FILE, "the target\n"),
+        (DIR_FILE, FILE, "in a directory\n"),
+        (LINK, SYMLINK, TARGET),
+        (DIR_LINK, SYMLINK, "dir"),
+        (BECOMES_LINK, SYMLINK, TARGET),
+    ],
+    &[
+        (SCRIPT, FILE, "#!/bin/sh\necho hello\n"),
+        (TARGET, FILE, "the target\n"),
+        (DIR_FILE, FILE, "in a directory\n"),
+        (LINK, SYMLINK, DIR_FILE),
+        (DIR_LINK, SYMLINK, "dir"),
+        (BECOMES_LINK, SYMLINK, TARGET),
+    ],
+];
+
+/// A generated repository of mode and type changes
+pub struct ModesRepo {
+    pub path: PathBuf,
+}
+
+impl ModesRepo {
+    /// The repository under `target/rl_fixtures/modes/<name>`, generating
+    /// it again whenever its definition here changes.
+    pub fn ensure(name: &str) -> Result<ModesRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("modes")
+            .join(name)
+            .join("repo");
+        let mut builder = FixtureBuilder::new();
+        let mut previous: &[(&str, &str, &str)] = &[];
+        for (n, tree) in COMMITS.iter().enumerate() {
+            // Each commit's tree replaces the one before
+            for (path, _, _) in previous {
+                builder = builder.delete(path);
+            }
+            for &(path, mode, content) in tree.iter() {
+                builder = match mode {
+                    EXECUTABLE => builder.executable(path, content),
+                    SYMLINK => builder.symlink(path, content),
+                    _ => builder.file(path, content),
+                };
+            }
+            let message = format!("M{}", n);
+            builder = builder.commit(&message).tag(&message);
+            previous = tree;
+        }
+        let repo = builder.build_at(path)?;
+        Ok(ModesRepo { path: repo.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn test_modes_repo_records_modes_and_types() {
+        let repo = ModesRepo::ensure("test_modes").expect("Failed to create modes repo");
+        let output = Command::new("git")
+            .current_dir(&repo.path)
+            .args(["diff", "--raw", "M1", "M2"])
+            .output()
+            .unwrap();
+        let raw = String::from_utf8_lossy(&output.stdout);
+        assert!(raw.contains(&format!(":{} {} ", FILE, SYMLINK)), "{}", raw);
+        assert!(raw.contains(&format!("T\t{}", BECOMES_LINK)), "{}", raw);
+
+        // Checkouts only show links and modes where the platform has them
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let link = fs::read_link(repo.path.join(LINK)).unwrap();
+            assert_eq!(link, std::path::Path::new(DIR_FILE));
+            assert!(fs::symlink_metadata(repo.path.join(BECOMES_LINK))
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            let mode = fs::metadata(repo.path.join(SCRIPT))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0, "M3 clears the executable bit");
+        }
+    }
+}
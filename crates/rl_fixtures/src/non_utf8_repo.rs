@@ -0,0 +1,103 @@
+//! A repository whose file names are Latin-1 bytes that aren't valid
+//! UTF-8, as a repository made on a system with a Latin-1 locale has them.
+//!
+//! Such names can only be made where paths are bytes, so this module is
+//! only built on POSIX systems, and tests that use it are skipped
+//! elsewhere.
+//!
+//! [`FIRST`] adds [`CAFE`], [`RESUME`] and, for contrast, [`UTF8`], a name
+//! with the same characters in UTF-8. [`SECOND`] changes [`CAFE`] and
+//! renames [`RESUME`] to [`RENAMED`]. The working tree then changes
+//! [`CAFE`] again and adds [`UNTRACKED`]. Commit ids are the same on every
+//! run.
+
+use crate::builder::FixtureBuilder;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::PathBuf;
+
+/// Tag on the first commit
+pub const FIRST: &str = "first";
+/// Tag on the second commit, where `master` ends
+pub const SECOND: &str = "second";
+/// `café.txt` in Latin-1
+pub const CAFE: &[u8] = b"caf\xe9.txt";
+/// `naïve/résumé.md` in Latin-1
+pub const RESUME: &[u8] = b"na\xefve/r\xe9sum\xe9.md";
+/// Where [`SECOND`] moves [`RESUME`]: `naïve/résumé-final.md` in Latin-1
+pub const RENAMED: &[u8] = b"na\xefve/r\xe9sum\xe9-final.md";
+/// `café.txt` in UTF-8, which needs no special handling
+pub const UTF8: &str = "café.txt";
+/// `über.txt` in Latin-1, left untracked
+pub const UNTRACKED: &[u8] = b"\xfcber.txt";
+
+/// Contents of [`RESUME`], long enough for git to see it renamed
+const RESUME_CONTENT: &str = "# R\u{e9}sum\u{e9}\n\nline 1\nline 2\nline 3\nline 4\n";
+
+/// A generated repository with file names that aren't UTF-8
+pub struct NonUtf8Repo {
+    pub path: PathBuf,
+}
+
+impl NonUtf8Repo {
+    /// Build the repository at `target/rl_fixtures/non_utf8/<name>/repo`,
+    /// reusing the history if it is already there.
+    pub fn create(name: &str) -> Result<NonUtf8Repo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("non_utf8")
+            .join(name)
+            .join("repo");
+        let built = FixtureBuilder::new()
+            .file(CAFE, "one\n")
+            .file(RESUME, RESUME_CONTENT)
+            .file(UTF8, "one\n")
+            .commit(&format!("{} commit", FIRST))
+            .tag(FIRST)
+            .file(CAFE, "one\ntwo\n")
+            .delete(RESUME)
+            .file(RENAMED, RESUME_CONTENT)
+            .commit(&format!("{} commit", SECOND))
+            .tag(SECOND)
+            .file(CAFE, "one\ntwo\nthree\n")
+            .file(UNTRACKED, "untracked\n")
+            .build_at(path)?;
+        Ok(NonUtf8Repo { path: built.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Output;
+
+    fn run(repo: &NonUtf8Repo, args: &[&[u8]]) -> Output {
+        let args: Vec<&OsStr> = args.iter().map(|arg| OsStr::from_bytes(arg)).collect();
+        git(&repo.path, &args).unwrap()
+    }
+
+    #[test]
+    fn test_non_utf8_repo_keeps_the_bytes_of_its_names() {
+        let repo = NonUtf8Repo::create("test_non_utf8").unwrap();
+        let output = run(
+            &repo,
+            &[b"ls-tree", b"-r", b"-z", b"--name-only", SECOND.as_bytes()],
+        );
+        let mut names: Vec<&[u8]> = output
+            .stdout
+            .split(|&byte| byte == 0)
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort();
+        let mut expected = vec![CAFE, UTF8.as_bytes(), RENAMED];
+        expected.sort();
+        assert_eq!(names, expected);
+
+        let status = run(&repo, &[b"status", b"--porcelain=v1", b"-z"]);
+        let expected = [&b" M "[..], CAFE, b"\0?? ", UNTRACKED, b"\0"].concat();
+        assert_eq!(status.stdout, expected);
+    }
+}
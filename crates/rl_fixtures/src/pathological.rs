@@ -0,0 +1,168 @@
+//! Repositories with worst-case shapes, for benchmarks of bound
+//! enforcement and streaming.
+//!
+//! Each one is written with `git fast-import`, which builds even 100,000
+//! commits or 100MB blobs in seconds, and then checked out.
+
+use crate::synth_repo::{FixtureError, SynthRepo};
+use crate::{fast_import, git};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Files changed by the full-size [`Pathology::WideCommit`]
+pub const WIDE_COMMIT_FILES: usize = 50_000;
+/// Size of the file rewritten by the full-size [`Pathology::HugeDiff`]
+pub const HUGE_DIFF_BYTES: usize = 100 * 1024 * 1024;
+/// Lines of the file in the full-size [`Pathology::LongFile`]
+pub const LONG_FILE_LINES: usize = 1_000_000;
+/// Commits in the full-size [`Pathology::DeepHistory`]
+pub const DEEP_HISTORY_COMMITS: usize = 100_000;
+
+/// Commits that [`Pathology::LongFile`] spreads its lines over
+const LONG_FILE_COMMITS: usize = 4;
+
+/// A worst-case repository shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pathology {
+    /// `HEAD` changes every one of `files` files under `files/`, each a
+    /// few lines long, added by `HEAD~1`
+    WideCommit { files: usize },
+    /// `HEAD` rewrites every line of `huge.txt`, about `bytes` long
+    HugeDiff { bytes: usize },
+    /// `long.txt` has `lines` lines, last changed by four different commits
+    LongFile { lines: usize },
+    /// `commits` commits in a line: the first adds `root.txt` and each one
+    /// after it changes `counter.txt`, so blaming `root.txt` walks them all
+    DeepHistory { commits: usize },
+}
+
+impl Pathology {
+    /// Name of the fixture, including its size
+    pub fn name(&self) -> String {
+        match self {
+            Self::WideCommit { files } => format!("wide_commit_{}", files),
+            Self::HugeDiff { bytes } => format!("huge_diff_{}", bytes),
+            Self::LongFile { lines } => format!("long_file_{}", lines),
+            Self::DeepHistory { commits } => format!("deep_history_{}", commits),
+        }
+    }
+
+    /// Write the repository's history as a `git fast-import` stream.
+    fn write_history(&self, out: &mut impl Write) -> std::io::Result<()> {
+        match *self {
+            Self::WideCommit { files } => {
+                for (commit, version) in ["added", "changed"].into_iter().enumerate() {
+                    begin_commit(out, commit, &format!("{} {} files", version, files))?;
+                    for i in 0..files {
+                        let content = format!(
+                            "file {i}\n{version} by a wide commit\nline 3 of {i}\nline 4 of {i}\n"
+                        );
+                        file(
+                            out,
+                            &format!("files/{}/{}.txt", i / 1000, i),
+                            content.as_bytes(),
+                        )?;
+                    }
+                }
+            }
+            Self::HugeDiff { bytes } => {
+                for (commit, version) in ["before", "after"].into_iter().enumerate() {
+                    let mut content = String::with_capacity(bytes + 64);
+                    let mut line = 0;
+                    while content.len() < bytes {
+                        content.push_str(&format!("line {} of huge.txt, {}\n", line, version));
+                        line += 1;
+                    }
+                    begin_commit(out, commit, &format!("huge.txt {}", version))?;
+                    file(out, "huge.txt", content.as_bytes())?;
+                }
+            }
+            Self::LongFile { lines } => {
+                // The first commit writes every line, each later one rewrites
+                // every LONG_FILE_COMMITS-th line
+                for commit in 0..LONG_FILE_COMMITS {
+                    let mut content = String::new();
+                    for line in 0..lines {
+                        let version = match line % LONG_FILE_COMMITS {
+                            changed if changed != 0 && changed <= commit => changed,
+                            _ => 0,
+                        };
+                        content.push_str(&format!("line {} version {}\n", line + 1, version));
+                    }
+                    begin_commit(out, commit, &format!("long.txt version {}", commit))?;
+                    file(out, "long.txt", content.as_bytes())?;
+                }
+            }
+            Self::DeepHistory { commits } => {
+                for commit in 0..commits.max(1) {
+                    begin_commit(out, commit, &format!("commit {}", commit))?;
+                    if commit == 0 {
+                        file(out, "root.txt", b"added by the root commit\n")?;
+                    }
+                    file(out, "counter.txt", format!("{}\n", commit).as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Start commit number `n` on `master`, a second after the one before it.
+fn begin_commit(out: &mut impl Write, n: usize, message: &str) -> std::io::Result<()> {
+    writeln!(out, "commit refs/heads/master")?;
+    writeln!(
+        out,
+        "committer Test User <test@example.com> {} +0000",
+        1_700_000_000 + n
+    )?;
+    data(out, message.as_bytes())
+}
+
+/// Set `path` to `content` in the commit being written.
+fn file(out: &mut impl Write, path: &str, content: &[u8]) -> std::io::Result<()> {
+    writeln!(out, "M 100644 inline {}", path)?;
+    data(out, content)
+}
+
+fn data(out: &mut impl Write, content: &[u8]) -> std::io::Result<()> {
+    writeln!(out, "data {}", content.len())?;
+    out.write_all(content)?;
+    writeln!(out)
+}
+
+/// A generated worst-case repository
+pub struct PathologicalRepo {
+    pub path: PathBuf,
+}
+
+impl PathologicalRepo {
+    /// The repository for `pathology` under
+    /// `target/rl_fixtures/pathological`, generating it the first time.
+    pub fn ensure(pathology: Pathology) -> Result<PathologicalRepo, FixtureError> {
+        let base = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("pathological")
+            .join(pathology.name());
+        let repo_path = base.join("repo");
+        if repo_path.join(".git").exists() {
+            return Ok(PathologicalRepo { path: repo_path });
+        }
+
+        // Build it aside, so an interrupted build is never mistaken for one
+        // that finished
+        let building = base.join("repo.building");
+        if building.exists() {
+            fs::remove_dir_all(&building)?;
+        }
+        fs::create_dir_all(&building)?;
+        git(&building, &["init", "--quiet"])?;
+        git(&building, &["symbolic-ref", "HEAD", "refs/heads/master"])?;
+        fast_import(&building, |out| pathology.write_history(out))?;
+        git(&building, &["reset", "--hard", "--quiet"])?;
+        fs::rename(&building, &repo_path)?;
+
+        Ok(PathologicalRepo { path: repo_path })
+    }
+}
@@ -0,0 +1,251 @@
+//! Small random repositories, for property tests that compare the engine
+//! with the git CLI.
+//!
+//! [`repo_plan`] generates a [`RepoPlan`]: a few commits on `master` that
+//! write, delete and rename a handful of files, some merging a side branch,
+//! then uncommitted changes, some of them staged. [`RandomRepo::create`]
+//! builds it, writing the history with `git fast-import` so that hundreds
+//! of cases stay quick.
+
+use crate::synth_repo::{FixtureError, SynthRepo};
+use crate::{fast_import, git};
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Paths the edits pick from; none is a prefix directory of another
+pub const PATHS: &[&str] = &[
+    "a.txt",
+    "b.txt",
+    "with space.txt",
+    "dir/c.txt",
+    "dir/d.txt",
+    "dir/sub/e.txt",
+];
+
+/// A change to one or two of the [`PATHS`], by index
+#[derive(Debug, Clone)]
+pub enum Edit {
+    /// Create or overwrite a file with lines picked from a small set, so
+    /// that versions share lines and renames are detected
+    Write { path: usize, lines: Vec<u8> },
+    /// Delete a file, if it exists
+    Delete { path: usize },
+    /// Rename a file, if it exists and the new path doesn't
+    Rename { from: usize, to: usize },
+}
+
+/// One commit on `master`
+#[derive(Debug, Clone)]
+pub struct PlannedCommit {
+    /// Changes the commit makes
+    pub edits: Vec<Edit>,
+    /// Changes made on a side branch off the previous commit, which this
+    /// commit merges; ignored for the first commit
+    pub side: Option<Vec<Edit>>,
+}
+
+/// A change left in the working tree
+#[derive(Debug, Clone)]
+pub struct WorktreeEdit {
+    /// The change
+    pub edit: Edit,
+    /// Whether it is added to the index too
+    pub staged: bool,
+}
+
+/// A random repository to build
+#[derive(Debug, Clone)]
+pub struct RepoPlan {
+    /// Commits on `master`, oldest first
+    pub commits: Vec<PlannedCommit>,
+    /// Uncommitted changes, applied in order
+    pub worktree: Vec<WorktreeEdit>,
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    let path = 0..PATHS.len();
+    prop_oneof![
+        3 => (path.clone(), prop::collection::vec(0u8..8, 0..6))
+            .prop_map(|(path, lines)| Edit::Write { path, lines }),
+        1 => path.clone().prop_map(|path| Edit::Delete { path }),
+        1 => (path.clone(), path).prop_map(|(from, to)| Edit::Rename { from, to }),
+    ]
+}
+
+/// Plans of one to six commits and up to four uncommitted changes.
+pub fn repo_plan() -> impl Strategy<Value = RepoPlan> {
+    let edits = || prop::collection::vec(edit(), 1..4);
+    let commit = (edits(), prop::option::weighted(0.25, edits()))
+        .prop_map(|(edits, side)| PlannedCommit { edits, side });
+    let worktree = (edit(), any::<bool>()).prop_map(|(edit, staged)| WorktreeEdit { edit, staged });
+    (
+        prop::collection::vec(commit, 1..7),
+        prop::collection::vec(worktree, 0..5),
+    )
+        .prop_map(|(commits, worktree)| RepoPlan { commits, worktree })
+}
+
+/// File contents by path
+type Tree = BTreeMap<&'static str, String>;
+
+impl Edit {
+    /// Apply the change to `tree`, returning the paths it touched.
+    fn apply(&self, tree: &mut Tree) -> Vec<&'static str> {
+        match *self {
+            Self::Write { path, ref lines } => {
+                let content = lines
+                    .iter()
+                    .map(|line| format!("line {}\n", line))
+                    .collect();
+                tree.insert(PATHS[path], content);
+                vec![PATHS[path]]
+            }
+            Self::Delete { path } => tree
+                .remove(PATHS[path])
+                .map(|_| PATHS[path])
+                .into_iter()
+                .collect(),
+            Self::Rename { from, to } => {
+                if from == to || tree.contains_key(PATHS[to]) {
+                    return Vec::new();
+                }
+                match tree.remove(PATHS[from]) {
+                    Some(content) => {
+                        tree.insert(PATHS[to], content);
+                        vec![PATHS[from], PATHS[to]]
+                    }
+                    None => Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+/// A generated random repository
+pub struct RandomRepo {
+    pub path: PathBuf,
+}
+
+impl RandomRepo {
+    /// Build `plan` at `target/rl_fixtures/random/<name>/repo`, replacing
+    /// whatever was there.
+    pub fn create(name: &str, plan: &RepoPlan) -> Result<RandomRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("random")
+            .join(name)
+            .join("repo");
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir_all(&path)?;
+
+        git(&path, &["init", "--quiet"])?;
+        git(&path, &["symbolic-ref", "HEAD", "refs/heads/master"])?;
+        let head = fast_import(&path, |out| write_history(out, plan))?;
+        git(&path, &["reset", "--hard", "--quiet"])?;
+
+        // Uncommitted changes, each staged as it is made, so that later
+        // ones can leave the working tree different from the index
+        let mut worktree = head.clone();
+        let mut index: Vec<&str> = head.keys().copied().collect();
+        for change in &plan.worktree {
+            let touched = change.edit.apply(&mut worktree);
+            for file in &touched {
+                let full_path = path.join(file);
+                match worktree.get(file) {
+                    Some(content) => {
+                        fs::create_dir_all(full_path.parent().expect("paths have a parent"))?;
+                        fs::write(&full_path, content)?;
+                    }
+                    None => fs::remove_file(&full_path)?,
+                }
+            }
+            // Paths in neither the index nor the working tree can't be added
+            let staged: Vec<_> = touched
+                .into_iter()
+                .filter(|file| worktree.contains_key(file) || index.contains(file))
+                .collect();
+            if change.staged && !staged.is_empty() {
+                let mut args = vec!["add", "--all", "--"];
+                args.extend(&staged);
+                git(&path, &args)?;
+                index.retain(|file| !staged.contains(file));
+                index.extend(
+                    staged
+                        .into_iter()
+                        .filter(|file| worktree.contains_key(file)),
+                );
+            }
+        }
+
+        Ok(RandomRepo { path })
+    }
+}
+
+/// Write the commits of `plan` as a `git fast-import` stream, returning
+/// the tree of the last one.
+fn write_history(out: &mut impl Write, plan: &RepoPlan) -> std::io::Result<Tree> {
+    let mut tree = Tree::new();
+    let mut mark = 0;
+    for (n, commit) in plan.commits.iter().enumerate() {
+        let mut side_mark = None;
+        if let (Some(side), true) = (&commit.side, n > 0) {
+            let mut side_tree = tree.clone();
+            for edit in side {
+                edit.apply(&mut side_tree);
+            }
+            mark += 1;
+            side_mark = Some(mark);
+            let branch = format!("refs/heads/side-{}", n);
+            write_commit(out, &branch, mark, Some(mark - 1), None, &side_tree)?;
+            // The merge takes the side branch's changes on top of its own
+            for edit in side {
+                edit.apply(&mut tree);
+            }
+        }
+        for edit in &commit.edits {
+            edit.apply(&mut tree);
+        }
+        mark += 1;
+        let parent = (n > 0).then(|| mark - 1 - usize::from(side_mark.is_some()));
+        write_commit(out, "refs/heads/master", mark, parent, side_mark, &tree)?;
+    }
+    Ok(tree)
+}
+
+/// Write commit `mark` to `branch` with every file in `tree`.
+fn write_commit(
+    out: &mut impl Write,
+    branch: &str,
+    mark: usize,
+    parent: Option<usize>,
+    merge: Option<usize>,
+    tree: &Tree,
+) -> std::io::Result<()> {
+    let message = format!("commit {}", mark);
+    writeln!(out, "commit {}", branch)?;
+    writeln!(out, "mark :{}", mark)?;
+    writeln!(
+        out,
+        "committer Test User <test@example.com> {} +0000",
+        1_700_000_000 + mark
+    )?;
+    writeln!(out, "data {}\n{}", message.len(), message)?;
+    if let Some(parent) = parent {
+        writeln!(out, "from :{}", parent)?;
+    }
+    if let Some(merge) = merge {
+        writeln!(out, "merge :{}", merge)?;
+    }
+    writeln!(out, "deleteall")?;
+    for (path, content) in tree {
+        writeln!(out, "M 100644 inline {}", path)?;
+        writeln!(out, "data {}\n{}", content.len(), content)?;
+    }
+    writeln!(out)
+}
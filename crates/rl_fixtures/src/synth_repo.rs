@@ -1,7 +1,10 @@
+use crate::cache;
 use std::fs;
 use std::io::Write;
+use std::ops::Deref;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug)]
 pub enum FixtureError {
@@ -26,10 +29,58 @@ impl From<std::io::Error> for FixtureError {
     }
 }
 
+/// Size of the binary blob added by C4
+pub const LARGE_BINARY_SIZE: usize = 256 * 1024;
+
+/// Contents of the Git LFS pointer added by C4, a file `.gitattributes`
+/// routes through the LFS filter
+pub const LFS_POINTER: &str = "version https://git-lfs.github.com/spec/v1\n\
+     oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+     size 12345\n";
+
 pub struct SynthRepo {
     pub path: PathBuf,
 }
 
+/// Isolated repositories made by this process so far, numbering each
+static ISOLATED: AtomicUsize = AtomicUsize::new(0);
+
+/// A synthetic repository in a temporary directory of its own, for tests
+/// that change the working tree and so can't share one with tests running
+/// alongside. The directory is removed when this is dropped.
+pub struct IsolatedRepo {
+    repo: SynthRepo,
+    root: PathBuf,
+    keep_on_failure: bool,
+}
+
+impl IsolatedRepo {
+    /// Keep the directory when it is dropped by a failing test, to look
+    /// at what the test left behind.
+    pub fn keep_on_failure(mut self) -> Self {
+        self.keep_on_failure = true;
+        self
+    }
+}
+
+impl Deref for IsolatedRepo {
+    type Target = SynthRepo;
+
+    fn deref(&self) -> &SynthRepo {
+        &self.repo
+    }
+}
+
+impl Drop for IsolatedRepo {
+    fn drop(&mut self) {
+        if self.keep_on_failure && std::thread::panicking() {
+            eprintln!("Keeping fixture repository at {}", self.repo.path.display());
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
 impl SynthRepo {
     pub fn ensure(name: &str) -> Result<SynthRepo, FixtureError> {
         // Find workspace root by walking up to find Cargo.toml with [workspace]
@@ -37,21 +88,49 @@ impl SynthRepo {
         let base = workspace_root.join("target").join("rl_fixtures").join(name);
         let repo_path = base.join("repo");
 
+        // The history is whatever this file's code writes
+        let definition = cache::hash(include_str!("synth_repo.rs").as_bytes());
+        if cache::is_cached(&repo_path, &definition) {
+            return Ok(SynthRepo { path: repo_path });
+        }
         if repo_path.exists() {
-            let git_dir = repo_path.join(".git");
-            if git_dir.exists() {
-                return Ok(SynthRepo { path: repo_path });
-            }
+            fs::remove_dir_all(&repo_path)?;
         }
 
         fs::create_dir_all(&repo_path)?;
 
         let repo = SynthRepo { path: repo_path };
         repo.initialize()?;
+        cache::record(&repo.path, &definition)?;
         Ok(repo)
     }
 
-    fn find_workspace_root() -> Result<PathBuf, FixtureError> {
+    /// Build a new repository with the same history as [`SynthRepo::ensure`]
+    /// gives, in a unique temporary directory.
+    pub fn isolated(name: &str) -> Result<IsolatedRepo, FixtureError> {
+        let root = std::env::temp_dir().join(format!(
+            "rl_fixtures-{}-{}-{}",
+            name,
+            std::process::id(),
+            ISOLATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let repo_path = root.join("repo");
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+        fs::create_dir_all(&repo_path)?;
+
+        // Removes the directory if the build fails
+        let isolated = IsolatedRepo {
+            repo: SynthRepo { path: repo_path },
+            root,
+            keep_on_failure: false,
+        };
+        isolated.repo.initialize()?;
+        Ok(isolated)
+    }
+
+    pub(crate) fn find_workspace_root() -> Result<PathBuf, FixtureError> {
         let mut current = std::env::current_dir()?;
         loop {
             let cargo_toml = current.join("Cargo.toml");
@@ -81,6 +160,9 @@ impl SynthRepo {
         self.create_c1()?;
         self.create_c2()?;
         self.create_c3()?;
+        self.create_c4()?;
+        self.create_c5()?;
+        self.create_c6()?;
 
         Ok(())
     }
@@ -139,6 +221,51 @@ impl SynthRepo {
         Ok(())
     }
 
+    /// A larger binary blob, a text file that C5 makes binary and C6 text
+    /// again, and a Git LFS pointer whose object is nowhere to be fetched.
+    fn create_c4(&self) -> Result<(), FixtureError> {
+        self.write_file_binary(
+            "assets/large.bin",
+            &pseudo_random_bytes(LARGE_BINARY_SIZE, 1),
+        )?;
+        self.write_file("flip.dat", "plain text for now\n")?;
+        self.write_file(
+            ".gitattributes",
+            "*.lfs filter=lfs diff=lfs merge=lfs -text\n",
+        )?;
+        self.write_file("model.lfs", LFS_POINTER)?;
+
+        self.run_git(&["add", "."])?;
+        self.run_git(&["commit", "-m", "C4: binary + LFS pointer"])?;
+        self.run_git(&["tag", "C4"])?;
+
+        Ok(())
+    }
+
+    fn create_c5(&self) -> Result<(), FixtureError> {
+        self.write_file_binary("flip.dat", b"binary now\0\x01\x02\xff\n")?;
+
+        self.run_git(&["add", "."])?;
+        self.run_git(&["commit", "-m", "C5: text to binary"])?;
+        self.run_git(&["tag", "C5"])?;
+
+        Ok(())
+    }
+
+    fn create_c6(&self) -> Result<(), FixtureError> {
+        self.write_file("flip.dat", "plain text again\n")?;
+        self.write_file_binary(
+            "assets/large.bin",
+            &pseudo_random_bytes(LARGE_BINARY_SIZE, 2),
+        )?;
+
+        self.run_git(&["add", "."])?;
+        self.run_git(&["commit", "-m", "C6: binary to text + binary change"])?;
+        self.run_git(&["tag", "C6"])?;
+
+        Ok(())
+    }
+
     fn write_file(&self, rel_path: &str, content: &str) -> Result<(), FixtureError> {
         let full_path = self.path.join(rel_path);
         if let Some(parent) = full_path.parent() {
@@ -185,6 +312,21 @@ impl SynthRepo {
     }
 }
 
+/// `len` bytes that are the same for the same `seed` on every run, with
+/// no pattern for git's delta compression to find.
+fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            // xorshift64*
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 56) as u8
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,5 +349,43 @@ mod tests {
             !repo.path.join("new.txt").exists(),
             "new.txt should not exist (deleted in C3)"
         );
+        assert_eq!(
+            fs::read(repo.path.join("assets/large.bin")).unwrap().len(),
+            LARGE_BINARY_SIZE
+        );
+        assert_eq!(
+            fs::read_to_string(repo.path.join("flip.dat")).unwrap(),
+            "plain text again\n",
+            "flip.dat should be text again (C6)"
+        );
+    }
+
+    #[test]
+    fn test_isolated_repos_are_separate_and_cleaned_up() {
+        let first = SynthRepo::isolated("test_isolated").unwrap();
+        let second = SynthRepo::isolated("test_isolated").unwrap();
+        assert_ne!(first.path, second.path);
+
+        first.modify_working_tree("a.txt", "changed\n").unwrap();
+        let unchanged = fs::read_to_string(second.path.join("a.txt")).unwrap();
+        assert!(!unchanged.ends_with("changed\n"));
+
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+
+        // A failing test keeps its repository when asked to
+        let kept = SynthRepo::isolated("test_isolated")
+            .unwrap()
+            .keep_on_failure();
+        let path = kept.path.clone();
+        let failed = std::thread::spawn(move || {
+            let _kept = kept;
+            panic!("test failure");
+        })
+        .join();
+        assert!(failed.is_err());
+        assert!(path.exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
     }
 }
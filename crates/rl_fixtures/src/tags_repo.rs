@@ -0,0 +1,141 @@
+//! A repository with each kind of tag: lightweight, annotated with a
+//! message of several paragraphs, and signed.
+//!
+//! Signing needs `ssh-keygen`, to make a throwaway key next to the
+//! repository, and a git that signs with SSH keys. Where either is missing
+//! the signed tag is left out and [`TagsRepo::signed`] says so, for tests
+//! to skip what depends on it.
+
+use crate::builder::FixtureBuilder;
+use crate::git;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lightweight tag on the first commit
+pub const LIGHTWEIGHT: &str = "v0.1";
+/// Annotated tag on the second commit
+pub const ANNOTATED: &str = "v1.0";
+/// Message of [`ANNOTATED`], as git stores it
+pub const ANNOTATED_MESSAGE: &str =
+    "Release 1.0\n\nFirst stable release, with:\n- paged logs\n- diff summaries\n";
+/// Signed tag on the third commit, when signing is available
+pub const SIGNED: &str = "v1.1";
+/// Message of [`SIGNED`], without its signature
+pub const SIGNED_MESSAGE: &str = "Release 1.1\n\nSigned by a throwaway test key.\n";
+
+/// A generated repository of tags
+pub struct TagsRepo {
+    pub path: PathBuf,
+    /// Whether [`SIGNED`] could be made
+    pub signed: bool,
+}
+
+impl TagsRepo {
+    /// Build the repository at `target/rl_fixtures/tags/<name>/repo`,
+    /// reusing the history if it is already there, and sign [`SIGNED`].
+    pub fn create(name: &str) -> Result<TagsRepo, FixtureError> {
+        let base = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("tags")
+            .join(name);
+        let built = FixtureBuilder::new()
+            .file("VERSION", "0.1\n")
+            .commit("Version 0.1")
+            .tag(LIGHTWEIGHT)
+            .file("VERSION", "1.0\n")
+            .commit("Version 1.0")
+            .annotated_tag(ANNOTATED, ANNOTATED_MESSAGE)
+            .file("VERSION", "1.1\n")
+            .commit("Version 1.1")
+            .build_at(base.join("repo"))?;
+
+        let mut repo = TagsRepo {
+            path: built.path,
+            signed: false,
+        };
+        repo.signed = repo.sign(&base)?;
+        Ok(repo)
+    }
+
+    /// Make [`SIGNED`] on the last commit with a new key, if this system
+    /// can.
+    fn sign(&self, base: &Path) -> Result<bool, FixtureError> {
+        let key = base.join("signing_key");
+        for file in [key.clone(), key.with_extension("pub")] {
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+        }
+        let keygen = Command::new("ssh-keygen")
+            .args([
+                "-q",
+                "-t",
+                "ed25519",
+                "-N",
+                "",
+                "-C",
+                "test@example.com",
+                "-f",
+            ])
+            .arg(&key)
+            .output();
+        if !keygen.is_ok_and(|output| output.status.success()) {
+            return Ok(false);
+        }
+        let key = key.to_string_lossy().to_string();
+        let tagged = git(
+            &self.path,
+            &[
+                "-c",
+                "gpg.format=ssh",
+                "-c",
+                &format!("user.signingKey={}", key),
+                "tag",
+                "--sign",
+                "-m",
+                SIGNED_MESSAGE,
+                SIGNED,
+            ],
+        );
+        Ok(tagged.is_ok())
+    }
+
+    /// Commit id that `revision` names.
+    pub fn rev_parse(&self, revision: &str) -> Result<String, FixtureError> {
+        let revision = format!("{}^{{commit}}", revision);
+        let output = git(&self.path, &["rev-parse", &revision])?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_repo_has_each_kind_of_tag() {
+        let repo = TagsRepo::create("test_tags").expect("Failed to create tags repo");
+        let kind = |tag: &str| {
+            let output = git(
+                &repo.path,
+                &["cat-file", "-t", &format!("refs/tags/{}", tag)],
+            )
+            .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        assert_eq!(kind(LIGHTWEIGHT), "commit");
+        assert_eq!(kind(ANNOTATED), "tag");
+
+        if !repo.signed {
+            eprintln!("Skipping signed tag checks: no SSH signing available");
+            return;
+        }
+        let object = git(&repo.path, &["cat-file", "tag", SIGNED]).unwrap();
+        let object = String::from_utf8_lossy(&object.stdout);
+        assert!(object.contains(SIGNED_MESSAGE));
+        assert!(object.contains("-----BEGIN SSH SIGNATURE-----"));
+    }
+}
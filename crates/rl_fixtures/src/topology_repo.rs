@@ -0,0 +1,131 @@
+//! A repository whose history has the merge shapes a straight line never
+//! shows: an octopus merge of three branches, and a criss-cross merge
+//! whose two sides have two merge bases.
+//!
+//! ```text
+//! crossed ─┬─ left2 ──┬─ left1 ──┐
+//!          │          ╳          ├─ octopus ─┬─ root
+//!          └─ right2 ─┴─ right1 ─┘           ├─ a ─── root
+//!                                            ├─ b ─── root
+//!                                            └─ c ─── root
+//! ```
+//!
+//! `left2` merges `right1` into `left1` and `right2` merges `left1` into
+//! `right1`, so `left1` and `right1` are both best common ancestors of
+//! `left2` and `right2`. Every commit is tagged with its name, and each
+//! adds a file of that name, so merges never conflict. The history is
+//! declared with [`FixtureBuilder`] and has the same commit ids everywhere.
+
+use crate::builder::FixtureBuilder;
+use crate::synth_repo::{FixtureError, SynthRepo};
+use std::path::PathBuf;
+
+/// First commit
+pub const ROOT: &str = "root";
+/// Octopus merge of the `OCTOPUS_BRANCHES` into `ROOT`
+pub const OCTOPUS: &str = "octopus";
+/// Branches merged by `OCTOPUS`, each one commit on `ROOT`
+pub const OCTOPUS_BRANCHES: &[&str] = &["a", "b", "c"];
+/// Side of the criss-cross, on `OCTOPUS`; branch `left` ends at `LEFT2`
+pub const LEFT1: &str = "left1";
+/// Other side of the criss-cross, on `OCTOPUS`; branch `right` ends at `RIGHT2`
+pub const RIGHT1: &str = "right1";
+/// Merge of `RIGHT1` into `LEFT1`
+pub const LEFT2: &str = "left2";
+/// Merge of `LEFT1` into `RIGHT1`
+pub const RIGHT2: &str = "right2";
+/// Merge of `RIGHT2` into `LEFT2`, where `master` ends
+pub const CROSSED: &str = "crossed";
+
+/// A generated repository of merge topologies
+pub struct TopologyRepo {
+    pub path: PathBuf,
+}
+
+impl TopologyRepo {
+    /// The repository under `target/rl_fixtures/topology/<name>`,
+    /// generating it again whenever its definition here changes.
+    pub fn ensure(name: &str) -> Result<TopologyRepo, FixtureError> {
+        let path = SynthRepo::find_workspace_root()?
+            .join("target")
+            .join("rl_fixtures")
+            .join("topology")
+            .join(name)
+            .join("repo");
+        // Each commit adds its own file and is tagged with its name
+        let add = |builder: FixtureBuilder, name: &str| {
+            builder.file(format!("{}.txt", name), &format!("added by {}\n", name))
+        };
+        let commit = |builder, name| add(builder, name).commit(name).tag(name);
+        let merge =
+            |builder, name, merged: &[&str]| add(builder, name).merge_all(merged, name).tag(name);
+
+        let mut builder = commit(FixtureBuilder::new(), ROOT);
+        for side in OCTOPUS_BRANCHES {
+            builder = commit(builder.branch(side), side).checkout("master");
+        }
+        builder = merge(builder, OCTOPUS, OCTOPUS_BRANCHES);
+        builder = commit(builder.branch("right").checkout("master"), LEFT1);
+        builder = commit(builder.checkout("right"), RIGHT1);
+        builder = merge(builder.checkout("master"), LEFT2, &[RIGHT1]);
+        builder = merge(builder.branch("left").checkout("right"), RIGHT2, &[LEFT1]);
+        builder = merge(builder.checkout("master"), CROSSED, &[RIGHT2]);
+        for side in OCTOPUS_BRANCHES {
+            builder = builder.delete_branch(side);
+        }
+
+        let repo = builder.build_at(path)?;
+        Ok(TopologyRepo { path: repo.path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_topology_repo_has_octopus_and_criss_cross_merges() {
+        let repo = TopologyRepo::ensure("test_topology").expect("Failed to create topology repo");
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .current_dir(&repo.path)
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let id = |name: &str| git(&["rev-parse", &format!("{}^{{commit}}", name)]);
+
+        let octopus_parents: Vec<_> = [ROOT]
+            .iter()
+            .chain(OCTOPUS_BRANCHES)
+            .map(|c| id(c))
+            .collect();
+        let listed = git(&["rev-list", "--parents", "-n1", OCTOPUS]);
+        assert_eq!(
+            listed.split(' ').skip(1).collect::<Vec<_>>(),
+            octopus_parents
+        );
+
+        let mut bases: Vec<_> = git(&["merge-base", "--all", LEFT2, RIGHT2])
+            .lines()
+            .map(str::to_string)
+            .collect();
+        bases.sort();
+        let mut expected = vec![id(LEFT1), id(RIGHT1)];
+        expected.sort();
+        assert_eq!(bases, expected);
+
+        assert_eq!(id("master"), id(CROSSED));
+        assert_eq!(id("left"), id(LEFT2));
+        assert_eq!(id("right"), id(RIGHT2));
+        assert_eq!(
+            git(&["for-each-ref", "--format=%(refname:short)", "refs/heads"]),
+            "left\nmaster\nright"
+        );
+        let tagged = git(&["tag"]).lines().count();
+        assert_eq!(git(&["rev-list", "--count", "master"]), tagged.to_string());
+        assert_eq!(git(&["ls-files"]).lines().count(), tagged);
+    }
+}
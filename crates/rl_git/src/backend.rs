@@ -1,7 +1,58 @@
 //! Git CLI backend implementation using std::process::Command.
+//!
+//! Every git process is killed if the future waiting on it is dropped, so a
+//! cancelled request does not leave git running in the background.
 
-use crate::{GitBackend, RepoHandle, RepoSnapshot, Result};
-use std::path::Path;
+use crate::{GitBackend, GitCapabilities, GitVersion, RepoHandle, RepoSnapshot, Result};
+use rl_api::request::ResetMode;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Oldest git release the CLI backend works with: `status --porcelain=v1`
+/// needs 2.11.
+pub const MIN_GIT_VERSION: GitVersion = GitVersion {
+    major: 2,
+    minor: 11,
+    patch: 0,
+};
+
+/// First release able to write and read commit-graph files.
+const COMMIT_GRAPH_VERSION: GitVersion = GitVersion {
+    major: 2,
+    minor: 18,
+    patch: 0,
+};
+
+/// `git log` format read by [`parse_log`]: fields separated by 0x1f, with
+/// the free-form message last. Used with `-z`, which ends each commit with
+/// a NUL.
+const LOG_FORMAT: &str = "--format=%H%x1f%T%x1f%P%x1f%an%x1f%ae%x1f%at%x1f%cn%x1f%ce%x1f%ct%x1f%B";
+
+/// `git stash list` format read by [`parse_stash_list`]: commit, time and
+/// reflog message separated by 0x1f. Used with `-z`.
+const STASH_FORMAT: &str = "--format=%H%x1f%ct%x1f%gs";
+
+/// `git for-each-ref` format read by [`parse_tag_list`]: name, object type,
+/// object, peeled object, signature and annotation, separated by 0x1f and
+/// ending with a NUL.
+const TAG_FORMAT: &str = "--format=%(refname:strip=2)%1f%(objecttype)%1f%(objectname)%1f%(*objectname)%1f%(contents:signature)%1f%(contents)%00";
+
+/// Remote settings read by [`parse_remote_list`].
+const REMOTE_CONFIG: &str = r"^remote\..*\.(url|fetch|push)$";
+
+/// Git processes started by this process so far
+static SPAWNED: AtomicU64 = AtomicU64::new(0);
+
+/// Git processes the CLI backend has started since this process began.
+pub fn processes_spawned() -> u64 {
+    SPAWNED.load(Ordering::Relaxed)
+}
+
+/// A `git` command, counted in [`processes_spawned`].
+fn git_command() -> tokio::process::Command {
+    SPAWNED.fetch_add(1, Ordering::Relaxed);
+    tokio::process::Command::new("git")
+}
 
 /// Git CLI backend that shells out to the git command.
 pub struct CliBackend;
@@ -11,6 +62,23 @@ impl CliBackend {
     pub fn new() -> Self {
         Self
     }
+
+    /// Find the root of the repository enclosing `start`, walking up as git
+    /// does: a linked worktree is its own root, and the search stops at
+    /// `GIT_CEILING_DIRECTORIES` and filesystem boundaries.
+    pub async fn discover(&self, start: &Path) -> Result<PathBuf> {
+        let output = run_git(start, &["rev-parse", "--show-toplevel"]).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.trim().trim_start_matches("fatal: ");
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::RepoNotFound,
+                format!("No git repository at {}: {}", start.display(), reason),
+            ));
+        }
+        let root = String::from_utf8_lossy(&output.stdout);
+        Ok(PathBuf::from(root.trim_end_matches(['\n', '\r'])))
+    }
 }
 
 impl Default for CliBackend {
@@ -21,6 +89,10 @@ impl Default for CliBackend {
 
 #[async_trait::async_trait]
 impl GitBackend for CliBackend {
+    fn kind(&self) -> &'static str {
+        "cli"
+    }
+
     async fn open_repo(&self, path: &Path) -> Result<Box<dyn RepoHandle>> {
         // Verify it's a git repository
         let is_valid = self.is_repo(path).await?;
@@ -35,28 +107,57 @@ impl GitBackend for CliBackend {
     }
 
     async fn is_repo(&self, path: &Path) -> Result<bool> {
-        let output = tokio::process::Command::new("git")
+        let output = git_command()
+            .kill_on_drop(true)
             .arg("-C")
             .arg(path)
             .arg("rev-parse")
             .arg("--git-dir")
             .output()
             .await
-            .map_err(|e| {
-                rl_api::Error::new(
-                    rl_api::ErrorCode::GitBackendError,
-                    format!("Failed to execute git: {}", e),
-                )
-            })?;
+            .map_err(|e| spawn_error("git", e))?;
 
         Ok(output.status.success())
     }
+
+    async fn capabilities(&self) -> GitCapabilities {
+        let unavailable = GitCapabilities {
+            min_version: Some(MIN_GIT_VERSION),
+            ..Default::default()
+        };
+        let Ok(output) = git_command()
+            .kill_on_drop(true)
+            .arg("version")
+            .arg("--build-options")
+            .output()
+            .await
+        else {
+            return unavailable;
+        };
+        let output = String::from_utf8_lossy(&output.stdout);
+        let Some(version) = output.lines().next().and_then(GitVersion::parse) else {
+            return unavailable;
+        };
+
+        GitCapabilities {
+            version: Some(version),
+            min_version: Some(MIN_GIT_VERSION),
+            // `diff -M` predates every supported release
+            rename_detection: true,
+            commit_graph: version >= COMMIT_GRAPH_VERSION,
+            // Only listed by builds for platforms the daemon runs on
+            fsmonitor: output
+                .lines()
+                .any(|line| line.trim() == "feature: fsmonitor--daemon"),
+        }
+    }
 }
 
 /// Repository handle using Git CLI.
 pub struct CliRepoHandle {
     path: std::path::PathBuf,
     workdir: CliWorkdir,
+    refs: CliRefsStore,
 }
 
 impl CliRepoHandle {
@@ -66,26 +167,206 @@ impl CliRepoHandle {
             workdir: CliWorkdir {
                 path: path_buf.clone(),
             },
+            refs: CliRefsStore {
+                path: path_buf.clone(),
+            },
             path: path_buf,
         }
     }
 
     async fn run_git(&self, args: &[&str]) -> Result<std::process::Output> {
-        tokio::process::Command::new("git")
+        run_git(&self.path, args).await
+    }
+
+    /// Run git, failing with its stderr if it exits unsuccessfully.
+    async fn run_git_checked(&self, command: &str, args: &[&str]) -> Result<()> {
+        let output = self.run_git(args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure(command, &stderr));
+        }
+        Ok(())
+    }
+
+    /// Read up to `limit` commits of `range` after skipping `skip`, in the
+    /// order `order` asks `git log` for, by default newest first.
+    async fn read_log(
+        &self,
+        range: &str,
+        skip: usize,
+        limit: usize,
+        order: Option<&str>,
+    ) -> Result<Vec<crate::Commit>> {
+        let skip = format!("--skip={}", skip);
+        let limit = format!("--max-count={}", limit);
+        let mut args = vec!["log", "-z", LOG_FORMAT];
+        args.extend(order);
+        args.extend([skip.as_str(), limit.as_str(), range, "--"]);
+        let output = self.run_git(&args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git log failed: {}", stderr),
+            ));
+        }
+
+        parse_log(&output.stdout)
+    }
+
+    /// Path of `name` in the git directory.
+    async fn git_path(&self, name: &str) -> Result<PathBuf> {
+        let output = self.run_git(&["rev-parse", "--git-path", name]).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure("rev-parse", &stderr));
+        }
+        let path = String::from_utf8_lossy(&output.stdout);
+        Ok(self.path.join(path.trim_end_matches(['\n', '\r'])))
+    }
+
+    /// Paths with unresolved conflicts in the index.
+    async fn conflicted_paths(&self) -> Result<Vec<String>> {
+        let output = self
+            .run_git(&["diff", "--name-only", "--diff-filter=U", "-z"])
+            .await?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Run git, sending each line it writes to stderr to `progress` as it
+    /// arrives. Returns whether git succeeded and the last line it wrote.
+    async fn run_git_with_progress(
+        &self,
+        command: &str,
+        args: &[&str],
+        progress: &tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<(bool, String)> {
+        use tokio::io::AsyncReadExt;
+
+        let mut child = git_command()
+            .kill_on_drop(true)
             .arg("-C")
             .arg(&self.path)
             .args(args)
-            .output()
-            .await
-            .map_err(|e| {
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(&format!("git {}", command), e))?;
+
+        // Git redraws progress lines with '\r', so split on both terminators
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let mut pending = Vec::new();
+        let mut last_line = String::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stderr.read(&mut buf).await.map_err(|e| {
                 rl_api::Error::new(
                     rl_api::ErrorCode::GitBackendError,
-                    format!("Failed to execute git: {}", e),
+                    format!("Failed to read git {} output: {}", command, e),
                 )
-            })
+            })?;
+            if n == 0 {
+                break;
+            }
+            for &byte in &buf[..n] {
+                if byte == b'\r' || byte == b'\n' {
+                    if !pending.is_empty() {
+                        last_line = String::from_utf8_lossy(&pending).to_string();
+                        let _ = progress.send(last_line.clone());
+                        pending.clear();
+                    }
+                } else {
+                    pending.push(byte);
+                }
+            }
+        }
+        if !pending.is_empty() {
+            last_line = String::from_utf8_lossy(&pending).to_string();
+            let _ = progress.send(last_line.clone());
+        }
+
+        let status = child.wait().await.map_err(|e| {
+            rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("Failed to wait for git {}: {}", command, e),
+            )
+        })?;
+        Ok((status.success(), last_line))
+    }
+}
+
+/// Fail with `InvalidRequest` if `value`, passed to git as a remote, refspec
+/// or revision, would be read as an option instead.
+fn reject_option(value: &str) -> Result<()> {
+    if value.starts_with('-') {
+        return Err(rl_api::Error::new(
+            rl_api::ErrorCode::InvalidRequest,
+            format!("Invalid remote, refspec or revision: {}", value),
+        ));
+    }
+    Ok(())
+}
+
+/// Error for a git command that failed with `stderr`. A lock file that
+/// already exists, held by another git or left by one that crashed, is a
+/// `Conflict`, as the command may succeed once it is gone.
+fn git_failure(command: &str, stderr: &str) -> rl_api::Error {
+    let failed = rl_api::Error::new(
+        rl_api::ErrorCode::GitBackendError,
+        format!("git {} failed: {}", command, stderr),
+    );
+    let lock = stderr
+        .split_once("Unable to create '")
+        .and_then(|(_, rest)| rest.split_once("': File exists"));
+    match lock {
+        Some((lock, _)) => lock_held(failed, Path::new(lock)),
+        None => failed,
     }
 }
 
+/// `failed`, a command that could not take `lock`, as a `Conflict`.
+fn lock_held(failed: rl_api::Error, lock: &Path) -> rl_api::Error {
+    rl_api::Error {
+        code: rl_api::ErrorCode::Conflict,
+        ..failed
+    }
+    .with_remediation(format!(
+        "Retry once other git processes have finished; if none is running, remove {}",
+        lock.display()
+    ))
+}
+
+/// Error for a git process that could not be started.
+fn spawn_error(command: &str, error: std::io::Error) -> rl_api::Error {
+    let failed = rl_api::Error::new(
+        rl_api::ErrorCode::GitBackendError,
+        format!("Failed to execute {}: {}", command, error),
+    );
+    if error.kind() == std::io::ErrorKind::NotFound {
+        failed.with_remediation(format!(
+            "Install git {} or later and make sure it is on PATH",
+            MIN_GIT_VERSION
+        ))
+    } else {
+        failed
+    }
+}
+
+async fn run_git(path: &Path, args: &[&str]) -> Result<std::process::Output> {
+    git_command()
+        .kill_on_drop(true)
+        .arg("-C")
+        .arg(path)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| spawn_error("git", e))
+}
+
 #[async_trait::async_trait]
 impl RepoHandle for CliRepoHandle {
     async fn snapshot(&self) -> Result<RepoSnapshot> {
@@ -101,17 +382,17 @@ impl RepoHandle for CliRepoHandle {
             None
         };
 
-        // Get current branch
-        let branch_output = self.run_git(&["rev-parse", "--abbrev-ref", "HEAD"]).await?;
+        // Get current branch, which a branch with no commits yet still has;
+        // a detached HEAD has none
+        let branch_output = self
+            .run_git(&["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .await?;
         let branch = if branch_output.status.success() {
-            let branch_name = String::from_utf8_lossy(&branch_output.stdout)
-                .trim()
-                .to_string();
-            if branch_name != "HEAD" {
-                Some(branch_name)
-            } else {
-                None
-            }
+            Some(
+                String::from_utf8_lossy(&branch_output.stdout)
+                    .trim()
+                    .to_string(),
+            )
         } else {
             None
         };
@@ -129,7 +410,7 @@ impl RepoHandle for CliRepoHandle {
     }
 
     fn refs_store(&self) -> &dyn crate::RefsStore {
-        &CliRefsStore
+        &self.refs
     }
 
     fn workdir(&self) -> &dyn crate::Workdir {
@@ -141,7 +422,8 @@ impl RepoHandle for CliRepoHandle {
     }
 
     async fn diff_name_status(&self, range: &str) -> Result<String> {
-        let output = tokio::process::Command::new("git")
+        let output = git_command()
+            .kill_on_drop(true)
             .arg("-C")
             .arg(&self.path)
             .arg("diff")
@@ -150,12 +432,7 @@ impl RepoHandle for CliRepoHandle {
             .arg(range)
             .output()
             .await
-            .map_err(|e| {
-                rl_api::Error::new(
-                    rl_api::ErrorCode::GitBackendError,
-                    format!("Failed to execute git diff: {}", e),
-                )
-            })?;
+            .map_err(|e| spawn_error("git diff", e))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -169,20 +446,17 @@ impl RepoHandle for CliRepoHandle {
     }
 
     async fn diff_numstat(&self, range: &str) -> Result<String> {
-        let output = tokio::process::Command::new("git")
+        let output = git_command()
+            .kill_on_drop(true)
             .arg("-C")
             .arg(&self.path)
             .arg("diff")
             .arg("--numstat")
+            .arg("-M") // Pair renames as --name-status does
             .arg(range)
             .output()
             .await
-            .map_err(|e| {
-                rl_api::Error::new(
-                    rl_api::ErrorCode::GitBackendError,
-                    format!("Failed to execute git diff: {}", e),
-                )
-            })?;
+            .map_err(|e| spawn_error("git diff", e))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -194,6 +468,379 @@ impl RepoHandle for CliRepoHandle {
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
+
+    async fn diff_patch(&self, range: &str, path: Option<&str>) -> Result<String> {
+        let mut args = vec![
+            "diff",
+            "--patch",
+            "-M",
+            "--no-color",
+            "--no-ext-diff",
+            range,
+        ];
+        if let Some(path) = path {
+            args.push("--");
+            args.push(path);
+        }
+
+        let output = self.run_git(&args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git diff failed: {}", stderr),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    async fn blame_porcelain(
+        &self,
+        path: &str,
+        revision: Option<&str>,
+        lines: Option<(usize, usize)>,
+    ) -> Result<String> {
+        let range = lines.map(|(start, end)| format!("-L{},{}", start, end));
+        let mut args = vec!["blame", "--porcelain"];
+        if let Some(range) = &range {
+            args.push(range);
+        }
+        if let Some(revision) = revision {
+            args.push(revision);
+        }
+        args.push("--");
+        args.push(path);
+
+        let output = self.run_git(&args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git blame failed: {}", stderr),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    async fn rev_parse(&self, revision: &str) -> Result<Option<String>> {
+        let spec = format!("{}^{{commit}}", revision);
+        let output = self
+            .run_git(&["rev-parse", "--verify", "--quiet", &spec])
+            .await?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ))
+    }
+
+    async fn rev_list(&self, range: &str) -> Result<Vec<String>> {
+        let output = self.run_git(&["rev-list", range, "--"]).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git rev-list failed: {}", stderr),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    async fn log(&self, range: &str, skip: usize, limit: usize) -> Result<Vec<crate::Commit>> {
+        self.read_log(range, skip, limit, None).await
+    }
+
+    async fn log_topo(&self, range: &str, skip: usize, limit: usize) -> Result<Vec<crate::Commit>> {
+        self.read_log(range, skip, limit, Some("--topo-order"))
+            .await
+    }
+
+    async fn update_ref(
+        &self,
+        name: &str,
+        new: Option<&str>,
+        expected: Option<&str>,
+    ) -> Result<()> {
+        // An empty old value makes git check the ref does not exist yet
+        let expected = expected.unwrap_or("");
+        match new {
+            Some(new) => {
+                self.run_git_checked("update-ref", &["update-ref", name, new, expected])
+                    .await
+            }
+            None => {
+                self.run_git_checked("update-ref", &["update-ref", "-d", name, expected])
+                    .await
+            }
+        }
+    }
+
+    async fn reset_keep(&self, commit: &str) -> Result<()> {
+        self.run_git_checked("reset", &["reset", "--keep", "--quiet", commit])
+            .await
+    }
+
+    async fn checkout(&self, target: &str, detach: bool) -> Result<()> {
+        reject_option(target)?;
+        let mut args = vec!["checkout", "--quiet"];
+        if detach {
+            args.push("--detach");
+        }
+        args.push(target);
+        self.run_git_checked("checkout", &args).await
+    }
+
+    async fn merge(&self, source: &str, message: Option<&str>) -> Result<Vec<String>> {
+        reject_option(source)?;
+        let mut args = vec!["merge", "--quiet", "--no-edit"];
+        if let Some(message) = message {
+            args.extend(["--message", message]);
+        }
+        args.push(source);
+        let output = self.run_git(&args).await?;
+        if output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        let conflicts = self.conflicted_paths().await?;
+        if conflicts.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure("merge", &stderr));
+        }
+        self.run_git_checked("merge --abort", &["merge", "--abort"])
+            .await?;
+        Ok(conflicts)
+    }
+
+    async fn reset(&self, commit: &str, mode: ResetMode) -> Result<()> {
+        reject_option(commit)?;
+        let mode = match mode {
+            ResetMode::Soft => "--soft",
+            ResetMode::Mixed => "--mixed",
+            ResetMode::Hard => "--hard",
+        };
+        self.run_git_checked("reset", &["reset", "--quiet", mode, commit])
+            .await
+    }
+
+    async fn clean(&self, directories: bool, ignored: bool, dry_run: bool) -> Result<Vec<String>> {
+        let mut args = vec!["-c", "core.quotePath=false", "clean"];
+        args.push(if dry_run { "--dry-run" } else { "--force" });
+        if directories {
+            args.push("-d");
+        }
+        if ignored {
+            args.push("-x");
+        }
+        let output = self.run_git(&args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure("clean", &stderr));
+        }
+        Ok(parse_clean(&output.stdout))
+    }
+
+    async fn fetch(
+        &self,
+        remote: &str,
+        refspecs: &[String],
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()> {
+        reject_option(remote)?;
+        let mut args = vec!["fetch", "--progress", remote];
+        args.extend(refspecs.iter().map(String::as_str));
+        let (success, last_line) = self
+            .run_git_with_progress("fetch", &args, &progress)
+            .await?;
+        if !success {
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git fetch failed: {}", last_line),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[String],
+        force: bool,
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()> {
+        reject_option(remote)?;
+        for refspec in refspecs {
+            reject_option(refspec)?;
+        }
+        let mut args = vec!["push", "--progress"];
+        if force {
+            args.push("--force-with-lease");
+        }
+        args.push(remote);
+        args.extend(refspecs.iter().map(String::as_str));
+        let (success, last_line) = self.run_git_with_progress("push", &args, &progress).await?;
+        if !success {
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git push failed: {}", last_line),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn rebase(
+        &self,
+        onto: &str,
+        upstream: Option<&str>,
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<Vec<String>> {
+        reject_option(onto)?;
+        let mut args = vec!["rebase"];
+        match upstream {
+            Some(upstream) => {
+                reject_option(upstream)?;
+                args.extend(["--onto", onto, upstream]);
+            }
+            None => args.push(onto),
+        }
+        let (success, last_line) = self
+            .run_git_with_progress("rebase", &args, &progress)
+            .await?;
+        if success {
+            return Ok(Vec::new());
+        }
+
+        let conflicts = self.conflicted_paths().await?;
+        if conflicts.is_empty() {
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git rebase failed: {}", last_line),
+            ));
+        }
+        self.run_git_checked("rebase --abort", &["rebase", "--abort"])
+            .await?;
+        Ok(conflicts)
+    }
+
+    async fn stash_push(&self, message: Option<&str>, include_untracked: bool) -> Result<bool> {
+        let before = self.rev_parse("refs/stash").await?;
+        let mut args = vec!["stash", "push", "--quiet"];
+        if include_untracked {
+            args.push("--include-untracked");
+        }
+        if let Some(message) = message {
+            args.extend(["--message", message]);
+        }
+        let output = self.run_git(&args).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // git stash gives no reason when it can't lock the index
+            let lock = self.git_path("index.lock").await?;
+            if stderr.trim().is_empty() && lock.exists() {
+                let failed = rl_api::Error::new(
+                    rl_api::ErrorCode::GitBackendError,
+                    "git stash push failed: the index is locked",
+                );
+                return Err(lock_held(failed, &lock));
+            }
+            return Err(git_failure("stash push", &stderr));
+        }
+        // With no changes to save git succeeds without making an entry
+        Ok(self.rev_parse("refs/stash").await? != before)
+    }
+
+    async fn stash_apply(&self, index: usize, pop: bool) -> Result<Vec<String>> {
+        let entry = format!("stash@{{{}}}", index);
+        let command = if pop { "pop" } else { "apply" };
+        let output = self.run_git(&["stash", command, "--quiet", &entry]).await?;
+        if output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        let conflicts = self.conflicted_paths().await?;
+        if conflicts.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure(&format!("stash {}", command), stderr.trim()));
+        }
+        Ok(conflicts)
+    }
+
+    async fn stash_drop(&self, index: usize) -> Result<()> {
+        let entry = format!("stash@{{{}}}", index);
+        self.run_git_checked("stash drop", &["stash", "drop", "--quiet", &entry])
+            .await
+    }
+
+    async fn stash_store(&self, id: &str) -> Result<()> {
+        reject_option(id)?;
+        // The entry's reflog message is gone with it, but git made the
+        // commit's subject the same text
+        let output = self.run_git(&["show", "-s", "--format=%s", id]).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(git_failure("show", &stderr));
+        }
+        let message = String::from_utf8_lossy(&output.stdout);
+        self.run_git_checked(
+            "stash store",
+            &["stash", "store", "--message", message.trim_end(), id],
+        )
+        .await
+    }
+
+    async fn stash_list(&self) -> Result<Vec<crate::StashEntry>> {
+        let output = self.run_git(&["stash", "list", "-z", STASH_FORMAT]).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git stash list failed: {}", stderr),
+            ));
+        }
+        parse_stash_list(&output.stdout)
+    }
+
+    async fn tag_list(&self) -> Result<Vec<crate::TagEntry>> {
+        let output = self
+            .run_git(&["for-each-ref", "--sort=refname", TAG_FORMAT, "refs/tags"])
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git for-each-ref failed: {}", stderr),
+            ));
+        }
+        parse_tag_list(&output.stdout)
+    }
+
+    async fn remote_list(&self) -> Result<Vec<crate::RemoteEntry>> {
+        let output = self
+            .run_git(&["config", "-z", "--get-regexp", REMOTE_CONFIG])
+            .await?;
+        // Exit code 1: no remote is configured
+        match output.status.code() {
+            Some(0) => Ok(parse_remote_list(&output.stdout)),
+            Some(1) => Ok(Vec::new()),
+            _ => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                Err(rl_api::Error::new(
+                    rl_api::ErrorCode::GitBackendError,
+                    format!("git config failed: {}", stderr),
+                ))
+            }
+        }
+    }
 }
 
 /// CLI-based workdir implementation.
@@ -204,7 +851,8 @@ pub struct CliWorkdir {
 #[async_trait::async_trait]
 impl crate::Workdir for CliWorkdir {
     async fn status(&self) -> Result<crate::WorkdirStatus> {
-        let output = tokio::process::Command::new("git")
+        let output = git_command()
+            .kill_on_drop(true)
             .arg("-C")
             .arg(&self.path)
             .arg("status")
@@ -212,12 +860,7 @@ impl crate::Workdir for CliWorkdir {
             .arg("-z") // Null-terminated for proper handling of special chars
             .output()
             .await
-            .map_err(|e| {
-                rl_api::Error::new(
-                    rl_api::ErrorCode::GitBackendError,
-                    format!("Failed to execute git status: {}", e),
-                )
-            })?;
+            .map_err(|e| spawn_error("git status", e))?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -232,15 +875,165 @@ impl crate::Workdir for CliWorkdir {
     }
 }
 
+/// Paths named in `git clean` output, one `Removing <path>` or `Would
+/// remove <path>` line each.
+fn parse_clean(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("Removing ")
+                .or_else(|| line.strip_prefix("Would remove "))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse `git log -z` output written with [`LOG_FORMAT`].
+/// Parse `git stash list -z` output in [`STASH_FORMAT`].
+fn parse_stash_list(output: &[u8]) -> Result<Vec<crate::StashEntry>> {
+    String::from_utf8_lossy(output)
+        .split('\0')
+        .filter(|record| !record.trim().is_empty())
+        .enumerate()
+        .map(|(index, record)| {
+            let record = record.trim_start_matches('\n');
+            let malformed = || {
+                rl_api::Error::new(
+                    rl_api::ErrorCode::GitBackendError,
+                    format!("Unexpected git stash list output: {:?}", record),
+                )
+            };
+            let fields: Vec<&str> = record.splitn(3, '\x1f').collect();
+            let [id, time, message] = fields[..] else {
+                return Err(malformed());
+            };
+            Ok(crate::StashEntry {
+                index,
+                id: id.to_string(),
+                message: message.trim_end().to_string(),
+                time: time.parse().map_err(|_| malformed())?,
+            })
+        })
+        .collect()
+}
+
+/// Parse `git for-each-ref` output in [`TAG_FORMAT`].
+fn parse_tag_list(output: &[u8]) -> Result<Vec<crate::TagEntry>> {
+    String::from_utf8_lossy(output)
+        .split('\0')
+        .map(|record| record.trim_start_matches('\n'))
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let fields: Vec<&str> = record.splitn(6, '\x1f').collect();
+            let [name, kind, id, peeled, signature, contents] = fields[..] else {
+                return Err(rl_api::Error::new(
+                    rl_api::ErrorCode::GitBackendError,
+                    format!("Unexpected git for-each-ref output: {:?}", record),
+                ));
+            };
+            let annotated = kind == "tag";
+            // The annotation of a signed tag ends with its signature
+            let message = contents.strip_suffix(signature).unwrap_or(contents);
+            Ok(crate::TagEntry {
+                name: name.to_string(),
+                id: if annotated { peeled } else { id }.to_string(),
+                message: annotated.then(|| message.trim_end().to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Parse `git config -z --get-regexp` output of [`REMOTE_CONFIG`] into
+/// remotes, in the order their first setting appears.
+fn parse_remote_list(output: &[u8]) -> Vec<crate::RemoteEntry> {
+    let mut remotes: Vec<crate::RemoteEntry> = Vec::new();
+    for entry in String::from_utf8_lossy(output).split('\0') {
+        let (key, value) = entry.split_once('\n').unwrap_or((entry, ""));
+        // Remote names may contain dots; the setting is after the last one
+        let Some((name, setting)) = key
+            .strip_prefix("remote.")
+            .and_then(|key| key.rsplit_once('.'))
+        else {
+            continue;
+        };
+        let index = match remotes.iter().position(|remote| remote.name == name) {
+            Some(index) => index,
+            None => {
+                remotes.push(crate::RemoteEntry {
+                    name: name.to_string(),
+                    url: String::new(),
+                    fetch_refspecs: Vec::new(),
+                    push_refspecs: Vec::new(),
+                });
+                remotes.len() - 1
+            }
+        };
+        let remote = &mut remotes[index];
+        match setting {
+            // As git does, the first URL is the one fetched from
+            "url" if remote.url.is_empty() => remote.url = value.to_string(),
+            "fetch" => remote.fetch_refspecs.push(value.to_string()),
+            "push" => remote.push_refspecs.push(value.to_string()),
+            _ => {}
+        }
+    }
+    remotes
+}
+
+fn parse_log(output: &[u8]) -> Result<Vec<crate::Commit>> {
+    let malformed = |record: &str| {
+        rl_api::Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            format!("Unexpected git log output: {:?}", record),
+        )
+    };
+    let time = |value: &str, record: &str| value.parse::<i64>().map_err(|_| malformed(record));
+
+    String::from_utf8_lossy(output)
+        .split('\0')
+        .filter(|record| !record.trim().is_empty())
+        .map(|record| {
+            // Records after the first start with the newline ending the last
+            let record = record.trim_start_matches('\n');
+            let fields: Vec<&str> = record.splitn(10, '\x1f').collect();
+            let [id, tree_id, parents, author_name, author_email, author_time, committer_name, committer_email, committer_time, message] =
+                fields[..]
+            else {
+                return Err(malformed(record));
+            };
+            Ok(crate::Commit {
+                id: id.to_string(),
+                tree_id: tree_id.to_string(),
+                parent_ids: parents.split_whitespace().map(str::to_string).collect(),
+                author: crate::Signature {
+                    name: author_name.to_string(),
+                    email: author_email.to_string(),
+                    time: time(author_time, record)?,
+                },
+                committer: crate::Signature {
+                    name: committer_name.to_string(),
+                    email: committer_email.to_string(),
+                    time: time(committer_time, record)?,
+                },
+                message: message.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
 /// Parse git status --porcelain=v1 -z output.
 ///
 /// Format: XY PATH
 /// - X shows status in index (staged)
 /// - Y shows status in working tree (unstaged)
 ///
-/// Returns WorkdirStatus which contains all changes (both staged and unstaged).
-/// The caller needs to separate them based on the XY codes.
+/// The columns are read apart: any change in X puts the path in `staged`,
+/// while `modified` and `deleted` come from Y alone, so a path staged and
+/// then changed again is in both. Renames are only detected in the index.
+/// Unmerged paths are `modified`, as nothing of them is staged until
+/// they are resolved.
 fn parse_status_porcelain(output: &[u8]) -> Result<crate::WorkdirStatus> {
+    let mut staged = Vec::new();
     let mut modified = Vec::new();
     let mut added = Vec::new();
     let mut deleted = Vec::new();
@@ -248,72 +1041,47 @@ fn parse_status_porcelain(output: &[u8]) -> Result<crate::WorkdirStatus> {
     let mut untracked = Vec::new();
 
     // Split on null bytes
-    let entries: Vec<&[u8]> = output
-        .split(|&b| b == 0)
-        .filter(|e| !e.is_empty())
-        .collect();
-
-    let mut i = 0;
-    while i < entries.len() {
-        let entry = entries[i];
+    let mut entries = output.split(|&b| b == 0).filter(|e| !e.is_empty());
+
+    while let Some(entry) = entries.next() {
         if entry.len() < 3 {
-            i += 1;
             continue;
         }
 
         let x = entry[0]; // Index status
         let y = entry[1]; // Working tree status
-        let path = String::from_utf8_lossy(&entry[3..]).to_string();
+        let path = crate::path::from_bytes(&entry[3..]);
 
-        // Parse status code (XY format)
-        // X is index (staged), Y is working tree (unstaged)
         match (x, y) {
-            (b'?', b'?') => {
-                // Untracked
-                untracked.push(path);
-            }
-            (b'A', _) => {
-                // Added to index (staged)
-                added.push(path);
-            }
-            (b'M', b' ') => {
-                // Modified in index only (staged modification)
-                modified.push(path);
-            }
-            (b' ', b'M') | (b'M', b'M') => {
-                // Modified in working tree (unstaged)
-                modified.push(path);
-            }
-            (b'D', _) | (b' ', b'D') => {
-                // Deleted
-                deleted.push(path);
-            }
-            (b'R', _) => {
-                // Renamed - next entry is the old name
-                if i + 1 < entries.len() {
-                    let old_path = String::from_utf8_lossy(entries[i + 1]).to_string();
-                    renamed.push((old_path, path));
-                    i += 1; // Skip next entry
-                }
-            }
+            (b'?', b'?') => untracked.push(path),
+            (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => modified.push(path),
             _ => {
-                // Handle any other cases by checking individual flags
-                if x == b'M' || y == b'M' {
-                    modified.push(path.clone());
+                // Renames and copies are followed by the path they came from
+                if x == b'R' || x == b'C' {
+                    let Some(from) = entries.next() else {
+                        continue;
+                    };
+                    if x == b'R' {
+                        renamed.push((crate::path::from_bytes(from), path.clone()));
+                    }
                 }
                 if x == b'A' {
                     added.push(path.clone());
                 }
-                if x == b'D' || y == b'D' {
-                    deleted.push(path.clone());
+                match y {
+                    b'M' | b'T' => modified.push(path.clone()),
+                    b'D' => deleted.push(path.clone()),
+                    _ => {}
+                }
+                if x != b' ' {
+                    staged.push(path);
                 }
             }
         }
-
-        i += 1;
     }
 
     Ok(crate::WorkdirStatus {
+        staged,
         modified,
         added,
         deleted,
@@ -350,22 +1118,55 @@ impl crate::ObjectStore for CliObjectStore {
     }
 }
 
-struct CliRefsStore;
+struct CliRefsStore {
+    path: std::path::PathBuf,
+}
 
 #[async_trait::async_trait]
 impl crate::RefsStore for CliRefsStore {
     async fn all_refs(&self) -> Result<Vec<crate::RefInfo>> {
-        Err(rl_api::Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "CLI refs store not fully implemented",
-        ))
+        let output = run_git(
+            &self.path,
+            &[
+                "for-each-ref",
+                "--format=%(refname)%00%(objectname)%00%(symref)%00%(*objectname)",
+            ],
+        )
+        .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::GitBackendError,
+                format!("git for-each-ref failed: {}", stderr),
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\0');
+                Some(crate::RefInfo {
+                    name: fields.next()?.to_string(),
+                    target: fields.next()?.to_string(),
+                    is_symbolic: fields.next().is_some_and(|symref| !symref.is_empty()),
+                    peeled: fields
+                        .next()
+                        .filter(|peeled| !peeled.is_empty())
+                        .map(str::to_string),
+                })
+            })
+            .collect())
     }
 
-    async fn resolve_ref(&self, _name: &str) -> Result<String> {
-        Err(rl_api::Error::new(
-            rl_api::ErrorCode::GitBackendError,
-            "CLI refs store not fully implemented",
-        ))
+    async fn resolve_ref(&self, name: &str) -> Result<String> {
+        let output = run_git(&self.path, &["rev-parse", "--verify", "--quiet", name]).await?;
+        if !output.status.success() {
+            return Err(rl_api::Error::new(
+                rl_api::ErrorCode::InvalidRequest,
+                format!("Unknown reference: {}", name),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 }
 
@@ -385,6 +1186,182 @@ impl crate::IndexReader for CliIndexReader {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_discover_finds_the_enclosing_repository_or_worktree() {
+        use std::process::Command;
+
+        let base = std::env::temp_dir().join(format!("rl-git-discover-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let repo = base.join("repo");
+        let worktree = base.join("worktree");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(repo.join("src/nested")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .current_dir(&repo)
+                .args([
+                    "-c",
+                    "user.name=Test User",
+                    "-c",
+                    "user.email=test@example.com",
+                ])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "--quiet"]);
+        git(&["commit", "--quiet", "--allow-empty", "-m", "first"]);
+        git(&[
+            "worktree",
+            "add",
+            "--quiet",
+            "-b",
+            "linked",
+            worktree.to_str().unwrap(),
+        ]);
+        std::fs::create_dir_all(worktree.join("docs")).unwrap();
+
+        let backend = CliBackend::new();
+        let root = |path: &Path| path.canonicalize().unwrap();
+        assert_eq!(backend.discover(&repo).await.unwrap(), root(&repo));
+        assert_eq!(
+            backend.discover(&repo.join("src/nested")).await.unwrap(),
+            root(&repo)
+        );
+        // A linked worktree is its own root, not the repository it came from
+        assert_eq!(
+            backend.discover(&worktree.join("docs")).await.unwrap(),
+            root(&worktree)
+        );
+        let error = backend.discover(&outside).await.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::RepoNotFound);
+        assert!(
+            error.message.contains("not a git repository"),
+            "{}",
+            error.message
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_git_version_is_parsed_from_git_version_output() {
+        let version = |major, minor, patch| GitVersion {
+            major,
+            minor,
+            patch,
+        };
+        assert_eq!(
+            GitVersion::parse("git version 2.39.5\n"),
+            Some(version(2, 39, 5))
+        );
+        assert_eq!(
+            GitVersion::parse("git version 2.45.1.windows.1"),
+            Some(version(2, 45, 1))
+        );
+        assert_eq!(
+            GitVersion::parse("git version 2.46.GIT"),
+            Some(version(2, 46, 0))
+        );
+        assert_eq!(GitVersion::parse("sh: git: not found"), None);
+        assert!(version(2, 9, 0) < MIN_GIT_VERSION);
+    }
+
+    #[test]
+    fn test_parse_log_reads_every_commit() {
+        let output = b"c2\x1ft2\x1fc1\x1fAda\x1fada@example.com\x1f200\x1fBob\x1fbob@example.com\x1f201\x1fSecond\n\nBody\n\0\
+                       \nc1\x1ft1\x1f\x1fAda\x1fada@example.com\x1f100\x1fAda\x1fada@example.com\x1f100\x1fFirst\n\0";
+        let commits = parse_log(output).unwrap();
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].id, "c2");
+        assert_eq!(commits[0].parent_ids, vec!["c1"]);
+        assert_eq!(commits[0].committer.time, 201);
+        assert_eq!(commits[0].message, "Second\n\nBody");
+        assert!(commits[1].parent_ids.is_empty());
+
+        assert!(parse_log(b"c1\x1ft1\0").is_err());
+    }
+
+    #[test]
+    fn test_parse_stash_list_numbers_entries() {
+        let output = b"s1\x1f300\x1fOn main: tidy\0s0\x1f200\x1fWIP on main: c1 First\0";
+        let entries = parse_stash_list(output).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].index, 0);
+        assert_eq!(entries[0].message, "On main: tidy");
+        assert_eq!(entries[1].index, 1);
+        assert_eq!(entries[1].id, "s0");
+        assert_eq!(entries[1].time, 200);
+
+        assert!(parse_stash_list(b"s1\x1fsoon\x1fOn main\0").is_err());
+    }
+
+    #[test]
+    fn test_parse_clean_reads_removed_and_would_remove_lines() {
+        assert_eq!(
+            parse_clean(b"Removing build/\nRemoving notes.txt\n"),
+            vec!["build/", "notes.txt"]
+        );
+        assert_eq!(
+            parse_clean(b"Would remove a file.txt\nWould skip repository nested/\n"),
+            vec!["a file.txt"]
+        );
+    }
+
+    #[test]
+    fn test_parse_tag_list_reads_each_kind_of_tag() {
+        use rl_fixtures::tags_repo::{self, TagsRepo};
+
+        let repo = TagsRepo::create("git_tag_list").expect("Failed to create tags repo");
+        let output = std::process::Command::new("git")
+            .current_dir(&repo.path)
+            .args(["for-each-ref", "--sort=refname", TAG_FORMAT, "refs/tags"])
+            .output()
+            .unwrap();
+        let tags = parse_tag_list(&output.stdout).unwrap();
+        let tag = |name: &str| tags.iter().find(|tag| tag.name == name).unwrap();
+
+        let lightweight = tag(tags_repo::LIGHTWEIGHT);
+        assert_eq!(
+            lightweight.id,
+            repo.rev_parse(tags_repo::LIGHTWEIGHT).unwrap()
+        );
+        assert_eq!(lightweight.message, None);
+        let annotated = tag(tags_repo::ANNOTATED);
+        assert_eq!(annotated.id, repo.rev_parse(tags_repo::ANNOTATED).unwrap());
+        assert_eq!(
+            annotated.message.as_deref(),
+            Some(tags_repo::ANNOTATED_MESSAGE.trim_end())
+        );
+        if repo.signed {
+            assert_eq!(
+                tag(tags_repo::SIGNED).message.as_deref(),
+                Some(tags_repo::SIGNED_MESSAGE.trim_end())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_remote_list_groups_settings_by_remote() {
+        let output = b"remote.origin.url\n/srv/a.git\0\
+                       remote.origin.fetch\n+refs/heads/*:refs/remotes/origin/*\0\
+                       remote.my.mirror.url\n/srv/b.git\0\
+                       remote.origin.push\nrefs/heads/main\0";
+        let remotes = parse_remote_list(output);
+        assert_eq!(remotes.len(), 2);
+        assert_eq!(remotes[0].name, "origin");
+        assert_eq!(remotes[0].url, "/srv/a.git");
+        assert_eq!(
+            remotes[0].fetch_refspecs,
+            vec!["+refs/heads/*:refs/remotes/origin/*"]
+        );
+        assert_eq!(remotes[0].push_refspecs, vec!["refs/heads/main"]);
+        assert_eq!(remotes[1].name, "my.mirror");
+        assert!(remotes[1].fetch_refspecs.is_empty());
+    }
+
     #[test]
     fn test_parse_status_porcelain() {
         // Test basic untracked file
@@ -410,6 +1387,21 @@ mod tests {
         let input = b" D deleted.txt\0";
         let status = parse_status_porcelain(input).unwrap();
         assert_eq!(status.deleted, vec!["deleted.txt"]);
+        assert!(status.staged.is_empty());
+
+        // Test staged changes, changed again in the working tree or not
+        let input = b"M  staged.txt\0MM both.txt\0D  removed.txt\0RM to.txt\0from.txt\0";
+        let status = parse_status_porcelain(input).unwrap();
+        assert_eq!(
+            status.staged,
+            vec!["staged.txt", "both.txt", "removed.txt", "to.txt"]
+        );
+        assert_eq!(status.modified, vec!["both.txt", "to.txt"]);
+        assert!(status.deleted.is_empty());
+        assert_eq!(
+            status.renamed,
+            vec![("from.txt".to_string(), "to.txt".to_string())]
+        );
 
         // Test multiple files
         let input = b"?? untracked.txt\0 M modified.txt\0A  added.txt\0";
@@ -418,4 +1410,56 @@ mod tests {
         assert_eq!(status.modified, vec!["modified.txt"]);
         assert_eq!(status.added, vec!["added.txt"]);
     }
+
+    /// Records in NUL-separated output, which bounds the entries parsed from it
+    fn records(output: &[u8]) -> usize {
+        output.split(|&b| b == 0).filter(|r| !r.is_empty()).count()
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_parse_status_porcelain_survives_garbled_output(
+            output in rl_fixtures::malformed::garbled(
+                b" M src/lib.rs\0MM both.rs\0A  new file.txt\0R  to.rs\0from.rs\0 D gone.rs\0?? tmp/\0",
+            )
+        ) {
+            let status = parse_status_porcelain(&output).unwrap();
+            // A record can be both modified and deleted, as with "MD"
+            let records = records(&output);
+            for entries in [&status.staged, &status.modified, &status.added, &status.deleted, &status.untracked] {
+                proptest::prop_assert!(entries.len() <= records);
+            }
+            proptest::prop_assert!(status.renamed.len() * 2 <= records);
+        }
+
+        #[test]
+        fn test_parse_log_survives_garbled_output(
+            output in rl_fixtures::malformed::garbled(
+                b"c2\x1ft2\x1fc1\x1fAda\x1fada@example.com\x1f200\x1fBob\x1fbob@example.com\x1f201\x1fSecond\n\nBody\n\0\
+                  \nc1\x1ft1\x1f\x1fAda\x1fada@example.com\x1f100\x1fAda\x1fada@example.com\x1f100\x1fFirst\n\0",
+            )
+        ) {
+            if let Ok(commits) = parse_log(&output) {
+                proptest::prop_assert!(commits.len() <= records(&output));
+            }
+        }
+
+        #[test]
+        fn test_parse_ref_listings_survive_garbled_output(
+            stashes in rl_fixtures::malformed::garbled(
+                b"s1\x1f300\x1fOn main: tidy\0s0\x1f200\x1fWIP on main: c1 First\0",
+            ),
+            tags in rl_fixtures::malformed::garbled(
+                b"v1.0\x1ftag\x1ft1\x1fc1\x1fsig\n\x1fRelease 1.0\nsig\n\0\nv0.9\x1fcommit\x1fc0\x1f\x1f\x1f\0",
+            ),
+            remotes in rl_fixtures::malformed::garbled(
+                b"remote.origin.url\n/srv/a.git\0remote.origin.fetch\n+refs/heads/*:refs/remotes/origin/*\0\
+                  remote.up.stream.push\nrefs/heads/main\0",
+            ),
+        ) {
+            let _ = parse_stash_list(&stashes);
+            let _ = parse_tag_list(&tags);
+            parse_remote_list(&remotes);
+        }
+    }
 }
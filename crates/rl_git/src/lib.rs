@@ -4,7 +4,9 @@
 //! behind a stable trait interface.
 
 pub mod backend;
+pub mod path;
 
+use rl_api::request::ResetMode;
 use rl_api::Error;
 use std::path::Path;
 
@@ -17,14 +19,84 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Git backend trait that abstracts the underlying Git implementation.
 #[async_trait::async_trait]
 pub trait GitBackend: Send + Sync {
+    /// Short name of the implementation (e.g. `"cli"`).
+    fn kind(&self) -> &'static str;
+
     /// Open a repository at the given path.
     async fn open_repo(&self, path: &Path) -> Result<Box<dyn RepoHandle>>;
 
     /// Check if a path is a valid Git repository.
     async fn is_repo(&self, path: &Path) -> Result<bool>;
+
+    /// Detect what the git installation the backend relies on can do.
+    ///
+    /// Backends that do not run git report no version and require none.
+    async fn capabilities(&self) -> GitCapabilities {
+        GitCapabilities::default()
+    }
+}
+
+/// A git release number, e.g. `2.39.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GitVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl GitVersion {
+    /// Parse the output of `git version`, e.g. `git version 2.39.5` or
+    /// `git version 2.45.1.windows.1`.
+    pub fn parse(output: &str) -> Option<Self> {
+        let version = output.trim().strip_prefix("git version ")?;
+        let mut parts = version
+            .split(|c: char| c == '.' || c.is_whitespace())
+            .map(|part| part.parse::<u32>());
+        Some(Self {
+            major: parts.next()?.ok()?,
+            minor: parts.next()?.ok()?,
+            // Development builds report e.g. `2.45.GIT`
+            patch: parts.next().and_then(|part| part.ok()).unwrap_or(0),
+        })
+    }
+}
+
+impl std::fmt::Display for GitVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// What the git installation behind a backend can do.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitCapabilities {
+    /// Installed version; `None` if git could not be run
+    pub version: Option<GitVersion>,
+    /// Oldest version the backend works with; `None` if it needs no git
+    pub min_version: Option<GitVersion>,
+    /// Renames can be detected in diffs
+    pub rename_detection: bool,
+    /// Commit-graph files can speed up history walks
+    pub commit_graph: bool,
+    /// The built-in file system monitor daemon is available
+    pub fsmonitor: bool,
+}
+
+impl GitCapabilities {
+    /// Whether the installed git, if any is needed, is recent enough.
+    pub fn supported(&self) -> bool {
+        match self.min_version {
+            Some(min) => self.version.is_some_and(|version| version >= min),
+            None => true,
+        }
+    }
 }
 
 /// Handle to an open repository.
+///
+/// Operations are cancelled by dropping their future: implementations must
+/// then stop promptly and release anything they started, such as a git
+/// subprocess.
 #[async_trait::async_trait]
 pub trait RepoHandle: Send + Sync {
     /// Get a snapshot of the current repository state.
@@ -47,6 +119,163 @@ pub trait RepoHandle: Send + Sync {
 
     /// Get diff numstat between two revisions.
     async fn diff_numstat(&self, range: &str) -> Result<String>;
+
+    /// Get the unified patch between two revisions, optionally limited to a path.
+    async fn diff_patch(&self, range: &str, path: Option<&str>) -> Result<String>;
+
+    /// Get `git blame --porcelain` output for a file, or only for lines
+    /// `start` to `end` of it.
+    async fn blame_porcelain(
+        &self,
+        path: &str,
+        revision: Option<&str>,
+        lines: Option<(usize, usize)>,
+    ) -> Result<String>;
+
+    /// Resolve a revision to a commit ID, or `None` if it names no commit.
+    async fn rev_parse(&self, revision: &str) -> Result<Option<String>>;
+
+    /// List the commit IDs in a revision range, newest first.
+    async fn rev_list(&self, range: &str) -> Result<Vec<String>>;
+
+    /// Read up to `limit` commits of a revision range, newest first, after
+    /// skipping the first `skip`.
+    async fn log(&self, range: &str, skip: usize, limit: usize) -> Result<Vec<Commit>>;
+
+    /// Like [`RepoHandle::log`], but in topological order: no commit comes
+    /// before all of its children have.
+    async fn log_topo(&self, range: &str, skip: usize, limit: usize) -> Result<Vec<Commit>>;
+
+    /// Point a reference at `new`, or delete it when `new` is `None`.
+    ///
+    /// Fails unless the reference currently points at `expected`, or does
+    /// not exist when `expected` is `None`.
+    async fn update_ref(&self, name: &str, new: Option<&str>, expected: Option<&str>)
+        -> Result<()>;
+
+    /// Move the current branch to `commit` with `git reset --keep`, keeping
+    /// uncommitted changes and failing if they would be lost.
+    async fn reset_keep(&self, commit: &str) -> Result<()>;
+
+    /// Check out a branch, or detach HEAD at a commit.
+    async fn checkout(&self, target: &str, detach: bool) -> Result<()>;
+
+    /// Merge `source` into the current branch, fast-forwarding when it
+    /// can, with `message` for a merge commit (git's default if `None`).
+    ///
+    /// A merge that stops on conflicts is aborted, leaving the branch as it
+    /// was; the conflicting paths are returned.
+    async fn merge(&self, source: &str, message: Option<&str>) -> Result<Vec<String>>;
+
+    /// Move the current branch to `commit` with `git reset`, resetting the
+    /// index with `mixed` or `hard` and the working tree with `hard`.
+    async fn reset(&self, commit: &str, mode: ResetMode) -> Result<()>;
+
+    /// Remove untracked files, untracked directories with `directories`
+    /// and ignored files with `ignored`; with `dry_run` nothing is removed.
+    ///
+    /// Returns the paths removed, or that would be, directories ending in
+    /// `/`.
+    async fn clean(&self, directories: bool, ignored: bool, dry_run: bool) -> Result<Vec<String>>;
+
+    /// Fetch from a remote, sending each progress line to `progress` as it
+    /// arrives.
+    async fn fetch(
+        &self,
+        remote: &str,
+        refspecs: &[String],
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()>;
+
+    /// Push to a remote, sending each progress line to `progress` as it
+    /// arrives.
+    ///
+    /// With `force`, a remote reference is overwritten only if it still
+    /// points where it did at the last fetch (`--force-with-lease`).
+    async fn push(
+        &self,
+        remote: &str,
+        refspecs: &[String],
+        force: bool,
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()>;
+
+    /// Rebase the current branch onto `onto`, replaying the commits after
+    /// `upstream` (by default `onto`), sending each progress line to
+    /// `progress` as it arrives.
+    ///
+    /// A rebase that stops on conflicts is aborted, leaving the branch as
+    /// it was; the conflicting paths are returned.
+    async fn rebase(
+        &self,
+        onto: &str,
+        upstream: Option<&str>,
+        progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<Vec<String>>;
+
+    /// Save uncommitted changes, and untracked files with
+    /// `include_untracked`, as a new stash entry, cleaning the working tree.
+    ///
+    /// Returns whether there were changes to save.
+    async fn stash_push(&self, message: Option<&str>, include_untracked: bool) -> Result<bool>;
+
+    /// Apply stash entry `index` to the working tree, dropping it if `pop`.
+    ///
+    /// Returns the paths left conflicted, in which case the entry is kept.
+    async fn stash_apply(&self, index: usize, pop: bool) -> Result<Vec<String>>;
+
+    /// Delete stash entry `index`.
+    async fn stash_drop(&self, index: usize) -> Result<()>;
+
+    /// Put the stash commit `id` back on top of the stash, as `stash@{0}`,
+    /// under the message it was made with.
+    async fn stash_store(&self, id: &str) -> Result<()>;
+
+    /// List the stash entries, most recent first.
+    async fn stash_list(&self) -> Result<Vec<StashEntry>>;
+
+    /// List the tags, sorted by name.
+    async fn tag_list(&self) -> Result<Vec<TagEntry>>;
+
+    /// List the configured remotes, in the order they are configured.
+    async fn remote_list(&self) -> Result<Vec<RemoteEntry>>;
+}
+
+/// An entry of the stash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StashEntry {
+    /// Position in the stash, 0 being the most recent
+    pub index: usize,
+    /// Commit ID of the entry
+    pub id: String,
+    /// Reflog message, e.g. `WIP on main: 1a2b3c4 Fix parser`
+    pub message: String,
+    /// When the entry was made (Unix timestamp)
+    pub time: i64,
+}
+
+/// A tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagEntry {
+    /// Tag name, without `refs/tags/`
+    pub name: String,
+    /// Object the tag points at, peeled through an annotated tag
+    pub id: String,
+    /// Message of an annotated tag; `None` for a lightweight one
+    pub message: Option<String>,
+}
+
+/// A configured remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteEntry {
+    /// Remote name
+    pub name: String,
+    /// URL fetched from
+    pub url: String,
+    /// `remote.<name>.fetch` refspecs
+    pub fetch_refspecs: Vec<String>,
+    /// `remote.<name>.push` refspecs
+    pub push_refspecs: Vec<String>,
 }
 
 /// Immutable snapshot of repository state at a point in time.
@@ -71,6 +300,8 @@ pub struct RefInfo {
     pub target: String,
     /// Whether this is a symbolic reference
     pub is_symbolic: bool,
+    /// Commit an annotated tag points at, through its tag object
+    pub peeled: Option<String>,
 }
 
 /// Object store interface.
@@ -176,13 +407,15 @@ pub trait Workdir: Send + Sync {
 /// Working directory status.
 #[derive(Debug, Clone)]
 pub struct WorkdirStatus {
-    /// Modified files
+    /// Files with changes staged in the index
+    pub staged: Vec<String>,
+    /// Files modified in the working tree since they were staged
     pub modified: Vec<String>,
-    /// Added files
+    /// Files added to the index
     pub added: Vec<String>,
-    /// Deleted files
+    /// Files deleted from the working tree but not from the index
     pub deleted: Vec<String>,
-    /// Renamed files (old_name, new_name)
+    /// Files renamed in the index (old_name, new_name)
     pub renamed: Vec<(String, String)>,
     /// Untracked files
     pub untracked: Vec<String>,
@@ -213,6 +446,10 @@ pub struct StubGitBackend;
 
 #[async_trait::async_trait]
 impl GitBackend for StubGitBackend {
+    fn kind(&self) -> &'static str {
+        "stub"
+    }
+
     async fn open_repo(&self, _path: &Path) -> Result<Box<dyn RepoHandle>> {
         Err(Error::new(
             rl_api::ErrorCode::GitBackendError,
@@ -269,6 +506,190 @@ impl RepoHandle for StubRepoHandle {
             "Git backend not implemented",
         ))
     }
+
+    async fn diff_patch(&self, _range: &str, _path: Option<&str>) -> Result<String> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn blame_porcelain(
+        &self,
+        _path: &str,
+        _revision: Option<&str>,
+        _lines: Option<(usize, usize)>,
+    ) -> Result<String> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn rev_parse(&self, _revision: &str) -> Result<Option<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn rev_list(&self, _range: &str) -> Result<Vec<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn log(&self, _range: &str, _skip: usize, _limit: usize) -> Result<Vec<Commit>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+    async fn log_topo(&self, _range: &str, _skip: usize, _limit: usize) -> Result<Vec<Commit>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn update_ref(
+        &self,
+        _name: &str,
+        _new: Option<&str>,
+        _expected: Option<&str>,
+    ) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn reset_keep(&self, _commit: &str) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn checkout(&self, _target: &str, _detach: bool) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn merge(&self, _source: &str, _message: Option<&str>) -> Result<Vec<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn reset(&self, _commit: &str, _mode: ResetMode) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn clean(
+        &self,
+        _directories: bool,
+        _ignored: bool,
+        _dry_run: bool,
+    ) -> Result<Vec<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn fetch(
+        &self,
+        _remote: &str,
+        _refspecs: &[String],
+        _progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn push(
+        &self,
+        _remote: &str,
+        _refspecs: &[String],
+        _force: bool,
+        _progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn rebase(
+        &self,
+        _onto: &str,
+        _upstream: Option<&str>,
+        _progress: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<Vec<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn stash_push(&self, _message: Option<&str>, _include_untracked: bool) -> Result<bool> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn stash_apply(&self, _index: usize, _pop: bool) -> Result<Vec<String>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn stash_drop(&self, _index: usize) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn stash_store(&self, _id: &str) -> Result<()> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn stash_list(&self) -> Result<Vec<StashEntry>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn tag_list(&self) -> Result<Vec<TagEntry>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
+
+    async fn remote_list(&self) -> Result<Vec<RemoteEntry>> {
+        Err(Error::new(
+            rl_api::ErrorCode::GitBackendError,
+            "Git backend not implemented",
+        ))
+    }
 }
 
 /// Stub object store.
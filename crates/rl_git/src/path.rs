@@ -0,0 +1,107 @@
+//! Paths as git writes them, turned into the text the API reports.
+//!
+//! Git stores paths as bytes, and on POSIX systems they need not be valid
+//! UTF-8. Such a path is reported with each byte that isn't part of a
+//! UTF-8 character written as `\xNN`, in lowercase hex, so that it stays
+//! distinct from every other path instead of collapsing into U+FFFD.
+//! Everything else, non-ASCII characters included, is reported as is.
+
+/// The path git wrote as `bytes`, with bytes that aren't UTF-8 escaped.
+pub fn from_bytes(bytes: &[u8]) -> String {
+    let mut path = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        path.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            path.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    path
+}
+
+/// A path from output git writes without `-z`, where a path with bytes
+/// outside printable ASCII, a double quote or a backslash is quoted as a
+/// C string: `"caf\303\251.txt"`. Unquoted paths are returned as they are.
+pub fn unquote(path: &str) -> String {
+    let Some(quoted) = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut rest = quoted.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let Some((&escape, tail)) = rest.split_first() else {
+            bytes.push(byte);
+            break;
+        };
+        rest = tail;
+        let unescaped = match escape {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            b'0'..=b'3' => {
+                // Three octal digits, the first already read
+                let digits = [
+                    escape,
+                    *rest.first().unwrap_or(&0),
+                    *rest.get(1).unwrap_or(&0),
+                ];
+                if !digits.iter().all(|digit| (b'0'..=b'7').contains(digit)) {
+                    bytes.extend([byte, escape]);
+                    continue;
+                }
+                rest = &rest[2..];
+                digits
+                    .iter()
+                    .fold(0, |value, digit| value * 8 + (digit - b'0'))
+            }
+            b'"' | b'\\' => escape,
+            _ => {
+                bytes.extend([byte, escape]);
+                continue;
+            }
+        };
+        bytes.push(unescaped);
+    }
+    from_bytes(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_that_are_not_utf8_are_escaped() {
+        assert_eq!(from_bytes(b"src/lib.rs"), "src/lib.rs");
+        assert_eq!(from_bytes("café.txt".as_bytes()), "café.txt");
+        assert_eq!(from_bytes(b"caf\xe9.txt"), "caf\\xe9.txt");
+        assert_eq!(from_bytes(b"\xff\xfe"), "\\xff\\xfe");
+        // A character cut short is escaped byte by byte
+        assert_eq!(from_bytes(b"a\xc3"), "a\\xc3");
+    }
+
+    #[test]
+    fn test_quoted_paths_are_unquoted() {
+        assert_eq!(unquote("src/lib.rs"), "src/lib.rs");
+        assert_eq!(unquote(r#""caf\303\251.txt""#), "café.txt");
+        assert_eq!(unquote(r#""caf\351.txt""#), "caf\\xe9.txt");
+        assert_eq!(
+            unquote(r#""tab\there \"quoted\" back\\slash""#),
+            "tab\there \"quoted\" back\\slash"
+        );
+        // Malformed escapes are kept rather than lost
+        assert_eq!(unquote(r#""bad\9""#), "bad\\9");
+        assert_eq!(unquote(r#""cut\""#), "cut\\");
+    }
+}
@@ -0,0 +1,12 @@
+//! Generate the gRPC service and messages from `proto/repo_lens.proto`.
+//!
+//! A vendored `protoc` is used unless `PROTOC` is already set, so building
+//! does not require protobuf tooling on the machine.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+    tonic_build::compile_protos("proto/repo_lens.proto")?;
+    Ok(())
+}
@@ -0,0 +1,863 @@
+//! Conversions between the generated protobuf messages and rl_api DTOs.
+//!
+//! Requests are validated on the way in, with the same bounds the JSON
+//! protocol enforces; responses convert infallibly on the way out.
+
+use crate::proto;
+use rl_api::request::{self, RequestPayload};
+use rl_api::response::{self, ResponsePayload};
+use rl_api::{ErrorCode, Paging};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+/// Metadata key carrying the rl_api error code of a failed call.
+pub const ERROR_CODE_KEY: &str = "rl-error-code";
+
+/// Metadata key carrying remediation hints, when the error has any.
+pub const REMEDIATION_KEY: &str = "rl-remediation";
+
+/// A protobuf request that maps onto an rl_api request payload.
+pub(crate) trait IntoPayload {
+    fn into_payload(self) -> Result<RequestPayload, Status>;
+}
+
+/// Turn an engine error into a gRPC status, keeping the typed code in
+/// metadata so clients do not have to parse messages.
+pub fn error_to_status(error: rl_api::Error) -> Status {
+    let code = match error.code {
+        ErrorCode::InvalidRequest | ErrorCode::InvalidCursor => Code::InvalidArgument,
+        ErrorCode::UnsupportedVersion | ErrorCode::UnsupportedEncoding => Code::Unimplemented,
+        ErrorCode::RepoNotFound => Code::NotFound,
+        ErrorCode::Conflict => Code::Aborted,
+        ErrorCode::AuthRequired => Code::Unauthenticated,
+        ErrorCode::OperationCanceled => Code::Cancelled,
+        ErrorCode::Timeout => Code::DeadlineExceeded,
+        ErrorCode::Overloaded | ErrorCode::ConnectionLost => Code::Unavailable,
+        ErrorCode::RateLimited | ErrorCode::QuotaExceeded => Code::ResourceExhausted,
+        ErrorCode::GitBackendError | ErrorCode::Internal => Code::Internal,
+    };
+
+    let mut status = Status::new(code, error.message);
+    if let Ok(value) = MetadataValue::try_from(error.code.to_string()) {
+        status.metadata_mut().insert(ERROR_CODE_KEY, value);
+    }
+    if let Some(value) = error
+        .remediation
+        .and_then(|hint| MetadataValue::try_from(hint).ok())
+    {
+        status.metadata_mut().insert(REMEDIATION_KEY, value);
+    }
+    status
+}
+
+/// Status for a response payload that does not belong to the called RPC.
+pub(crate) fn unexpected_payload(payload: &ResponsePayload) -> Status {
+    Status::internal(format!(
+        "Engine returned an unexpected payload: {:?}",
+        payload
+    ))
+}
+
+fn bounded<T, V>(field: &str, value: V) -> Result<T, Status>
+where
+    T: TryFrom<V, Error = rl_api::bounds::BoundsError>,
+{
+    T::try_from(value).map_err(|e| Status::invalid_argument(format!("{}: {}", field, e)))
+}
+
+fn refspecs(refspecs: Vec<String>) -> Option<Vec<String>> {
+    if refspecs.is_empty() {
+        None
+    } else {
+        Some(refspecs)
+    }
+}
+
+// Requests
+
+impl IntoPayload for proto::StatusRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Status(request::StatusRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::LogRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Log(request::LogRequest {
+            repo_path: self.repo_path,
+            paging: Paging {
+                page_size: bounded("page_size", self.page_size)?,
+                cursor: self.cursor.into(),
+            },
+            revision_range: self.revision_range,
+        }))
+    }
+}
+
+impl IntoPayload for proto::GraphRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Graph(request::GraphRequest {
+            repo_path: self.repo_path,
+            window_size: bounded("window_size", self.window_size)?,
+            cursor: self.cursor.into(),
+            revision_range: self.revision_range,
+        }))
+    }
+}
+
+impl IntoPayload for proto::ShowCommitRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::ShowCommit(request::ShowCommitRequest {
+            repo_path: self.repo_path,
+            commit_id: self.commit_id,
+        }))
+    }
+}
+
+impl IntoPayload for proto::DiffSummaryRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::DiffSummary(request::DiffSummaryRequest {
+            repo_path: self.repo_path,
+            from: self.from,
+            to: self.to,
+            max_bytes: bounded("max_bytes", self.max_bytes)?,
+            max_hunks: bounded("max_hunks", self.max_hunks)?,
+        }))
+    }
+}
+
+impl IntoPayload for proto::DiffContentRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::DiffContent(request::DiffContentRequest {
+            repo_path: self.repo_path,
+            from: self.from,
+            to: self.to,
+            path: self.path,
+            max_bytes: bounded("max_bytes", self.max_bytes)?,
+        }))
+    }
+}
+
+impl IntoPayload for proto::BlameRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Blame(request::BlameRequest {
+            repo_path: self.repo_path,
+            path: self.path,
+            revision: self.revision,
+            lines: match (self.start_line, self.end_line) {
+                (Some(start), Some(end)) => Some(request::LineRange {
+                    start: start as usize,
+                    end: end as usize,
+                }),
+                (None, None) => None,
+                _ => {
+                    return Err(Status::invalid_argument(
+                        "start_line and end_line must be given together",
+                    ))
+                }
+            },
+        }))
+    }
+}
+
+impl IntoPayload for proto::BranchesRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Branches(request::BranchesRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::TagsRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Tags(request::TagsRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::RemotesRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Remotes(request::RemotesRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::CheckoutRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Checkout(request::CheckoutRequest {
+            repo_path: self.repo_path,
+            target: self.target,
+            create_branch: self.create_branch,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::CommitRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Commit(request::CommitRequest {
+            repo_path: self.repo_path,
+            message: self.message,
+            author_name: self.author_name,
+            author_email: self.author_email,
+        }))
+    }
+}
+
+impl IntoPayload for proto::FetchRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Fetch(request::FetchRequest {
+            repo_path: self.repo_path,
+            remote: self.remote,
+            refspecs: refspecs(self.refspecs),
+        }))
+    }
+}
+
+impl IntoPayload for proto::PushRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Push(request::PushRequest {
+            repo_path: self.repo_path,
+            remote: self.remote,
+            refspecs: refspecs(self.refspecs),
+            force: self.force,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::MergeRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Merge(request::MergeRequest {
+            repo_path: self.repo_path,
+            source: self.source,
+            message: self.message,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::RebaseRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Rebase(request::RebaseRequest {
+            repo_path: self.repo_path,
+            onto: self.onto,
+            upstream: self.upstream,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::ResetRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        let mode = match proto::ResetMode::try_from(self.mode) {
+            Ok(proto::ResetMode::Mixed) => request::ResetMode::Mixed,
+            Ok(proto::ResetMode::Soft) => request::ResetMode::Soft,
+            Ok(proto::ResetMode::Hard) => request::ResetMode::Hard,
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "mode: unknown reset mode {}",
+                    self.mode
+                )))
+            }
+        };
+        Ok(RequestPayload::Reset(request::ResetRequest {
+            repo_path: self.repo_path,
+            target: self.target,
+            mode,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::CleanRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Clean(request::CleanRequest {
+            repo_path: self.repo_path,
+            directories: self.directories,
+            ignored: self.ignored,
+            dry_run: false,
+        }))
+    }
+}
+
+impl IntoPayload for proto::StashRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        let action = match proto::StashAction::try_from(self.action) {
+            Ok(proto::StashAction::Push) => request::StashAction::Push,
+            Ok(proto::StashAction::Pop) => request::StashAction::Pop,
+            Ok(proto::StashAction::Apply) => request::StashAction::Apply,
+            Ok(proto::StashAction::Drop) => request::StashAction::Drop,
+            Ok(proto::StashAction::List) => request::StashAction::List,
+            Ok(proto::StashAction::Show) => request::StashAction::Show,
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "action: unknown stash action {}",
+                    self.action
+                )))
+            }
+        };
+        Ok(RequestPayload::Stash(request::StashRequest {
+            repo_path: self.repo_path,
+            action,
+            message: self.message,
+            include_untracked: self.include_untracked,
+            index: self.index.map(|index| index as usize),
+        }))
+    }
+}
+
+impl IntoPayload for proto::WatchRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Watch(request::WatchRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::UndoRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Undo(request::UndoRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::JournalRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Journal(request::JournalRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::DryRunRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        use proto::dry_run_request::Operation;
+
+        let payload = match self.operation {
+            Some(Operation::Checkout(req)) => req.into_payload()?,
+            Some(Operation::Push(req)) => req.into_payload()?,
+            Some(Operation::Merge(req)) => req.into_payload()?,
+            Some(Operation::Rebase(req)) => req.into_payload()?,
+            Some(Operation::Reset(req)) => req.into_payload()?,
+            Some(Operation::Clean(req)) => req.into_payload()?,
+            None => return Err(Status::invalid_argument("operation: missing")),
+        };
+        Ok(match payload {
+            RequestPayload::Checkout(req) => RequestPayload::Checkout(request::CheckoutRequest {
+                dry_run: true,
+                ..req
+            }),
+            RequestPayload::Push(req) => RequestPayload::Push(request::PushRequest {
+                dry_run: true,
+                ..req
+            }),
+            RequestPayload::Merge(req) => RequestPayload::Merge(request::MergeRequest {
+                dry_run: true,
+                ..req
+            }),
+            RequestPayload::Rebase(req) => RequestPayload::Rebase(request::RebaseRequest {
+                dry_run: true,
+                ..req
+            }),
+            RequestPayload::Reset(req) => RequestPayload::Reset(request::ResetRequest {
+                dry_run: true,
+                ..req
+            }),
+            RequestPayload::Clean(req) => RequestPayload::Clean(request::CleanRequest {
+                dry_run: true,
+                ..req
+            }),
+            other => other,
+        })
+    }
+}
+
+impl IntoPayload for proto::OpenRepoRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::OpenRepo(request::OpenRepoRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::CloseRepoRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::CloseRepo(request::CloseRepoRequest {
+            repo_path: self.repo_path,
+        }))
+    }
+}
+
+impl IntoPayload for proto::ListReposRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::ListRepos(request::ListReposRequest {}))
+    }
+}
+
+impl IntoPayload for proto::CapabilitiesRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::Capabilities(
+            request::CapabilitiesRequest {},
+        ))
+    }
+}
+
+impl IntoPayload for proto::ReloadConfigRequest {
+    fn into_payload(self) -> Result<RequestPayload, Status> {
+        Ok(RequestPayload::ReloadConfig(
+            request::ReloadConfigRequest {},
+        ))
+    }
+}
+
+// Responses
+
+impl From<response::StatusView> for proto::StatusView {
+    fn from(view: response::StatusView) -> Self {
+        Self {
+            branch: view.branch,
+            head: view.head,
+            workdir: Some(proto::WorkdirStatus {
+                modified: view.workdir.modified,
+                added: view.workdir.added,
+                deleted: view.workdir.deleted,
+                renamed: view
+                    .workdir
+                    .renamed
+                    .into_iter()
+                    .map(|(from, to)| proto::RenamedFile { from, to })
+                    .collect(),
+                untracked: view.workdir.untracked,
+            }),
+            index: Some(proto::IndexStatus {
+                staged: view.index.staged,
+            }),
+        }
+    }
+}
+
+impl From<response::CommitSummary> for proto::CommitSummary {
+    fn from(commit: response::CommitSummary) -> Self {
+        Self {
+            id: commit.id,
+            message: commit.message,
+            author_name: commit.author_name,
+            author_email: commit.author_email,
+            time: commit.time,
+            parents: commit.parents,
+        }
+    }
+}
+
+impl From<response::CommitListPage> for proto::CommitListPage {
+    fn from(page: response::CommitListPage) -> Self {
+        Self {
+            commits: page.commits.into_iter().map(Into::into).collect(),
+            next_cursor: page.next_cursor.map(|cursor| cursor.get().to_string()),
+            has_more: page.has_more,
+        }
+    }
+}
+
+impl From<response::LaneType> for proto::LaneType {
+    fn from(lane_type: response::LaneType) -> Self {
+        match lane_type {
+            response::LaneType::Commit => Self::Commit,
+            response::LaneType::Merge => Self::Merge,
+            response::LaneType::Branch => Self::Branch,
+            response::LaneType::Empty => Self::Empty,
+        }
+    }
+}
+
+impl From<response::CommitGraphWindow> for proto::CommitGraphWindow {
+    fn from(window: response::CommitGraphWindow) -> Self {
+        Self {
+            commits: window
+                .commits
+                .into_iter()
+                .map(|node| proto::CommitGraphNode {
+                    commit: Some(node.commit.into()),
+                    lanes: node
+                        .lanes
+                        .into_iter()
+                        .map(|lane| proto::GraphLane {
+                            index: lane.index as u32,
+                            lane_type: proto::LaneType::from(lane.lane_type) as i32,
+                        })
+                        .collect(),
+                    refs: node.refs,
+                })
+                .collect(),
+            next_cursor: window.next_cursor.map(|cursor| cursor.get().to_string()),
+            has_more: window.has_more,
+        }
+    }
+}
+
+impl From<response::ChangeType> for proto::ChangeType {
+    fn from(change_type: response::ChangeType) -> Self {
+        match change_type {
+            response::ChangeType::Added => Self::Added,
+            response::ChangeType::Modified => Self::Modified,
+            response::ChangeType::Deleted => Self::Deleted,
+            response::ChangeType::Renamed => Self::Renamed,
+        }
+    }
+}
+
+impl From<response::FileChange> for proto::FileChange {
+    fn from(change: response::FileChange) -> Self {
+        Self {
+            path: change.path,
+            change_type: proto::ChangeType::from(change.change_type) as i32,
+            additions: change.additions as u64,
+            deletions: change.deletions as u64,
+            old_path: change.old_path,
+        }
+    }
+}
+
+impl From<response::CommitDetails> for proto::CommitDetails {
+    fn from(details: response::CommitDetails) -> Self {
+        Self {
+            summary: Some(details.summary.into()),
+            full_message: details.full_message,
+            changed_files: details.changed_files.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<response::DiffSummary> for proto::DiffSummary {
+    fn from(summary: response::DiffSummary) -> Self {
+        Self {
+            files_changed: summary.files_changed as u64,
+            additions: summary.additions as u64,
+            deletions: summary.deletions as u64,
+            changes: summary.changes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<response::Range> for proto::Range {
+    fn from(range: response::Range) -> Self {
+        Self {
+            start: range.start as u64,
+            count: range.count as u64,
+        }
+    }
+}
+
+impl From<response::DiffLineType> for proto::DiffLineType {
+    fn from(line_type: response::DiffLineType) -> Self {
+        match line_type {
+            response::DiffLineType::Context => Self::Context,
+            response::DiffLineType::Addition => Self::Addition,
+            response::DiffLineType::Deletion => Self::Deletion,
+        }
+    }
+}
+
+impl From<response::DiffChunk> for proto::DiffChunk {
+    fn from(chunk: response::DiffChunk) -> Self {
+        Self {
+            path: chunk.path,
+            hunks: chunk
+                .hunks
+                .into_iter()
+                .map(|hunk| proto::DiffHunk {
+                    old_range: Some(hunk.old_range.into()),
+                    new_range: Some(hunk.new_range.into()),
+                    header: hunk.header,
+                    lines: hunk
+                        .lines
+                        .into_iter()
+                        .map(|line| proto::DiffLine {
+                            line_type: proto::DiffLineType::from(line.line_type) as i32,
+                            old_line: line.old_line.map(|n| n as u64),
+                            new_line: line.new_line.map(|n| n as u64),
+                            content: line.content,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<response::BlameChunk> for proto::BlameChunk {
+    fn from(chunk: response::BlameChunk) -> Self {
+        Self {
+            path: chunk.path,
+            lines: chunk
+                .lines
+                .into_iter()
+                .map(|line| proto::BlameLine {
+                    line_number: line.line_number as u64,
+                    commit_id: line.commit_id,
+                    author_name: line.author_name,
+                    author_email: line.author_email,
+                    content: line.content,
+                    author_time: line.author_time,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<response::BranchInfo> for proto::BranchInfo {
+    fn from(branch: response::BranchInfo) -> Self {
+        Self {
+            name: branch.name,
+            commit_id: branch.commit_id,
+            is_remote: branch.is_remote,
+        }
+    }
+}
+
+impl From<response::BranchList> for proto::BranchList {
+    fn from(list: response::BranchList) -> Self {
+        Self {
+            local: list.local.into_iter().map(Into::into).collect(),
+            remote: list.remote.into_iter().map(Into::into).collect(),
+            current: list.current,
+        }
+    }
+}
+
+impl From<response::TagList> for proto::TagList {
+    fn from(list: response::TagList) -> Self {
+        Self {
+            tags: list
+                .tags
+                .into_iter()
+                .map(|tag| proto::TagInfo {
+                    name: tag.name,
+                    commit_id: tag.commit_id,
+                    message: tag.message,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<response::RemoteList> for proto::RemoteList {
+    fn from(list: response::RemoteList) -> Self {
+        Self {
+            remotes: list
+                .remotes
+                .into_iter()
+                .map(|remote| proto::RemoteInfo {
+                    name: remote.name,
+                    url: remote.url,
+                    fetch_refspecs: remote.fetch_refspecs,
+                    push_refspecs: remote.push_refspecs,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<response::RepoInfo> for proto::RepoInfo {
+    fn from(info: response::RepoInfo) -> Self {
+        Self {
+            repo_path: info.repo_path,
+            watch_active: info.watch_active,
+            cache_bytes: info.cache_bytes,
+            backend: info.backend,
+        }
+    }
+}
+
+impl From<response::RepoList> for proto::RepoList {
+    fn from(list: response::RepoList) -> Self {
+        Self {
+            repos: list.repos.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<response::Capabilities> for proto::BackendCapabilities {
+    fn from(capabilities: response::Capabilities) -> Self {
+        Self {
+            backend: capabilities.backend,
+            git_available: capabilities.git_available,
+            git_version: capabilities.git_version,
+            min_git_version: capabilities.min_git_version,
+            supported: capabilities.supported,
+            features: Some(proto::GitFeatures {
+                rename_detection: capabilities.features.rename_detection,
+                commit_graph: capabilities.features.commit_graph,
+                fsmonitor: capabilities.features.fsmonitor,
+            }),
+        }
+    }
+}
+
+impl From<response::OperationResult> for proto::OperationResult {
+    fn from(result: response::OperationResult) -> Self {
+        Self {
+            success: result.success,
+            message: result.message,
+        }
+    }
+}
+
+impl From<response::MergeType> for proto::MergeType {
+    fn from(merge_type: response::MergeType) -> Self {
+        match merge_type {
+            response::MergeType::FastForward => Self::FastForward,
+            response::MergeType::MergeCommit => Self::MergeCommit,
+            response::MergeType::UpToDate => Self::UpToDate,
+        }
+    }
+}
+
+impl From<response::MergeResult> for proto::MergeResult {
+    fn from(result: response::MergeResult) -> Self {
+        Self {
+            success: result.success,
+            merge_type: proto::MergeType::from(result.merge_type) as i32,
+            conflicts: result.conflicts,
+        }
+    }
+}
+
+impl From<response::RebaseResult> for proto::RebaseResult {
+    fn from(result: response::RebaseResult) -> Self {
+        Self {
+            success: result.success,
+            commits_rebased: result.commits_rebased as u64,
+            conflicts: result.conflicts,
+        }
+    }
+}
+
+impl From<response::CleanResult> for proto::CleanResult {
+    fn from(result: response::CleanResult) -> Self {
+        Self {
+            removed: result.removed,
+        }
+    }
+}
+
+impl From<response::DryRunReport> for proto::DryRunReport {
+    fn from(report: response::DryRunReport) -> Self {
+        Self {
+            files: report.files,
+            refs: report.refs.into_iter().map(Into::into).collect(),
+            commits_rewritten: report.commits_rewritten,
+        }
+    }
+}
+
+impl From<response::RefUpdate> for proto::RefUpdate {
+    fn from(update: response::RefUpdate) -> Self {
+        Self {
+            name: update.name,
+            old: update.old,
+            new: update.new,
+        }
+    }
+}
+
+impl From<response::JournalEntry> for proto::JournalEntry {
+    fn from(entry: response::JournalEntry) -> Self {
+        Self {
+            id: entry.id,
+            operation: entry.operation,
+            timestamp_ms: entry.timestamp_ms,
+            head_before: entry.head_before,
+            head_after: entry.head_after,
+            branch_before: entry.branch_before,
+            branch_after: entry.branch_after,
+            refs: entry.refs.into_iter().map(Into::into).collect(),
+            stash_created: entry.stash_created,
+            stash_dropped: entry.stash_dropped,
+        }
+    }
+}
+
+impl From<response::StashResult> for proto::StashResult {
+    fn from(result: response::StashResult) -> Self {
+        Self {
+            entries: result.entries.into_iter().map(Into::into).collect(),
+            diff: result.diff.map(Into::into),
+        }
+    }
+}
+
+impl From<response::StashEntry> for proto::StashEntry {
+    fn from(entry: response::StashEntry) -> Self {
+        Self {
+            index: entry.index as u64,
+            commit: entry.commit,
+            message: entry.message,
+            timestamp: entry.timestamp,
+        }
+    }
+}
+
+impl From<response::OperationJournal> for proto::OperationJournal {
+    fn from(journal: response::OperationJournal) -> Self {
+        Self {
+            entries: journal.entries.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<response::ProgressUpdate> for proto::ProgressUpdate {
+    fn from(update: response::ProgressUpdate) -> Self {
+        Self {
+            stage: update.stage,
+            progress: update.progress.into(),
+            message: update.message,
+        }
+    }
+}
+
+impl From<rl_api::Event> for proto::Event {
+    fn from(event: rl_api::Event) -> Self {
+        use proto::event::Event as Kind;
+        use rl_api::Event;
+
+        let kind = match event {
+            Event::HeadChanged(e) => Kind::HeadChanged(proto::HeadChangedEvent {
+                repo_path: e.repo_path,
+                new_head: e.new_head,
+                old_head: e.old_head,
+            }),
+            Event::IndexChanged(e) => Kind::IndexChanged(proto::IndexChangedEvent {
+                repo_path: e.repo_path,
+                changed_files: e.changed_files,
+            }),
+            Event::WorkdirChanged(e) => Kind::WorkdirChanged(proto::WorkdirChangedEvent {
+                repo_path: e.repo_path,
+                changed_files: e.changed_files,
+            }),
+            Event::RefsChanged(e) => Kind::RefsChanged(proto::RefsChangedEvent {
+                repo_path: e.repo_path,
+                changed_refs: e.changed_refs,
+            }),
+            Event::RepoOpened(e) => Kind::RepoOpened(proto::RepoOpenedEvent {
+                repo_path: e.repo_path,
+            }),
+            Event::RepoClosed(e) => Kind::RepoClosed(proto::RepoClosedEvent {
+                repo_path: e.repo_path,
+            }),
+            Event::OperationProgress(e) => Kind::OperationProgress(proto::OperationProgressEvent {
+                repo_path: e.repo_path,
+                operation_id: e.operation_id,
+                progress: e.progress.into(),
+                message: e.message,
+            }),
+        };
+        Self { event: Some(kind) }
+    }
+}
@@ -0,0 +1,206 @@
+//! gRPC transport for the repo-lens engine.
+//!
+//! Exposes the rl_api surface as the `repo_lens.v0.RepoLens` service defined
+//! in `proto/repo_lens.proto`, so clients in any language can generate a
+//! typed stub instead of speaking the newline-delimited JSON protocol by
+//! hand. Diff content, blame, fetch/push progress and watch events are
+//! server-streaming RPCs.
+//!
+//! When a token is configured, every call must carry an
+//! `authorization: Bearer <token>` header.
+
+// `tonic::Status` is large, but it is the error type every handler returns
+#![allow(clippy::result_large_err)]
+
+pub mod convert;
+pub mod service;
+
+/// Messages, client and server generated from `proto/repo_lens.proto`.
+pub mod proto {
+    tonic::include_proto!("repo_lens.v0");
+}
+
+pub use convert::{error_to_status, ERROR_CODE_KEY, REMEDIATION_KEY};
+pub use service::RepoLensService;
+
+use proto::repo_lens_server::RepoLensServer;
+use rl_core::RepoEngine;
+use rl_ipc::AuthToken;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::Status;
+
+/// gRPC server for the repo engine.
+pub struct GrpcServer {
+    /// The repo engine, shared by every call
+    engine: Arc<RepoEngine>,
+    /// Token callers must present, if any
+    auth_token: Option<AuthToken>,
+}
+
+impl GrpcServer {
+    /// Create a server that accepts unauthenticated calls.
+    pub fn new(engine: RepoEngine) -> Self {
+        Self::with_auth_token(engine, None)
+    }
+
+    /// Create a server that requires `auth_token` on every call when set.
+    pub fn with_auth_token(engine: RepoEngine, auth_token: Option<AuthToken>) -> Self {
+        Self {
+            engine: Arc::new(engine),
+            auth_token,
+        }
+    }
+
+    /// The engine serving calls.
+    pub fn engine(&self) -> &Arc<RepoEngine> {
+        &self.engine
+    }
+
+    /// Serve on `addr` until the transport fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+    }
+
+    /// Serve clients accepted by an already bound listener.
+    pub async fn serve_with_listener(
+        self,
+        listener: tokio::net::TcpListener,
+    ) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+    }
+
+    /// The service with authentication applied, for mounting alongside
+    /// other services on a custom tonic server.
+    pub fn into_service(self) -> InterceptedService<RepoLensServer<RepoLensService>, TokenCheck> {
+        RepoLensServer::with_interceptor(
+            RepoLensService::new(self.engine),
+            TokenCheck {
+                token: self.auth_token,
+            },
+        )
+    }
+}
+
+/// Interceptor that rejects calls without the configured bearer token.
+#[derive(Clone)]
+pub struct TokenCheck {
+    /// Expected token; every call passes when `None`
+    token: Option<AuthToken>,
+}
+
+impl Interceptor for TokenCheck {
+    fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let Some(token) = &self.token else {
+            return Ok(request);
+        };
+
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match presented {
+            Some(candidate) if token.verify(candidate) => Ok(request),
+            _ => Err(error_to_status(rl_api::Error::new(
+                rl_api::ErrorCode::AuthRequired,
+                "Missing or invalid bearer token",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::repo_lens_client::RepoLensClient;
+    use tokio_stream::StreamExt;
+    use tonic::transport::Channel;
+
+    async fn start(auth_token: Option<AuthToken>) -> RepoLensClient<Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = GrpcServer::with_auth_token(RepoEngine::new(), auth_token);
+        tokio::spawn(server.serve_with_listener(listener));
+        RepoLensClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unary_and_streaming_calls() {
+        let mut client = start(None).await;
+
+        let status = client
+            .status(proto::StatusRequest {
+                repo_path: ".".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(status.head.is_some());
+
+        let error = client
+            .show_commit(proto::ShowCommitRequest {
+                repo_path: ".".to_string(),
+                commit_id: "HEAD".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::Internal);
+        assert_eq!(
+            error.metadata().get(ERROR_CODE_KEY).unwrap(),
+            "git_backend_error"
+        );
+
+        let mut chunks = client
+            .diff_content(proto::DiffContentRequest {
+                repo_path: ".".to_string(),
+                from: Some("HEAD".to_string()),
+                to: Some("HEAD".to_string()),
+                path: None,
+                max_bytes: 1024,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        while let Some(chunk) = chunks.next().await {
+            chunk.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_validated_and_authenticated() {
+        let token = AuthToken::new("s3cret");
+        let mut client = start(Some(token)).await;
+
+        let rejected = client
+            .status(proto::StatusRequest {
+                repo_path: ".".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(rejected.code(), tonic::Code::Unauthenticated);
+
+        let mut request = tonic::Request::new(proto::LogRequest {
+            repo_path: ".".to_string(),
+            page_size: 0,
+            cursor: String::new(),
+            revision_range: None,
+        });
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cret".parse().unwrap());
+        let invalid = client.log(request).await.unwrap_err();
+        assert_eq!(invalid.code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,465 @@
+//! The `RepoLens` gRPC service, backed by a [`RepoEngine`].
+
+use crate::convert::{error_to_status, unexpected_payload, IntoPayload};
+use crate::proto;
+use crate::proto::repo_lens_server::RepoLens;
+use rl_api::response::ResponsePayload;
+use rl_api::Request;
+use rl_core::events::canonical_repo_path;
+use rl_core::session::Session;
+use rl_core::RepoEngine;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+
+/// Chunks buffered per streaming call before the engine waits on the client.
+const STREAM_BUFFER: usize = 16;
+
+/// Metadata entry carrying a mutation's idempotency key.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Server-streaming response body.
+pub type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// Pulls the payload an RPC expects out of an engine response, handing
+/// anything else back.
+type Extract<T> = fn(ResponsePayload) -> Result<T, ResponsePayload>;
+
+/// Serves each RPC by translating it into an rl_api request for the engine.
+pub struct RepoLensService {
+    /// The repo engine, shared by every call
+    engine: Arc<RepoEngine>,
+    /// Source of request ids for engine tracing
+    next_id: AtomicU64,
+}
+
+impl RepoLensService {
+    /// Create a service backed by `engine`.
+    pub fn new(engine: Arc<RepoEngine>) -> Self {
+        Self {
+            engine,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    fn request(&self, request: tonic::Request<impl IntoPayload>) -> Result<Request, Status> {
+        let idempotency_key = match request.metadata().get(IDEMPOTENCY_KEY_HEADER) {
+            Some(key) => Some(
+                key.to_str()
+                    .map_err(|_| {
+                        Status::invalid_argument("idempotency-key metadata must be ASCII")
+                    })?
+                    .to_string(),
+            ),
+            None => None,
+        };
+        let id = format!("grpc-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        Ok(Request {
+            idempotency_key,
+            ..Request::new(id, request.into_inner().into_payload()?)
+        })
+    }
+
+    /// Run a request that produces a single response.
+    async fn unary<T>(
+        &self,
+        request: tonic::Request<impl IntoPayload>,
+        extract: Extract<T>,
+    ) -> Result<tonic::Response<T>, Status> {
+        let request = self.request(request)?;
+        match self.engine.handle(request).await.result {
+            Ok(payload) => extract(payload)
+                .map(tonic::Response::new)
+                .map_err(|payload| unexpected_payload(&payload)),
+            Err(error) => Err(error_to_status(error)),
+        }
+    }
+
+    /// Run a streaming request, forwarding each chunk as it is produced.
+    ///
+    /// The stream ends after the final chunk, or with the error status if
+    /// the request fails part way through.
+    fn stream<T: Send + 'static>(
+        &self,
+        request: tonic::Request<impl IntoPayload>,
+        extract: Extract<T>,
+    ) -> Result<tonic::Response<ResponseStream<T>>, Status> {
+        let request = self.request(request)?;
+        let (response_tx, response_rx) = mpsc::channel(STREAM_BUFFER);
+        let engine = self.engine.clone();
+        tokio::spawn(async move { engine.handle_stream(request, response_tx).await });
+
+        let chunks = ReceiverStream::new(response_rx).map(move |response| match response.result {
+            Ok(payload) => extract(payload).map_err(|payload| unexpected_payload(&payload)),
+            Err(error) => Err(error_to_status(error)),
+        });
+        Ok(tonic::Response::new(Box::pin(chunks)))
+    }
+}
+
+#[tonic::async_trait]
+impl RepoLens for RepoLensService {
+    type DiffContentStream = ResponseStream<proto::DiffChunk>;
+    type BlameStream = ResponseStream<proto::BlameChunk>;
+    type FetchStream = ResponseStream<proto::ProgressUpdate>;
+    type PushStream = ResponseStream<proto::ProgressUpdate>;
+    type WatchStream = ResponseStream<proto::Event>;
+
+    async fn status(
+        &self,
+        request: tonic::Request<proto::StatusRequest>,
+    ) -> Result<tonic::Response<proto::StatusView>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Status(view) => Ok(view.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn log(
+        &self,
+        request: tonic::Request<proto::LogRequest>,
+    ) -> Result<tonic::Response<proto::CommitListPage>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Log(page) => Ok(page.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn graph(
+        &self,
+        request: tonic::Request<proto::GraphRequest>,
+    ) -> Result<tonic::Response<proto::CommitGraphWindow>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Graph(window) => Ok(window.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn show_commit(
+        &self,
+        request: tonic::Request<proto::ShowCommitRequest>,
+    ) -> Result<tonic::Response<proto::CommitDetails>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::ShowCommit(details) => Ok(details.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn diff_summary(
+        &self,
+        request: tonic::Request<proto::DiffSummaryRequest>,
+    ) -> Result<tonic::Response<proto::DiffSummary>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::DiffSummary(summary) => Ok(summary.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn diff_content(
+        &self,
+        request: tonic::Request<proto::DiffContentRequest>,
+    ) -> Result<tonic::Response<Self::DiffContentStream>, Status> {
+        self.stream(request, |payload| match payload {
+            ResponsePayload::DiffContent(chunk) => Ok(chunk.data.into()),
+            other => Err(other),
+        })
+    }
+
+    async fn blame(
+        &self,
+        request: tonic::Request<proto::BlameRequest>,
+    ) -> Result<tonic::Response<Self::BlameStream>, Status> {
+        self.stream(request, |payload| match payload {
+            ResponsePayload::Blame(chunk) => Ok(chunk.data.into()),
+            other => Err(other),
+        })
+    }
+
+    async fn branches(
+        &self,
+        request: tonic::Request<proto::BranchesRequest>,
+    ) -> Result<tonic::Response<proto::BranchList>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Branches(list) => Ok(list.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn tags(
+        &self,
+        request: tonic::Request<proto::TagsRequest>,
+    ) -> Result<tonic::Response<proto::TagList>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Tags(list) => Ok(list.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn remotes(
+        &self,
+        request: tonic::Request<proto::RemotesRequest>,
+    ) -> Result<tonic::Response<proto::RemoteList>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Remotes(list) => Ok(list.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn checkout(
+        &self,
+        request: tonic::Request<proto::CheckoutRequest>,
+    ) -> Result<tonic::Response<proto::OperationResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::OperationResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn commit(
+        &self,
+        request: tonic::Request<proto::CommitRequest>,
+    ) -> Result<tonic::Response<proto::OperationResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::OperationResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn fetch(
+        &self,
+        request: tonic::Request<proto::FetchRequest>,
+    ) -> Result<tonic::Response<Self::FetchStream>, Status> {
+        self.stream(request, |payload| match payload {
+            ResponsePayload::Progress(chunk) => Ok(chunk.data.into()),
+            other => Err(other),
+        })
+    }
+
+    async fn push(
+        &self,
+        request: tonic::Request<proto::PushRequest>,
+    ) -> Result<tonic::Response<Self::PushStream>, Status> {
+        self.stream(request, |payload| match payload {
+            ResponsePayload::Progress(chunk) => Ok(chunk.data.into()),
+            other => Err(other),
+        })
+    }
+
+    async fn merge(
+        &self,
+        request: tonic::Request<proto::MergeRequest>,
+    ) -> Result<tonic::Response<proto::MergeResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::MergeResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn rebase(
+        &self,
+        request: tonic::Request<proto::RebaseRequest>,
+    ) -> Result<tonic::Response<proto::RebaseResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::RebaseResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn reset(
+        &self,
+        request: tonic::Request<proto::ResetRequest>,
+    ) -> Result<tonic::Response<proto::OperationResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::OperationResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn clean(
+        &self,
+        request: tonic::Request<proto::CleanRequest>,
+    ) -> Result<tonic::Response<proto::CleanResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Clean(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn stash(
+        &self,
+        request: tonic::Request<proto::StashRequest>,
+    ) -> Result<tonic::Response<proto::StashResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Stash(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn undo(
+        &self,
+        request: tonic::Request<proto::UndoRequest>,
+    ) -> Result<tonic::Response<proto::JournalEntry>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Undo(entry) => Ok(entry.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn journal(
+        &self,
+        request: tonic::Request<proto::JournalRequest>,
+    ) -> Result<tonic::Response<proto::OperationJournal>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Journal(journal) => Ok(journal.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn dry_run(
+        &self,
+        request: tonic::Request<proto::DryRunRequest>,
+    ) -> Result<tonic::Response<proto::DryRunReport>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::DryRun(report) => Ok(report.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    /// Acknowledges the watch, then streams change events for the repo from
+    /// the engine's event bus until the client goes away.
+    async fn watch(
+        &self,
+        request: tonic::Request<proto::WatchRequest>,
+    ) -> Result<tonic::Response<Self::WatchStream>, Status> {
+        let request = self.request(request)?;
+        let repo = canonical_repo_path(request.payload.repo_path());
+        // Subscribed first so nothing published after the ack is missed
+        let mut events = self.engine.subscribe();
+        let (event_tx, event_rx) = mpsc::channel(STREAM_BUFFER);
+        let engine = self.engine.clone();
+
+        tokio::spawn(async move {
+            // Holds the watch until the stream ends
+            let session = Session::new();
+            let (response_tx, mut response_rx) = mpsc::channel(1);
+            engine
+                .handle_in_session(request, &session, response_tx)
+                .await;
+            let ack = match response_rx.recv().await.map(|response| response.result) {
+                Some(Ok(ResponsePayload::Event(event))) => Ok(event.into()),
+                Some(Ok(other)) => Err(unexpected_payload(&other)),
+                Some(Err(error)) => Err(error_to_status(error)),
+                None => Err(Status::internal("The engine sent no response")),
+            };
+            let acknowledged = ack.is_ok();
+            if event_tx.send(ack).await.is_ok() && acknowledged {
+                forward_events(&mut events, &event_tx, &repo).await;
+            }
+        });
+
+        Ok(tonic::Response::new(Box::pin(ReceiverStream::new(
+            event_rx,
+        ))))
+    }
+    async fn open_repo(
+        &self,
+        request: tonic::Request<proto::OpenRepoRequest>,
+    ) -> Result<tonic::Response<proto::RepoInfo>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Repo(info) => Ok(info.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn close_repo(
+        &self,
+        request: tonic::Request<proto::CloseRepoRequest>,
+    ) -> Result<tonic::Response<proto::OperationResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::OperationResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn list_repos(
+        &self,
+        request: tonic::Request<proto::ListReposRequest>,
+    ) -> Result<tonic::Response<proto::RepoList>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Repos(list) => Ok(list.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn capabilities(
+        &self,
+        request: tonic::Request<proto::CapabilitiesRequest>,
+    ) -> Result<tonic::Response<proto::BackendCapabilities>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::Capabilities(capabilities) => Ok(capabilities.into()),
+            other => Err(other),
+        })
+        .await
+    }
+
+    async fn reload_config(
+        &self,
+        request: tonic::Request<proto::ReloadConfigRequest>,
+    ) -> Result<tonic::Response<proto::OperationResult>, Status> {
+        self.unary(request, |payload| match payload {
+            ResponsePayload::OperationResult(result) => Ok(result.into()),
+            other => Err(other),
+        })
+        .await
+    }
+}
+
+/// Send bus events for `repo` to a watch stream until the client goes away.
+async fn forward_events(
+    events: &mut broadcast::Receiver<rl_api::Event>,
+    event_tx: &mpsc::Sender<Result<proto::Event, Status>>,
+    repo: &Path,
+) {
+    loop {
+        let event = tokio::select! {
+            _ = event_tx.closed() => return,
+            event = events.recv() => event,
+        };
+        match event {
+            Ok(event) if canonical_repo_path(event.repo_path()) == repo => {
+                if event_tx.send(Ok(event.into())).await.is_err() {
+                    return;
+                }
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
@@ -4,6 +4,7 @@
 //! like commit graph traversal, tree snapshots, and blame computation.
 
 use rl_git::{Commit, Tree};
+use serde::Deserialize;
 use std::collections::HashMap;
 
 /// Index manager that coordinates all caches.
@@ -43,10 +44,35 @@ impl IndexManager {
             blame_cache: BlameCache::new(),
         }
     }
+
+    /// Replace the cache policy, e.g. after the configuration was reloaded.
+    pub fn set_policy(&mut self, policy: CachePolicy) {
+        self.policy = policy;
+    }
+
+    /// Drop cached history after HEAD or references moved.
+    ///
+    /// Trees and blame are keyed by immutable object ids and stay valid.
+    pub fn invalidate_history(&mut self) {
+        self.commit_graph.clear();
+    }
+
+    /// Drop cached diffs after the index or working directory changed, as
+    /// they may compare against either.
+    pub fn invalidate_worktree(&mut self) {
+        self.diff_cache.clear();
+    }
+
+    /// Bytes cached for the repository at `repo_path` (stub implementation).
+    pub fn repo_bytes(&self, _repo_path: &str) -> u64 {
+        // Stub: caches do not record entry sizes yet
+        0
+    }
 }
 
 /// Cache policy configuration.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct CachePolicy {
     /// Maximum bytes to use for all caches combined
     pub max_total_bytes: u64,
@@ -67,7 +93,8 @@ impl Default for CachePolicy {
 }
 
 /// Cache eviction strategy.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EvictionStrategy {
     /// Least Recently Used
     Lru,
@@ -103,6 +130,11 @@ impl CommitGraphCache {
         None
     }
 
+    /// Drop every cached window.
+    pub fn clear(&mut self) {
+        self.windows.clear();
+    }
+
     /// Store a commit graph window (stub implementation).
     pub fn put_window(
         &mut self,
@@ -206,6 +238,12 @@ impl DiffCache {
         }
     }
 
+    /// Drop every cached summary and chunk.
+    pub fn clear(&mut self) {
+        self.diff_summaries.clear();
+        self.diff_chunks.clear();
+    }
+
     /// Get a cached diff summary (stub implementation).
     pub fn get_diff_summary(&self, _from_commit: &str, _to_commit: &str) -> Option<&DiffSummary> {
         // Stub: always return None
@@ -0,0 +1,255 @@
+//! Structured access log.
+//!
+//! The server writes one JSON line per answered request: who asked, what
+//! they asked for, how long it took, how it ended, and how many bytes went
+//! back. It is separate from the engine's tracing output so operators can
+//! audit daemon usage without turning on debug logging.
+
+use rl_api::{Request, Response};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// One access log line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccessRecord {
+    /// When the final response was sent, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Server-assigned connection id
+    pub connection_id: u64,
+    /// Peer description
+    pub peer: String,
+    /// Client request id
+    pub request_id: String,
+    /// Request type, or `"unknown"` if the request could not be decoded
+    pub request_type: String,
+    /// Repository the request targeted
+    pub repo: Option<String>,
+    /// Time from receiving the request to sending its final response
+    pub duration_ms: u64,
+    /// `"ok"` or the error code of the final response
+    pub status: String,
+    /// Encoded bytes sent for the request, across every chunk
+    pub bytes_out: u64,
+}
+
+/// Destination for access records, shared by every connection.
+#[derive(Clone)]
+pub struct AccessLog {
+    /// Line-oriented writer
+    sink: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl AccessLog {
+    /// Log to an arbitrary writer.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            sink: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// Log to standard error.
+    pub fn stderr() -> Self {
+        Self::new(io::stderr())
+    }
+
+    /// Append to the file at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::new(io::LineWriter::new(file)))
+    }
+
+    /// Write one record. Logging failures never affect the request.
+    pub fn record(&self, record: &AccessRecord) {
+        if let Ok(line) = serde_json::to_string(record) {
+            let mut sink = self.sink.lock().unwrap();
+            let _ = writeln!(sink, "{}", line);
+        }
+    }
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AccessLog")
+    }
+}
+
+/// Request still waiting for its final response.
+struct PendingAccess {
+    received: Instant,
+    request_type: &'static str,
+    repo: String,
+    bytes_out: u64,
+}
+
+/// Per-connection bookkeeping that turns requests and responses into
+/// access records.
+pub(crate) struct AccessTracker {
+    /// Where records go; tracking is skipped entirely when `None`
+    log: Option<AccessLog>,
+    connection_id: u64,
+    peer: String,
+    /// Requests by id
+    pending: Mutex<HashMap<String, PendingAccess>>,
+}
+
+impl AccessTracker {
+    pub(crate) fn new(log: Option<AccessLog>, connection_id: u64, peer: String) -> Self {
+        Self {
+            log,
+            connection_id,
+            peer,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Note that `request` was received.
+    pub(crate) fn begin(&self, request: &Request) {
+        if self.log.is_none() {
+            return;
+        }
+        self.pending.lock().unwrap().insert(
+            request.id.clone(),
+            PendingAccess {
+                received: Instant::now(),
+                request_type: request.payload.kind(),
+                repo: request.payload.repo_path().to_string(),
+                bytes_out: 0,
+            },
+        );
+    }
+
+    /// Note that a batch of responses was sent in one frame; each is charged
+    /// its own encoded size.
+    pub(crate) fn sent_batch(&self, responses: &[Response]) {
+        if self.log.is_none() {
+            return;
+        }
+        for response in responses {
+            let bytes = serde_json::to_vec(response)
+                .map(|line| line.len() as u64)
+                .unwrap_or(0);
+            self.sent(response, bytes);
+        }
+    }
+
+    /// Note that `bytes` were sent for `response`, logging the request once
+    /// its final response is out.
+    pub(crate) fn sent(&self, response: &Response, bytes: u64) {
+        let Some(log) = &self.log else {
+            return;
+        };
+
+        let mut pending = self.pending.lock().unwrap();
+        if !response.is_final() {
+            if let Some(entry) = pending.get_mut(&response.id) {
+                entry.bytes_out += bytes;
+            }
+            return;
+        }
+
+        // Requests rejected before they could be decoded have no entry
+        let entry = pending.remove(&response.id);
+        drop(pending);
+
+        let status = match &response.result {
+            Ok(_) => "ok".to_string(),
+            Err(error) => error.code.to_string(),
+        };
+        log.record(&AccessRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or(0),
+            connection_id: self.connection_id,
+            peer: self.peer.clone(),
+            request_id: response.id.clone(),
+            request_type: entry
+                .as_ref()
+                .map_or("unknown", |entry| entry.request_type)
+                .to_string(),
+            repo: entry.as_ref().map(|entry| entry.repo.clone()),
+            duration_ms: entry
+                .as_ref()
+                .map_or(0, |entry| entry.received.elapsed().as_millis() as u64),
+            status,
+            bytes_out: entry.map_or(0, |entry| entry.bytes_out) + bytes,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::paging::StreamingChunk;
+    use rl_api::response::{ProgressUpdate, ResponsePayload};
+
+    /// Writer whose contents the test can read back.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn progress(id: &str, is_final: bool) -> Response {
+        Response {
+            id: id.to_string(),
+            result: Ok(ResponsePayload::Progress(StreamingChunk {
+                sequence: 0,
+                is_final,
+                data: ProgressUpdate {
+                    stage: "receiving".to_string(),
+                    progress: 50,
+                    message: None,
+                },
+            })),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn test_one_record_per_request_with_total_bytes() {
+        let captured = Captured::default();
+        let tracker = AccessTracker::new(
+            Some(AccessLog::new(captured.clone())),
+            7,
+            "tcp:127.0.0.1:1".to_string(),
+        );
+
+        tracker.begin(&Request::new(
+            "f1",
+            rl_api::request::RequestPayload::Fetch(rl_api::request::FetchRequest {
+                repo_path: "/repo".to_string(),
+                remote: None,
+                refspecs: None,
+            }),
+        ));
+        tracker.sent(&progress("f1", false), 100);
+        tracker.sent(&progress("f1", true), 50);
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["connection_id"], 7);
+        assert_eq!(record["request_type"], "fetch");
+        assert_eq!(record["repo"], "/repo");
+        assert_eq!(record["status"], "ok");
+        assert_eq!(record["bytes_out"], 150);
+    }
+}
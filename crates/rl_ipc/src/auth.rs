@@ -0,0 +1,106 @@
+//! Token authentication for socket transports.
+//!
+//! A server configured with a token expects the first frame on a connection
+//! to be `{"type":"auth","token":"..."}`. Requests sent before a successful
+//! handshake are answered with `ErrorCode::AuthRequired` and the connection
+//! is closed.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Environment variable holding the token itself.
+pub const TOKEN_ENV: &str = "REPO_LENS_TOKEN";
+
+/// Environment variable holding the path of a file containing the token.
+pub const TOKEN_FILE_ENV: &str = "REPO_LENS_TOKEN_FILE";
+
+/// Shared secret clients must present before sending requests.
+#[derive(Clone)]
+pub struct AuthToken(String);
+
+impl AuthToken {
+    /// Create a token from a string.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// Read a token from a file, ignoring surrounding whitespace.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let token = content.trim();
+        if token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Token file is empty: {}", path.display()),
+            ));
+        }
+        Ok(Self(token.to_string()))
+    }
+
+    /// Load a token from `REPO_LENS_TOKEN` or the file named by
+    /// `REPO_LENS_TOKEN_FILE`, in that order.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        if let Ok(token) = std::env::var(TOKEN_ENV) {
+            if !token.trim().is_empty() {
+                return Ok(Some(Self(token.trim().to_string())));
+            }
+        }
+        match std::env::var_os(TOKEN_FILE_ENV) {
+            Some(path) => Self::from_file(Path::new(&path)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the token value.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check a presented token in constant time.
+    pub fn verify(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.as_bytes();
+        if expected.len() != candidate.len() {
+            return false;
+        }
+        expected
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AuthToken(<redacted>)")
+    }
+}
+
+/// Error sent for requests that arrive before a successful handshake.
+pub(crate) fn auth_required(message: impl Into<String>) -> rl_api::Error {
+    rl_api::Error::new(rl_api::ErrorCode::AuthRequired, message).with_remediation(
+        "Send {\"type\":\"auth\",\"token\":\"...\"} as the first frame on the connection",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_token() {
+        let token = AuthToken::new("s3cret");
+        assert!(token.verify("s3cret"));
+        assert!(!token.verify("s3cre"));
+        assert!(!token.verify("s3creT"));
+        assert!(!token.verify(""));
+    }
+
+    #[test]
+    fn test_debug_redacts_token() {
+        let token = AuthToken::new("s3cret");
+        assert!(!format!("{:?}", token).contains("s3cret"));
+    }
+}
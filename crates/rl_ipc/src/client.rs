@@ -0,0 +1,862 @@
+//! IPC client that talks to a server over any byte stream.
+
+use crate::auth::{auth_required, AuthToken};
+use crate::frame::{ControlFrame, Frame};
+use crate::handshake::{Encoding, Negotiated};
+use crate::keepalive::{KeepaliveAction, KeepaliveConfig, Liveness};
+use crate::retry::{ConnectionState, RetryPolicy};
+use crate::TransportConfig;
+use rl_api::{ApiVersion, Request, Response};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// IPC client for communicating with the server.
+///
+/// Responses are matched to requests by id, so several requests may be in
+/// flight at once. Background tasks answer server pings and tear the
+/// connection down when the server goes silent.
+///
+/// Clients created with [`IpcClient::connect_tcp`] or
+/// [`IpcClient::connect_unix`] reconnect with exponential backoff when the
+/// connection drops, replaying the hello and auth exchanges. Read-only
+/// requests that were cut off are re-sent once the connection is back;
+/// mutations never are, since they may already have run.
+pub struct IpcClient {
+    /// Current connection, replaced on reconnect
+    link: Arc<Mutex<Link>>,
+    /// Connection state, updated by the supervisor task
+    state: watch::Receiver<ConnectionState>,
+    /// Handshakes to replay on a new connection
+    setup: Arc<Mutex<Setup>>,
+    /// Reconnect and retry settings
+    retry: RetryPolicy,
+}
+
+/// One connection to the server.
+#[derive(Clone)]
+struct Link {
+    /// Number of reconnects before this connection was made
+    generation: u32,
+    /// Outgoing frames, drained by the writer task
+    frames: mpsc::Sender<Frame<Request>>,
+    /// Requests awaiting a response
+    pending: Arc<Mutex<Pending>>,
+}
+
+/// Requests awaiting a response, keyed by request id.
+#[derive(Default)]
+struct Pending {
+    /// Set once the reader has stopped; no response can arrive after this
+    closed: bool,
+    /// Response slots for in-flight requests
+    waiters: HashMap<String, Waiter>,
+    /// Result slot for an in-flight auth handshake
+    auth: Option<oneshot::Sender<Result<(), rl_api::Error>>>,
+    /// Result slot for an in-flight hello exchange
+    hello: Option<oneshot::Sender<Result<Negotiated, rl_api::Error>>>,
+}
+
+/// Handshakes completed so far.
+#[derive(Default)]
+struct Setup {
+    /// Token accepted by the server
+    token: Option<AuthToken>,
+    /// Whether the hello exchange succeeded
+    hello: bool,
+}
+
+/// Where responses for one request are delivered.
+enum Waiter {
+    /// A single response
+    Single(oneshot::Sender<Response>),
+    /// Every chunk of a streaming response, up to the final one
+    Stream(mpsc::Sender<Response>),
+}
+
+/// A byte stream to the server, split into halves.
+type Stream = (
+    Box<dyn AsyncRead + Unpin + Send>,
+    Box<dyn AsyncWrite + Unpin + Send>,
+);
+
+/// Opens a new stream to the server when reconnecting.
+type Connector =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = io::Result<Stream>> + Send>> + Send + Sync>;
+
+impl IpcClient {
+    /// Connect over an already-established byte stream.
+    ///
+    /// Such clients cannot reconnect; their state becomes
+    /// [`ConnectionState::Disconnected`] when the stream closes.
+    pub fn connect<R, W>(reader: R, writer: W, config: TransportConfig) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::start(Box::new(reader), Box::new(writer), config, None)
+    }
+
+    /// Connect to a server listening on TCP, reconnecting if it drops.
+    pub async fn connect_tcp(
+        addr: impl tokio::net::ToSocketAddrs,
+        config: TransportConfig,
+    ) -> io::Result<Self> {
+        let addrs: Arc<[std::net::SocketAddr]> = tokio::net::lookup_host(addr).await?.collect();
+        let connector: Connector = Arc::new(move || {
+            let addrs = addrs.clone();
+            Box::pin(async move {
+                let stream = tokio::net::TcpStream::connect(&addrs[..]).await?;
+                stream.set_nodelay(true)?;
+                let (reader, writer) = stream.into_split();
+                Ok::<Stream, io::Error>((Box::new(reader), Box::new(writer)))
+            })
+        });
+        let (reader, writer) = connector().await?;
+        Ok(Self::start(reader, writer, config, Some(connector)))
+    }
+
+    /// Connect to a server listening on a Unix domain socket, reconnecting
+    /// if it drops.
+    #[cfg(unix)]
+    pub async fn connect_unix(
+        path: impl AsRef<std::path::Path>,
+        config: TransportConfig,
+    ) -> io::Result<Self> {
+        let path: Arc<std::path::Path> = Arc::from(path.as_ref());
+        let connector: Connector = Arc::new(move || {
+            let path = path.clone();
+            Box::pin(async move {
+                let stream = tokio::net::UnixStream::connect(&*path).await?;
+                let (reader, writer) = stream.into_split();
+                Ok::<Stream, io::Error>((Box::new(reader), Box::new(writer)))
+            })
+        });
+        let (reader, writer) = connector().await?;
+        Ok(Self::start(reader, writer, config, Some(connector)))
+    }
+
+    fn start(
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+        writer: Box<dyn AsyncWrite + Unpin + Send>,
+        config: TransportConfig,
+        connector: Option<Connector>,
+    ) -> Self {
+        let (link, closed) = Link::spawn(reader, writer, 0, &config);
+        let link = Arc::new(Mutex::new(link));
+        let (state_tx, state) = watch::channel(ConnectionState::Connected { reconnects: 0 });
+        let setup = Arc::new(Mutex::new(Setup::default()));
+
+        tokio::spawn(supervise(
+            closed,
+            link.clone(),
+            setup.clone(),
+            state_tx,
+            config.clone(),
+            connector,
+        ));
+
+        Self {
+            link,
+            state,
+            setup,
+            retry: config.retry,
+        }
+    }
+
+    /// Current connection state.
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Receiver that is notified on every connection state change.
+    pub fn state_changes(&self) -> watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
+
+    /// Authenticate with the server; must be called before any request when
+    /// the server requires a token.
+    pub async fn authenticate(&self, token: &AuthToken) -> Result<(), rl_api::Error> {
+        self.link().authenticate(token).await?;
+        self.setup.lock().unwrap().token = Some(token.clone());
+        Ok(())
+    }
+
+    /// Negotiate the API version and encoding with the server.
+    ///
+    /// Offers every version and encoding this build supports. Fails with the
+    /// server's error code when there is no overlap; the server then closes
+    /// the connection.
+    pub async fn hello(&self) -> Result<Negotiated, rl_api::Error> {
+        let negotiated = self.link().hello().await?;
+        self.setup.lock().unwrap().hello = true;
+        Ok(negotiated)
+    }
+
+    /// Send a request and get a response.
+    ///
+    /// For streaming requests only the final chunk is returned. Read-only
+    /// requests are re-sent after a reconnect if the connection dropped
+    /// before their response arrived.
+    pub async fn send_request(&self, request: Request) -> Result<Response, rl_api::Error> {
+        let retryable = request.payload.is_read_only();
+        let mut retries = 0;
+        loop {
+            let link = self.link();
+            match link.send_request(request.clone()).await {
+                Err(error)
+                    if error.code == rl_api::ErrorCode::ConnectionLost
+                        && retryable
+                        && retries < self.retry.max_request_retries
+                        && self.reconnected_since(link.generation).await =>
+                {
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send several requests in one frame and get their final responses in
+    /// the same order.
+    ///
+    /// The server runs them back to back and answers with one array frame,
+    /// saving a round trip per request. Batches are never retried.
+    pub async fn send_batch(&self, requests: Vec<Request>) -> Result<Vec<Response>, rl_api::Error> {
+        self.link().send_batch(requests).await
+    }
+
+    /// Send a streaming request and receive its chunks in order.
+    ///
+    /// The receiver yields every response for the request and closes after
+    /// the final chunk (or an error). If the connection drops first it closes
+    /// without a final chunk; streaming requests are never retried.
+    pub async fn send_streaming_request(
+        &self,
+        request: Request,
+    ) -> Result<mpsc::Receiver<Response>, rl_api::Error> {
+        self.link().send_streaming_request(request).await
+    }
+
+    /// Ask the server to stop the request with `id`.
+    ///
+    /// The request's response still arrives, normally as an
+    /// `OperationCanceled` error. Requests that already finished are not
+    /// affected.
+    pub async fn cancel(&self, id: &str) -> Result<(), rl_api::Error> {
+        self.link().cancel(id).await
+    }
+
+    fn link(&self) -> Link {
+        self.link.lock().unwrap().clone()
+    }
+
+    /// Wait until a connection newer than `generation` is up. Returns false
+    /// if the client gave up reconnecting.
+    async fn reconnected_since(&self, generation: u32) -> bool {
+        let mut state = self.state.clone();
+        let settled = state
+            .wait_for(|state| match state {
+                ConnectionState::Connected { reconnects } => *reconnects > generation,
+                ConnectionState::Reconnecting { .. } => false,
+                ConnectionState::Disconnected => true,
+            })
+            .await
+            .map(|state| *state);
+        matches!(settled, Ok(ConnectionState::Connected { .. }))
+    }
+}
+
+impl Link {
+    /// Start the reader and writer tasks for a new stream. The returned
+    /// handle completes when the connection is lost.
+    fn spawn(
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+        writer: Box<dyn AsyncWrite + Unpin + Send>,
+        generation: u32,
+        config: &TransportConfig,
+    ) -> (Self, JoinHandle<()>) {
+        let (frame_tx, frame_rx) = mpsc::channel(config.max_pending_requests);
+        let pending = Arc::new(Mutex::new(Pending::default()));
+
+        tokio::spawn(write_frames(writer, frame_rx));
+        let closed = tokio::spawn(read_frames(
+            reader,
+            frame_tx.clone(),
+            pending.clone(),
+            config.keepalive.clone(),
+        ));
+
+        let link = Self {
+            generation,
+            frames: frame_tx,
+            pending,
+        };
+        (link, closed)
+    }
+
+    async fn authenticate(&self, token: &AuthToken) -> Result<(), rl_api::Error> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_lost("Connection to server is closed"));
+            }
+            pending.auth = Some(result_tx);
+        }
+
+        let frame = Frame::Control(ControlFrame::Auth {
+            token: token.as_str().to_string(),
+        });
+        if self.frames.send(frame).await.is_err() {
+            return Err(connection_lost("Connection to server is closed"));
+        }
+
+        result_rx
+            .await
+            .map_err(|_| connection_lost("Connection to server lost during authentication"))?
+    }
+
+    async fn hello(&self) -> Result<Negotiated, rl_api::Error> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_lost("Connection to server is closed"));
+            }
+            pending.hello = Some(result_tx);
+        }
+
+        let frame = Frame::Control(ControlFrame::Hello {
+            versions: ApiVersion::SUPPORTED.to_vec(),
+            encodings: Encoding::SUPPORTED.to_vec(),
+        });
+        if self.frames.send(frame).await.is_err() {
+            return Err(connection_lost("Connection to server is closed"));
+        }
+
+        result_rx
+            .await
+            .map_err(|_| connection_lost("Connection to server lost during handshake"))?
+    }
+
+    async fn cancel(&self, id: &str) -> Result<(), rl_api::Error> {
+        let frame = Frame::Control(ControlFrame::Cancel { id: id.to_string() });
+        self.frames
+            .send(frame)
+            .await
+            .map_err(|_| connection_lost("Connection to server is closed"))
+    }
+
+    async fn send_request(&self, request: Request) -> Result<Response, rl_api::Error> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let id = request.id.clone();
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_lost("Connection to server is closed"));
+            }
+            pending
+                .waiters
+                .insert(id.clone(), Waiter::Single(response_tx));
+        }
+
+        if self.frames.send(Frame::Message(request)).await.is_err() {
+            self.pending.lock().unwrap().waiters.remove(&id);
+            return Err(connection_lost("Connection to server is closed"));
+        }
+
+        response_rx
+            .await
+            .map_err(|_| connection_lost("Connection to server lost before a response arrived"))
+    }
+
+    async fn send_batch(&self, requests: Vec<Request>) -> Result<Vec<Response>, rl_api::Error> {
+        let mut receivers = Vec::with_capacity(requests.len());
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_lost("Connection to server is closed"));
+            }
+            for request in &requests {
+                let (response_tx, response_rx) = oneshot::channel();
+                pending
+                    .waiters
+                    .insert(request.id.clone(), Waiter::Single(response_tx));
+                receivers.push(response_rx);
+            }
+        }
+
+        let ids: Vec<String> = requests.iter().map(|request| request.id.clone()).collect();
+        if self.frames.send(Frame::Batch(requests)).await.is_err() {
+            let mut pending = self.pending.lock().unwrap();
+            for id in &ids {
+                pending.waiters.remove(id);
+            }
+            return Err(connection_lost("Connection to server is closed"));
+        }
+
+        let mut responses = Vec::with_capacity(receivers.len());
+        for receiver in receivers {
+            responses.push(receiver.await.map_err(|_| {
+                connection_lost("Connection to server lost before a response arrived")
+            })?);
+        }
+        Ok(responses)
+    }
+
+    async fn send_streaming_request(
+        &self,
+        request: Request,
+    ) -> Result<mpsc::Receiver<Response>, rl_api::Error> {
+        let (chunk_tx, chunk_rx) = mpsc::channel(STREAM_BUFFER);
+        let id = request.id.clone();
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_lost("Connection to server is closed"));
+            }
+            pending.waiters.insert(id.clone(), Waiter::Stream(chunk_tx));
+        }
+
+        if self.frames.send(Frame::Message(request)).await.is_err() {
+            self.pending.lock().unwrap().waiters.remove(&id);
+            return Err(connection_lost("Connection to server is closed"));
+        }
+
+        Ok(chunk_rx)
+    }
+}
+
+/// Watch the connection and, if the client can reconnect, re-establish it
+/// with backoff whenever it drops.
+async fn supervise(
+    mut closed: JoinHandle<()>,
+    link: Arc<Mutex<Link>>,
+    setup: Arc<Mutex<Setup>>,
+    state: watch::Sender<ConnectionState>,
+    config: TransportConfig,
+    connector: Option<Connector>,
+) {
+    let mut reconnects = 0;
+    loop {
+        let _ = (&mut closed).await;
+
+        // Nobody is left to use a new connection
+        let Some(connector) = connector.as_ref().filter(|_| !state.is_closed()) else {
+            let _ = state.send(ConnectionState::Disconnected);
+            return;
+        };
+
+        let mut attempt = 0;
+        closed = loop {
+            if attempt == config.retry.max_reconnect_attempts {
+                let _ = state.send(ConnectionState::Disconnected);
+                return;
+            }
+            attempt += 1;
+            let _ = state.send(ConnectionState::Reconnecting { attempt });
+            tokio::time::sleep(config.retry.backoff(attempt)).await;
+
+            let Ok((reader, writer)) = connector().await else {
+                continue;
+            };
+            let (candidate, candidate_closed) =
+                Link::spawn(reader, writer, reconnects + 1, &config);
+            if replay(&candidate, &setup).await.is_err() {
+                candidate_closed.abort();
+                continue;
+            }
+
+            *link.lock().unwrap() = candidate;
+            break candidate_closed;
+        };
+
+        reconnects += 1;
+        let _ = state.send(ConnectionState::Connected { reconnects });
+    }
+}
+
+/// Repeat the handshakes the client completed on its previous connection.
+async fn replay(link: &Link, setup: &Mutex<Setup>) -> Result<(), rl_api::Error> {
+    let (token, hello) = {
+        let setup = setup.lock().unwrap();
+        (setup.token.clone(), setup.hello)
+    };
+    if hello {
+        link.hello().await?;
+    }
+    if let Some(token) = token {
+        link.authenticate(&token).await?;
+    }
+    Ok(())
+}
+
+/// Chunks buffered per streaming request before the reader waits.
+const STREAM_BUFFER: usize = 16;
+
+/// Write outgoing frames, one per line.
+async fn write_frames<W>(mut writer: W, mut frames: mpsc::Receiver<Frame<Request>>)
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(frame) = frames.recv().await {
+        let mut line = match frame.encode() {
+            Ok(line) => line,
+            Err(_) => continue,
+        };
+        line.push('\n');
+
+        if writer.write_all(line.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Read incoming frames, route responses, and keep the connection alive.
+async fn read_frames<R>(
+    reader: R,
+    frames: mpsc::Sender<Frame<Request>>,
+    pending: Arc<Mutex<Pending>>,
+    keepalive: KeepaliveConfig,
+) where
+    R: AsyncRead + Unpin,
+{
+    let liveness = Liveness::new();
+    let mut lines = BufReader::new(reader).lines();
+    let mut checks = tokio::time::interval(keepalive.check_interval());
+    checks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    _ => break, // EOF or broken stream
+                };
+                liveness.touch();
+
+                match Frame::<Response>::decode(&line) {
+                    Ok(Frame::Message(response)) => route(&pending, response).await,
+                    Ok(Frame::Batch(responses)) => {
+                        for response in responses {
+                            route(&pending, response).await;
+                        }
+                    }
+                    Ok(Frame::Control(ControlFrame::Ping { seq })) => {
+                        let pong = Frame::Control(ControlFrame::Pong { seq });
+                        if frames.send(pong).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Frame::Control(ControlFrame::AuthAccepted)) => {
+                        if let Some(auth) = pending.lock().unwrap().auth.take() {
+                            let _ = auth.send(Ok(()));
+                        }
+                    }
+                    Ok(Frame::Control(ControlFrame::AuthRejected { message })) => {
+                        if let Some(auth) = pending.lock().unwrap().auth.take() {
+                            let _ = auth.send(Err(auth_required(message)));
+                        }
+                    }
+                    Ok(Frame::Control(ControlFrame::Welcome { version, encoding })) => {
+                        if let Some(hello) = pending.lock().unwrap().hello.take() {
+                            let _ = hello.send(Ok(Negotiated { version, encoding }));
+                        }
+                    }
+                    Ok(Frame::Control(ControlFrame::HelloRejected { code, message })) => {
+                        if let Some(hello) = pending.lock().unwrap().hello.take() {
+                            let _ = hello.send(Err(rl_api::Error::new(code, message)));
+                        }
+                    }
+                    Ok(Frame::Control(_)) => {}
+                    Err(_) => {} // Ignore frames we cannot decode
+                }
+            }
+            _ = checks.tick() => match keepalive.action(liveness.idle()) {
+                KeepaliveAction::Wait => {}
+                KeepaliveAction::Ping => {
+                    let ping = ControlFrame::Ping {
+                        seq: liveness.next_ping_seq(),
+                    };
+                    if frames.send(Frame::Control(ping)).await.is_err() {
+                        break;
+                    }
+                }
+                KeepaliveAction::Disconnect => break,
+            },
+        }
+    }
+
+    // Dropping the waiters fails every in-flight request with ConnectionLost
+    let mut pending = pending.lock().unwrap();
+    pending.closed = true;
+    pending.waiters.clear();
+    pending.auth = None;
+    pending.hello = None;
+}
+
+/// Deliver a response to whoever is waiting for its request.
+async fn route(pending: &Mutex<Pending>, response: Response) {
+    let waiter = pending.lock().unwrap().waiters.remove(&response.id);
+    match waiter {
+        Some(Waiter::Single(waiter)) if response.is_final() => {
+            let _ = waiter.send(response);
+        }
+        // Single waiters only want the final chunk of a stream
+        Some(Waiter::Single(waiter)) => {
+            pending
+                .lock()
+                .unwrap()
+                .waiters
+                .insert(response.id, Waiter::Single(waiter));
+        }
+        Some(Waiter::Stream(chunks)) => {
+            let id = response.id.clone();
+            let is_final = response.is_final();
+            // Keep routing until the final chunk, unless the caller dropped
+            // the receiver
+            if chunks.send(response).await.is_ok() && !is_final {
+                pending
+                    .lock()
+                    .unwrap()
+                    .waiters
+                    .insert(id, Waiter::Stream(chunks));
+            }
+        }
+        None => {}
+    }
+}
+
+fn connection_lost(message: &str) -> rl_api::Error {
+    rl_api::Error::new(rl_api::ErrorCode::ConnectionLost, message)
+        .with_remediation("Check that the repo-lens server is still running")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+    fn status_request(id: &str) -> Request {
+        Request::new(
+            id,
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                repo_path: ".".to_string(),
+            }),
+        )
+    }
+
+    fn connect_pair(
+        config: TransportConfig,
+    ) -> (
+        IpcClient,
+        tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+        tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    ) {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let (client_read, client_write) = tokio::io::split(client_io);
+        let (server_read, server_write) = tokio::io::split(server_io);
+        let client = IpcClient::connect(client_read, client_write, config);
+        (client, BufReader::new(server_read).lines(), server_write)
+    }
+
+    #[tokio::test]
+    async fn test_request_response_round_trip() {
+        let (client, mut server_lines, mut server_write) = connect_pair(TransportConfig::default());
+
+        let server = tokio::spawn(async move {
+            let line = server_lines.next_line().await.unwrap().unwrap();
+            let request = match Frame::<Request>::decode(&line).unwrap() {
+                Frame::Message(request) => request,
+                _ => panic!("Expected request"),
+            };
+            let response = Response {
+                id: request.id,
+                result: Err(rl_api::Error::new(rl_api::ErrorCode::Internal, "test")),
+                meta: None,
+            };
+            let line = Frame::Message(response).encode().unwrap();
+            server_write
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .unwrap();
+            server_write
+        });
+
+        let response = client.send_request(status_request("r1")).await.unwrap();
+        assert_eq!(response.id, "r1");
+        let _server_write = server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_answers_ping() {
+        let (_client, mut server_lines, mut server_write) =
+            connect_pair(TransportConfig::default());
+
+        server_write
+            .write_all(b"{\"type\":\"ping\",\"seq\":3}\n")
+            .await
+            .unwrap();
+
+        let line = server_lines.next_line().await.unwrap().unwrap();
+        match Frame::<Request>::decode(&line).unwrap() {
+            Frame::Control(frame) => assert_eq!(frame, ControlFrame::Pong { seq: 3 }),
+            _ => panic!("Expected pong"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_server_fails_pending_requests() {
+        let config = TransportConfig {
+            keepalive: KeepaliveConfig {
+                ping_interval_ms: 10,
+                idle_timeout_ms: 40,
+            },
+            ..Default::default()
+        };
+        let (client, _server_lines, _server_write) = connect_pair(config);
+
+        let error = client.send_request(status_request("r1")).await.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::ConnectionLost);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token_maps_to_auth_required() {
+        let (client, mut server_lines, mut server_write) = connect_pair(TransportConfig::default());
+
+        tokio::spawn(async move {
+            let line = server_lines.next_line().await.unwrap().unwrap();
+            assert!(matches!(
+                Frame::<Request>::decode(&line).unwrap(),
+                Frame::Control(ControlFrame::Auth { .. })
+            ));
+            server_write
+                .write_all(b"{\"type\":\"auth_rejected\",\"message\":\"bad token\"}\n")
+                .await
+                .unwrap();
+            server_write
+        });
+
+        let error = client
+            .authenticate(&AuthToken::new("wrong"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::AuthRequired);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_yields_chunks_until_final() {
+        let (client, mut server_lines, mut server_write) = connect_pair(TransportConfig::default());
+
+        tokio::spawn(async move {
+            let line = server_lines.next_line().await.unwrap().unwrap();
+            let id = match Frame::<Request>::decode(&line).unwrap() {
+                Frame::Message(request) => request.id,
+                _ => panic!("Expected request"),
+            };
+            for (sequence, is_final) in [(0, false), (1, true)] {
+                let chunk = rl_api::response::ResponsePayload::Progress(rl_api::StreamingChunk {
+                    sequence,
+                    is_final,
+                    data: rl_api::response::ProgressUpdate {
+                        stage: "fetch".to_string(),
+                        progress: 50,
+                        message: None,
+                    },
+                });
+                let response = Response {
+                    id: id.clone(),
+                    result: Ok(chunk),
+                    meta: None,
+                };
+                let line = Frame::Message(response).encode().unwrap();
+                server_write
+                    .write_all(format!("{}\n", line).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            server_write
+        });
+
+        let mut chunks = client
+            .send_streaming_request(status_request("s1"))
+            .await
+            .unwrap();
+        assert!(!chunks.recv().await.unwrap().is_final());
+        assert!(chunks.recv().await.unwrap().is_final());
+        assert!(chunks.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_retries_reads_but_not_mutations() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Hang up on the first attempt of every request; answer repeats
+        tokio::spawn(async move {
+            let seen = Arc::new(Mutex::new(std::collections::HashSet::new()));
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let seen = seen.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let Ok(Frame::Message(request)) = Frame::<Request>::decode(&line) else {
+                            continue;
+                        };
+                        if seen.lock().unwrap().insert(request.id.clone()) {
+                            return;
+                        }
+                        let response = Response {
+                            id: request.id,
+                            result: Err(rl_api::Error::new(rl_api::ErrorCode::Internal, "test")),
+                            meta: None,
+                        };
+                        let line = Frame::Message(response).encode().unwrap();
+                        writer
+                            .write_all(format!("{}\n", line).as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let config = TransportConfig {
+            retry: RetryPolicy {
+                initial_backoff_ms: 1,
+                ..RetryPolicy::default()
+            },
+            ..Default::default()
+        };
+        let client = IpcClient::connect_tcp(addr, config).await.unwrap();
+
+        let response = client.send_request(status_request("r1")).await.unwrap();
+        assert_eq!(response.id, "r1");
+        assert_eq!(client.state(), ConnectionState::Connected { reconnects: 1 });
+
+        let commit = Request::new(
+            "c1",
+            rl_api::request::RequestPayload::Commit(rl_api::request::CommitRequest {
+                repo_path: ".".to_string(),
+                message: "test".to_string(),
+                author_name: None,
+                author_email: None,
+            }),
+        );
+        let error = client.send_request(commit).await.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::ConnectionLost);
+    }
+}
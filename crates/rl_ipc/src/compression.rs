@@ -0,0 +1,129 @@
+//! Per-frame compression of large responses.
+//!
+//! When the hello exchange settles on a compressing [`Encoding`], response
+//! lines above the configured threshold are compressed and sent as
+//! `{"type":"compressed","encoding":"zstd","data":"<base64>"}`. Everything
+//! else stays plain JSON, so small frames pay nothing.
+
+use crate::frame::ControlFrame;
+use crate::handshake::Encoding;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::io::{self, Read, Write};
+
+/// zstd level: fast enough to beat the network on anything but loopback.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compress an encoded frame line if it is above `threshold` bytes and the
+/// connection negotiated a compressing encoding.
+///
+/// Returns the line unchanged when compression would not make it smaller.
+pub(crate) fn compress_line(line: String, encoding: Encoding, threshold: usize) -> String {
+    if encoding == Encoding::Json || line.len() < threshold {
+        return line;
+    }
+
+    let compressed = match compress(encoding, line.as_bytes()) {
+        Ok(compressed) => compressed,
+        Err(_) => return line,
+    };
+    let frame = ControlFrame::Compressed {
+        encoding,
+        data: BASE64.encode(compressed),
+    };
+    match serde_json::to_string(&frame) {
+        Ok(wrapped) if wrapped.len() < line.len() => wrapped,
+        _ => line,
+    }
+}
+
+/// Recover the original frame line from a `compressed` frame's body.
+pub fn decompress_line(encoding: Encoding, data: &str) -> io::Result<String> {
+    let compressed = BASE64
+        .decode(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut line = String::new();
+    match encoding {
+        Encoding::Json => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "json frames are never compressed",
+            ))
+        }
+        Encoding::Zstd => {
+            zstd::stream::read::Decoder::new(compressed.as_slice())?.read_to_string(&mut line)?
+        }
+        Encoding::Gzip => {
+            flate2::read::GzDecoder::new(compressed.as_slice()).read_to_string(&mut line)?
+        }
+    };
+    Ok(line)
+}
+
+fn compress(encoding: Encoding, bytes: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Json => Ok(bytes.to_vec()),
+        Encoding::Zstd => zstd::encode_all(bytes, ZSTD_LEVEL),
+        Encoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Frame;
+    use rl_api::Response;
+
+    #[test]
+    fn test_large_lines_round_trip_through_each_codec() {
+        let line = format!(
+            r#"{{"id":"r1","Ok":{{"Pong":"{}"}}}}"#,
+            "diff ".repeat(10_000)
+        );
+
+        for encoding in [Encoding::Zstd, Encoding::Gzip] {
+            let wire = compress_line(line.clone(), encoding, 1024);
+            assert!(
+                wire.len() < line.len() / 10,
+                "{:?} did not shrink",
+                encoding
+            );
+
+            match serde_json::from_str::<ControlFrame>(&wire).unwrap() {
+                ControlFrame::Compressed { encoding, data } => {
+                    assert_eq!(decompress_line(encoding, &data).unwrap(), line)
+                }
+                other => panic!("Expected compressed frame, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_small_lines_and_json_stay_plain() {
+        let line = r#"{"id":"r1","Ok":"Pong"}"#.to_string();
+        assert_eq!(compress_line(line.clone(), Encoding::Zstd, 1024), line);
+
+        let big = "x".repeat(4096);
+        assert_eq!(compress_line(big.clone(), Encoding::Json, 1024), big);
+    }
+
+    #[test]
+    fn test_decode_unwraps_compressed_frames() {
+        let line = format!(
+            r#"{{"id":"r1","Err":{{"code":"internal","message":"{}","remediation":null,"details":null}}}}"#,
+            "e".repeat(8192)
+        );
+        let wire = compress_line(line, Encoding::Zstd, 1024);
+
+        match Frame::<Response>::decode(&wire).unwrap() {
+            Frame::Message(response) => assert_eq!(response.id, "r1"),
+            other => panic!("Expected response, got {:?}", other),
+        }
+    }
+}
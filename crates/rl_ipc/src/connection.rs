@@ -0,0 +1,903 @@
+//! Per-connection request handling and state.
+//!
+//! Every accepted [`Connection`] is served by its own task with its own
+//! request queue, limits, keepalive, and repository session, all sharing one
+//! engine. Repositories stay open for the life of the connection.
+
+use crate::access_log::AccessTracker;
+use crate::auth::{auth_required, AuthToken};
+use crate::compression::compress_line;
+use crate::frame::{decode_request, frame_too_large, ControlFrame, Frame, Inbound, Line};
+use crate::handshake::{negotiate, Encoding, Negotiated};
+use crate::keepalive::{KeepaliveAction, Liveness};
+use crate::limits::{quota_exceeded_response, rate_limited_response, ClientUsage, RateLimiter};
+use crate::listener::Connection;
+use crate::recording::Direction;
+use crate::TransportConfig;
+use rl_api::request::RequestPayload;
+use rl_api::response::ResponsePayload;
+use rl_api::{Request, Response};
+use rl_core::events::canonical_repo_path;
+use rl_core::session::Session;
+use rl_core::{CancellationToken, Priority, RepoEngine, Scheduler};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+
+/// Chunks buffered between the engine and the writer for a single request.
+const STREAM_BUFFER: usize = 16;
+
+/// Snapshot of one client connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// Server-assigned connection id
+    pub id: u64,
+    /// Peer description
+    pub peer: String,
+    /// When the connection was accepted
+    pub connected_at: SystemTime,
+    /// Whether the peer may send requests
+    pub authenticated: bool,
+    /// Version and encoding agreed in the hello exchange, if the peer sent one
+    pub protocol: Option<Negotiated>,
+    /// Ids of requests queued or executing
+    pub in_flight: BTreeSet<String>,
+    /// Repositories the peer is watching
+    pub subscriptions: BTreeSet<String>,
+}
+
+/// Live connections, shared by the accept loop and connection tasks.
+#[derive(Default)]
+pub(crate) struct ConnectionRegistry {
+    /// Connection state keyed by id
+    connections: Mutex<HashMap<u64, ConnectionInfo>>,
+}
+
+impl ConnectionRegistry {
+    fn update(&self, id: u64, f: impl FnOnce(&mut ConnectionInfo)) {
+        if let Some(info) = self.connections.lock().unwrap().get_mut(&id) {
+            f(info);
+        }
+    }
+
+    fn register(&self, info: ConnectionInfo) {
+        self.connections.lock().unwrap().insert(info.id, info);
+    }
+
+    fn unregister(&self, id: u64) {
+        self.connections.lock().unwrap().remove(&id);
+    }
+
+    /// Snapshot of every live connection, ordered by id.
+    pub(crate) fn snapshot(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<_> = self.connections.lock().unwrap().values().cloned().collect();
+        connections.sort_by_key(|info| info.id);
+        connections
+    }
+}
+
+/// State shared by the tasks serving one connection.
+struct ConnectionContext {
+    /// Connection id
+    id: u64,
+    /// Registry holding this connection's `ConnectionInfo`
+    registry: Arc<ConnectionRegistry>,
+    /// Token the peer must present; `None` for trusted peers
+    auth_token: Option<AuthToken>,
+    /// Limit counters
+    usage: ClientUsage,
+    /// Inbound activity tracker
+    liveness: Liveness,
+    /// Version and encoding agreed in the hello exchange
+    protocol: Mutex<Option<Negotiated>>,
+    /// Repositories kept open for this peer's requests
+    session: Session,
+    /// Access log bookkeeping
+    access: AccessTracker,
+    /// Jobs accepted but not yet started, including those the worker has
+    /// already pulled off the channel to order by priority
+    queued_jobs: AtomicUsize,
+    /// Cancellation tokens of queued and running requests, by request id
+    cancellations: Mutex<HashMap<String, CancellationToken>>,
+    /// Id of the watch request for each watched repository, by canonical path
+    watches: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl ConnectionContext {
+    fn protocol(&self) -> Option<Negotiated> {
+        *self.protocol.lock().unwrap()
+    }
+
+    /// Count a job against the queue limit, failing if the queue is full.
+    fn reserve_queue_slot(&self, limit: usize) -> bool {
+        self.queued_jobs
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .is_ok()
+    }
+}
+
+/// Serve one connection until the peer disconnects or goes silent.
+///
+/// Requests are buffered in a bounded queue between the reader and the
+/// engine. When the queue is full, new requests are rejected immediately
+/// with `ErrorCode::Overloaded` instead of growing memory without bound.
+///
+/// The peer is pinged after a period of silence and the connection is
+/// closed once it stays silent past the idle timeout.
+///
+/// Untrusted peers must authenticate with an auth frame before any request
+/// is served when `auth_token` is configured.
+///
+/// A hello frame negotiates the API version and encoding; once agreed,
+/// requests for any other version are rejected with
+/// `ErrorCode::UnsupportedVersion`, and responses above the compression
+/// threshold are compressed if the encoding calls for it.
+///
+/// Requests beyond the configured rate or concurrency limits are answered
+/// with `ErrorCode::RateLimited`; responses that would push the connection
+/// past its byte quota are replaced with `ErrorCode::QuotaExceeded`.
+///
+/// Once a watch request succeeds, change events for its repository from the
+/// engine's event bus are sent as further responses to that request.
+///
+/// A cancel frame stops the queued or running request with the given id,
+/// which is then answered with `ErrorCode::OperationCanceled`.
+///
+/// Every final response is recorded in the access log when one is configured.
+pub(crate) async fn serve_connection(
+    id: u64,
+    engine: Arc<RepoEngine>,
+    config: TransportConfig,
+    registry: Arc<ConnectionRegistry>,
+    connection: Connection,
+) {
+    let Connection {
+        peer,
+        trusted,
+        incoming,
+        outgoing,
+    } = connection;
+
+    let auth_token = if trusted {
+        None
+    } else {
+        config.auth_token.clone()
+    };
+    registry.register(ConnectionInfo {
+        id,
+        peer: peer.clone(),
+        connected_at: SystemTime::now(),
+        authenticated: auth_token.is_none(),
+        protocol: None,
+        in_flight: BTreeSet::new(),
+        subscriptions: BTreeSet::new(),
+    });
+
+    let context = Arc::new(ConnectionContext {
+        id,
+        registry: registry.clone(),
+        auth_token,
+        usage: ClientUsage::default(),
+        liveness: Liveness::new(),
+        protocol: Mutex::new(None),
+        session: Session::new(),
+        access: AccessTracker::new(config.access_log.clone(), id, peer.clone()),
+        queued_jobs: AtomicUsize::new(0),
+        cancellations: Mutex::new(HashMap::new()),
+        watches: Mutex::new(HashMap::new()),
+    });
+
+    let mut events = engine.subscribe();
+    // Channels can't be empty, so nor can the queue
+    let max_pending = config.max_pending_requests.max(1);
+    let (request_tx, request_rx) = mpsc::channel(max_pending);
+    let (frame_tx, mut frame_rx) = mpsc::channel(max_pending);
+
+    let reader = tokio::spawn(read_frames(
+        incoming,
+        request_tx,
+        frame_tx.clone(),
+        context.clone(),
+        config.clone(),
+    ));
+    let worker = tokio::spawn(process_requests(
+        engine.clone(),
+        request_rx,
+        frame_tx,
+        context.clone(),
+    ));
+
+    let keepalive = config.keepalive;
+    let mut checks = tokio::time::interval(keepalive.check_interval());
+    checks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // Send frames in the order they are produced
+    loop {
+        let frame = tokio::select! {
+            frame = frame_rx.recv() => match frame {
+                Some(frame) => enforce_quota(
+                    frame,
+                    &context.usage,
+                    config.limits.max_streamed_bytes,
+                ),
+                None => break,
+            },
+            Ok(event) = events.recv() => {
+                let watch = context
+                    .watches
+                    .lock()
+                    .unwrap()
+                    .get(&canonical_repo_path(event.repo_path()))
+                    .cloned();
+                match watch {
+                    Some(id) => Frame::Message(Response {
+                        id,
+                        result: Ok(ResponsePayload::Event(event)),
+                        meta: None,
+                    }),
+                    None => continue,
+                }
+            },
+            _ = checks.tick() => match keepalive.action(context.liveness.idle()) {
+                KeepaliveAction::Wait => continue,
+                KeepaliveAction::Ping => Frame::Control(ControlFrame::Ping {
+                    seq: context.liveness.next_ping_seq(),
+                }),
+                KeepaliveAction::Disconnect => {
+                    eprintln!(
+                        "Peer {} silent for {} ms, closing connection",
+                        peer,
+                        context.liveness.idle().as_millis()
+                    );
+                    break;
+                }
+            },
+        };
+
+        let responses: &[Response] = match &frame {
+            Frame::Message(response) => std::slice::from_ref(response),
+            Frame::Batch(responses) => responses,
+            Frame::Control(_) => &[],
+        };
+        let compressible = !responses.is_empty();
+        let encoded = frame.encode();
+        if let (Some(recorder), Ok(line)) = (&config.recorder, &encoded) {
+            recorder.record(id, Direction::Out, line);
+        }
+        let line = match encoded {
+            Ok(line) if compressible => {
+                let encoding = context
+                    .protocol()
+                    .map_or(Encoding::Json, |protocol| protocol.encoding);
+                compress_line(line, encoding, config.compression_threshold_bytes)
+            }
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Failed to encode frame for {}: {}", peer, e);
+                continue;
+            }
+        };
+        let bytes = line.len() as u64 + 1;
+        if outgoing.send(line).await.is_err() {
+            break;
+        }
+        match (&frame, responses) {
+            (Frame::Message(_), [response]) => context.access.sent(response, bytes),
+            (_, responses) => context.access.sent_batch(responses),
+        }
+    }
+
+    // Dropping the worker frees the engine for other connections
+    reader.abort();
+    worker.abort();
+    for repo in context.watches.lock().unwrap().keys() {
+        context.session.unwatch(repo);
+    }
+    registry.unregister(id);
+}
+
+/// Swap successful responses for a quota error if sending them would exceed
+/// the connection's byte quota. Errors and control frames always go through.
+fn enforce_quota(
+    frame: Frame<Response>,
+    usage: &ClientUsage,
+    limit: Option<u64>,
+) -> Frame<Response> {
+    match frame {
+        Frame::Message(response) => Frame::Message(enforce_response_quota(response, usage, limit)),
+        Frame::Batch(responses) => Frame::Batch(
+            responses
+                .into_iter()
+                .map(|response| enforce_response_quota(response, usage, limit))
+                .collect(),
+        ),
+        control => {
+            let bytes = control
+                .encode()
+                .map(|line| line.len() as u64 + 1)
+                .unwrap_or(0);
+            usage.try_stream(bytes, None);
+            control
+        }
+    }
+}
+
+fn enforce_response_quota(response: Response, usage: &ClientUsage, limit: Option<u64>) -> Response {
+    let bytes = serde_json::to_vec(&response)
+        .map(|line| line.len() as u64 + 1)
+        .unwrap_or(0);
+    match limit {
+        Some(limit) if response.result.is_ok() && !usage.try_stream(bytes, Some(limit)) => {
+            quota_exceeded_response(response.id, limit)
+        }
+        _ => {
+            usage.try_stream(bytes, None);
+            response
+        }
+    }
+}
+
+/// Read frames until the peer disconnects, feeding requests into the queue.
+///
+/// Returns early, closing the connection, when authentication or version
+/// negotiation fails.
+async fn read_frames(
+    mut incoming: mpsc::Receiver<Line>,
+    requests: mpsc::Sender<Job>,
+    frames: mpsc::Sender<Frame<Response>>,
+    context: Arc<ConnectionContext>,
+    config: TransportConfig,
+) {
+    let mut authenticated = context.auth_token.is_none();
+    let mut rate_limiter = config
+        .limits
+        .requests_per_second
+        .map(|rps| RateLimiter::new(rps, Instant::now()));
+
+    while let Some(line) = incoming.recv().await {
+        // Any inbound frame proves the peer is alive
+        context.liveness.touch();
+
+        let line = match line {
+            Line::Frame(line) => {
+                if let Some(recorder) = &config.recorder {
+                    recorder.record(context.id, Direction::In, &line);
+                }
+                line
+            }
+            Line::TooLarge(size) => {
+                let response = frame_too_large(size, config.max_frame_bytes).into_response();
+                if frames.send(Frame::Message(response)).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        // Parse the frame
+        let (entries, batch) = match decode_request(&line) {
+            Ok(Inbound::Request(request)) => (vec![Ok(request)], false),
+            Ok(Inbound::Batch(entries)) => (entries, true),
+            Ok(Inbound::Control(ControlFrame::Ping { seq })) => {
+                let pong = Frame::Control(ControlFrame::Pong { seq });
+                if frames.send(pong).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            Ok(Inbound::Control(ControlFrame::Auth { token })) => {
+                let reply = match &context.auth_token {
+                    Some(expected) if !expected.verify(&token) => ControlFrame::AuthRejected {
+                        message: "Invalid authentication token".to_string(),
+                    },
+                    _ => {
+                        authenticated = true;
+                        context
+                            .registry
+                            .update(context.id, |info| info.authenticated = true);
+                        ControlFrame::AuthAccepted
+                    }
+                };
+                let rejected = matches!(reply, ControlFrame::AuthRejected { .. });
+                if frames.send(Frame::Control(reply)).await.is_err() || rejected {
+                    break;
+                }
+                continue;
+            }
+            Ok(Inbound::Control(ControlFrame::Hello {
+                versions,
+                encodings,
+            })) => {
+                let reply = match negotiate(&versions, &encodings) {
+                    Ok(negotiated) => {
+                        *context.protocol.lock().unwrap() = Some(negotiated);
+                        context
+                            .registry
+                            .update(context.id, |info| info.protocol = Some(negotiated));
+                        ControlFrame::Welcome {
+                            version: negotiated.version,
+                            encoding: negotiated.encoding,
+                        }
+                    }
+                    Err(rejected) => rejected,
+                };
+                let rejected = matches!(reply, ControlFrame::HelloRejected { .. });
+                if frames.send(Frame::Control(reply)).await.is_err() || rejected {
+                    break;
+                }
+                continue;
+            }
+            Ok(Inbound::Control(ControlFrame::Cancel { id })) => {
+                // Requests that already finished have nothing to cancel
+                if let Some(cancellation) = context.cancellations.lock().unwrap().get(&id) {
+                    cancellation.cancel();
+                }
+                continue;
+            }
+            Ok(Inbound::Control(_)) => continue,
+            Err(rejected) => {
+                if frames
+                    .send(Frame::Message(rejected.into_response()))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        for request in entries.iter().flatten() {
+            context.access.begin(request);
+        }
+
+        if !authenticated {
+            let responses = entries
+                .into_iter()
+                .map(|entry| Response {
+                    id: entry.map_or_else(|rejected| rejected.id, |request| request.id),
+                    result: Err(auth_required(
+                        "Authentication required before sending requests",
+                    )),
+                    meta: None,
+                })
+                .collect();
+            let _ = frames.send(reply_frame(responses, batch)).await;
+            break;
+        }
+
+        // Requests that fail validation or limits are answered in place
+        let slots: Vec<Slot> = entries
+            .into_iter()
+            .map(|entry| match entry {
+                Ok(request) => admit(request, &context, &config, &mut rate_limiter),
+                Err(rejected) => Slot::Done(rejected.into_response()),
+            })
+            .collect();
+        let queued: Vec<String> = slots
+            .iter()
+            .filter_map(|slot| match slot {
+                Slot::Queued(request) => Some(request.id.clone()),
+                Slot::Done(_) => None,
+            })
+            .collect();
+
+        let job = Job { slots, batch };
+        if queued.is_empty() {
+            if frames.send(job.into_reply()).await.is_err() {
+                break;
+            }
+            continue;
+        }
+
+        // Registered before the worker can see the job, so a cancel frame
+        // that follows the request always finds it
+        {
+            let mut cancellations = context.cancellations.lock().unwrap();
+            for id in &queued {
+                cancellations.insert(id.clone(), CancellationToken::new());
+            }
+        }
+
+        // Never wait for queue space: a full queue means the engine is behind
+        let send = if context.reserve_queue_slot(config.max_pending_requests.max(1)) {
+            requests.try_send(job)
+        } else {
+            Err(TrySendError::Full(job))
+        };
+        if send.is_err() {
+            let mut cancellations = context.cancellations.lock().unwrap();
+            for id in &queued {
+                cancellations.remove(id);
+            }
+        }
+        match send {
+            Ok(()) => context.registry.update(context.id, |info| {
+                info.in_flight.extend(queued);
+            }),
+            Err(TrySendError::Full(job)) => {
+                let shed = Job {
+                    slots: job
+                        .slots
+                        .into_iter()
+                        .map(|slot| match slot {
+                            Slot::Queued(request) => {
+                                context.usage.finish_request();
+                                Slot::Done(overloaded_response(request.id, config.retry_after_ms))
+                            }
+                            done => done,
+                        })
+                        .collect(),
+                    batch: job.batch,
+                };
+                if frames.send(shed.into_reply()).await.is_err() {
+                    break;
+                }
+            }
+            Err(TrySendError::Closed(_)) => break,
+        }
+    }
+}
+
+/// Requests from one inbound line.
+struct Job {
+    /// Each request, in the order the client sent them
+    slots: Vec<Slot>,
+    /// Whether the line was a batch, answered with one array frame
+    batch: bool,
+}
+
+/// One request in a job.
+enum Slot {
+    /// Waiting for the engine
+    Queued(Request),
+    /// Already answered, e.g. rejected by a limit
+    Done(Response),
+}
+
+impl Job {
+    /// Most urgent priority among the job's queued requests.
+    fn priority(&self) -> Priority {
+        self.slots
+            .iter()
+            .filter_map(|slot| match slot {
+                Slot::Queued(request) => Some(Priority::of(request)),
+                Slot::Done(_) => None,
+            })
+            .min()
+            .unwrap_or(Priority::UiImmediate)
+    }
+
+    /// Frame carrying the responses of a job whose requests are all done.
+    fn into_reply(self) -> Frame<Response> {
+        let responses = self
+            .slots
+            .into_iter()
+            .filter_map(|slot| match slot {
+                Slot::Done(response) => Some(response),
+                Slot::Queued(_) => None,
+            })
+            .collect();
+        reply_frame(responses, self.batch)
+    }
+}
+
+/// Send final responses as a single message, or as an array for a batch.
+fn reply_frame(mut responses: Vec<Response>, batch: bool) -> Frame<Response> {
+    match responses.pop() {
+        Some(response) if !batch && responses.is_empty() => Frame::Message(response),
+        last => {
+            responses.extend(last);
+            Frame::Batch(responses)
+        }
+    }
+}
+
+/// Check a request against the negotiated version and the per-connection
+/// limits, which come before the shared queue.
+fn admit(
+    request: Request,
+    context: &ConnectionContext,
+    config: &TransportConfig,
+    rate_limiter: &mut Option<RateLimiter>,
+) -> Slot {
+    if let Some(negotiated) = context.protocol().filter(|p| p.version != request.version) {
+        return Slot::Done(Response {
+            id: request.id,
+            result: Err(rl_api::Error::new(
+                rl_api::ErrorCode::UnsupportedVersion,
+                format!(
+                    "Request version {} does not match negotiated version {}",
+                    request.version.as_str(),
+                    negotiated.version.as_str()
+                ),
+            )),
+            meta: None,
+        });
+    }
+
+    if let Some(Err(wait)) = rate_limiter
+        .as_mut()
+        .map(|limiter| limiter.try_acquire(Instant::now()))
+    {
+        return Slot::Done(rate_limited_response(
+            request.id,
+            "Request rate limit exceeded",
+            Some(wait),
+        ));
+    }
+    if !context
+        .usage
+        .try_begin_request(config.limits.max_concurrent_requests)
+    {
+        return Slot::Done(rate_limited_response(
+            request.id,
+            "Too many concurrent requests on this connection",
+            None,
+        ));
+    }
+
+    Slot::Queued(request)
+}
+
+/// Handle queued jobs one at a time, most urgent first.
+///
+/// Jobs are ordered by the most urgent `priority` among their requests;
+/// jobs of equal priority run in the order they arrived.
+///
+/// Every response for a request, including each chunk of a streaming
+/// response, is written before the next request starts. A batch's requests
+/// run in order; streamed chunks go out as they arrive, and the final
+/// responses are sent together as one array frame.
+async fn process_requests(
+    engine: Arc<RepoEngine>,
+    mut jobs: mpsc::Receiver<Job>,
+    frames: mpsc::Sender<Frame<Response>>,
+    context: Arc<ConnectionContext>,
+) {
+    // Jobs waiting behind the one being run, so an interactive request that
+    // arrives behind a burst of prefetches is taken first
+    let mut waiting = Scheduler::new();
+    loop {
+        while let Ok(job) = jobs.try_recv() {
+            let priority = job.priority();
+            waiting.schedule(job, priority);
+        }
+        let job = match waiting.next_query() {
+            Some(job) => job,
+            None => match jobs.recv().await {
+                Some(job) => job,
+                None => return,
+            },
+        };
+
+        context.queued_jobs.fetch_sub(1, Ordering::AcqRel);
+
+        let Job { slots, batch } = job;
+        let mut held = Vec::new();
+        for slot in slots {
+            let request = match slot {
+                Slot::Queued(request) => request,
+                Slot::Done(response) => {
+                    held.push(response);
+                    continue;
+                }
+            };
+
+            let request_id = request.id.clone();
+            let watched_repo = match &request.payload {
+                RequestPayload::Watch(watch) => Some(watch.repo_path.clone()),
+                _ => None,
+            };
+
+            let (response_tx, mut response_rx) = mpsc::channel::<Response>(STREAM_BUFFER);
+            let forward = async {
+                while let Some(response) = response_rx.recv().await {
+                    if let (Some(repo), Ok(_)) = (&watched_repo, &response.result) {
+                        context.registry.update(context.id, |info| {
+                            info.subscriptions.insert(repo.clone());
+                        });
+                        // The newer watch replaces the older one
+                        context
+                            .watches
+                            .lock()
+                            .unwrap()
+                            .insert(canonical_repo_path(repo), request_id.clone());
+                    }
+                    if batch && response.is_final() {
+                        held.push(response);
+                    } else if frames.send(Frame::Message(response)).await.is_err() {
+                        return false;
+                    }
+                }
+                true
+            };
+
+            let cancellation = context
+                .cancellations
+                .lock()
+                .unwrap()
+                .get(&request_id)
+                .cloned()
+                .unwrap_or_default();
+            // Spans the engine opens nest under this one, so a trace covers
+            // the request from the moment its turn came
+            let span = tracing::info_span!(
+                "ipc_request",
+                connection_id = context.id,
+                id = %request_id,
+                request_type = request.payload.kind()
+            );
+            let (_, delivered) = async {
+                tokio::join!(
+                    engine.handle_cancellable(request, &context.session, response_tx, cancellation),
+                    forward
+                )
+            }
+            .instrument(span)
+            .await;
+            context.cancellations.lock().unwrap().remove(&request_id);
+            context.usage.finish_request();
+            context.registry.update(context.id, |info| {
+                info.in_flight.remove(&request_id);
+            });
+            if !delivered {
+                return;
+            }
+        }
+
+        if batch && frames.send(Frame::Batch(held)).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Build the response sent when the request queue is full.
+fn overloaded_response(id: String, retry_after_ms: u64) -> Response {
+    Response {
+        id,
+        result: Err(rl_api::Error::new(
+            rl_api::ErrorCode::Overloaded,
+            "Server request queue is full",
+        )
+        .with_remediation(format!("Retry after {} ms", retry_after_ms))
+        .with_details(serde_json::json!({ "retry_after_ms": retry_after_ms }))),
+        meta: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_api::request::StatusRequest;
+    use rl_api::response::OperationResult;
+    use rl_core::handler::{Handler, HandlerContext};
+    use tokio::sync::{Notify, Semaphore};
+
+    fn status_request(id: &str) -> Request {
+        Request::new(
+            id,
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                repo_path: ".".to_string(),
+            }),
+        )
+    }
+
+    /// Holds every Status request until it is released.
+    struct BlockedStatus {
+        started: Arc<Notify>,
+        release: Arc<Semaphore>,
+    }
+
+    #[async_trait::async_trait]
+    impl Handler for BlockedStatus {
+        type Request = StatusRequest;
+
+        async fn handle(
+            &self,
+            _request: StatusRequest,
+            _cx: &HandlerContext<'_>,
+        ) -> Result<ResponsePayload, rl_api::Error> {
+            self.started.notify_one();
+            self.release.acquire().await.unwrap().forget();
+            Ok(ResponsePayload::OperationResult(OperationResult {
+                success: true,
+                message: None,
+            }))
+        }
+    }
+
+    /// Serve a connection over channels, returning the ends a peer holds.
+    fn connect(
+        engine: RepoEngine,
+        config: TransportConfig,
+    ) -> (mpsc::Sender<Line>, mpsc::Receiver<String>) {
+        let (request_tx, incoming) = mpsc::channel(8);
+        let (outgoing, response_rx) = mpsc::channel(8);
+        let connection = Connection {
+            peer: "test".to_string(),
+            trusted: true,
+            incoming,
+            outgoing,
+        };
+        tokio::spawn(serve_connection(
+            1,
+            Arc::new(engine),
+            config,
+            Arc::new(ConnectionRegistry::default()),
+            connection,
+        ));
+        (request_tx, response_rx)
+    }
+
+    async fn send(peer: &mpsc::Sender<Line>, id: &str) {
+        let line = serde_json::to_string(&status_request(id)).unwrap();
+        peer.send(Line::Frame(line)).await.unwrap();
+    }
+
+    async fn receive(peer: &mut mpsc::Receiver<String>) -> Response {
+        serde_json::from_str(&peer.recv().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_rejects_with_overloaded() {
+        let started = Arc::new(Notify::new());
+        let release = Arc::new(Semaphore::new(0));
+        let mut engine = RepoEngine::new();
+        engine.register_handler(BlockedStatus {
+            started: started.clone(),
+            release: release.clone(),
+        });
+        let config = TransportConfig {
+            max_pending_requests: 1,
+            retry_after_ms: 250,
+            ..Default::default()
+        };
+        let (requests, mut responses) = connect(engine, config);
+
+        // One request runs and holds the engine, one waits behind it, and
+        // the queue is then full
+        send(&requests, "running").await;
+        started.notified().await;
+        send(&requests, "queued").await;
+        send(&requests, "shed").await;
+
+        let response = receive(&mut responses).await;
+        assert_eq!(response.id, "shed");
+        let error = response.result.unwrap_err();
+        assert_eq!(error.code, rl_api::ErrorCode::Overloaded);
+        assert_eq!(error.details.unwrap()["retry_after_ms"], 250);
+
+        release.add_permits(2);
+        // Requests run in the order they were queued
+        for id in ["running", "queued"] {
+            let response = receive(&mut responses).await;
+            assert_eq!(response.id, id);
+            assert!(response.result.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zero_pending_requests_still_queues_one() {
+        let config = TransportConfig {
+            max_pending_requests: 0,
+            ..Default::default()
+        };
+        let (requests, mut responses) = connect(RepoEngine::new(), config);
+
+        send(&requests, "status").await;
+        let response = receive(&mut responses).await;
+        assert_eq!(response.id, "status");
+        assert!(!matches!(
+            response.result,
+            Err(ref error) if error.code == rl_api::ErrorCode::Overloaded
+        ));
+    }
+}
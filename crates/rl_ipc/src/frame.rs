@@ -0,0 +1,385 @@
+//! Wire framing for the IPC protocol.
+//!
+//! Every frame is a single line of JSON. Requests and responses keep the
+//! plain rl_api shapes; transport-level control frames are distinguished by
+//! a top-level `type` field, which neither `Request` nor `Response` has.
+
+use crate::compression::decompress_line;
+use crate::handshake::Encoding;
+use rl_api::{ApiVersion, Request, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Transport-level control messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlFrame {
+    /// Liveness probe; the peer answers with a pong carrying the same sequence
+    Ping { seq: u64 },
+    /// Answer to a ping
+    Pong { seq: u64 },
+    /// Client credentials; must be the first frame when the server requires auth
+    Auth { token: String },
+    /// The server accepted the presented token
+    AuthAccepted,
+    /// The server rejected the presented token and will close the connection
+    AuthRejected { message: String },
+    /// Client's supported API versions and encodings, most preferred first
+    Hello {
+        versions: Vec<ApiVersion>,
+        encodings: Vec<Encoding>,
+    },
+    /// The version and encoding the server picked for this connection
+    Welcome {
+        version: ApiVersion,
+        encoding: Encoding,
+    },
+    /// No common version or encoding; the server will close the connection
+    HelloRejected {
+        code: rl_api::ErrorCode,
+        message: String,
+    },
+    /// Stop the request with this id; it is answered with `OperationCanceled`
+    /// unless it already finished
+    Cancel { id: String },
+    /// Another frame, compressed with the negotiated encoding and base64'd
+    Compressed { encoding: Encoding, data: String },
+}
+
+/// A single line on the wire: a control frame, an API message, or a batch
+/// of API messages sent as a JSON array.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Frame<T> {
+    /// Transport control frame
+    Control(ControlFrame),
+    /// API message (`Request` from clients, `Response` from the server)
+    Message(T),
+    /// Several API messages in one line
+    Batch(Vec<T>),
+}
+
+impl<T: DeserializeOwned> Frame<T> {
+    /// Decode one line of JSON into a frame.
+    ///
+    /// Compressed frames are unwrapped, so callers only ever see the
+    /// original frame.
+    pub fn decode(line: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if value.get("type").is_some() {
+            match serde_json::from_value(value)? {
+                ControlFrame::Compressed { encoding, data } => {
+                    let line = decompress_line(encoding, &data)
+                        .map_err(<serde_json::Error as serde::de::Error>::custom)?;
+                    Self::decode(&line)
+                }
+                control => Ok(Frame::Control(control)),
+            }
+        } else if value.is_array() {
+            Ok(Frame::Batch(serde_json::from_value(value)?))
+        } else {
+            Ok(Frame::Message(serde_json::from_value(value)?))
+        }
+    }
+}
+
+impl<T: Serialize> Frame<T> {
+    /// Encode the frame as a single line of JSON (without the newline).
+    pub fn encode(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Id used in error responses when the offending request's id is unknown.
+pub const UNKNOWN_ID: &str = "unknown";
+
+/// One line read from the wire.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Line {
+    /// A complete line within the size limit (without the newline)
+    Frame(String),
+    /// A line longer than the limit; its bytes were discarded
+    TooLarge(usize),
+}
+
+/// Read one newline-terminated line of at most `max_bytes` bytes.
+///
+/// Oversized lines are drained without buffering so a single huge frame
+/// cannot exhaust memory. Returns `None` at EOF.
+pub fn read_line_bounded(reader: &mut impl BufRead, max_bytes: usize) -> io::Result<Option<Line>> {
+    let mut assembler = LineAssembler::new(max_bytes);
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(assembler.finish());
+        }
+        let (consumed, line) = assembler.push(available);
+        reader.consume(consumed);
+        if line.is_some() {
+            return Ok(line);
+        }
+    }
+}
+
+/// Async counterpart of [`read_line_bounded`].
+pub async fn read_line_bounded_async(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_bytes: usize,
+) -> io::Result<Option<Line>> {
+    let mut assembler = LineAssembler::new(max_bytes);
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(assembler.finish());
+        }
+        let (consumed, line) = assembler.push(available);
+        reader.consume(consumed);
+        if line.is_some() {
+            return Ok(line);
+        }
+    }
+}
+
+/// Accumulates buffered input into a bounded line.
+struct LineAssembler {
+    /// Bytes kept so far; dropped once the line is known to be too large
+    line: Vec<u8>,
+    /// Bytes seen so far, kept or not
+    total: usize,
+    /// Size limit
+    max_bytes: usize,
+}
+
+impl LineAssembler {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            line: Vec::new(),
+            total: 0,
+            max_bytes,
+        }
+    }
+
+    /// Take bytes from `available`, returning how many were consumed and the
+    /// line if it ended within them.
+    fn push(&mut self, available: &[u8]) -> (usize, Option<Line>) {
+        let (chunk, done) = match available.iter().position(|&b| b == b'\n') {
+            Some(end) => (&available[..end], true),
+            None => (available, false),
+        };
+        self.total += chunk.len();
+        if self.total <= self.max_bytes {
+            self.line.extend_from_slice(chunk);
+        } else {
+            self.line = Vec::new();
+        }
+
+        let consumed = chunk.len() + usize::from(done);
+        if !done {
+            return (consumed, None);
+        }
+        if self.line.last() == Some(&b'\r') {
+            self.line.pop();
+        }
+        (consumed, Some(self.take()))
+    }
+
+    /// Finish at EOF; a trailing partial line still counts as a frame.
+    fn finish(mut self) -> Option<Line> {
+        (self.total > 0).then(|| self.take())
+    }
+
+    fn take(&mut self) -> Line {
+        if self.total > self.max_bytes {
+            Line::TooLarge(self.total)
+        } else {
+            Line::Frame(String::from_utf8_lossy(&self.line).into_owned())
+        }
+    }
+}
+
+/// A decoded client line.
+#[derive(Debug)]
+pub enum Inbound {
+    /// Transport control frame
+    Control(ControlFrame),
+    /// A single request
+    Request(Request),
+    /// Requests sent together as a JSON array, each validated on its own
+    Batch(Vec<Result<Request, Rejected>>),
+}
+
+/// Decode a client line, validating the API version before the payload.
+///
+/// On failure the rejection carries the request's id whenever
+/// it could be read, so clients can tell which request was rejected.
+/// Invalid entries in a batch are rejected individually.
+pub fn decode_request(line: &str) -> Result<Inbound, Rejected> {
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+        invalid_request(
+            UNKNOWN_ID.to_string(),
+            format!("Failed to parse request: {}", e),
+        )
+    })?;
+
+    if value.get("type").is_some() {
+        return serde_json::from_value(value)
+            .map(Inbound::Control)
+            .map_err(|e| {
+                invalid_request(
+                    UNKNOWN_ID.to_string(),
+                    format!("Invalid control frame: {}", e),
+                )
+            });
+    }
+
+    match value {
+        serde_json::Value::Array(values) if values.is_empty() => Err(invalid_request(
+            UNKNOWN_ID.to_string(),
+            "Batch must contain at least one request".to_string(),
+        )),
+        serde_json::Value::Array(values) => Ok(Inbound::Batch(
+            values.into_iter().map(decode_message).collect(),
+        )),
+        value => decode_message(value).map(Inbound::Request),
+    }
+}
+
+/// Decode one request object, checking its version first.
+fn decode_message(value: serde_json::Value) -> Result<Request, Rejected> {
+    let id = value
+        .get("id")
+        .and_then(|id| id.as_str())
+        .unwrap_or(UNKNOWN_ID)
+        .to_string();
+
+    let version = value.get("version").cloned().unwrap_or_default();
+    if serde_json::from_value::<ApiVersion>(version.clone()).is_err() {
+        let supported: Vec<&str> = ApiVersion::SUPPORTED.iter().map(|v| v.as_str()).collect();
+        return Err(Rejected {
+            id,
+            error: rl_api::Error::new(
+                rl_api::ErrorCode::UnsupportedVersion,
+                format!("Unsupported API version: {}", version),
+            )
+            .with_remediation(format!("Use one of: {}", supported.join(", ")))
+            .with_details(serde_json::json!({ "supported": supported })),
+        });
+    }
+
+    serde_json::from_value(value)
+        .map_err(|e| invalid_request(id, format!("Failed to parse request: {}", e)))
+}
+
+/// Rejection for a frame that exceeded the size limit.
+pub fn frame_too_large(size: usize, max_bytes: usize) -> Rejected {
+    Rejected {
+        id: UNKNOWN_ID.to_string(),
+        error: rl_api::Error::new(
+            rl_api::ErrorCode::InvalidRequest,
+            format!(
+                "Frame of {} bytes exceeds the {} byte limit",
+                size, max_bytes
+            ),
+        )
+        .with_details(serde_json::json!({ "max_frame_bytes": max_bytes })),
+    }
+}
+
+/// A client frame rejected before dispatch.
+#[derive(Debug)]
+pub struct Rejected {
+    /// Id of the offending request, or `UNKNOWN_ID` if it could not be read
+    pub id: String,
+    /// Why the frame was rejected
+    pub error: rl_api::Error,
+}
+
+impl Rejected {
+    /// Error response to send back to the client.
+    pub fn into_response(self) -> Response {
+        Response {
+            id: self.id,
+            result: Err(self.error),
+            meta: None,
+        }
+    }
+}
+
+fn invalid_request(id: String, message: String) -> Rejected {
+    Rejected {
+        id,
+        error: rl_api::Error::new(rl_api::ErrorCode::InvalidRequest, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_frame_round_trip() {
+        let line = Frame::<Request>::Control(ControlFrame::Ping { seq: 7 })
+            .encode()
+            .unwrap();
+        assert_eq!(line, r#"{"type":"ping","seq":7}"#);
+
+        match Frame::<Request>::decode(&line).unwrap() {
+            Frame::Control(frame) => assert_eq!(frame, ControlFrame::Ping { seq: 7 }),
+            _ => panic!("Expected control frame"),
+        }
+    }
+
+    #[test]
+    fn test_request_decodes_as_message() {
+        let line = r#"{"version":"v0","id":"r1","payload":{"status":{"repo_path":"."}}}"#;
+        match Frame::<Request>::decode(line).unwrap() {
+            Frame::Message(request) => assert_eq!(request.id, "r1"),
+            _ => panic!("Expected request"),
+        }
+    }
+
+    #[test]
+    fn test_parse_error_echoes_request_id() {
+        let line = r#"{"version":"v0","id":"r9","payload":{"status":{}}}"#;
+        let rejected = decode_request(line).unwrap_err();
+        assert_eq!(rejected.id, "r9");
+        assert_eq!(rejected.error.code, rl_api::ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected_before_payload() {
+        let line = r#"{"version":"v7","id":"r1","payload":{"status":{"repo_path":"."}}}"#;
+        let rejected = decode_request(line).unwrap_err();
+        assert_eq!(rejected.id, "r1");
+        assert_eq!(rejected.error.code, rl_api::ErrorCode::UnsupportedVersion);
+    }
+
+    #[test]
+    fn test_batch_entries_are_validated_individually() {
+        let line = r#"[{"version":"v0","id":"a","payload":{"status":{"repo_path":"."}}},{"version":"v0","id":"b","payload":{"status":{}}}]"#;
+        let entries = match decode_request(line).unwrap() {
+            Inbound::Batch(entries) => entries,
+            other => panic!("Expected batch, got {:?}", other),
+        };
+        assert_eq!(entries[0].as_ref().unwrap().id, "a");
+        assert_eq!(entries[1].as_ref().unwrap_err().id, "b");
+
+        assert!(decode_request("[]").is_err());
+    }
+
+    #[test]
+    fn test_oversized_lines_are_drained() {
+        let mut input = io::Cursor::new(b"0123456789\nok\n".to_vec());
+        assert_eq!(
+            read_line_bounded(&mut input, 4).unwrap(),
+            Some(Line::TooLarge(10))
+        );
+        assert_eq!(
+            read_line_bounded(&mut input, 4).unwrap(),
+            Some(Line::Frame("ok".to_string()))
+        );
+        assert_eq!(read_line_bounded(&mut input, 4).unwrap(), None);
+    }
+}
@@ -0,0 +1,109 @@
+//! Protocol negotiation.
+//!
+//! A client may open with `{"type":"hello","versions":[...],"encodings":[...]}`
+//! listing what it understands. The server answers with `welcome` naming the
+//! version and encoding both sides will use, or `hello_rejected` with a typed
+//! error code and closes the connection. Clients that skip the hello keep
+//! working as before.
+
+use crate::frame::ControlFrame;
+use rl_api::{ApiVersion, ErrorCode};
+use serde::{Deserialize, Serialize};
+
+/// Frame body encodings.
+///
+/// Every encoding sends one JSON document per line; the compressing ones
+/// additionally wrap large responses in `compressed` frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Plain JSON
+    Json,
+    /// JSON, with large responses zstd-compressed
+    Zstd,
+    /// JSON, with large responses gzip-compressed
+    Gzip,
+}
+
+impl Encoding {
+    /// Encodings this build can speak, most preferred first.
+    pub const SUPPORTED: &'static [Encoding] = &[Encoding::Zstd, Encoding::Gzip, Encoding::Json];
+}
+
+/// Outcome of a successful negotiation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Negotiated {
+    /// API version requests on this connection must use
+    pub version: ApiVersion,
+    /// Encoding used for frames on this connection
+    pub encoding: Encoding,
+}
+
+/// Pick the newest version and the client's most preferred encoding that
+/// both sides support.
+///
+/// Returns the `welcome` or `hello_rejected` frame to send back.
+pub(crate) fn negotiate(
+    versions: &[ApiVersion],
+    encodings: &[Encoding],
+) -> Result<Negotiated, ControlFrame> {
+    let version = ApiVersion::SUPPORTED
+        .iter()
+        .rev()
+        .find(|version| versions.contains(version))
+        .copied()
+        .ok_or_else(|| ControlFrame::HelloRejected {
+            code: ErrorCode::UnsupportedVersion,
+            message: format!(
+                "No common API version; server supports {}",
+                list(ApiVersion::SUPPORTED.iter().map(|v| v.as_str()))
+            ),
+        })?;
+
+    let encoding = encodings
+        .iter()
+        .find(|encoding| Encoding::SUPPORTED.contains(encoding))
+        .copied()
+        .ok_or_else(|| ControlFrame::HelloRejected {
+            code: ErrorCode::UnsupportedEncoding,
+            message: format!(
+                "No common encoding; server supports {:?}",
+                Encoding::SUPPORTED
+            ),
+        })?;
+
+    Ok(Negotiated { version, encoding })
+}
+
+fn list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    items.collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_picks_common_version_and_encoding() {
+        let negotiated = negotiate(&[ApiVersion::V0], &[Encoding::Json]).unwrap();
+        assert_eq!(negotiated.version, ApiVersion::V0);
+        assert_eq!(negotiated.encoding, Encoding::Json);
+
+        match negotiate(&[], &[Encoding::Json]) {
+            Err(ControlFrame::HelloRejected { code, .. }) => {
+                assert_eq!(code, ErrorCode::UnsupportedVersion)
+            }
+            other => panic!("Expected rejection, got {:?}", other),
+        }
+
+        let negotiated = negotiate(&[ApiVersion::V0], &[Encoding::Gzip, Encoding::Json]).unwrap();
+        assert_eq!(negotiated.encoding, Encoding::Gzip);
+
+        match negotiate(&[ApiVersion::V0], &[]) {
+            Err(ControlFrame::HelloRejected { code, .. }) => {
+                assert_eq!(code, ErrorCode::UnsupportedEncoding)
+            }
+            other => panic!("Expected rejection, got {:?}", other),
+        }
+    }
+}
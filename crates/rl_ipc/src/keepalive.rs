@@ -0,0 +1,125 @@
+//! Keepalive and dead-peer detection shared by the server and client.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Keepalive configuration.
+#[derive(Debug, Clone)]
+pub struct KeepaliveConfig {
+    /// Send a ping after this much inbound silence
+    pub ping_interval_ms: u64,
+    /// Consider the peer dead after this much inbound silence
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval_ms: 15000, // 15 seconds
+            idle_timeout_ms: 45000,  // 45 seconds
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    /// How often the connection should be checked.
+    pub(crate) fn check_interval(&self) -> Duration {
+        Duration::from_millis(self.ping_interval_ms.max(1))
+    }
+
+    /// Decide what to do after `idle` time without inbound frames.
+    pub(crate) fn action(&self, idle: Duration) -> KeepaliveAction {
+        let idle_ms = idle.as_millis() as u64;
+        if idle_ms >= self.idle_timeout_ms {
+            KeepaliveAction::Disconnect
+        } else if idle_ms >= self.ping_interval_ms {
+            KeepaliveAction::Ping
+        } else {
+            KeepaliveAction::Wait
+        }
+    }
+}
+
+/// What a connection should do on a keepalive check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeepaliveAction {
+    /// Peer was heard from recently
+    Wait,
+    /// Peer has been quiet; probe it
+    Ping,
+    /// Peer is gone; tear the connection down
+    Disconnect,
+}
+
+/// Tracks when a peer last sent anything.
+///
+/// Cheap to clone and safe to update from a blocking reader thread.
+#[derive(Debug, Clone)]
+pub(crate) struct Liveness {
+    /// Reference point for `last_seen_ms`
+    started: Instant,
+    /// Milliseconds since `started` at which the last frame arrived
+    last_seen_ms: Arc<AtomicU64>,
+    /// Sequence number for outgoing pings
+    next_seq: Arc<AtomicU64>,
+}
+
+impl Liveness {
+    /// Start tracking; the peer counts as seen right now.
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_seen_ms: Arc::new(AtomicU64::new(0)),
+            next_seq: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Record inbound activity.
+    pub(crate) fn touch(&self) {
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        self.last_seen_ms.store(now_ms, Ordering::Relaxed);
+    }
+
+    /// Time since the peer was last heard from.
+    pub(crate) fn idle(&self) -> Duration {
+        let now_ms = self.started.elapsed().as_millis() as u64;
+        let last_seen_ms = self.last_seen_ms.load(Ordering::Relaxed);
+        Duration::from_millis(now_ms.saturating_sub(last_seen_ms))
+    }
+
+    /// Sequence number for the next ping.
+    pub(crate) fn next_ping_seq(&self) -> u64 {
+        self.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keepalive_actions() {
+        let config = KeepaliveConfig {
+            ping_interval_ms: 100,
+            idle_timeout_ms: 300,
+        };
+
+        assert_eq!(
+            config.action(Duration::from_millis(50)),
+            KeepaliveAction::Wait
+        );
+        assert_eq!(
+            config.action(Duration::from_millis(100)),
+            KeepaliveAction::Ping
+        );
+        assert_eq!(
+            config.action(Duration::from_millis(299)),
+            KeepaliveAction::Ping
+        );
+        assert_eq!(
+            config.action(Duration::from_millis(300)),
+            KeepaliveAction::Disconnect
+        );
+    }
+}
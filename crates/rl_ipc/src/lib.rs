@@ -1,119 +1,86 @@
-//! Transport layer for repo-lens engine with JSON-RPC over stdio.
+//! Transport layer for repo-lens engine with JSON-RPC over stdio and sockets.
 //!
 //! This crate provides IPC transport that maps rl_api messages to rl_core calls.
+//! One server can accept many clients (stdio, TCP, or Unix sockets), each
+//! served on its own task against a shared engine.
 
-use rl_api::{Request, Response};
-use rl_core::RepoEngine;
-use std::io::{self, Write};
-use tokio::sync::mpsc;
+pub mod access_log;
+pub mod auth;
+pub mod client;
+pub mod compression;
+pub mod connection;
+pub mod frame;
+pub mod handshake;
+pub mod keepalive;
+pub mod limits;
+pub mod listener;
+pub mod metrics;
+pub mod recording;
+pub mod retry;
+pub mod server;
 
-/// IPC server that handles JSON-RPC over stdio.
-pub struct IpcServer {
-    /// The repo engine
-    engine: RepoEngine,
-}
-
-impl IpcServer {
-    /// Create a new IPC server with the given engine.
-    pub fn new(engine: RepoEngine) -> Self {
-        Self { engine }
-    }
-
-    /// Run the IPC server, reading from stdin and writing to stdout.
-    pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        let stdin = io::stdin();
-        let mut stdout = io::stdout();
-        let mut lines = stdin.lines();
-
-        loop {
-            // Read a line from stdin
-            let line = match lines.next() {
-                Some(Ok(line)) => line,
-                Some(Err(e)) => {
-                    eprintln!("Error reading from stdin: {}", e);
-                    continue;
-                }
-                None => break, // EOF
-            };
-
-            // Parse the request
-            let request: Request = match serde_json::from_str(&line) {
-                Ok(req) => req,
-                Err(e) => {
-                    // Send error response
-                    let error_response = Response {
-                        id: "unknown".to_string(),
-                        result: Err(rl_api::Error::new(
-                            rl_api::ErrorCode::InvalidRequest,
-                            format!("Failed to parse request: {}", e),
-                        )),
-                    };
-                    let response_json = serde_json::to_string(&error_response)?;
-                    writeln!(stdout, "{}", response_json)?;
-                    stdout.flush()?;
-                    continue;
-                }
-            };
-
-            // Handle the request
-            let response = self.engine.handle(request).await;
-
-            // Send the response
-            let response_json = serde_json::to_string(&response)?;
-            writeln!(stdout, "{}", response_json)?;
-            stdout.flush()?;
-        }
-
-        Ok(())
-    }
-}
-
-/// IPC client for communicating with the server.
-pub struct IpcClient {
-    /// Channel sender for requests
-    #[allow(dead_code)]
-    request_tx: mpsc::UnboundedSender<Request>,
-    /// Channel receiver for responses
-    #[allow(dead_code)]
-    response_rx: mpsc::UnboundedReceiver<Response>,
-}
-
-#[allow(clippy::new_without_default)]
-impl IpcClient {
-    /// Create a new IPC client (stub implementation).
-    pub fn new() -> Self {
-        let (request_tx, _request_rx) = mpsc::unbounded_channel();
-        let (_response_tx, response_rx) = mpsc::unbounded_channel();
-
-        Self {
-            request_tx,
-            response_rx,
-        }
-    }
-
-    /// Send a request and get a response (stub implementation).
-    pub async fn send_request(&mut self, _request: Request) -> Result<Response, rl_api::Error> {
-        Err(rl_api::Error::new(
-            rl_api::ErrorCode::Internal,
-            "IPC client not implemented",
-        ))
-    }
-}
+pub use access_log::{AccessLog, AccessRecord};
+pub use auth::AuthToken;
+pub use client::IpcClient;
+pub use connection::ConnectionInfo;
+pub use frame::{ControlFrame, Frame};
+pub use handshake::{Encoding, Negotiated};
+pub use keepalive::KeepaliveConfig;
+pub use limits::ClientLimits;
+#[cfg(unix)]
+pub use listener::UnixListener;
+pub use listener::{Connection, Listener, StdioListener, TcpListener};
+pub use metrics::MetricsEndpoint;
+pub use recording::{read_recording, replay, Recorder};
+pub use retry::{ConnectionState, RetryPolicy};
+pub use server::IpcServer;
 
 /// Transport configuration.
 #[derive(Debug, Clone)]
 pub struct TransportConfig {
     /// Buffer size for reading
     pub buffer_size: usize,
+    /// Largest accepted frame; longer lines are rejected unparsed
+    pub max_frame_bytes: usize,
     /// Timeout for operations
     pub timeout_ms: u64,
+    /// Maximum number of requests waiting for the engine; zero is taken
+    /// as one
+    pub max_pending_requests: usize,
+    /// Retry hint returned to clients when the queue is full
+    pub retry_after_ms: u64,
+    /// Ping and idle-timeout settings
+    pub keepalive: KeepaliveConfig,
+    /// Token clients must present before sending requests (none = trusted peer)
+    pub auth_token: Option<AuthToken>,
+    /// Per-connection rate limits and quotas
+    pub limits: ClientLimits,
+    /// Responses at least this large are compressed when the peer negotiated
+    /// a compressing encoding
+    pub compression_threshold_bytes: usize,
+    /// Where to write one record per answered request (none = no access log)
+    pub access_log: Option<AccessLog>,
+    /// How clients reconnect and retry after a dropped connection
+    pub retry: RetryPolicy,
+    /// Where to record every frame the server reads or writes (none = off)
+    pub recorder: Option<Recorder>,
 }
 
 impl Default for TransportConfig {
     fn default() -> Self {
         Self {
             buffer_size: 8192,
-            timeout_ms: 30000, // 30 seconds
+            max_frame_bytes: 1024 * 1024, // 1MB
+            timeout_ms: 30000,            // 30 seconds
+            max_pending_requests: 64,
+            retry_after_ms: 100,
+            keepalive: KeepaliveConfig::default(),
+            auth_token: None,
+            limits: ClientLimits::default(),
+            compression_threshold_bytes: 16 * 1024, // 16KB
+            access_log: None,
+            retry: RetryPolicy::default(),
+            recorder: None,
         }
     }
 }
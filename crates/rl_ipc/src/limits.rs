@@ -0,0 +1,187 @@
+//! Per-connection rate limits and quotas.
+//!
+//! Each connection gets its own request rate, concurrency, and output
+//! budget so a single runaway client cannot monopolise a shared daemon.
+
+use rl_api::Response;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Limits applied to every connection independently.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientLimits {
+    /// Sustained request rate; bursts of up to one second's worth are allowed
+    pub requests_per_second: Option<u32>,
+    /// Requests queued or executing at once
+    pub max_concurrent_requests: Option<usize>,
+    /// Total response bytes sent over the lifetime of the connection
+    pub max_streamed_bytes: Option<u64>,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_second: Some(100),
+            max_concurrent_requests: Some(16),
+            max_streamed_bytes: Some(1024 * 1024 * 1024), // 1GB
+        }
+    }
+}
+
+impl ClientLimits {
+    /// Limits that never reject anything.
+    pub fn unlimited() -> Self {
+        Self {
+            requests_per_second: None,
+            max_concurrent_requests: None,
+            max_streamed_bytes: None,
+        }
+    }
+}
+
+/// Token bucket enforcing `requests_per_second`.
+pub(crate) struct RateLimiter {
+    /// Tokens added per second (and bucket capacity)
+    rate: f64,
+    /// Tokens currently available
+    tokens: f64,
+    /// Last time tokens were added
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Create a full bucket for the given rate.
+    pub(crate) fn new(requests_per_second: u32, now: Instant) -> Self {
+        let rate = f64::from(requests_per_second.max(1));
+        Self {
+            rate,
+            tokens: rate,
+            refilled_at: now,
+        }
+    }
+
+    /// Take a token, or return how long until one is available.
+    pub(crate) fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// Usage counters shared by a connection's reader, worker, and writer.
+#[derive(Default)]
+pub(crate) struct ClientUsage {
+    /// Requests admitted but not yet answered
+    in_flight: AtomicUsize,
+    /// Response bytes written so far
+    streamed_bytes: AtomicU64,
+}
+
+impl ClientUsage {
+    /// Reserve a concurrency slot, failing if the limit is reached.
+    pub(crate) fn try_begin_request(&self, limit: Option<usize>) -> bool {
+        let limit = limit.unwrap_or(usize::MAX);
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .is_ok()
+    }
+
+    /// Release a slot reserved by `try_begin_request`.
+    pub(crate) fn finish_request(&self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Account for `bytes` of output, failing without recording them if that
+    /// would exceed the quota.
+    pub(crate) fn try_stream(&self, bytes: u64, limit: Option<u64>) -> bool {
+        let limit = limit.unwrap_or(u64::MAX);
+        self.streamed_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                n.checked_add(bytes).filter(|total| *total <= limit)
+            })
+            .is_ok()
+    }
+}
+
+/// Build the response sent when a client exceeds its request rate or
+/// concurrency limit.
+pub(crate) fn rate_limited_response(
+    id: String,
+    message: &str,
+    retry_after: Option<Duration>,
+) -> Response {
+    let mut error = rl_api::Error::new(rl_api::ErrorCode::RateLimited, message);
+    if let Some(retry_after) = retry_after {
+        let retry_after_ms = retry_after.as_millis().max(1) as u64;
+        error = error
+            .with_remediation(format!("Retry after {} ms", retry_after_ms))
+            .with_details(serde_json::json!({ "retry_after_ms": retry_after_ms }));
+    } else {
+        error = error.with_remediation("Wait for outstanding requests to complete");
+    }
+    Response {
+        id,
+        result: Err(error),
+        meta: None,
+    }
+}
+
+/// Build the response sent in place of output that would exceed the
+/// connection's byte quota.
+pub(crate) fn quota_exceeded_response(id: String, limit: u64) -> Response {
+    Response {
+        id,
+        result: Err(rl_api::Error::new(
+            rl_api::ErrorCode::QuotaExceeded,
+            format!("Connection exceeded its quota of {} streamed bytes", limit),
+        )
+        .with_remediation("Open a new connection or request smaller pages")
+        .with_details(serde_json::json!({ "max_streamed_bytes": limit }))),
+        meta: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_refills_over_time() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2, start);
+
+        assert!(limiter.try_acquire(start).is_ok());
+        assert!(limiter.try_acquire(start).is_ok());
+        let wait = limiter.try_acquire(start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        assert!(limiter
+            .try_acquire(start + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_usage_enforces_limits() {
+        let usage = ClientUsage::default();
+
+        assert!(usage.try_begin_request(Some(1)));
+        assert!(!usage.try_begin_request(Some(1)));
+        usage.finish_request();
+        assert!(usage.try_begin_request(Some(1)));
+
+        assert!(usage.try_stream(60, Some(100)));
+        assert!(!usage.try_stream(60, Some(100)));
+        assert!(usage.try_stream(40, Some(100)));
+    }
+}
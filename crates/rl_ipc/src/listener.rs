@@ -0,0 +1,220 @@
+//! Listeners that accept client connections.
+//!
+//! A listener turns each accepted stream into a [`Connection`]: a pair of
+//! line channels plus some metadata. The server only ever sees connections,
+//! so every transport gets the same framing, auth, limits, and keepalive.
+
+use crate::frame::{read_line_bounded_async, Line};
+use crate::TransportConfig;
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Lines buffered in each direction per connection.
+const LINE_BUFFER: usize = 64;
+
+/// An accepted client connection.
+pub struct Connection {
+    /// Peer description for logs
+    pub peer: String,
+    /// Whether the peer is trusted without authenticating (e.g. our own stdio)
+    pub trusted: bool,
+    /// Lines read from the peer; closes at EOF
+    pub incoming: mpsc::Receiver<Line>,
+    /// Encoded frames to send to the peer, one per line
+    pub outgoing: mpsc::Sender<String>,
+}
+
+/// Source of client connections.
+#[async_trait::async_trait]
+pub trait Listener: Send {
+    /// Wait for the next connection; `None` means no more will arrive.
+    async fn accept(&mut self) -> io::Result<Option<Connection>>;
+}
+
+/// Serves the single peer attached to this process's stdin and stdout.
+pub struct StdioListener {
+    /// Read buffer size
+    buffer_size: usize,
+    /// Largest accepted frame
+    max_frame_bytes: usize,
+    /// Set once the stdio connection has been handed out
+    accepted: bool,
+    /// Stdout pump, awaited before reporting that no more connections will
+    /// arrive so the last responses reach stdout before the process exits
+    writer: Option<JoinHandle<()>>,
+}
+
+impl StdioListener {
+    /// Create a stdio listener using the framing settings from `config`.
+    pub fn new(config: &TransportConfig) -> Self {
+        Self {
+            buffer_size: config.buffer_size,
+            max_frame_bytes: config.max_frame_bytes,
+            accepted: false,
+            writer: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Listener for StdioListener {
+    async fn accept(&mut self) -> io::Result<Option<Connection>> {
+        if self.accepted {
+            if let Some(writer) = self.writer.take() {
+                let _ = writer.await;
+            }
+            return Ok(None);
+        }
+        self.accepted = true;
+
+        // Stdin and stdout go through the same async pumps as sockets, so
+        // intake keeps going (and can shed load) while the engine is busy
+        // without tying up a runtime thread.
+        let (mut connection, writer) = spawn_stream(
+            tokio::io::stdin(),
+            tokio::io::stdout(),
+            "stdio".to_string(),
+            self.buffer_size,
+            self.max_frame_bytes,
+        );
+        connection.trusted = true;
+        self.writer = Some(writer);
+        Ok(Some(connection))
+    }
+}
+
+/// Accepts clients over TCP.
+pub struct TcpListener {
+    /// Bound socket
+    inner: tokio::net::TcpListener,
+    /// Read buffer size
+    buffer_size: usize,
+    /// Largest accepted frame
+    max_frame_bytes: usize,
+}
+
+impl TcpListener {
+    /// Bind to `addr`, using the framing settings from `config`.
+    pub async fn bind(
+        addr: impl tokio::net::ToSocketAddrs,
+        config: &TransportConfig,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            inner: tokio::net::TcpListener::bind(addr).await?,
+            buffer_size: config.buffer_size,
+            max_frame_bytes: config.max_frame_bytes,
+        })
+    }
+
+    /// Address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[async_trait::async_trait]
+impl Listener for TcpListener {
+    async fn accept(&mut self) -> io::Result<Option<Connection>> {
+        let (stream, addr) = self.inner.accept().await?;
+        let _ = stream.set_nodelay(true);
+        let (reader, writer) = stream.into_split();
+        let (connection, _) = spawn_stream(
+            reader,
+            writer,
+            format!("tcp:{}", addr),
+            self.buffer_size,
+            self.max_frame_bytes,
+        );
+        Ok(Some(connection))
+    }
+}
+
+/// Accepts clients over a Unix domain socket.
+#[cfg(unix)]
+pub struct UnixListener {
+    /// Bound socket
+    inner: tokio::net::UnixListener,
+    /// Socket path, for peer descriptions
+    path: std::path::PathBuf,
+    /// Read buffer size
+    buffer_size: usize,
+    /// Largest accepted frame
+    max_frame_bytes: usize,
+}
+
+#[cfg(unix)]
+impl UnixListener {
+    /// Bind to the socket at `path`, using the framing settings from `config`.
+    pub fn bind(path: impl AsRef<std::path::Path>, config: &TransportConfig) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        Ok(Self {
+            inner: tokio::net::UnixListener::bind(&path)?,
+            path,
+            buffer_size: config.buffer_size,
+            max_frame_bytes: config.max_frame_bytes,
+        })
+    }
+}
+
+#[cfg(unix)]
+#[async_trait::async_trait]
+impl Listener for UnixListener {
+    async fn accept(&mut self) -> io::Result<Option<Connection>> {
+        let (stream, _) = self.inner.accept().await?;
+        let (reader, writer) = stream.into_split();
+        let (connection, _) = spawn_stream(
+            reader,
+            writer,
+            format!("unix:{}", self.path.display()),
+            self.buffer_size,
+            self.max_frame_bytes,
+        );
+        Ok(Some(connection))
+    }
+}
+
+/// Pump a byte stream through line channels, returning the connection and
+/// the writer task, which ends once every queued line is flushed.
+fn spawn_stream<R, W>(
+    reader: R,
+    mut writer: W,
+    peer: String,
+    buffer_size: usize,
+    max_frame_bytes: usize,
+) -> (Connection, JoinHandle<()>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (incoming_tx, incoming) = mpsc::channel(LINE_BUFFER);
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<String>(LINE_BUFFER);
+
+    tokio::spawn(async move {
+        let mut input = BufReader::with_capacity(buffer_size, reader);
+        while let Ok(Some(line)) = read_line_bounded_async(&mut input, max_frame_bytes).await {
+            if incoming_tx.send(line).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let writing = tokio::spawn(async move {
+        while let Some(mut line) = outgoing_rx.recv().await {
+            line.push('\n');
+            if writer.write_all(line.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    });
+
+    let connection = Connection {
+        peer,
+        trusted: false,
+        incoming,
+        outgoing,
+    };
+    (connection, writing)
+}
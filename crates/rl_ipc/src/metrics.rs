@@ -0,0 +1,139 @@
+//! HTTP endpoint Prometheus scrapes the engine's metrics from.
+//!
+//! Only `GET /metrics` is answered, with the engine's
+//! [`Metrics`](rl_core::metrics::Metrics) in the Prometheus text format;
+//! other paths get a 404 and other methods a 405. One request is served per
+//! connection, which is all a scraper needs, so there is no HTTP library
+//! behind it. It listens apart from the IPC transport and asks for no
+//! token, so bind it to an address only trusted scrapers can reach.
+
+use rl_core::RepoEngine;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head read; scrapers send far less
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+/// Time a client has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Answers metrics scrapes for an engine.
+pub struct MetricsEndpoint {
+    listener: TcpListener,
+    engine: Arc<RepoEngine>,
+}
+
+impl MetricsEndpoint {
+    /// Bind to `addr`, serving the metrics of `engine`.
+    pub async fn bind(
+        addr: impl tokio::net::ToSocketAddrs,
+        engine: Arc<RepoEngine>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            engine,
+        })
+    }
+
+    /// Address the endpoint is bound to.
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer scrapes until accepting a connection fails.
+    pub async fn serve(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let engine = self.engine.clone();
+            tokio::spawn(async move {
+                let _ = answer(stream, &engine).await;
+            });
+        }
+    }
+}
+
+/// Read one request from `stream` and answer it.
+async fn answer(mut stream: TcpStream, engine: &RepoEngine) -> io::Result<()> {
+    let head = match tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream)).await {
+        Ok(head) => head?,
+        Err(_) => return Ok(()),
+    };
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (method, target) = (parts.next(), parts.next());
+    // Scrapers may add a query string, which changes nothing here
+    let path = target.map(|target| target.split('?').next().unwrap_or_default());
+
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", engine.metrics().render()),
+        (Some("GET"), _) => ("404 Not Found", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "Only GET is allowed\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read up to the blank line ending the request head.
+async fn read_head(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_HEAD_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_served_over_http() {
+        let engine = Arc::new(RepoEngine::new());
+        let endpoint = MetricsEndpoint::bind("127.0.0.1:0", engine).await.unwrap();
+        let addr = endpoint.local_addr().unwrap();
+        tokio::spawn(endpoint.serve());
+
+        let response = get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.contains("# TYPE repo_lens_requests_total counter\n"));
+
+        let response = get(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = get(addr, "POST /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+}
@@ -0,0 +1,281 @@
+//! Traffic recording and replay.
+//!
+//! With a [`Recorder`] configured, the server appends every frame it reads
+//! or writes to a file, one JSON line each:
+//!
+//! ```json
+//! {"at_ms":12,"connection":1,"direction":"in","line":"{\"version\":\"v0\",...}"}
+//! ```
+//!
+//! [`replay`] feeds the recorded requests back through an engine in their
+//! original order, one session per recorded connection, and pairs each new
+//! final response with the recorded one so differences and timing changes
+//! stand out.
+
+use crate::frame::{decode_request, Frame, Inbound};
+use rl_api::{Request, Response};
+use rl_core::session::Session;
+use rl_core::RepoEngine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Which way a recorded frame travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Client to server
+    In,
+    /// Server to client
+    Out,
+}
+
+/// One recorded frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Milliseconds since recording started
+    pub at_ms: u64,
+    /// Server-assigned connection id
+    pub connection: u64,
+    /// Direction of travel
+    pub direction: Direction,
+    /// The frame exactly as sent, before compression
+    pub line: String,
+}
+
+/// Appends frames to a recording, shared by every connection.
+#[derive(Clone)]
+pub struct Recorder {
+    /// When recording started
+    started: Instant,
+    /// Line-oriented writer
+    sink: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Recorder {
+    /// Record to an arbitrary writer.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            started: Instant::now(),
+            sink: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// Record to the file at `path`, replacing any previous recording.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(io::LineWriter::new(std::fs::File::create(path)?)))
+    }
+
+    /// Append one frame. Recording failures never affect the connection.
+    pub(crate) fn record(&self, connection: u64, direction: Direction, line: &str) {
+        let frame = RecordedFrame {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            connection,
+            direction,
+            line: line.to_string(),
+        };
+        if let Ok(line) = serde_json::to_string(&frame) {
+            let mut sink = self.sink.lock().unwrap();
+            let _ = writeln!(sink, "{}", line);
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Recorder")
+    }
+}
+
+/// Read a recording written by [`Recorder`].
+pub fn read_recording(reader: impl BufRead) -> io::Result<Vec<RecordedFrame>> {
+    let mut frames = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        frames.push(
+            serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        );
+    }
+    Ok(frames)
+}
+
+/// Result of replaying one recorded request.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayOutcome {
+    /// Recorded connection the request arrived on
+    pub connection: u64,
+    /// Request id
+    pub request_id: String,
+    /// Final response in the recording, if it was captured
+    pub recorded: Option<Response>,
+    /// Final response from the replay
+    pub replayed: Response,
+    /// Time the recorded request took, if its response was captured
+    pub recorded_ms: Option<u64>,
+    /// Time the replayed request took
+    pub replayed_ms: u64,
+}
+
+impl ReplayOutcome {
+    /// Whether the replay produced the same final response as the recording.
+    ///
+    /// Only results are compared; `meta` differs from run to run.
+    pub fn matches(&self) -> bool {
+        match &self.recorded {
+            Some(recorded) => {
+                serde_json::to_value(&recorded.result).ok()
+                    == serde_json::to_value(&self.replayed.result).ok()
+            }
+            None => false,
+        }
+    }
+}
+
+/// Replay the requests in `frames` through `engine`, one at a time in
+/// recorded order.
+///
+/// With `realtime`, each request is delayed until the same offset from the
+/// start of the replay as it had in the recording; otherwise requests run
+/// back to back. Control frames and undecodable lines are skipped.
+pub async fn replay(
+    engine: &RepoEngine,
+    frames: &[RecordedFrame],
+    realtime: bool,
+) -> Vec<ReplayOutcome> {
+    let recorded = recorded_responses(frames);
+    let mut sessions: HashMap<u64, Session> = HashMap::new();
+    let mut outcomes = Vec::new();
+    let started = Instant::now();
+
+    for frame in frames.iter().filter(|f| f.direction == Direction::In) {
+        let requests: Vec<Request> = match decode_request(&frame.line) {
+            Ok(Inbound::Request(request)) => vec![request],
+            Ok(Inbound::Batch(entries)) => entries.into_iter().flatten().collect(),
+            Ok(Inbound::Control(_)) | Err(_) => continue,
+        };
+
+        if realtime {
+            let due = Duration::from_millis(frame.at_ms);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+
+        let session = sessions.entry(frame.connection).or_default();
+        for request in requests {
+            let request_id = request.id.clone();
+            let began = Instant::now();
+            let replayed = run_to_final(engine, session, request).await;
+            let replayed_ms = began.elapsed().as_millis() as u64;
+
+            let original = recorded.get(&(frame.connection, request_id.clone()));
+            outcomes.push(ReplayOutcome {
+                connection: frame.connection,
+                request_id,
+                recorded: original.map(|(response, _)| response.clone()),
+                replayed,
+                recorded_ms: original.map(|(_, at_ms)| at_ms.saturating_sub(frame.at_ms)),
+                replayed_ms,
+            });
+        }
+    }
+
+    outcomes
+}
+
+/// Final recorded response and its timestamp, by connection and request id.
+fn recorded_responses(frames: &[RecordedFrame]) -> HashMap<(u64, String), (Response, u64)> {
+    let mut responses = HashMap::new();
+    for frame in frames.iter().filter(|f| f.direction == Direction::Out) {
+        let batch = match Frame::<Response>::decode(&frame.line) {
+            Ok(Frame::Message(response)) => vec![response],
+            Ok(Frame::Batch(batch)) => batch,
+            Ok(Frame::Control(_)) | Err(_) => continue,
+        };
+        for response in batch.into_iter().filter(Response::is_final) {
+            responses.insert(
+                (frame.connection, response.id.clone()),
+                (response, frame.at_ms),
+            );
+        }
+    }
+    responses
+}
+
+/// Run one request and keep only its final response.
+async fn run_to_final(engine: &RepoEngine, session: &Session, request: Request) -> Response {
+    let id = request.id.clone();
+    let (response_tx, mut response_rx) = mpsc::channel(16);
+    let drain = async {
+        let mut last = None;
+        while let Some(response) = response_rx.recv().await {
+            last = Some(response);
+        }
+        last
+    };
+    let ((), last) = tokio::join!(
+        engine.handle_in_session(request, session, response_tx),
+        drain
+    );
+    last.unwrap_or_else(|| Response {
+        id,
+        result: Err(rl_api::Error::new(
+            rl_api::ErrorCode::Internal,
+            "Engine produced no response",
+        )),
+        meta: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recorded_requests_replay_against_their_responses() {
+        let request = r#"{"version":"v0","id":"r1","payload":{"show_commit":{"repo_path":".","commit_id":"HEAD"}}}"#;
+        let engine = RepoEngine::new();
+        let expected = engine.handle(serde_json::from_str(request).unwrap()).await;
+
+        let frames = vec![
+            RecordedFrame {
+                at_ms: 0,
+                connection: 1,
+                direction: Direction::In,
+                line: r#"{"type":"ping","seq":1}"#.to_string(),
+            },
+            RecordedFrame {
+                at_ms: 5,
+                connection: 1,
+                direction: Direction::In,
+                line: request.to_string(),
+            },
+            RecordedFrame {
+                at_ms: 9,
+                connection: 1,
+                direction: Direction::Out,
+                line: Frame::Message(expected).encode().unwrap(),
+            },
+        ];
+        let file: String = frames
+            .iter()
+            .map(|frame| serde_json::to_string(frame).unwrap() + "\n")
+            .collect();
+        assert_eq!(read_recording(file.as_bytes()).unwrap(), frames);
+
+        let outcomes = replay(&engine, &frames, false).await;
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].request_id, "r1");
+        assert_eq!(outcomes[0].recorded_ms, Some(4));
+        assert!(outcomes[0].matches());
+    }
+}
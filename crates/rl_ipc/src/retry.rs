@@ -0,0 +1,79 @@
+//! Client reconnect and retry policy.
+
+use std::time::Duration;
+
+/// How a socket client recovers from a dropped connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Reconnect attempts after the connection drops before giving up
+    pub max_reconnect_attempts: u32,
+    /// Delay before the first reconnect attempt; doubles on each attempt
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between attempts
+    pub max_backoff_ms: u64,
+    /// Times a read-only request is re-sent after its connection dropped
+    pub max_request_retries: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_reconnect_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 5_000,
+            max_request_retries: 2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never reconnect or retry.
+    pub fn disabled() -> Self {
+        Self {
+            max_reconnect_attempts: 0,
+            max_request_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before reconnect attempt `attempt` (starting at 1).
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(16);
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
+
+/// Connection state reported by [`crate::IpcClient::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connected; `reconnects` counts how many times the connection was
+    /// re-established, so subscriptions can be renewed when it changes
+    Connected { reconnects: u32 },
+    /// The connection dropped and reconnect attempt `attempt` is under way
+    Reconnecting { attempt: u32 },
+    /// The connection is gone for good
+    Disconnected,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1_000));
+        assert_eq!(policy.backoff(60), Duration::from_millis(1_000));
+    }
+}
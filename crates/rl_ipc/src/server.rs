@@ -0,0 +1,181 @@
+//! IPC server that accepts clients from a listener and serves each one on
+//! its own task.
+
+use crate::connection::{serve_connection, ConnectionInfo, ConnectionRegistry};
+use crate::listener::{Listener, StdioListener};
+use crate::TransportConfig;
+use rl_core::RepoEngine;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// IPC server that handles JSON-RPC over any [`Listener`].
+pub struct IpcServer {
+    /// The repo engine, shared by every connection
+    engine: Arc<RepoEngine>,
+    /// Transport configuration
+    config: TransportConfig,
+    /// Live connections
+    connections: Arc<ConnectionRegistry>,
+}
+
+impl IpcServer {
+    /// Create a new IPC server with the given engine.
+    pub fn new(engine: RepoEngine) -> Self {
+        Self::with_config(engine, TransportConfig::default())
+    }
+
+    /// Create a new IPC server with custom transport configuration.
+    pub fn with_config(engine: RepoEngine, config: TransportConfig) -> Self {
+        Self {
+            engine: Arc::new(engine),
+            config,
+            connections: Arc::new(ConnectionRegistry::default()),
+        }
+    }
+
+    /// The engine serving connections.
+    pub fn engine(&self) -> &Arc<RepoEngine> {
+        &self.engine
+    }
+
+    /// Run the IPC server, reading from stdin and writing to stdout.
+    pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.serve(StdioListener::new(&self.config)).await
+    }
+
+    /// Accept connections from `listener` and serve them concurrently.
+    ///
+    /// Returns once the listener stops producing connections and every
+    /// connection has closed. Accept errors are logged and do not stop the
+    /// server.
+    pub async fn serve<L: Listener>(
+        &self,
+        mut listener: L,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tasks = JoinSet::new();
+        let mut next_id = 0;
+
+        loop {
+            let connection = match listener.accept().await {
+                Ok(Some(connection)) => connection,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            next_id += 1;
+            tasks.spawn(serve_connection(
+                next_id,
+                self.engine.clone(),
+                self.config.clone(),
+                self.connections.clone(),
+                connection,
+            ));
+
+            // Reap connections that have already closed
+            while tasks.try_join_next().is_some() {}
+        }
+
+        while tasks.join_next().await.is_some() {}
+
+        Ok(())
+    }
+
+    /// Snapshot of the currently connected clients.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.connections.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listener::TcpListener;
+    use crate::{AuthToken, IpcClient};
+    use rl_api::Request;
+
+    fn status_request(id: &str) -> Request {
+        Request::new(
+            id,
+            rl_api::request::RequestPayload::Status(rl_api::request::StatusRequest {
+                repo_path: ".".to_string(),
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_serves_several_tcp_clients_at_once() {
+        let token = AuthToken::new("s3cret");
+        let config = TransportConfig {
+            auth_token: Some(token.clone()),
+            ..Default::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0", &config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = Arc::new(IpcServer::with_config(RepoEngine::new(), config.clone()));
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await.unwrap() });
+
+        let first = IpcClient::connect_tcp(addr, config.clone()).await.unwrap();
+        let second = IpcClient::connect_tcp(addr, config).await.unwrap();
+        first.authenticate(&token).await.unwrap();
+        second.authenticate(&token).await.unwrap();
+
+        let (a, b) = tokio::join!(
+            first.send_request(status_request("a")),
+            second.send_request(status_request("b")),
+        );
+        assert_eq!(a.unwrap().id, "a");
+        assert_eq!(b.unwrap().id, "b");
+
+        let connections = server.connections();
+        assert_eq!(connections.len(), 2);
+        assert!(connections.iter().all(|info| info.authenticated));
+    }
+
+    #[tokio::test]
+    async fn test_hello_negotiates_protocol() {
+        let config = TransportConfig::default();
+        let listener = TcpListener::bind("127.0.0.1:0", &config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = Arc::new(IpcServer::with_config(RepoEngine::new(), config.clone()));
+        let serving = server.clone();
+        tokio::spawn(async move { serving.serve(listener).await.unwrap() });
+
+        let client = IpcClient::connect_tcp(addr, config).await.unwrap();
+        let negotiated = client.hello().await.unwrap();
+        assert_eq!(negotiated.version, rl_api::ApiVersion::V0);
+        assert_eq!(negotiated.encoding, crate::Encoding::Zstd);
+
+        client.send_request(status_request("a")).await.unwrap();
+        assert_eq!(server.connections()[0].protocol, Some(negotiated));
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_answered_in_order() {
+        let config = TransportConfig::default();
+        let listener = TcpListener::bind("127.0.0.1:0", &config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = IpcServer::with_config(RepoEngine::new(), config.clone());
+        tokio::spawn(async move { server.serve(listener).await.unwrap() });
+
+        let client = IpcClient::connect_tcp(addr, config).await.unwrap();
+        let mut branches = status_request("b");
+        branches.payload =
+            rl_api::request::RequestPayload::Branches(rl_api::request::BranchesRequest {
+                repo_path: ".".to_string(),
+            });
+
+        let responses = client
+            .send_batch(vec![status_request("s"), branches, status_request("t")])
+            .await
+            .unwrap();
+        let ids: Vec<_> = responses.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["s", "b", "t"]);
+    }
+}